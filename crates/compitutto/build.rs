@@ -0,0 +1,25 @@
+//! Records the git commit the binary is built from, for `/api/version`.
+//! Packagers building outside a checkout can set `COMPITUTTO_GIT_SHA`.
+
+use std::process::Command;
+
+fn main() {
+    let sha = std::env::var("COMPITUTTO_GIT_SHA")
+        .ok()
+        .filter(|sha| !sha.is_empty())
+        .or_else(|| {
+            let output = Command::new("git")
+                .args(["rev-parse", "--short=12", "HEAD"])
+                .output()
+                .ok()?;
+            output
+                .status
+                .success()
+                .then(|| String::from_utf8_lossy(&output.stdout).trim().to_string())
+        })
+        .unwrap_or_else(|| "unknown".to_string());
+    println!("cargo:rustc-env=COMPITUTTO_GIT_SHA={}", sha);
+    println!("cargo:rerun-if-env-changed=COMPITUTTO_GIT_SHA");
+    println!("cargo:rerun-if-changed=../../.git/HEAD");
+    println!("cargo:rerun-if-changed=../../.git/refs/heads");
+}
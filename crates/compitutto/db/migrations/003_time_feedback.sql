@@ -0,0 +1,8 @@
+-- Post-completion feedback: how long an entry actually took and how hard it was,
+-- alongside an optional up-front estimate, so plans can be compared with reality.
+
+ALTER TABLE entries ADD COLUMN estimated_minutes INTEGER;
+ALTER TABLE entries ADD COLUMN actual_minutes INTEGER;
+
+-- 1 (easy) .. 5 (very hard)
+ALTER TABLE entries ADD COLUMN difficulty INTEGER CHECK (difficulty BETWEEN 1 AND 5);
@@ -0,0 +1,21 @@
+-- Staging area for parsed export entries awaiting review.
+-- Accepted rows are moved into `entries` and deleted from here; rejected rows
+-- are kept (status = 'rejected') so the same parse isn't staged again.
+
+CREATE TABLE IF NOT EXISTS pending_imports (
+    id TEXT PRIMARY KEY,                     -- id the entry gets when accepted
+    source_id TEXT NOT NULL UNIQUE,
+    entry_type TEXT NOT NULL,
+    date TEXT NOT NULL,
+    subject TEXT NOT NULL DEFAULT '',
+    task TEXT NOT NULL,
+    replaces_id TEXT,                        -- existing entry whose task this changes
+    status TEXT NOT NULL DEFAULT 'pending',  -- 'pending' | 'rejected'
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_pending_imports_status ON pending_imports(status);
+
+-- Review is on by default
+INSERT OR IGNORE INTO settings (key, value)
+VALUES ('review_imports', 'true');
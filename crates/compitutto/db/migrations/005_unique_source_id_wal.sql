@@ -0,0 +1,33 @@
+-- Move deduplication into the database: one entry per source_id, so
+-- concurrent imports (watcher + manual refresh) can't race into duplicates.
+-- WAL lets the web UI keep reading while an import writes; db::init_db
+-- switches it on, since journal_mode can't change inside the transaction a
+-- migration runs in.
+
+-- Collapse duplicates left by earlier races before adding the constraint:
+-- keep the oldest row per source_id and point the others' children at it.
+UPDATE entries
+SET parent_id = (
+    SELECT keep.id
+    FROM entries AS dup
+    JOIN entries AS keep ON keep.source_id = dup.source_id
+    WHERE dup.id = entries.parent_id
+    ORDER BY keep.rowid
+    LIMIT 1
+)
+WHERE parent_id IN (
+    SELECT id FROM entries AS e
+    WHERE e.source_id IS NOT NULL
+      AND e.rowid > (SELECT MIN(rowid) FROM entries WHERE source_id = e.source_id)
+);
+
+DELETE FROM entries
+WHERE source_id IS NOT NULL
+  AND rowid > (SELECT MIN(rowid) FROM entries AS e WHERE e.source_id = entries.source_id);
+
+DROP INDEX IF EXISTS idx_entries_source_id;
+CREATE UNIQUE INDEX idx_entries_source_id ON entries(source_id);
+
+-- Already created by 001; kept here so the constraint set is in one place
+CREATE INDEX IF NOT EXISTS idx_entries_date_position ON entries(date, position);
+CREATE INDEX IF NOT EXISTS idx_entries_parent ON entries(parent_id);
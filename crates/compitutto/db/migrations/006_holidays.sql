@@ -0,0 +1,7 @@
+-- School holidays imported from an iCal feed, one row per day.
+-- Replaced wholesale on each import.
+
+CREATE TABLE IF NOT EXISTS holidays (
+    date TEXT PRIMARY KEY,   -- YYYY-MM-DD
+    name TEXT NOT NULL DEFAULT ''
+);
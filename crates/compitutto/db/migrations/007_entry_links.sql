@@ -0,0 +1,16 @@
+-- Typed links between entries ("lab report depends on reading chapter 3").
+-- `depends_on`: from_id should be done after to_id.
+-- `related`: symmetric; stored with from_id < to_id so each pair appears once.
+
+CREATE TABLE IF NOT EXISTS entry_links (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    from_id TEXT NOT NULL,
+    to_id TEXT NOT NULL,
+    relation TEXT NOT NULL,                  -- 'depends_on' | 'related'
+    created_at TEXT NOT NULL,
+    UNIQUE (from_id, to_id, relation),
+    FOREIGN KEY (from_id) REFERENCES entries(id) ON DELETE CASCADE,
+    FOREIGN KEY (to_id) REFERENCES entries(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_entry_links_to ON entry_links(to_id);
@@ -0,0 +1,13 @@
+-- Read-only share links for single entries (`/share/{stable_id}?token=...`).
+-- The token is random and is the only thing that grants access; links stop
+-- working after expires_at or when the entry is deleted.
+
+CREATE TABLE IF NOT EXISTS share_links (
+    token TEXT PRIMARY KEY,
+    entry_id TEXT NOT NULL,
+    expires_at TEXT NOT NULL,                -- RFC 3339, UTC
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (entry_id) REFERENCES entries(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_share_links_entry ON share_links(entry_id);
@@ -0,0 +1,14 @@
+-- Study material per subject (textbook pages, Quizlet sets, class Drive
+-- folders), shown as quick links on that subject's entries. Subjects have no
+-- table of their own, so resources hang off the subject name, compared
+-- case-insensitively. A `{page}` in the url is filled from the entry's task.
+
+CREATE TABLE IF NOT EXISTS subject_resources (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    subject TEXT NOT NULL COLLATE NOCASE,
+    title TEXT NOT NULL,
+    url TEXT NOT NULL,
+    created_at TEXT NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_subject_resources_subject ON subject_resources(subject);
@@ -0,0 +1,12 @@
+-- Log of subject merges ("Inglese" -> "Lingua Inglese"). The merge itself
+-- rewrites entries and pending imports; the alias that keeps future imports
+-- mapped lives in data/subjects.json.
+
+CREATE TABLE IF NOT EXISTS subject_merges (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    from_subject TEXT NOT NULL,
+    into_subject TEXT NOT NULL,
+    entries INTEGER NOT NULL,                -- entries rewritten
+    pending INTEGER NOT NULL,                -- pending imports rewritten
+    merged_at TEXT NOT NULL
+);
@@ -0,0 +1,11 @@
+-- Last run of each background job (see src/jobs.rs), for /api/jobs and for
+-- catching up on runs missed while the server was down.
+
+CREATE TABLE IF NOT EXISTS job_runs (
+    name TEXT PRIMARY KEY,
+    started_at TEXT NOT NULL,                -- RFC 3339, UTC
+    finished_at TEXT NOT NULL,
+    ok INTEGER NOT NULL,
+    message TEXT NOT NULL,                   -- summary, or the error
+    runs INTEGER NOT NULL DEFAULT 0          -- runs so far
+);
@@ -0,0 +1,65 @@
+-- Files attached to entries on the portal, from raschietto's `--details`
+-- sidecars. Photos (whiteboard pictures) are saved locally and OCR'd by the
+-- `ocr_attachments` job (see src/ocr.rs), so a search also finds homework that
+-- only exists as a photo. Attachments hang off the entry's source_id, so they
+-- survive re-imports and also apply to entries still waiting for review.
+
+CREATE TABLE IF NOT EXISTS attachments (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    source_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    url TEXT NOT NULL,
+    file TEXT,                                 -- local copy, photos only
+    ocr_status TEXT NOT NULL DEFAULT 'none',   -- none, pending, done, failed
+    ocr_text TEXT,
+    ocr_error TEXT,
+    created_at TEXT NOT NULL,
+    processed_at TEXT,
+    UNIQUE (source_id, url)
+);
+
+CREATE INDEX IF NOT EXISTS idx_attachments_ocr_status ON attachments(ocr_status);
+
+-- Full-text index over each entry's task and its attachments' OCR text,
+-- kept up to date by the triggers below
+CREATE VIRTUAL TABLE IF NOT EXISTS entries_fts USING fts5(
+    entry_id UNINDEXED,
+    task,
+    ocr,
+    tokenize = 'unicode61 remove_diacritics 2'
+);
+
+INSERT INTO entries_fts (entry_id, task, ocr)
+SELECT id, task, '' FROM entries;
+
+-- Entries are sometimes written with INSERT OR REPLACE, which doesn't fire the
+-- delete trigger, so every insert clears the old row first
+CREATE TRIGGER IF NOT EXISTS entries_fts_insert AFTER INSERT ON entries BEGIN
+    DELETE FROM entries_fts WHERE entry_id = new.id;
+    INSERT INTO entries_fts (entry_id, task, ocr)
+    VALUES (new.id, new.task, COALESCE((
+        SELECT group_concat(ocr_text, ' ') FROM attachments
+        WHERE source_id = new.source_id AND ocr_text IS NOT NULL
+    ), ''));
+END;
+
+CREATE TRIGGER IF NOT EXISTS entries_fts_update AFTER UPDATE OF task, source_id ON entries BEGIN
+    DELETE FROM entries_fts WHERE entry_id = old.id;
+    INSERT INTO entries_fts (entry_id, task, ocr)
+    VALUES (new.id, new.task, COALESCE((
+        SELECT group_concat(ocr_text, ' ') FROM attachments
+        WHERE source_id = new.source_id AND ocr_text IS NOT NULL
+    ), ''));
+END;
+
+CREATE TRIGGER IF NOT EXISTS entries_fts_delete AFTER DELETE ON entries BEGIN
+    DELETE FROM entries_fts WHERE entry_id = old.id;
+END;
+
+CREATE TRIGGER IF NOT EXISTS attachments_fts_update AFTER UPDATE OF ocr_text ON attachments BEGIN
+    UPDATE entries_fts SET ocr = COALESCE((
+        SELECT group_concat(ocr_text, ' ') FROM attachments
+        WHERE source_id = new.source_id AND ocr_text IS NOT NULL
+    ), '')
+    WHERE entry_id IN (SELECT id FROM entries WHERE source_id = new.source_id);
+END;
@@ -0,0 +1,22 @@
+-- Absences and disciplinary notes mirrored from raschietto's
+-- `absences_*.json` / `notes_*.json` (see src/register.rs). Each import
+-- replaces the table with the newest snapshot of the portal page, so these
+-- are read-only copies: nothing in compitutto edits them.
+
+CREATE TABLE IF NOT EXISTS absences (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    date TEXT NOT NULL,                        -- YYYY-MM-DD when recognised
+    kind TEXT NOT NULL,                        -- Assenza, Ritardo, Uscita anticipata
+    justified INTEGER NOT NULL DEFAULT 0,
+    note TEXT NOT NULL DEFAULT '',
+    fetched TEXT NOT NULL                      -- day raschietto read the page
+);
+
+CREATE TABLE IF NOT EXISTS discipline_notes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    date TEXT NOT NULL,
+    teacher TEXT NOT NULL DEFAULT '',
+    kind TEXT NOT NULL DEFAULT '',
+    text TEXT NOT NULL,
+    fetched TEXT NOT NULL
+);
@@ -0,0 +1,5 @@
+-- Highlighting: a pinned entry sorts first within its day, and `color`
+-- (#rrggbb) overrides the card's accent color.
+
+ALTER TABLE entries ADD COLUMN pinned INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE entries ADD COLUMN color TEXT;
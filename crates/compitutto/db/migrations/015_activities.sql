@@ -0,0 +1,13 @@
+-- After-school activities (sports, music lessons) from an iCal feed, one row
+-- per occurrence. Replaced wholesale on each import.
+
+CREATE TABLE IF NOT EXISTS activities (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    date TEXT NOT NULL,               -- YYYY-MM-DD
+    name TEXT NOT NULL DEFAULT '',
+    start_time TEXT,                  -- HH:MM, NULL for all-day events
+    end_time TEXT,                    -- HH:MM
+    minutes INTEGER NOT NULL DEFAULT 0
+);
+
+CREATE INDEX IF NOT EXISTS idx_activities_date ON activities(date);
@@ -0,0 +1,15 @@
+-- Reactions and encouragement left on completed entries ("⭐ Bravissima!").
+-- Either part may be missing, not both; the API checks the emoji is one of
+-- the offered ones and keeps messages short.
+
+CREATE TABLE IF NOT EXISTS reactions (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    entry_id TEXT NOT NULL,
+    emoji TEXT,
+    message TEXT,
+    author TEXT,                             -- name from the presence chip, if set
+    created_at TEXT NOT NULL,
+    FOREIGN KEY (entry_id) REFERENCES entries(id) ON DELETE CASCADE
+);
+
+CREATE INDEX IF NOT EXISTS idx_reactions_entry ON reactions(entry_id);
@@ -0,0 +1,4 @@
+-- The grade a verifica or interrogazione got, recorded when it's ticked off
+-- as happened. Free text as written on the register ("7½", "8-").
+
+ALTER TABLE entries ADD COLUMN grade TEXT;
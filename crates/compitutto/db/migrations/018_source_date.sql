@@ -0,0 +1,6 @@
+-- The date an export gave an entry, kept when the entry is moved so
+-- teacher-assigned dates can be told apart from our own rescheduling. NULL
+-- for entries that didn't come from an export; existing ones are filled in
+-- the next time their export is scanned.
+
+ALTER TABLE entries ADD COLUMN source_date TEXT;
@@ -0,0 +1,7 @@
+-- Topics under subjects (Matematica -> Geometria), taken from a "Geometria:"
+-- prefix in the task. NULL when the task doesn't name one; entries imported
+-- before this migration are filled in on the next import.
+
+ALTER TABLE entries ADD COLUMN topic TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_entries_subject_topic ON entries(subject, topic);
@@ -0,0 +1,5 @@
+-- Last day of multi-day events, from the export's data_fine column. NULL
+-- for single-day entries; entries imported before this migration get theirs
+-- on the next import.
+
+ALTER TABLE entries ADD COLUMN end_date TEXT;
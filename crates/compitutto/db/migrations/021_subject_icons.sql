@@ -0,0 +1,9 @@
+-- Icons chosen for subjects (📐 Matematica), overriding the built-in
+-- defaults in subjects.rs. Like subject_resources, keyed on the subject name,
+-- compared case-insensitively.
+
+CREATE TABLE IF NOT EXISTS subject_icons (
+    subject TEXT PRIMARY KEY COLLATE NOCASE,
+    icon TEXT NOT NULL,
+    updated_at TEXT NOT NULL
+);
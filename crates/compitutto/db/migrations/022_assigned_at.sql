@@ -0,0 +1,7 @@
+-- When the teacher posted an entry (the export's data_inserimento) and who
+-- did (autore), for how much notice homework and tests come with. NULL for
+-- entries added by hand or generated; entries imported before this
+-- migration get theirs on the next import.
+
+ALTER TABLE entries ADD COLUMN assigned_at TEXT;
+ALTER TABLE entries ADD COLUMN teacher TEXT;
@@ -0,0 +1,6 @@
+-- Hide an entry from the list and dashboard until a day (YYYY-MM-DD), for
+-- long-horizon work like a book report due in two months. NULL, the
+-- default, shows it right away; study sessions and reminders follow their
+-- parent's.
+
+ALTER TABLE entries ADD COLUMN visible_from TEXT;
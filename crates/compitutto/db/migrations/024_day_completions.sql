@@ -0,0 +1,17 @@
+-- Days whose entries were all ticked off, recorded when the last one was:
+-- the "all done" calendar and streak on /stats. Unticking one of the day's
+-- entries removes the row. Days already done before this table existed are
+-- filled in from the entries, dated by their last update.
+
+CREATE TABLE IF NOT EXISTS day_completions (
+    date TEXT PRIMARY KEY,            -- YYYY-MM-DD
+    completed_at TEXT NOT NULL,       -- UTC, YYYY-MM-DD HH:MM:SS
+    entries INTEGER NOT NULL          -- entries on the day when it was done
+);
+
+INSERT OR IGNORE INTO day_completions (date, completed_at, entries)
+SELECT date, MAX(updated_at), COUNT(*)
+FROM entries
+WHERE date <= date('now')
+GROUP BY date
+HAVING SUM(completed = 0) = 0;
@@ -0,0 +1,6 @@
+-- The export file an entry was first imported from (its name in data/), so
+-- the original can be downloaded to check an entry against what the school
+-- published. NULL for entries added by hand or generated; entries imported
+-- before this migration get theirs the next time their export is scanned.
+
+ALTER TABLE entries ADD COLUMN source_file TEXT;
@@ -0,0 +1,12 @@
+-- Log of bulk text corrections (`compitutto fix-text`, POST /api/text/fix):
+-- recurring typos and encoding artifacts in teachers' exports ("pag,.",
+-- "Ã¨"). Dry runs aren't logged.
+
+CREATE TABLE IF NOT EXISTS text_fixes (
+    id INTEGER PRIMARY KEY AUTOINCREMENT,
+    find TEXT NOT NULL,
+    replace_with TEXT NOT NULL,
+    subject TEXT,                            -- NULL = every subject
+    entries INTEGER NOT NULL,                -- entries rewritten
+    fixed_at TEXT NOT NULL
+);
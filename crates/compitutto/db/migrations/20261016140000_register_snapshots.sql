@@ -0,0 +1,14 @@
+-- When each register page was last read, kept apart from its rows so an
+-- empty snapshot ("no notes") still says when it was fetched. `kind` is
+-- `absences` or `notes` (see src/register.rs).
+
+CREATE TABLE IF NOT EXISTS register_snapshots (
+    kind TEXT PRIMARY KEY,
+    fetched TEXT                             -- NULL when the file didn't say
+);
+
+INSERT OR IGNORE INTO register_snapshots (kind, fetched)
+SELECT 'absences', fetched FROM absences WHERE fetched != '' ORDER BY fetched DESC LIMIT 1;
+
+INSERT OR IGNORE INTO register_snapshots (kind, fetched)
+SELECT 'notes', fetched FROM discipline_notes WHERE fetched != '' ORDER BY fetched DESC LIMIT 1;
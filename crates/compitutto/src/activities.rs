@@ -0,0 +1,425 @@
+//! After-school activities (sports, music lessons) from an iCal feed.
+//!
+//! `compitutto activities <URL|FILE>` reads the calendar, expands recurring
+//! events into one `Activity` per day and replaces the `activities` table.
+//! Given a URL it also subscribes: the server fetches the feed again every
+//! [`REFRESH_HOURS`] hours. Activities show in the calendar next to the
+//! homework, and study sessions go on the days with the fewest activity
+//! minutes.
+//!
+//! Supported: `DTSTART`/`DTEND` as dates or date-times (`Z` times are
+//! converted to local time, `TZID` is ignored), `SUMMARY`, `EXDATE`,
+//! `STATUS:CANCELLED`, and `RRULE` with `FREQ=DAILY` or `WEEKLY`, `INTERVAL`,
+//! `COUNT`, `UNTIL` and `BYDAY`. Other frequencies count once.
+
+use anyhow::{bail, Context, Result};
+use chrono::{
+    Datelike, Duration, Local, NaiveDate, NaiveDateTime, NaiveTime, TimeZone, Utc, Weekday,
+};
+use std::collections::HashSet;
+use std::sync::Arc;
+use tracing::{error, info};
+
+use crate::db;
+use crate::holidays::{self, unescape, unfold};
+use crate::server::AppState;
+use crate::types::Activity;
+
+/// How often the server re-fetches a subscribed feed
+pub const REFRESH_HOURS: u64 = 6;
+
+/// Load of an all-day event (a tournament, a school trip), in minutes
+pub const ALL_DAY_MINUTES: u32 = 240;
+
+/// Length of a timed event without `DTEND`
+const DEFAULT_MINUTES: u32 = 60;
+
+/// Open-ended recurrences are expanded this far past their start
+const RECURRENCE_DAYS: i64 = 366;
+
+/// Longest all-day event we expand, so a malformed DTEND can't fill the table
+const MAX_EVENT_DAYS: i64 = 31;
+
+/// Parse an iCal calendar into activities, sorted by date and start time.
+pub fn parse_ical(text: &str) -> Result<Vec<Activity>> {
+    let lines = unfold(text);
+    if !lines
+        .iter()
+        .any(|l| l.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        bail!("Not an iCal calendar (no BEGIN:VCALENDAR)");
+    }
+
+    let mut activities = Vec::new();
+    let mut event: Option<Event> = None;
+    for line in &lines {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Drop parameters: "DTSTART;TZID=Europe/Rome" -> "DTSTART"
+        let name = key.split(';').next().unwrap_or(key).to_ascii_uppercase();
+
+        match (name.as_str(), event.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                event = Some(Event::default());
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                activities.extend(event.take().unwrap().activities()?);
+            }
+            ("DTSTART", Some(ev)) => ev.start = Some(parse_date_time(value)?),
+            ("DTEND", Some(ev)) => ev.end = Some(parse_date_time(value)?),
+            ("SUMMARY", Some(ev)) => ev.summary = unescape(value),
+            ("STATUS", Some(ev)) => ev.cancelled = value.eq_ignore_ascii_case("CANCELLED"),
+            ("RRULE", Some(ev)) => ev.rule = Some(Rule::parse(value)?),
+            ("EXDATE", Some(ev)) => {
+                for date in value.split(',') {
+                    ev.exdates.insert(parse_date_time(date)?.0);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    activities.sort_by(|a, b| (&a.date, &a.start_time).cmp(&(&b.date, &b.start_time)));
+    Ok(activities)
+}
+
+#[derive(Debug, Default)]
+struct Event {
+    start: Option<(NaiveDate, Option<NaiveTime>)>,
+    end: Option<(NaiveDate, Option<NaiveTime>)>,
+    summary: String,
+    cancelled: bool,
+    rule: Option<Rule>,
+    exdates: HashSet<NaiveDate>,
+}
+
+impl Event {
+    fn activities(&self) -> Result<Vec<Activity>> {
+        let Some((start_date, start_time)) = self.start else {
+            return Ok(Vec::new());
+        };
+        if self.cancelled {
+            return Ok(Vec::new());
+        }
+
+        // What one occurrence looks like: its days (relative to the start),
+        // times and length
+        let (span, end_time, minutes) = match (start_time, self.end) {
+            (Some(start), Some((end_date, Some(end)))) => {
+                let length = NaiveDateTime::new(end_date, end) - start_date.and_time(start);
+                (1, Some(end), length.num_minutes().max(0) as u32)
+            }
+            (Some(start), _) => (
+                1,
+                Some(start + Duration::minutes(DEFAULT_MINUTES as i64)),
+                DEFAULT_MINUTES,
+            ),
+            // All-day DTEND is exclusive
+            (None, Some((end_date, _))) => (
+                (end_date - start_date).num_days().max(1),
+                None,
+                ALL_DAY_MINUTES,
+            ),
+            (None, None) => (1, None, ALL_DAY_MINUTES),
+        };
+        if span > MAX_EVENT_DAYS {
+            bail!(
+                "Activity '{}' spans {} days (from {}); refusing to import",
+                self.summary,
+                span,
+                start_date
+            );
+        }
+
+        let starts = match &self.rule {
+            Some(rule) => rule.occurrences(start_date),
+            None => vec![start_date],
+        };
+        Ok(starts
+            .into_iter()
+            .filter(|date| !self.exdates.contains(date))
+            .flat_map(|date| (0..span).map(move |d| date + Duration::days(d)))
+            .map(|date| Activity {
+                date: date.format("%Y-%m-%d").to_string(),
+                name: self.summary.clone(),
+                start_time: start_time.map(|t| t.format("%H:%M").to_string()),
+                end_time: end_time.map(|t| t.format("%H:%M").to_string()),
+                minutes,
+            })
+            .collect())
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Freq {
+    Daily,
+    Weekly,
+    /// Anything else; the event counts once
+    Other,
+}
+
+/// The supported part of an `RRULE`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Rule {
+    freq: Freq,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<NaiveDate>,
+    by_day: Vec<Weekday>,
+}
+
+impl Rule {
+    /// `FREQ=WEEKLY;BYDAY=TU,TH;UNTIL=20260610`
+    fn parse(value: &str) -> Result<Self> {
+        let mut rule = Rule {
+            freq: Freq::Other,
+            interval: 1,
+            count: None,
+            until: None,
+            by_day: Vec::new(),
+        };
+        for part in value.split(';') {
+            let Some((key, value)) = part.split_once('=') else {
+                continue;
+            };
+            match key.to_ascii_uppercase().as_str() {
+                "FREQ" => {
+                    rule.freq = match value.to_ascii_uppercase().as_str() {
+                        "DAILY" => Freq::Daily,
+                        "WEEKLY" => Freq::Weekly,
+                        _ => Freq::Other,
+                    }
+                }
+                "INTERVAL" => {
+                    rule.interval = value
+                        .parse::<u32>()
+                        .with_context(|| format!("Invalid RRULE interval: {}", value))?
+                        .max(1)
+                }
+                "COUNT" => {
+                    rule.count = Some(
+                        value
+                            .parse()
+                            .with_context(|| format!("Invalid RRULE count: {}", value))?,
+                    )
+                }
+                "UNTIL" => rule.until = Some(parse_date_time(value)?.0),
+                "BYDAY" => {
+                    // "TU" or "1TU"; the ordinal only matters for monthly rules
+                    rule.by_day = value
+                        .split(',')
+                        .filter_map(|day| {
+                            let day = day.trim_start_matches(|c: char| {
+                                c == '+' || c == '-' || c.is_ascii_digit()
+                            });
+                            weekday(day)
+                        })
+                        .collect()
+                }
+                _ => {}
+            }
+        }
+        Ok(rule)
+    }
+
+    /// Start dates of the occurrences, the first being `start` itself
+    fn occurrences(&self, start: NaiveDate) -> Vec<NaiveDate> {
+        let last = self
+            .until
+            .unwrap_or(start + Duration::days(RECURRENCE_DAYS))
+            .min(start + Duration::days(RECURRENCE_DAYS));
+        let limit = self.count.map_or(usize::MAX, |c| c as usize);
+        let step = self.interval as i64;
+
+        let candidates: Box<dyn Iterator<Item = NaiveDate>> = match self.freq {
+            Freq::Other => return vec![start],
+            Freq::Daily => Box::new((0..).map(move |n| start + Duration::days(n * step))),
+            Freq::Weekly => {
+                let mut days: Vec<i64> = if self.by_day.is_empty() {
+                    vec![start.weekday().num_days_from_monday() as i64]
+                } else {
+                    self.by_day
+                        .iter()
+                        .map(|d| d.num_days_from_monday() as i64)
+                        .collect()
+                };
+                days.sort_unstable();
+                days.dedup();
+                let monday = start - Duration::days(start.weekday().num_days_from_monday() as i64);
+                Box::new((0..).flat_map(move |week| {
+                    let week_start = monday + Duration::weeks(week * step);
+                    days.clone()
+                        .into_iter()
+                        .map(move |d| week_start + Duration::days(d))
+                }))
+            }
+        };
+        candidates
+            .skip_while(|date| *date < start)
+            .take_while(|date| *date <= last)
+            .take(limit)
+            .collect()
+    }
+}
+
+fn weekday(code: &str) -> Option<Weekday> {
+    Some(match code.to_ascii_uppercase().as_str() {
+        "MO" => Weekday::Mon,
+        "TU" => Weekday::Tue,
+        "WE" => Weekday::Wed,
+        "TH" => Weekday::Thu,
+        "FR" => Weekday::Fri,
+        "SA" => Weekday::Sat,
+        "SU" => Weekday::Sun,
+        _ => return None,
+    })
+}
+
+/// `20251224` (no time), `20251224T170000` (local) or `20251224T160000Z`
+/// (UTC, converted to local time)
+fn parse_date_time(value: &str) -> Result<(NaiveDate, Option<NaiveTime>)> {
+    let value = value.trim();
+    let Some((date, time)) = value.split_once('T') else {
+        let date = NaiveDate::parse_from_str(value, "%Y%m%d")
+            .with_context(|| format!("Invalid iCal date: {}", value))?;
+        return Ok((date, None));
+    };
+    let utc = time.ends_with('Z');
+    let naive = NaiveDateTime::parse_from_str(
+        &format!("{}T{}", date, time.trim_end_matches('Z')),
+        "%Y%m%dT%H%M%S",
+    )
+    .with_context(|| format!("Invalid iCal date-time: {}", value))?;
+    let local = if utc {
+        Utc.from_utc_datetime(&naive)
+            .with_timezone(&Local)
+            .naive_local()
+    } else {
+        naive
+    };
+    Ok((local.date(), Some(local.time())))
+}
+
+// ========== Subscription ==========
+
+/// Re-fetch the subscribed feed (`db::get_activities_url`) every
+/// `REFRESH_HOURS`, starting with one fetch right away. The database isn't
+/// locked while the feed downloads.
+pub fn start_subscription(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        loop {
+            refresh(&state).await;
+            tokio::time::sleep(std::time::Duration::from_secs(REFRESH_HOURS * 3600)).await;
+        }
+    });
+}
+
+async fn refresh(state: &AppState) {
+    let url = {
+        let conn = state.conn.lock().unwrap();
+        db::get_activities_url(&conn).unwrap_or_default()
+    };
+    let Some(url) = url else {
+        return;
+    };
+    let parsed = match holidays::read_source(&url).await {
+        Ok(text) => parse_ical(&text),
+        Err(e) => Err(e),
+    };
+    match parsed {
+        Ok(activities) => {
+            let conn = state.conn.lock().unwrap();
+            match db::replace_activities(&conn, &activities) {
+                Ok(count) => info!(count, url = %url, "Refreshed activities"),
+                Err(e) => error!(error = %e, "Failed to store activities"),
+            }
+        }
+        Err(e) => error!(error = %e, url = %url, "Failed to refresh activities"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEED: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+DTSTART;TZID=Europe/Rome:20250114T170000\r
+DTEND;TZID=Europe/Rome:20250114T183000\r
+RRULE:FREQ=WEEKLY;BYDAY=TU,TH;UNTIL=20250128T235959Z\r
+EXDATE;TZID=Europe/Rome:20250121T170000\r
+SUMMARY:Nuoto\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20250118\r
+DTEND;VALUE=DATE:20250120\r
+SUMMARY:Torneo di pallavolo\\, Bologna\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART:20250115T150000\r
+SUMMARY:Pianoforte\r
+STATUS:CANCELLED\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn days(activities: &[Activity]) -> Vec<&str> {
+        activities.iter().map(|a| a.date.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_ical_expands_weekly_rule() {
+        let activities = parse_ical(FEED).unwrap();
+        assert_eq!(
+            days(&activities),
+            vec![
+                "2025-01-14",
+                "2025-01-16",
+                "2025-01-18",
+                "2025-01-19",
+                "2025-01-23",
+                "2025-01-28",
+            ]
+        );
+        let swim = &activities[0];
+        assert_eq!(swim.name, "Nuoto");
+        assert_eq!(swim.start_time.as_deref(), Some("17:00"));
+        assert_eq!(swim.end_time.as_deref(), Some("18:30"));
+        assert_eq!(swim.minutes, 90);
+
+        let tournament = &activities[2];
+        assert_eq!(tournament.name, "Torneo di pallavolo, Bologna");
+        assert!(tournament.start_time.is_none());
+        assert_eq!(tournament.minutes, ALL_DAY_MINUTES);
+    }
+
+    #[test]
+    fn test_rule_occurrences() {
+        let start = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap(); // Wednesday
+        let rule = Rule::parse("FREQ=WEEKLY;INTERVAL=2;COUNT=3").unwrap();
+        let dates: Vec<String> = rule
+            .occurrences(start)
+            .iter()
+            .map(|d| d.to_string())
+            .collect();
+        assert_eq!(dates, vec!["2025-01-15", "2025-01-29", "2025-02-12"]);
+
+        let rule = Rule::parse("FREQ=DAILY;UNTIL=20250117").unwrap();
+        assert_eq!(rule.occurrences(start).len(), 3);
+
+        // Open-ended rules stop after a year
+        let rule = Rule::parse("FREQ=WEEKLY").unwrap();
+        assert_eq!(rule.occurrences(start).len(), 53);
+
+        let rule = Rule::parse("FREQ=MONTHLY;BYDAY=1MO").unwrap();
+        assert_eq!(rule.occurrences(start), vec![start]);
+    }
+
+    #[test]
+    fn test_parse_ical_rejects_non_calendar() {
+        assert!(parse_ical("<html></html>").is_err());
+        assert!(parse_ical("BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:2025\nEND:VEVENT\n").is_err());
+    }
+}
@@ -0,0 +1,276 @@
+//! Per-day agenda summaries for voice assistants.
+//!
+//! Turns one day's entries into counts plus short sentences that read well
+//! aloud (Home Assistant, Alexa skills, etc.).
+
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+use crate::types::HomeworkEntry;
+
+/// One day's agenda, ready to be read aloud
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Agenda {
+    /// `YYYY-MM-DD`
+    pub date: String,
+
+    /// Spoken form of the date, e.g. "Wednesday 15 January"
+    pub day: String,
+
+    pub total: usize,
+    pub completed: usize,
+    pub remaining: usize,
+
+    /// Remaining entries per entry type
+    pub by_type: BTreeMap<String, usize>,
+
+    /// One-sentence overview of what's left
+    pub summary: String,
+
+    pub items: Vec<AgendaItem>,
+}
+
+/// A single entry as a sentence
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AgendaItem {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    pub subject: String,
+    pub completed: bool,
+    pub text: String,
+}
+
+/// Build the agenda for `date` from that day's entries.
+pub fn build_agenda(date: NaiveDate, entries: &[HomeworkEntry]) -> Agenda {
+    let day = date.format("%A %-d %B").to_string();
+
+    let items: Vec<AgendaItem> = entries
+        .iter()
+        .map(|entry| AgendaItem {
+            id: entry.id.clone(),
+            entry_type: entry.entry_type.clone(),
+            subject: entry.subject.clone(),
+            completed: entry.completed,
+            text: entry_sentence(entry),
+        })
+        .collect();
+
+    let mut by_type: BTreeMap<String, usize> = BTreeMap::new();
+    for entry in entries.iter().filter(|e| !e.completed) {
+        *by_type.entry(entry.entry_type.clone()).or_default() += 1;
+    }
+
+    let total = entries.len();
+    let completed = entries.iter().filter(|e| e.completed).count();
+    let remaining = total - completed;
+
+    let summary = if total == 0 {
+        format!("Nothing on the agenda for {}.", day)
+    } else if remaining == 0 {
+        format!("Everything for {} is done.", day)
+    } else {
+        let parts: Vec<String> = by_type
+            .iter()
+            .map(|(entry_type, count)| count_phrase(entry_type, *count))
+            .collect();
+        format!(
+            "{}: {} {} left, {}.",
+            day,
+            remaining,
+            if remaining == 1 { "thing" } else { "things" },
+            join_spoken(&parts)
+        )
+    };
+
+    Agenda {
+        date: date.format("%Y-%m-%d").to_string(),
+        day,
+        total,
+        completed,
+        remaining,
+        by_type,
+        summary,
+        items,
+    }
+}
+
+/// Describe one entry in a sentence, e.g. "Matematica homework: pag. 10."
+pub fn entry_sentence(entry: &HomeworkEntry) -> String {
+    let task = speakable(&entry.task);
+    let subject = entry.subject.trim();
+
+    let (label, task) = match entry.entry_type.as_str() {
+        "compiti" => ("homework", task.as_str()),
+        "verifica" => ("test", task.as_str()),
+        "interrogazione" => ("oral test", task.as_str()),
+        "nota" => ("note", task.as_str()),
+        "studio" => (
+            "study session",
+            task.strip_prefix("Study for: ").unwrap_or(&task),
+        ),
+        "lavoro" => (
+            "homework to do",
+            task.strip_prefix("Do homework: ").unwrap_or(&task),
+        ),
+        _ => ("", task.as_str()),
+    };
+
+    let head = match (subject.is_empty(), label.is_empty()) {
+        (true, true) => String::new(),
+        (true, false) => capitalize(label),
+        (false, true) => subject.to_string(),
+        (false, false) => format!("{} {}", subject, label),
+    };
+
+    let sentence = match (head.is_empty(), task.is_empty()) {
+        (true, _) => task.to_string(),
+        (false, true) => head,
+        (false, false) => format!("{}: {}", head, task),
+    };
+
+    if sentence.ends_with(['.', '!', '?']) {
+        sentence
+    } else {
+        format!("{}.", sentence)
+    }
+}
+
+/// Collapse whitespace and line breaks so the text reads as one phrase
+fn speakable(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+/// "1 test", "2 tests", "3 homework"
+fn count_phrase(entry_type: &str, count: usize) -> String {
+    let (singular, plural) = match entry_type {
+        "compiti" => ("homework", "homework"),
+        "verifica" => ("test", "tests"),
+        "interrogazione" => ("oral test", "oral tests"),
+        "nota" => ("note", "notes"),
+        "studio" => ("study session", "study sessions"),
+        "lavoro" => ("homework reminder", "homework reminders"),
+        _ => ("other item", "other items"),
+    };
+    format!("{} {}", count, if count == 1 { singular } else { plural })
+}
+
+/// Join phrases as "a", "a and b" or "a, b and c"
+fn join_spoken(parts: &[String]) -> String {
+    match parts {
+        [] => String::new(),
+        [only] => only.clone(),
+        [rest @ .., last] => format!("{} and {}", rest.join(", "), last),
+    }
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_entry(entry_type: &str, subject: &str, task: &str) -> HomeworkEntry {
+        HomeworkEntry::new(
+            entry_type.to_string(),
+            "2025-01-15".to_string(),
+            subject.to_string(),
+            task.to_string(),
+        )
+    }
+
+    fn date() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, 15).unwrap()
+    }
+
+    #[test]
+    fn test_entry_sentence_by_type() {
+        assert_eq!(
+            entry_sentence(&make_entry("compiti", "Matematica", "Pag. 10 es. 3")),
+            "Matematica homework: Pag. 10 es. 3."
+        );
+        assert_eq!(
+            entry_sentence(&make_entry("verifica", "Storia", "Cap. 5")),
+            "Storia test: Cap. 5."
+        );
+        assert_eq!(
+            entry_sentence(&make_entry("studio", "Storia", "Study for: Cap. 5")),
+            "Storia study session: Cap. 5."
+        );
+        assert_eq!(
+            entry_sentence(&make_entry("lavoro", "Matematica", "Do homework: Pag. 10")),
+            "Matematica homework to do: Pag. 10."
+        );
+    }
+
+    #[test]
+    fn test_entry_sentence_collapses_whitespace_and_keeps_punctuation() {
+        assert_eq!(
+            entry_sentence(&make_entry("nota", "", "Portare\n  il libro!")),
+            "Note: Portare il libro!"
+        );
+        assert_eq!(
+            entry_sentence(&make_entry("altro", "Arte", "Album")),
+            "Arte: Album."
+        );
+    }
+
+    #[test]
+    fn test_build_agenda_counts_and_summary() {
+        let mut done = make_entry("compiti", "Inglese", "Unit 3");
+        done.completed = true;
+        let entries = vec![
+            make_entry("compiti", "Matematica", "Pag. 10"),
+            make_entry("verifica", "Storia", "Cap. 5"),
+            make_entry("compiti", "Italiano", "Tema"),
+            done,
+        ];
+
+        let agenda = build_agenda(date(), &entries);
+        assert_eq!(agenda.date, "2025-01-15");
+        assert_eq!(agenda.day, "Wednesday 15 January");
+        assert_eq!(agenda.total, 4);
+        assert_eq!(agenda.completed, 1);
+        assert_eq!(agenda.remaining, 3);
+        assert_eq!(agenda.by_type.get("compiti"), Some(&2));
+        assert_eq!(agenda.by_type.get("verifica"), Some(&1));
+        assert_eq!(
+            agenda.summary,
+            "Wednesday 15 January: 3 things left, 2 homework and 1 test."
+        );
+        assert_eq!(agenda.items.len(), 4);
+        assert!(agenda.items[3].completed);
+    }
+
+    #[test]
+    fn test_build_agenda_empty_and_all_done() {
+        assert_eq!(
+            build_agenda(date(), &[]).summary,
+            "Nothing on the agenda for Wednesday 15 January."
+        );
+
+        let mut done = make_entry("compiti", "Inglese", "Unit 3");
+        done.completed = true;
+        let agenda = build_agenda(date(), &[done]);
+        assert_eq!(
+            agenda.summary,
+            "Everything for Wednesday 15 January is done."
+        );
+        assert!(agenda.by_type.is_empty());
+    }
+
+    #[test]
+    fn test_join_spoken() {
+        let parts = |v: &[&str]| v.iter().map(|s| s.to_string()).collect::<Vec<_>>();
+        assert_eq!(join_spoken(&parts(&["a"])), "a");
+        assert_eq!(join_spoken(&parts(&["a", "b"])), "a and b");
+        assert_eq!(join_spoken(&parts(&["a", "b", "c"])), "a, b and c");
+    }
+}
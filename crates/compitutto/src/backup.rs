@@ -0,0 +1,264 @@
+//! Database backups in `data/backups/`, so a corrupted or wrongly edited
+//! database can be rolled back.
+//!
+//! The `backup_db` job (see `jobs`) copies the open database every night
+//! with SQLite's online backup API, which gives a consistent snapshot even
+//! while the server writes (a plain file copy could catch the WAL half
+//! applied). Files are named `homework_YYYYMMDD_HHMMSS.db` after the local
+//! time they were taken. Rotation keeps every backup of the last
+//! [`KEEP_DAYS`] days plus the newest of each of the last [`KEEP_WEEKS`]
+//! weeks, so a problem noticed weeks later can still be undone.
+//!
+//! `compitutto backups list` shows them and `compitutto backups restore`
+//! copies one back over the database, after checking it and backing up the
+//! current state first.
+
+use anyhow::{bail, Context, Result};
+use chrono::{Datelike, Duration, NaiveDateTime};
+use rusqlite::{backup::Progress, Connection, OpenFlags, MAIN_DB};
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+/// Directory next to the database that holds the backups
+pub const BACKUP_DIR: &str = "backups";
+
+/// Every backup younger than this is kept
+pub const KEEP_DAYS: i64 = 7;
+
+/// The newest backup of each of this many weeks is kept
+pub const KEEP_WEEKS: usize = 8;
+
+const NAME_PREFIX: &str = "homework_";
+const NAME_FORMAT: &str = "%Y%m%d_%H%M%S";
+
+/// A backup file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Backup {
+    pub path: PathBuf,
+    /// Local time it was taken, from the file name
+    pub taken: NaiveDateTime,
+    pub bytes: u64,
+}
+
+impl Backup {
+    pub fn name(&self) -> String {
+        self.path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default()
+    }
+}
+
+/// `data/backups/` for the database `conn` has open
+pub fn backup_dir(conn: &Connection) -> Result<PathBuf> {
+    let Some(db_path) = conn.path().filter(|p| !p.is_empty()) else {
+        bail!("An in-memory database has no backup directory");
+    };
+    let parent = Path::new(db_path).parent().unwrap_or(Path::new("."));
+    Ok(parent.join(BACKUP_DIR))
+}
+
+fn file_name(taken: NaiveDateTime) -> String {
+    format!("{}{}.db", NAME_PREFIX, taken.format(NAME_FORMAT))
+}
+
+/// When a backup named `name` was taken, `None` for other files
+fn parse_name(name: &str) -> Option<NaiveDateTime> {
+    let stamp = name.strip_prefix(NAME_PREFIX)?.strip_suffix(".db")?;
+    NaiveDateTime::parse_from_str(stamp, NAME_FORMAT).ok()
+}
+
+/// Back up the database `conn` has open into `dir`, named after `now`
+pub fn create(conn: &Connection, dir: &Path, now: NaiveDateTime) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let path = dir.join(file_name(now));
+    conn.backup(MAIN_DB, &path, None)
+        .with_context(|| format!("Failed to back up the database to {}", path.display()))?;
+    Ok(path)
+}
+
+/// The backups in `dir`, newest first. A missing directory has none.
+pub fn list(dir: &Path) -> Result<Vec<Backup>> {
+    if !dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut backups = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        let name = entry.file_name();
+        let Some(taken) = parse_name(&name.to_string_lossy()) else {
+            continue;
+        };
+        backups.push(Backup {
+            path: entry.path(),
+            taken,
+            bytes: entry.metadata()?.len(),
+        });
+    }
+    backups.sort_by(|a, b| b.taken.cmp(&a.taken));
+    Ok(backups)
+}
+
+/// Of `taken` (newest first), the indexes rotation would delete at `now`.
+/// The newest backup always stays.
+fn expired(taken: &[NaiveDateTime], now: NaiveDateTime) -> Vec<usize> {
+    let mut weeks = HashSet::new();
+    taken
+        .iter()
+        .enumerate()
+        .filter(|&(i, &t)| {
+            let week = t.iso_week();
+            let newest_of_week = weeks.insert((week.year(), week.week()));
+            let keep = i == 0
+                || now - t < Duration::days(KEEP_DAYS)
+                || (newest_of_week && weeks.len() <= KEEP_WEEKS);
+            !keep
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Delete the backups in `dir` the rotation policy no longer keeps
+pub fn rotate(dir: &Path, now: NaiveDateTime) -> Result<Vec<PathBuf>> {
+    let backups = list(dir)?;
+    let taken: Vec<NaiveDateTime> = backups.iter().map(|b| b.taken).collect();
+    let mut removed = Vec::new();
+    for i in expired(&taken, now) {
+        let path = &backups[i].path;
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to delete {}", path.display()))?;
+        removed.push(path.clone());
+    }
+    Ok(removed)
+}
+
+/// `compitutto backups restore` takes a file name in `dir` or a path
+pub fn resolve(dir: &Path, name: &str) -> PathBuf {
+    let path = Path::new(name);
+    if path.exists() {
+        path.to_path_buf()
+    } else {
+        dir.join(name)
+    }
+}
+
+/// Fail unless `path` is an intact compitutto database
+pub fn verify(path: &Path) -> Result<()> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .with_context(|| format!("{} is not a SQLite database", path.display()))?;
+    if integrity != "ok" {
+        bail!("{} is damaged: {}", path.display(), integrity);
+    }
+    let has_entries: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type = 'table' AND name = 'entries'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !has_entries {
+        bail!("{} is not a compitutto database", path.display());
+    }
+    Ok(())
+}
+
+/// Replace the database `conn` has open with the backup at `path`. The
+/// current contents are backed up first; returns where they went.
+pub fn restore(conn: &mut Connection, path: &Path, now: NaiveDateTime) -> Result<PathBuf> {
+    verify(path)?;
+    let previous = create(conn, &backup_dir(conn)?, now)?;
+    conn.restore(MAIN_DB, path, None::<fn(Progress)>)
+        .with_context(|| format!("Failed to restore {}", path.display()))?;
+    Ok(previous)
+}
+
+/// The `backup_db` job: a new backup, then rotation
+pub fn run_job(conn: &Connection) -> Result<String> {
+    let dir = backup_dir(conn)?;
+    let now = chrono::Local::now().naive_local();
+    let path = create(conn, &dir, now)?;
+    let removed = rotate(&dir, now)?;
+    Ok(format!(
+        "Saved {}, deleted {} old backups",
+        path.file_name().unwrap_or_default().to_string_lossy(),
+        removed.len()
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::db;
+    use crate::types::HomeworkEntry;
+    use tempfile::TempDir;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_names() {
+        let taken = at("2025-01-15 02:15");
+        assert_eq!(file_name(taken), "homework_20250115_021500.db");
+        assert_eq!(parse_name(&file_name(taken)), Some(taken));
+        assert_eq!(parse_name("homework.db"), None);
+        assert_eq!(parse_name("homework_20250115.db"), None);
+    }
+
+    #[test]
+    fn test_expired() {
+        let now = at("2025-03-10 03:00");
+        // Nightly backups for the last 70 days, newest first
+        let taken: Vec<NaiveDateTime> = (0..70).map(|d| now - Duration::days(d)).collect();
+        let kept: Vec<NaiveDateTime> = {
+            let gone = expired(&taken, now);
+            (0..taken.len())
+                .filter(|i| !gone.contains(i))
+                .map(|i| taken[i])
+                .collect()
+        };
+        // A week of nightlies (Tue 4th – Mon 10th), then the Sunday of six more weeks
+        assert_eq!(kept.len(), 7 + 6);
+        assert_eq!(kept[6], at("2025-03-04 03:00"));
+        assert_eq!(kept[7], at("2025-03-02 03:00"));
+        assert_eq!(kept[12], at("2025-01-26 03:00"));
+
+        // An old lone backup is never deleted
+        assert!(expired(&[at("2024-01-01 03:00")], now).is_empty());
+    }
+
+    #[test]
+    fn test_backup_and_restore() {
+        let temp_dir = TempDir::new().unwrap();
+        let migrations = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations).unwrap();
+        db::write_test_migrations(&migrations);
+        let db_path = temp_dir.path().join("homework.db");
+        let mut conn = db::init_db(&db_path, &migrations).unwrap();
+        let entry = HomeworkEntry::new(
+            "compiti".to_string(),
+            "2025-01-20".to_string(),
+            "Matematica".to_string(),
+            "Esercizi pag. 50".to_string(),
+        );
+        db::insert_entry(&conn, &entry).unwrap();
+
+        let dir = backup_dir(&conn).unwrap();
+        assert_eq!(dir, temp_dir.path().join("backups"));
+        let path = create(&conn, &dir, at("2025-01-15 02:15")).unwrap();
+        verify(&path).unwrap();
+        let backups = list(&dir).unwrap();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(backups[0].name(), "homework_20250115_021500.db");
+
+        db::delete_entry(&conn, &entry.id).unwrap();
+        let previous = restore(&mut conn, &path, at("2025-01-16 09:00")).unwrap();
+        assert!(db::get_entry(&conn, &entry.id).unwrap().is_some());
+        assert_eq!(list(&dir).unwrap()[0].path, previous);
+
+        let junk = temp_dir.path().join("junk.db");
+        std::fs::write(&junk, "not a database").unwrap();
+        assert!(restore(&mut conn, &junk, at("2025-01-16 09:01")).is_err());
+    }
+}
@@ -0,0 +1,120 @@
+//! Homework that only exists on paper: a photo of the diary page becomes a
+//! draft entry to review.
+//!
+//! `POST /api/capture` takes the photo as the request body (`Content-Type:
+//! image/jpeg` and friends, no multipart form), saves it under
+//! `data/captures/` and stages a draft in `pending_imports`, whatever the
+//! `review_imports` setting says: the date, subject and type are guesses
+//! until someone looks at the page. The photo is recorded as the draft's
+//! attachment, so it follows the entry once accepted, shows on the import
+//! page, and goes through OCR like the portal's photos. When the `ocr`
+//! feature is on, the photo is read straight away and its text becomes the
+//! draft's task; otherwise the task says to look at the photo and the
+//! `ocr_attachments` job gets to it later.
+
+use anyhow::{Context, Result};
+use std::path::{Path, PathBuf};
+
+/// Where captured photos are kept, under the data directory
+pub const CAPTURES_DIR: &str = "captures";
+
+/// Largest photo accepted; phone cameras easily go past axum's 2 MB default
+pub const MAX_PHOTO_BYTES: usize = 20 * 1024 * 1024;
+
+/// Prefix of a captured draft's `source_id`
+pub const SOURCE_PREFIX: &str = "capture-";
+
+/// Attachment name of a captured photo
+pub const PHOTO_NAME: &str = "Diary page";
+
+/// Task of a draft whose photo hasn't been read
+pub const UNREAD_TASK: &str = "Photo of the diary page: see the attachment";
+
+/// Image types accepted, with the extension the copy is saved under
+const PHOTO_TYPES: &[(&str, &str)] = &[
+    ("image/jpeg", "jpg"),
+    ("image/png", "png"),
+    ("image/webp", "webp"),
+    ("image/heic", "heic"),
+    ("image/gif", "gif"),
+];
+
+/// Extension for a photo sent as `content_type` (parameters ignored);
+/// `None` for anything that isn't an accepted image type
+pub fn photo_extension(content_type: &str) -> Option<&'static str> {
+    let mime = content_type.split(';').next()?.trim().to_lowercase();
+    PHOTO_TYPES
+        .iter()
+        .find(|(t, _)| *t == mime)
+        .map(|(_, ext)| *ext)
+}
+
+/// Content type to serve a local attachment copy with, from its extension
+pub fn content_type_for(path: &Path) -> &'static str {
+    let ext = path
+        .extension()
+        .map(|e| e.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+    let ext = if ext == "jpeg" {
+        "jpg".to_string()
+    } else {
+        ext
+    };
+    PHOTO_TYPES
+        .iter()
+        .find(|(_, e)| *e == ext)
+        .map(|(t, _)| *t)
+        .unwrap_or("application/octet-stream")
+}
+
+/// Accepted types, for the error message
+pub fn accepted_types() -> String {
+    PHOTO_TYPES
+        .iter()
+        .map(|(t, _)| *t)
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+/// Save `bytes` in `dir` as `<id>.<extension>` and return the path
+pub fn save_photo(dir: &Path, id: &str, extension: &str, bytes: &[u8]) -> Result<PathBuf> {
+    std::fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    let path = dir.join(format!("{}.{}", id, extension));
+    std::fs::write(&path, bytes).with_context(|| format!("Failed to write {:?}", path))?;
+    Ok(path)
+}
+
+/// The draft's task: the photo's text when there is some
+pub fn draft_task(ocr_text: Option<&str>) -> String {
+    match ocr_text.map(str::trim) {
+        Some(text) if !text.is_empty() => text.to_string(),
+        _ => UNREAD_TASK.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_photo_types() {
+        assert_eq!(photo_extension("image/jpeg"), Some("jpg"));
+        assert_eq!(photo_extension("Image/PNG; charset=binary"), Some("png"));
+        assert_eq!(photo_extension("application/pdf"), None);
+        assert_eq!(photo_extension(""), None);
+
+        assert_eq!(content_type_for(Path::new("a/page.JPEG")), "image/jpeg");
+        assert_eq!(content_type_for(Path::new("page.webp")), "image/webp");
+        assert_eq!(
+            content_type_for(Path::new("notes")),
+            "application/octet-stream"
+        );
+    }
+
+    #[test]
+    fn test_draft_task() {
+        assert_eq!(draft_task(Some(" Es. 3 pag. 42 \n")), "Es. 3 pag. 42");
+        assert_eq!(draft_task(Some("  ")), UNREAD_TASK);
+        assert_eq!(draft_task(None), UNREAD_TASK);
+    }
+}
@@ -0,0 +1,149 @@
+//! Cross-origin access to the API, for companion apps served from somewhere
+//! else (a separately hosted front end, a browser extension).
+//!
+//! The `cors_origins` setting lists the origins a browser may call the API
+//! from, as `scheme://host[:port]` (`https://diario.example.org`,
+//! `chrome-extension://abcdef...`). Empty, the default, means none: pages
+//! served by compitutto itself don't need it. A listed origin is echoed in
+//! `Access-Control-Allow-Origin` and may send credentials (cookies or the
+//! basic auth of a proxy in front of the server); `*` lets any origin read
+//! and write, but without credentials, which browsers refuse with a
+//! wildcard anyway.
+//!
+//! `server::cors_guard` answers preflight requests itself, before the
+//! read-only guard, and in read-only mode only offers GET and HEAD.
+
+use axum::http::{header, HeaderMap, HeaderValue};
+
+/// Setting value that lets any origin in
+pub const ANY: &str = "*";
+
+/// Methods a preflight is told it may use
+const METHODS: &str = "GET, HEAD, POST, PUT, PATCH, DELETE";
+
+/// Methods a preflight may use in read-only mode
+const READ_METHODS: &str = "GET, HEAD";
+
+/// How long a browser may cache a preflight answer
+const MAX_AGE_SECS: &str = "600";
+
+/// `origin` the way browsers send it: `scheme://host[:port]`, lowercase,
+/// without a trailing slash. `None` for anything with a path, query or
+/// user, or without a scheme. `*` is kept as is.
+pub fn normalize_origin(origin: &str) -> Option<String> {
+    let origin = origin.trim().trim_end_matches('/');
+    if origin == ANY {
+        return Some(ANY.to_string());
+    }
+    let (scheme, host) = origin.split_once("://")?;
+    let scheme_ok = scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '+' | '-' | '.'));
+    let host_ok = !host.is_empty()
+        && !host.contains(|c: char| c.is_whitespace() || matches!(c, '/' | '?' | '#' | '@'));
+    (scheme_ok && host_ok).then(|| origin.to_lowercase())
+}
+
+/// Headers for a response to a request from `origin`: who may read it,
+/// and `Vary: Origin` whenever the answer depends on who asks. Empty when
+/// `origins` allows nobody.
+pub fn response_headers(origins: &[String], origin: &HeaderValue) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let listed = origin
+        .to_str()
+        .ok()
+        .and_then(normalize_origin)
+        .is_some_and(|o| o != ANY && origins.contains(&o));
+    if origins.iter().any(|o| o != ANY) {
+        headers.insert(header::VARY, HeaderValue::from_static("origin"));
+    }
+    if listed {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_ORIGIN, origin.clone());
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_CREDENTIALS,
+            HeaderValue::from_static("true"),
+        );
+    } else if origins.iter().any(|o| o == ANY) {
+        headers.insert(
+            header::ACCESS_CONTROL_ALLOW_ORIGIN,
+            HeaderValue::from_static(ANY),
+        );
+    }
+    headers
+}
+
+/// Whether `headers` (from [`response_headers`]) let the origin in
+pub fn is_allowed(headers: &HeaderMap) -> bool {
+    headers.contains_key(header::ACCESS_CONTROL_ALLOW_ORIGIN)
+}
+
+/// The rest of a preflight answer: the methods, the request headers the
+/// browser asked about, and how long to remember it
+pub fn preflight_headers(request: &HeaderMap, read_only: bool) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    let methods = if read_only { READ_METHODS } else { METHODS };
+    headers.insert(
+        header::ACCESS_CONTROL_ALLOW_METHODS,
+        HeaderValue::from_static(methods),
+    );
+    if let Some(asked) = request.get(header::ACCESS_CONTROL_REQUEST_HEADERS) {
+        headers.insert(header::ACCESS_CONTROL_ALLOW_HEADERS, asked.clone());
+    }
+    headers.insert(
+        header::ACCESS_CONTROL_MAX_AGE,
+        HeaderValue::from_static(MAX_AGE_SECS),
+    );
+    headers
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_normalize_origin() {
+        assert_eq!(
+            normalize_origin(" https://Diario.example.org/ ").as_deref(),
+            Some("https://diario.example.org")
+        );
+        assert_eq!(
+            normalize_origin("http://localhost:5173").as_deref(),
+            Some("http://localhost:5173")
+        );
+        assert_eq!(
+            normalize_origin("chrome-extension://abcdef").as_deref(),
+            Some("chrome-extension://abcdef")
+        );
+        assert_eq!(normalize_origin("*").as_deref(), Some("*"));
+        assert_eq!(normalize_origin("diario.example.org"), None);
+        assert_eq!(normalize_origin("https://example.org/app"), None);
+        assert_eq!(normalize_origin("https://user@example.org"), None);
+        assert_eq!(normalize_origin("https://"), None);
+    }
+
+    #[test]
+    fn test_response_headers() {
+        let app = HeaderValue::from_static("https://app.example.org");
+        let other = HeaderValue::from_static("https://evil.example.com");
+        let listed = vec!["https://app.example.org".to_string()];
+
+        let headers = response_headers(&listed, &app);
+        assert!(is_allowed(&headers));
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], app);
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[header::VARY], "origin");
+
+        let headers = response_headers(&listed, &other);
+        assert!(!is_allowed(&headers));
+        assert_eq!(headers[header::VARY], "origin");
+
+        // A wildcard never comes with credentials
+        let headers = response_headers(&[ANY.to_string()], &other);
+        assert_eq!(headers[header::ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert!(!headers.contains_key(header::ACCESS_CONTROL_ALLOW_CREDENTIALS));
+        assert!(!headers.contains_key(header::VARY));
+
+        assert!(response_headers(&[], &app).is_empty());
+    }
+}
@@ -0,0 +1,171 @@
+//! The same task posted under two subjects.
+//!
+//! Interdisciplinary work ("Ricerca sul Rinascimento" for Storia and Arte)
+//! often shows up once per subject in the export. Two entries are the same
+//! task when they're due the same day, have the same type, are under
+//! different subjects and their tasks share nearly all their words
+//! ([`SIMILARITY`]). The one imported first is the primary; every other one
+//! becomes a copy, stored as a `cross_listed` link from the copy to the
+//! primary.
+//!
+//! A copy gets no study sessions of its own, is left out of the list (the
+//! primary shows a badge with every subject instead) and is completed
+//! together with its primary. `db::link_cross_listed()` runs on every
+//! import; deleting the link turns it into a `related` one, so the two are
+//! shown apart again and never merged a second time.
+
+use std::collections::{HashMap, HashSet};
+
+use crate::types::HomeworkEntry;
+
+/// Share of words two tasks need in common (over all the words in either)
+pub const SIMILARITY: f64 = 0.8;
+
+/// A task's words, lowercased, without punctuation
+fn words(task: &str) -> HashSet<String> {
+    task.split(|c: char| !c.is_alphanumeric())
+        .filter(|w| !w.is_empty())
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Whether two tasks say the same thing, give or take a few words
+pub fn similar(a: &str, b: &str) -> bool {
+    let (a, b) = (words(a), words(b));
+    if a.is_empty() || b.is_empty() {
+        return false;
+    }
+    let shared = a.intersection(&b).count() as f64;
+    let all = a.union(&b).count() as f64;
+    shared / all >= SIMILARITY
+}
+
+/// `(copy, primary)` id pairs among `entries`, which should be top-level
+/// entries that aren't copies already. Pairs in `linked` (either order)
+/// are never merged.
+pub fn find_pairs(
+    entries: &[HomeworkEntry],
+    linked: &HashSet<(String, String)>,
+) -> Vec<(String, String)> {
+    let mut groups: HashMap<(&str, String), Vec<&HomeworkEntry>> = HashMap::new();
+    for entry in entries.iter().filter(|e| e.parent_id.is_none()) {
+        groups
+            .entry((entry.date.as_str(), entry.entry_type.to_lowercase()))
+            .or_default()
+            .push(entry);
+    }
+
+    let mut pairs = Vec::new();
+    for group in groups.values_mut() {
+        group.sort_by(|a, b| (&a.created_at, &a.id).cmp(&(&b.created_at, &b.id)));
+        let mut copies: HashSet<&str> = HashSet::new();
+        for (i, primary) in group.iter().enumerate() {
+            if copies.contains(primary.id.as_str()) {
+                continue;
+            }
+            let mut subjects = vec![primary.subject.to_lowercase()];
+            for copy in &group[i + 1..] {
+                let subject = copy.subject.to_lowercase();
+                let is_linked = linked.contains(&(copy.id.clone(), primary.id.clone()))
+                    || linked.contains(&(primary.id.clone(), copy.id.clone()));
+                if copies.contains(copy.id.as_str())
+                    || subjects.contains(&subject)
+                    || is_linked
+                    || !similar(&primary.task, &copy.task)
+                {
+                    continue;
+                }
+                copies.insert(&copy.id);
+                subjects.push(subject);
+                pairs.push((copy.id.clone(), primary.id.clone()));
+            }
+        }
+    }
+    pairs.sort();
+    pairs
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, subject: &str, task: &str, created_at: &str) -> HomeworkEntry {
+        let mut entry = HomeworkEntry::with_id(
+            id.to_string(),
+            "compiti".to_string(),
+            "2025-01-20".to_string(),
+            subject.to_string(),
+            task.to_string(),
+        );
+        entry.created_at = created_at.to_string();
+        entry
+    }
+
+    #[test]
+    fn test_similar() {
+        assert!(similar(
+            "Ricerca sul Rinascimento (gruppi da 3)",
+            "ricerca sul Rinascimento, gruppi da 3"
+        ));
+        assert!(similar(
+            "Ricerca sul Rinascimento a gruppi: consegna su Classroom entro le 20",
+            "Ricerca sul Rinascimento a gruppi: consegna su Classroom entro le 21"
+        ));
+        assert!(!similar("Es. 3 pag. 42", "Es. 5 pag. 42"));
+        assert!(!similar("", ""));
+    }
+
+    #[test]
+    fn test_find_pairs() {
+        let storia = entry(
+            "s",
+            "Storia",
+            "Ricerca sul Rinascimento",
+            "2025-01-10 08:00:00",
+        );
+        let arte = entry(
+            "a",
+            "Arte",
+            "Ricerca sul rinascimento.",
+            "2025-01-10 08:00:01",
+        );
+        let italiano = entry(
+            "i",
+            "Italiano",
+            "Ricerca sul Rinascimento",
+            "2025-01-10 08:00:02",
+        );
+        let same_subject = entry(
+            "t",
+            "storia",
+            "Ricerca sul Rinascimento",
+            "2025-01-10 08:00:03",
+        );
+        let mut other_day = arte.clone();
+        other_day.id = "d".to_string();
+        other_day.date = "2025-01-21".to_string();
+        let entries = vec![
+            italiano.clone(),
+            arte.clone(),
+            storia.clone(),
+            same_subject,
+            other_day,
+        ];
+
+        // The first imported is the primary, whatever the order given
+        assert_eq!(
+            find_pairs(&entries, &HashSet::new()),
+            vec![
+                ("a".to_string(), "s".to_string()),
+                ("i".to_string(), "s".to_string()),
+            ]
+        );
+
+        // A pair already linked (e.g. split apart before) stays apart
+        let linked = HashSet::from([("s".to_string(), "a".to_string())]);
+        assert_eq!(
+            find_pairs(&[storia, arte, italiano], &linked),
+            vec![("i".to_string(), "s".to_string())]
+        );
+    }
+}
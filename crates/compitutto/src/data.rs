@@ -1,11 +1,17 @@
 use anyhow::Result;
 use chrono::NaiveDate;
+use regex::Regex;
+use serde::Serialize;
+use std::collections::{HashMap, HashSet};
 use std::hash::{Hash, Hasher};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
 use tracing::{debug, info, warn};
 
-use crate::parser;
-use crate::types::HomeworkEntry;
+use crate::details;
+use crate::parser::{self, ParseReport};
+use crate::subjects::{self, SubjectPipeline};
+use crate::types::{HomeworkEntry, NewAttachment};
 
 /// Keywords that indicate a test/quiz (case-insensitive)
 const TEST_KEYWORDS: &[&str] = &["verifica", "prova", "test", "interrogazione"];
@@ -19,13 +25,25 @@ pub fn is_test_or_quiz(entry: &HomeworkEntry) -> bool {
 /// Generate study sessions for a test entry.
 ///
 /// Creates up to `study_days_before` session entries on the days leading up to
-/// the test (minimum 3). Each session links back to its parent via `parent_id`.
+/// the test (minimum 3), skipping `holidays`. Among the nearest twice as many
+/// days, the ones with the fewest `activity_minutes` are used, so sessions
+/// move off swimming-lesson days when there's room. Each session links back
+/// to its parent via `parent_id`. With `split_topics`, sessions for a test
+/// that names several chapters or pages each get their own (see
+/// [`study_topics`]). Generated entries never get sessions of their own,
+/// even though "Study for: Verifica ..." names a test.
 pub fn generate_study_sessions(
     test: &HomeworkEntry,
     today: NaiveDate,
     study_days_before: u32,
+    holidays: &HashSet<NaiveDate>,
+    activity_minutes: &HashMap<NaiveDate, u32>,
+    split_topics: bool,
 ) -> Vec<HomeworkEntry> {
-    let study_days_before = study_days_before.max(3) as i64;
+    if test.is_generated() {
+        return Vec::new();
+    }
+    let study_days_before = study_days_before.max(3) as usize;
 
     let test_date = match NaiveDate::parse_from_str(&test.date, "%Y-%m-%d") {
         Ok(d) => d,
@@ -39,8 +57,16 @@ pub fn generate_study_sessions(
         return Vec::new();
     }
 
-    // Generate up to study_days_before days before, but only for future dates
-    let days_to_generate = std::cmp::min(study_days_before, days_until - 1) as usize;
+    // The days before the test, latest first, after today and not on a holiday
+    let mut study_dates: Vec<NaiveDate> = (1..days_until)
+        .map(|days_before| test_date - chrono::Duration::days(days_before))
+        .filter(|date| !holidays.contains(date))
+        .take(study_days_before * 2)
+        .collect();
+    // Least busy first; the sort is stable, so ties keep the days nearest the test
+    study_dates.sort_by_key(|date| activity_minutes.get(date).copied().unwrap_or(0));
+    study_dates.truncate(study_days_before);
+    study_dates.sort_by(|a, b| b.cmp(a));
 
     // Truncate task to 100 chars for study session text
     let truncated_task = if test.task.len() > 100 {
@@ -50,38 +76,163 @@ pub fn generate_study_sessions(
     };
 
     let now = chrono::Utc::now().to_rfc3339();
-
-    (1..=days_to_generate)
-        .map(|days_before| {
-            let study_date = test_date - chrono::Duration::days(days_before as i64);
+    // Topics run earliest session first, dates nearest first
+    let topics = split_topics
+        .then(|| study_topics(&test.task, study_dates.len()))
+        .flatten();
+    let session_count = study_dates.len();
+
+    study_dates
+        .into_iter()
+        .enumerate()
+        .map(|(i, study_date)| {
             let date_str = study_date.format("%Y-%m-%d").to_string();
-            let task_str = format!("Study for: {}", truncated_task);
-            let id = compute_study_session_id(&test.id, days_before);
+            let task_str = match &topics {
+                Some(topics) => format!(
+                    "Study for: {} — {}",
+                    truncated_task,
+                    topics[session_count - 1 - i]
+                ),
+                None => format!("Study for: {}", truncated_task),
+            };
+            let id = compute_study_session_id(generation_key(test), i + 1);
             let source_id = HomeworkEntry::generate_source_id(&date_str, &test.subject, &task_str);
             HomeworkEntry {
                 id,
                 source_id: Some(source_id),
                 entry_type: "studio".to_string(),
                 date: date_str,
+                end_date: None,
                 subject: test.subject.clone(),
+                topic: test.topic.clone(),
                 task: task_str,
                 completed: false,
                 position: 0,
                 parent_id: Some(test.id.clone()),
                 created_at: now.clone(),
                 updated_at: now.clone(),
+                estimated_minutes: None,
+                actual_minutes: None,
+                difficulty: None,
+                pinned: false,
+                color: None,
+                grade: None,
+                source_date: None,
+                assigned_at: None,
+                teacher: None,
+                visible_from: None,
+                source_file: None,
             }
         })
         .collect()
 }
 
-/// Compute a deterministic ID for a study session based on parent ID and days before
-fn compute_study_session_id(parent_id: &str, days_before: usize) -> String {
+// ========== Study topics ==========
+
+/// A chapter, unit or page number range: `capitoli 4-6`, `cap. 4, 5 e 6`,
+/// `unità 2 e 3`, `pag. 100-130`
+static TOPIC_RANGE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(
+        r"(?i)\b(capitol[oi]|cap\.|unit[àa]|lezion[ei]|modul[oi]|pagin[ae]|pagg?\.|pp\.)\s*(\d+(?:\s*(?:-|–|,|e|ed|a|al)\s*\d+)+)",
+    )
+    .unwrap()
+});
+
+/// Ranges wider than this are probably not what they seem
+const MAX_TOPIC_SPAN: u32 = 500;
+
+/// How a kind of topic is written: one, several
+fn topic_words(word: &str) -> (&'static str, &'static str) {
+    let word = word.to_lowercase();
+    if word.starts_with("cap") {
+        ("capitolo", "capitoli")
+    } else if word.starts_with("unit") {
+        ("unità", "unità")
+    } else if word.starts_with("lezion") {
+        ("lezione", "lezioni")
+    } else if word.starts_with("modul") {
+        ("modulo", "moduli")
+    } else {
+        ("pagina", "pagine")
+    }
+}
+
+/// The numbers in `4-6`, `4, 5 e 6` or `4 a 6`, in order, without repeats.
+fn topic_numbers(list: &str) -> Option<Vec<u32>> {
+    static PART: LazyLock<Regex> =
+        LazyLock::new(|| Regex::new(r"(?i)(\d+)(?:\s*(?:-|–|a|al)\s*(\d+))?").unwrap());
+    let mut numbers: Vec<u32> = Vec::new();
+    for part in PART.captures_iter(list) {
+        let from: u32 = part[1].parse().ok()?;
+        let to: u32 = match part.get(2) {
+            Some(to) => to.as_str().parse().ok()?,
+            None => from,
+        };
+        if to < from || to - from > MAX_TOPIC_SPAN {
+            return None;
+        }
+        numbers.extend((from..=to).filter(|n| !numbers.contains(n)));
+    }
+    Some(numbers)
+}
+
+/// `capitolo 4`, `capitoli 4-6`, `capitoli 4, 7`
+fn topic_label(words: (&str, &str), numbers: &[u32]) -> String {
+    match numbers {
+        [one] => format!("{} {}", words.0, one),
+        [first, .., last] if numbers.windows(2).all(|w| w[1] == w[0] + 1) => {
+            format!("{} {}-{}", words.1, first, last)
+        }
+        _ => {
+            let list: Vec<String> = numbers.iter().map(|n| n.to_string()).collect();
+            format!("{} {}", words.1, list.join(", "))
+        }
+    }
+}
+
+/// What each of `sessions` study sessions should cover, earliest first, when
+/// `task` names several chapters, units or pages; `None` otherwise.
+///
+/// The parts are spread evenly over the sessions in order. With more
+/// sessions than parts, the ones left before the test review everything.
+pub fn study_topics(task: &str, sessions: usize) -> Option<Vec<String>> {
+    let captures = TOPIC_RANGE.captures(task)?;
+    let words = topic_words(&captures[1]);
+    let numbers = topic_numbers(&captures[2])?;
+    if numbers.len() < 2 || sessions < 2 {
+        return None;
+    }
+
+    let parts = numbers.len().min(sessions);
+    let (base, extra) = (numbers.len() / parts, numbers.len() % parts);
+    let mut rest = numbers.as_slice();
+    let mut topics = Vec::with_capacity(sessions);
+    for part in 0..parts {
+        let (chunk, tail) = rest.split_at(base + usize::from(part < extra));
+        topics.push(format!("Ripassa {}", topic_label(words, chunk)));
+        rest = tail;
+    }
+    let all = format!("Ripassa tutto ({})", topic_label(words, &numbers));
+    topics.resize(sessions, all);
+    Some(topics)
+}
+
+/// What a parent's generated entries are keyed on: its source_id, which an
+/// entry deleted and imported again keeps, so the new copy finds (and takes
+/// back) the sessions of the old one instead of spawning its own. Entries
+/// without one fall back to their id.
+pub fn generation_key(parent: &HomeworkEntry) -> &str {
+    parent.source_id.as_deref().unwrap_or(&parent.id)
+}
+
+/// Compute a deterministic ID for a study session based on the parent's
+/// [`generation_key`] and session number (1 = closest to the test)
+fn compute_study_session_id(parent_key: &str, session: usize) -> String {
     use std::collections::hash_map::DefaultHasher;
 
     let mut hasher = DefaultHasher::new();
-    parent_id.hash(&mut hasher);
-    days_before.hash(&mut hasher);
+    parent_key.hash(&mut hasher);
+    session.hash(&mut hasher);
     "study".hash(&mut hasher);
     format!("study_{:016x}", hasher.finish())
 }
@@ -145,7 +296,7 @@ pub fn generate_work_reminder(
 
     let date_str = work_date.format("%Y-%m-%d").to_string();
     let task_str = format!("Do homework: {}", entry.task);
-    let id = compute_work_reminder_id(&entry.id);
+    let id = compute_work_reminder_id(generation_key(entry));
     let source_id = HomeworkEntry::generate_source_id(&date_str, &entry.subject, &task_str);
     let now = chrono::Utc::now().to_rfc3339();
 
@@ -154,44 +305,113 @@ pub fn generate_work_reminder(
         source_id: Some(source_id),
         entry_type: "lavoro".to_string(),
         date: date_str,
+        end_date: None,
         subject: entry.subject.clone(),
+        topic: entry.topic.clone(),
         task: task_str,
         completed: false,
         position: 0,
         parent_id: Some(entry.id.clone()),
         created_at: now.clone(),
         updated_at: now,
+        estimated_minutes: None,
+        actual_minutes: None,
+        difficulty: None,
+        pinned: false,
+        color: None,
+        grade: None,
+        source_date: None,
+        assigned_at: None,
+        teacher: None,
+        visible_from: None,
+        source_file: None,
     })
 }
 
-/// Compute a deterministic ID for a work reminder based on the parent entry ID.
-fn compute_work_reminder_id(parent_id: &str) -> String {
+/// Compute a deterministic ID for a work reminder based on the parent's
+/// [`generation_key`].
+fn compute_work_reminder_id(parent_key: &str) -> String {
     use std::collections::hash_map::DefaultHasher;
     use std::hash::{Hash, Hasher};
     let mut hasher = DefaultHasher::new();
-    parent_id.hash(&mut hasher);
+    parent_key.hash(&mut hasher);
     "lavoro".hash(&mut hasher);
     format!("lavoro_{:016x}", hasher.finish())
 }
 
+/// Entries and attachments read from the export files
+#[derive(Debug, Default)]
+pub struct ParsedExports {
+    pub entries: Vec<HomeworkEntry>,
+    pub attachments: Vec<NewAttachment>,
+    /// One per file that parsed
+    pub reports: Vec<FileReport>,
+}
+
+/// The parse report for one export file
+#[derive(Debug, Clone, Serialize)]
+pub struct FileReport {
+    pub file: String,
+    #[serde(flatten)]
+    pub report: ParseReport,
+}
+
+/// Log what a parse skipped or found suspicious: a warning for the file, and
+/// the rows at debug level.
+pub fn log_report(file: &Path, report: &ParseReport) {
+    if report.is_clean() {
+        return;
+    }
+    warn!(
+        file = %file.display(),
+        parsed = report.parsed,
+        skipped = report.skipped.len(),
+        warnings = report.warnings.len(),
+        missing_columns = ?report.missing_columns,
+        "Export file has rows that need a look"
+    );
+    for issue in report.skipped.iter().chain(&report.warnings) {
+        debug!(file = %file.display(), row = issue.row, reason = %issue.reason, "Row issue");
+    }
+}
+
 /// Parse all export files and return the entries.
 ///
 /// This function only parses files - deduplication is handled by the database
 /// via the `source_id` field when entries are imported.
 pub fn parse_all_exports() -> Result<Vec<HomeworkEntry>> {
+    parse_exports().map(|parsed| parsed.entries)
+}
+
+/// Parse all export files, with the attachments listed in their sidecars.
+pub fn parse_exports() -> Result<ParsedExports> {
     let files = find_all_exports()?;
 
     if files.is_empty() {
         anyhow::bail!("No export files found in data/");
     }
 
+    let subjects = SubjectPipeline::load(Path::new(subjects::RULES_FILE))?;
+
     let mut entries: Vec<HomeworkEntry> = Vec::new();
+    let mut attachments = Vec::new();
+    let mut reports = Vec::new();
     for file in &files {
         debug!(file = %file.display(), "Processing export file");
-        match parser::parse_excel_xml(file) {
-            Ok(parsed) => {
+        match parser::parse_with_report(file, &subjects) {
+            Ok((mut parsed, report)) => {
                 debug!(count = parsed.len(), "Found entries");
+                log_report(file, &report);
+                attachments.extend(merge_sidecar(file, &mut parsed));
+                let name = file
+                    .file_name()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                for entry in &mut parsed {
+                    entry.source_file = Some(name.clone());
+                }
                 entries.extend(parsed);
+                reports.push(FileReport { file: name, report });
             }
             Err(e) => {
                 warn!(file = %file.display(), error = %e, "Failed to parse export file");
@@ -205,11 +425,33 @@ pub fn parse_all_exports() -> Result<Vec<HomeworkEntry>> {
         "Parsed export files"
     );
 
-    Ok(entries)
+    Ok(ParsedExports {
+        entries,
+        attachments,
+        reports,
+    })
+}
+
+/// Merge the export's `.details.json` sidecar, if any, into its entries and
+/// return the attachments it lists.
+fn merge_sidecar(file: &Path, entries: &mut [HomeworkEntry]) -> Vec<NewAttachment> {
+    match details::load_sidecar(file) {
+        Ok(Some(sidecar)) => {
+            let attachments = details::collect_attachments(entries, &sidecar);
+            let merged = details::merge_details(entries, &sidecar);
+            debug!(merged, file = %file.display(), "Merged entry details");
+            attachments
+        }
+        Ok(None) => Vec::new(),
+        Err(e) => {
+            warn!(file = %file.display(), error = %e, "Failed to read entry details");
+            Vec::new()
+        }
+    }
 }
 
 /// Find all export files in data/ directory
-fn find_all_exports() -> Result<Vec<PathBuf>> {
+pub fn find_all_exports() -> Result<Vec<PathBuf>> {
     let data_dir = PathBuf::from("data");
 
     if !data_dir.exists() {
@@ -438,7 +680,8 @@ mod tests {
         let test = make_entry("compiti", "2025-01-20", "Matematica", "Verifica sui limiti");
         let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
 
-        let sessions = generate_study_sessions(&test, today, 4);
+        let sessions =
+            generate_study_sessions(&test, today, 4, &HashSet::new(), &HashMap::new(), false);
 
         // 5 days away, should generate 4 study sessions
         assert_eq!(sessions.len(), 4);
@@ -463,7 +706,8 @@ mod tests {
         let test = make_entry("compiti", "2025-01-17", "Matematica", "Verifica");
         let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
 
-        let sessions = generate_study_sessions(&test, today, 4);
+        let sessions =
+            generate_study_sessions(&test, today, 4, &HashSet::new(), &HashMap::new(), false);
 
         // 2 days away, should generate 1 study session (day before)
         assert_eq!(sessions.len(), 1);
@@ -475,7 +719,8 @@ mod tests {
         let test = make_entry("compiti", "2025-01-16", "Matematica", "Verifica");
         let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
 
-        let sessions = generate_study_sessions(&test, today, 4);
+        let sessions =
+            generate_study_sessions(&test, today, 4, &HashSet::new(), &HashMap::new(), false);
 
         // Only 1 day away, no time for study sessions
         assert!(sessions.is_empty());
@@ -486,7 +731,8 @@ mod tests {
         let test = make_entry("compiti", "2025-01-10", "Matematica", "Verifica");
         let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
 
-        let sessions = generate_study_sessions(&test, today, 4);
+        let sessions =
+            generate_study_sessions(&test, today, 4, &HashSet::new(), &HashMap::new(), false);
 
         // Test is in the past
         assert!(sessions.is_empty());
@@ -498,7 +744,8 @@ mod tests {
         let test = make_entry("compiti", "2025-01-20", "Matematica", &long_task);
         let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
 
-        let sessions = generate_study_sessions(&test, today, 4);
+        let sessions =
+            generate_study_sessions(&test, today, 4, &HashSet::new(), &HashMap::new(), false);
 
         // Task should be truncated with "..."
         assert!(sessions[0].task.len() < 150);
@@ -510,8 +757,10 @@ mod tests {
         let test = make_entry("compiti", "2025-01-20", "Matematica", "Verifica");
         let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
 
-        let sessions1 = generate_study_sessions(&test, today, 4);
-        let sessions2 = generate_study_sessions(&test, today, 4);
+        let sessions1 =
+            generate_study_sessions(&test, today, 4, &HashSet::new(), &HashMap::new(), false);
+        let sessions2 =
+            generate_study_sessions(&test, today, 4, &HashSet::new(), &HashMap::new(), false);
 
         // IDs should be the same for the same test
         for (s1, s2) in sessions1.iter().zip(sessions2.iter()) {
@@ -519,23 +768,113 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_generated_ids_follow_the_parents_source_id() {
+        let test = make_entry("verifica", "2025-01-30", "Matematica", "Verifica");
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        // Deleted and imported again: a new id, the same source_id
+        let again = make_entry("verifica", "2025-01-30", "Matematica", "Verifica");
+        assert_ne!(test.id, again.id);
+
+        let sessions =
+            generate_study_sessions(&test, today, 4, &HashSet::new(), &HashMap::new(), false);
+        let sessions_again =
+            generate_study_sessions(&again, today, 4, &HashSet::new(), &HashMap::new(), false);
+        let ids: Vec<&str> = sessions.iter().map(|s| s.id.as_str()).collect();
+        let ids_again: Vec<&str> = sessions_again.iter().map(|s| s.id.as_str()).collect();
+        assert_eq!(ids, ids_again);
+        assert_eq!(
+            sessions_again[0].parent_id.as_deref(),
+            Some(again.id.as_str())
+        );
+
+        let homework = make_entry("compiti", "2025-01-20", "Storia", "Cap. 2");
+        let homework_again = make_entry("compiti", "2025-01-20", "Storia", "Cap. 2");
+        assert_eq!(
+            generate_work_reminder(&homework, today, &[1, 2, 3, 4, 5], 2)
+                .unwrap()
+                .id,
+            generate_work_reminder(&homework_again, today, &[1, 2, 3, 4, 5], 2)
+                .unwrap()
+                .id
+        );
+
+        // "Study for: Verifica" names a test but doesn't get sessions itself
+        assert!(is_test_or_quiz(&sessions[3]));
+        assert!(generate_study_sessions(
+            &sessions[3],
+            today,
+            4,
+            &HashSet::new(),
+            &HashMap::new(),
+            false
+        )
+        .is_empty());
+    }
+
     #[test]
     fn test_generate_study_sessions_invalid_date() {
         let test = make_entry("compiti", "invalid-date", "Matematica", "Verifica");
         let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
 
-        let sessions = generate_study_sessions(&test, today, 4);
+        let sessions =
+            generate_study_sessions(&test, today, 4, &HashSet::new(), &HashMap::new(), false);
 
         // Should return empty for invalid date
         assert!(sessions.is_empty());
     }
 
+    #[test]
+    fn test_generate_study_sessions_skips_holidays() {
+        let test = make_entry("compiti", "2025-01-20", "Matematica", "Verifica");
+        let today = NaiveDate::from_ymd_opt(2025, 1, 10).unwrap();
+        let holidays: HashSet<NaiveDate> = [17, 18]
+            .into_iter()
+            .map(|d| NaiveDate::from_ymd_opt(2025, 1, d).unwrap())
+            .collect();
+
+        let sessions = generate_study_sessions(&test, today, 4, &holidays, &HashMap::new(), false);
+        let dates: Vec<&str> = sessions.iter().map(|s| s.date.as_str()).collect();
+        assert_eq!(
+            dates,
+            vec!["2025-01-19", "2025-01-16", "2025-01-15", "2025-01-14"]
+        );
+
+        // Ids follow the session number, so they match the no-holiday ids
+        let plain =
+            generate_study_sessions(&test, today, 4, &HashSet::new(), &HashMap::new(), false);
+        assert_eq!(sessions[1].id, plain[1].id);
+    }
+
+    #[test]
+    fn test_generate_study_sessions_avoids_activity_days() {
+        let test = make_entry("compiti", "2025-01-20", "Matematica", "Verifica");
+        let today = NaiveDate::from_ymd_opt(2025, 1, 5).unwrap();
+        let day = |d| NaiveDate::from_ymd_opt(2025, 1, d).unwrap();
+        // Swimming on the 16th and 18th, a long lesson on the 19th
+        let activity_minutes: HashMap<NaiveDate, u32> =
+            [(day(16), 90), (day(18), 90), (day(19), 120)].into();
+
+        let sessions =
+            generate_study_sessions(&test, today, 3, &HashSet::new(), &activity_minutes, false);
+        let dates: Vec<&str> = sessions.iter().map(|s| s.date.as_str()).collect();
+        // Free days among the six nearest, nearest first
+        assert_eq!(dates, vec!["2025-01-17", "2025-01-15", "2025-01-14"]);
+
+        // With every nearby day busy, the lightest ones are used
+        let busy: HashMap<NaiveDate, u32> = (8..20).map(|d| (day(d), 60 + d)).collect();
+        let sessions = generate_study_sessions(&test, today, 3, &HashSet::new(), &busy, false);
+        let dates: Vec<&str> = sessions.iter().map(|s| s.date.as_str()).collect();
+        assert_eq!(dates, vec!["2025-01-16", "2025-01-15", "2025-01-14"]);
+    }
+
     #[test]
     fn test_study_session_is_generated() {
         let test = make_entry("compiti", "2025-01-20", "Matematica", "Verifica");
         let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
 
-        let sessions = generate_study_sessions(&test, today, 4);
+        let sessions =
+            generate_study_sessions(&test, today, 4, &HashSet::new(), &HashMap::new(), false);
 
         // All study sessions should be marked as generated
         for session in &sessions {
@@ -545,4 +884,76 @@ mod tests {
         // Original test is not generated
         assert!(!test.is_generated());
     }
+
+    // ========== study_topics tests ==========
+
+    #[test]
+    fn test_study_topics_one_chapter_per_session() {
+        let topics = study_topics("Verifica capitoli 4-6", 3).unwrap();
+        assert_eq!(
+            topics,
+            vec![
+                "Ripassa capitolo 4",
+                "Ripassa capitolo 5",
+                "Ripassa capitolo 6"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_study_topics_lists_and_spare_sessions() {
+        let topics = study_topics("Interrogazione cap. 2, 3 e 7", 4).unwrap();
+        assert_eq!(
+            topics,
+            vec![
+                "Ripassa capitolo 2",
+                "Ripassa capitolo 3",
+                "Ripassa capitolo 7",
+                "Ripassa tutto (capitoli 2, 3, 7)",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_study_topics_page_ranges_split_evenly() {
+        let topics = study_topics("Verifica pag. 100-109", 3).unwrap();
+        assert_eq!(
+            topics,
+            vec![
+                "Ripassa pagine 100-103",
+                "Ripassa pagine 104-106",
+                "Ripassa pagine 107-109",
+            ]
+        );
+    }
+
+    #[test]
+    fn test_study_topics_none_without_a_list() {
+        assert!(study_topics("Verifica sui limiti", 3).is_none());
+        assert!(study_topics("Verifica capitolo 4", 3).is_none());
+        assert!(study_topics("Verifica capitoli 6-4", 3).is_none());
+        assert!(study_topics("Verifica capitoli 4-6", 1).is_none());
+    }
+
+    #[test]
+    fn test_generate_study_sessions_split_topics() {
+        let test = make_entry("compiti", "2025-01-20", "Storia", "Verifica capitoli 4-6");
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+
+        let sessions =
+            generate_study_sessions(&test, today, 3, &HashSet::new(), &HashMap::new(), true);
+        // Sessions run nearest first, so the earliest one starts the list
+        assert_eq!(
+            sessions[2].task,
+            "Study for: Verifica capitoli 4-6 — Ripassa capitolo 4"
+        );
+        assert_eq!(
+            sessions[0].task,
+            "Study for: Verifica capitoli 4-6 — Ripassa capitolo 6"
+        );
+
+        let plain =
+            generate_study_sessions(&test, today, 3, &HashSet::new(), &HashMap::new(), false);
+        assert_eq!(plain[0].task, "Study for: Verifica capitoli 4-6");
+    }
 }
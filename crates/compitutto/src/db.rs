@@ -5,21 +5,86 @@
 //! - CRUD operations for homework entries
 //! - Study session management
 //! - Position management for drag-drop reordering
-
-use anyhow::{Context, Result};
-use rusqlite::{params, Connection, OptionalExtension};
-use std::path::Path;
+//!
+//! Everything returns a [`DbError`], so callers can tell a missing row or a
+//! clashing write from a broken database (`ApiError::from_db` turns them
+//! into 404, 409 and 500).
+
+use chrono::NaiveDate;
+use rusqlite::{params, Connection, OptionalExtension, Row};
+use serde::{de::DeserializeOwned, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::{Path, PathBuf};
 use tracing::{debug, info};
 
-use crate::types::HomeworkEntry;
+use crate::cors;
+use crate::crosslist;
+use crate::subjects;
+use crate::types::{
+    default_completion, Absence, AbsencesSnapshot, Activity, Attachment, Completion, Dashboard,
+    DashboardCounts, DayCompleted, DisciplineNote, EntryLink, GoalProgress, Holiday, HomeworkEntry,
+    JobRun, LinkRelation, NewAttachment, NotesSnapshot, NoticeStats, PendingImport, PeriodCounts,
+    PeriodStats, Reaction, SessionPosition, Settings, ShareLink, SubjectCount, SubjectMerge,
+    SubjectPeriodStats, SubjectResource, SubjectTimeStats, TextChange, TextFix, TextFixReport,
+    TopicStats, WeeklyGoal,
+};
+
+/// Why a database operation failed
+#[derive(Debug, thiserror::Error)]
+pub enum DbError {
+    /// The row the operation needed isn't there ("Entry", "Link", ...)
+    #[error("{0} not found")]
+    NotFound(String),
+    /// A UNIQUE, FOREIGN KEY or CHECK constraint refused the write
+    #[error(transparent)]
+    Constraint(rusqlite::Error),
+    #[error("Failed to apply migration {version}: {source}")]
+    Migration {
+        version: String,
+        source: rusqlite::Error,
+    },
+    /// The database file couldn't be opened
+    #[error("Failed to open {}: {source}", .path.display())]
+    Open {
+        path: PathBuf,
+        source: rusqlite::Error,
+    },
+    /// A migration couldn't be found or read
+    #[error("{}: {source}", .path.display())]
+    Io {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+    /// A setting that doesn't convert to or from its JSON form
+    #[error("Invalid setting value: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Sqlite(rusqlite::Error),
+}
+
+impl From<rusqlite::Error> for DbError {
+    fn from(error: rusqlite::Error) -> Self {
+        match error {
+            rusqlite::Error::SqliteFailure(e, _)
+                if e.code == rusqlite::ErrorCode::ConstraintViolation =>
+            {
+                DbError::Constraint(error)
+            }
+            error => DbError::Sqlite(error),
+        }
+    }
+}
+
+pub type Result<T, E = DbError> = std::result::Result<T, E>;
 
 /// Initialize the database at the given path, running any pending migrations
 pub fn init_db(db_path: &Path, migrations_dir: &Path) -> Result<Connection> {
-    let conn = Connection::open(db_path)
-        .with_context(|| format!("Failed to open database at {}", db_path.display()))?;
+    let conn = open_db(db_path)?;
 
-    // Enable foreign keys
-    conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+    // Enable foreign keys. WAL lets the web UI keep reading while an import
+    // writes; it's set here rather than in a migration because journal_mode
+    // can't change inside the transaction each migration runs in.
+    conn.execute_batch("PRAGMA foreign_keys = ON; PRAGMA journal_mode = WAL;")?;
 
     let count = run_migrations(&conn, migrations_dir)?;
     if count > 0 {
@@ -29,77 +94,96 @@ pub fn init_db(db_path: &Path, migrations_dir: &Path) -> Result<Connection> {
     Ok(conn)
 }
 
-/// Run pending migrations from the migrations directory
-pub fn run_migrations(conn: &Connection, migrations_dir: &Path) -> Result<usize> {
-    // First, ensure the schema_migrations table exists
-    // We need to check if any tables exist first
-    let tables_exist: bool = conn
-        .query_row(
-            "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='schema_migrations'",
-            [],
-            |row| row.get(0),
-        )
-        .unwrap_or(false);
-
-    if !tables_exist {
-        // No migrations table, so we need to run the initial migration
-        // But first check if it exists in the migrations dir
-    }
+/// Open the database without touching its schema
+pub fn open_db(db_path: &Path) -> Result<Connection> {
+    Connection::open(db_path).map_err(|source| DbError::Open {
+        path: db_path.to_path_buf(),
+        source,
+    })
+}
 
-    // Get list of migration files
+/// The `.sql` files in the migrations directory as (version, path), in the
+/// order they run
+fn migration_files(migrations_dir: &Path) -> Result<Vec<(String, PathBuf)>> {
     let mut migrations: Vec<_> = std::fs::read_dir(migrations_dir)
-        .with_context(|| {
-            format!(
-                "Failed to read migrations directory: {}",
-                migrations_dir.display()
-            )
+        .map_err(|source| DbError::Io {
+            path: migrations_dir.to_path_buf(),
+            source,
         })?
         .filter_map(|e| e.ok())
-        .filter(|e| {
-            e.path()
-                .extension()
-                .map(|ext| ext == "sql")
-                .unwrap_or(false)
-        })
         .map(|e| e.path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "sql"))
         .collect();
-
     migrations.sort();
 
-    let mut applied = 0;
+    migrations
+        .into_iter()
+        .map(|path| {
+            let version = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .ok_or_else(|| DbError::Io {
+                    path: path.clone(),
+                    source: std::io::Error::new(
+                        std::io::ErrorKind::InvalidData,
+                        "Invalid migration filename",
+                    ),
+                })?
+                .to_string();
+            Ok((version, path))
+        })
+        .collect()
+}
 
-    for migration_path in migrations {
-        let version = migration_path
-            .file_stem()
-            .and_then(|s| s.to_str())
-            .ok_or_else(|| anyhow::anyhow!("Invalid migration filename"))?
-            .to_string();
+/// Applied migrations and when (`applied_at`); empty on a new database
+fn applied_migrations(conn: &Connection) -> Result<BTreeMap<String, String>> {
+    let table_exists: bool = conn.query_row(
+        "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='schema_migrations'",
+        [],
+        |row| row.get(0),
+    )?;
+    if !table_exists {
+        return Ok(BTreeMap::new());
+    }
+    let mut stmt = conn.prepare("SELECT version, applied_at FROM schema_migrations")?;
+    let rows = stmt.query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
 
-        // Check if already applied
-        let already_applied: bool = conn
-            .query_row(
-                "SELECT COUNT(*) > 0 FROM schema_migrations WHERE version = ?1",
-                [&version],
-                |row| row.get(0),
-            )
-            .unwrap_or(false);
+/// Run pending migrations from the migrations directory.
+///
+/// Each migration runs in its own transaction together with its
+/// `schema_migrations` row, so one that fails halfway is rolled back whole
+/// and the next start retries it; the ones before it stay applied.
+pub fn run_migrations(conn: &Connection, migrations_dir: &Path) -> Result<usize> {
+    let migrations = migration_files(migrations_dir)?;
+    let already_applied = applied_migrations(conn)?;
+
+    let mut applied = 0;
 
-        if already_applied {
+    for (version, migration_path) in migrations {
+        if already_applied.contains_key(&version) {
             continue;
         }
 
-        // Read and execute migration
-        let sql = std::fs::read_to_string(&migration_path)
-            .with_context(|| format!("Failed to read migration: {}", migration_path.display()))?;
+        let sql = std::fs::read_to_string(&migration_path).map_err(|source| DbError::Io {
+            path: migration_path.clone(),
+            source,
+        })?;
 
-        conn.execute_batch(&sql)
-            .with_context(|| format!("Failed to apply migration: {}", version))?;
-
-        // Record migration
-        conn.execute(
+        let failed = |source| DbError::Migration {
+            version: version.clone(),
+            source,
+        };
+        // Dropped without commit() on failure, which rolls back
+        let tx = conn.unchecked_transaction().map_err(failed)?;
+        tx.execute_batch(&sql).map_err(failed)?;
+        tx.execute(
             "INSERT INTO schema_migrations (version, applied_at) VALUES (?1, datetime('now'))",
             [&version],
-        )?;
+        )
+        .map_err(failed)?;
+        tx.commit().map_err(failed)?;
 
         debug!(version = %version, "Applied migration");
         applied += 1;
@@ -108,6 +192,93 @@ pub fn run_migrations(conn: &Connection, migrations_dir: &Path) -> Result<usize>
     Ok(applied)
 }
 
+/// One migration in `compitutto migrate status`
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStatus {
+    pub version: String,
+    /// When it was applied (UTC, `YYYY-MM-DD HH:MM:SS`); `None` while pending
+    pub applied_at: Option<String>,
+    /// Whether its file is still in the migrations directory (a database
+    /// from a newer build can have versions this one doesn't ship)
+    pub on_disk: bool,
+}
+
+/// Every migration on disk or recorded in the database, in version order
+pub fn migration_status(conn: &Connection, migrations_dir: &Path) -> Result<Vec<MigrationStatus>> {
+    let mut applied = applied_migrations(conn)?;
+    let mut status: Vec<MigrationStatus> = migration_files(migrations_dir)?
+        .into_iter()
+        .map(|(version, _)| MigrationStatus {
+            applied_at: applied.remove(&version),
+            version,
+            on_disk: true,
+        })
+        .collect();
+    status.extend(
+        applied
+            .into_iter()
+            .map(|(version, applied_at)| MigrationStatus {
+                version,
+                applied_at: Some(applied_at),
+                on_disk: false,
+            }),
+    );
+    status.sort_by(|a, b| a.version.cmp(&b.version));
+    Ok(status)
+}
+
+/// Create an empty migration named `<YYYYMMDDHHMMSS>_<name>.sql` in
+/// `migrations_dir`, stamped with `now` (UTC). The name is lowercased and anything
+/// but letters and digits becomes `_`. Timestamped versions sort after the
+/// numbered ones (`024_...`) the repo started with.
+pub fn new_migration(
+    migrations_dir: &Path,
+    name: &str,
+    now: chrono::NaiveDateTime,
+) -> Result<PathBuf> {
+    let slug = name
+        .trim()
+        .to_lowercase()
+        .split(|c: char| !c.is_ascii_alphanumeric())
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("_");
+    let invalid = |message: &str| DbError::Io {
+        path: migrations_dir.to_path_buf(),
+        source: std::io::Error::new(std::io::ErrorKind::InvalidInput, message.to_string()),
+    };
+    if slug.is_empty() {
+        return Err(invalid("The migration name needs a letter or digit"));
+    }
+
+    let path = migrations_dir.join(format!("{}_{}.sql", now.format("%Y%m%d%H%M%S"), slug));
+    let io_error = |source| DbError::Io {
+        path: path.clone(),
+        source,
+    };
+    std::fs::create_dir_all(migrations_dir).map_err(io_error)?;
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(io_error)?;
+    let header = format!(
+        "-- {}\n--\n-- Runs once, in a transaction, on the next start. PRAGMAs that\n-- can't run in one (journal_mode) belong in db::init_db.\n",
+        name.trim()
+    );
+    std::io::Write::write_all(&mut file, header.as_bytes()).map_err(io_error)?;
+    Ok(path)
+}
+
+/// The newest applied migration (`015_activities`), `None` on an empty database
+pub fn get_schema_version(conn: &Connection) -> Result<Option<String>> {
+    Ok(
+        conn.query_row("SELECT MAX(version) FROM schema_migrations", [], |row| {
+            row.get(0)
+        })?,
+    )
+}
+
 /// Import multiple entries into the database, skipping duplicates based on source_id.
 /// Returns the number of entries actually inserted.
 pub fn import_entries(conn: &Connection, entries: &[HomeworkEntry]) -> Result<usize> {
@@ -120,117 +291,379 @@ pub fn import_entries(conn: &Connection, entries: &[HomeworkEntry]) -> Result<us
     Ok(count)
 }
 
+/// Columns selected for every entry query, in the order `row_to_entry` reads them.
+const ENTRY_COLUMNS: &str = "id, source_id, entry_type, date, subject, task, completed, position, \
+     parent_id, created_at, updated_at, estimated_minutes, actual_minutes, difficulty, pinned, color, \
+     grade, source_date, topic, end_date, assigned_at, teacher, visible_from, source_file";
+
+/// Map a row selected with `ENTRY_COLUMNS` to a `HomeworkEntry`
+fn row_to_entry(row: &Row) -> rusqlite::Result<HomeworkEntry> {
+    Ok(HomeworkEntry {
+        id: row.get(0)?,
+        source_id: row.get(1)?,
+        entry_type: row.get(2)?,
+        date: row.get(3)?,
+        subject: row.get(4)?,
+        task: row.get(5)?,
+        completed: row.get::<_, i32>(6)? != 0,
+        position: row.get(7)?,
+        parent_id: row.get(8)?,
+        created_at: row.get(9)?,
+        updated_at: row.get(10)?,
+        estimated_minutes: row.get(11)?,
+        actual_minutes: row.get(12)?,
+        difficulty: row.get(13)?,
+        pinned: row.get::<_, i32>(14)? != 0,
+        color: row.get(15)?,
+        grade: row.get(16)?,
+        source_date: row.get(17)?,
+        topic: row.get(18)?,
+        end_date: row.get(19)?,
+        assigned_at: row.get(20)?,
+        teacher: row.get(21)?,
+        visible_from: row.get(22)?,
+        source_file: row.get(23)?,
+    })
+}
+
 /// Get all entries from the database, sorted by date and position
 pub fn get_all_entries(conn: &Connection) -> Result<Vec<HomeworkEntry>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, source_id, entry_type, date, subject, task, completed, position, parent_id, created_at, updated_at
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
          FROM entries
-         ORDER BY date ASC, position ASC"
-    )?;
+         ORDER BY date ASC, pinned DESC, position ASC"
+    ))?;
 
     let entries = stmt
-        .query_map([], |row| {
-            Ok(HomeworkEntry {
-                id: row.get(0)?,
-                source_id: row.get(1)?,
-                entry_type: row.get(2)?,
-                date: row.get(3)?,
-                subject: row.get(4)?,
-                task: row.get(5)?,
-                completed: row.get::<_, i32>(6)? != 0,
-                position: row.get(7)?,
-                parent_id: row.get(8)?,
-                created_at: row.get(9)?,
-                updated_at: row.get(10)?,
-            })
-        })?
+        .query_map([], row_to_entry)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(entries)
+}
+
+/// Get entries whose date falls within `[from, to]` (inclusive, `YYYY-MM-DD`),
+/// sorted by date and position
+pub fn get_entries_in_range(conn: &Connection, from: &str, to: &str) -> Result<Vec<HomeworkEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
+         FROM entries
+         WHERE date >= ?1 AND date <= ?2
+         ORDER BY date ASC, pinned DESC, position ASC"
+    ))?;
+
+    let entries = stmt
+        .query_map(params![from, to], row_to_entry)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(entries)
+}
+
+/// Entries on any day from `from` to `to`, multi-day events that started
+/// earlier included, and cross-listed copies left out (for the calendar)
+pub fn get_entries_overlapping(
+    conn: &Connection,
+    from: &str,
+    to: &str,
+) -> Result<Vec<HomeworkEntry>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
+         FROM entries
+         WHERE date <= ?2 AND COALESCE(end_date, date) >= ?1
+           AND {NOT_CROSS_LISTED_COPY}
+         ORDER BY date ASC, pinned DESC, position ASC"
+    ))?;
+
+    let entries = stmt
+        .query_map(params![from, to], row_to_entry)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(entries)
+}
+
+/// Which entries `get_entries_filtered` returns. Every field is optional;
+/// `subjects`, `types` and `topics` match any of their values,
+/// case-insensitively.
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct EntryFilter {
+    /// First date, inclusive (`YYYY-MM-DD`)
+    pub from: Option<String>,
+    /// Last date, inclusive (`YYYY-MM-DD`)
+    pub to: Option<String>,
+    pub subjects: Vec<String>,
+    pub types: Vec<String>,
+    pub topics: Vec<String>,
+    /// Words that must all appear in the task or its attachments' OCR text
+    /// (prefix match, accents ignored)
+    pub q: Option<String>,
+    /// Only done (`true`) or only pending (`false`) entries
+    pub completed: Option<bool>,
+    /// Leave out entries hidden on this day (see `visible_on`)
+    pub visible_on: Option<String>,
+    /// Leave out cross-listed copies, so the task shows once, as its
+    /// primary (see `NOT_CROSS_LISTED_COPY`)
+    pub skip_cross_listed_copies: bool,
+}
+
+/// SQL condition for an `entries` row not being a cross-listed copy of
+/// another entry. Every view that shows a task once uses it.
+const NOT_CROSS_LISTED_COPY: &str = "NOT EXISTS (SELECT 1 FROM entry_links l
+     WHERE l.from_id = entries.id AND l.relation = 'cross_listed')";
+
+/// SQL condition for an `entries` row being visible on the day bound to
+/// `?{param}`: its `visible_from`, or else its parent's, isn't later
+fn visible_on(param: usize) -> String {
+    format!(
+        "COALESCE(visible_from, (SELECT p.visible_from FROM entries p WHERE p.id = entries.parent_id), '') <= ?{param}"
+    )
+}
+
+/// Whether `value` is `wanted`, trimmed and in any case. Done in Rust:
+/// SQLite's `LOWER()` only folds ASCII, so "ATTIVITÀ" wouldn't match
+/// "Attività".
+fn same_name(value: &str, wanted: &str) -> bool {
+    value.trim().to_lowercase() == wanted.trim().to_lowercase()
+}
+
+/// Whether `value` is one of `wanted` (see `same_name`); an empty list
+/// matches anything
+fn matches_any(value: Option<&str>, wanted: &[String]) -> bool {
+    wanted.is_empty() || value.is_some_and(|v| wanted.iter().any(|w| same_name(v, w)))
+}
+
+/// Entries matching `filter`, sorted by date and position
+pub fn get_entries_filtered(conn: &Connection, filter: &EntryFilter) -> Result<Vec<HomeworkEntry>> {
+    let mut clauses = Vec::new();
+    let mut params_vec: Vec<Box<dyn rusqlite::ToSql>> = Vec::new();
+
+    if let Some(from) = &filter.from {
+        params_vec.push(Box::new(from.clone()));
+        clauses.push(format!("date >= ?{}", params_vec.len()));
+    }
+    if let Some(to) = &filter.to {
+        params_vec.push(Box::new(to.clone()));
+        clauses.push(format!("date <= ?{}", params_vec.len()));
+    }
+    if let Some(completed) = filter.completed {
+        params_vec.push(Box::new(completed));
+        clauses.push(format!("completed = ?{}", params_vec.len()));
+    }
+
+    if let Some(day) = &filter.visible_on {
+        params_vec.push(Box::new(day.clone()));
+        clauses.push(visible_on(params_vec.len()));
+    }
+
+    if filter.skip_cross_listed_copies {
+        clauses.push(NOT_CROSS_LISTED_COPY.to_string());
+    }
+
+    if let Some(query) = filter.q.as_deref().and_then(fts_query) {
+        params_vec.push(Box::new(query));
+        clauses.push(format!(
+            "id IN (SELECT entry_id FROM entries_fts WHERE entries_fts MATCH ?{})",
+            params_vec.len()
+        ));
+    }
+
+    let where_clause = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
+         FROM entries
+         {where_clause}
+         ORDER BY date ASC, pinned DESC, position ASC"
+    ))?;
+    let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
+    let mut entries = stmt
+        .query_map(params_refs.as_slice(), row_to_entry)?
         .collect::<Result<Vec<_>, _>>()?;
+    // Subjects, types and topics are matched here, accents and all
+    entries.retain(|e| {
+        matches_any(Some(&e.subject), &filter.subjects)
+            && matches_any(Some(&e.entry_type), &filter.types)
+            && matches_any(e.topic.as_deref(), &filter.topics)
+    });
 
     Ok(entries)
 }
 
+/// Turn free text into an FTS5 query: every word quoted (so punctuation and
+/// operators are literal) and prefix-matched. `None` if there are no words.
+fn fts_query(text: &str) -> Option<String> {
+    let words: Vec<String> = text
+        .split_whitespace()
+        .map(|word| word.replace('"', ""))
+        .filter(|word| !word.is_empty())
+        .map(|word| format!("\"{}\"*", word))
+        .collect();
+    (!words.is_empty()).then(|| words.join(" "))
+}
+
 /// Get a single entry by ID
 pub fn get_entry(conn: &Connection, id: &str) -> Result<Option<HomeworkEntry>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, source_id, entry_type, date, subject, task, completed, position, parent_id, created_at, updated_at
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
          FROM entries
          WHERE id = ?1"
-    )?;
+    ))?;
 
-    let entry = stmt
-        .query_row([id], |row| {
-            Ok(HomeworkEntry {
-                id: row.get(0)?,
-                source_id: row.get(1)?,
-                entry_type: row.get(2)?,
-                date: row.get(3)?,
-                subject: row.get(4)?,
-                task: row.get(5)?,
-                completed: row.get::<_, i32>(6)? != 0,
-                position: row.get(7)?,
-                parent_id: row.get(8)?,
-                created_at: row.get(9)?,
-                updated_at: row.get(10)?,
-            })
-        })
-        .optional()?;
+    let entry = stmt.query_row([id], row_to_entry).optional()?;
 
     Ok(entry)
 }
 
 /// Insert a new entry into the database
 pub fn insert_entry(conn: &Connection, entry: &HomeworkEntry) -> Result<()> {
-    conn.execute(
-        "INSERT INTO entries (id, source_id, entry_type, date, subject, task, completed, position, parent_id, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
-        params![
-            entry.id,
-            entry.source_id,
-            entry.entry_type,
-            entry.date,
-            entry.subject,
-            entry.task,
-            entry.completed as i32,
-            entry.position,
-            entry.parent_id,
-            entry.created_at,
-            entry.updated_at,
-        ],
-    )?;
+    insert_entry_with(conn, "INSERT", entry)?;
     Ok(())
 }
 
-/// Insert an entry only if no entry with the same source_id already exists.
+/// Insert an entry unless one with the same id or source_id already exists.
 /// This allows entries to be moved to different dates while still being
 /// recognized as duplicates during future imports.
+///
+/// Relies on the primary key and the UNIQUE index on `source_id`, so two
+/// imports running at once can't both insert the same entry.
 pub fn insert_entry_if_not_exists(conn: &Connection, entry: &HomeworkEntry) -> Result<bool> {
-    // Check if an entry with this id already exists (covers generated entries
-    // whose id is deterministic, e.g. "lavoro_…" and "study_…" prefixes).
-    let id_exists: bool = conn.query_row(
-        "SELECT COUNT(*) > 0 FROM entries WHERE id = ?1",
-        [&entry.id],
-        |row| row.get(0),
-    )?;
-    if id_exists {
-        return Ok(false);
-    }
+    Ok(insert_entry_with(conn, "INSERT OR IGNORE", entry)? > 0)
+}
 
-    // Also check by source_id to catch re-imported export entries.
-    if let Some(ref source_id) = entry.source_id {
-        let exists: bool = conn.query_row(
-            "SELECT COUNT(*) > 0 FROM entries WHERE source_id = ?1",
-            [source_id],
-            |row| row.get(0),
+/// Insert the study sessions or reminder generated for `parent`, once per
+/// parent. Returns how many were inserted.
+///
+/// Generated ids are keyed on the parent's source_id
+/// (`data::generation_key`), so one that's already stored was generated for
+/// this parent, or for an earlier entry with the same source_id whose
+/// deletion left it without a parent; such an orphan is linked to `parent`
+/// again rather than generated a second time. Children stored under other
+/// ids (from before ids followed the source_id, or before the parent's task
+/// changed) count too: no more are added once the parent has as many of a
+/// type as were generated, and none on a day a sibling already takes.
+///
+/// New study sessions go where the `study_session_position` setting says
+/// (see `place_study_session`), whichever path generated them. A
+/// cross-listed copy (see `crosslist`) gets none: its primary has them.
+pub fn insert_generated(
+    conn: &Connection,
+    parent: &HomeworkEntry,
+    generated: &[HomeworkEntry],
+) -> Result<usize> {
+    if is_cross_listed_copy(conn, &parent.id)? {
+        return Ok(0);
+    }
+    let placement: SessionPosition = get_enum_setting(conn, "study_session_position")?;
+    for entry in generated {
+        conn.execute(
+            "UPDATE entries SET parent_id = ?2, updated_at = datetime('now')
+             WHERE id = ?1 AND parent_id IS NULL",
+            params![entry.id, parent.id],
         )?;
-        if exists {
-            return Ok(false);
+    }
+
+    let mut children = get_children(conn, &parent.id)?;
+    let mut inserted = 0;
+    for entry in generated {
+        let planned = generated
+            .iter()
+            .filter(|e| e.entry_type == entry.entry_type)
+            .count();
+        let siblings: Vec<&HomeworkEntry> = children
+            .iter()
+            .filter(|c| c.entry_type == entry.entry_type)
+            .collect();
+        if siblings.len() >= planned
+            || siblings
+                .iter()
+                .any(|c| c.id == entry.id || c.date == entry.date)
+        {
+            continue;
+        }
+        if insert_entry_if_not_exists(conn, entry)? {
+            if entry.entry_type == "studio" {
+                place_study_session(conn, entry, &parent.date, placement)?;
+            }
+            children.push(entry.clone());
+            inserted += 1;
         }
     }
+    Ok(inserted)
+}
 
-    // No duplicate found, insert the entry
-    conn.execute(
-        "INSERT INTO entries (id, source_id, entry_type, date, subject, task, completed, position, parent_id, created_at, updated_at)
-         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+/// Move a just-inserted study session for a test on `test_date` to where
+/// `placement` wants it. `Top` leaves it at the position it was generated
+/// with (0). Doesn't open a transaction, so callers may hold one.
+fn place_study_session(
+    conn: &Connection,
+    session: &HomeworkEntry,
+    test_date: &str,
+    placement: SessionPosition,
+) -> Result<()> {
+    let day: Vec<(String, String, Option<String>)> = match placement {
+        SessionPosition::Top => return Ok(()),
+        SessionPosition::Bottom => {
+            conn.execute(
+                "UPDATE entries SET position = (
+                     SELECT COALESCE(MAX(position), -1) + 1 FROM entries
+                     WHERE date = ?1 AND id != ?2
+                 )
+                 WHERE id = ?2",
+                params![session.date, session.id],
+            )?;
+            return Ok(());
+        }
+        SessionPosition::Priority => conn
+            .prepare(
+                "SELECT e.id, e.entry_type, p.date FROM entries e
+                 LEFT JOIN entries p ON p.id = e.parent_id
+                 WHERE e.date = ?1 AND e.id != ?2
+                 ORDER BY e.position ASC, e.created_at ASC",
+            )?
+            .query_map(params![session.date, session.id], |row| {
+                Ok((row.get(0)?, row.get(1)?, row.get(2)?))
+            })?
+            .collect::<rusqlite::Result<_>>()?,
+    };
+
+    let ranks: Vec<(&str, Option<&str>)> = day
+        .iter()
+        .map(|(_, entry_type, test)| (entry_type.as_str(), test.as_deref()))
+        .collect();
+    let mut ids: Vec<&str> = day.iter().map(|(id, _, _)| id.as_str()).collect();
+    ids.insert(priority_index(&ranks, test_date), &session.id);
+    let mut stmt = conn.prepare("UPDATE entries SET position = ?2 WHERE id = ?1")?;
+    for (position, id) in ids.iter().enumerate() {
+        stmt.execute(params![id, position as i32])?;
+    }
+    Ok(())
+}
+
+/// Where a study session for a test on `test_date` goes in a day's entries
+/// (type and, for study sessions, their test's date, in list order): after
+/// the homework and any session for a test no later than its own, before
+/// the tests, notes and sessions for later tests
+fn priority_index(day: &[(&str, Option<&str>)], test_date: &str) -> usize {
+    fn rank<'a>(entry_type: &str, test_date: Option<&'a str>) -> (u8, Option<&'a str>) {
+        match entry_type {
+            "compiti" | "lavoro" => (0, None),
+            "studio" => (1, test_date),
+            _ => (2, None),
+        }
+    }
+    let own = rank("studio", Some(test_date));
+    day.iter()
+        .position(|&(entry_type, test)| rank(entry_type, test) > own)
+        .unwrap_or(day.len())
+}
+
+fn insert_entry_with(conn: &Connection, verb: &str, entry: &HomeworkEntry) -> Result<usize> {
+    let inserted = conn.execute(
+        &format!(
+            "{verb} INTO entries ({ENTRY_COLUMNS})
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17, ?18, ?19, ?20, ?21, ?22, ?23, ?24)"
+        ),
         params![
             entry.id,
             entry.source_id,
@@ -243,9 +676,35 @@ pub fn insert_entry_if_not_exists(conn: &Connection, entry: &HomeworkEntry) -> R
             entry.parent_id,
             entry.created_at,
             entry.updated_at,
+            entry.estimated_minutes,
+            entry.actual_minutes,
+            entry.difficulty,
+            entry.pinned as i32,
+            entry.color,
+            entry.grade,
+            entry.source_date,
+            entry.topic,
+            entry.end_date,
+            entry.assigned_at,
+            entry.teacher,
+            entry.visible_from,
+            entry.source_file,
         ],
     )?;
-    Ok(true)
+    Ok(inserted)
+}
+
+/// Whether `err` is a UNIQUE / PRIMARY KEY constraint violation, rather
+/// than another constraint
+pub fn is_unique_violation(err: &DbError) -> bool {
+    matches!(
+        err,
+        DbError::Constraint(rusqlite::Error::SqliteFailure(e, _))
+            if matches!(
+                e.extended_code,
+                rusqlite::ffi::SQLITE_CONSTRAINT_UNIQUE | rusqlite::ffi::SQLITE_CONSTRAINT_PRIMARYKEY
+            )
+    )
 }
 
 /// Helper struct for partial entry updates
@@ -255,6 +714,17 @@ pub struct EntryUpdate {
     pub completed: Option<bool>,
     pub position: Option<i32>,
     pub task: Option<String>,
+    /// `Some(None)` clears the estimate, and the same for the two below
+    pub estimated_minutes: Option<Option<u32>>,
+    pub actual_minutes: Option<Option<u32>>,
+    pub difficulty: Option<Option<u8>>,
+    pub pinned: Option<bool>,
+    /// `Some(None)` clears the override
+    pub color: Option<Option<String>>,
+    /// `Some(None)` clears the grade
+    pub grade: Option<Option<String>>,
+    /// `Some(None)` shows the entry right away
+    pub visible_from: Option<Option<String>>,
 }
 
 /// Update an existing entry
@@ -278,6 +748,34 @@ pub fn update_entry(conn: &Connection, id: &str, updates: &EntryUpdate) -> Resul
         set_clauses.push("task = ?");
         params_vec.push(Box::new(task.clone()));
     }
+    if let Some(minutes) = updates.estimated_minutes {
+        set_clauses.push("estimated_minutes = ?");
+        params_vec.push(Box::new(minutes));
+    }
+    if let Some(minutes) = updates.actual_minutes {
+        set_clauses.push("actual_minutes = ?");
+        params_vec.push(Box::new(minutes));
+    }
+    if let Some(difficulty) = updates.difficulty {
+        set_clauses.push("difficulty = ?");
+        params_vec.push(Box::new(difficulty));
+    }
+    if let Some(pinned) = updates.pinned {
+        set_clauses.push("pinned = ?");
+        params_vec.push(Box::new(pinned as i32));
+    }
+    if let Some(ref color) = updates.color {
+        set_clauses.push("color = ?");
+        params_vec.push(Box::new(color.clone()));
+    }
+    if let Some(ref grade) = updates.grade {
+        set_clauses.push("grade = ?");
+        params_vec.push(Box::new(grade.clone()));
+    }
+    if let Some(ref visible_from) = updates.visible_from {
+        set_clauses.push("visible_from = ?");
+        params_vec.push(Box::new(visible_from.clone()));
+    }
 
     params_vec.push(Box::new(id.to_string()));
 
@@ -285,6 +783,25 @@ pub fn update_entry(conn: &Connection, id: &str, updates: &EntryUpdate) -> Resul
 
     let params_refs: Vec<&dyn rusqlite::ToSql> = params_vec.iter().map(|p| p.as_ref()).collect();
     let affected = conn.execute(&sql, params_refs.as_slice())?;
+    if affected > 0 {
+        if let Some(completed) = updates.completed {
+            // A cross-listed task is done under every subject at once,
+            // whichever of them it's ticked off under
+            conn.execute(
+                "WITH pair(id) AS (
+                     SELECT COALESCE((SELECT to_id FROM entry_links
+                                      WHERE from_id = ?1 AND relation = 'cross_listed'), ?1)
+                 )
+                 UPDATE entries SET completed = ?2, updated_at = datetime('now')
+                 WHERE id != ?1
+                   AND (id IN (SELECT id FROM pair)
+                        OR id IN (SELECT from_id FROM entry_links
+                                  WHERE relation = 'cross_listed'
+                                    AND to_id IN (SELECT id FROM pair)))",
+                params![id, completed as i32],
+            )?;
+        }
+    }
     Ok(affected > 0)
 }
 
@@ -296,34 +813,59 @@ pub fn delete_entry(conn: &Connection, id: &str) -> Result<bool> {
 
 /// Get all child entries (study sessions) for a parent entry
 pub fn get_children(conn: &Connection, parent_id: &str) -> Result<Vec<HomeworkEntry>> {
-    let mut stmt = conn.prepare(
-        "SELECT id, source_id, entry_type, date, subject, task, completed, position, parent_id, created_at, updated_at
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
          FROM entries
          WHERE parent_id = ?1
          ORDER BY date ASC"
-    )?;
+    ))?;
 
     let entries = stmt
-        .query_map([parent_id], |row| {
-            Ok(HomeworkEntry {
-                id: row.get(0)?,
-                source_id: row.get(1)?,
-                entry_type: row.get(2)?,
-                date: row.get(3)?,
-                subject: row.get(4)?,
-                task: row.get(5)?,
-                completed: row.get::<_, i32>(6)? != 0,
-                position: row.get(7)?,
-                parent_id: row.get(8)?,
-                created_at: row.get(9)?,
-                updated_at: row.get(10)?,
-            })
-        })?
+        .query_map([parent_id], row_to_entry)?
         .collect::<Result<Vec<_>, _>>()?;
 
     Ok(entries)
 }
 
+/// Move a test's incomplete study sessions by `days`, keeping their spacing
+/// relative to the test. Completed sessions stay where they were.
+/// Returns the number of sessions moved.
+pub fn shift_study_sessions(conn: &Connection, parent_id: &str, days: i64) -> Result<usize> {
+    if days == 0 {
+        return Ok(0);
+    }
+
+    let mut moved = 0;
+    for session in get_children(conn, parent_id)? {
+        if session.entry_type != "studio" || session.completed {
+            continue;
+        }
+        let Ok(date) = NaiveDate::parse_from_str(&session.date, "%Y-%m-%d") else {
+            continue;
+        };
+        let new_date = (date + chrono::Duration::days(days))
+            .format("%Y-%m-%d")
+            .to_string();
+        let source_id =
+            HomeworkEntry::generate_source_id(&new_date, &session.subject, &session.task);
+        // Another session may already own the new source_id (same subject and
+        // task on that day); keep the old one rather than break the UNIQUE index.
+        let updated = conn.execute(
+            "UPDATE OR IGNORE entries SET date = ?1, source_id = ?2, updated_at = datetime('now')
+             WHERE id = ?3",
+            params![new_date, source_id, session.id],
+        )?;
+        moved += match updated {
+            0 => conn.execute(
+                "UPDATE entries SET date = ?1, updated_at = datetime('now') WHERE id = ?2",
+                params![new_date, session.id],
+            )?,
+            n => n,
+        };
+    }
+    Ok(moved)
+}
+
 /// Delete an entry and all its children (cascade delete)
 pub fn delete_with_children(conn: &Connection, id: &str) -> Result<usize> {
     // First delete children
@@ -345,6 +887,105 @@ pub fn get_max_position_for_date(conn: &Connection, date: &str) -> Result<i32> {
     Ok(max.unwrap_or(-1))
 }
 
+/// Move an entry to `date` in one transaction.
+///
+/// With `index`, the entry goes before the entry currently at that place on
+/// the target day (past the end means last) and the day is renumbered from
+/// 0. Without it, the entry goes to the bottom and nothing else changes.
+/// Returns `false` if there is no such entry.
+pub fn move_entry(conn: &Connection, id: &str, date: &str, index: Option<usize>) -> Result<bool> {
+    let tx = conn.unchecked_transaction()?;
+    if !entry_exists(&tx, id)? {
+        return Ok(false);
+    }
+
+    match index {
+        None => {
+            let position: i32 = tx.query_row(
+                "SELECT COALESCE(MAX(position), -1) + 1 FROM entries WHERE date = ?1 AND id != ?2",
+                params![date, id],
+                |row| row.get(0),
+            )?;
+            tx.execute(
+                "UPDATE entries SET date = ?1, position = ?2, updated_at = datetime('now')
+                 WHERE id = ?3",
+                params![date, position, id],
+            )?;
+        }
+        Some(index) => {
+            let mut ids: Vec<String> = tx
+                .prepare(
+                    "SELECT id FROM entries WHERE date = ?1 AND id != ?2
+                     ORDER BY position ASC, created_at ASC",
+                )?
+                .query_map(params![date, id], |row| row.get(0))?
+                .collect::<Result<_, _>>()?;
+            ids.insert(index.min(ids.len()), id.to_string());
+            let mut stmt = tx.prepare(
+                "UPDATE entries SET date = ?1, position = ?2, updated_at = datetime('now')
+                 WHERE id = ?3",
+            )?;
+            for (position, entry_id) in ids.iter().enumerate() {
+                stmt.execute(params![date, position as i32, entry_id])?;
+            }
+        }
+    }
+
+    tx.commit()?;
+    Ok(true)
+}
+
+/// What [`compact_positions`] changed
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompactedPositions {
+    /// Days that were renumbered
+    pub days: usize,
+    /// Entries whose position changed
+    pub entries: usize,
+}
+
+/// Renumber every day's positions from 0 with no gaps or duplicates, in one
+/// transaction. The order stays as shown: by position, then (for
+/// duplicates) the order `move_entry` uses, creation time. Pinning isn't
+/// looked at, since it sorts ahead of position anyway. `updated_at` is left
+/// alone, as nothing visible changes.
+pub fn compact_positions(conn: &Connection) -> Result<CompactedPositions> {
+    let tx = conn.unchecked_transaction()?;
+    let rows: Vec<(String, String, i32)> = tx
+        .prepare(
+            "SELECT id, date, position FROM entries
+             ORDER BY date ASC, position ASC, created_at ASC, rowid ASC",
+        )?
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)))?
+        .collect::<Result<_, _>>()?;
+
+    let mut compacted = CompactedPositions::default();
+    let mut stmt = tx.prepare("UPDATE entries SET position = ?2 WHERE id = ?1")?;
+    let mut day: Option<&str> = None;
+    let mut next = 0;
+    let mut day_changed = false;
+    for (id, date, position) in &rows {
+        if day != Some(date.as_str()) {
+            day = Some(date);
+            next = 0;
+            day_changed = false;
+        }
+        if *position != next {
+            stmt.execute(params![id, next])?;
+            compacted.entries += 1;
+            if !day_changed {
+                compacted.days += 1;
+                day_changed = true;
+            }
+        }
+        next += 1;
+    }
+    drop(stmt);
+
+    tx.commit()?;
+    Ok(compacted)
+}
+
 /// Reorder entries for a specific date based on the provided ID order
 #[cfg(test)]
 pub fn reorder_entries(conn: &Connection, date: &str, entry_ids: &[&str]) -> Result<()> {
@@ -362,7 +1003,6 @@ pub fn reorder_entries(conn: &Connection, date: &str, entry_ids: &[&str]) -> Res
 }
 
 /// Check if an entry with the given ID exists
-#[cfg(test)]
 pub fn entry_exists(conn: &Connection, id: &str) -> Result<bool> {
     let exists: bool = conn.query_row(
         "SELECT COUNT(*) > 0 FROM entries WHERE id = ?1",
@@ -391,248 +1031,3668 @@ pub fn count_entries(conn: &Connection) -> Result<usize> {
     Ok(count as usize)
 }
 
-// ========== Settings ==========
+/// `(total, completed)` over all entries
+pub fn count_completion(conn: &Connection) -> Result<(usize, usize)> {
+    let (total, completed): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(completed), 0) FROM entries",
+        [],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    Ok((total as usize, completed as usize))
+}
 
-/// Get the list of allowed work-day weekday numbers (1=Mon … 5=Fri).
-/// Weekends (0=Sun, 6=Sat) are always allowed and are not stored here.
-/// Returns the stored list, or the default [1,2,3,4,5] if nothing is stored.
-pub fn get_work_days(conn: &Connection) -> Result<Vec<u32>> {
-    let result: Option<String> = conn
-        .query_row(
-            "SELECT value FROM settings WHERE key = 'work_days'",
-            [],
-            |row| row.get(0),
-        )
-        .optional()?;
+/// Pending entries hidden on `today` by their own `visible_from`, for the
+/// "hidden until later" counts (their study sessions don't add to it)
+pub fn count_hidden(conn: &Connection, today: &str) -> Result<usize> {
+    let count: i64 = conn.query_row(
+        &format!(
+            "SELECT COUNT(*) FROM entries
+             WHERE completed = 0 AND visible_from > ?1 AND {NOT_CROSS_LISTED_COPY}"
+        ),
+        [today],
+        |row| row.get(0),
+    )?;
+    Ok(count as usize)
+}
 
-    match result {
-        Some(json) => {
-            let days: Vec<u32> =
-                serde_json::from_str(&json).unwrap_or_else(|_| vec![1, 2, 3, 4, 5]);
-            Ok(days)
-        }
-        None => Ok(vec![1, 2, 3, 4, 5]),
-    }
+/// Earliest and latest entry dates, or `None` when there are no entries
+pub fn get_date_bounds(conn: &Connection) -> Result<Option<(String, String)>> {
+    let bounds: (Option<String>, Option<String>) =
+        conn.query_row("SELECT MIN(date), MAX(date) FROM entries", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })?;
+    Ok(match bounds {
+        (Some(min), Some(max)) => Some((min, max)),
+        _ => None,
+    })
 }
 
-/// Save the list of allowed work-day weekday numbers.
-/// Only weekdays (1–5) are meaningful; weekends are always allowed.
-pub fn set_work_days(conn: &Connection, days: &[u32]) -> Result<()> {
-    let mut days: Vec<u32> = days
+// ========== Pending imports ==========
+
+/// Stage parsed export entries for review instead of importing them directly.
+///
+/// Entries already in `entries` or already staged (pending or rejected) are
+/// skipped by `source_id`. A new row is staged as a change to an existing
+/// entry with the same date, subject and type only when that entry's own
+/// row is no longer in `entries` (the task was edited in the register);
+/// otherwise it's a new entry next to it. Returns the number of newly
+/// staged entries.
+pub fn stage_imports(conn: &Connection, entries: &[HomeworkEntry]) -> Result<usize> {
+    let exported: HashSet<&str> = entries
         .iter()
-        .copied()
-        .filter(|&d| (1..=5).contains(&d))
+        .filter_map(|e| e.source_id.as_deref())
         .collect();
-    days.sort();
-    days.dedup();
-    let json = serde_json::to_string(&days)?;
-    conn.execute(
-        "INSERT INTO settings (key, value) VALUES ('work_days', ?1)
-         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
-        params![json],
+    let mut replaced = HashSet::new();
+    let mut candidates = conn.prepare(
+        "SELECT id, source_id FROM entries
+         WHERE date = ?1 AND subject = ?2 AND entry_type = ?3 AND parent_id IS NULL
+         ORDER BY position ASC",
     )?;
-    Ok(())
-}
+    let mut staged = 0;
+    for entry in entries {
+        let Some(ref source_id) = entry.source_id else {
+            continue;
+        };
 
-/// Get how many days ahead of the due date to place the work reminder (1 or 2).
-/// Default: 2.
-pub fn get_homework_days_ahead(conn: &Connection) -> Result<u32> {
-    let result: Option<String> = conn
-        .query_row(
-            "SELECT value FROM settings WHERE key = 'homework_days_ahead'",
-            [],
-            |row| row.get(0),
-        )
-        .optional()?;
-    let v = result.and_then(|s| s.parse::<u32>().ok()).unwrap_or(2);
-    Ok(v.clamp(1, 2))
+        if is_known_source_id(conn, source_id)? {
+            continue;
+        }
+
+        let mut replaces_id = None;
+        let mut rows = candidates.query(params![entry.date, entry.subject, entry.entry_type])?;
+        while let Some(row) = rows.next()? {
+            let id: String = row.get(0)?;
+            let own: Option<String> = row.get(1)?;
+            if !own.as_deref().is_some_and(|s| exported.contains(s)) && !replaced.contains(&id) {
+                replaces_id = Some(id);
+                break;
+            }
+        }
+        drop(rows);
+        if let Some(id) = &replaces_id {
+            replaced.insert(id.clone());
+        }
+
+        conn.execute(
+            "INSERT INTO pending_imports
+                 (id, source_id, entry_type, date, subject, task, replaces_id, created_at)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                entry.id,
+                source_id,
+                entry.entry_type,
+                entry.date,
+                entry.subject,
+                entry.task,
+                replaces_id,
+                entry.created_at,
+            ],
+        )?;
+        staged += 1;
+    }
+    Ok(staged)
 }
 
-pub fn set_homework_days_ahead(conn: &Connection, days: u32) -> Result<()> {
-    let days = days.clamp(1, 2);
+/// Stage a draft made from a photo of the diary page (see `capture`), with
+/// the photo as its attachment. Never a change to an existing entry: the
+/// draft's date and subject are only guesses. Returns the attachment's id.
+pub fn stage_capture(
+    conn: &Connection,
+    draft: &HomeworkEntry,
+    photo: &NewAttachment,
+) -> Result<i64> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute(
+        "INSERT INTO pending_imports
+             (id, source_id, entry_type, date, subject, task, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+        params![
+            draft.id,
+            photo.source_id,
+            draft.entry_type,
+            draft.date,
+            draft.subject,
+            draft.task,
+            draft.created_at,
+        ],
+    )?;
+    record_attachments(&tx, std::slice::from_ref(photo))?;
+    let id = tx.last_insert_rowid();
+    tx.commit()?;
+    Ok(id)
+}
+
+/// Whether a parsed entry has been seen before: imported, or staged
+/// (pending or rejected)
+pub fn is_known_source_id(conn: &Connection, source_id: &str) -> Result<bool> {
+    Ok(conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM entries WHERE source_id = ?1)
+             OR EXISTS(SELECT 1 FROM pending_imports WHERE source_id = ?1)",
+        [source_id],
+        |row| row.get(0),
+    )?)
+}
+
+/// Remember the export date of already stored entries that don't have one
+/// yet, matched by `source_id`. Moved entries keep theirs. Returns how many
+/// were filled in.
+pub fn record_source_dates(conn: &Connection, entries: &[HomeworkEntry]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut updated = 0;
+    {
+        let mut stmt = tx.prepare(
+            "UPDATE entries SET source_date = ?2 WHERE source_id = ?1 AND source_date IS NULL",
+        )?;
+        for entry in entries {
+            if let Some(source_id) = &entry.source_id {
+                updated += stmt.execute(params![source_id, entry.date])?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(updated)
+}
+
+/// Remember the last day of already stored multi-day events, matched by
+/// `source_id`: filled in where missing, updated where the register moved
+/// it. Returns how many changed.
+pub fn record_end_dates(conn: &Connection, entries: &[HomeworkEntry]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut updated = 0;
+    {
+        let mut stmt = tx.prepare(
+            "UPDATE entries SET end_date = ?2 WHERE source_id = ?1 AND end_date IS NOT ?2",
+        )?;
+        for entry in entries {
+            if let (Some(source_id), Some(end_date)) = (&entry.source_id, &entry.end_date) {
+                updated += stmt.execute(params![source_id, end_date])?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(updated)
+}
+
+/// Remember when and by whom already stored entries were posted, for those
+/// that don't know yet, matched by `source_id`. Returns how many were
+/// filled in.
+pub fn record_assignments(conn: &Connection, entries: &[HomeworkEntry]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut updated = 0;
+    {
+        let mut stmt = tx.prepare(
+            "UPDATE entries SET assigned_at = ?2, teacher = COALESCE(teacher, ?3)
+             WHERE source_id = ?1 AND assigned_at IS NULL",
+        )?;
+        for entry in entries {
+            if let (Some(source_id), Some(assigned_at)) = (&entry.source_id, &entry.assigned_at) {
+                updated += stmt.execute(params![source_id, assigned_at, entry.teacher])?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(updated)
+}
+
+/// Remember which export file already stored entries came from, for those
+/// that don't know yet, matched by `source_id`. Files are scanned oldest
+/// first, so an entry keeps the first export that had it. Returns how many
+/// were filled in.
+pub fn record_source_files(conn: &Connection, entries: &[HomeworkEntry]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let mut updated = 0;
+    {
+        let mut stmt = tx.prepare(
+            "UPDATE entries SET source_file = ?2 WHERE source_id = ?1 AND source_file IS NULL",
+        )?;
+        for entry in entries {
+            if let (Some(source_id), Some(file)) = (&entry.source_id, &entry.source_file) {
+                updated += stmt.execute(params![source_id, file])?;
+            }
+        }
+    }
+    tx.commit()?;
+    Ok(updated)
+}
+
+/// How many entries came from each export file
+pub fn count_entries_by_source_file(conn: &Connection) -> Result<BTreeMap<String, usize>> {
+    let mut stmt = conn.prepare(
+        "SELECT source_file, COUNT(*) FROM entries
+         WHERE source_file IS NOT NULL
+         GROUP BY source_file",
+    )?;
+    let rows = stmt.query_map([], |row| {
+        Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)? as usize))
+    })?;
+    Ok(rows.collect::<rusqlite::Result<_>>()?)
+}
+
+/// Give entries without a topic the one their task starts with, if any
+/// (`subjects::detect_topic`). Generated entries take their parent's.
+/// Returns how many were filled in.
+pub fn fill_topics(conn: &Connection) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    let found: Vec<(String, String)> = {
+        let mut stmt = tx.prepare(
+            "SELECT id, task, subject FROM entries
+             WHERE topic IS NULL AND parent_id IS NULL AND task LIKE '%:%'",
+        )?;
+        let rows = stmt.query_map([], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, String>(1)?,
+                row.get::<_, String>(2)?,
+            ))
+        })?;
+        rows.filter_map(|row| {
+            let (id, task, subject) = row.ok()?;
+            Some((id, subjects::detect_topic(&task, &subject)?))
+        })
+        .collect()
+    };
+    for (id, topic) in &found {
+        tx.execute(
+            "UPDATE entries SET topic = ?2 WHERE id = ?1 OR parent_id = ?1",
+            params![id, topic],
+        )?;
+    }
+    tx.commit()?;
+    Ok(found.len())
+}
+
+/// Get all staged imports awaiting review, sorted by date and subject
+pub fn get_pending_imports(conn: &Connection) -> Result<Vec<PendingImport>> {
+    let mut stmt = conn.prepare(
+        "SELECT p.id, p.source_id, p.entry_type, p.date, p.subject, p.task,
+                p.replaces_id, e.task, p.created_at
+         FROM pending_imports p
+         LEFT JOIN entries e ON e.id = p.replaces_id
+         WHERE p.status = 'pending'
+         ORDER BY p.date ASC, p.subject ASC",
+    )?;
+
+    let pending = stmt
+        .query_map([], |row| {
+            Ok(PendingImport {
+                id: row.get(0)?,
+                source_id: row.get(1)?,
+                entry_type: row.get(2)?,
+                date: row.get(3)?,
+                subject: row.get(4)?,
+                task: row.get(5)?,
+                replaces_id: row.get(6)?,
+                previous_task: row.get(7)?,
+                created_at: row.get(8)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(pending)
+}
+
+pub fn count_pending_imports(conn: &Connection) -> Result<usize> {
+    let count: i64 = conn.query_row(
+        "SELECT COUNT(*) FROM pending_imports WHERE status = 'pending'",
+        [],
+        |row| row.get(0),
+    )?;
+    Ok(count as usize)
+}
+
+/// Accept a staged import: apply it to `entries` and drop it from staging.
+///
+/// A change updates the replaced entry's task and `source_id` in place (so
+/// completion state and children survive) and forgets its `source_file`,
+/// which has the old text; the next scan records the export with the new
+/// one. If that entry is gone, or the import is new, a fresh entry is
+/// inserted at the end of its day.
+/// Returns the resulting entry, or `None` if nothing is pending with that id.
+pub fn accept_pending_import(conn: &Connection, id: &str) -> Result<Option<HomeworkEntry>> {
+    let pending = match get_pending_imports(conn)?.into_iter().find(|p| p.id == id) {
+        Some(p) => p,
+        None => return Ok(None),
+    };
+
+    let replaced = match pending.replaces_id.as_deref() {
+        Some(replaces_id) => {
+            conn.execute(
+                "UPDATE entries
+                 SET task = ?1, source_id = ?2, source_file = NULL, updated_at = datetime('now')
+                 WHERE id = ?3",
+                params![pending.task, pending.source_id, replaces_id],
+            )? > 0
+        }
+        None => false,
+    };
+
+    let entry_id = if replaced {
+        pending.replaces_id.clone().unwrap_or_default()
+    } else {
+        let mut entry = HomeworkEntry::new(
+            pending.entry_type.clone(),
+            pending.date.clone(),
+            pending.subject.clone(),
+            pending.task.clone(),
+        );
+        entry.id = pending.id.clone();
+        entry.source_id = Some(pending.source_id.clone());
+        entry.source_date = Some(pending.date.clone());
+        entry.topic = subjects::detect_topic(&entry.task, &entry.subject);
+        entry.position = get_max_position_for_date(conn, &entry.date)? + 1;
+        insert_entry(conn, &entry)?;
+        entry.id
+    };
+
+    conn.execute("DELETE FROM pending_imports WHERE id = ?1", [id])?;
+    get_entry(conn, &entry_id)
+}
+
+/// Reject a staged import. It stays recorded so re-imports don't stage it again.
+pub fn reject_pending_import(conn: &Connection, id: &str) -> Result<bool> {
+    let affected = conn.execute(
+        "UPDATE pending_imports SET status = 'rejected' WHERE id = ?1 AND status = 'pending'",
+        [id],
+    )?;
+    Ok(affected > 0)
+}
+
+// ========== Holidays ==========
+
+/// Replace all holidays with `holidays`. Returns how many were stored.
+pub fn replace_holidays(conn: &Connection, holidays: &[Holiday]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM holidays", [])?;
+    {
+        let mut stmt = tx.prepare("INSERT OR IGNORE INTO holidays (date, name) VALUES (?1, ?2)")?;
+        for holiday in holidays {
+            stmt.execute(params![holiday.date, holiday.name])?;
+        }
+    }
+    tx.commit()?;
+    let count: i64 = conn.query_row("SELECT COUNT(*) FROM holidays", [], |row| row.get(0))?;
+    Ok(count as usize)
+}
+
+/// All holidays, sorted by date
+pub fn get_holidays(conn: &Connection) -> Result<Vec<Holiday>> {
+    get_holidays_in_range(conn, "0000-01-01", "9999-12-31")
+}
+
+/// Holidays between `from` and `to` inclusive (`YYYY-MM-DD`), sorted by date
+pub fn get_holidays_in_range(conn: &Connection, from: &str, to: &str) -> Result<Vec<Holiday>> {
+    let mut stmt = conn
+        .prepare("SELECT date, name FROM holidays WHERE date >= ?1 AND date <= ?2 ORDER BY date")?;
+    let holidays = stmt
+        .query_map([from, to], |row| {
+            Ok(Holiday {
+                date: row.get(0)?,
+                name: row.get(1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(holidays)
+}
+
+/// All holiday dates, for scheduling
+pub fn get_holiday_dates(conn: &Connection) -> Result<HashSet<NaiveDate>> {
+    let mut stmt = conn.prepare("SELECT date FROM holidays")?;
+    let dates = stmt
+        .query_map([], |row| row.get::<_, String>(0))?
+        .filter_map(|date| {
+            date.ok()
+                .and_then(|d| NaiveDate::parse_from_str(&d, "%Y-%m-%d").ok())
+        })
+        .collect();
+    Ok(dates)
+}
+
+// ========== Activities ==========
+
+/// Replace all activities with `activities`. Returns how many were stored.
+pub fn replace_activities(conn: &Connection, activities: &[Activity]) -> Result<usize> {
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM activities", [])?;
+    {
+        let mut stmt = tx.prepare(
+            "INSERT INTO activities (date, name, start_time, end_time, minutes)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+        )?;
+        for activity in activities {
+            stmt.execute(params![
+                activity.date,
+                activity.name,
+                activity.start_time,
+                activity.end_time,
+                activity.minutes,
+            ])?;
+        }
+    }
+    tx.commit()?;
+    Ok(activities.len())
+}
+
+/// Activities between `from` and `to` inclusive (`YYYY-MM-DD`), sorted by
+/// date and start time (all-day ones first)
+pub fn get_activities_in_range(conn: &Connection, from: &str, to: &str) -> Result<Vec<Activity>> {
+    let mut stmt = conn.prepare(
+        "SELECT date, name, start_time, end_time, minutes FROM activities
+         WHERE date >= ?1 AND date <= ?2
+         ORDER BY date, start_time, id",
+    )?;
+    let activities = stmt
+        .query_map([from, to], |row| {
+            Ok(Activity {
+                date: row.get(0)?,
+                name: row.get(1)?,
+                start_time: row.get(2)?,
+                end_time: row.get(3)?,
+                minutes: row.get(4)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(activities)
+}
+
+/// Total activity minutes per day, for scheduling
+pub fn get_activity_minutes(conn: &Connection) -> Result<HashMap<NaiveDate, u32>> {
+    let mut stmt = conn.prepare("SELECT date, SUM(minutes) FROM activities GROUP BY date")?;
+    let minutes = stmt
+        .query_map([], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, u32>(1)?))
+        })?
+        .filter_map(|row| {
+            let (date, minutes) = row.ok()?;
+            Some((NaiveDate::parse_from_str(&date, "%Y-%m-%d").ok()?, minutes))
+        })
+        .collect();
+    Ok(minutes)
+}
+
+/// The iCal URL the server re-fetches activities from, if any
+pub fn get_activities_url(conn: &Connection) -> Result<Option<String>> {
+    Ok(get_setting(conn, "activities_url")?.filter(|url| !url.is_empty()))
+}
+
+/// Subscribe to `url`, or unsubscribe with `None`
+pub fn set_activities_url(conn: &Connection, url: Option<&str>) -> Result<()> {
+    set_setting(conn, "activities_url", url.unwrap_or(""))
+}
+
+// ========== Reactions ==========
+
+const REACTION_COLUMNS: &str = "id, entry_id, emoji, message, author, created_at";
+
+fn row_to_reaction(row: &Row) -> rusqlite::Result<Reaction> {
+    Ok(Reaction {
+        id: row.get(0)?,
+        entry_id: row.get(1)?,
+        emoji: row.get(2)?,
+        message: row.get(3)?,
+        author: row.get(4)?,
+        created_at: row.get(5)?,
+    })
+}
+
+/// Add a reaction to an entry. Validation is up to the caller.
+pub fn add_reaction(
+    conn: &Connection,
+    entry_id: &str,
+    emoji: Option<&str>,
+    message: Option<&str>,
+    author: Option<&str>,
+) -> Result<Reaction> {
     conn.execute(
-        "INSERT INTO settings (key, value) VALUES ('homework_days_ahead', ?1)
-         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
-        params![days.to_string()],
+        "INSERT INTO reactions (entry_id, emoji, message, author, created_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            entry_id,
+            emoji,
+            message,
+            author,
+            chrono::Utc::now().to_rfc3339()
+        ],
     )?;
-    Ok(())
+    let id = conn.last_insert_rowid();
+    conn.query_row(
+        &format!("SELECT {REACTION_COLUMNS} FROM reactions WHERE id = ?1"),
+        [id],
+        row_to_reaction,
+    )
+    .optional()?
+    .ok_or_else(|| DbError::NotFound("Reaction".to_string()))
 }
 
-/// Get how many study-session days to generate before a verifica (minimum 3).
-/// Default: 4.
-pub fn get_study_days_before(conn: &Connection) -> Result<u32> {
-    let result: Option<String> = conn
-        .query_row(
-            "SELECT value FROM settings WHERE key = 'study_days_before'",
-            [],
-            |row| row.get(0),
-        )
-        .optional()?;
-    let v = result.and_then(|s| s.parse::<u32>().ok()).unwrap_or(4);
-    Ok(v.max(3))
+/// Every reaction, oldest first
+pub fn get_reactions(conn: &Connection) -> Result<Vec<Reaction>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {REACTION_COLUMNS} FROM reactions ORDER BY id"
+    ))?;
+    let reactions = stmt
+        .query_map([], row_to_reaction)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(reactions)
 }
 
-pub fn set_study_days_before(conn: &Connection, days: u32) -> Result<()> {
-    let days = days.max(3);
+/// One entry's reactions, oldest first
+pub fn get_reactions_for_entry(conn: &Connection, entry_id: &str) -> Result<Vec<Reaction>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {REACTION_COLUMNS} FROM reactions WHERE entry_id = ?1 ORDER BY id"
+    ))?;
+    let reactions = stmt
+        .query_map([entry_id], row_to_reaction)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(reactions)
+}
+
+pub fn delete_reaction(conn: &Connection, id: i64) -> Result<bool> {
+    let affected = conn.execute("DELETE FROM reactions WHERE id = ?1", [id])?;
+    Ok(affected > 0)
+}
+
+// ========== Entry links ==========
+
+const LINK_COLUMNS: &str = "id, from_id, to_id, relation, created_at";
+
+fn row_to_link(row: &Row) -> rusqlite::Result<EntryLink> {
+    let relation: String = row.get(3)?;
+    Ok(EntryLink {
+        id: row.get(0)?,
+        from_id: row.get(1)?,
+        to_id: row.get(2)?,
+        relation: match relation.as_str() {
+            "related" => LinkRelation::Related,
+            "cross_listed" => LinkRelation::CrossListed,
+            _ => LinkRelation::DependsOn,
+        },
+        created_at: row.get(4)?,
+    })
+}
+
+/// Link `from_id` to `to_id`. `related` links are stored with the smaller id
+/// first, so linking A to B and B to A is the same link. A duplicate link is
+/// a UNIQUE violation (see `is_unique_violation`).
+pub fn create_link(
+    conn: &Connection,
+    from_id: &str,
+    to_id: &str,
+    relation: LinkRelation,
+) -> Result<EntryLink> {
+    let (from_id, to_id) = match relation {
+        LinkRelation::Related if to_id < from_id => (to_id, from_id),
+        _ => (from_id, to_id),
+    };
     conn.execute(
-        "INSERT INTO settings (key, value) VALUES ('study_days_before', ?1)
-         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
-        params![days.to_string()],
+        "INSERT INTO entry_links (from_id, to_id, relation, created_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            from_id,
+            to_id,
+            relation.as_str(),
+            chrono::Utc::now().to_rfc3339()
+        ],
     )?;
-    Ok(())
+    get_link(conn, conn.last_insert_rowid())?.ok_or_else(|| DbError::NotFound("Link".to_string()))
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use tempfile::TempDir;
+pub fn get_link(conn: &Connection, id: i64) -> Result<Option<EntryLink>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT {LINK_COLUMNS} FROM entry_links WHERE id = ?1"),
+            [id],
+            row_to_link,
+        )
+        .optional()?)
+}
 
-    fn setup_test_db() -> (TempDir, Connection) {
-        let temp_dir = TempDir::new().unwrap();
-        let db_path = temp_dir.path().join("test.db");
-        let migrations_dir = temp_dir.path().join("migrations");
-        std::fs::create_dir(&migrations_dir).unwrap();
+/// All links, oldest first
+pub fn get_links(conn: &Connection) -> Result<Vec<EntryLink>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {LINK_COLUMNS} FROM entry_links ORDER BY id"
+    ))?;
+    let links = stmt
+        .query_map([], row_to_link)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(links)
+}
 
-        // Create the initial migration
-        std::fs::write(
-            migrations_dir.join("001_initial_schema.sql"),
-            include_str!("../db/migrations/001_initial_schema.sql"),
+/// Links with `id` on either side, oldest first
+pub fn get_links_for_entry(conn: &Connection, id: &str) -> Result<Vec<EntryLink>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {LINK_COLUMNS} FROM entry_links WHERE from_id = ?1 OR to_id = ?1 ORDER BY id"
+    ))?;
+    let links = stmt
+        .query_map([id], row_to_link)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(links)
+}
+
+pub fn delete_link(conn: &Connection, id: i64) -> Result<bool> {
+    let affected = conn.execute("DELETE FROM entry_links WHERE id = ?1", [id])?;
+    Ok(affected > 0)
+}
+
+/// Whether `id` is a cross-listed copy of another entry
+pub fn is_cross_listed_copy(conn: &Connection, id: &str) -> Result<bool> {
+    Ok(conn
+        .query_row(
+            "SELECT 1 FROM entry_links WHERE from_id = ?1 AND relation = 'cross_listed'",
+            [id],
+            |_| Ok(()),
         )
-        .unwrap();
+        .optional()?
+        .is_some())
+}
 
-        let conn = init_db(&db_path, &migrations_dir).unwrap();
-        (temp_dir, conn)
+/// Why `copy_id` can't become a cross-listed copy of `primary_id`, if it
+/// can't: a copy has one primary, and a primary isn't a copy itself (which
+/// also rules out linking a pair both ways)
+pub fn cross_list_conflict(
+    conn: &Connection,
+    copy_id: &str,
+    primary_id: &str,
+) -> Result<Option<&'static str>> {
+    if is_cross_listed_copy(conn, copy_id)? {
+        return Ok(Some("This entry is already a copy of another"));
+    }
+    if is_cross_listed_copy(conn, primary_id)? {
+        return Ok(Some("That entry is a copy itself; link to its primary"));
     }
+    let has_copies: bool = conn.query_row(
+        "SELECT EXISTS(SELECT 1 FROM entry_links WHERE to_id = ?1 AND relation = 'cross_listed')",
+        [copy_id],
+        |row| row.get(0),
+    )?;
+    Ok(has_copies.then_some("This entry has copies of its own"))
+}
 
-    fn make_entry(entry_type: &str, date: &str, subject: &str, task: &str) -> HomeworkEntry {
-        HomeworkEntry::new(
-            entry_type.to_string(),
-            date.to_string(),
-            subject.to_string(),
-            task.to_string(),
-        )
+/// Mark `copy_id` as the same task as `primary_id`: link them and drop the
+/// copy's study sessions and reminders that aren't done yet, since the
+/// primary has its own. Takes the primary's completed state. Check
+/// `cross_list_conflict` first.
+pub fn cross_list(conn: &Connection, copy_id: &str, primary_id: &str) -> Result<EntryLink> {
+    let tx = conn.unchecked_transaction()?;
+    let link = create_link(&tx, copy_id, primary_id, LinkRelation::CrossListed)?;
+    let dropped = tx.execute(
+        "DELETE FROM entries WHERE parent_id = ?1 AND completed = 0",
+        [copy_id],
+    )?;
+    if dropped > 0 {
+        info!(
+            copy = copy_id,
+            primary = primary_id,
+            count = dropped,
+            "Removed the cross-listed copy's pending study sessions and reminders"
+        );
+    }
+    tx.execute(
+        "UPDATE entries SET completed = (SELECT completed FROM entries WHERE id = ?2)
+         WHERE id = ?1",
+        params![copy_id, primary_id],
+    )?;
+    tx.commit()?;
+    Ok(link)
+}
+
+/// Find top-level entries posted under more than one subject and
+/// cross-list them (see `crosslist`). Entries that are linked in any way
+/// already are left alone. Returns the number of copies found.
+pub fn link_cross_listed(conn: &Connection) -> Result<usize> {
+    let links = get_links(conn)?;
+    let copies: HashSet<&str> = links
+        .iter()
+        .filter(|l| l.relation == LinkRelation::CrossListed)
+        .map(|l| l.from_id.as_str())
+        .collect();
+    let linked: HashSet<(String, String)> = links
+        .iter()
+        .map(|l| (l.from_id.clone(), l.to_id.clone()))
+        .collect();
+    let candidates: Vec<HomeworkEntry> = get_all_entries(conn)?
+        .into_iter()
+        .filter(|e| e.parent_id.is_none() && !copies.contains(e.id.as_str()))
+        .collect();
+
+    let pairs = crosslist::find_pairs(&candidates, &linked);
+    for (copy, primary) in &pairs {
+        cross_list(conn, copy, primary)?;
+    }
+    Ok(pairs.len())
+}
+
+/// Split a cross-listed pair apart again: the `cross_listed` link `id`
+/// becomes a `related` one, which also keeps the detector from merging them
+/// on the next import. `None` when there's no such link.
+pub fn split_cross_listed(conn: &Connection, id: i64) -> Result<Option<EntryLink>> {
+    let Some(link) = get_link(conn, id)?.filter(|l| l.relation == LinkRelation::CrossListed) else {
+        return Ok(None);
+    };
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM entry_links WHERE id = ?1", [id])?;
+    let related = create_link(&tx, &link.from_id, &link.to_id, LinkRelation::Related)?;
+    tx.commit()?;
+    Ok(Some(related))
+}
+
+/// Subjects each primary is cross-listed under besides its own, keyed by
+/// the primary's id, and each copy's primary
+pub fn get_cross_listings(
+    conn: &Connection,
+) -> Result<(HashMap<String, Vec<String>>, HashMap<String, String>)> {
+    let mut stmt = conn.prepare(
+        "SELECT l.to_id, l.from_id, e.subject FROM entry_links l
+         JOIN entries e ON e.id = l.from_id
+         WHERE l.relation = 'cross_listed'
+         ORDER BY e.subject",
+    )?;
+    let mut subjects: HashMap<String, Vec<String>> = HashMap::new();
+    let mut primaries = HashMap::new();
+    let rows = stmt.query_map([], |row| {
+        Ok((
+            row.get::<_, String>(0)?,
+            row.get::<_, String>(1)?,
+            row.get::<_, String>(2)?,
+        ))
+    })?;
+    for row in rows {
+        let (primary, copy, subject) = row?;
+        subjects.entry(primary.clone()).or_default().push(subject);
+        primaries.insert(copy, primary);
+    }
+    Ok((subjects, primaries))
+}
+
+/// Whether `target` can be reached from `start` by following `depends_on`
+/// links. Adding `from depends_on to` makes a cycle exactly when `from` is
+/// reachable from `to`.
+pub fn depends_on_reaches(conn: &Connection, start: &str, target: &str) -> Result<bool> {
+    let mut stmt = conn
+        .prepare("SELECT to_id FROM entry_links WHERE from_id = ?1 AND relation = 'depends_on'")?;
+    let mut seen = HashSet::new();
+    let mut queue = vec![start.to_string()];
+    while let Some(id) = queue.pop() {
+        if id == target {
+            return Ok(true);
+        }
+        if !seen.insert(id.clone()) {
+            continue;
+        }
+        let next = stmt
+            .query_map([&id], |row| row.get::<_, String>(0))?
+            .collect::<Result<Vec<_>, _>>()?;
+        queue.extend(next);
     }
+    Ok(false)
+}
+
+// ========== Share links ==========
+
+const SHARE_COLUMNS: &str = "token, entry_id, expires_at, created_at";
+
+fn row_to_share_link(row: &Row) -> rusqlite::Result<ShareLink> {
+    Ok(ShareLink {
+        token: row.get(0)?,
+        entry_id: row.get(1)?,
+        expires_at: row.get(2)?,
+        created_at: row.get(3)?,
+    })
+}
+
+/// Create a share link for `entry_id` that works until `expires_at`.
+pub fn create_share_link(
+    conn: &Connection,
+    entry_id: &str,
+    expires_at: chrono::DateTime<chrono::Utc>,
+) -> Result<ShareLink> {
+    let link = ShareLink {
+        token: uuid::Uuid::new_v4().simple().to_string(),
+        entry_id: entry_id.to_string(),
+        // Fixed-width UTC, so expiry can be compared as text
+        expires_at: expires_at.to_rfc3339_opts(chrono::SecondsFormat::Secs, true),
+        created_at: chrono::Utc::now().to_rfc3339(),
+    };
+    conn.execute(
+        &format!("INSERT INTO share_links ({SHARE_COLUMNS}) VALUES (?1, ?2, ?3, ?4)"),
+        params![link.token, link.entry_id, link.expires_at, link.created_at],
+    )?;
+    Ok(link)
+}
+
+pub fn get_share_link(conn: &Connection, token: &str) -> Result<Option<ShareLink>> {
+    Ok(conn
+        .query_row(
+            &format!("SELECT {SHARE_COLUMNS} FROM share_links WHERE token = ?1"),
+            [token],
+            row_to_share_link,
+        )
+        .optional()?)
+}
+
+/// Drop links that have expired by `now`
+pub fn delete_expired_share_links(
+    conn: &Connection,
+    now: chrono::DateTime<chrono::Utc>,
+) -> Result<usize> {
+    Ok(conn.execute(
+        "DELETE FROM share_links WHERE expires_at <= ?1",
+        [now.to_rfc3339_opts(chrono::SecondsFormat::Secs, true)],
+    )?)
+}
+
+// ========== Subject resources ==========
+
+const RESOURCE_COLUMNS: &str = "id, subject, title, url, created_at";
+
+fn row_to_resource(row: &Row) -> rusqlite::Result<SubjectResource> {
+    Ok(SubjectResource {
+        id: row.get(0)?,
+        subject: row.get(1)?,
+        title: row.get(2)?,
+        url: row.get(3)?,
+        created_at: row.get(4)?,
+    })
+}
+
+/// Add a resource to `subject`
+pub fn create_resource(
+    conn: &Connection,
+    subject: &str,
+    title: &str,
+    url: &str,
+) -> Result<SubjectResource> {
+    conn.execute(
+        "INSERT INTO subject_resources (subject, title, url, created_at)
+         VALUES (?1, ?2, ?3, ?4)",
+        params![
+            subject.trim(),
+            title.trim(),
+            url.trim(),
+            chrono::Utc::now().to_rfc3339()
+        ],
+    )?;
+    let id = conn.last_insert_rowid();
+    conn.query_row(
+        &format!("SELECT {RESOURCE_COLUMNS} FROM subject_resources WHERE id = ?1"),
+        [id],
+        row_to_resource,
+    )
+    .optional()?
+    .ok_or_else(|| DbError::NotFound("Resource".to_string()))
+}
+
+/// Every subject's resources, by subject then in the order they were added
+pub fn get_resources(conn: &Connection) -> Result<Vec<SubjectResource>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {RESOURCE_COLUMNS} FROM subject_resources ORDER BY subject, id"
+    ))?;
+    let resources = stmt
+        .query_map([], row_to_resource)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(resources)
+}
+
+/// One subject's resources (the name is matched case-insensitively)
+pub fn get_resources_for_subject(conn: &Connection, subject: &str) -> Result<Vec<SubjectResource>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {RESOURCE_COLUMNS} FROM subject_resources WHERE subject = ?1 ORDER BY id"
+    ))?;
+    let resources = stmt
+        .query_map([subject.trim()], row_to_resource)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(resources)
+}
+
+/// Remove resource `id`, only if it belongs to `subject`
+pub fn delete_resource(conn: &Connection, subject: &str, id: i64) -> Result<bool> {
+    let affected = conn.execute(
+        "DELETE FROM subject_resources WHERE id = ?1 AND subject = ?2",
+        params![id, subject.trim()],
+    )?;
+    Ok(affected > 0)
+}
+
+// ========== Subject icons ==========
+
+/// Icons set for subjects, as (subject, icon), by subject
+pub fn get_subject_icons(conn: &Connection) -> Result<Vec<(String, String)>> {
+    let mut stmt = conn.prepare("SELECT subject, icon FROM subject_icons ORDER BY subject")?;
+    let icons = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(icons)
+}
+
+/// Give `subject` its own icon, replacing any set before
+pub fn set_subject_icon(conn: &Connection, subject: &str, icon: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO subject_icons (subject, icon, updated_at) VALUES (?1, ?2, ?3)
+         ON CONFLICT(subject) DO UPDATE SET icon = excluded.icon, updated_at = excluded.updated_at",
+        params![subject.trim(), icon.trim(), chrono::Utc::now().to_rfc3339()],
+    )?;
+    Ok(())
+}
+
+/// Drop `subject`'s own icon, back to the default
+pub fn delete_subject_icon(conn: &Connection, subject: &str) -> Result<bool> {
+    let affected = conn.execute(
+        "DELETE FROM subject_icons WHERE subject = ?1",
+        [subject.trim()],
+    )?;
+    Ok(affected > 0)
+}
+
+// ========== Attachments ==========
+
+const ATTACHMENT_COLUMNS: &str = "id, source_id, name, url, file, ocr_status, ocr_text, \
+     ocr_error, created_at, processed_at";
+
+fn row_to_attachment(row: &Row) -> rusqlite::Result<Attachment> {
+    Ok(Attachment {
+        id: row.get(0)?,
+        source_id: row.get(1)?,
+        name: row.get(2)?,
+        url: row.get(3)?,
+        file: row.get(4)?,
+        ocr_status: row.get(5)?,
+        ocr_text: row.get(6)?,
+        ocr_error: row.get(7)?,
+        created_at: row.get(8)?,
+        processed_at: row.get(9)?,
+    })
+}
+
+/// Record attachments read from sidecars. Ones with a local file are queued
+/// for OCR; a file turning up for a known attachment queues it too.
+/// Returns how many were new.
+pub fn record_attachments(conn: &Connection, attachments: &[NewAttachment]) -> Result<usize> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut added = 0;
+    for attachment in attachments {
+        let exists: bool = conn.query_row(
+            "SELECT COUNT(*) > 0 FROM attachments WHERE source_id = ?1 AND url = ?2",
+            params![attachment.source_id, attachment.url],
+            |row| row.get(0),
+        )?;
+        conn.execute(
+            "INSERT INTO attachments (source_id, name, url, file, ocr_status, created_at)
+             VALUES (?1, ?2, ?3, ?4, CASE WHEN ?4 IS NULL THEN 'none' ELSE 'pending' END, ?5)
+             ON CONFLICT(source_id, url) DO UPDATE SET
+                 name = excluded.name,
+                 file = COALESCE(excluded.file, file),
+                 ocr_status = CASE
+                     WHEN ocr_status = 'none' AND excluded.file IS NOT NULL THEN 'pending'
+                     ELSE ocr_status
+                 END",
+            params![
+                attachment.source_id,
+                attachment.name,
+                attachment.url,
+                attachment.file,
+                now
+            ],
+        )?;
+        if !exists {
+            added += 1;
+        }
+    }
+    Ok(added)
+}
+
+/// Every attachment, newest first
+pub fn get_attachments(conn: &Connection) -> Result<Vec<Attachment>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ATTACHMENT_COLUMNS} FROM attachments ORDER BY id DESC"
+    ))?;
+    let attachments = stmt
+        .query_map([], row_to_attachment)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(attachments)
+}
+
+/// One attachment
+pub fn get_attachment(conn: &Connection, id: i64) -> Result<Attachment> {
+    conn.query_row(
+        &format!("SELECT {ATTACHMENT_COLUMNS} FROM attachments WHERE id = ?1"),
+        [id],
+        row_to_attachment,
+    )
+    .optional()?
+    .ok_or_else(|| DbError::NotFound("Attachment".to_string()))
+}
+
+/// The attachments of entry `entry_id`
+pub fn get_entry_attachments(conn: &Connection, entry_id: &str) -> Result<Vec<Attachment>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ATTACHMENT_COLUMNS} FROM attachments
+         WHERE source_id = (SELECT source_id FROM entries WHERE id = ?1)
+         ORDER BY id"
+    ))?;
+    let attachments = stmt
+        .query_map([entry_id], row_to_attachment)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(attachments)
+}
+
+/// Up to `limit` photos waiting for OCR, oldest first
+pub fn get_pending_ocr(conn: &Connection, limit: usize) -> Result<Vec<Attachment>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ATTACHMENT_COLUMNS} FROM attachments
+         WHERE ocr_status = 'pending' ORDER BY id LIMIT ?1"
+    ))?;
+    let attachments = stmt
+        .query_map([limit as i64], row_to_attachment)?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(attachments)
+}
+
+/// Store the OCR outcome for attachment `id`: the text, or why it failed
+pub fn set_ocr_result(conn: &Connection, id: i64, result: Result<&str, &str>) -> Result<()> {
+    let now = chrono::Utc::now().to_rfc3339();
+    let (status, text, error) = match result {
+        Ok(text) => ("done", Some(text), None),
+        Err(error) => ("failed", None, Some(error)),
+    };
+    conn.execute(
+        "UPDATE attachments SET ocr_status = ?2, ocr_text = ?3, ocr_error = ?4, processed_at = ?5
+         WHERE id = ?1",
+        params![id, status, text, error, now],
+    )?;
+    Ok(())
+}
+
+// ========== Register ==========
+
+/// Replace the absences with a newer snapshot of the register page.
+/// Returns how many there are now.
+pub fn replace_absences(conn: &Connection, snapshot: &AbsencesSnapshot) -> Result<usize> {
+    let fetched = snapshot.fetched.as_deref();
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM absences", [])?;
+    for absence in &snapshot.absences {
+        tx.execute(
+            "INSERT INTO absences (date, kind, justified, note, fetched)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                absence.date,
+                absence.kind,
+                absence.justified,
+                absence.note,
+                fetched.unwrap_or_default()
+            ],
+        )?;
+    }
+    set_register_fetched(&tx, "absences", fetched)?;
+    tx.commit()?;
+    Ok(snapshot.absences.len())
+}
+
+/// The absences, newest first
+pub fn get_absences(conn: &Connection) -> Result<AbsencesSnapshot> {
+    let mut stmt =
+        conn.prepare("SELECT date, kind, justified, note FROM absences ORDER BY date DESC, id")?;
+    let absences = stmt
+        .query_map([], |row| {
+            Ok(Absence {
+                date: row.get(0)?,
+                kind: row.get(1)?,
+                justified: row.get(2)?,
+                note: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(AbsencesSnapshot {
+        fetched: get_register_fetched(conn, "absences")?,
+        absences,
+    })
+}
+
+/// Replace the disciplinary notes with a newer snapshot of the register
+/// page. Returns how many there are now.
+pub fn replace_discipline_notes(conn: &Connection, snapshot: &NotesSnapshot) -> Result<usize> {
+    let fetched = snapshot.fetched.as_deref();
+    let tx = conn.unchecked_transaction()?;
+    tx.execute("DELETE FROM discipline_notes", [])?;
+    for note in &snapshot.notes {
+        tx.execute(
+            "INSERT INTO discipline_notes (date, teacher, kind, text, fetched)
+             VALUES (?1, ?2, ?3, ?4, ?5)",
+            params![
+                note.date,
+                note.teacher,
+                note.kind,
+                note.text,
+                fetched.unwrap_or_default()
+            ],
+        )?;
+    }
+    set_register_fetched(&tx, "notes", fetched)?;
+    tx.commit()?;
+    Ok(snapshot.notes.len())
+}
+
+/// The disciplinary notes, newest first
+pub fn get_discipline_notes(conn: &Connection) -> Result<NotesSnapshot> {
+    let mut stmt = conn
+        .prepare("SELECT date, teacher, kind, text FROM discipline_notes ORDER BY date DESC, id")?;
+    let notes = stmt
+        .query_map([], |row| {
+            Ok(DisciplineNote {
+                date: row.get(0)?,
+                teacher: row.get(1)?,
+                kind: row.get(2)?,
+                text: row.get(3)?,
+            })
+        })?
+        .collect::<rusqlite::Result<Vec<_>>>()?;
+    Ok(NotesSnapshot {
+        fetched: get_register_fetched(conn, "notes")?,
+        notes,
+    })
+}
+
+/// Record when the `kind` page was read, even if it listed nothing
+fn set_register_fetched(conn: &Connection, kind: &str, fetched: Option<&str>) -> Result<()> {
+    conn.execute(
+        "INSERT INTO register_snapshots (kind, fetched) VALUES (?1, ?2)
+         ON CONFLICT(kind) DO UPDATE SET fetched = excluded.fetched",
+        params![kind, fetched],
+    )?;
+    Ok(())
+}
+
+/// When the `kind` page was last read; `None` before its first import
+fn get_register_fetched(conn: &Connection, kind: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT fetched FROM register_snapshots WHERE kind = ?1",
+            [kind],
+            |row| row.get::<_, Option<String>>(0),
+        )
+        .optional()?
+        .flatten())
+}
+
+// ========== Subject merges ==========
+
+/// Subject names in use by entries, with how many entries use each
+pub fn get_subject_counts(conn: &Connection) -> Result<Vec<SubjectCount>> {
+    let mut stmt = conn.prepare(
+        "SELECT subject, COUNT(*) FROM entries
+         WHERE TRIM(subject) != '' GROUP BY subject ORDER BY subject",
+    )?;
+    let counts = stmt
+        .query_map([], |row| {
+            Ok(SubjectCount {
+                subject: row.get(0)?,
+                entries: row.get::<_, i64>(1)? as usize,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(counts)
+}
+
+/// Rename subject `from` (any case) to `into` on entries, pending imports,
+/// study links, icons and weekly goals, in one transaction, and log the merge.
+///
+/// An entry's `source_id` is recomputed with the new name when it still
+/// matches its date, subject and task, so the next import of the same row
+/// (now mapped to `into`) is recognised. Entries moved or edited since, or
+/// whose new key another entry already has, keep the old one.
+pub fn merge_subjects(conn: &Connection, from: &str, into: &str) -> Result<SubjectMerge> {
+    let (from, into) = (from.trim(), into.trim());
+    let tx = conn.unchecked_transaction()?;
+    let now = chrono::Utc::now().to_rfc3339();
+
+    let entries = rekey_subject(&tx, "entries", from, into, Some(&now))?;
+    let pending = rekey_subject(&tx, "pending_imports", from, into, None)?;
+    tx.execute(
+        "UPDATE subject_resources SET subject = ?2 WHERE subject = ?1",
+        params![from, into],
+    )?;
+    // An icon `into` already has wins
+    tx.execute(
+        "UPDATE OR IGNORE subject_icons SET subject = ?2 WHERE subject = ?1",
+        params![from, into],
+    )?;
+    tx.execute("DELETE FROM subject_icons WHERE subject = ?1", [from])?;
+
+    // Goals for the merged name go first, so a goal `into` already had wins
+    let (mut goals, rest): (Vec<WeeklyGoal>, Vec<WeeklyGoal>) = get_weekly_goals(&tx)?
+        .into_iter()
+        .partition(|g| g.subject.trim().eq_ignore_ascii_case(from));
+    if !goals.is_empty() {
+        goals.iter_mut().for_each(|g| g.subject = into.to_string());
+        goals.extend(rest);
+        set_weekly_goals(&tx, &goals)?;
+    }
+
+    tx.execute(
+        "INSERT INTO subject_merges (from_subject, into_subject, entries, pending, merged_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![from, into, entries as i64, pending as i64, now],
+    )?;
+    let id = tx.last_insert_rowid();
+    tx.commit()?;
+
+    Ok(SubjectMerge {
+        id,
+        from: from.to_string(),
+        into: into.to_string(),
+        entries,
+        pending,
+        merged_at: now,
+    })
+}
+
+/// Point rows of `table` with subject `from` at `into`, re-keying
+/// `source_id` where it's still the content hash. Returns the rows changed.
+fn rekey_subject(
+    conn: &Connection,
+    table: &str,
+    from: &str,
+    into: &str,
+    updated_at: Option<&str>,
+) -> Result<usize> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT id, source_id, date, subject, task FROM {table}
+         WHERE LOWER(TRIM(subject)) = LOWER(?1)"
+    ))?;
+    let rows = stmt
+        .query_map([from], |row| {
+            Ok((
+                row.get::<_, String>(0)?,
+                row.get::<_, Option<String>>(1)?,
+                row.get::<_, String>(2)?,
+                row.get::<_, String>(3)?,
+                row.get::<_, String>(4)?,
+            ))
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    for (id, source_id, date, subject, task) in &rows {
+        let unchanged = source_id.as_deref()
+            == Some(HomeworkEntry::generate_source_id(date, subject, task).as_str());
+        let new_key = HomeworkEntry::generate_source_id(date, into, task);
+        let taken: bool = conn.query_row(
+            &format!("SELECT COUNT(*) > 0 FROM {table} WHERE source_id = ?1 AND id != ?2"),
+            params![new_key, id],
+            |row| row.get(0),
+        )?;
+        let new_source_id = if unchanged && !taken {
+            Some(new_key)
+        } else {
+            source_id.clone()
+        };
+        // Attachments follow the entry; they must move first so the search
+        // index picks up their OCR text under the new key
+        if new_source_id != *source_id {
+            conn.execute(
+                "UPDATE OR IGNORE attachments SET source_id = ?2 WHERE source_id = ?1",
+                params![source_id, new_source_id],
+            )?;
+        }
+        let source_id = new_source_id;
+        conn.execute(
+            &format!("UPDATE {table} SET subject = ?2, source_id = ?3 WHERE id = ?1"),
+            params![id, into, source_id],
+        )?;
+        if let Some(updated_at) = updated_at {
+            conn.execute(
+                &format!("UPDATE {table} SET updated_at = ?2 WHERE id = ?1"),
+                params![id, updated_at],
+            )?;
+        }
+    }
+    Ok(rows.len())
+}
+
+/// Logged subject merges, newest first
+pub fn get_subject_merges(conn: &Connection) -> Result<Vec<SubjectMerge>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, from_subject, into_subject, entries, pending, merged_at
+         FROM subject_merges ORDER BY id DESC",
+    )?;
+    let merges = stmt
+        .query_map([], |row| {
+            Ok(SubjectMerge {
+                id: row.get(0)?,
+                from: row.get(1)?,
+                into: row.get(2)?,
+                entries: row.get::<_, i64>(3)? as usize,
+                pending: row.get::<_, i64>(4)? as usize,
+                merged_at: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(merges)
+}
+
+// ========== Text fixes ==========
+
+/// Replace every `find` in entry tasks with `replace`, in entries of
+/// `subject` (any case) or of every subject, in one transaction. A dry run
+/// only reports what would change. An applied fix that changed anything is
+/// logged in `text_fixes`.
+///
+/// `source_id` is kept: the next export still has the original text, and
+/// must be recognised as the same entry rather than imported again.
+pub fn fix_text(
+    conn: &Connection,
+    find: &str,
+    replace: &str,
+    subject: Option<&str>,
+    dry_run: bool,
+) -> Result<TextFixReport> {
+    let subject = subject.map(str::trim).filter(|s| !s.is_empty());
+    let tx = conn.unchecked_transaction()?;
+    let mut changes: Vec<TextChange> = tx
+        .prepare(
+            "SELECT id, date, subject, task FROM entries
+             WHERE instr(task, ?1) > 0
+             ORDER BY date ASC, position ASC",
+        )?
+        .query_map([find], |row| {
+            let before: String = row.get(3)?;
+            Ok(TextChange {
+                id: row.get(0)?,
+                date: row.get(1)?,
+                subject: row.get(2)?,
+                after: before.replace(find, replace),
+                before,
+            })
+        })?
+        .collect::<Result<_, _>>()?;
+    // The subject is matched here, accents and all (see `same_name`)
+    changes.retain(|c| subject.is_none_or(|subject| same_name(&c.subject, subject)));
+    if dry_run || changes.is_empty() {
+        return Ok(TextFixReport {
+            dry_run,
+            changes,
+            fix: None,
+        });
+    }
+
+    let now = chrono::Utc::now().to_rfc3339();
+    let mut stmt = tx.prepare("UPDATE entries SET task = ?2, updated_at = ?3 WHERE id = ?1")?;
+    for change in &changes {
+        stmt.execute(params![change.id, change.after, now])?;
+    }
+    drop(stmt);
+    tx.execute(
+        "INSERT INTO text_fixes (find, replace_with, subject, entries, fixed_at)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![find, replace, subject, changes.len() as i64, now],
+    )?;
+    let id = tx.last_insert_rowid();
+    tx.commit()?;
+
+    Ok(TextFixReport {
+        dry_run,
+        fix: Some(TextFix {
+            id,
+            find: find.to_string(),
+            replace: replace.to_string(),
+            subject: subject.map(str::to_string),
+            entries: changes.len(),
+            fixed_at: now,
+        }),
+        changes,
+    })
+}
+
+/// Logged text fixes, newest first
+pub fn get_text_fixes(conn: &Connection) -> Result<Vec<TextFix>> {
+    let mut stmt = conn.prepare(
+        "SELECT id, find, replace_with, subject, entries, fixed_at
+         FROM text_fixes ORDER BY id DESC",
+    )?;
+    let fixes = stmt
+        .query_map([], |row| {
+            Ok(TextFix {
+                id: row.get(0)?,
+                find: row.get(1)?,
+                replace: row.get(2)?,
+                subject: row.get(3)?,
+                entries: row.get::<_, i64>(4)? as usize,
+                fixed_at: row.get(5)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(fixes)
+}
+
+// ========== Background jobs ==========
+
+/// Record a finished run of job `run.name`; `run.runs` is ignored and the
+/// stored count goes up by one.
+pub fn record_job_run(conn: &Connection, run: &JobRun) -> Result<()> {
+    conn.execute(
+        "INSERT INTO job_runs (name, started_at, finished_at, ok, message, runs)
+         VALUES (?1, ?2, ?3, ?4, ?5, 1)
+         ON CONFLICT(name) DO UPDATE SET
+             started_at = excluded.started_at,
+             finished_at = excluded.finished_at,
+             ok = excluded.ok,
+             message = excluded.message,
+             runs = runs + 1",
+        params![
+            run.name,
+            run.started_at,
+            run.finished_at,
+            run.ok as i32,
+            run.message
+        ],
+    )?;
+    Ok(())
+}
+
+/// Last run of every job that has run
+pub fn get_job_runs(conn: &Connection) -> Result<Vec<JobRun>> {
+    let mut stmt = conn.prepare(
+        "SELECT name, started_at, finished_at, ok, message, runs FROM job_runs ORDER BY name",
+    )?;
+    let runs = stmt
+        .query_map([], |row| {
+            Ok(JobRun {
+                name: row.get(0)?,
+                started_at: row.get(1)?,
+                finished_at: row.get(2)?,
+                ok: row.get::<_, i32>(3)? != 0,
+                message: row.get(4)?,
+                runs: row.get::<_, i64>(5)? as u64,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(runs)
+}
+
+/// Let SQLite refresh its query planner statistics
+pub fn optimize(conn: &Connection) -> Result<()> {
+    conn.execute_batch("PRAGMA optimize;")?;
+    Ok(())
+}
+
+// ========== Stats ==========
+
+/// Per-subject time and difficulty stats over completed entries, sorted by subject
+pub fn get_subject_time_stats(conn: &Connection) -> Result<Vec<SubjectTimeStats>> {
+    let mut stmt = conn.prepare(
+        "SELECT subject,
+                COUNT(*),
+                COUNT(actual_minutes),
+                AVG(estimated_minutes),
+                AVG(actual_minutes),
+                AVG(difficulty),
+                SUM(CASE WHEN estimated_minutes IS NOT NULL THEN actual_minutes END) * 1.0
+                    / NULLIF(SUM(CASE WHEN actual_minutes IS NOT NULL THEN estimated_minutes END), 0)
+         FROM entries
+         WHERE completed = 1
+         GROUP BY subject
+         ORDER BY subject ASC",
+    )?;
+
+    let stats = stmt
+        .query_map([], |row| {
+            Ok(SubjectTimeStats {
+                subject: row.get(0)?,
+                completed: row.get::<_, i64>(1)? as usize,
+                with_feedback: row.get::<_, i64>(2)? as usize,
+                avg_estimated_minutes: row.get(3)?,
+                avg_actual_minutes: row.get(4)?,
+                avg_difficulty: row.get(5)?,
+                estimate_ratio: row.get(6)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(stats)
+}
+
+/// Entries per subject and topic, most first. Generated study sessions and
+/// reminders don't count; `minutes` adds up the actual time, or the
+/// estimate where none was recorded. Tests are told apart by their task, as
+/// for study sessions (`data::is_test_or_quiz`).
+pub fn get_topic_stats(conn: &Connection) -> Result<Vec<TopicStats>> {
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS} FROM entries WHERE topic IS NOT NULL AND parent_id IS NULL"
+    ))?;
+    let entries = stmt
+        .query_map([], row_to_entry)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut by_topic: BTreeMap<(String, String), TopicStats> = BTreeMap::new();
+    for entry in &entries {
+        let Some(topic) = &entry.topic else {
+            continue;
+        };
+        let stats = by_topic
+            .entry((entry.subject.clone(), topic.clone()))
+            .or_insert_with(|| TopicStats {
+                subject: entry.subject.clone(),
+                topic: topic.clone(),
+                entries: 0,
+                tests: 0,
+                completed: 0,
+                minutes: 0,
+            });
+        stats.entries += 1;
+        stats.tests += usize::from(crate::data::is_test_or_quiz(entry));
+        stats.completed += usize::from(entry.completed);
+        stats.minutes += entry
+            .actual_minutes
+            .or(entry.estimated_minutes)
+            .unwrap_or_default();
+    }
+    let mut stats: Vec<TopicStats> = by_topic.into_values().collect();
+    // Already by subject then topic; a stable sort keeps that for ties
+    stats.sort_by(|a, b| b.entries.cmp(&a.entries));
+    Ok(stats)
+}
+
+/// Notice at or under which an entry counts as short notice, in days
+pub const SHORT_NOTICE_DAYS: i64 = 1;
+
+/// After an entry on `date` is ticked off: record the day as all done if
+/// nothing on it is pending any more (kept from the first time it was).
+/// Returns whether the day is all done.
+pub fn record_day_completed(conn: &Connection, date: &str) -> Result<bool> {
+    let (entries, pending): (i64, i64) = conn.query_row(
+        "SELECT COUNT(*), COALESCE(SUM(completed = 0), 0) FROM entries WHERE date = ?1",
+        [date],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )?;
+    if entries == 0 || pending > 0 {
+        return Ok(false);
+    }
+    conn.execute(
+        "INSERT OR IGNORE INTO day_completions (date, completed_at, entries)
+         VALUES (?1, datetime('now'), ?2)",
+        params![date, entries],
+    )?;
+    Ok(true)
+}
+
+/// After an entry on `date` is unticked: the day isn't all done any more
+pub fn reopen_day(conn: &Connection, date: &str) -> Result<()> {
+    conn.execute("DELETE FROM day_completions WHERE date = ?1", [date])?;
+    Ok(())
+}
+
+/// Days between `from` and `to` (inclusive) with entries and none pending,
+/// oldest first. Worked out from `entries` each time, so moves, deletions,
+/// new entries and generated sessions count without re-checking both days
+/// in every path; `day_completions` only keeps when a day was first finished.
+pub fn get_days_completed(conn: &Connection, from: &str, to: &str) -> Result<Vec<DayCompleted>> {
+    let mut stmt = conn.prepare(
+        "SELECT e.date, COALESCE(d.completed_at, MAX(e.updated_at)), COUNT(*)
+         FROM entries e
+         LEFT JOIN day_completions d ON d.date = e.date
+         WHERE e.date >= ?1 AND e.date <= ?2
+         GROUP BY e.date
+         HAVING SUM(e.completed = 0) = 0
+         ORDER BY e.date",
+    )?;
+    let days = stmt
+        .query_map([from, to], |row| {
+            Ok(DayCompleted {
+                date: row.get(0)?,
+                completed_at: row.get(1)?,
+                entries: row.get::<_, i64>(2)? as usize,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(days)
+}
+
+/// Every day with entries, oldest first, and whether none is pending (for
+/// `streak::streaks`)
+pub fn get_day_outcomes(conn: &Connection) -> Result<Vec<(NaiveDate, bool)>> {
+    let mut stmt = conn.prepare(
+        "SELECT date, SUM(completed = 0) = 0 FROM entries
+         GROUP BY date
+         ORDER BY date",
+    )?;
+    let days = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get(1)?)))?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(days
+        .into_iter()
+        .filter_map(|(date, done)| {
+            NaiveDate::parse_from_str(&date, "%Y-%m-%d")
+                .ok()
+                .map(|d| (d, done))
+        })
+        .collect())
+}
+
+/// Notice given per subject and teacher, least first, over top-level
+/// entries whose posting time the export gave. Measured to the date the
+/// export gave (`source_date`), so moving an entry doesn't change it.
+pub fn get_notice_stats(conn: &Connection) -> Result<Vec<NoticeStats>> {
+    let mut stmt = conn.prepare(
+        "SELECT subject, teacher, COUNT(*), SUM(entry_type IN ('verifica', 'interrogazione')),
+                AVG(days), MIN(days), SUM(days <= ?1)
+         FROM (SELECT subject, teacher, entry_type,
+                      CAST(julianday(COALESCE(source_date, date))
+                           - julianday(substr(assigned_at, 1, 10)) AS INTEGER) AS days
+               FROM entries
+               WHERE assigned_at IS NOT NULL AND parent_id IS NULL)
+         WHERE days IS NOT NULL
+         GROUP BY subject, teacher
+         ORDER BY AVG(days) ASC, subject ASC, teacher ASC",
+    )?;
+    let stats = stmt
+        .query_map([SHORT_NOTICE_DAYS], |row| {
+            Ok(NoticeStats {
+                subject: row.get(0)?,
+                teacher: row.get(1)?,
+                entries: row.get::<_, i64>(2)? as usize,
+                tests: row.get::<_, i64>(3)? as usize,
+                avg_days: row.get(4)?,
+                shortest_days: row.get(5)?,
+                short_notice: row.get::<_, i64>(6)? as usize,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(stats)
+}
+
+/// Progress on each weekly goal between `from` and `to` (inclusive). Completed
+/// compiti and studio entries count; subjects match case-insensitively.
+pub fn get_goal_progress(
+    conn: &Connection,
+    goals: &[WeeklyGoal],
+    from: &str,
+    to: &str,
+) -> Result<Vec<GoalProgress>> {
+    let mut stmt = conn.prepare(
+        "SELECT COUNT(*)
+         FROM entries
+         WHERE completed = 1
+           AND entry_type IN ('compiti', 'studio')
+           AND date >= ?1 AND date <= ?2
+           AND LOWER(TRIM(subject)) = LOWER(?3)",
+    )?;
+    goals
+        .iter()
+        .map(|goal| {
+            let done: i64 = stmt.query_row(params![from, to, goal.subject], |row| row.get(0))?;
+            let done = done as u32;
+            Ok(GoalProgress {
+                subject: goal.subject.clone(),
+                target: goal.target,
+                done,
+                met: done >= goal.target,
+            })
+        })
+        .collect()
+}
+
+/// Entry, test and completion counts plus average lead time between `from`
+/// and `to` (inclusive), overall and per subject. Generated study sessions
+/// are left out. Lead time is measured from when the teacher posted the
+/// entry (`assigned_at`), or from when it was added for entries whose export
+/// didn't say; entries posted or added after they were due don't count
+/// towards it.
+pub fn get_period_stats(conn: &Connection, from: &str, to: &str) -> Result<PeriodStats> {
+    let query = |group: &str| {
+        format!(
+            "SELECT {group},
+                    COUNT(*),
+                    COALESCE(SUM(entry_type IN ('verifica', 'interrogazione')), 0),
+                    COALESCE(SUM(completed), 0),
+                    AVG(CASE WHEN date >= substr(COALESCE(assigned_at, created_at), 1, 10)
+                             THEN julianday(date)
+                                  - julianday(substr(COALESCE(assigned_at, created_at), 1, 10))
+                             END)
+             FROM entries
+             WHERE parent_id IS NULL AND date >= ?1 AND date <= ?2"
+        )
+    };
+    let to_counts = |row: &rusqlite::Row, offset: usize| -> rusqlite::Result<PeriodCounts> {
+        let entries = row.get::<_, i64>(offset)? as usize;
+        let completed = row.get::<_, i64>(offset + 2)? as usize;
+        Ok(PeriodCounts {
+            entries,
+            tests: row.get::<_, i64>(offset + 1)? as usize,
+            completed,
+            completion_rate: (entries > 0).then(|| completed as f64 / entries as f64),
+            avg_lead_days: row.get(offset + 3)?,
+        })
+    };
+
+    let total = conn.query_row(&query("NULL"), params![from, to], |row| to_counts(row, 1))?;
+    let mut stmt = conn.prepare(&format!(
+        "{} GROUP BY subject ORDER BY subject ASC",
+        query("subject")
+    ))?;
+    let subjects = stmt
+        .query_map(params![from, to], |row| {
+            Ok(SubjectPeriodStats {
+                subject: row.get(0)?,
+                counts: to_counts(row, 1)?,
+            })
+        })?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(PeriodStats {
+        from: from.to_string(),
+        to: to.to_string(),
+        total,
+        subjects,
+    })
+}
+
+// ========== Dashboard ==========
+
+/// Days the dashboard's "This week" column reaches past today
+pub const DASHBOARD_WEEK_DAYS: i64 = 6;
+
+/// How far ahead the dashboard lists tests
+pub const DASHBOARD_TEST_DAYS: i64 = 30;
+
+/// The dashboard for `today`: today's, tomorrow's and the rest of the week's
+/// entries, open tests in the next `DASHBOARD_TEST_DAYS`, and the counters.
+pub fn get_dashboard(conn: &Connection, today: NaiveDate) -> Result<Dashboard> {
+    let day = |offset: i64| (today + chrono::Duration::days(offset)).to_string();
+    let (today_str, tomorrow, week_end) = (day(0), day(1), day(DASHBOARD_WEEK_DAYS));
+
+    let mut due_today = Vec::new();
+    let mut due_tomorrow = Vec::new();
+    let mut this_week = Vec::new();
+    let week = EntryFilter {
+        from: Some(today_str.clone()),
+        to: Some(week_end.clone()),
+        visible_on: Some(today_str.clone()),
+        skip_cross_listed_copies: true,
+        ..Default::default()
+    };
+    for entry in get_entries_filtered(conn, &week)? {
+        if entry.date == today_str {
+            due_today.push(entry);
+        } else if entry.date == tomorrow {
+            due_tomorrow.push(entry);
+        } else {
+            this_week.push(entry);
+        }
+    }
+
+    let mut stmt = conn.prepare(&format!(
+        "SELECT {ENTRY_COLUMNS}
+         FROM entries
+         WHERE completed = 0
+           AND entry_type IN ('verifica', 'interrogazione')
+           AND date >= ?1 AND date <= ?2
+           AND {}
+           AND {NOT_CROSS_LISTED_COPY}
+         ORDER BY date ASC, pinned DESC, position ASC",
+        visible_on(1)
+    ))?;
+    let upcoming_tests = stmt
+        .query_map(params![today_str, day(DASHBOARD_TEST_DAYS)], row_to_entry)?
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let counts = conn.query_row(
+        &format!(
+            "SELECT COALESCE(SUM(completed = 0 AND date < ?1), 0),
+                    COALESCE(SUM(completed = 0 AND date = ?1), 0),
+                    COALESCE(SUM(completed = 0 AND date >= ?1 AND date <= ?2), 0),
+                    COALESCE(SUM(completed = 1 AND date >= ?1 AND date <= ?2), 0)
+             FROM entries
+             WHERE {} AND {NOT_CROSS_LISTED_COPY}",
+            visible_on(1)
+        ),
+        params![today_str, week_end],
+        |row| {
+            Ok(DashboardCounts {
+                overdue: row.get::<_, i64>(0)? as usize,
+                open_today: row.get::<_, i64>(1)? as usize,
+                open_week: row.get::<_, i64>(2)? as usize,
+                completed_week: row.get::<_, i64>(3)? as usize,
+                hidden: 0,
+            })
+        },
+    )?;
+
+    let counts = DashboardCounts {
+        hidden: count_hidden(conn, &today_str)?,
+        ..counts
+    };
+
+    let (cross_listed, _) = get_cross_listings(conn)?;
+    let shown: HashSet<&str> = [&due_today, &due_tomorrow, &this_week, &upcoming_tests]
+        .into_iter()
+        .flatten()
+        .map(|e| e.id.as_str())
+        .collect();
+    let cross_listed = cross_listed
+        .into_iter()
+        .filter(|(primary, _)| shown.contains(primary.as_str()))
+        .collect();
+
+    Ok(Dashboard {
+        today: today_str,
+        due_today,
+        due_tomorrow,
+        this_week,
+        upcoming_tests,
+        counts,
+        cross_listed,
+    })
+}
+
+// ========== Settings ==========
+
+/// Raw value of a settings key, if stored
+fn get_setting(conn: &Connection, key: &str) -> Result<Option<String>> {
+    Ok(conn
+        .query_row(
+            "SELECT value FROM settings WHERE key = ?1",
+            params![key],
+            |row| row.get(0),
+        )
+        .optional()?)
+}
+
+fn set_setting(conn: &Connection, key: &str, value: &str) -> Result<()> {
+    conn.execute(
+        "INSERT INTO settings (key, value) VALUES (?1, ?2)
+         ON CONFLICT(key) DO UPDATE SET value = excluded.value",
+        params![key, value],
+    )?;
+    Ok(())
+}
+
+/// Read a lowercase enum setting (`"calendar"`, `"light"`, …), falling back
+/// to the default for missing or unknown values
+fn get_enum_setting<T: DeserializeOwned + Default>(conn: &Connection, key: &str) -> Result<T> {
+    Ok(get_setting(conn, key)?
+        .and_then(|v| serde_json::from_value(serde_json::Value::String(v)).ok())
+        .unwrap_or_default())
+}
+
+fn set_enum_setting<T: Serialize>(conn: &Connection, key: &str, value: T) -> Result<()> {
+    match serde_json::to_value(value)? {
+        serde_json::Value::String(v) => set_setting(conn, key, &v),
+        other => Err(DbError::Json(serde::ser::Error::custom(format!(
+            "Setting {} is not a string: {}",
+            key, other
+        )))),
+    }
+}
+
+fn get_bool_setting(conn: &Connection, key: &str, default: bool) -> Result<bool> {
+    Ok(get_setting(conn, key)?
+        .map(|s| s == "true")
+        .unwrap_or(default))
+}
+
+/// All settings, with defaults for anything not stored
+pub fn get_settings(conn: &Connection) -> Result<Settings> {
+    let defaults = Settings::default();
+    Ok(Settings {
+        default_view: get_enum_setting(conn, "default_view")?,
+        hide_completed: get_bool_setting(conn, "hide_completed", defaults.hide_completed)?,
+        list_days_back: get_list_days(conn, "list_days_back", defaults.list_days_back)?,
+        list_days_ahead: get_list_days(conn, "list_days_ahead", defaults.list_days_ahead)?,
+        theme: get_enum_setting(conn, "theme")?,
+        language: get_enum_setting(conn, "language")?,
+        study_sessions: get_bool_setting(conn, "study_sessions", defaults.study_sessions)?,
+        study_days_before: get_study_days_before(conn)?,
+        split_study_topics: get_bool_setting(
+            conn,
+            "split_study_topics",
+            defaults.split_study_topics,
+        )?,
+        study_session_position: get_enum_setting(conn, "study_session_position")?,
+        homework_days_ahead: get_homework_days_ahead(conn)?,
+        work_days: get_work_days(conn)?,
+        review_imports: get_review_imports(conn)?,
+        weekly_goals: get_weekly_goals(conn)?,
+        daily_budget_minutes: get_daily_budget_minutes(conn)?,
+        day_limit_entries: get_day_limit(
+            conn,
+            "day_limit_entries",
+            defaults.day_limit_entries,
+            MAX_DAY_LIMIT_ENTRIES,
+        )?,
+        day_limit_minutes: get_day_limit(
+            conn,
+            "day_limit_minutes",
+            defaults.day_limit_minutes,
+            MAX_DAY_LIMIT_MINUTES,
+        )?,
+        check_updates: get_bool_setting(conn, "check_updates", defaults.check_updates)?,
+        completion: get_completion(conn)?,
+        ask_grade: get_bool_setting(conn, "ask_grade", defaults.ask_grade)?,
+        cors_origins: get_cors_origins(conn)?,
+    })
+}
+
+/// Store every setting. Out-of-range numbers are clamped the same way the
+/// individual setters do; read back with `get_settings()` for the stored values.
+pub fn save_settings(conn: &Connection, settings: &Settings) -> Result<()> {
+    let tx = conn.unchecked_transaction()?;
+    set_enum_setting(&tx, "default_view", settings.default_view)?;
+    set_setting(&tx, "hide_completed", &settings.hide_completed.to_string())?;
+    set_list_days(&tx, "list_days_back", settings.list_days_back)?;
+    set_list_days(&tx, "list_days_ahead", settings.list_days_ahead)?;
+    set_enum_setting(&tx, "theme", settings.theme)?;
+    set_enum_setting(&tx, "language", settings.language)?;
+    set_setting(&tx, "study_sessions", &settings.study_sessions.to_string())?;
+    set_study_days_before(&tx, settings.study_days_before)?;
+    set_setting(
+        &tx,
+        "split_study_topics",
+        &settings.split_study_topics.to_string(),
+    )?;
+    set_enum_setting(
+        &tx,
+        "study_session_position",
+        settings.study_session_position,
+    )?;
+    set_homework_days_ahead(&tx, settings.homework_days_ahead)?;
+    set_work_days(&tx, &settings.work_days)?;
+    set_review_imports(&tx, settings.review_imports)?;
+    set_weekly_goals(&tx, &settings.weekly_goals)?;
+    set_daily_budget_minutes(&tx, settings.daily_budget_minutes)?;
+    set_day_limit(
+        &tx,
+        "day_limit_entries",
+        settings.day_limit_entries,
+        MAX_DAY_LIMIT_ENTRIES,
+    )?;
+    set_day_limit(
+        &tx,
+        "day_limit_minutes",
+        settings.day_limit_minutes,
+        MAX_DAY_LIMIT_MINUTES,
+    )?;
+    set_setting(&tx, "check_updates", &settings.check_updates.to_string())?;
+    set_completion(&tx, &settings.completion)?;
+    set_setting(&tx, "ask_grade", &settings.ask_grade.to_string())?;
+    set_cors_origins(&tx, &settings.cors_origins)?;
+    tx.commit()?;
+    Ok(())
+}
+
+/// Whether parsed exports are staged for review (default) or imported directly
+pub fn get_review_imports(conn: &Connection) -> Result<bool> {
+    Ok(get_setting(conn, "review_imports")?
+        .map(|s| s != "false")
+        .unwrap_or(true))
+}
+
+pub fn set_review_imports(conn: &Connection, enabled: bool) -> Result<()> {
+    set_setting(conn, "review_imports", &enabled.to_string())
+}
+
+/// Get the list of allowed work-day weekday numbers (1=Mon … 5=Fri).
+/// Weekends (0=Sun, 6=Sat) are always allowed and are not stored here.
+/// Returns the stored list, or the default [1,2,3,4,5] if nothing is stored.
+pub fn get_work_days(conn: &Connection) -> Result<Vec<u32>> {
+    match get_setting(conn, "work_days")? {
+        Some(json) => {
+            let days: Vec<u32> =
+                serde_json::from_str(&json).unwrap_or_else(|_| vec![1, 2, 3, 4, 5]);
+            Ok(days)
+        }
+        None => Ok(vec![1, 2, 3, 4, 5]),
+    }
+}
+
+/// Save the list of allowed work-day weekday numbers.
+/// Only weekdays (1–5) are meaningful; weekends are always allowed.
+pub fn set_work_days(conn: &Connection, days: &[u32]) -> Result<()> {
+    let mut days: Vec<u32> = days
+        .iter()
+        .copied()
+        .filter(|&d| (1..=5).contains(&d))
+        .collect();
+    days.sort();
+    days.dedup();
+    let json = serde_json::to_string(&days)?;
+    set_setting(conn, "work_days", &json)
+}
+
+/// Get how many days ahead of the due date to place the work reminder (1 or 2).
+/// Default: 2.
+pub fn get_homework_days_ahead(conn: &Connection) -> Result<u32> {
+    let v = get_setting(conn, "homework_days_ahead")?
+        .and_then(|s| s.parse::<u32>().ok())
+        .unwrap_or(2);
+    Ok(v.clamp(1, 2))
+}
+
+pub fn set_homework_days_ahead(conn: &Connection, days: u32) -> Result<()> {
+    set_setting(conn, "homework_days_ahead", &days.clamp(1, 2).to_string())
+}
+
+/// Get how many study-session days to generate before a verifica (minimum 3).
+/// Default: 4.
+pub fn get_study_days_before(conn: &Connection) -> Result<u32> {
+    let v = get_setting(conn, "study_days_before")?
+        .and_then(|s| s.parse::<u32>().ok())
+        .unwrap_or(4);
+    Ok(v.max(3))
+}
+
+pub fn set_study_days_before(conn: &Connection, days: u32) -> Result<()> {
+    set_setting(conn, "study_days_before", &days.max(3).to_string())
+}
+
+/// Get the daily time budget for the "Start here" plan (15–600 minutes).
+/// Default: 120.
+pub fn get_daily_budget_minutes(conn: &Connection) -> Result<u32> {
+    let v = get_setting(conn, "daily_budget_minutes")?
+        .and_then(|s| s.parse::<u32>().ok())
+        .unwrap_or(120);
+    Ok(v.clamp(15, 600))
+}
+
+pub fn set_daily_budget_minutes(conn: &Connection, minutes: u32) -> Result<()> {
+    set_setting(
+        conn,
+        "daily_budget_minutes",
+        &minutes.clamp(15, 600).to_string(),
+    )
+}
+
+/// Widest default list window on either side of today
+const MAX_LIST_DAYS: u32 = 365;
+
+/// Days the list view shows before or after today
+/// (`list_days_back`/`list_days_ahead`)
+fn get_list_days(conn: &Connection, key: &str, default: u32) -> Result<u32> {
+    let v = get_setting(conn, key)?
+        .and_then(|s| s.parse::<u32>().ok())
+        .unwrap_or(default);
+    Ok(v.min(MAX_LIST_DAYS))
+}
+
+fn set_list_days(conn: &Connection, key: &str, days: u32) -> Result<()> {
+    set_setting(conn, key, &days.min(MAX_LIST_DAYS).to_string())
+}
+
+/// Highest busy-day limit in entries, as the settings page offers it
+const MAX_DAY_LIMIT_ENTRIES: u32 = 20;
+
+/// Highest busy-day limit in minutes, as the settings page offers it
+const MAX_DAY_LIMIT_MINUTES: u32 = 600;
+
+/// A busy-day warning threshold (`day_limit_entries`/`day_limit_minutes`,
+/// 0 = off), at most `max`
+fn get_day_limit(conn: &Connection, key: &str, default: u32, max: u32) -> Result<u32> {
+    let v = get_setting(conn, key)?
+        .and_then(|s| s.parse::<u32>().ok())
+        .unwrap_or(default);
+    Ok(v.min(max))
+}
+
+fn set_day_limit(conn: &Connection, key: &str, limit: u32, max: u32) -> Result<()> {
+    set_setting(conn, key, &limit.min(max).to_string())
+}
+
+/// Highest weekly target accepted for one subject
+const MAX_WEEKLY_GOAL: u32 = 50;
+
+/// Per-subject weekly goals, as stored (JSON). Default: none.
+pub fn get_weekly_goals(conn: &Connection) -> Result<Vec<WeeklyGoal>> {
+    Ok(get_setting(conn, "weekly_goals")?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
+/// Save the weekly goals. Subjects are trimmed, goals without a subject or
+/// with a zero target are dropped, and a subject set twice (in any case)
+/// keeps the later target.
+pub fn set_weekly_goals(conn: &Connection, goals: &[WeeklyGoal]) -> Result<()> {
+    let mut cleaned: Vec<WeeklyGoal> = Vec::new();
+    for goal in goals {
+        let subject = goal.subject.trim();
+        if subject.is_empty() || goal.target == 0 {
+            continue;
+        }
+        cleaned.retain(|g| !g.subject.eq_ignore_ascii_case(subject));
+        cleaned.push(WeeklyGoal {
+            subject: subject.to_string(),
+            target: goal.target.min(MAX_WEEKLY_GOAL),
+        });
+    }
+    set_setting(conn, "weekly_goals", &serde_json::to_string(&cleaned)?)
+}
+
+/// Origins allowed to call the API from a browser, as stored (JSON).
+/// Default: none.
+pub fn get_cors_origins(conn: &Connection) -> Result<Vec<String>> {
+    Ok(get_setting(conn, "cors_origins")?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
+/// Save the allowed origins in the form browsers send them
+/// (`cors::normalize_origin`), dropping anything that isn't an origin and
+/// repeats.
+pub fn set_cors_origins(conn: &Connection, origins: &[String]) -> Result<()> {
+    let mut cleaned: Vec<String> = Vec::new();
+    for origin in origins.iter().filter_map(|o| cors::normalize_origin(o)) {
+        if !cleaned.contains(&origin) {
+            cleaned.push(origin);
+        }
+    }
+    set_setting(conn, "cors_origins", &serde_json::to_string(&cleaned)?)
+}
+
+/// What ticking off means per entry type, as stored (JSON). Default:
+/// `types::default_completion()`.
+pub fn get_completion(conn: &Connection) -> Result<BTreeMap<String, Completion>> {
+    Ok(get_setting(conn, "completion")?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_else(default_completion))
+}
+
+/// Save the per-type completion. Types are trimmed and lowercased; blank
+/// ones are dropped.
+pub fn set_completion(conn: &Connection, completion: &BTreeMap<String, Completion>) -> Result<()> {
+    let cleaned: BTreeMap<String, Completion> = completion
+        .iter()
+        .map(|(t, c)| (t.trim().to_lowercase(), *c))
+        .filter(|(t, _)| !t.is_empty())
+        .collect();
+    set_setting(conn, "completion", &serde_json::to_string(&cleaned)?)
+}
+
+/// Feature flags switched through `/api/features`, by name. Flags never set
+/// are absent and count as on (see `features`).
+pub fn get_features(conn: &Connection) -> Result<BTreeMap<String, bool>> {
+    Ok(get_setting(conn, "features")?
+        .and_then(|json| serde_json::from_str(&json).ok())
+        .unwrap_or_default())
+}
+
+pub fn set_features(conn: &Connection, features: &BTreeMap<String, bool>) -> Result<()> {
+    set_setting(conn, "features", &serde_json::to_string(features)?)
+}
+
+/// Copy every migration shipped with the crate into `dir`, so tests get the
+/// same schema as a real database.
+#[cfg(test)]
+pub fn write_test_migrations(dir: &Path) {
+    let source = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("db")
+        .join("migrations");
+    for entry in std::fs::read_dir(source).unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().is_some_and(|ext| ext == "sql") {
+            std::fs::copy(&path, dir.join(path.file_name().unwrap())).unwrap();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup_test_db() -> (TempDir, Connection) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+
+        write_test_migrations(&migrations_dir);
+
+        let conn = init_db(&db_path, &migrations_dir).unwrap();
+        (temp_dir, conn)
+    }
+
+    fn make_entry(entry_type: &str, date: &str, subject: &str, task: &str) -> HomeworkEntry {
+        HomeworkEntry::new(
+            entry_type.to_string(),
+            date.to_string(),
+            subject.to_string(),
+            task.to_string(),
+        )
+    }
+
+    // ========== init_db tests ==========
+
+    #[test]
+    fn test_db_errors() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let migrations_dir = temp_dir.path().join("migrations");
+        let err = init_db(&db_path, &migrations_dir).unwrap_err();
+        assert!(matches!(err, DbError::Io { ref path, .. } if *path == migrations_dir));
+
+        let missing_dir = temp_dir.path().join("nope").join("test.db");
+        let err = open_db(&missing_dir).unwrap_err();
+        assert!(matches!(err, DbError::Open { ref path, .. } if *path == missing_dir));
+
+        std::fs::create_dir(&migrations_dir).unwrap();
+        write_test_migrations(&migrations_dir);
+        std::fs::write(migrations_dir.join("999_broken.sql"), "CREATE TABLE (;").unwrap();
+        let err = init_db(&db_path, &migrations_dir).unwrap_err();
+        assert!(matches!(err, DbError::Migration { ref version, .. } if version == "999_broken"));
+
+        std::fs::remove_file(migrations_dir.join("999_broken.sql")).unwrap();
+        let conn = init_db(&db_path, &migrations_dir).unwrap();
+        let err = conn
+            .query_row("SELECT id FROM entries", [], |row| row.get::<_, String>(0))
+            .map_err(DbError::from)
+            .unwrap_err();
+        // Only the caller knows what was missing
+        assert!(matches!(
+            err,
+            DbError::Sqlite(rusqlite::Error::QueryReturnedNoRows)
+        ));
+    }
+
+    #[test]
+    fn test_failed_migration_rolls_back() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+        write_test_migrations(&migrations_dir);
+        // The first statement works, the second doesn't
+        std::fs::write(
+            migrations_dir.join("998_half.sql"),
+            "CREATE TABLE half (id INTEGER);\nINSERT INTO missing VALUES (1);",
+        )
+        .unwrap();
+        let err = init_db(&db_path, &migrations_dir).unwrap_err();
+        assert!(matches!(err, DbError::Migration { ref version, .. } if version == "998_half"));
+
+        let conn = open_db(&db_path).unwrap();
+        let half: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM sqlite_master WHERE name = 'half'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(!half);
+        let status = migration_status(&conn, &migrations_dir).unwrap();
+        let last = status.last().unwrap();
+        assert_eq!(last.version, "998_half");
+        assert_eq!(last.applied_at, None);
+        // Everything before it is applied
+        assert!(status[..status.len() - 1]
+            .iter()
+            .all(|m| m.applied_at.is_some()));
+        let journal: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(journal, "wal");
+
+        // Fixed, it applies on the next start
+        std::fs::write(
+            migrations_dir.join("998_half.sql"),
+            "CREATE TABLE half (id INTEGER);",
+        )
+        .unwrap();
+        drop(conn);
+        let conn = init_db(&db_path, &migrations_dir).unwrap();
+        assert_eq!(
+            get_schema_version(&conn).unwrap().as_deref(),
+            Some("998_half")
+        );
+    }
+
+    #[test]
+    fn test_migration_status_and_new() {
+        let temp_dir = TempDir::new().unwrap();
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+        write_test_migrations(&migrations_dir);
+        let conn = open_db(&temp_dir.path().join("test.db")).unwrap();
+        let status = migration_status(&conn, &migrations_dir).unwrap();
+        assert_eq!(status[0].version, "001_initial_schema");
+        assert!(status.iter().all(|m| m.applied_at.is_none() && m.on_disk));
+
+        let now = NaiveDate::from_ymd_opt(2026, 10, 16)
+            .unwrap()
+            .and_hms_opt(9, 5, 0)
+            .unwrap();
+        let path = new_migration(&migrations_dir, " Add Due-Time column ", now).unwrap();
+        assert_eq!(
+            path.file_name().unwrap(),
+            "20261016090500_add_due_time_column.sql"
+        );
+        assert!(new_migration(&migrations_dir, "Add due-time column", now).is_err());
+        assert!(new_migration(&migrations_dir, "--", now).is_err());
+
+        run_migrations(&conn, &migrations_dir).unwrap();
+        std::fs::remove_file(&path).unwrap();
+        let status = migration_status(&conn, &migrations_dir).unwrap();
+        let last = status.last().unwrap();
+        assert_eq!(last.version, "20261016090500_add_due_time_column");
+        assert!(last.applied_at.is_some());
+        assert!(!last.on_disk);
+    }
+
+    #[test]
+    fn test_init_db_creates_tables() {
+        let (_temp_dir, conn) = setup_test_db();
+
+        // Check that entries table exists
+        let table_exists: bool = conn
+            .query_row(
+                "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='entries'",
+                [],
+                |row| row.get(0),
+            )
+            .unwrap();
+        assert!(table_exists);
+    }
+
+    #[test]
+    fn test_init_db_idempotent() {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+
+        write_test_migrations(&migrations_dir);
+
+        // Initialize twice
+        let _conn1 = init_db(&db_path, &migrations_dir).unwrap();
+        drop(_conn1);
+        let conn2 = init_db(&db_path, &migrations_dir).unwrap();
+
+        // Should still work
+        let count = count_entries(&conn2).unwrap();
+        assert_eq!(count, 0);
+    }
+
+    // ========== CRUD tests ==========
+
+    #[test]
+    fn test_insert_and_get_entry() {
+        let (_temp_dir, conn) = setup_test_db();
+        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+
+        insert_entry(&conn, &entry).unwrap();
+
+        let retrieved = get_entry(&conn, &entry.id).unwrap();
+        assert!(retrieved.is_some());
+        let retrieved = retrieved.unwrap();
+        assert_eq!(retrieved.id, entry.id);
+        assert_eq!(retrieved.entry_type, "compiti");
+        assert_eq!(retrieved.date, "2025-01-15");
+        assert_eq!(retrieved.subject, "Matematica");
+        assert_eq!(retrieved.task, "Task 1");
+    }
+
+    #[test]
+    fn test_get_nonexistent_entry() {
+        let (_temp_dir, conn) = setup_test_db();
+        let retrieved = get_entry(&conn, "nonexistent").unwrap();
+        assert!(retrieved.is_none());
+    }
+
+    #[test]
+    fn test_get_all_entries_sorted() {
+        let (_temp_dir, conn) = setup_test_db();
+
+        let entry1 = make_entry("compiti", "2025-01-20", "Matematica", "Task 3");
+        let entry2 = make_entry("nota", "2025-01-10", "Italiano", "Task 1");
+        let entry3 = make_entry("compiti", "2025-01-15", "INGLESE", "Task 2");
+
+        insert_entry(&conn, &entry1).unwrap();
+        insert_entry(&conn, &entry2).unwrap();
+        insert_entry(&conn, &entry3).unwrap();
+
+        let entries = get_all_entries(&conn).unwrap();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0].date, "2025-01-10");
+        assert_eq!(entries[1].date, "2025-01-15");
+        assert_eq!(entries[2].date, "2025-01-20");
+    }
+
+    #[test]
+    fn test_get_entries_in_range() {
+        let (_temp_dir, conn) = setup_test_db();
+        for date in ["2025-01-31", "2025-02-01", "2025-02-28", "2025-03-01"] {
+            insert_entry(&conn, &make_entry("compiti", date, "Matematica", date)).unwrap();
+        }
+
+        let entries = get_entries_in_range(&conn, "2025-02-01", "2025-02-28").unwrap();
+        let dates: Vec<&str> = entries.iter().map(|e| e.date.as_str()).collect();
+        assert_eq!(dates, vec!["2025-02-01", "2025-02-28"]);
+    }
+
+    #[test]
+    fn test_get_entries_filtered() {
+        let (_temp_dir, conn) = setup_test_db();
+        for (entry_type, date, subject) in [
+            ("compiti", "2025-01-10", "Matematica"),
+            ("verifica", "2025-01-15", "Matematica"),
+            ("Verifica", "2025-01-20", "Storia"),
+            ("interrogazione", "2025-02-05", "Storia"),
+            ("compiti", "2025-02-10", "ATTIVITÀ ALTERNATIVA"),
+        ] {
+            insert_entry(&conn, &make_entry(entry_type, date, subject, date)).unwrap();
+        }
+        let dates = |filter: EntryFilter| -> Vec<String> {
+            get_entries_filtered(&conn, &filter)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.date)
+                .collect()
+        };
+
+        assert_eq!(dates(EntryFilter::default()).len(), 5);
+        assert_eq!(
+            dates(EntryFilter {
+                types: vec!["verifica".to_string()],
+                ..Default::default()
+            }),
+            vec!["2025-01-15", "2025-01-20"]
+        );
+        assert_eq!(
+            dates(EntryFilter {
+                from: Some("2025-01-12".to_string()),
+                to: Some("2025-01-31".to_string()),
+                subjects: vec![" matematica".to_string()],
+                ..Default::default()
+            }),
+            vec!["2025-01-15"]
+        );
+        assert_eq!(
+            dates(EntryFilter {
+                subjects: vec!["Storia".to_string()],
+                types: vec!["verifica".to_string(), "interrogazione".to_string()],
+                ..Default::default()
+            }),
+            vec!["2025-01-20", "2025-02-05"]
+        );
+        // Accented letters match in any case too
+        assert_eq!(
+            dates(EntryFilter {
+                subjects: vec!["Attività alternativa".to_string()],
+                ..Default::default()
+            }),
+            vec!["2025-02-10"]
+        );
+
+        let mut done = make_entry("compiti", "2025-01-25", "Storia", "Done");
+        done.completed = true;
+        insert_entry(&conn, &done).unwrap();
+        assert_eq!(
+            dates(EntryFilter {
+                completed: Some(true),
+                ..Default::default()
+            }),
+            vec!["2025-01-25"]
+        );
+        assert_eq!(
+            dates(EntryFilter {
+                subjects: vec!["storia".to_string()],
+                completed: Some(false),
+                ..Default::default()
+            }),
+            vec!["2025-01-20", "2025-02-05"]
+        );
+    }
+
+    #[test]
+    fn test_count_completion_and_date_bounds() {
+        let (_temp_dir, conn) = setup_test_db();
+        assert_eq!(count_completion(&conn).unwrap(), (0, 0));
+        assert_eq!(get_date_bounds(&conn).unwrap(), None);
+
+        let mut done = make_entry("compiti", "2025-03-01", "Storia", "B");
+        done.completed = true;
+        insert_entry(&conn, &done).unwrap();
+        insert_entry(&conn, &make_entry("compiti", "2025-01-31", "Storia", "A")).unwrap();
+
+        assert_eq!(count_completion(&conn).unwrap(), (2, 1));
+        assert_eq!(
+            get_date_bounds(&conn).unwrap(),
+            Some(("2025-01-31".to_string(), "2025-03-01".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_insert_entry_if_not_exists() {
+        let (_temp_dir, conn) = setup_test_db();
+        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+
+        // First insert should succeed
+        let inserted = insert_entry_if_not_exists(&conn, &entry).unwrap();
+        assert!(inserted);
+
+        // Second insert should be ignored
+        let inserted = insert_entry_if_not_exists(&conn, &entry).unwrap();
+        assert!(!inserted);
+
+        // Only one entry should exist
+        assert_eq!(count_entries(&conn).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_update_entry() {
+        let (_temp_dir, conn) = setup_test_db();
+        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        insert_entry(&conn, &entry).unwrap();
+
+        let updates = EntryUpdate {
+            completed: Some(true),
+            position: Some(5),
+            ..Default::default()
+        };
+
+        let updated = update_entry(&conn, &entry.id, &updates).unwrap();
+        assert!(updated);
+
+        let retrieved = get_entry(&conn, &entry.id).unwrap().unwrap();
+        assert!(retrieved.completed);
+        assert_eq!(retrieved.position, 5);
+    }
+
+    #[test]
+    fn test_update_entry_time_feedback() {
+        let (_temp_dir, conn) = setup_test_db();
+        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        insert_entry(&conn, &entry).unwrap();
+
+        let updates = EntryUpdate {
+            completed: Some(true),
+            estimated_minutes: Some(Some(20)),
+            actual_minutes: Some(Some(35)),
+            difficulty: Some(Some(4)),
+            ..Default::default()
+        };
+        assert!(update_entry(&conn, &entry.id, &updates).unwrap());
+
+        let updated = get_entry(&conn, &entry.id).unwrap().unwrap();
+        assert_eq!(updated.estimated_minutes, Some(20));
+        assert_eq!(updated.actual_minutes, Some(35));
+        assert_eq!(updated.difficulty, Some(4));
+    }
+
+    #[test]
+    fn test_pinned_entries_sort_first_in_their_day() {
+        let (_temp_dir, conn) = setup_test_db();
+        let mut first = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        first.position = 0;
+        let mut second = make_entry("verifica", "2025-01-15", "Storia", "Task 2");
+        second.position = 1;
+        insert_entry(&conn, &first).unwrap();
+        insert_entry(&conn, &second).unwrap();
+
+        let updates = EntryUpdate {
+            pinned: Some(true),
+            color: Some(Some("#ffaa00".to_string())),
+            ..Default::default()
+        };
+        assert!(update_entry(&conn, &second.id, &updates).unwrap());
+
+        let all = get_all_entries(&conn).unwrap();
+        assert_eq!(all[0].id, second.id);
+        assert!(all[0].pinned);
+        assert_eq!(all[0].color.as_deref(), Some("#ffaa00"));
+
+        // Some(None) clears the color; pinned stays as it was
+        let updates = EntryUpdate {
+            color: Some(None),
+            ..Default::default()
+        };
+        update_entry(&conn, &second.id, &updates).unwrap();
+        let updated = get_entry(&conn, &second.id).unwrap().unwrap();
+        assert!(updated.pinned);
+        assert!(updated.color.is_none());
+    }
+
+    #[test]
+    fn test_update_entry_grade() {
+        let (_temp_dir, conn) = setup_test_db();
+        let test = make_entry("verifica", "2025-01-15", "Storia", "Cap. 5");
+        insert_entry(&conn, &test).unwrap();
+        assert!(get_settings(&conn).unwrap().ask_grade);
+
+        let updates = EntryUpdate {
+            completed: Some(true),
+            grade: Some(Some("7½".to_string())),
+            ..Default::default()
+        };
+        update_entry(&conn, &test.id, &updates).unwrap();
+        let updated = get_entry(&conn, &test.id).unwrap().unwrap();
+        assert_eq!(updated.grade.as_deref(), Some("7½"));
+
+        let updates = EntryUpdate {
+            grade: Some(None),
+            ..Default::default()
+        };
+        update_entry(&conn, &test.id, &updates).unwrap();
+        assert!(get_entry(&conn, &test.id).unwrap().unwrap().grade.is_none());
+    }
+
+    #[test]
+    fn test_difficulty_out_of_range_rejected() {
+        let (_temp_dir, conn) = setup_test_db();
+        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        insert_entry(&conn, &entry).unwrap();
+
+        let updates = EntryUpdate {
+            difficulty: Some(Some(6)),
+            ..Default::default()
+        };
+        assert!(update_entry(&conn, &entry.id, &updates).is_err());
+    }
+
+    // ========== Pending imports tests ==========
+
+    #[test]
+    fn test_stage_imports_skips_known_entries() {
+        let (_temp_dir, conn) = setup_test_db();
+        let existing = make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10");
+        insert_entry(&conn, &existing).unwrap();
+
+        let parsed = vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10"),
+            make_entry("nota", "2025-01-16", "Italiano", "Portare il libro"),
+        ];
+        assert_eq!(stage_imports(&conn, &parsed).unwrap(), 1);
+        // Staging the same parse again is a no-op
+        assert_eq!(stage_imports(&conn, &parsed).unwrap(), 0);
+
+        let pending = get_pending_imports(&conn).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].subject, "Italiano");
+        assert!(!pending[0].is_change());
+        assert_eq!(count_entries(&conn).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_stage_imports_detects_changed_task() {
+        let (_temp_dir, conn) = setup_test_db();
+        let existing = make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10");
+        insert_entry(&conn, &existing).unwrap();
+
+        let parsed = vec![make_entry(
+            "compiti",
+            "2025-01-15",
+            "Matematica",
+            "Pag. 10-12",
+        )];
+        stage_imports(&conn, &parsed).unwrap();
+
+        let pending = get_pending_imports(&conn).unwrap();
+        assert_eq!(
+            pending[0].replaces_id.as_deref(),
+            Some(existing.id.as_str())
+        );
+        assert_eq!(pending[0].previous_task.as_deref(), Some("Pag. 10"));
+    }
+
+    #[test]
+    fn test_stage_imports_new_row_next_to_exported_entry() {
+        let (_temp_dir, conn) = setup_test_db();
+        let existing = make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10");
+        insert_entry(&conn, &existing).unwrap();
+
+        // The existing entry's row is still in the export: a second
+        // assignment that day, not a change
+        let parsed = vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10"),
+            make_entry("compiti", "2025-01-15", "Matematica", "Es. 4"),
+        ];
+        assert_eq!(stage_imports(&conn, &parsed).unwrap(), 1);
+        let pending = get_pending_imports(&conn).unwrap();
+        assert!(!pending[0].is_change());
+
+        let entry = accept_pending_import(&conn, &pending[0].id)
+            .unwrap()
+            .unwrap();
+        assert_ne!(entry.id, existing.id);
+        assert_eq!(
+            get_entry(&conn, &existing.id).unwrap().unwrap().task,
+            "Pag. 10"
+        );
+        assert_eq!(count_entries(&conn).unwrap(), 2);
+    }
+
+    #[test]
+    fn test_accept_pending_import_new_entry() {
+        let (_temp_dir, conn) = setup_test_db();
+        let parsed = make_entry("nota", "2025-01-16", "Italiano", "Portare il libro");
+        stage_imports(&conn, std::slice::from_ref(&parsed)).unwrap();
+
+        let entry = accept_pending_import(&conn, &parsed.id).unwrap().unwrap();
+        assert_eq!(entry.task, "Portare il libro");
+        assert_eq!(entry.source_id, parsed.source_id);
+        assert_eq!(count_entries(&conn).unwrap(), 1);
+        assert_eq!(count_pending_imports(&conn).unwrap(), 0);
+
+        // Accepted entries are not staged again
+        assert_eq!(stage_imports(&conn, &[parsed]).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_accept_pending_import_change_keeps_entry_state() {
+        let (_temp_dir, conn) = setup_test_db();
+        let mut existing = make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10");
+        existing.completed = true;
+        existing.source_file = Some("export_2025-01-10.xls".to_string());
+        insert_entry(&conn, &existing).unwrap();
+
+        let parsed = make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10-12");
+        stage_imports(&conn, std::slice::from_ref(&parsed)).unwrap();
+
+        let entry = accept_pending_import(&conn, &parsed.id).unwrap().unwrap();
+        assert_eq!(entry.id, existing.id);
+        assert_eq!(entry.task, "Pag. 10-12");
+        assert_eq!(entry.source_id, parsed.source_id);
+        assert!(entry.completed);
+        // The old export has the old text; the next scan records the new one
+        assert_eq!(entry.source_file, None);
+        let mut rescanned = parsed.clone();
+        rescanned.source_file = Some("export_2025-01-16.xls".to_string());
+        assert_eq!(record_source_files(&conn, &[rescanned]).unwrap(), 1);
+        assert_eq!(count_entries(&conn).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_accept_unknown_pending_import() {
+        let (_temp_dir, conn) = setup_test_db();
+        assert!(accept_pending_import(&conn, "missing").unwrap().is_none());
+    }
+
+    #[test]
+    fn test_reject_pending_import_is_remembered() {
+        let (_temp_dir, conn) = setup_test_db();
+        let parsed = make_entry("nota", "2025-01-16", "Italiano", "Bad parse");
+        stage_imports(&conn, std::slice::from_ref(&parsed)).unwrap();
+
+        assert!(reject_pending_import(&conn, &parsed.id).unwrap());
+        assert!(!reject_pending_import(&conn, &parsed.id).unwrap());
+        assert_eq!(count_pending_imports(&conn).unwrap(), 0);
+        assert_eq!(stage_imports(&conn, &[parsed]).unwrap(), 0);
+        assert_eq!(count_entries(&conn).unwrap(), 0);
+    }
+
+    #[test]
+    fn test_review_imports_setting() {
+        let (_temp_dir, conn) = setup_test_db();
+        assert!(get_review_imports(&conn).unwrap());
+        set_review_imports(&conn, false).unwrap();
+        assert!(!get_review_imports(&conn).unwrap());
+    }
+
+    // ========== Settings tests ==========
+
+    #[test]
+    fn test_get_settings_defaults() {
+        let (_temp_dir, conn) = setup_test_db();
+        assert_eq!(get_settings(&conn).unwrap(), Settings::default());
+    }
+
+    #[test]
+    fn test_save_settings_round_trip_and_clamps() {
+        use crate::types::{Language, Theme, View};
+
+        let (_temp_dir, conn) = setup_test_db();
+        let settings = Settings {
+            default_view: View::Calendar,
+            hide_completed: true,
+            list_days_back: 0,
+            list_days_ahead: 1000,
+            theme: Theme::Light,
+            language: Language::It,
+            study_sessions: false,
+            study_days_before: 1,
+            split_study_topics: false,
+            study_session_position: SessionPosition::Priority,
+            homework_days_ahead: 7,
+            work_days: vec![5, 1, 9],
+            review_imports: false,
+            weekly_goals: vec![
+                WeeklyGoal {
+                    subject: " Matematica ".to_string(),
+                    target: 2,
+                },
+                WeeklyGoal {
+                    subject: "Storia".to_string(),
+                    target: 0,
+                },
+                WeeklyGoal {
+                    subject: "MATEMATICA".to_string(),
+                    target: 99,
+                },
+            ],
+            daily_budget_minutes: 5,
+            day_limit_entries: 99,
+            day_limit_minutes: 9999,
+            check_updates: true,
+            completion: [
+                (" Verifica ".to_string(), Completion::Done),
+                ("nota".to_string(), Completion::Happened),
+            ]
+            .into_iter()
+            .collect(),
+            ask_grade: false,
+            cors_origins: vec![
+                "https://App.example.org/".to_string(),
+                "not an origin".to_string(),
+                "https://app.example.org".to_string(),
+                "*".to_string(),
+            ],
+        };
+        save_settings(&conn, &settings).unwrap();
+
+        let stored = get_settings(&conn).unwrap();
+        assert_eq!(stored.default_view, View::Calendar);
+        assert!(stored.hide_completed);
+        assert_eq!((stored.list_days_back, stored.list_days_ahead), (0, 365));
+        assert_eq!(stored.theme, Theme::Light);
+        assert_eq!(stored.language, Language::It);
+        assert!(!stored.study_sessions);
+        assert_eq!(stored.study_days_before, 3);
+        assert!(!stored.split_study_topics);
+        assert_eq!(stored.study_session_position, SessionPosition::Priority);
+        assert_eq!(stored.homework_days_ahead, 2);
+        assert_eq!(stored.work_days, vec![1, 5]);
+        assert!(!stored.review_imports);
+        assert_eq!(stored.daily_budget_minutes, 15);
+        assert_eq!(stored.day_limit_entries, 20);
+        assert_eq!(stored.day_limit_minutes, 600);
+        assert!(stored.check_updates);
+        assert!(!stored.ask_grade);
+        assert_eq!(stored.cors_origins, vec!["https://app.example.org", "*"]);
+        assert_eq!(stored.completion_for("verifica"), Completion::Done);
+        assert_eq!(stored.completion_for("Nota"), Completion::Happened);
+        // Not mentioned any more, so back to the default
+        assert_eq!(stored.completion_for("interrogazione"), Completion::Done);
+        assert_eq!(
+            stored.weekly_goals,
+            vec![WeeklyGoal {
+                subject: "MATEMATICA".to_string(),
+                target: 50,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_unknown_enum_setting_falls_back_to_default() {
+        let (_temp_dir, conn) = setup_test_db();
+        set_setting(&conn, "theme", "neon").unwrap();
+        assert_eq!(
+            get_settings(&conn).unwrap().theme,
+            Settings::default().theme
+        );
+    }
+
+    // ========== Holidays tests ==========
+
+    #[test]
+    fn test_replace_holidays_and_query() {
+        let (_temp_dir, conn) = setup_test_db();
+        let holiday = |date: &str, name: &str| Holiday {
+            date: date.to_string(),
+            name: name.to_string(),
+        };
+
+        let stored = replace_holidays(
+            &conn,
+            &[
+                holiday("2025-12-24", "Natale"),
+                holiday("2025-12-25", "Natale"),
+                holiday("2026-01-06", "Epifania"),
+            ],
+        )
+        .unwrap();
+        assert_eq!(stored, 3);
+
+        let december = get_holidays_in_range(&conn, "2025-12-01", "2025-12-31").unwrap();
+        assert_eq!(december.len(), 2);
+        assert_eq!(december[0].date, "2025-12-24");
+
+        // A second import replaces the first
+        replace_holidays(&conn, &[holiday("2026-04-03", "Pasqua")]).unwrap();
+        let dates = get_holiday_dates(&conn).unwrap();
+        assert_eq!(dates.len(), 1);
+        assert!(dates.contains(&NaiveDate::from_ymd_opt(2026, 4, 3).unwrap()));
+    }
+
+    // ========== Activities tests ==========
+
+    #[test]
+    fn test_replace_activities_and_query() {
+        let (_temp_dir, conn) = setup_test_db();
+        let activity = |date: &str, name: &str, start: Option<&str>, minutes| Activity {
+            date: date.to_string(),
+            name: name.to_string(),
+            start_time: start.map(str::to_string),
+            end_time: None,
+            minutes,
+        };
+
+        let stored = replace_activities(
+            &conn,
+            &[
+                activity("2025-01-14", "Nuoto", Some("17:00"), 90),
+                activity("2025-01-14", "Inglese", Some("15:00"), 60),
+                activity("2025-01-18", "Torneo", None, 240),
+            ],
+        )
+        .unwrap();
+        assert_eq!(stored, 3);
+
+        let tuesday = get_activities_in_range(&conn, "2025-01-14", "2025-01-14").unwrap();
+        let names: Vec<&str> = tuesday.iter().map(|a| a.name.as_str()).collect();
+        assert_eq!(names, vec!["Inglese", "Nuoto"]);
+
+        let minutes = get_activity_minutes(&conn).unwrap();
+        assert_eq!(minutes[&NaiveDate::from_ymd_opt(2025, 1, 14).unwrap()], 150);
+        assert_eq!(minutes[&NaiveDate::from_ymd_opt(2025, 1, 18).unwrap()], 240);
+
+        // A second import replaces the first
+        replace_activities(&conn, &[]).unwrap();
+        assert!(get_activity_minutes(&conn).unwrap().is_empty());
+
+        assert_eq!(get_activities_url(&conn).unwrap(), None);
+        set_activities_url(&conn, Some("https://example.com/nuoto.ics")).unwrap();
+        assert_eq!(
+            get_activities_url(&conn).unwrap().as_deref(),
+            Some("https://example.com/nuoto.ics")
+        );
+        set_activities_url(&conn, None).unwrap();
+        assert_eq!(get_activities_url(&conn).unwrap(), None);
+    }
+
+    // ========== Entry link tests ==========
+
+    #[test]
+    fn test_entry_links_create_query_and_cascade() {
+        let (_temp_dir, conn) = setup_test_db();
+        let reading = make_entry("compiti", "2025-01-15", "Scienze", "Leggere cap. 3");
+        let report = make_entry(
+            "compiti",
+            "2025-01-17",
+            "Scienze",
+            "Relazione di laboratorio",
+        );
+        let other = make_entry("nota", "2025-01-17", "Scienze", "Portare il camice");
+        for entry in [&reading, &report, &other] {
+            insert_entry(&conn, entry).unwrap();
+        }
+
+        let dep = create_link(&conn, &report.id, &reading.id, LinkRelation::DependsOn).unwrap();
+        assert_eq!(dep.from_id, report.id);
+        let err = create_link(&conn, &report.id, &reading.id, LinkRelation::DependsOn).unwrap_err();
+        assert!(is_unique_violation(&err));
+
+        // related is symmetric: the reverse link is the same one
+        create_link(&conn, &other.id, &report.id, LinkRelation::Related).unwrap();
+        let err = create_link(&conn, &report.id, &other.id, LinkRelation::Related).unwrap_err();
+        assert!(is_unique_violation(&err));
+
+        assert_eq!(get_links(&conn).unwrap().len(), 2);
+        assert_eq!(get_links_for_entry(&conn, &report.id).unwrap().len(), 2);
+        assert!(depends_on_reaches(&conn, &report.id, &reading.id).unwrap());
+        assert!(!depends_on_reaches(&conn, &reading.id, &report.id).unwrap());
+
+        // Deleting an entry removes its links
+        delete_entry(&conn, &reading.id).unwrap();
+        assert_eq!(get_links(&conn).unwrap().len(), 1);
+        assert!(delete_link(&conn, get_links(&conn).unwrap()[0].id).unwrap());
+        assert!(get_links(&conn).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_cross_listed_entries() {
+        let (_temp_dir, conn) = setup_test_db();
+        let mut storia = make_entry(
+            "compiti",
+            "2025-01-20",
+            "Storia",
+            "Ricerca sul Rinascimento",
+        );
+        storia.created_at = "2025-01-10T08:00:00+00:00".to_string();
+        let mut arte = make_entry("compiti", "2025-01-20", "Arte", "Ricerca sul rinascimento");
+        arte.created_at = "2025-01-10T08:00:01+00:00".to_string();
+        let mut reminder = make_entry("lavoro", "2025-01-19", "Arte", "Ricerca sul rinascimento");
+        reminder.parent_id = Some(arte.id.clone());
+        for entry in [&storia, &arte, &reminder] {
+            insert_entry(&conn, entry).unwrap();
+        }
+
+        assert_eq!(link_cross_listed(&conn).unwrap(), 1);
+        assert_eq!(link_cross_listed(&conn).unwrap(), 0);
+        assert!(is_cross_listed_copy(&conn, &arte.id).unwrap());
+        assert!(get_entry(&conn, &reminder.id).unwrap().is_none());
+        let (subjects, primaries) = get_cross_listings(&conn).unwrap();
+        assert_eq!(subjects[&storia.id], vec!["Arte".to_string()]);
+        assert_eq!(primaries[&arte.id], storia.id);
+
+        // The copy gets no study sessions and is done with its primary
+        assert_eq!(
+            insert_generated(&conn, &arte, &[reminder.clone()]).unwrap(),
+            0
+        );
+        let done = EntryUpdate {
+            completed: Some(true),
+            ..Default::default()
+        };
+        update_entry(&conn, &storia.id, &done).unwrap();
+        assert!(get_entry(&conn, &arte.id).unwrap().unwrap().completed);
+        // ... and the other way round
+        let undone = EntryUpdate {
+            completed: Some(false),
+            ..Default::default()
+        };
+        update_entry(&conn, &arte.id, &undone).unwrap();
+        assert!(!get_entry(&conn, &storia.id).unwrap().unwrap().completed);
+
+        // One primary per copy, and no copies of copies
+        assert!(cross_list_conflict(&conn, &arte.id, &reminder.id)
+            .unwrap()
+            .is_some());
+        assert!(cross_list_conflict(&conn, &storia.id, &arte.id)
+            .unwrap()
+            .is_some());
+        let other = make_entry("compiti", "2025-01-21", "Musica", "Altro");
+        insert_entry(&conn, &other).unwrap();
+        assert!(cross_list_conflict(&conn, &storia.id, &other.id)
+            .unwrap()
+            .is_some());
+        assert!(cross_list_conflict(&conn, &other.id, &storia.id)
+            .unwrap()
+            .is_none());
+
+        // Split apart, they stay apart
+        let link = get_links(&conn).unwrap().remove(0);
+        let related = split_cross_listed(&conn, link.id).unwrap().unwrap();
+        assert_eq!(related.relation, LinkRelation::Related);
+        assert!(split_cross_listed(&conn, related.id).unwrap().is_none());
+        assert_eq!(link_cross_listed(&conn).unwrap(), 0);
+        assert!(!is_cross_listed_copy(&conn, &arte.id).unwrap());
+    }
+
+    // ========== Reaction tests ==========
+
+    #[test]
+    fn test_reactions_add_query_and_cascade() {
+        let (_temp_dir, conn) = setup_test_db();
+        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10");
+        let other = make_entry("compiti", "2025-01-15", "Storia", "Cap. 3");
+        insert_entry(&conn, &entry).unwrap();
+        insert_entry(&conn, &other).unwrap();
+
+        let star = add_reaction(&conn, &entry.id, Some("⭐"), None, Some("Mamma")).unwrap();
+        assert_eq!(star.emoji.as_deref(), Some("⭐"));
+        assert_eq!(star.author.as_deref(), Some("Mamma"));
+        add_reaction(&conn, &entry.id, None, Some("Bravissima!"), None).unwrap();
+        add_reaction(&conn, &other.id, Some("🎉"), None, None).unwrap();
+
+        let reactions = get_reactions_for_entry(&conn, &entry.id).unwrap();
+        assert_eq!(reactions.len(), 2);
+        assert_eq!(reactions[1].message.as_deref(), Some("Bravissima!"));
+        assert_eq!(get_reactions(&conn).unwrap().len(), 3);
+
+        assert!(delete_reaction(&conn, star.id).unwrap());
+        assert!(!delete_reaction(&conn, star.id).unwrap());
+        // Deleting an entry removes its reactions
+        delete_entry(&conn, &entry.id).unwrap();
+        assert_eq!(get_reactions(&conn).unwrap().len(), 1);
+    }
+
+    // ========== Stats tests ==========
+
+    #[test]
+    fn test_get_subject_time_stats() {
+        let (_temp_dir, conn) = setup_test_db();
+
+        let mut a = make_entry("compiti", "2025-01-15", "Matematica", "A");
+        a.completed = true;
+        a.estimated_minutes = Some(20);
+        a.actual_minutes = Some(30);
+        a.difficulty = Some(3);
+        let mut b = make_entry("compiti", "2025-01-16", "Matematica", "B");
+        b.completed = true;
+        b.estimated_minutes = Some(20);
+        b.actual_minutes = Some(50);
+        b.difficulty = Some(5);
+        // Completed without feedback: counted, but not in averages
+        let mut c = make_entry("compiti", "2025-01-17", "Matematica", "C");
+        c.completed = true;
+        // Not completed: ignored
+        let mut d = make_entry("compiti", "2025-01-18", "Italiano", "D");
+        d.actual_minutes = Some(10);
+        for entry in [&a, &b, &c, &d] {
+            insert_entry(&conn, entry).unwrap();
+        }
+
+        let stats = get_subject_time_stats(&conn).unwrap();
+        assert_eq!(stats.len(), 1);
+        let math = &stats[0];
+        assert_eq!(math.subject, "Matematica");
+        assert_eq!(math.completed, 3);
+        assert_eq!(math.with_feedback, 2);
+        assert_eq!(math.avg_estimated_minutes, Some(20.0));
+        assert_eq!(math.avg_actual_minutes, Some(40.0));
+        assert_eq!(math.avg_difficulty, Some(4.0));
+        assert_eq!(math.estimate_ratio, Some(2.0));
+    }
+
+    #[test]
+    fn test_get_period_stats() {
+        let (_temp_dir, conn) = setup_test_db();
+
+        let mut a = make_entry("compiti", "2025-01-15", "Matematica", "A");
+        a.created_at = "2025-01-10T08:00:00+00:00".to_string();
+        a.completed = true;
+        let mut b = make_entry("verifica", "2025-01-20", "Matematica", "B");
+        b.created_at = "2025-01-10T08:00:00+00:00".to_string();
+        let mut c = make_entry("compiti", "2025-01-16", "Storia", "C");
+        // Imported after it was due: no lead time
+        c.created_at = "2025-01-18T08:00:00+00:00".to_string();
+        // Generated study session and out-of-range entry: ignored
+        let mut d = make_entry("studio", "2025-01-14", "Matematica", "D");
+        d.parent_id = Some(b.id.clone());
+        let e = make_entry("compiti", "2025-03-01", "Storia", "E");
+        for entry in [&a, &b, &c, &d, &e] {
+            insert_entry(&conn, entry).unwrap();
+        }
+
+        let stats = get_period_stats(&conn, "2025-01-01", "2025-01-31").unwrap();
+        assert_eq!(stats.total.entries, 3);
+        assert_eq!(stats.total.tests, 1);
+        assert_eq!(stats.total.completed, 1);
+        assert_eq!(stats.total.avg_lead_days, Some(7.5));
+        assert_eq!(stats.subjects.len(), 2);
+
+        let math = stats.subject("Matematica").unwrap();
+        assert_eq!(math.entries, 2);
+        assert_eq!(math.completion_rate, Some(0.5));
+        let history = stats.subject("Storia").unwrap();
+        assert_eq!(history.avg_lead_days, None);
+
+        let empty = get_period_stats(&conn, "2024-01-01", "2024-01-31").unwrap();
+        assert_eq!(empty.total.entries, 0);
+        assert_eq!(empty.total.completion_rate, None);
+        assert!(empty.subjects.is_empty());
+    }
+
+    #[test]
+    fn test_get_goal_progress() {
+        let (_temp_dir, conn) = setup_test_db();
+        let done = |entry_type: &str, date: &str, subject: &str| {
+            let mut entry = make_entry(entry_type, date, subject, &format!("{entry_type} {date}"));
+            entry.completed = true;
+            entry
+        };
+        for entry in [
+            done("studio", "2025-01-13", "Matematica"),
+            done("compiti", "2025-01-19", "MATEMATICA"),
+            // Outside the week, not the right type, or not done
+            done("compiti", "2025-01-20", "Matematica"),
+            done("verifica", "2025-01-14", "Matematica"),
+            make_entry("studio", "2025-01-15", "Matematica", "Pending"),
+            done("compiti", "2025-01-15", "Storia"),
+        ] {
+            insert_entry(&conn, &entry).unwrap();
+        }
 
-    // ========== init_db tests ==========
+        let goals = vec![
+            WeeklyGoal {
+                subject: "Matematica".to_string(),
+                target: 3,
+            },
+            WeeklyGoal {
+                subject: "Storia".to_string(),
+                target: 1,
+            },
+        ];
+        let progress = get_goal_progress(&conn, &goals, "2025-01-13", "2025-01-19").unwrap();
+        assert_eq!(progress[0].done, 2);
+        assert!(!progress[0].met);
+        assert_eq!(progress[1].done, 1);
+        assert!(progress[1].met);
+    }
 
     #[test]
-    fn test_init_db_creates_tables() {
+    fn test_get_dashboard() {
         let (_temp_dir, conn) = setup_test_db();
+        let mut done_today = make_entry("compiti", "2025-01-15", "Storia", "Letto");
+        done_today.completed = true;
+        // Hidden until after today, with its study session; and one shown from today
+        let mut hidden = make_entry("verifica", "2025-01-20", "Latino", "Hidden test");
+        hidden.visible_from = Some("2025-01-18".to_string());
+        let mut session = make_entry("studio", "2025-01-16", "Latino", "Study: Hidden test");
+        session.parent_id = Some(hidden.id.clone());
+        let mut shown = make_entry("compiti", "2025-01-17", "Inglese", "Shown again");
+        shown.visible_from = Some("2025-01-15".to_string());
+        for entry in [
+            hidden,
+            session,
+            shown,
+            make_entry("compiti", "2025-01-14", "Italiano", "Overdue"),
+            make_entry("compiti", "2025-01-15", "Matematica", "Today"),
+            done_today,
+            make_entry("studio", "2025-01-16", "Scienze", "Tomorrow"),
+            make_entry("verifica", "2025-01-21", "Scienze", "Verifica cap. 2"),
+            make_entry("compiti", "2025-01-22", "Musica", "Next week"),
+            make_entry("interrogazione", "2025-02-10", "Storia", "Interrogazione"),
+            make_entry("verifica", "2025-03-30", "Storia", "Too far ahead"),
+        ] {
+            insert_entry(&conn, &entry).unwrap();
+        }
 
-        // Check that entries table exists
-        let table_exists: bool = conn
-            .query_row(
-                "SELECT COUNT(*) > 0 FROM sqlite_master WHERE type='table' AND name='entries'",
-                [],
-                |row| row.get(0),
-            )
-            .unwrap();
-        assert!(table_exists);
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let dashboard = get_dashboard(&conn, today).unwrap();
+        let tasks = |entries: &[HomeworkEntry]| -> Vec<String> {
+            entries.iter().map(|e| e.task.clone()).collect()
+        };
+        assert_eq!(dashboard.today, "2025-01-15");
+        assert_eq!(tasks(&dashboard.due_today), vec!["Today", "Letto"]);
+        assert_eq!(tasks(&dashboard.due_tomorrow), vec!["Tomorrow"]);
+        assert_eq!(
+            tasks(&dashboard.this_week),
+            vec!["Shown again", "Verifica cap. 2"]
+        );
+        assert_eq!(
+            tasks(&dashboard.upcoming_tests),
+            vec!["Verifica cap. 2", "Interrogazione"]
+        );
+        assert_eq!(
+            dashboard.counts,
+            DashboardCounts {
+                overdue: 1,
+                open_today: 1,
+                open_week: 4,
+                completed_week: 1,
+                hidden: 1,
+            }
+        );
     }
 
     #[test]
-    fn test_init_db_idempotent() {
-        let temp_dir = TempDir::new().unwrap();
-        let db_path = temp_dir.path().join("test.db");
-        let migrations_dir = temp_dir.path().join("migrations");
-        std::fs::create_dir(&migrations_dir).unwrap();
+    fn test_share_links() {
+        let (_temp_dir, conn) = setup_test_db();
+        let entry = make_entry("verifica", "2025-01-21", "Scienze", "Capitolo 2");
+        insert_entry(&conn, &entry).unwrap();
 
-        std::fs::write(
-            migrations_dir.join("001_initial_schema.sql"),
-            include_str!("../db/migrations/001_initial_schema.sql"),
-        )
-        .unwrap();
+        let now = chrono::Utc::now();
+        let live = create_share_link(&conn, &entry.id, now + chrono::Duration::days(7)).unwrap();
+        let old = create_share_link(&conn, &entry.id, now - chrono::Duration::days(1)).unwrap();
+        assert_ne!(live.token, old.token);
+        assert!(!live.is_expired(now));
+        assert!(old.is_expired(now));
+        assert_eq!(
+            get_share_link(&conn, &live.token).unwrap(),
+            Some(live.clone())
+        );
 
-        // Initialize twice
-        let _conn1 = init_db(&db_path, &migrations_dir).unwrap();
-        drop(_conn1);
-        let conn2 = init_db(&db_path, &migrations_dir).unwrap();
+        assert_eq!(delete_expired_share_links(&conn, now).unwrap(), 1);
+        assert_eq!(get_share_link(&conn, &old.token).unwrap(), None);
 
-        // Should still work
-        let count = count_entries(&conn2).unwrap();
-        assert_eq!(count, 0);
+        // Deleting the entry revokes its links
+        delete_entry(&conn, &entry.id).unwrap();
+        assert_eq!(get_share_link(&conn, &live.token).unwrap(), None);
     }
 
-    // ========== CRUD tests ==========
-
     #[test]
-    fn test_insert_and_get_entry() {
+    fn test_subject_resources() {
         let (_temp_dir, conn) = setup_test_db();
-        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
-
-        insert_entry(&conn, &entry).unwrap();
+        let book = create_resource(
+            &conn,
+            "Matematica",
+            "Libro",
+            "https://example.com/libro?p={page}",
+        )
+        .unwrap();
+        create_resource(&conn, " matematica ", "Quizlet", "https://quizlet.com/1").unwrap();
+        create_resource(&conn, "Storia", "Drive", "https://drive.google.com/x").unwrap();
+
+        let titles = |subject: &str| -> Vec<String> {
+            get_resources_for_subject(&conn, subject)
+                .unwrap()
+                .into_iter()
+                .map(|r| r.title)
+                .collect()
+        };
+        assert_eq!(titles("MATEMATICA"), vec!["Libro", "Quizlet"]);
+        assert_eq!(get_resources(&conn).unwrap().len(), 3);
 
-        let retrieved = get_entry(&conn, &entry.id).unwrap();
-        assert!(retrieved.is_some());
-        let retrieved = retrieved.unwrap();
-        assert_eq!(retrieved.id, entry.id);
-        assert_eq!(retrieved.entry_type, "compiti");
-        assert_eq!(retrieved.date, "2025-01-15");
-        assert_eq!(retrieved.subject, "Matematica");
-        assert_eq!(retrieved.task, "Task 1");
+        // Only deletes within the named subject
+        assert!(!delete_resource(&conn, "Storia", book.id).unwrap());
+        assert!(delete_resource(&conn, "matematica", book.id).unwrap());
+        assert_eq!(titles("Matematica"), vec!["Quizlet"]);
     }
 
     #[test]
-    fn test_get_nonexistent_entry() {
+    fn test_attachments_ocr_and_search() {
         let (_temp_dir, conn) = setup_test_db();
-        let retrieved = get_entry(&conn, "nonexistent").unwrap();
-        assert!(retrieved.is_none());
+        let photo_entry = make_entry("compiti", "2025-01-15", "Matematica", "Vedi foto");
+        let other = make_entry("compiti", "2025-01-15", "Storia", "Leggere il capitolo");
+        insert_entry(&conn, &photo_entry).unwrap();
+        insert_entry(&conn, &other).unwrap();
+
+        let source_id = photo_entry.source_id.clone().unwrap();
+        let attachment = |url: &str, file: Option<&str>| NewAttachment {
+            source_id: source_id.clone(),
+            name: url.rsplit('/').next().unwrap().to_string(),
+            url: url.to_string(),
+            file: file.map(str::to_string),
+        };
+        let new = record_attachments(
+            &conn,
+            &[
+                attachment("https://example.it/lavagna.jpg", Some("data/a.jpg")),
+                attachment("https://example.it/scheda.pdf", None),
+            ],
+        )
+        .unwrap();
+        assert_eq!(new, 2);
+        // Recording again adds nothing; a file turning up queues it for OCR
+        let again = record_attachments(
+            &conn,
+            &[attachment("https://example.it/scheda.pdf", Some("x"))],
+        );
+        assert_eq!(again.unwrap(), 0);
+
+        let pending = get_pending_ocr(&conn, 10).unwrap();
+        assert_eq!(pending.len(), 2);
+        set_ocr_result(
+            &conn,
+            pending[0].id,
+            Ok("Esercizi pag. 112 sulle equazioni"),
+        )
+        .unwrap();
+        set_ocr_result(&conn, pending[1].id, Err("unreadable")).unwrap();
+        assert!(get_pending_ocr(&conn, 10).unwrap().is_empty());
+
+        let statuses: Vec<String> = get_entry_attachments(&conn, &photo_entry.id)
+            .unwrap()
+            .into_iter()
+            .map(|a| a.ocr_status)
+            .collect();
+        assert_eq!(statuses, vec!["done", "failed"]);
+        assert!(get_entry_attachments(&conn, &other.id).unwrap().is_empty());
+
+        let search = |q: &str| -> Vec<String> {
+            get_entries_filtered(
+                &conn,
+                &EntryFilter {
+                    q: Some(q.to_string()),
+                    ..Default::default()
+                },
+            )
+            .unwrap()
+            .into_iter()
+            .map(|e| e.subject)
+            .collect()
+        };
+        // Found through the photo's text (prefix match), and through the task
+        assert_eq!(search("equaz"), vec!["Matematica"]);
+        assert_eq!(search("CAPITOLO"), vec!["Storia"]);
+        assert!(search("equazioni capitolo").is_empty());
+        // Quotes and FTS operators are taken literally
+        assert!(search("\"OR\" NEAR(").is_empty());
+        assert_eq!(search("  ").len(), 2);
+
+        // Editing the task keeps the photo's text searchable
+        let update = EntryUpdate {
+            task: Some("Vedi la foto della lavagna".to_string()),
+            ..Default::default()
+        };
+        update_entry(&conn, &photo_entry.id, &update).unwrap();
+        assert_eq!(search("lavagna pag"), vec!["Matematica"]);
+        delete_entry(&conn, &photo_entry.id).unwrap();
+        assert!(search("equazioni").is_empty());
     }
 
     #[test]
-    fn test_get_all_entries_sorted() {
+    fn test_stage_capture() {
         let (_temp_dir, conn) = setup_test_db();
+        // Same day, subject and type as a draft, but not something it replaces
+        let existing = make_entry("compiti", "2025-01-15", "Matematica", "Es. 1");
+        insert_entry(&conn, &existing).unwrap();
+
+        let draft = make_entry("compiti", "2025-01-15", "Matematica", "Es. 3 pag. 42");
+        let photo = NewAttachment {
+            source_id: format!("capture-{}", draft.id),
+            name: "Diary page".to_string(),
+            url: "capture.jpg".to_string(),
+            file: Some("data/captures/capture.jpg".to_string()),
+        };
+        let id = stage_capture(&conn, &draft, &photo).unwrap();
+        let attachment = get_attachment(&conn, id).unwrap();
+        assert_eq!(attachment.source_id, photo.source_id);
+        assert_eq!(attachment.ocr_status, "pending");
+        assert!(matches!(
+            get_attachment(&conn, id + 1),
+            Err(DbError::NotFound(_))
+        ));
+
+        let pending = get_pending_imports(&conn).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].replaces_id, None);
+
+        // The photo follows the accepted entry
+        let entry = accept_pending_import(&conn, &draft.id).unwrap().unwrap();
+        assert_eq!(entry.source_id.as_deref(), Some(photo.source_id.as_str()));
+        assert_eq!(get_entry_attachments(&conn, &entry.id).unwrap().len(), 1);
+        assert_eq!(
+            get_entry(&conn, &existing.id).unwrap().unwrap().task,
+            "Es. 1"
+        );
+    }
 
-        let entry1 = make_entry("compiti", "2025-01-20", "Matematica", "Task 3");
-        let entry2 = make_entry("nota", "2025-01-10", "Italiano", "Task 1");
-        let entry3 = make_entry("compiti", "2025-01-15", "INGLESE", "Task 2");
+    #[test]
+    fn test_merge_subjects() {
+        let (_temp_dir, conn) = setup_test_db();
+        let kept = make_entry("compiti", "2025-01-15", "Inglese", "Unit 3");
+        let moved = make_entry("compiti", "2025-01-16", "INGLESE", "Unit 4");
+        let other = make_entry("compiti", "2025-01-16", "Storia", "Cap. 2");
+        for entry in [&kept, &moved, &other] {
+            insert_entry(&conn, entry).unwrap();
+        }
+        let update = EntryUpdate {
+            date: Some("2025-01-20".to_string()),
+            ..Default::default()
+        };
+        update_entry(&conn, &moved.id, &update).unwrap();
+        stage_imports(
+            &conn,
+            &[make_entry("verifica", "2025-01-22", "Inglese", "Test")],
+        )
+        .unwrap();
+        set_weekly_goals(
+            &conn,
+            &[WeeklyGoal {
+                subject: "inglese".to_string(),
+                target: 3,
+            }],
+        )
+        .unwrap();
+        create_resource(&conn, "Inglese", "Quizlet", "https://quizlet.com/1").unwrap();
+        set_subject_icon(&conn, "Inglese", "🇬🇧").unwrap();
+
+        let merge = merge_subjects(&conn, "inglese", "Lingua Inglese").unwrap();
+        assert_eq!((merge.entries, merge.pending), (2, 1));
+        assert_eq!(get_subject_merges(&conn).unwrap(), vec![merge]);
+
+        let counts = get_subject_counts(&conn).unwrap();
+        let names: Vec<&str> = counts.iter().map(|c| c.subject.as_str()).collect();
+        assert_eq!(names, vec!["Lingua Inglese", "Storia"]);
+
+        // Re-keyed so the aliased row imports as the same entry; a moved
+        // entry keeps its original key
+        let kept = get_entry(&conn, &kept.id).unwrap().unwrap();
+        assert_eq!(
+            kept.source_id,
+            Some(HomeworkEntry::generate_source_id(
+                "2025-01-15",
+                "Lingua Inglese",
+                "Unit 3"
+            ))
+        );
+        assert_eq!(
+            get_entry(&conn, &moved.id).unwrap().unwrap().source_id,
+            moved.source_id
+        );
+        assert_eq!(
+            get_pending_imports(&conn).unwrap()[0].subject,
+            "Lingua Inglese"
+        );
+        assert_eq!(
+            get_weekly_goals(&conn).unwrap()[0].subject,
+            "Lingua Inglese"
+        );
+        assert_eq!(
+            get_resources_for_subject(&conn, "Lingua Inglese")
+                .unwrap()
+                .len(),
+            1
+        );
+        assert_eq!(
+            get_subject_icons(&conn).unwrap(),
+            vec![("Lingua Inglese".to_string(), "🇬🇧".to_string())]
+        );
+    }
 
-        insert_entry(&conn, &entry1).unwrap();
-        insert_entry(&conn, &entry2).unwrap();
-        insert_entry(&conn, &entry3).unwrap();
+    #[test]
+    fn test_fix_text() {
+        let (_temp_dir, conn) = setup_test_db();
+        let math = make_entry("compiti", "2025-01-15", "Matematica", "Pag,. 12 e pag,. 14");
+        let history = make_entry("compiti", "2025-01-16", "Storia", "Pag,. 40");
+        let clean = make_entry("compiti", "2025-01-16", "Matematica", "Pag. 20");
+        let other = make_entry("compiti", "2025-01-17", "ATTIVITÀ ALTERNATIVA", "Pag,. 3");
+        for entry in [&math, &history, &clean, &other] {
+            insert_entry(&conn, entry).unwrap();
+        }
+        // Accented subjects match in any case
+        let preview = fix_text(&conn, "ag,.", "ag.", Some("Attività alternativa"), true).unwrap();
+        assert_eq!(preview.changes.len(), 1);
+        assert_eq!(preview.changes[0].id, other.id);
+
+        let preview = fix_text(&conn, "ag,.", "ag.", Some(" matematica "), true).unwrap();
+        assert!(preview.dry_run);
+        assert_eq!(preview.fix, None);
+        assert_eq!(preview.changes.len(), 1);
+        assert_eq!(preview.changes[0].before, "Pag,. 12 e pag,. 14");
+        assert_eq!(preview.changes[0].after, "Pag. 12 e pag. 14");
+        // Nothing written or logged
+        assert_eq!(get_entry(&conn, &math.id).unwrap().unwrap().task, math.task);
+        assert!(get_text_fixes(&conn).unwrap().is_empty());
+
+        let applied = fix_text(&conn, "ag,.", "ag.", None, false).unwrap();
+        assert_eq!(applied.changes.len(), 3);
+        let fix = applied.fix.unwrap();
+        assert_eq!(fix.entries, 3);
+        assert_eq!(fix.subject, None);
+        let fixed = get_entry(&conn, &math.id).unwrap().unwrap();
+        assert_eq!(fixed.task, "Pag. 12 e pag. 14");
+        // Still recognised when the next export repeats the typo
+        assert_eq!(fixed.source_id, math.source_id);
+        assert_eq!(
+            get_entry(&conn, &history.id).unwrap().unwrap().task,
+            "Pag. 40"
+        );
+        assert_eq!(get_text_fixes(&conn).unwrap(), vec![fix]);
 
-        let entries = get_all_entries(&conn).unwrap();
-        assert_eq!(entries.len(), 3);
-        assert_eq!(entries[0].date, "2025-01-10");
-        assert_eq!(entries[1].date, "2025-01-15");
-        assert_eq!(entries[2].date, "2025-01-20");
+        // Nothing left to fix: not logged
+        let again = fix_text(&conn, "ag,.", "ag.", None, false).unwrap();
+        assert!(again.changes.is_empty());
+        assert_eq!(again.fix, None);
+        assert_eq!(get_text_fixes(&conn).unwrap().len(), 1);
     }
 
     #[test]
-    fn test_insert_entry_if_not_exists() {
+    fn test_move_entry() {
         let (_temp_dir, conn) = setup_test_db();
-        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        let mut entries = [
+            make_entry("compiti", "2025-01-15", "Matematica", "A"),
+            make_entry("compiti", "2025-01-15", "Italiano", "B"),
+            make_entry("compiti", "2025-01-16", "Storia", "C"),
+        ];
+        for (i, entry) in entries.iter_mut().enumerate() {
+            entry.position = i as i32 * 10;
+            insert_entry(&conn, entry).unwrap();
+        }
+        let order = |date: &str| -> Vec<(String, i32)> {
+            get_entries_in_range(&conn, date, date)
+                .unwrap()
+                .into_iter()
+                .map(|e| (e.task, e.position))
+                .collect()
+        };
+        let pair = |task: &str, position: i32| (task.to_string(), position);
 
-        // First insert should succeed
-        let inserted = insert_entry_if_not_exists(&conn, &entry).unwrap();
-        assert!(inserted);
+        // To the top of another day: that day is renumbered
+        assert!(move_entry(&conn, &entries[2].id, "2025-01-15", Some(0)).unwrap());
+        assert_eq!(
+            order("2025-01-15"),
+            vec![pair("C", 0), pair("A", 1), pair("B", 2)]
+        );
+        assert!(order("2025-01-16").is_empty());
 
-        // Second insert should be ignored
-        let inserted = insert_entry_if_not_exists(&conn, &entry).unwrap();
-        assert!(!inserted);
+        // Within the same day
+        assert!(move_entry(&conn, &entries[2].id, "2025-01-15", Some(1)).unwrap());
+        assert_eq!(
+            order("2025-01-15"),
+            vec![pair("A", 0), pair("C", 1), pair("B", 2)]
+        );
 
-        // Only one entry should exist
-        assert_eq!(count_entries(&conn).unwrap(), 1);
+        // To the bottom: after the current last, nothing else touched
+        assert!(move_entry(&conn, &entries[0].id, "2025-01-15", None).unwrap());
+        assert_eq!(
+            order("2025-01-15"),
+            vec![pair("C", 1), pair("B", 2), pair("A", 3)]
+        );
+
+        assert!(!move_entry(&conn, "nope", "2025-01-15", Some(0)).unwrap());
     }
 
     #[test]
-    fn test_update_entry() {
+    fn test_compact_positions() {
         let (_temp_dir, conn) = setup_test_db();
-        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
-        insert_entry(&conn, &entry).unwrap();
-
-        let updates = EntryUpdate {
-            completed: Some(true),
-            position: Some(5),
-            ..Default::default()
+        let days = [
+            ("2025-01-15", "A", 3, "2025-01-10T08:00:00+00:00"),
+            ("2025-01-15", "B", 7, "2025-01-10T08:00:00+00:00"),
+            // Same position as C, but older
+            ("2025-01-15", "D", 12, "2025-01-09T08:00:00+00:00"),
+            ("2025-01-15", "C", 12, "2025-01-11T08:00:00+00:00"),
+            ("2025-01-16", "E", 0, "2025-01-10T08:00:00+00:00"),
+            ("2025-01-16", "F", 1, "2025-01-10T08:00:00+00:00"),
+        ];
+        for (date, task, position, created_at) in days {
+            let mut entry = make_entry("compiti", date, "Matematica", task);
+            entry.position = position;
+            entry.created_at = created_at.to_string();
+            insert_entry(&conn, &entry).unwrap();
+        }
+        let order = |date: &str| -> Vec<(String, i32)> {
+            get_entries_in_range(&conn, date, date)
+                .unwrap()
+                .into_iter()
+                .map(|e| (e.task, e.position))
+                .collect()
         };
-
-        let updated = update_entry(&conn, &entry.id, &updates).unwrap();
-        assert!(updated);
-
-        let retrieved = get_entry(&conn, &entry.id).unwrap().unwrap();
-        assert!(retrieved.completed);
-        assert_eq!(retrieved.position, 5);
+        let pair = |task: &str, position: i32| (task.to_string(), position);
+
+        assert_eq!(
+            compact_positions(&conn).unwrap(),
+            CompactedPositions {
+                days: 1,
+                entries: 4
+            }
+        );
+        assert_eq!(
+            order("2025-01-15"),
+            vec![pair("A", 0), pair("B", 1), pair("D", 2), pair("C", 3)]
+        );
+        // Already compact: untouched
+        assert_eq!(order("2025-01-16"), vec![pair("E", 0), pair("F", 1)]);
+        assert_eq!(
+            compact_positions(&conn).unwrap(),
+            CompactedPositions::default()
+        );
     }
 
     #[test]
@@ -749,6 +4809,56 @@ mod tests {
         assert!(orphan.parent_id.is_none()); // Foreign key ON DELETE SET NULL
     }
 
+    #[test]
+    fn test_shift_study_sessions_skips_completed() {
+        let (_temp_dir, conn) = setup_test_db();
+
+        let parent = make_entry("verifica", "2025-01-20", "Matematica", "Verifica");
+        insert_entry(&conn, &parent).unwrap();
+
+        for (id, date, completed) in [
+            ("child1", "2025-01-17", true),
+            ("child2", "2025-01-18", false),
+            ("child3", "2025-01-19", false),
+        ] {
+            let mut child = HomeworkEntry::with_id(
+                id.to_string(),
+                "studio".to_string(),
+                date.to_string(),
+                "Matematica".to_string(),
+                "Study for: Verifica".to_string(),
+            );
+            child.parent_id = Some(parent.id.clone());
+            child.completed = completed;
+            insert_entry(&conn, &child).unwrap();
+        }
+
+        assert_eq!(shift_study_sessions(&conn, &parent.id, 3).unwrap(), 2);
+
+        let children = get_children(&conn, &parent.id).unwrap();
+        let dates: Vec<_> = children
+            .iter()
+            .map(|c| (c.id.as_str(), c.date.as_str()))
+            .collect();
+        assert_eq!(
+            dates,
+            vec![
+                ("child1", "2025-01-17"),
+                ("child2", "2025-01-21"),
+                ("child3", "2025-01-22"),
+            ]
+        );
+        let moved = get_entry(&conn, "child2").unwrap().unwrap();
+        assert_eq!(
+            moved.source_id,
+            Some(HomeworkEntry::generate_source_id(
+                "2025-01-21",
+                "Matematica",
+                "Study for: Verifica"
+            ))
+        );
+    }
+
     // ========== Position management tests ==========
 
     #[test]
@@ -830,6 +4940,67 @@ mod tests {
         assert!(entry_exists(&conn, &entry.id).unwrap());
     }
 
+    // ========== Constraint tests ==========
+
+    #[test]
+    fn test_database_uses_wal() {
+        let (_temp_dir, conn) = setup_test_db();
+        let mode: String = conn
+            .query_row("PRAGMA journal_mode", [], |row| row.get(0))
+            .unwrap();
+        assert_eq!(mode, "wal");
+    }
+
+    #[test]
+    fn test_source_id_is_unique() {
+        let (_temp_dir, conn) = setup_test_db();
+        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        insert_entry(&conn, &entry).unwrap();
+
+        // Same content, fresh id
+        let dup = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        let err = insert_entry(&conn, &dup).unwrap_err();
+        assert!(is_unique_violation(&err));
+
+        assert!(!insert_entry_if_not_exists(&conn, &dup).unwrap());
+        assert_eq!(count_entries(&conn).unwrap(), 1);
+    }
+
+    #[test]
+    fn test_unique_migration_collapses_existing_duplicates() {
+        let temp_dir = TempDir::new().unwrap();
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+        write_test_migrations(&migrations_dir);
+        let unique_migration = migrations_dir.join("005_unique_source_id_wal.sql");
+        let sql = std::fs::read_to_string(&unique_migration).unwrap();
+        std::fs::remove_file(&unique_migration).unwrap();
+
+        // A database from before the constraint, with a raced duplicate
+        let conn = init_db(&temp_dir.path().join("test.db"), &migrations_dir).unwrap();
+        let kept = make_entry("verifica", "2025-01-20", "Storia", "Verifica");
+        let dup = make_entry("verifica", "2025-01-20", "Storia", "Verifica");
+        insert_entry(&conn, &kept).unwrap();
+        insert_entry(&conn, &dup).unwrap();
+        let mut child = HomeworkEntry::with_id(
+            "child1".to_string(),
+            "studio".to_string(),
+            "2025-01-18".to_string(),
+            "Storia".to_string(),
+            "Study for: Verifica".to_string(),
+        );
+        child.parent_id = Some(dup.id.clone());
+        insert_entry(&conn, &child).unwrap();
+
+        std::fs::write(&unique_migration, sql).unwrap();
+        assert_eq!(run_migrations(&conn, &migrations_dir).unwrap(), 1);
+
+        assert!(get_entry(&conn, &kept.id).unwrap().is_some());
+        assert!(get_entry(&conn, &dup.id).unwrap().is_none());
+        let child = get_entry(&conn, "child1").unwrap().unwrap();
+        assert_eq!(child.parent_id, Some(kept.id));
+    }
+
     // ========== import_entries tests ==========
 
     #[test]
@@ -901,7 +5072,7 @@ mod tests {
         // Import an entry originally on 2025-01-15
         let original = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
         let original_source_id = original.source_id.clone();
-        import_entries(&conn, &[original.clone()]).unwrap();
+        import_entries(&conn, std::slice::from_ref(&original)).unwrap();
         assert_eq!(count_entries(&conn).unwrap(), 1);
 
         // Simulate user moving the entry to 2025-01-20 via the UI
@@ -935,4 +5106,343 @@ mod tests {
         assert_eq!(entries.len(), 1);
         assert_eq!(entries[0].date, "2025-01-20");
     }
+
+    #[test]
+    fn test_record_source_dates_keeps_original_date() {
+        let (_temp_dir, conn) = setup_test_db();
+        let original = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        let by_hand = make_entry("nota", "2025-01-16", "Storia", "Libro");
+        import_entries(&conn, &[original.clone(), by_hand.clone()]).unwrap();
+        assert_eq!(
+            record_source_dates(&conn, std::slice::from_ref(&original)).unwrap(),
+            1
+        );
+
+        let updates = EntryUpdate {
+            date: Some("2025-01-20".to_string()),
+            ..Default::default()
+        };
+        update_entry(&conn, &original.id, &updates).unwrap();
+        // A later scan of the same export leaves the recorded date alone
+        assert_eq!(
+            record_source_dates(&conn, std::slice::from_ref(&original)).unwrap(),
+            0
+        );
+
+        let moved = get_entry(&conn, &original.id).unwrap().unwrap();
+        assert_eq!(moved.source_date.as_deref(), Some("2025-01-15"));
+        assert_eq!(moved.original_date(), Some("2025-01-15"));
+        let untouched = get_entry(&conn, &by_hand.id).unwrap().unwrap();
+        assert_eq!(untouched.source_date, None);
+        assert_eq!(untouched.original_date(), None);
+    }
+
+    #[test]
+    fn test_end_dates() {
+        let (_temp_dir, conn) = setup_test_db();
+        let mut trip = make_entry("nota", "2025-01-30", "Geografia", "Gita");
+        let single = make_entry("compiti", "2025-01-20", "Storia", "Cap. 2");
+        import_entries(&conn, &[trip.clone(), single.clone()]).unwrap();
+
+        // Imported before end dates were parsed: the next import fills it in
+        trip.end_date = Some("2025-02-02".to_string());
+        assert_eq!(
+            record_end_dates(&conn, &[trip.clone(), single.clone()]).unwrap(),
+            1
+        );
+        let stored = get_entry(&conn, &trip.id).unwrap().unwrap();
+        assert_eq!(stored.end_date.as_deref(), Some("2025-02-02"));
+
+        // Extended on the register: the stored end follows; unchanged, it's left alone
+        trip.end_date = Some("2025-02-03".to_string());
+        assert_eq!(
+            record_end_dates(&conn, std::slice::from_ref(&trip)).unwrap(),
+            1
+        );
+        assert_eq!(
+            record_end_dates(&conn, std::slice::from_ref(&trip)).unwrap(),
+            0
+        );
+        let stored = get_entry(&conn, &trip.id).unwrap().unwrap();
+        assert_eq!(stored.end_date.as_deref(), Some("2025-02-03"));
+
+        let in_february = get_entries_overlapping(&conn, "2025-02-01", "2025-02-28").unwrap();
+        assert_eq!(in_february.len(), 1);
+        assert_eq!(in_february[0].id, trip.id);
+        assert!(get_entries_in_range(&conn, "2025-02-01", "2025-02-28")
+            .unwrap()
+            .is_empty());
+        assert_eq!(
+            get_entries_overlapping(&conn, "2025-01-01", "2025-01-31")
+                .unwrap()
+                .len(),
+            2
+        );
+    }
+
+    #[test]
+    fn test_assignments_and_notice_stats() {
+        let (_temp_dir, conn) = setup_test_db();
+        let posted = |entry: &mut HomeworkEntry, at: &str, teacher: &str| {
+            entry.assigned_at = Some(at.to_string());
+            entry.teacher = Some(teacher.to_string());
+        };
+        let mut early = make_entry("compiti", "2025-01-20", "Matematica", "Es. 1");
+        posted(&mut early, "2025-01-15 10:00:00", "ROSSI MARIA");
+        let mut late = make_entry("compiti", "2025-01-21", "Matematica", "Es. 2");
+        posted(&mut late, "2025-01-20 18:30:00", "ROSSI MARIA");
+        let mut test = make_entry("verifica", "2025-01-30", "Storia", "Cap. 4");
+        import_entries(&conn, &[early.clone(), late.clone(), test.clone()]).unwrap();
+
+        // Imported before the posting time was parsed: the next import fills it in
+        posted(&mut test, "2025-01-16", "BIANCHI LUCA");
+        assert_eq!(
+            record_assignments(&conn, &[early.clone(), test.clone()]).unwrap(),
+            1
+        );
+        assert_eq!(record_assignments(&conn, &[test.clone()]).unwrap(), 0);
+        let stored = get_entry(&conn, &test.id).unwrap().unwrap();
+        assert_eq!(stored.teacher.as_deref(), Some("BIANCHI LUCA"));
+        assert_eq!(stored.notice_days(), Some(14));
+
+        // Moving an entry doesn't change the notice it came with
+        record_source_dates(&conn, std::slice::from_ref(&late)).unwrap();
+        let updates = EntryUpdate {
+            date: Some("2025-01-28".to_string()),
+            ..Default::default()
+        };
+        update_entry(&conn, &late.id, &updates).unwrap();
+
+        let stats = get_notice_stats(&conn).unwrap();
+        assert_eq!(
+            stats,
+            vec![
+                NoticeStats {
+                    subject: "Matematica".to_string(),
+                    teacher: Some("ROSSI MARIA".to_string()),
+                    entries: 2,
+                    tests: 0,
+                    avg_days: 3.0,
+                    shortest_days: 1,
+                    short_notice: 1,
+                },
+                NoticeStats {
+                    subject: "Storia".to_string(),
+                    teacher: Some("BIANCHI LUCA".to_string()),
+                    entries: 1,
+                    tests: 1,
+                    avg_days: 14.0,
+                    shortest_days: 14,
+                    short_notice: 0,
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_day_completions() {
+        let (_temp_dir, conn) = setup_test_db();
+        let math = make_entry("compiti", "2025-01-15", "Matematica", "Es. 1");
+        let history = make_entry("compiti", "2025-01-15", "Storia", "Cap. 3");
+        let other_day = make_entry("compiti", "2025-01-16", "Arte", "Disegno");
+        for entry in [&math, &history, &other_day] {
+            insert_entry(&conn, entry).unwrap();
+        }
+        let tick = |id: &str, completed: bool| {
+            let updates = EntryUpdate {
+                completed: Some(completed),
+                ..Default::default()
+            };
+            update_entry(&conn, id, &updates).unwrap();
+        };
+
+        tick(&math.id, true);
+        assert!(!record_day_completed(&conn, "2025-01-15").unwrap());
+        tick(&history.id, true);
+        assert!(record_day_completed(&conn, "2025-01-15").unwrap());
+        // Recorded once, however often it's asked
+        assert!(record_day_completed(&conn, "2025-01-15").unwrap());
+        assert!(!record_day_completed(&conn, "2025-01-17").unwrap());
+
+        let days = get_days_completed(&conn, "2025-01-01", "2025-01-31").unwrap();
+        assert_eq!(days.len(), 1);
+        assert_eq!(days[0].date, "2025-01-15");
+        assert_eq!(days[0].entries, 2);
+        assert_eq!(
+            get_day_outcomes(&conn).unwrap(),
+            vec![
+                (NaiveDate::from_ymd_opt(2025, 1, 15).unwrap(), true),
+                (NaiveDate::from_ymd_opt(2025, 1, 16).unwrap(), false),
+            ]
+        );
+
+        tick(&history.id, false);
+        reopen_day(&conn, "2025-01-15").unwrap();
+        assert!(get_days_completed(&conn, "2025-01-01", "2025-01-31")
+            .unwrap()
+            .is_empty());
+
+        // Paths that never call record_day_completed still count: a pending
+        // entry moved away finishes its old day, one moved in reopens it
+        tick(&history.id, true);
+        record_day_completed(&conn, "2025-01-15").unwrap();
+        move_entry(&conn, &other_day.id, "2025-01-15", Some(0)).unwrap();
+        assert!(get_days_completed(&conn, "2025-01-01", "2025-01-31")
+            .unwrap()
+            .is_empty());
+        delete_entry(&conn, &other_day.id).unwrap();
+        let days = get_days_completed(&conn, "2025-01-01", "2025-01-31").unwrap();
+        assert_eq!(days.len(), 1);
+        assert_eq!(get_day_outcomes(&conn).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_insert_generated_once_per_parent() {
+        use crate::data::generate_study_sessions;
+        use std::collections::{HashMap, HashSet};
+
+        let (_temp_dir, conn) = setup_test_db();
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let sessions_for = |parent: &HomeworkEntry| {
+            generate_study_sessions(parent, today, 4, &HashSet::new(), &HashMap::new(), false)
+        };
+
+        let test = make_entry("verifica", "2025-01-30", "Matematica", "Verifica");
+        insert_entry(&conn, &test).unwrap();
+        assert_eq!(
+            insert_generated(&conn, &test, &sessions_for(&test)).unwrap(),
+            4
+        );
+        assert_eq!(
+            insert_generated(&conn, &test, &sessions_for(&test)).unwrap(),
+            0
+        );
+
+        // Deleted (its sessions lose their parent) and imported again
+        delete_entry(&conn, &test.id).unwrap();
+        let again = make_entry("verifica", "2025-01-30", "Matematica", "Verifica");
+        insert_entry(&conn, &again).unwrap();
+        assert_eq!(
+            insert_generated(&conn, &again, &sessions_for(&again)).unwrap(),
+            0
+        );
+        assert_eq!(get_children(&conn, &again.id).unwrap().len(), 4);
+        assert_eq!(count_entries(&conn).unwrap(), 5);
+
+        // Sessions stored under other ids still count
+        let history = make_entry("verifica", "2025-01-30", "Storia", "Verifica cap. 3");
+        insert_entry(&conn, &history).unwrap();
+        for (i, mut session) in sessions_for(&history).into_iter().enumerate() {
+            session.id = format!("older-{}", i);
+            session.task = format!("{} (older)", session.task);
+            session.source_id = Some(format!("older-{}", i));
+            insert_entry(&conn, &session).unwrap();
+        }
+        assert_eq!(
+            insert_generated(&conn, &history, &sessions_for(&history)).unwrap(),
+            0
+        );
+        assert_eq!(get_children(&conn, &history.id).unwrap().len(), 4);
+    }
+
+    #[test]
+    fn test_study_session_position() {
+        let (_temp_dir, conn) = setup_test_db();
+        let day = "2025-01-29";
+        let mut homework = make_entry("compiti", day, "Storia", "Cap. 4");
+        homework.position = 0;
+        let mut note = make_entry("nota", day, "Arte", "Portare i colori");
+        note.position = 1;
+        insert_entry(&conn, &homework).unwrap();
+        insert_entry(&conn, &note).unwrap();
+        let session_for = |test: &HomeworkEntry| {
+            insert_entry(&conn, test).unwrap();
+            let mut session = make_entry("studio", day, &test.subject, "Ripasso");
+            session.parent_id = Some(test.id.clone());
+            assert_eq!(
+                insert_generated(&conn, test, std::slice::from_ref(&session)).unwrap(),
+                1
+            );
+            session.id
+        };
+        let order = || -> Vec<String> {
+            get_entries_in_range(&conn, day, day)
+                .unwrap()
+                .into_iter()
+                .map(|e| e.id)
+                .collect()
+        };
+
+        set_enum_setting(&conn, "study_session_position", SessionPosition::Bottom).unwrap();
+        let soon = session_for(&make_entry(
+            "verifica",
+            "2025-01-30",
+            "Matematica",
+            "Frazioni",
+        ));
+        assert_eq!(order(), [&*homework.id, &note.id, &soon]);
+
+        // After the homework and sessions for earlier tests, before the rest
+        set_enum_setting(&conn, "study_session_position", SessionPosition::Priority).unwrap();
+        let later = session_for(&make_entry("verifica", "2025-02-03", "Inglese", "Unit 5"));
+        assert_eq!(order(), [&*homework.id, &later, &note.id, &soon]);
+        let sooner = session_for(&make_entry("verifica", "2025-01-30", "Scienze", "Cellula"));
+        assert_eq!(order(), [&*homework.id, &sooner, &later, &note.id, &soon]);
+    }
+
+    #[test]
+    fn test_topics() {
+        let (_temp_dir, conn) = setup_test_db();
+        let mut algebra = make_entry("compiti", "2025-01-15", "Matematica", "Algebra: pag. 12");
+        algebra.estimated_minutes = Some(30);
+        let geometry = make_entry(
+            "verifica",
+            "2025-01-20",
+            "Matematica",
+            "Geometria: verifica sui triangoli",
+        );
+        let mut homework = make_entry("compiti", "2025-01-16", "Matematica", "Geometria: es. 4");
+        homework.completed = true;
+        homework.actual_minutes = Some(45);
+        let plain = make_entry("compiti", "2025-01-16", "Storia", "Ripassare il capitolo 3");
+        import_entries(&conn, &[algebra, geometry.clone(), homework, plain]).unwrap();
+        let mut session = make_entry("studio", "2025-01-19", "Matematica", "Ripasso");
+        session.parent_id = Some(geometry.id.clone());
+        insert_entry(&conn, &session).unwrap();
+
+        assert_eq!(fill_topics(&conn).unwrap(), 3);
+        assert_eq!(fill_topics(&conn).unwrap(), 0);
+        let session = get_entry(&conn, &session.id).unwrap().unwrap();
+        assert_eq!(session.topic.as_deref(), Some("Geometria"));
+
+        let filter = EntryFilter {
+            topics: vec!["geometria".to_string()],
+            ..Default::default()
+        };
+        let found = get_entries_filtered(&conn, &filter).unwrap();
+        assert_eq!(found.len(), 3);
+
+        let stats = get_topic_stats(&conn).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert_eq!(
+            stats[0],
+            TopicStats {
+                subject: "Matematica".to_string(),
+                topic: "Geometria".to_string(),
+                entries: 2,
+                tests: 1,
+                completed: 1,
+                minutes: 45,
+            }
+        );
+        assert_eq!((stats[1].topic.as_str(), stats[1].minutes), ("Algebra", 30));
+
+        // A test is known by its task, whatever type the row had
+        let quiz = make_entry("compiti", "2025-01-22", "Matematica", "Algebra: test");
+        import_entries(&conn, &[quiz]).unwrap();
+        fill_topics(&conn).unwrap();
+        let stats = get_topic_stats(&conn).unwrap();
+        let algebra = stats.iter().find(|s| s.topic == "Algebra").unwrap();
+        assert_eq!((algebra.entries, algebra.tests), (2, 1));
+    }
 }
@@ -0,0 +1,273 @@
+//! Full entry text from raschietto's `.details.json` sidecars.
+//!
+//! The Excel export truncates long notes. `raschietto fetch --details` saves
+//! what each agenda item's detail popup shows next to the export
+//! (`export_x.xls` -> `export_x.details.json`); here we swap the truncated
+//! tasks for the full text before the entries are imported, and collect the
+//! attachments (photos come with a local copy to OCR, see `ocr`).
+
+use anyhow::{Context, Result};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+
+use crate::types::{HomeworkEntry, NewAttachment};
+
+#[derive(Debug, Deserialize)]
+struct DetailsFile {
+    entries: Vec<EntryDetail>,
+}
+
+/// One agenda item's popup contents.
+#[derive(Debug, Clone, Deserialize)]
+pub struct EntryDetail {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    #[serde(default)]
+    pub subject: String,
+    pub text: String,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+/// A file linked from the popup; only the name is shown in the task.
+#[derive(Debug, Clone, Deserialize)]
+pub struct Attachment {
+    pub name: String,
+    #[serde(default)]
+    pub url: String,
+    /// Local copy of a photo. Relative to the sidecar in the file, resolved
+    /// by `load_sidecar`.
+    #[serde(default)]
+    pub file: Option<String>,
+}
+
+/// Sidecar path for an export file.
+pub fn sidecar_path(export_path: &Path) -> PathBuf {
+    export_path.with_extension("details.json")
+}
+
+/// Load the sidecar for `export_path`, if there is one.
+pub fn load_sidecar(export_path: &Path) -> Result<Option<Vec<EntryDetail>>> {
+    let path = sidecar_path(export_path);
+    if !path.exists() {
+        return Ok(None);
+    }
+    let text = std::fs::read_to_string(&path)
+        .with_context(|| format!("Failed to read details file: {}", path.display()))?;
+    let mut file: DetailsFile = serde_json::from_str(&text)
+        .with_context(|| format!("Invalid details file: {}", path.display()))?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    for attachment in file.entries.iter_mut().flat_map(|d| &mut d.attachments) {
+        if let Some(relative) = &attachment.file {
+            attachment.file = Some(dir.join(relative).to_string_lossy().into_owned());
+        }
+    }
+    Ok(Some(file.entries))
+}
+
+/// Replace truncated tasks with the full text from `details`.
+///
+/// A detail matches an entry on the same date whose task is a prefix of the
+/// detail text (ignoring whitespace and a trailing ellipsis). When several
+/// match, the one whose subject matches the entry's wins. Attachment names are
+/// appended on their own lines. Returns how many entries changed.
+pub fn merge_details(entries: &mut [HomeworkEntry], details: &[EntryDetail]) -> usize {
+    let mut merged = 0;
+    for entry in entries.iter_mut() {
+        let Some(detail) = find_detail(entry, details) else {
+            continue;
+        };
+
+        let mut full = detail.text.trim().to_string();
+        for attachment in &detail.attachments {
+            full.push_str(&format!("\n📎 {}", attachment.name));
+        }
+        if full != entry.task {
+            entry.task = full;
+            merged += 1;
+        }
+    }
+    merged
+}
+
+/// The attachments of each entry's detail, keyed by the entry's source_id.
+/// Call before `merge_details`, which changes the tasks it matches on.
+pub fn collect_attachments(
+    entries: &[HomeworkEntry],
+    details: &[EntryDetail],
+) -> Vec<NewAttachment> {
+    entries
+        .iter()
+        .filter_map(|entry| Some((entry.source_id.as_ref()?, find_detail(entry, details)?)))
+        .flat_map(|(source_id, detail)| {
+            detail
+                .attachments
+                .iter()
+                .filter(|a| !a.url.is_empty())
+                .map(|a| NewAttachment {
+                    source_id: source_id.clone(),
+                    name: a.name.clone(),
+                    url: a.url.clone(),
+                    file: a.file.clone(),
+                })
+        })
+        .collect()
+}
+
+/// The detail for `entry`: same date, with the task as a prefix of its text,
+/// and the entry's subject. One that matches on date and text alone is only
+/// taken when it's the only one; among several, guessing could give a short
+/// task ("Ripassare") another subject's text and attachments.
+fn find_detail<'a>(entry: &HomeworkEntry, details: &'a [EntryDetail]) -> Option<&'a EntryDetail> {
+    let task = squash(strip_ellipsis(&entry.task));
+    if task.is_empty() {
+        return None;
+    }
+    let candidates: Vec<&EntryDetail> = details
+        .iter()
+        .filter(|d| d.date == entry.date && squash(&d.text).starts_with(&task))
+        .collect();
+    let subject = entry.subject.to_lowercase();
+    match candidates
+        .iter()
+        .find(|d| d.subject.to_lowercase() == subject)
+    {
+        Some(detail) => Some(*detail),
+        None if candidates.len() == 1 => Some(candidates[0]),
+        None => None,
+    }
+}
+
+fn strip_ellipsis(text: &str) -> &str {
+    let text = text.trim_end();
+    text.strip_suffix("...")
+        .or_else(|| text.strip_suffix('…'))
+        .unwrap_or(text)
+}
+
+/// Collapse all whitespace (including line breaks) to single spaces.
+fn squash(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn make_entry(subject: &str, task: &str) -> HomeworkEntry {
+        HomeworkEntry::new(
+            "compiti".to_string(),
+            "2025-01-15".to_string(),
+            subject.to_string(),
+            task.to_string(),
+        )
+    }
+
+    fn detail(subject: &str, text: &str) -> EntryDetail {
+        EntryDetail {
+            date: "2025-01-15".to_string(),
+            subject: subject.to_string(),
+            text: text.to_string(),
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_merge_replaces_truncated_task() {
+        let mut entries = vec![
+            make_entry("Storia", "Studiare il capitolo 5 e fare..."),
+            make_entry("Matematica", "Pag. 10"),
+        ];
+        let details = vec![detail(
+            "STORIA",
+            "Studiare il capitolo 5 e fare\nla mappa concettuale",
+        )];
+
+        assert_eq!(merge_details(&mut entries, &details), 1);
+        assert_eq!(
+            entries[0].task,
+            "Studiare il capitolo 5 e fare\nla mappa concettuale"
+        );
+        assert_eq!(entries[1].task, "Pag. 10");
+    }
+
+    #[test]
+    fn test_merge_requires_same_date_and_prefers_subject() {
+        let mut entries = vec![make_entry("Storia", "Ripassare")];
+        let mut other_day = detail("Storia", "Ripassare tutto il libro");
+        other_day.date = "2025-01-16".to_string();
+        assert_eq!(merge_details(&mut entries, &[other_day]), 0);
+
+        let details = vec![
+            detail("Geografia", "Ripassare i fiumi"),
+            detail("Storia", "Ripassare i Romani"),
+        ];
+        assert_eq!(merge_details(&mut entries, &details), 1);
+        assert_eq!(entries[0].task, "Ripassare i Romani");
+
+        // Several candidates and none for this subject: leave it alone
+        let mut entries = vec![make_entry("Scienze", "Ripassare")];
+        assert_eq!(merge_details(&mut entries, &details), 0);
+        assert_eq!(entries[0].task, "Ripassare");
+        // A single one is taken even if the subject is spelled differently
+        let mut entries = vec![make_entry("Scienze", "Ripassare")];
+        let details = vec![detail("SCIENZE NATURALI", "Ripassare la cellula")];
+        assert_eq!(merge_details(&mut entries, &details), 1);
+    }
+
+    #[test]
+    fn test_merge_appends_attachments() {
+        let mut entries = vec![make_entry("Arte", "Vedi scheda")];
+        let mut with_file = detail("Arte", "Vedi scheda");
+        with_file.attachments.push(Attachment {
+            name: "scheda.pdf".to_string(),
+            url: "https://example.it/scheda.pdf".to_string(),
+            file: None,
+        });
+
+        assert_eq!(merge_details(&mut entries, &[with_file.clone()]), 1);
+        assert_eq!(entries[0].task, "Vedi scheda\n📎 scheda.pdf");
+        // Merging again is a no-op
+        assert_eq!(merge_details(&mut entries, &[with_file]), 0);
+    }
+
+    #[test]
+    fn test_load_sidecar() {
+        let temp_dir = TempDir::new().unwrap();
+        let export = temp_dir.path().join("export_1.xls");
+        assert!(load_sidecar(&export).unwrap().is_none());
+
+        std::fs::write(
+            temp_dir.path().join("export_1.details.json"),
+            r#"{"from":"2025-01-08","to":"2025-01-30","entries":[
+                {"date":"2025-01-15","subject":"STORIA","text":"Cap. 5",
+                 "attachments":[{"name":"a.pdf","url":"https://example.it/a.pdf"},
+                                {"name":"b.jpg","url":"https://example.it/b.jpg",
+                                 "file":"attachments/b.jpg"}]}
+            ]}"#,
+        )
+        .unwrap();
+        let details = load_sidecar(&export).unwrap().unwrap();
+        assert_eq!(details.len(), 1);
+        assert_eq!(details[0].text, "Cap. 5");
+        assert_eq!(details[0].attachments[0].name, "a.pdf");
+        assert_eq!(
+            details[0].attachments[1].file.as_deref().map(Path::new),
+            Some(temp_dir.path().join("attachments/b.jpg").as_path())
+        );
+
+        let entries = vec![
+            make_entry("Storia", "Cap. 5"),
+            make_entry("Storia", "Cap. 6"),
+        ];
+        let attachments = collect_attachments(&entries, &details);
+        assert_eq!(attachments.len(), 2);
+        assert_eq!(
+            attachments[0].source_id,
+            entries[0].source_id.clone().unwrap()
+        );
+        assert_eq!(attachments[0].file, None);
+        assert!(attachments[1].file.is_some());
+    }
+}
@@ -0,0 +1,222 @@
+//! `compitutto due`: what's still to do in the next few days, for the terminal.
+//!
+//! Prints pending entries grouped by day and subject. The command exits with
+//! status 1 when a test falls inside the window, so a shell profile or cron
+//! job can react to it (`compitutto due --tomorrow || notify-send ...`).
+
+use chrono::{Duration, NaiveDate};
+use std::collections::BTreeMap;
+use std::fmt::Write;
+
+use crate::data::is_test_or_quiz;
+use crate::types::HomeworkEntry;
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const DIM: &str = "\x1b[2m";
+const RED: &str = "\x1b[1;31m";
+const CYAN: &str = "\x1b[1;36m";
+
+/// Pending entries from tomorrow through `to`, by date then subject.
+#[derive(Debug)]
+pub struct DueReport {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub days: BTreeMap<NaiveDate, BTreeMap<String, Vec<HomeworkEntry>>>,
+}
+
+/// Collect the pending entries due in the `days` days after `today`.
+///
+/// Entries keep their order within a subject (callers pass them sorted by
+/// date and position). Orphaned study sessions are left out.
+pub fn build_report(entries: &[HomeworkEntry], today: NaiveDate, days: u32) -> DueReport {
+    let from = today + Duration::days(1);
+    let to = today + Duration::days(days.max(1) as i64);
+
+    let mut report = DueReport {
+        from,
+        to,
+        days: BTreeMap::new(),
+    };
+    for entry in entries {
+        if entry.completed || entry.is_orphaned() {
+            continue;
+        }
+        let Ok(date) = NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d") else {
+            continue;
+        };
+        if date < from || date > to {
+            continue;
+        }
+        let subject = match entry.subject.trim() {
+            "" => "(no subject)".to_string(),
+            subject => subject.to_string(),
+        };
+        report
+            .days
+            .entry(date)
+            .or_default()
+            .entry(subject)
+            .or_default()
+            .push(entry.clone());
+    }
+    report
+}
+
+/// A test or oral test; study sessions mention the test but aren't one.
+pub fn is_test(entry: &HomeworkEntry) -> bool {
+    !entry.is_generated()
+        && (entry.entry_type == "verifica"
+            || entry.entry_type == "interrogazione"
+            || is_test_or_quiz(entry))
+}
+
+impl DueReport {
+    /// Whether any pending test falls inside the window.
+    pub fn has_test(&self) -> bool {
+        self.entries().any(is_test)
+    }
+
+    fn entries(&self) -> impl Iterator<Item = &HomeworkEntry> {
+        self.days.values().flat_map(|s| s.values().flatten())
+    }
+
+    /// Render as indented plain text, with ANSI colours when `color` is set.
+    pub fn render(&self, color: bool) -> String {
+        let paint = |style: &str, text: &str| {
+            if color {
+                format!("{}{}{}", style, text, RESET)
+            } else {
+                text.to_string()
+            }
+        };
+
+        let window = if self.from == self.to {
+            "tomorrow".to_string()
+        } else {
+            format!("in the next {} days", (self.to - self.from).num_days() + 1)
+        };
+        let mut out = String::new();
+        if self.days.is_empty() {
+            let _ = writeln!(out, "Nothing due {}.", window);
+            return out;
+        }
+
+        let count = self.entries().count();
+        let _ = writeln!(
+            out,
+            "{} due {}",
+            paint(
+                BOLD,
+                &format!("{} {}", count, plural(count, "entry", "entries"))
+            ),
+            window
+        );
+        for (date, subjects) in &self.days {
+            let _ = writeln!(out);
+            let _ = writeln!(
+                out,
+                "{}",
+                paint(CYAN, &date.format("%A %-d %B").to_string())
+            );
+            for (subject, entries) in subjects {
+                let _ = writeln!(out, "  {}", paint(BOLD, subject));
+                for entry in entries {
+                    let task = entry.task.split_whitespace().collect::<Vec<_>>().join(" ");
+                    let line = if is_test(entry) {
+                        paint(RED, &format!("[{}] {}", entry.entry_type, task))
+                    } else if entry.is_generated() {
+                        paint(DIM, &task)
+                    } else {
+                        task
+                    };
+                    let _ = writeln!(out, "    • {}", line);
+                }
+            }
+        }
+        out
+    }
+}
+
+fn plural<'a>(count: usize, singular: &'a str, plural: &'a str) -> &'a str {
+    if count == 1 {
+        singular
+    } else {
+        plural
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn today() -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, 15).unwrap()
+    }
+
+    fn make_entry(entry_type: &str, date: &str, subject: &str, task: &str) -> HomeworkEntry {
+        HomeworkEntry::new(
+            entry_type.to_string(),
+            date.to_string(),
+            subject.to_string(),
+            task.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_build_report_window_and_grouping() {
+        let mut done = make_entry("compiti", "2025-01-16", "Storia", "Cap. 4");
+        done.completed = true;
+        let entries = vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Today"),
+            make_entry("compiti", "2025-01-16", "Matematica", "Pag. 10"),
+            make_entry("nota", "2025-01-16", "Matematica", "Portare il compasso"),
+            done,
+            make_entry("compiti", "2025-01-18", "Inglese", "Unit 3"),
+            make_entry("compiti", "2025-01-19", "Inglese", "Too late"),
+        ];
+
+        let tomorrow = build_report(&entries, today(), 1);
+        assert_eq!(tomorrow.days.len(), 1);
+        let day = &tomorrow.days[&NaiveDate::from_ymd_opt(2025, 1, 16).unwrap()];
+        assert_eq!(day.keys().collect::<Vec<_>>(), vec!["Matematica"]);
+        assert_eq!(day["Matematica"].len(), 2);
+
+        let three = build_report(&entries, today(), 3);
+        assert_eq!(three.days.len(), 2);
+        assert!(!three.has_test());
+    }
+
+    #[test]
+    fn test_has_test_ignores_study_sessions() {
+        let mut session = make_entry("studio", "2025-01-16", "Storia", "Study for: Verifica");
+        session.parent_id = Some("abc".to_string());
+        assert!(!build_report(&[session], today(), 1).has_test());
+
+        let test = make_entry("verifica", "2025-01-17", "Storia", "Cap. 5");
+        assert!(!build_report(std::slice::from_ref(&test), today(), 1).has_test());
+        assert!(build_report(&[test], today(), 2).has_test());
+    }
+
+    #[test]
+    fn test_render() {
+        let entries = vec![
+            make_entry("verifica", "2025-01-16", "Storia", "Cap.  5\ne 6"),
+            make_entry("compiti", "2025-01-16", "Arte", "Disegno"),
+        ];
+        let text = build_report(&entries, today(), 1).render(false);
+        assert_eq!(
+            text,
+            "2 entries due tomorrow\n\nThursday 16 January\n  Arte\n    • Disegno\n  \
+             Storia\n    • [verifica] Cap. 5 e 6\n"
+        );
+        assert!(build_report(&entries, today(), 1)
+            .render(true)
+            .contains("\x1b[1;31m[verifica]"));
+
+        assert_eq!(
+            build_report(&[], today(), 3).render(false),
+            "Nothing due in the next 3 days.\n"
+        );
+    }
+}
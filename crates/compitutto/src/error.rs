@@ -0,0 +1,188 @@
+//! Error responses for the HTTP API.
+//!
+//! Every failed request gets a JSON body with a stable `code` to branch on,
+//! a human-readable `message`, and `details` where there is more to say:
+//!
+//! ```json
+//! {"code": "not_found", "message": "Entry not found"}
+//! ```
+//!
+//! Handlers take JSON bodies through [`ApiJson`] rather than axum's `Json`,
+//! so a malformed body gets the same kind of answer.
+
+use axum::extract::rejection::JsonRejection;
+use axum::extract::{FromRequest, Request};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde::Serialize;
+
+use crate::db::DbError;
+
+/// What went wrong, for clients to branch on. Each code has one HTTP status.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorCode {
+    /// 400: the request is malformed or a value is out of range
+    Validation,
+    /// 403: the server runs with `--read-only`
+    ReadOnly,
+    /// 404: the entry, link, job, ... doesn't exist
+    NotFound,
+    /// 409: the request clashes with what's stored (a duplicate, a wrong state)
+    Conflict,
+    /// 422: a field of an entry update is unknown, read-only or invalid
+    Unprocessable,
+    /// 500: the database or the disk failed; the server log has the cause
+    Internal,
+}
+
+impl ErrorCode {
+    pub fn status(self) -> StatusCode {
+        match self {
+            ErrorCode::Validation => StatusCode::BAD_REQUEST,
+            ErrorCode::ReadOnly => StatusCode::FORBIDDEN,
+            ErrorCode::NotFound => StatusCode::NOT_FOUND,
+            ErrorCode::Conflict => StatusCode::CONFLICT,
+            ErrorCode::Unprocessable => StatusCode::UNPROCESSABLE_ENTITY,
+            ErrorCode::Internal => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+/// An error response: `code`, `message` and optional `details`.
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ApiError {
+    pub code: ErrorCode,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub details: Option<serde_json::Value>,
+}
+
+impl ApiError {
+    pub fn new(code: ErrorCode, message: impl Into<String>) -> Self {
+        Self {
+            code,
+            message: message.into(),
+            details: None,
+        }
+    }
+
+    pub fn validation(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Validation, message)
+    }
+
+    pub fn read_only() -> Self {
+        Self::new(ErrorCode::ReadOnly, "Read-only mode")
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::NotFound, message)
+    }
+
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Conflict, message)
+    }
+
+    pub fn unprocessable(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Unprocessable, message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(ErrorCode::Internal, message)
+    }
+
+    /// A failed database call: a missing row is 404 and a refused write
+    /// 409, each with the database's reason; anything else is a 500 with
+    /// `message`, the cause being for the log only.
+    pub fn from_db(error: &DbError, message: impl Into<String>) -> Self {
+        match error {
+            DbError::NotFound(_) => Self::not_found(error.to_string()),
+            DbError::Constraint(_) => Self::conflict("That clashes with what's already stored"),
+            _ => Self::internal(message),
+        }
+    }
+
+    /// Attach anything serializable as `details`
+    pub fn with_details(mut self, details: impl Serialize) -> Self {
+        self.details = serde_json::to_value(details).ok();
+        self
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.code.status(), Json(self)).into_response()
+    }
+}
+
+/// A JSON request body. Like axum's `Json`, but a body that's missing, not
+/// JSON or the wrong shape is a `validation` error instead of plain text.
+pub struct ApiJson<T>(pub T);
+
+impl<T, S> FromRequest<S> for ApiJson<T>
+where
+    Json<T>: FromRequest<S, Rejection = JsonRejection>,
+    S: Send + Sync,
+{
+    type Rejection = ApiError;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        match Json::<T>::from_request(req, state).await {
+            Ok(Json(value)) => Ok(Self(value)),
+            Err(rejection) => Err(ApiError::validation(rejection.body_text())),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http_body_util::BodyExt;
+
+    #[tokio::test]
+    async fn test_api_error_response() {
+        let response = ApiError::not_found("Entry not found").into_response();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            String::from_utf8(bytes.to_vec()).unwrap(),
+            r#"{"code":"not_found","message":"Entry not found"}"#
+        );
+
+        let error = ApiError::validation("Invalid date").with_details(serde_json::json!({
+            "field": "date"
+        }));
+        assert_eq!(error.code.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            serde_json::to_value(&error).unwrap(),
+            serde_json::json!({
+                "code": "validation",
+                "message": "Invalid date",
+                "details": {"field": "date"}
+            })
+        );
+    }
+
+    #[test]
+    fn test_from_db() {
+        let missing = ApiError::from_db(&DbError::NotFound("Entry".to_string()), "Database error");
+        assert_eq!(missing, ApiError::not_found("Entry not found"));
+
+        let conn = rusqlite::Connection::open_in_memory().unwrap();
+        conn.execute_batch("CREATE TABLE t (id TEXT PRIMARY KEY); INSERT INTO t VALUES ('a');")
+            .unwrap();
+        let clash = DbError::from(conn.execute("INSERT INTO t VALUES ('a')", []).unwrap_err());
+        assert!(matches!(clash, DbError::Constraint(_)));
+        assert_eq!(
+            ApiError::from_db(&clash, "Database error").code,
+            ErrorCode::Conflict
+        );
+
+        let broken = DbError::from(conn.execute("SELECT * FROM nope", []).unwrap_err());
+        assert_eq!(
+            ApiError::from_db(&broken, "Database error"),
+            ApiError::internal("Database error")
+        );
+    }
+}
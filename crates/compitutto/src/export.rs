@@ -0,0 +1,433 @@
+//! Entries out of compitutto as CSV or iCalendar, for `/api/export.csv` and
+//! `/api/export.ics` (not to be confused with the portal's export files that
+//! come in through the parser), and as the CSV that Todoist and TickTick
+//! import, for `compitutto export`.
+//!
+//! All take whatever list the caller filtered; see `db::EntryFilter`.
+
+use chrono::{DateTime, Duration, NaiveDate, Utc};
+use std::collections::BTreeMap;
+
+use crate::data::is_test_or_quiz;
+use crate::subjects::SubjectIcons;
+use crate::types::HomeworkEntry;
+
+const CSV_HEADER: &str = "date,type,subject,task,completed,icon";
+
+/// Columns of Todoist's CSV import template
+const TODOIST_HEADER: &str =
+    "TYPE,CONTENT,DESCRIPTION,PRIORITY,INDENT,AUTHOR,RESPONSIBLE,DATE,DATE_LANG,TIMEZONE";
+
+/// Columns of a TickTick backup, which is what its CSV import reads
+const TICKTICK_COLUMNS: [&str; 24] = [
+    "Folder Name",
+    "List Name",
+    "Title",
+    "Kind",
+    "Tags",
+    "Content",
+    "Is Check list",
+    "Start Date",
+    "Due Date",
+    "Reminder",
+    "Repeat",
+    "Priority",
+    "Status",
+    "Created Time",
+    "Completed Time",
+    "Order",
+    "Timezone",
+    "Is All Day",
+    "Is Floating",
+    "Column Name",
+    "Column Order",
+    "View Mode",
+    "taskId",
+    "parentId",
+];
+
+/// Classe Viva is Italian; TickTick wants a zone for all-day dates
+const TICKTICK_TIMEZONE: &str = "Europe/Rome";
+
+/// iCalendar lines are folded at 75 octets
+const ICS_LINE_LIMIT: usize = 75;
+
+/// One row per entry, RFC 4180 quoting, CRLF line ends. `icon` is the
+/// subject's icon, empty when it has none.
+pub fn to_csv(entries: &[HomeworkEntry], icons: &SubjectIcons) -> String {
+    let mut out = String::from(CSV_HEADER);
+    out.push_str("\r\n");
+    for entry in entries {
+        let fields = [
+            entry.date.as_str(),
+            entry.entry_type.as_str(),
+            entry.subject.as_str(),
+            entry.task.as_str(),
+            if entry.completed { "yes" } else { "no" },
+            icons.icon(&entry.subject).unwrap_or(""),
+        ];
+        let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+        out.push_str(&row.join(","));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+fn csv_field(value: &str) -> String {
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value.to_string()
+    }
+}
+
+/// How urgent an entry is in a todo app
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Priority {
+    Low,
+    Normal,
+    High,
+    Urgent,
+}
+
+impl Priority {
+    /// Tests first, then homework, then study sessions
+    fn of(entry: &HomeworkEntry) -> Self {
+        match entry.entry_type.as_str() {
+            "studio" => Priority::Normal,
+            _ if entry.entry_type == "verifica" || is_test_or_quiz(entry) => Priority::Urgent,
+            "compiti" => Priority::High,
+            _ => Priority::Low,
+        }
+    }
+
+    /// Todoist's CSV counts from 1 (p1, most urgent) to 4
+    fn todoist(self) -> u8 {
+        match self {
+            Priority::Urgent => 1,
+            Priority::High => 2,
+            Priority::Normal => 3,
+            Priority::Low => 4,
+        }
+    }
+
+    /// TickTick uses 5 (high), 3 (medium), 1 (low) and 0 (none)
+    fn ticktick(self) -> u8 {
+        match self {
+            Priority::Urgent => 5,
+            Priority::High => 3,
+            Priority::Normal => 1,
+            Priority::Low => 0,
+        }
+    }
+}
+
+/// What's left to do, grouped by subject (the project or list in the todo
+/// app). Done entries and "Do it" reminders, which would duplicate their
+/// compiti, are left out.
+fn todo_by_subject(entries: &[HomeworkEntry]) -> BTreeMap<&str, Vec<&HomeworkEntry>> {
+    let mut by_subject: BTreeMap<&str, Vec<&HomeworkEntry>> = BTreeMap::new();
+    for entry in entries {
+        if entry.completed || (entry.entry_type == "lavoro" && entry.is_generated()) {
+            continue;
+        }
+        by_subject.entry(&entry.subject).or_default().push(entry);
+    }
+    by_subject
+}
+
+/// One line: "[verifica] Cap. 5" (compiti go without the type)
+fn todo_title(entry: &HomeworkEntry) -> String {
+    let task = entry.task.split_whitespace().collect::<Vec<_>>().join(" ");
+    if entry.entry_type == "compiti" || entry.entry_type.is_empty() {
+        task
+    } else {
+        format!("[{}] {}", entry.entry_type, task)
+    }
+}
+
+/// Todoist's CSV import: a section per subject with its tasks, dated and
+/// prioritised. Import it into a "School" project.
+pub fn to_todoist(entries: &[HomeworkEntry]) -> String {
+    let mut out = String::from(TODOIST_HEADER);
+    out.push_str("\r\n");
+    for (subject, entries) in todo_by_subject(entries) {
+        out.push_str(&format!("section,{},,,,,,,,\r\n", csv_field(subject)));
+        for entry in entries {
+            let fields = [
+                "task".to_string(),
+                todo_title(entry),
+                entry.task.clone(),
+                Priority::of(entry).todoist().to_string(),
+                "1".to_string(),
+                String::new(),
+                String::new(),
+                entry.date.clone(),
+                "en".to_string(),
+                String::new(),
+            ];
+            let row: Vec<String> = fields.iter().map(|f| csv_field(f)).collect();
+            out.push_str(&row.join(","));
+            out.push_str("\r\n");
+        }
+    }
+    out
+}
+
+/// TickTick's CSV import, shaped like its own backups: a list per subject in
+/// a "Scuola" folder, all-day tasks on the entry's date. Entries with an
+/// unparseable date are skipped.
+pub fn to_ticktick(entries: &[HomeworkEntry], now: DateTime<Utc>) -> String {
+    let mut out = format!(
+        "\"Date: {}\"\n\"Version: 7.1\"\n\"Status: \n0 Normal\n1 Completed\n2 Archived\"\n",
+        now.format("%Y-%m-%d+0000")
+    );
+    let header: Vec<String> = TICKTICK_COLUMNS
+        .iter()
+        .map(|c| format!("\"{}\"", c))
+        .collect();
+    out.push_str(&header.join(","));
+    out.push('\n');
+    let created = now.format("%Y-%m-%dT%H:%M:%S+0000").to_string();
+    for (subject, entries) in todo_by_subject(entries) {
+        for (order, entry) in entries.into_iter().enumerate() {
+            let Ok(date) = NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d") else {
+                continue;
+            };
+            let due = date.format("%Y-%m-%dT00:00:00+0000").to_string();
+            let priority = Priority::of(entry).ticktick().to_string();
+            let order = order.to_string();
+            let fields: [&str; 24] = [
+                "Scuola",
+                subject,
+                &todo_title(entry),
+                "TEXT",
+                &entry.entry_type,
+                &entry.task,
+                "N",
+                &due,
+                &due,
+                "",
+                "",
+                &priority,
+                "0",
+                &created,
+                "",
+                &order,
+                TICKTICK_TIMEZONE,
+                "true",
+                "false",
+                "",
+                "",
+                "list",
+                &entry.id,
+                "",
+            ];
+            let row: Vec<String> = fields
+                .iter()
+                .map(|f| format!("\"{}\"", f.replace('"', "\"\"")))
+                .collect();
+            out.push_str(&row.join(","));
+            out.push('\n');
+        }
+    }
+    out
+}
+
+/// An all-day `VEVENT` per entry. `UID`s are stable per entry, so calendar
+/// apps update events in place when the feed is imported again. Multi-day
+/// events run through their `end_date`, and the summary starts with the
+/// subject's icon. Entries with an unparseable date are skipped.
+pub fn to_ics(entries: &[HomeworkEntry], icons: &SubjectIcons, now: DateTime<Utc>) -> String {
+    let stamp = now.format("%Y%m%dT%H%M%SZ").to_string();
+    let mut lines = vec![
+        "BEGIN:VCALENDAR".to_string(),
+        "VERSION:2.0".to_string(),
+        "PRODID:-//compitutto//homework//IT".to_string(),
+        "CALSCALE:GREGORIAN".to_string(),
+        "X-WR-CALNAME:Compitutto".to_string(),
+    ];
+    for entry in entries {
+        let Ok(date) = NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d") else {
+            continue;
+        };
+        let last = entry
+            .end_date
+            .as_deref()
+            .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+            .filter(|&end| end > date)
+            .unwrap_or(date);
+        let subject = icons.label(&entry.subject);
+        let summary = if entry.entry_type.is_empty() {
+            subject
+        } else {
+            format!("{} ({})", subject, entry.entry_type)
+        };
+        lines.extend([
+            "BEGIN:VEVENT".to_string(),
+            format!("UID:{}@compitutto", entry.id),
+            format!("DTSTAMP:{}", stamp),
+            format!("DTSTART;VALUE=DATE:{}", date.format("%Y%m%d")),
+            format!(
+                "DTEND;VALUE=DATE:{}",
+                (last + Duration::days(1)).format("%Y%m%d")
+            ),
+            format!("SUMMARY:{}", ics_text(&summary)),
+            format!("DESCRIPTION:{}", ics_text(&entry.task)),
+            format!("CATEGORIES:{}", ics_text(&entry.entry_type)),
+            "END:VEVENT".to_string(),
+        ]);
+    }
+    lines.push("END:VCALENDAR".to_string());
+
+    let mut out = String::new();
+    for line in lines {
+        out.push_str(&fold(&line));
+        out.push_str("\r\n");
+    }
+    out
+}
+
+/// Escape a TEXT value (RFC 5545 §3.3.11)
+fn ics_text(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace(';', "\\;")
+        .replace(',', "\\,")
+        .replace("\r\n", "\\n")
+        .replace('\n', "\\n")
+}
+
+/// Split `line` into 75-octet pieces joined by CRLF + space, never inside a
+/// UTF-8 character
+fn fold(line: &str) -> String {
+    let mut out = String::new();
+    let mut width = 0;
+    for c in line.chars() {
+        if width + c.len_utf8() > ICS_LINE_LIMIT {
+            out.push_str("\r\n ");
+            // The leading space counts towards the next line
+            width = 1;
+        }
+        out.push(c);
+        width += c.len_utf8();
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(entry_type: &str, date: &str, subject: &str, task: &str) -> HomeworkEntry {
+        HomeworkEntry::new(
+            entry_type.to_string(),
+            date.to_string(),
+            subject.to_string(),
+            task.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_to_csv() {
+        let mut done = entry("compiti", "2025-01-15", "Matematica", "Pag. 10, es. 1");
+        done.completed = true;
+        let csv = to_csv(
+            &[
+                done,
+                entry("verifica", "2025-01-20", "Storia", "Cap. \"Roma\"\nripasso"),
+                entry("nota", "2025-01-21", "Coro", "Prove"),
+            ],
+            &SubjectIcons::default(),
+        );
+        assert_eq!(
+            csv,
+            "date,type,subject,task,completed,icon\r\n\
+             2025-01-15,compiti,Matematica,\"Pag. 10, es. 1\",yes,📐\r\n\
+             2025-01-20,verifica,Storia,\"Cap. \"\"Roma\"\"\nripasso\",no,🏛️\r\n\
+             2025-01-21,nota,Coro,Prove,no,\r\n"
+        );
+    }
+
+    #[test]
+    fn test_to_ics() {
+        let test = entry(
+            "verifica",
+            "2025-01-20",
+            "Storia",
+            "Cap. 5; date, nomi\nripasso",
+        );
+        let now = DateTime::parse_from_rfc3339("2025-01-10T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let ics = to_ics(
+            &[test.clone(), entry("compiti", "soon", "Arte", "?")],
+            &SubjectIcons::default(),
+            now,
+        );
+
+        assert!(ics.starts_with("BEGIN:VCALENDAR\r\nVERSION:2.0\r\n"));
+        assert!(ics.ends_with("END:VCALENDAR\r\n"));
+        assert_eq!(ics.matches("BEGIN:VEVENT").count(), 1);
+        assert!(ics.contains(&format!("UID:{}@compitutto\r\n", test.id)));
+        assert!(ics.contains("DTSTAMP:20250110T080000Z\r\n"));
+        assert!(ics.contains("DTSTART;VALUE=DATE:20250120\r\nDTEND;VALUE=DATE:20250121\r\n"));
+        assert!(ics.contains("SUMMARY:🏛️ Storia (verifica)\r\n"));
+        assert!(ics.contains("DESCRIPTION:Cap. 5\\; date\\, nomi\\nripasso\r\n"));
+    }
+
+    #[test]
+    fn test_to_todoist() {
+        let mut done = entry("compiti", "2025-01-14", "Storia", "Già fatto");
+        done.completed = true;
+        let mut reminder = entry("lavoro", "2025-01-15", "Storia", "Cap. 3");
+        reminder.parent_id = Some("parent".to_string());
+        let csv = to_todoist(&[
+            entry("compiti", "2025-01-16", "Storia", "Cap. 3,\nriassunto"),
+            entry("verifica", "2025-01-20", "Matematica", "Equazioni"),
+            done,
+            reminder,
+        ]);
+        assert_eq!(
+            csv,
+            "TYPE,CONTENT,DESCRIPTION,PRIORITY,INDENT,AUTHOR,RESPONSIBLE,DATE,DATE_LANG,TIMEZONE\r\n\
+             section,Matematica,,,,,,,,\r\n\
+             task,[verifica] Equazioni,Equazioni,1,1,,,2025-01-20,en,\r\n\
+             section,Storia,,,,,,,,\r\n\
+             task,\"Cap. 3, riassunto\",\"Cap. 3,\nriassunto\",2,1,,,2025-01-16,en,\r\n"
+        );
+    }
+
+    #[test]
+    fn test_to_ticktick() {
+        let now = DateTime::parse_from_rfc3339("2025-01-10T08:00:00Z")
+            .unwrap()
+            .with_timezone(&Utc);
+        let study = entry("studio", "2025-01-18", "Storia", "Ripasso per \"verifica\"");
+        let csv = to_ticktick(&[study.clone(), entry("compiti", "soon", "Arte", "?")], now);
+
+        let lines: Vec<&str> = csv.lines().collect();
+        assert_eq!(lines[0], "\"Date: 2025-01-10+0000\"");
+        assert!(lines[6].starts_with("\"Folder Name\",\"List Name\",\"Title\""));
+        assert_eq!(lines.len(), 8);
+        let row = lines[7];
+        assert!(row.starts_with(
+            "\"Scuola\",\"Storia\",\"[studio] Ripasso per \"\"verifica\"\"\",\"TEXT\",\"studio\""
+        ));
+        assert!(row.contains(
+            "\"2025-01-18T00:00:00+0000\",\"2025-01-18T00:00:00+0000\",\"\",\"\",\"1\",\"0\""
+        ));
+        assert!(row.contains(&format!("\"{}\"", study.id)));
+    }
+
+    #[test]
+    fn test_fold() {
+        let line = format!("DESCRIPTION:{}", "è".repeat(60));
+        let folded = fold(&line);
+        for piece in folded.split("\r\n") {
+            assert!(piece.len() <= ICS_LINE_LIMIT);
+        }
+        assert_eq!(folded.replace("\r\n ", ""), line);
+        assert_eq!(fold("SHORT"), "SHORT");
+    }
+}
@@ -0,0 +1,188 @@
+//! Feature flags: turn whole subsystems off, and back on, without rebuilding
+//! or restarting.
+//!
+//! Every flag is on by default. The `features` setting (a JSON object in the
+//! settings table, changed with `PUT /api/features`) can switch one off, and
+//! a `COMPITUTTO_FEATURE_<NAME>` environment variable (`on`/`off`, `true`/
+//! `false`, `1`/`0`) overrides both, for a deployment that wants a flag
+//! fixed. Flags are looked up each time the subsystem is about to do
+//! something, so a change applies to the next import, photo or page load.
+//!
+//! `GET /api/features` reports each flag with where its value came from, so
+//! the page can leave out what's switched off.
+
+use rusqlite::Connection;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use tracing::warn;
+
+use crate::db;
+
+/// A subsystem that can be switched off
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Feature {
+    /// Study sessions generated before tests (the `study_sessions` setting
+    /// still chooses whether one wants them)
+    StudySessions,
+    /// Importing exports dropped into `data/` as they arrive
+    Watcher,
+    /// Presence and completion notifications between open pages (`/api/live`)
+    Live,
+    /// Reading the text in photo attachments
+    Ocr,
+}
+
+impl Feature {
+    pub const ALL: [Feature; 4] = [
+        Feature::StudySessions,
+        Feature::Watcher,
+        Feature::Live,
+        Feature::Ocr,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            Feature::StudySessions => "study_sessions",
+            Feature::Watcher => "watcher",
+            Feature::Live => "live",
+            Feature::Ocr => "ocr",
+        }
+    }
+
+    pub fn description(self) -> &'static str {
+        match self {
+            Feature::StudySessions => "Generate study sessions before tests",
+            Feature::Watcher => "Import exports as they appear in data/",
+            Feature::Live => "Show who else is viewing and their ticks as they happen",
+            Feature::Ocr => "Read the text in photo attachments for search",
+        }
+    }
+
+    pub fn parse(name: &str) -> Option<Self> {
+        Feature::ALL.into_iter().find(|f| f.name() == name)
+    }
+
+    /// `COMPITUTTO_FEATURE_WATCHER` and so on
+    pub fn env_var(self) -> String {
+        format!("COMPITUTTO_FEATURE_{}", self.name().to_uppercase())
+    }
+}
+
+/// Where a flag's value came from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Source {
+    Default,
+    Settings,
+    /// The environment variable; `PUT /api/features` can't change it
+    Env,
+}
+
+/// One flag as reported by `/api/features`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FeatureState {
+    pub name: String,
+    pub enabled: bool,
+    pub source: Source,
+    pub env_var: String,
+    pub description: String,
+}
+
+/// `on`/`off`, `true`/`false`, `yes`/`no`, `1`/`0`, any case
+fn parse_switch(value: &str) -> Option<bool> {
+    match value.trim().to_lowercase().as_str() {
+        "on" | "true" | "yes" | "1" => Some(true),
+        "off" | "false" | "no" | "0" => Some(false),
+        _ => None,
+    }
+}
+
+/// Every flag's value from the stored flags and `env` (the environment, or
+/// a stand-in in tests). An environment value that doesn't parse is
+/// ignored with a warning.
+pub fn resolve(
+    stored: &BTreeMap<String, bool>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Vec<FeatureState> {
+    Feature::ALL
+        .into_iter()
+        .map(|feature| {
+            let env_var = feature.env_var();
+            let from_env = env(&env_var).and_then(|value| {
+                let switch = parse_switch(&value);
+                if switch.is_none() {
+                    warn!(var = %env_var, value = %value, "Ignoring feature flag that isn't on or off");
+                }
+                switch
+            });
+            let (enabled, source) = match (from_env, stored.get(feature.name())) {
+                (Some(enabled), _) => (enabled, Source::Env),
+                (None, Some(&enabled)) => (enabled, Source::Settings),
+                (None, None) => (true, Source::Default),
+            };
+            FeatureState {
+                name: feature.name().to_string(),
+                enabled,
+                source,
+                env_var,
+                description: feature.description().to_string(),
+            }
+        })
+        .collect()
+}
+
+/// Every flag, as the server sees it now
+pub fn load(conn: &Connection) -> db::Result<Vec<FeatureState>> {
+    Ok(resolve(&db::get_features(conn)?, |var| {
+        std::env::var(var).ok()
+    }))
+}
+
+/// Whether `feature` is on. A database error counts as on, so a hiccup
+/// doesn't quietly stop imports.
+pub fn enabled(conn: &Connection, feature: Feature) -> bool {
+    match load(conn) {
+        Ok(states) => states
+            .iter()
+            .find(|s| s.name == feature.name())
+            .is_none_or(|s| s.enabled),
+        Err(e) => {
+            warn!(error = %e, feature = feature.name(), "Failed to read feature flags");
+            true
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_resolve() {
+        let stored = BTreeMap::from([("watcher".to_string(), false), ("ocr".to_string(), false)]);
+        let env = |var: &str| match var {
+            "COMPITUTTO_FEATURE_OCR" => Some("On".to_string()),
+            "COMPITUTTO_FEATURE_LIVE" => Some("maybe".to_string()),
+            _ => None,
+        };
+        let states = resolve(&stored, env);
+        let flags: Vec<(&str, bool, Source)> = states
+            .iter()
+            .map(|s| (s.name.as_str(), s.enabled, s.source))
+            .collect();
+        assert_eq!(
+            flags,
+            vec![
+                ("study_sessions", true, Source::Default),
+                ("watcher", false, Source::Settings),
+                // A value that isn't a switch falls through to the default
+                ("live", true, Source::Default),
+                ("ocr", true, Source::Env),
+            ]
+        );
+        assert_eq!(states[1].env_var, "COMPITUTTO_FEATURE_WATCHER");
+        assert_eq!(Feature::parse("ocr"), Some(Feature::Ocr));
+        assert_eq!(Feature::parse("auth"), None);
+    }
+}
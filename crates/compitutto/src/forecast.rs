@@ -0,0 +1,265 @@
+//! Workload forecast for the coming weeks (`GET /api/forecast`).
+//!
+//! Each Monday–Sunday week from the current one on gets a count of open
+//! entries and tests and an estimate of the homework minutes, so a heavy
+//! week shows up while there's still time to get ahead. Study sessions and
+//! "Do it" reminders that haven't been generated yet (a test imported for
+//! next month, a reprocess not run) are projected with the same rules as
+//! the real generation and counted too. After-school activity time is
+//! reported alongside, since it eats into the same afternoons.
+
+use chrono::{Datelike, Duration, NaiveDate, Weekday};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::data::{generate_study_sessions, generate_work_reminder, is_test_or_quiz};
+use crate::plan::DEFAULT_ITEM_MINUTES;
+use crate::types::{HomeworkEntry, Settings};
+
+/// Weeks reported without `?weeks=`
+pub const DEFAULT_WEEKS: u32 = 4;
+
+/// Most weeks one request may ask for
+pub const MAX_WEEKS: u32 = 12;
+
+/// One week of the forecast
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WeekForecast {
+    /// Monday, `YYYY-MM-DD`
+    pub week_start: String,
+    /// Sunday, `YYYY-MM-DD`
+    pub week_end: String,
+    /// Open entries, projected ones included
+    pub entries: usize,
+    pub tests: usize,
+    /// Homework time: estimates, or the plan's default for compiti, reminders
+    /// and study sessions without one
+    pub estimated_minutes: u32,
+    /// How many of `entries` are study sessions or reminders still to be
+    /// generated
+    pub projected: usize,
+    pub activity_minutes: u32,
+}
+
+/// Response of `GET /api/forecast`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Forecast {
+    pub weeks: Vec<WeekForecast>,
+    /// `week_start` of the week with the most minutes, if any has some
+    pub busiest: Option<String>,
+}
+
+/// The study sessions and reminders `entries` would get from a reprocess
+/// today, minus those that already exist.
+pub fn project_auto_entries(
+    entries: &[HomeworkEntry],
+    settings: &Settings,
+    today: NaiveDate,
+    holidays: &HashSet<NaiveDate>,
+    activity_minutes: &HashMap<NaiveDate, u32>,
+) -> Vec<HomeworkEntry> {
+    let existing: HashSet<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+    let mut projected = Vec::new();
+    for entry in entries.iter().filter(|e| !e.is_generated() && !e.completed) {
+        if settings.study_sessions && is_test_or_quiz(entry) {
+            projected.extend(generate_study_sessions(
+                entry,
+                today,
+                settings.study_days_before,
+                holidays,
+                activity_minutes,
+                settings.split_study_topics,
+            ));
+        }
+        projected.extend(generate_work_reminder(
+            entry,
+            today,
+            &settings.work_days,
+            settings.homework_days_ahead,
+        ));
+    }
+    projected.retain(|e| !existing.contains(e.id.as_str()));
+    projected
+}
+
+/// Forecast `weeks` weeks from the one containing `today`.
+pub fn build_forecast(
+    entries: &[HomeworkEntry],
+    projected: &[HomeworkEntry],
+    activity_minutes: &HashMap<NaiveDate, u32>,
+    today: NaiveDate,
+    weeks: u32,
+) -> Forecast {
+    let by_id: HashMap<&str, &HomeworkEntry> = entries.iter().map(|e| (e.id.as_str(), e)).collect();
+    // A compiti with a reminder is counted on the reminder's day
+    let reminded: HashSet<&str> = entries
+        .iter()
+        .chain(projected)
+        .filter(|e| e.entry_type == "lavoro")
+        .filter_map(|e| e.parent_id.as_deref())
+        .collect();
+    let minutes = |entry: &HomeworkEntry| -> u32 {
+        let estimate = entry.estimated_minutes.or_else(|| {
+            let parent = entry
+                .parent_id
+                .as_deref()
+                .filter(|_| entry.entry_type == "lavoro")?;
+            by_id.get(parent)?.estimated_minutes
+        });
+        match entry.entry_type.as_str() {
+            "compiti" if reminded.contains(entry.id.as_str()) => 0,
+            "compiti" | "lavoro" | "studio" => estimate.unwrap_or(DEFAULT_ITEM_MINUTES),
+            _ => estimate.unwrap_or(0),
+        }
+    };
+
+    let first = today.week(Weekday::Mon).first_day();
+    let mut forecast: Vec<WeekForecast> = (0..weeks.clamp(1, MAX_WEEKS))
+        .map(|w| {
+            let start = first + Duration::weeks(w as i64);
+            let end = start + Duration::days(6);
+            WeekForecast {
+                week_start: start.to_string(),
+                week_end: end.to_string(),
+                entries: 0,
+                tests: 0,
+                estimated_minutes: 0,
+                projected: 0,
+                activity_minutes: activity_minutes
+                    .iter()
+                    .filter(|(d, _)| (start..=end).contains(d))
+                    .map(|(_, m)| m)
+                    .sum(),
+            }
+        })
+        .collect();
+    let week_count = forecast.len();
+    let week_of = |date: &str| -> Option<usize> {
+        let date = NaiveDate::parse_from_str(date, "%Y-%m-%d").ok()?;
+        let index = usize::try_from((date - first).num_days().div_euclid(7)).ok()?;
+        (index < week_count).then_some(index)
+    };
+
+    let open = entries.iter().filter(|e| !e.completed).map(|e| (e, false));
+    for (entry, is_projected) in open.chain(projected.iter().map(|e| (e, true))) {
+        let Some(index) = week_of(&entry.date) else {
+            continue;
+        };
+        let week = &mut forecast[index];
+        week.entries += 1;
+        if !entry.is_generated() && is_test_or_quiz(entry) {
+            week.tests += 1;
+        }
+        week.estimated_minutes += minutes(entry);
+        if is_projected {
+            week.projected += 1;
+        }
+    }
+
+    let busiest = forecast
+        .iter()
+        .filter(|w| w.estimated_minutes > 0)
+        // The earliest of equally busy weeks
+        .max_by(|a, b| {
+            a.estimated_minutes
+                .cmp(&b.estimated_minutes)
+                .then(b.week_start.cmp(&a.week_start))
+        })
+        .map(|w| w.week_start.clone());
+    Forecast {
+        weeks: forecast,
+        busiest,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(entry_type: &str, date: &str, task: &str, minutes: Option<u32>) -> HomeworkEntry {
+        let mut entry = HomeworkEntry::new(
+            entry_type.to_string(),
+            date.to_string(),
+            "Matematica".to_string(),
+            task.to_string(),
+        );
+        entry.estimated_minutes = minutes;
+        entry
+    }
+
+    fn day(s: &str) -> NaiveDate {
+        NaiveDate::parse_from_str(s, "%Y-%m-%d").unwrap()
+    }
+
+    #[test]
+    fn test_build_forecast_weeks() {
+        // Wednesday
+        let today = day("2025-01-15");
+        let mut done = entry("compiti", "2025-01-16", "Esercizi", Some(90));
+        done.completed = true;
+        let entries = vec![
+            entry("compiti", "2025-01-13", "Pag. 10", None),
+            done,
+            entry("compiti", "2025-01-21", "Pag. 20", Some(45)),
+            entry("verifica", "2025-01-24", "Verifica sui limiti", None),
+            entry("compiti", "2025-02-20", "Too far", Some(60)),
+        ];
+        let activities: HashMap<NaiveDate, u32> =
+            [(day("2025-01-14"), 60), (day("2025-01-22"), 90)].into();
+
+        let forecast = build_forecast(&entries, &[], &activities, today, 2);
+        assert_eq!(forecast.weeks.len(), 2);
+        let (this, next) = (&forecast.weeks[0], &forecast.weeks[1]);
+        assert_eq!(this.week_start, "2025-01-13");
+        assert_eq!(this.week_end, "2025-01-19");
+        assert_eq!((this.entries, this.tests), (1, 0));
+        assert_eq!(this.estimated_minutes, DEFAULT_ITEM_MINUTES);
+        assert_eq!(this.activity_minutes, 60);
+        assert_eq!((next.entries, next.tests), (2, 1));
+        assert_eq!(next.estimated_minutes, 45);
+        assert_eq!(next.activity_minutes, 90);
+        assert_eq!(forecast.busiest.as_deref(), Some("2025-01-20"));
+    }
+
+    #[test]
+    fn test_projected_entries_are_counted_once() {
+        let today = day("2025-01-15");
+        let homework = entry("compiti", "2025-01-24", "Pag. 30", Some(40));
+        let test = entry("verifica", "2025-01-27", "Verifica sui limiti", None);
+        let entries = vec![homework, test];
+        let settings = Settings::default();
+
+        let projected =
+            project_auto_entries(&entries, &settings, today, &HashSet::new(), &HashMap::new());
+        let sessions = projected
+            .iter()
+            .filter(|e| e.entry_type == "studio")
+            .count();
+        assert_eq!(sessions as u32, settings.study_days_before);
+        assert_eq!(
+            projected
+                .iter()
+                .filter(|e| e.entry_type == "lavoro")
+                .count(),
+            1
+        );
+
+        // Generated already: nothing left to project
+        let mut all = entries.clone();
+        all.extend(projected.clone());
+        assert!(
+            project_auto_entries(&all, &settings, today, &HashSet::new(), &HashMap::new())
+                .is_empty()
+        );
+
+        let forecast = build_forecast(&entries, &projected, &HashMap::new(), today, 3);
+        let total: usize = forecast.weeks.iter().map(|w| w.projected).sum();
+        assert_eq!(total, projected.len());
+        // The compiti's 40 minutes move to its reminder, not counted twice
+        let minutes: u32 = forecast.weeks.iter().map(|w| w.estimated_minutes).sum();
+        assert_eq!(
+            minutes,
+            40 + settings.study_days_before * DEFAULT_ITEM_MINUTES
+        );
+    }
+}
@@ -0,0 +1,419 @@
+//! `POST /graphql`: entries, subjects and stats in the shape a client asks
+//! for, built only with the `graphql` cargo feature.
+//!
+//! The REST API stays the complete one. This is for companion clients that
+//! would otherwise fetch a list of entries, then each entry's children, then
+//! three stats endpoints, to show one screen. `entries` takes the filters of
+//! `/api/entries`; the two mutations tick an entry off (or back on) and move
+//! it, with the same side effects as `PATCH /api/entries/{id}` and
+//! `POST /api/entries/{id}/move`: live notifications, all-done days, study
+//! sessions following their test. Fields are camelCase, as GraphQL has
+//! them, and errors carry the REST error `code` in their `extensions`.
+//!
+//! With `--read-only` queries still work and mutations are refused.
+
+use async_graphql::{
+    ComplexObject, Context, EmptySubscription, ErrorExtensions, Object, Schema, SimpleObject,
+};
+use axum::{extract::State, Json};
+use chrono::NaiveDate;
+use std::sync::{Arc, OnceLock};
+use tracing::{debug, error};
+
+use crate::db::{self, DbError, EntryUpdate};
+use crate::error::{ApiError, ApiJson};
+use crate::live::LiveEvent;
+use crate::overload::DayWarning;
+use crate::server::{self, AppState};
+use crate::subjects;
+use crate::types::{
+    DaysCompletedReport, HomeworkEntry, NoticeStats, SubjectTimeStats, SubjectsReport, TopicStats,
+};
+
+/// Deepest a query may nest (`entries { children { children ... } }`)
+const MAX_DEPTH: usize = 10;
+
+pub type AppSchema = Schema<QueryRoot, MutationRoot, EmptySubscription>;
+
+/// The schema, built once; each request brings the state along
+pub fn schema() -> &'static AppSchema {
+    static SCHEMA: OnceLock<AppSchema> = OnceLock::new();
+    SCHEMA.get_or_init(|| {
+        Schema::build(QueryRoot, MutationRoot, EmptySubscription)
+            .limit_depth(MAX_DEPTH)
+            .finish()
+    })
+}
+
+/// Run one query or mutation (`{"query": ..., "variables": ...}`)
+pub async fn graphql_handler(
+    State(state): State<Arc<AppState>>,
+    ApiJson(request): ApiJson<async_graphql::Request>,
+) -> Json<async_graphql::Response> {
+    Json(schema().execute(request.data(state)).await)
+}
+
+fn app_state<'a>(ctx: &Context<'a>) -> &'a Arc<AppState> {
+    ctx.data_unchecked::<Arc<AppState>>()
+}
+
+/// `error` as a GraphQL error, with its `code` and `details` as extensions
+fn graphql_error(error: ApiError) -> async_graphql::Error {
+    let code = serde_json::to_value(error.code)
+        .ok()
+        .and_then(|code| code.as_str().map(str::to_string))
+        .unwrap_or_default();
+    let details = error
+        .details
+        .and_then(|details| async_graphql::Value::from_json(details).ok());
+    async_graphql::Error::new(error.message).extend_with(|_, extensions| {
+        extensions.set("code", code);
+        if let Some(details) = details {
+            extensions.set("details", details);
+        }
+    })
+}
+
+/// Log a failed database call, and answer what REST would
+fn db_error(action: &'static str) -> impl FnOnce(DbError) -> async_graphql::Error {
+    move |e| {
+        error!(error = %e, "Failed to {}", action);
+        graphql_error(ApiError::from_db(&e, "Database error"))
+    }
+}
+
+fn not_found() -> async_graphql::Error {
+    graphql_error(ApiError::not_found("Entry not found"))
+}
+
+/// A `YYYY-MM-DD` argument
+fn check_date(name: &str, value: &str) -> async_graphql::Result<()> {
+    match NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        Ok(_) => Ok(()),
+        Err(_) => Err(graphql_error(
+            ApiError::validation(format!("{} must be YYYY-MM-DD", name))
+                .with_details(serde_json::json!({ "field": name })),
+        )),
+    }
+}
+
+pub struct QueryRoot;
+
+#[Object]
+impl QueryRoot {
+    /// Entries by date and position. `subjects`, `types` and `topics` match
+    /// any of their values, case-insensitively; `q` searches tasks and
+    /// photo text.
+    #[allow(clippy::too_many_arguments)]
+    async fn entries(
+        &self,
+        ctx: &Context<'_>,
+        from: Option<String>,
+        to: Option<String>,
+        #[graphql(default)] subjects: Vec<String>,
+        #[graphql(default)] types: Vec<String>,
+        #[graphql(default)] topics: Vec<String>,
+        q: Option<String>,
+        completed: Option<bool>,
+    ) -> async_graphql::Result<Vec<HomeworkEntry>> {
+        for (name, value) in [("from", &from), ("to", &to)] {
+            if let Some(value) = value {
+                check_date(name, value)?;
+            }
+        }
+        let filter = db::EntryFilter {
+            from,
+            to,
+            subjects,
+            types,
+            topics,
+            q,
+            completed,
+            visible_on: None,
+        };
+        let conn = app_state(ctx).conn.lock().unwrap();
+        db::get_entries_filtered(&conn, &filter).map_err(db_error("get entries"))
+    }
+
+    /// One entry, `null` if there's none with `id`
+    async fn entry(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+    ) -> async_graphql::Result<Option<HomeworkEntry>> {
+        let conn = app_state(ctx).conn.lock().unwrap();
+        db::get_entry(&conn, &id).map_err(db_error("get entry"))
+    }
+
+    /// Subjects in use with entry counts, plus likely duplicates to merge
+    async fn subjects(&self, ctx: &Context<'_>) -> async_graphql::Result<SubjectsReport> {
+        let conn = app_state(ctx).conn.lock().unwrap();
+        let subjects = db::get_subject_counts(&conn).map_err(db_error("get subjects"))?;
+        Ok(SubjectsReport {
+            suggestions: subjects::suggest_merges(&subjects),
+            subjects,
+        })
+    }
+
+    /// The numbers of `/stats`; each is only worked out when asked for
+    async fn stats(&self) -> Stats {
+        Stats
+    }
+}
+
+pub struct Stats;
+
+#[Object]
+impl Stats {
+    /// Estimated vs actual time per subject
+    async fn time(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<SubjectTimeStats>> {
+        let conn = app_state(ctx).conn.lock().unwrap();
+        db::get_subject_time_stats(&conn).map_err(db_error("compute stats"))
+    }
+
+    /// Entries, tests and time per subject and topic, busiest first
+    async fn topics(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<TopicStats>> {
+        let conn = app_state(ctx).conn.lock().unwrap();
+        db::get_topic_stats(&conn).map_err(db_error("compute topic stats"))
+    }
+
+    /// Days of notice per subject and teacher, least first
+    async fn notice(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<NoticeStats>> {
+        let conn = app_state(ctx).conn.lock().unwrap();
+        db::get_notice_stats(&conn).map_err(db_error("compute notice stats"))
+    }
+
+    /// Days all done in `month` (`YYYY-MM`, default this month) and the
+    /// streak
+    async fn days_completed(
+        &self,
+        ctx: &Context<'_>,
+        month: Option<String>,
+    ) -> async_graphql::Result<DaysCompletedReport> {
+        let Some(first) = server::parse_month(month.as_deref()) else {
+            return Err(graphql_error(ApiError::validation("month must be YYYY-MM")));
+        };
+        let conn = app_state(ctx).conn.lock().unwrap();
+        server::days_completed_report(&conn, first).map_err(db_error("load completed days"))
+    }
+}
+
+#[ComplexObject]
+impl HomeworkEntry {
+    /// Study sessions and reminders generated from this entry
+    async fn children(&self, ctx: &Context<'_>) -> async_graphql::Result<Vec<HomeworkEntry>> {
+        let conn = app_state(ctx).conn.lock().unwrap();
+        db::get_children(&conn, &self.id).map_err(db_error("get children"))
+    }
+}
+
+/// An entry after `setCompleted`
+#[derive(SimpleObject)]
+pub struct CompletedEntry {
+    entry: HomeworkEntry,
+    /// Ticking it off finished its day
+    day_completed: bool,
+}
+
+/// An entry after `moveEntry`, with its new day's warning if any
+#[derive(SimpleObject)]
+pub struct MovedEntry {
+    entry: HomeworkEntry,
+    warning: Option<DayWarning>,
+}
+
+pub struct MutationRoot;
+
+#[Object]
+impl MutationRoot {
+    /// Tick an entry off, or back on
+    async fn set_completed(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        completed: bool,
+    ) -> async_graphql::Result<CompletedEntry> {
+        let state = app_state(ctx);
+        if state.read_only {
+            return Err(graphql_error(ApiError::read_only()));
+        }
+        let conn = state.conn.lock().unwrap();
+        let updates = EntryUpdate {
+            completed: Some(completed),
+            ..Default::default()
+        };
+        if !db::update_entry(&conn, &id, &updates).map_err(db_error("update entry"))? {
+            return Err(not_found());
+        }
+        let entry = db::get_entry(&conn, &id)
+            .map_err(db_error("get entry"))?
+            .ok_or_else(not_found)?;
+        debug!(id = %id, completed, "Entry completion set");
+        state.live.publish(LiveEvent::Entry {
+            id: entry.id.clone(),
+            completed: entry.completed,
+        });
+        let day_completed = server::track_day(&conn, &entry.date, completed);
+        Ok(CompletedEntry {
+            entry,
+            day_completed,
+        })
+    }
+
+    /// Move an entry to a day, at `index` there (0 = top; left out, the
+    /// bottom)
+    async fn move_entry(
+        &self,
+        ctx: &Context<'_>,
+        id: String,
+        date: String,
+        index: Option<usize>,
+    ) -> async_graphql::Result<MovedEntry> {
+        let state = app_state(ctx);
+        if state.read_only {
+            return Err(graphql_error(ApiError::read_only()));
+        }
+        check_date("date", &date)?;
+        let conn = state.conn.lock().unwrap();
+        let old_date = db::get_entry(&conn, &id)
+            .map_err(db_error("get entry"))?
+            .ok_or_else(not_found)?
+            .date;
+        db::move_entry(&conn, &id, &date, index).map_err(db_error("move entry"))?;
+        let entry = db::get_entry(&conn, &id)
+            .map_err(db_error("get entry"))?
+            .ok_or_else(not_found)?;
+        debug!(id = %id, date = %entry.date, position = entry.position, "Entry moved");
+        if entry.date == old_date {
+            return Ok(MovedEntry {
+                entry,
+                warning: None,
+            });
+        }
+        server::reschedule_study_sessions(&conn, &entry, &old_date);
+        let checked = server::checked_entry(&conn, entry).entry;
+        Ok(MovedEntry {
+            entry: checked.entry,
+            warning: checked.warning,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn test_state(entries: &[HomeworkEntry]) -> (TempDir, Arc<AppState>) {
+        let temp_dir = TempDir::new().unwrap();
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+        db::write_test_migrations(&migrations_dir);
+        let conn = db::init_db(&temp_dir.path().join("test.db"), &migrations_dir).unwrap();
+        for entry in entries {
+            db::insert_entry(&conn, entry).unwrap();
+        }
+        (temp_dir, Arc::new(AppState::new(conn)))
+    }
+
+    async fn run(state: &Arc<AppState>, query: &str) -> async_graphql::Response {
+        schema()
+            .execute(async_graphql::Request::new(query).data(state.clone()))
+            .await
+    }
+
+    fn entry(entry_type: &str, date: &str, subject: &str, task: &str) -> HomeworkEntry {
+        HomeworkEntry::new(
+            entry_type.to_string(),
+            date.to_string(),
+            subject.to_string(),
+            task.to_string(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_graphql() {
+        let test = entry("verifica", "2025-01-20", "Storia", "Cap. 5");
+        let mut session = entry("studio", "2025-01-19", "Storia", "Ripasso");
+        session.parent_id = Some(test.id.clone());
+        let homework = entry("compiti", "2025-01-21", "Matematica", "Es. 4");
+        let (_dir, state) = test_state(&[test.clone(), session.clone(), homework.clone()]);
+
+        let response = run(
+            &state,
+            r#"{ entries(subjects: ["storia"], types: ["verifica"]) { subject type children { task } } }"#,
+        )
+        .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({"entries": [{"subject": "Storia", "type": "verifica", "children": [{"task": "Ripasso"}]}]})
+        );
+
+        let response = run(
+            &state,
+            &format!(
+                r#"mutation {{ setCompleted(id: "{}", completed: true) {{ entry {{ completed }} dayCompleted }} }}"#,
+                homework.id
+            ),
+        )
+        .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        assert_eq!(
+            response.data.into_json().unwrap(),
+            json!({"setCompleted": {"entry": {"completed": true}, "dayCompleted": true}})
+        );
+
+        let response = run(
+            &state,
+            &format!(
+                r#"mutation {{ moveEntry(id: "{}", date: "2025-01-22") {{ entry {{ date }} }} }}"#,
+                test.id
+            ),
+        )
+        .await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+        // The study session moved along with its test
+        let moved = db::get_entry(&state.conn.lock().unwrap(), &session.id)
+            .unwrap()
+            .unwrap();
+        assert_eq!(moved.date, "2025-01-21");
+
+        let response = run(&state, r#"{ entries(from: "22/01/2025") { id } }"#).await;
+        let error = &response.errors[0];
+        assert_eq!(error.message, "from must be YYYY-MM-DD");
+        assert_eq!(
+            error.extensions.as_ref().and_then(|e| e.get("code")),
+            Some(&async_graphql::Value::from("validation".to_string()))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_graphql_read_only() {
+        let homework = entry("compiti", "2025-01-21", "Matematica", "Es. 4");
+        let (_dir, state) = test_state(std::slice::from_ref(&homework));
+        let mut state = Arc::into_inner(state).unwrap();
+        state.read_only = true;
+        let state = Arc::new(state);
+
+        let response = run(&state, "{ subjects { subjects { subject entries } } }").await;
+        assert!(response.errors.is_empty(), "{:?}", response.errors);
+
+        let response = run(
+            &state,
+            &format!(
+                r#"mutation {{ setCompleted(id: "{}", completed: true) {{ dayCompleted }} }}"#,
+                homework.id
+            ),
+        )
+        .await;
+        assert_eq!(response.errors[0].message, "Read-only mode");
+        assert!(
+            !db::get_entry(&state.conn.lock().unwrap(), &homework.id)
+                .unwrap()
+                .unwrap()
+                .completed
+        );
+    }
+}
@@ -0,0 +1,240 @@
+//! School holidays from an iCal (`.ics`) feed.
+//!
+//! `compitutto holidays <URL|FILE>` reads the calendar, expands every event
+//! into the days it covers and replaces the `holidays` table. Holidays are
+//! shaded in the calendar view, skipped when placing study sessions, and
+//! imported homework dated on one is logged as a likely parse error.
+//!
+//! Only what school calendars use is supported: `DTSTART`/`DTEND` as dates or
+//! date-times, and `SUMMARY`. Recurring events (`RRULE`) count once.
+
+use anyhow::{bail, Context, Result};
+use chrono::{Duration, NaiveDate};
+use std::collections::BTreeMap;
+
+use crate::types::Holiday;
+
+/// Longest event we expand, so a malformed DTEND can't fill the table
+const MAX_EVENT_DAYS: i64 = 120;
+
+/// Read an iCal feed from an `http(s)://` URL or a local file.
+pub async fn read_source(source: &str) -> Result<String> {
+    if is_url(source) {
+        let response = reqwest::get(source)
+            .await
+            .with_context(|| format!("Failed to fetch calendar from {}", source))?
+            .error_for_status()
+            .with_context(|| format!("Failed to fetch calendar from {}", source))?;
+        Ok(response.text().await?)
+    } else {
+        std::fs::read_to_string(source)
+            .with_context(|| format!("Failed to read calendar file: {}", source))
+    }
+}
+
+/// Whether `source` is an `http(s)://` URL rather than a file
+pub fn is_url(source: &str) -> bool {
+    source.starts_with("http://") || source.starts_with("https://")
+}
+
+/// Parse an iCal calendar into one `Holiday` per day, sorted by date.
+///
+/// When events overlap, the first one's name is kept.
+pub fn parse_ical(text: &str) -> Result<Vec<Holiday>> {
+    let lines = unfold(text);
+    if !lines
+        .iter()
+        .any(|l| l.eq_ignore_ascii_case("BEGIN:VCALENDAR"))
+    {
+        bail!("Not an iCal calendar (no BEGIN:VCALENDAR)");
+    }
+
+    let mut days: BTreeMap<NaiveDate, String> = BTreeMap::new();
+    let mut event: Option<Event> = None;
+    for line in &lines {
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        // Drop parameters: "DTSTART;VALUE=DATE" -> "DTSTART"
+        let name = key.split(';').next().unwrap_or(key).to_ascii_uppercase();
+
+        match (name.as_str(), event.as_mut()) {
+            ("BEGIN", None) if value.eq_ignore_ascii_case("VEVENT") => {
+                event = Some(Event::default());
+            }
+            ("END", Some(_)) if value.eq_ignore_ascii_case("VEVENT") => {
+                let ev = event.take().unwrap();
+                for (date, name) in ev.days()? {
+                    days.entry(date).or_insert(name);
+                }
+            }
+            ("DTSTART", Some(ev)) => ev.start = Some(parse_ical_date(value)?),
+            ("DTEND", Some(ev)) => ev.end = Some(parse_ical_date(value)?),
+            ("SUMMARY", Some(ev)) => ev.summary = unescape(value),
+            _ => {}
+        }
+    }
+
+    Ok(days
+        .into_iter()
+        .map(|(date, name)| Holiday {
+            date: date.format("%Y-%m-%d").to_string(),
+            name,
+        })
+        .collect())
+}
+
+#[derive(Debug, Default)]
+struct Event {
+    start: Option<IcalDate>,
+    end: Option<IcalDate>,
+    summary: String,
+}
+
+/// A `DTSTART`/`DTEND` value: the date, and whether it had a time part
+#[derive(Debug, Clone, Copy)]
+struct IcalDate {
+    date: NaiveDate,
+    /// Time of day was exactly midnight (or absent)
+    midnight: bool,
+}
+
+impl Event {
+    /// Days covered. All-day `DTEND` is exclusive; a timed end counts its own
+    /// day unless it is midnight. No `DTEND` means a single day.
+    fn days(&self) -> Result<Vec<(NaiveDate, String)>> {
+        let Some(start) = self.start else {
+            return Ok(Vec::new());
+        };
+        let last = match self.end {
+            Some(end) if end.midnight => end.date - Duration::days(1),
+            Some(end) => end.date,
+            None => start.date,
+        }
+        .max(start.date);
+
+        let span = (last - start.date).num_days() + 1;
+        if span > MAX_EVENT_DAYS {
+            bail!(
+                "Holiday '{}' spans {} days (from {}); refusing to import",
+                self.summary,
+                span,
+                start.date
+            );
+        }
+        Ok((0..span)
+            .map(|d| (start.date + Duration::days(d), self.summary.clone()))
+            .collect())
+    }
+}
+
+/// `20251224`, `20251224T090000` or `20251224T090000Z`
+fn parse_ical_date(value: &str) -> Result<IcalDate> {
+    let value = value.trim();
+    let (date, time) = value.split_once('T').unwrap_or((value, ""));
+    let date = NaiveDate::parse_from_str(date, "%Y%m%d")
+        .with_context(|| format!("Invalid iCal date: {}", value))?;
+    Ok(IcalDate {
+        date,
+        midnight: time.trim_end_matches('Z').chars().all(|c| c == '0'),
+    })
+}
+
+/// Join folded lines (continuations start with a space or tab)
+pub(crate) fn unfold(text: &str) -> Vec<String> {
+    let mut lines: Vec<String> = Vec::new();
+    for line in text.lines() {
+        match (line.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(rest), Some(last)) => last.push_str(rest),
+            _ => lines.push(line.trim_end().to_string()),
+        }
+    }
+    lines
+}
+
+/// Undo iCal text escaping (`\,` `\;` `\n` `\\`)
+pub(crate) fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push(' '),
+            Some(other) => out.push(other),
+            None => {}
+        }
+    }
+    out.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FEED: &str = "BEGIN:VCALENDAR\r
+VERSION:2.0\r
+BEGIN:VEVENT\r
+DTSTART;VALUE=DATE:20251222\r
+DTEND;VALUE=DATE:20251225\r
+SUMMARY:Vacanze di Natale\\, prima parte\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART:20251208T000000Z\r
+SUMMARY:Immacolata\r
+  Concezione\r
+END:VEVENT\r
+BEGIN:VEVENT\r
+DTSTART:20260402T080000\r
+DTEND:20260403T120000\r
+SUMMARY:Vacanze di Pasqua\r
+END:VEVENT\r
+END:VCALENDAR\r
+";
+
+    fn dates(holidays: &[Holiday]) -> Vec<&str> {
+        holidays.iter().map(|h| h.date.as_str()).collect()
+    }
+
+    #[test]
+    fn test_parse_ical_expands_events_into_days() {
+        let holidays = parse_ical(FEED).unwrap();
+        assert_eq!(
+            dates(&holidays),
+            vec![
+                "2025-12-08",
+                "2025-12-22",
+                "2025-12-23",
+                "2025-12-24",
+                "2026-04-02",
+                "2026-04-03",
+            ]
+        );
+        assert_eq!(holidays[0].name, "Immacolata Concezione");
+        assert_eq!(holidays[1].name, "Vacanze di Natale, prima parte");
+    }
+
+    #[test]
+    fn test_parse_ical_rejects_non_calendars_and_huge_events() {
+        assert!(parse_ical("date,name\n2025-12-25,Natale").is_err());
+
+        let huge = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART;VALUE=DATE:20250101\n\
+                    DTEND;VALUE=DATE:20260101\nSUMMARY:Oops\nEND:VEVENT\nEND:VCALENDAR";
+        assert!(parse_ical(huge).unwrap_err().to_string().contains("Oops"));
+    }
+
+    #[test]
+    fn test_parse_ical_keeps_first_name_on_overlap() {
+        let feed = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART;VALUE=DATE:20251225\n\
+                    SUMMARY:Natale\nEND:VEVENT\nBEGIN:VEVENT\nDTSTART;VALUE=DATE:20251224\n\
+                    DTEND;VALUE=DATE:20251227\nSUMMARY:Vacanze\nEND:VEVENT\nEND:VCALENDAR";
+        let holidays = parse_ical(feed).unwrap();
+        assert_eq!(
+            dates(&holidays),
+            vec!["2025-12-24", "2025-12-25", "2025-12-26"]
+        );
+        assert_eq!(holidays[1].name, "Natale");
+    }
+}
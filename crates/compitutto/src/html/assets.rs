@@ -94,6 +94,169 @@ h1 {
     letter-spacing: 0.1em;
 }
 
+/* Presence */
+.presence {
+    margin-top: 8px;
+    padding: 2px 10px;
+    font-size: 0.75em;
+    font-weight: 700;
+    color: #888;
+    background: none;
+    border: 1px solid rgba(255,255,255,0.15);
+    border-radius: 10px;
+    cursor: pointer;
+}
+
+.presence.others {
+    color: #33ff99;
+    border-color: #33ff99;
+}
+
+/* New release banner */
+.update-banner {
+    display: flex;
+    align-items: center;
+    gap: 12px;
+    margin-bottom: 20px;
+    padding: 8px 14px;
+    font-size: 0.85em;
+    border: 1px solid rgba(0,255,255,0.3);
+    border-radius: 4px;
+    color: #aaa;
+}
+
+.update-banner[hidden] { display: none; }
+.update-banner #update-text { flex: 1; }
+.update-banner a { color: #00ffff; }
+
+.update-dismiss {
+    background: none;
+    border: none;
+    color: #888;
+    cursor: pointer;
+    font-size: 1em;
+}
+
+/* "Start here" plan */
+.plan-panel {
+    margin-bottom: 30px;
+    padding: 16px 20px;
+    border: 1px solid rgba(255,170,0,0.4);
+    border-radius: 6px;
+    background: rgba(255,170,0,0.06);
+}
+
+.plan-header {
+    display: flex;
+    align-items: baseline;
+    justify-content: space-between;
+    gap: 12px;
+    margin-bottom: 10px;
+}
+
+.plan-header h2 {
+    font-size: 1.1em;
+    font-weight: 900;
+    text-transform: uppercase;
+    letter-spacing: 0.05em;
+    color: #ffaa00;
+}
+
+.plan-budget,
+.plan-later {
+    font-size: 0.8em;
+    color: #888;
+}
+
+.plan-items {
+    list-style: none;
+    counter-reset: plan;
+}
+
+.plan-item {
+    counter-increment: plan;
+    display: flex;
+    align-items: baseline;
+    gap: 10px;
+    padding: 4px 0;
+}
+
+.plan-item::before {
+    content: counter(plan) ".";
+    font-weight: 700;
+    color: #ffaa00;
+    min-width: 1.5em;
+}
+
+.plan-item a {
+    color: inherit;
+    text-decoration: none;
+    flex: 1;
+}
+
+.plan-item a:hover { text-decoration: underline; }
+
+.plan-reason {
+    font-size: 0.7em;
+    font-weight: 700;
+    text-transform: uppercase;
+    padding: 1px 6px;
+    border-radius: 8px;
+    border: 1px solid currentColor;
+}
+
+.plan-reason.overdue { color: #ff3333; }
+.plan-reason.today { color: #ffaa00; }
+.plan-reason.study { color: #00ffff; }
+
+.plan-minutes {
+    font-size: 0.8em;
+    color: #888;
+    white-space: nowrap;
+}
+
+/* Weekly goals */
+.goals-widget {
+    display: flex;
+    flex-wrap: wrap;
+    gap: 6px;
+    padding-top: 8px;
+}
+
+.goal {
+    font-size: 0.75em;
+    font-weight: 700;
+    padding: 2px 8px;
+    border: 1px solid rgba(255,255,255,0.15);
+    border-radius: 10px;
+    color: #aaa;
+}
+
+.goal.met {
+    border-color: #33ff99;
+    color: #33ff99;
+}
+
+/* Workload forecast */
+.forecast-widget {
+    display: flex;
+    align-items: flex-end;
+    gap: 3px;
+    height: 28px;
+    padding-top: 8px;
+}
+
+.forecast-widget[hidden] { display: none; }
+
+.forecast-bar {
+    width: 14px;
+    min-height: 2px;
+    background: rgba(0, 255, 255, 0.4);
+    border-radius: 2px 2px 0 0;
+}
+
+.forecast-bar.busiest { background: #ffaa00; }
+
 /* View toggle */
 .view-toggle {
     display: flex;
@@ -138,6 +301,8 @@ h1 {
     gap: 50px;
 }
 
+.plan-panel.hidden,
+.filter-bar.hidden,
 .list-view.hidden,
 .calendar-view.hidden {
     display: none;
@@ -162,6 +327,11 @@ h1 {
     margin-left: -28px;
     padding: 12px 28px;
     background: linear-gradient(90deg, rgba(255, 0, 150, 0.15) 0%, rgba(0, 255, 255, 0.1) 50%, transparent 100%);
+    /* Opaque, since it stays on top of its day's items while scrolling */
+    background-color: #0a0a0a;
+    position: sticky;
+    top: 0;
+    z-index: 5;
     border-left: 4px solid;
     border-image: linear-gradient(180deg, #ff0096, #00ffff) 1;
     text-shadow: 0 0 8px rgba(0,255,255,0.6);
@@ -174,7 +344,16 @@ h1 {
 }
 
 .date-header:hover {
-    background: linear-gradient(90deg, rgba(255, 0, 150, 0.25) 0%, rgba(0, 255, 255, 0.15) 50%, transparent 100%);
+    background-image: linear-gradient(90deg, rgba(255, 0, 150, 0.25) 0%, rgba(0, 255, 255, 0.15) 50%, transparent 100%);
+}
+
+.date-header .date-count {
+    margin-left: auto;
+    font-size: 0.75em;
+    font-weight: 600;
+    letter-spacing: 0.05em;
+    color: #888;
+    text-shadow: none;
 }
 
 .date-header .collapse-indicator {
@@ -330,6 +509,19 @@ h1 {
     text-decoration: underline;
 }
 
+.original-date,
+.assigned-date,
+.visible-from,
+.source-file {
+    font-size: 0.75em;
+    margin-top: 4px;
+    color: #888;
+    font-style: italic;
+}
+.source-file a {
+    color: inherit;
+}
+
 .homework-task {
     color: #ccc;
     line-height: 1.6;
@@ -337,6 +529,38 @@ h1 {
     margin-top: 4px;
 }
 
+/* Task text structure (html/text.rs) */
+.homework-task p,
+.sidebar-entry-task p {
+    margin: 0;
+}
+
+.homework-task p + *,
+.homework-task ul + *,
+.homework-task ol + *,
+.sidebar-entry-task p + *,
+.sidebar-entry-task ul + *,
+.sidebar-entry-task ol + * {
+    margin-top: 6px;
+}
+
+.homework-task ul,
+.homework-task ol,
+.sidebar-entry-task ul,
+.sidebar-entry-task ol {
+    margin: 0;
+    padding-left: 20px;
+}
+
+.task-link {
+    color: #00cccc;
+    word-break: break-all;
+}
+
+.task-link:hover {
+    text-decoration: underline;
+}
+
 .empty-state {
     padding: 60px 20px;
     text-align: center;
@@ -344,6 +568,78 @@ h1 {
     font-size: 0.9em;
 }
 
+/* Load older/newer */
+.load-more {
+    display: block;
+    width: 100%;
+    padding: 10px;
+    margin: 8px 0;
+    background: transparent;
+    border: 1px dashed #ccc;
+    border-radius: 6px;
+    color: #666;
+    font-size: 0.85em;
+    cursor: pointer;
+}
+
+.load-more:hover {
+    background: #f5f5f5;
+    color: #333;
+}
+
+.load-more:disabled {
+    opacity: 0.5;
+    cursor: default;
+}
+
+.list-tools {
+    display: flex;
+    justify-content: flex-end;
+    gap: 8px;
+    margin-bottom: 8px;
+}
+
+.collapse-past,
+.show-hidden {
+    padding: 3px 10px;
+    background: transparent;
+    border: 1px solid #444;
+    border-radius: 6px;
+    color: #888;
+    font: inherit;
+    font-size: 0.85em;
+    cursor: pointer;
+}
+
+.collapse-past:hover,
+.show-hidden:hover { color: #00ffff; border-color: #00ffff; }
+
+/* List date range */
+.list-range {
+    display: flex;
+    flex-wrap: wrap;
+    align-items: center;
+    gap: 8px;
+    margin: 4px 0 8px;
+    font-size: 0.85em;
+    color: #666;
+}
+
+.list-range input[type="date"] {
+    font: inherit;
+    padding: 2px 4px;
+}
+
+.list-range button {
+    padding: 3px 10px;
+    background: transparent;
+    border: 1px solid #ccc;
+    border-radius: 6px;
+    color: inherit;
+    font: inherit;
+    cursor: pointer;
+}
+
 /* Delete button */
 .delete-btn {
     position: absolute;
@@ -368,86 +664,250 @@ h1 {
     background: rgba(255, 0, 0, 0.2);
 }
 
-/* Study session (generated) styling */
-.homework-item[data-generated="true"] {
-    background: rgba(0, 255, 255, 0.03);
-}
-
-/* Orphaned study session */
-.homework-item[data-orphaned="true"] {
-    background: rgba(255, 153, 0, 0.03);
+/* Link button sits left of the delete button */
+.link-btn {
+    position: absolute;
+    top: 8px;
+    right: 40px;
+    background: transparent;
+    border: none;
+    cursor: pointer;
+    opacity: 0;
+    transition: opacity 0.2s;
+    font-size: 14px;
+    padding: 4px 8px;
+    border-radius: 4px;
 }
 
-.auto-badge, .orphan-badge {
-    font-size: 0.55em;
-    padding: 2px 6px;
-    border-radius: 3px;
-    margin-left: 8px;
-    text-transform: uppercase;
-    letter-spacing: 0.05em;
+.homework-item:hover .link-btn,
+body.linking .link-btn {
+    opacity: 0.6;
 }
 
-.auto-badge {
+.link-btn:hover {
+    opacity: 1 !important;
     background: rgba(0, 255, 255, 0.2);
-    color: #00ffff;
 }
 
-.orphan-badge {
-    background: rgba(255, 153, 0, 0.2);
-    color: #ff9900;
+/* Share button sits left of the link button */
+.share-btn {
+    position: absolute;
+    top: 8px;
+    right: 72px;
+    background: transparent;
+    border: none;
+    cursor: pointer;
+    opacity: 0;
+    transition: opacity 0.2s;
+    font-size: 14px;
+    padding: 4px 8px;
+    border-radius: 4px;
 }
 
-/* Drag states */
-.homework-item.dragging {
-    opacity: 0.4;
+.homework-item:hover .share-btn {
+    opacity: 0.6;
 }
 
-.homework-item[draggable="true"] {
-    cursor: grab;
+.share-btn:hover {
+    opacity: 1 !important;
+    background: rgba(0, 255, 0, 0.15);
 }
 
-.homework-item[draggable="true"]:active {
-    cursor: grabbing;
+.homework-item.link-source {
+    outline: 2px dashed #00ffff;
 }
 
-.date-group.drag-over {
-    background: rgba(255, 0, 150, 0.05);
-    box-shadow: inset 0 0 20px rgba(255, 0, 150, 0.2);
+/* Linked entries shown under the task */
+.entry-links {
+    font-size: 0.8em;
+    margin-top: 6px;
+    color: #aaa;
 }
-
-/* Add button */
-.add-entry-btn {
-    position: fixed;
-    bottom: 30px;
-    right: 30px;
-    width: 56px;
-    height: 56px;
-    border-radius: 50%;
-    background: linear-gradient(135deg, #ff0096, #00ffff);
+.entry-links a {
+    color: #00ffff;
+    text-decoration: none;
+}
+.entry-links a:hover {
+    text-decoration: underline;
+}
+.entry-link.pending a {
+    color: #ff6600;
+    font-weight: 700;
+}
+.link-remove {
+    background: transparent;
     border: none;
-    color: #000;
-    font-size: 28px;
-    font-weight: bold;
+    color: #666;
     cursor: pointer;
-    box-shadow: 0 4px 20px rgba(255, 0, 150, 0.4);
-    z-index: 100;
-    transition: transform 0.2s, box-shadow 0.2s;
+    padding: 0 4px;
 }
-
-.add-entry-btn:hover {
-    transform: scale(1.1);
-    box-shadow: 0 6px 30px rgba(255, 0, 150, 0.6);
+.link-remove:hover {
+    color: #ff3366;
 }
 
-/* Dialogs */
-dialog {
-    background: #1a1a1a;
-    border: 1px solid rgba(255, 255, 255, 0.2);
-    border-radius: 8px;
-    color: #fff;
-    padding: 24px;
-    max-width: 400px;
-    width: 90%;
+/* Reactions and encouragement on completed entries */
+.entry-reactions {
+    display: flex;
+    flex-wrap: wrap;
+    align-items: center;
+    gap: 6px;
+    margin-top: 6px;
+    font-size: 0.8em;
+}
+.reaction {
+    border: 1px solid rgba(255, 0, 150, 0.4);
+    border-radius: 12px;
+    padding: 1px 8px;
+    color: #ddd;
+}
+.reaction-author {
+    color: #888;
+    margin-left: 4px;
+}
+.reaction-remove {
+    background: transparent;
+    border: none;
+    color: #666;
+    cursor: pointer;
+    padding: 0 0 0 4px;
+}
+.reaction-remove:hover {
+    color: #ff3366;
+}
+.react-btn,
+.reaction-picker button {
+    background: transparent;
+    border: 1px dashed rgba(255, 255, 255, 0.25);
+    border-radius: 12px;
+    color: #aaa;
+    cursor: pointer;
+    padding: 1px 8px;
+    font-size: 1em;
+}
+.react-btn:hover,
+.reaction-picker button:hover {
+    border-color: #ff0096;
+}
+.reaction-picker {
+    display: inline-flex;
+    gap: 4px;
+}
+.reaction-picker[hidden] {
+    display: none;
+}
+
+/* Study links for the entry's subject */
+.resource-links {
+    display: flex;
+    flex-wrap: wrap;
+    gap: 6px;
+    margin-top: 6px;
+    font-size: 0.8em;
+}
+.resource-links a {
+    border: 1px solid #333;
+    border-radius: 10px;
+    padding: 1px 8px;
+    color: #00ffff;
+    text-decoration: none;
+}
+.resource-links a:hover {
+    border-color: #00ffff;
+}
+
+.link-hint {
+    position: fixed;
+    bottom: 20px;
+    left: 50%;
+    transform: translateX(-50%);
+    background: #1a1a2e;
+    border: 1px solid #00ffff;
+    border-radius: 8px;
+    padding: 10px 16px;
+    font-size: 0.85em;
+    z-index: 100;
+}
+
+/* Study session (generated) styling */
+.homework-item[data-generated="true"] {
+    background: rgba(0, 255, 255, 0.03);
+}
+
+/* Orphaned study session */
+.homework-item[data-orphaned="true"] {
+    background: rgba(255, 153, 0, 0.03);
+}
+
+.auto-badge, .orphan-badge {
+    font-size: 0.55em;
+    padding: 2px 6px;
+    border-radius: 3px;
+    margin-left: 8px;
+    text-transform: uppercase;
+    letter-spacing: 0.05em;
+}
+
+.auto-badge {
+    background: rgba(0, 255, 255, 0.2);
+    color: #00ffff;
+}
+
+.orphan-badge {
+    background: rgba(255, 153, 0, 0.2);
+    color: #ff9900;
+}
+
+/* Drag states */
+.homework-item.dragging {
+    opacity: 0.4;
+}
+
+.homework-item[draggable="true"] {
+    cursor: grab;
+}
+
+.homework-item[draggable="true"]:active {
+    cursor: grabbing;
+}
+
+.date-group.drag-over {
+    background: rgba(255, 0, 150, 0.05);
+    box-shadow: inset 0 0 20px rgba(255, 0, 150, 0.2);
+}
+
+/* Add button */
+.add-entry-btn {
+    position: fixed;
+    bottom: 30px;
+    right: 30px;
+    width: 56px;
+    height: 56px;
+    border-radius: 50%;
+    background: linear-gradient(135deg, #ff0096, #00ffff);
+    border: none;
+    color: #000;
+    font-size: 28px;
+    font-weight: bold;
+    cursor: pointer;
+    box-shadow: 0 4px 20px rgba(255, 0, 150, 0.4);
+    z-index: 100;
+    transition: transform 0.2s, box-shadow 0.2s;
+}
+
+.add-entry-btn:hover {
+    transform: scale(1.1);
+    box-shadow: 0 6px 30px rgba(255, 0, 150, 0.6);
+}
+
+/* Dialogs */
+dialog {
+    background: #1a1a1a;
+    border: 1px solid rgba(255, 255, 255, 0.2);
+    border-radius: 8px;
+    color: #fff;
+    padding: 24px;
+    max-width: 400px;
+    width: 90%;
     position: fixed;
     top: 50%;
     left: 50%;
@@ -578,6 +1038,33 @@ dialog p {
     min-height: 80px;
 }
 
+.difficulty-options {
+    display: flex;
+    gap: 8px;
+}
+
+.difficulty-option {
+    flex: 1;
+    text-align: center;
+    cursor: pointer;
+}
+
+.difficulty-option input {
+    display: none;
+}
+
+.difficulty-option span {
+    display: block;
+    padding: 8px 0;
+    border: 1px solid rgba(255, 255, 255, 0.2);
+    border-radius: 4px;
+}
+
+.difficulty-option input:checked + span {
+    border-color: #ff0096;
+    background: rgba(255, 0, 150, 0.2);
+}
+
 /* Calendar view */
 .calendar-view {
     width: 100%;
@@ -677,6 +1164,11 @@ dialog p {
     background: rgba(255, 255, 255, 0.04);
 }
 
+.cal-day.holiday {
+    background: repeating-linear-gradient(135deg, rgba(51, 255, 153, 0.06) 0 8px, transparent 8px 16px);
+    border-color: rgba(51, 255, 153, 0.3);
+}
+.cal-day.holiday .cal-day-number { color: #33ff99; }
 .cal-day.other-month { opacity: 0.3; }
 .cal-day.today { border-color: #ff0096; box-shadow: 0 0 10px rgba(255, 0, 150, 0.3); }
 .cal-day.has-entries { border-color: rgba(0, 255, 255, 0.4); }
@@ -706,8 +1198,37 @@ dialog p {
 .cal-entry[data-type="interrogazione"] { background: rgba(255, 51, 102, 0.15); border-left-color: #ff3366; }
 .cal-entry[data-type="studio"] { background: rgba(0, 255, 255, 0.15); border-left-color: #00ffff; }
 .cal-entry.completed { opacity: 0.4; text-decoration: line-through; }
+.cal-entry.continued { border-left-style: dashed; }
+.cal-entry.continued .cal-entry-subject::before { content: "↳ "; font-weight: 400; }
+.cal-entry[draggable="true"] { cursor: pointer; }
+.cal-entry[draggable="true"]:active { cursor: grab; }
+.cal-entry:hover { filter: brightness(1.3); }
+.cal-entry-check { display: inline-block; width: 1em; margin-right: 2px; color: #555; font-weight: 700; }
+.cal-entry.completed .cal-entry-check { color: #00ff88; }
+.cal-entry.dragging { opacity: 0.3; }
+.cal-day.drag-over { border-color: #00ffff; background: rgba(0, 255, 255, 0.1); box-shadow: 0 0 15px rgba(0, 255, 255, 0.3); }
 
 .cal-entry-subject { font-weight: 600; color: #fff; }
+.cal-activity {
+    font-size: 0.65em;
+    padding: 1px 6px;
+    margin-bottom: 2px;
+    border: 1px dashed rgba(51, 255, 153, 0.6);
+    border-radius: 3px;
+    color: #33ff99;
+    font-style: italic;
+    white-space: nowrap;
+    overflow: hidden;
+    text-overflow: ellipsis;
+}
+.sidebar-activity {
+    padding: 8px 12px;
+    margin-bottom: 12px;
+    border: 1px dashed rgba(51, 255, 153, 0.6);
+    border-radius: 8px;
+    color: #33ff99;
+    font-size: 0.85em;
+}
 .cal-entry-more { font-size: 0.65em; color: #00ffff; text-align: center; padding: 2px; cursor: pointer; }
 .cal-entry-more:hover { color: #ff0096; }
 
@@ -779,6 +1300,312 @@ dialog p {
 .sidebar-entry-task { color: #ccc; font-size: 0.85em; line-height: 1.5; margin-left: 32px; }
 .sidebar-entry.completed .sidebar-entry-task { text-decoration: line-through; }
 
+/* Filter bar above the list */
+.filter-bar {
+    display: flex;
+    flex-wrap: wrap;
+    align-items: center;
+    gap: 8px 16px;
+    margin-bottom: 30px;
+}
+.filter-bar[hidden] { display: none; }
+.filter-group { display: flex; flex-wrap: wrap; gap: 6px; }
+.filter-chip,
+.filter-clear {
+    padding: 4px 12px;
+    border: 1px solid rgba(255, 255, 255, 0.2);
+    border-radius: 14px;
+    background: transparent;
+    color: #888;
+    font-size: 0.8em;
+    cursor: pointer;
+}
+.filter-chip:hover { color: #fff; }
+.filter-chip.active {
+    border-color: #00ffff;
+    background: rgba(0, 255, 255, 0.12);
+    color: #00ffff;
+}
+.filter-clear { border-style: dashed; }
+body.theme-light .filter-chip,
+body.theme-light .filter-clear { border-color: rgba(0, 0, 0, 0.2); color: #555; }
+body.theme-light .filter-chip.active { border-color: #007a7a; background: rgba(0, 122, 122, 0.1); color: #007a7a; }
+
+/* Pinned entries and accent color overrides */
+.pin-badge { margin-right: 6px; }
+
+/* Types that complete as "happened" (tests): ticked off, not crossed out */
+.homework-item.happened-type.completed { opacity: 0.6; filter: none; }
+.homework-item.happened-type.completed .homework-task { text-decoration: none; }
+.homework-item.happened-type.completed .homework-subject::after {
+    content: "✓ happened";
+    margin-left: 8px;
+    font-size: 0.75em;
+    color: #00ff88;
+}
+.grade-badge {
+    margin-left: 8px;
+    padding: 1px 8px;
+    border: 1px solid rgba(0, 255, 136, 0.5);
+    border-radius: 10px;
+    font-size: 0.8em;
+    color: #00ff88;
+}
+body.theme-light .homework-item.happened-type.completed .homework-subject::after,
+body.theme-light .grade-badge { color: #00a35c; border-color: rgba(0, 163, 92, 0.5); }
+.cross-badge {
+    margin-left: 8px;
+    padding: 1px 8px;
+    border: 1px solid rgba(255, 0, 255, 0.5);
+    border-radius: 10px;
+    font-size: 0.8em;
+    font-weight: 400;
+    color: #ff66ff;
+    text-transform: none;
+}
+body.theme-light .cross-badge { color: #a3009c; border-color: rgba(163, 0, 156, 0.5); }
+.schedule-badge {
+    margin-left: 8px;
+    padding: 1px 8px;
+    border: 1px solid rgba(255, 170, 0, 0.5);
+    border-radius: 10px;
+    font-size: 0.8em;
+    font-weight: 400;
+    color: #ffaa00;
+    text-transform: none;
+    cursor: help;
+}
+body.theme-light .schedule-badge { color: #a36d00; border-color: rgba(163, 109, 0, 0.5); }
+.homework-topic { font-weight: 400; font-size: 0.85em; opacity: 0.75; }
+.subject-icon { font-weight: 400; margin-right: 4px; text-transform: none; }
+.homework-subject .subject-icon { margin-right: -6px; }
+.until-date { font-weight: 400; font-size: 0.8em; color: #888; margin-left: 6px; }
+.sidebar-entry-until { color: #888; font-size: 0.75em; margin-left: 32px; font-style: italic; }
+.sidebar-add {
+    width: 100%;
+    margin-bottom: 12px;
+    padding: 8px 12px;
+    background: transparent;
+    border: 1px dashed rgba(255, 0, 150, 0.5);
+    border-radius: 8px;
+    color: #ff0096;
+    font: inherit;
+    font-size: 0.85em;
+    cursor: pointer;
+}
+.sidebar-add:hover { background: rgba(255, 0, 150, 0.1); }
+.sidebar-entry-actions { display: flex; justify-content: flex-end; align-items: center; gap: 4px; margin: 8px 0 0 32px; }
+.sidebar-entry-actions .delete-btn { position: static; opacity: 0.6; }
+.sidebar-edit-btn { background: transparent; border: none; color: #888; font: inherit; font-size: 0.8em; cursor: pointer; padding: 4px 8px; border-radius: 4px; }
+.sidebar-edit-btn:hover { color: #00ffff; background: rgba(0, 255, 255, 0.1); }
+.sidebar-edit { display: flex; flex-wrap: wrap; align-items: center; gap: 6px; width: 100%; font-size: 0.8em; color: #ccc; }
+.sidebar-edit input { background: rgba(255, 255, 255, 0.05); border: 1px solid rgba(255, 255, 255, 0.2); border-radius: 4px; color: inherit; font: inherit; padding: 2px 4px; }
+.sidebar-edit input[type="number"] { width: 4.5em; }
+.sidebar-edit button { background: transparent; border: 1px solid rgba(255, 255, 255, 0.2); border-radius: 4px; color: inherit; font: inherit; padding: 2px 8px; cursor: pointer; }
+.sidebar-edit button[type="submit"] { border-color: #ff0096; color: #ff0096; }
+.cal-entry .pin-badge { margin-right: 2px; }
+.homework-item.pinned { box-shadow: inset 0 0 0 1px rgba(255, 170, 0, 0.35); }
+.homework-item.accented,
+body.theme-light .homework-item.accented { border-left: 4px solid var(--accent); }
+.cal-entry.accented { background: color-mix(in srgb, var(--accent) 18%, transparent); border-left-color: var(--accent); }
+.sidebar-entry.accented,
+body.theme-light .sidebar-entry.accented { border-left-color: var(--accent); }
+
+@keyframes celebrate {
+    0%, 100% { scale: 1; }
+    30% { scale: 1.03; }
+}
+.date-group.celebrating { animation: celebrate 0.6s ease-in-out 2; box-shadow: 0 0 0 2px rgba(255, 200, 0, 0.6); border-radius: 12px; }
+.day-celebration {
+    position: fixed;
+    left: 50%;
+    bottom: 32px;
+    transform: translateX(-50%);
+    z-index: 1000;
+    padding: 12px 24px;
+    border-radius: 999px;
+    background: linear-gradient(90deg, #ff0096, #ffaa00);
+    color: #fff;
+    font-weight: 700;
+    box-shadow: 0 8px 24px rgba(0, 0, 0, 0.4);
+    animation: celebrate 0.6s ease-in-out 3;
+}
+
+.entry-menu {
+    position: fixed;
+    z-index: 1000;
+    min-width: 180px;
+    padding: 6px;
+    background: #1a1a2e;
+    border: 1px solid rgba(255, 0, 150, 0.4);
+    border-radius: 6px;
+    box-shadow: 0 8px 24px rgba(0, 0, 0, 0.5);
+}
+.entry-menu[hidden] { display: none; }
+/* The long-press opens the entry menu, not the browser's own */
+.homework-item, .cal-entry, .sidebar-entry { -webkit-touch-callout: none; }
+.entry-menu button.menu-item {
+    display: block;
+    width: 100%;
+    padding: 8px 10px;
+    background: none;
+    border: none;
+    color: #eee;
+    font: inherit;
+    text-align: left;
+    cursor: pointer;
+}
+.entry-menu button.menu-item:hover { background: rgba(255, 0, 150, 0.15); }
+.entry-menu .menu-swatches { display: flex; flex-wrap: wrap; gap: 6px; padding: 8px 10px; }
+.entry-menu .menu-swatch {
+    width: 20px;
+    height: 20px;
+    padding: 0;
+    border: 2px solid transparent;
+    border-radius: 50%;
+    cursor: pointer;
+}
+.entry-menu .menu-swatch.current { border-color: #fff; }
+body.theme-light .entry-menu { background: #fff; border-color: rgba(0, 0, 0, 0.2); }
+body.theme-light .entry-menu button.menu-item { color: #111; }
+body.theme-light .entry-menu .menu-swatch.current { border-color: #111; }
+
+/* Hide completed (setting) */
+body.hide-completed .homework-item.completed,
+body.hide-completed .cal-entry.completed,
+body.hide-completed .sidebar-entry.completed { display: none; }
+
+/* Read-only mode (serve --read-only): no editing controls */
+body.read-only .delete-btn,
+body.read-only .link-btn,
+body.read-only .share-btn,
+body.read-only .sidebar-add,
+body.read-only .sidebar-entry-actions { display: none; }
+body.read-only .homework-checkbox,
+body.read-only .sidebar-entry-checkbox { pointer-events: none; }
+body.read-only .homework-item,
+body.read-only .cal-entry { cursor: default; }
+
+/* Light theme (setting) */
+body.theme-light { background: #f5f5f2; color: #111; }
+body.theme-light::before { opacity: 0.4; }
+body.theme-light h1 { color: #111; text-shadow: 3px 3px 0 #ff0096, -2px -2px 0 #00cccc; }
+body.theme-light .goal { border-color: rgba(0,0,0,0.15); color: #555; }
+body.theme-light .update-banner { border-color: rgba(0,140,140,0.4); color: #555; }
+body.theme-light .update-banner a { color: #008c8c; }
+body.theme-light .plan-panel { border-color: rgba(204,119,0,0.4); background: rgba(204,119,0,0.06); }
+body.theme-light .plan-header h2,
+body.theme-light .plan-item::before,
+body.theme-light .plan-reason.today { color: #cc7700; }
+body.theme-light .plan-reason.study { color: #008c8c; }
+body.theme-light .reaction { border-color: rgba(204,0,120,0.4); color: #333; }
+body.theme-light .react-btn,
+body.theme-light .reaction-picker button { border-color: rgba(0,0,0,0.25); color: #555; }
+body.theme-light .presence { border-color: rgba(0,0,0,0.15); color: #555; }
+body.theme-light .presence.others { border-color: #1a9c5b; color: #1a9c5b; }
+body.theme-light .goal.met { border-color: #00a35c; color: #00a35c; }
+body.theme-light .stats,
+body.theme-light .cal-day-header,
+body.theme-light .cal-day-number { color: #555; }
+body.theme-light .view-toggle,
+body.theme-light .calendar-main,
+body.theme-light .calendar-sidebar { background: #fff; border-color: rgba(0, 0, 0, 0.12); }
+body.theme-light .view-btn { color: #555; }
+body.theme-light .date-header { color: #111; text-shadow: none; background-color: #f5f5f2; }
+body.theme-light .homework-item,
+body.theme-light .sidebar-entry,
+body.theme-light .cal-day { background: #fff; border-color: rgba(0, 0, 0, 0.1); }
+body.theme-light .homework-item:hover,
+body.theme-light .sidebar-entry:hover,
+body.theme-light .cal-day:hover { background: #fafafa; }
+body.theme-light .cal-day.holiday { background: repeating-linear-gradient(135deg, rgba(0, 170, 90, 0.08) 0 8px, #fff 8px 16px); }
+body.theme-light .homework-subject,
+body.theme-light .cal-entry-subject,
+body.theme-light .sidebar-date,
+body.theme-light .sidebar-entry-subject { color: #111; }
+body.theme-light .task-link { color: #007a7a; }
+body.theme-light .cal-activity,
+body.theme-light .sidebar-activity { color: #1a9c5b; border-color: #1a9c5b; }
+body.theme-light .homework-task,
+body.theme-light .sidebar-entry-task { color: #333; }
+body.theme-light .sidebar-header { border-color: rgba(0, 0, 0, 0.1); }
+body.theme-light .sidebar-edit { color: #333; }
+body.theme-light .sidebar-edit input,
+body.theme-light .sidebar-edit button { background: #fafafa; border-color: rgba(0, 0, 0, 0.2); }
+body.theme-light .cal-nav-btn { background: #fff; border-color: rgba(0, 0, 0, 0.2); color: #111; }
+body.theme-light dialog { background: #fff; color: #111; border-color: rgba(0, 0, 0, 0.2); }
+body.theme-light .form-group label { color: #333; }
+body.theme-light .form-group input,
+body.theme-light .form-group select,
+body.theme-light .form-group textarea { background: #fafafa; border-color: rgba(0, 0, 0, 0.2); color: #111; }
+
+/* High contrast: black and white, types told apart by text and border
+   style rather than pink/cyan, no motion, bigger targets, visible focus and
+   no controls that only show on hover */
+body.theme-contrast { background: #000; color: #fff; }
+body.theme-contrast::before { display: none; }
+body.theme-contrast *,
+body.theme-contrast *::before,
+body.theme-contrast *::after { animation: none !important; transition: none !important; text-shadow: none !important; box-shadow: none !important; }
+body.theme-contrast h1 { transform: none; }
+body.theme-contrast :focus-visible { outline: 3px solid #ffd400 !important; outline-offset: 2px; }
+body.theme-contrast .date-header { background: #000; color: #fff; border-left: 4px solid #fff; border-image: none; }
+body.theme-contrast .homework-item,
+body.theme-contrast .sidebar-entry,
+body.theme-contrast .cal-day { background: #000; border: 2px solid #fff; }
+body.theme-contrast .homework-item:hover { transform: none; border-color: #ffd400; }
+body.theme-contrast .homework-item.completed,
+body.theme-contrast .cal-entry.completed,
+body.theme-contrast .sidebar-entry.completed { opacity: 0.7; filter: none; }
+body.theme-contrast .homework-item.completed .homework-subject::before { content: "✓ "; }
+body.theme-contrast .homework-subject,
+body.theme-contrast .homework-task,
+body.theme-contrast .sidebar-entry-subject,
+body.theme-contrast .sidebar-entry-task,
+body.theme-contrast .cal-entry-subject,
+body.theme-contrast .stats,
+body.theme-contrast .view-btn { color: #fff; }
+body.theme-contrast .homework-type,
+body.theme-contrast .sidebar-entry-type { background: #000 !important; color: #fff !important; border: 2px solid #fff; }
+body.theme-contrast .homework-type[data-type="verifica"],
+body.theme-contrast .homework-type[data-type="interrogazione"],
+body.theme-contrast .sidebar-entry-type[data-type="verifica"],
+body.theme-contrast .sidebar-entry-type[data-type="interrogazione"] { background: #fff !important; color: #000 !important; }
+body.theme-contrast .homework-type[data-type="studio"],
+body.theme-contrast .homework-type[data-type="lavoro"] { border-style: dashed; }
+body.theme-contrast .cal-entry { background: #000; color: #fff; border: 1px solid #fff; }
+body.theme-contrast .cal-entry.continued { border-style: dashed; }
+body.theme-contrast .date-header .date-count,
+body.theme-contrast .collapse-past,
+body.theme-contrast .show-hidden { color: #fff; border-color: #fff; }
+body.theme-contrast .until-date,
+body.theme-contrast .sidebar-entry-until { color: #fff; }
+body.theme-contrast .task-link { color: #ffd400; text-decoration: underline; }
+body.theme-contrast .homework-checkbox { width: 32px; height: 32px; min-width: 32px; accent-color: #ffd400; filter: none; }
+body.theme-contrast .sidebar-entry-checkbox { width: 28px; height: 28px; accent-color: #ffd400; }
+body.theme-contrast .delete-btn,
+body.theme-contrast .link-btn,
+body.theme-contrast .share-btn { opacity: 1; min-width: 44px; min-height: 44px; border: 1px solid #fff; background: #000; }
+body.theme-contrast .sidebar-add,
+body.theme-contrast .sidebar-edit-btn,
+body.theme-contrast .sidebar-edit,
+body.theme-contrast .sidebar-edit input,
+body.theme-contrast .sidebar-edit button { color: #fff; background: #000; border: 1px solid #fff; }
+body.theme-contrast .link-btn { right: 60px; }
+body.theme-contrast .share-btn { right: 112px; }
+body.theme-contrast .homework-subject { padding-right: 160px; }
+body.theme-contrast button,
+body.theme-contrast .filter-chip,
+body.theme-contrast .view-btn { min-height: 44px; }
+body.theme-contrast .filter-chip.active { background: #fff; color: #000; }
+body.theme-contrast dialog,
+body.theme-contrast .entry-menu { background: #000; color: #fff; border: 2px solid #fff; }
+
+@media (prefers-reduced-motion: reduce) {
+    *, *::before, *::after { animation: none !important; transition: none !important; }
+    h1 { transform: none; }
+}
+
 @media (max-width: 1200px) {
     .calendar-layout { flex-direction: column; }
     .calendar-sidebar { width: 100%; max-height: 400px; }
@@ -801,110 +1628,472 @@ dialog p {
 "#;
 
 pub const JAVASCRIPT: &str = r#"
+// Set by `serve --read-only`: show the schedule without any editing controls
+const READ_ONLY = document.body.classList.contains('read-only');
+
 // ========== Helper Functions ==========
 
+// The message of an API error response ({"code", "message", "details"})
+async function errorMessage(response) {
+    const text = await response.text();
+    try {
+        return JSON.parse(text).message || text;
+    } catch (e) {
+        return text;
+    }
+}
+
 function updateCompletedCount(delta) {
     const el = document.getElementById('completed-count');
     if (el) {
         const current = parseInt(el.textContent) || 0;
         el.textContent = current + delta;
     }
+    scheduleGoalsRefresh();
 }
 
-// ========== Collapsible Date Sections ==========
+// ========== Weekly Goals ==========
 
-function checkAndCollapseIfAllCompleted(dateGroup) {
-    const items = dateGroup.querySelectorAll('.homework-item');
-    const allCompleted = Array.from(items).every(item => item.classList.contains('completed'));
-    if (allCompleted && items.length > 0) {
-        dateGroup.classList.add('collapsed');
+let goalsTimer = null;
+
+// Fill the header's goal chips from /api/stats/goals.
+async function loadGoals() {
+    const widget = document.getElementById('goals-widget');
+    if (!widget) return;
+    try {
+        const response = await fetch('/api/stats/goals');
+        if (!response.ok) return;
+        const report = await response.json();
+        report.goals.forEach(goal => {
+            const chip = widget.querySelector(`.goal[data-subject="${CSS.escape(goal.subject.toLowerCase())}"]`);
+            if (!chip) return;
+            chip.querySelector('.goal-done').textContent = goal.done;
+            chip.classList.toggle('met', goal.met);
+        });
+    } catch (error) {
+        console.error('Error loading goals:', error);
     }
 }
 
-document.querySelectorAll('.date-header').forEach(header => {
-    header.addEventListener('click', function(e) {
-        const dateGroup = this.closest('.date-group');
-        dateGroup.classList.toggle('collapsed');
-    });
-});
+// Completion is saved in the background; wait for it before re-counting
+function scheduleGoalsRefresh() {
+    clearTimeout(goalsTimer);
+    goalsTimer = setTimeout(() => { loadGoals(); loadPlan(); }, 800);
+}
 
-// ========== Checkbox Completion (API-backed) ==========
+loadGoals();
 
-/// Sync a linked entry's visual state and persist it to the API.
-/// Used to keep lavoro ↔ compiti completion in sync.
-async function syncLinkedEntry(linkedId, isChecked) {
-    const linkedItem = document.querySelector(`[data-entry-id="${linkedId}"]`);
-    if (!linkedItem) return; // may be off-screen / different date, just persist
-    const linkedCheckbox = linkedItem.querySelector('.homework-checkbox');
-    if (linkedCheckbox) linkedCheckbox.checked = isChecked;
-    if (isChecked) {
-        linkedItem.classList.add('completed');
-        updateCompletedCount(1);
-        checkAndCollapseIfAllCompleted(linkedItem.closest('.date-group'));
-    } else {
-        linkedItem.classList.remove('completed');
-        updateCompletedCount(-1);
-        linkedItem.closest('.date-group')?.classList.remove('collapsed');
+// ========== Workload Forecast ==========
+
+// Draw one bar per coming week from /api/forecast, as tall as its
+// estimated minutes. Stays hidden where there's no API (static export).
+async function loadForecast() {
+    const widget = document.getElementById('forecast-widget');
+    if (!widget) return;
+    let forecast;
+    try {
+        const response = await fetch('/api/forecast?weeks=4');
+        if (!response.ok) return;
+        forecast = await response.json();
+    } catch (error) {
+        return;
     }
-    // Fire-and-forget — best effort, no revert on error for the linked entry
-    await fetch(`/api/entries/${linkedId}`, {
-        method: 'PUT',
-        headers: { 'Content-Type': 'application/json' },
-        body: JSON.stringify({ completed: isChecked })
+    const most = Math.max(1, ...forecast.weeks.map(w => w.estimated_minutes));
+    widget.innerHTML = '';
+    forecast.weeks.forEach(week => {
+        const bar = document.createElement('span');
+        bar.className = 'forecast-bar';
+        bar.classList.toggle('busiest', week.week_start === forecast.busiest);
+        bar.style.height = `${Math.round(100 * week.estimated_minutes / most)}%`;
+        const hours = (week.estimated_minutes / 60).toFixed(1);
+        bar.title = `Week of ${week.week_start}: ${week.entries} entries, ${week.tests} tests, ~${hours} h`
+            + (week.activity_minutes ? ` (+${(week.activity_minutes / 60).toFixed(1)} h activities)` : '');
+        widget.appendChild(bar);
     });
+    widget.hidden = false;
 }
 
-document.querySelectorAll('.homework-checkbox').forEach(checkbox => {
-    checkbox.addEventListener('change', async function() {
-        const entryId = this.getAttribute('data-entry-id');
-        const item = document.querySelector(`[data-entry-id="${entryId}"]`);
-        const isChecked = this.checked;
-        const dateGroup = item.closest('.date-group');
-
-        // Optimistic UI update for the clicked item
-        if (isChecked) {
-            item.classList.add('completed');
-            updateCompletedCount(1);
-        } else {
-            item.classList.remove('completed');
-            updateCompletedCount(-1);
-            dateGroup.classList.remove('collapsed');
-        }
-        if (isChecked) {
-            checkAndCollapseIfAllCompleted(dateGroup);
-        }
+loadForecast();
 
-        // Sync the linked entry (lavoro ↔ compiti)
-        const parentId = item.dataset.parentId;   // set on lavoro items
-        const lavoroId = item.dataset.lavoroId;   // set on compiti items
-        const linkedId = parentId || lavoroId;
-        if (linkedId) {
-            syncLinkedEntry(linkedId, isChecked);
-        }
+// ========== Update Banner ==========
 
-        // Persist the primary entry
-        try {
-            const response = await fetch(`/api/entries/${entryId}`, {
-                method: 'PUT',
-                headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ completed: isChecked })
-            });
-            if (!response.ok) {
-                // Revert primary
+// Show the banner when the server knows of a newer release that hasn't
+// been dismissed in this browser.
+async function checkForUpdate() {
+    const banner = document.getElementById('update-banner');
+    if (!banner || READ_ONLY) return;
+    let info;
+    try {
+        const response = await fetch('/api/version');
+        if (!response.ok) return;
+        info = await response.json();
+    } catch (error) {
+        return;
+    }
+    if (!info.update_available) return;
+    if (localStorage.getItem('dismissedRelease') === info.latest.version) return;
+    document.getElementById('update-text').textContent =
+        `Compitutto ${info.latest.version} is available (this is ${info.version}).`;
+    document.getElementById('update-link').href = info.latest.url;
+    document.getElementById('update-dismiss').addEventListener('click', () => {
+        localStorage.setItem('dismissedRelease', info.latest.version);
+        banner.hidden = true;
+    });
+    banner.hidden = false;
+}
+
+checkForUpdate();
+
+// ========== Start Here Plan ==========
+
+const PLAN_REASONS = { overdue: 'Overdue', today: 'Today', study: 'Study' };
+
+function planItemHtml(item) {
+    const label = `${item.subject}: ${item.task}`;
+    const minutes = `${item.estimated ? '' : '~'}${item.minutes} min`;
+    const href = '#entry-group-' + item.date;
+    return `<li class="plan-item">`
+        + `<span class="plan-reason ${item.reason}">${PLAN_REASONS[item.reason]}</span>`
+        + `<a href="${href}" data-scroll-to="${item.id}">${escapeHtml(label)}</a>`
+        + `<span class="plan-minutes">${minutes}</span></li>`;
+}
+
+// Fill the "Start here" panel from /api/plan/today. It stays hidden when
+// there is nothing to do, in the calendar view and in the static build.
+async function loadPlan() {
+    const panel = document.getElementById('plan-panel');
+    if (!panel) return;
+    let plan;
+    try {
+        const response = await fetch('/api/plan/today');
+        if (!response.ok) return;
+        plan = await response.json();
+    } catch (error) {
+        return;
+    }
+    panel.dataset.empty = plan.items.length === 0;
+    document.getElementById('plan-items').innerHTML = plan.items.map(planItemHtml).join('');
+    document.getElementById('plan-budget').textContent =
+        `${plan.planned_minutes} / ${plan.budget_minutes} min`;
+    document.getElementById('plan-later').textContent = plan.later.length > 0
+        ? `+${plan.later.length} more when you have time`
+        : '';
+    updatePlanVisibility();
+}
+
+function updatePlanVisibility() {
+    const panel = document.getElementById('plan-panel');
+    if (!panel) return;
+    const inList = !document.getElementById('list-view').classList.contains('hidden');
+    panel.classList.toggle('hidden', panel.dataset.empty !== 'false' || !inList);
+}
+
+loadPlan();
+
+// ========== Collapsible Date Sections ==========
+
+// Days opened or closed by hand, by date, kept on this device. Fully done
+// days start collapsed; a stored choice wins over that.
+const COLLAPSED_KEY = 'compitutto-collapsed';
+// Choices for days older than this are dropped
+const COLLAPSED_KEEP_DAYS = 90;
+
+function loadCollapsed() {
+    try {
+        return JSON.parse(localStorage.getItem(COLLAPSED_KEY) || '{}');
+    } catch (error) {
+        return {};
+    }
+}
+
+function saveCollapsed(changes) {
+    const state = Object.assign(loadCollapsed(), changes);
+    const oldest = shiftDate(localToday(), -COLLAPSED_KEEP_DAYS);
+    Object.keys(state).forEach(date => { if (date < oldest) delete state[date]; });
+    localStorage.setItem(COLLAPSED_KEY, JSON.stringify(state));
+}
+
+// Today as YYYY-MM-DD in the browser's time zone
+function localToday() {
+    const now = new Date();
+    return `${now.getFullYear()}-${String(now.getMonth() + 1).padStart(2, '0')}-${String(now.getDate()).padStart(2, '0')}`;
+}
+
+function setCollapsed(dateGroup, collapsed) {
+    dateGroup.classList.toggle('collapsed', collapsed);
+    dateGroup.querySelector('.date-header')?.setAttribute('aria-expanded', !collapsed);
+    updateCollapsePast();
+}
+
+// Open a day again after one of its entries was unticked
+function expandDateGroup(dateGroup) {
+    if (!dateGroup) return;
+    setCollapsed(dateGroup, false);
+    updateDateCount(dateGroup);
+}
+
+function checkAndCollapseIfAllCompleted(dateGroup) {
+    const items = dateGroup.querySelectorAll('.homework-item');
+    const allCompleted = Array.from(items).every(item => item.classList.contains('completed'));
+    if (allCompleted && items.length > 0) {
+        setCollapsed(dateGroup, true);
+    }
+    updateDateCount(dateGroup);
+}
+
+function updateDateCount(dateGroup) {
+    const count = dateGroup.querySelector('.date-count');
+    if (!count) return;
+    const items = dateGroup.querySelectorAll('.homework-item');
+    const done = dateGroup.querySelectorAll('.homework-item.completed').length;
+    count.textContent = `${done}/${items.length}`;
+}
+
+function pastDateGroups() {
+    const today = localToday();
+    return Array.from(document.querySelectorAll('.date-group'))
+        .filter(group => group.dataset.date < today);
+}
+
+// "Collapse past days" closes every day before today, or opens them all
+// again once they're all closed
+function bindCollapsePast(root) {
+    const button = root.querySelector('#collapse-past');
+    if (!button) return;
+    button.addEventListener('click', () => {
+        const groups = pastDateGroups();
+        const collapse = !groups.every(group => group.classList.contains('collapsed'));
+        const changes = {};
+        groups.forEach(group => {
+            setCollapsed(group, collapse);
+            changes[group.dataset.date] = collapse;
+        });
+        saveCollapsed(changes);
+    });
+    updateCollapsePast();
+}
+
+function updateCollapsePast() {
+    const button = document.getElementById('collapse-past');
+    if (!button) return;
+    const groups = pastDateGroups();
+    button.hidden = groups.length === 0;
+    button.textContent = groups.length > 0 && groups.every(group => group.classList.contains('collapsed'))
+        ? 'Expand past days'
+        : 'Collapse past days';
+}
+
+function bindDateHeaders(root) {
+    const stored = loadCollapsed();
+    root.querySelectorAll('.date-group').forEach(dateGroup => {
+        if (dateGroup.dataset.date in stored) setCollapsed(dateGroup, stored[dateGroup.dataset.date]);
+    });
+    root.querySelectorAll('.date-header').forEach(header => {
+        header.addEventListener('click', function(e) {
+            const dateGroup = this.closest('.date-group');
+            const collapsed = !dateGroup.classList.contains('collapsed');
+            setCollapsed(dateGroup, collapsed);
+            saveCollapsed({ [dateGroup.dataset.date]: collapsed });
+        });
+        // Headers are focusable buttons for keyboard users
+        header.addEventListener('keydown', function(e) {
+            if (e.key !== 'Enter' && e.key !== ' ') return;
+            e.preventDefault();
+            this.click();
+        });
+    });
+}
+
+// ========== Checkbox Completion (API-backed) ==========
+
+// Sync a linked entry's visual state and persist it to the API.
+// Used to keep lavoro ↔ compiti completion in sync.
+async function syncLinkedEntry(linkedId, isChecked) {
+    const linkedItem = document.querySelector(`[data-entry-id="${linkedId}"]`);
+    if (!linkedItem) return; // may be off-screen / different date, just persist
+    const linkedCheckbox = linkedItem.querySelector('.homework-checkbox');
+    if (linkedCheckbox) linkedCheckbox.checked = isChecked;
+    if (isChecked) {
+        linkedItem.classList.add('completed');
+        updateCompletedCount(1);
+        checkAndCollapseIfAllCompleted(linkedItem.closest('.date-group'));
+    } else {
+        linkedItem.classList.remove('completed');
+        updateCompletedCount(-1);
+        expandDateGroup(linkedItem.closest('.date-group'));
+    }
+    // Fire-and-forget — best effort, no revert on error for the linked entry
+    await fetch(`/api/entries/${linkedId}`, {
+        method: 'PATCH',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ completed: isChecked })
+    });
+}
+
+function bindCheckboxes(root) {
+    root.querySelectorAll('.homework-checkbox').forEach(checkbox => {
+        checkbox.addEventListener('change', async function() {
+            const entryId = this.getAttribute('data-entry-id');
+            const item = document.querySelector(`[data-entry-id="${entryId}"]`);
+            const isChecked = this.checked;
+            if (isChecked && !confirmOutOfOrder(entryId)) {
+                this.checked = false;
+                return;
+            }
+            const dateGroup = item.closest('.date-group');
+
+            // Optimistic UI update for the clicked item
+            if (isChecked) {
+                item.classList.add('completed');
+                updateCompletedCount(1);
+            } else {
+                item.classList.remove('completed');
+                updateCompletedCount(-1);
+                expandDateGroup(dateGroup);
+            }
+            if (isChecked) {
+                checkAndCollapseIfAllCompleted(dateGroup);
+            }
+            renderReactions(dateGroup);
+
+            // Sync the linked entry (lavoro ↔ compiti)
+            const parentId = item.dataset.parentId;   // set on lavoro items
+            const lavoroId = item.dataset.lavoroId;   // set on compiti items
+            const linkedId = parentId || lavoroId;
+            if (linkedId) {
+                syncLinkedEntry(linkedId, isChecked);
+            }
+
+            // Persist the primary entry
+            try {
+                const response = await fetch(`/api/entries/${entryId}`, {
+                    method: 'PATCH',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({ completed: isChecked })
+                });
+                if (!response.ok) {
+                    // Revert primary
+                    this.checked = !isChecked;
+                    item.classList.toggle('completed');
+                    updateCompletedCount(isChecked ? -1 : 1);
+                    if (isChecked) expandDateGroup(dateGroup);
+                    console.error('Failed to update completion state');
+                } else if (isChecked) {
+                    const updated = await response.json();
+                    if (updated.day_completed) celebrateDay(dateGroup.dataset.date);
+                    if (item.classList.contains('happened-type')) {
+                        if (ASK_GRADE) askForGrade(item);
+                    } else {
+                        openFeedbackDialog(entryId);
+                    }
+                }
+            } catch (error) {
                 this.checked = !isChecked;
                 item.classList.toggle('completed');
                 updateCompletedCount(isChecked ? -1 : 1);
-                if (isChecked) dateGroup.classList.remove('collapsed');
-                console.error('Failed to update completion state');
+                if (isChecked) expandDateGroup(dateGroup);
+                console.error('Error updating completion:', error);
             }
+        });
+    });
+}
+
+// The last pending entry of a day was just ticked off: cheer for a few
+// seconds, on the day's group in the list and in a banner
+function celebrateDay(dateStr) {
+    document.querySelectorAll(`.date-group[data-date="${dateStr}"]`).forEach(group => {
+        group.classList.add('celebrating');
+        setTimeout(() => group.classList.remove('celebrating'), 3000);
+    });
+    const banner = document.createElement('div');
+    banner.className = 'day-celebration';
+    banner.setAttribute('role', 'status');
+    banner.textContent = `🎉 All done for ${formatDateForSidebar(dateStr)}!`;
+    document.body.appendChild(banner);
+    setTimeout(() => banner.remove(), 3500);
+}
+
+// ========== Completion by Type ==========
+
+// Types whose tick means "it happened" (settings.completion), from <body>
+const HAPPENED_TYPES = new Set((document.body.dataset.happenedTypes || '').split(' ').filter(Boolean));
+const ASK_GRADE = document.body.dataset.askGrade === 'true';
+
+function markCompletionKinds(root) {
+    root.querySelectorAll('.homework-item').forEach(item => {
+        const happened = HAPPENED_TYPES.has(item.dataset.type);
+        item.classList.toggle('happened-type', happened);
+        const checkbox = item.querySelector('.homework-checkbox');
+        if (checkbox) checkbox.title = happened ? 'It happened' : 'Done';
+    });
+}
+
+// After a test is ticked off: record its grade, if the user gives one
+async function askForGrade(item) {
+    const grade = prompt('It happened! Grade (optional):');
+    if (!grade || !grade.trim()) return;
+    try {
+        const response = await fetch(`/api/entries/${item.dataset.entryId}`, {
+            method: 'PATCH',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ grade: grade.trim() })
+        });
+        if (!response.ok) {
+            alert(await errorMessage(response));
+            return;
+        }
+        const entry = await response.json();
+        let badge = item.querySelector('.grade-badge');
+        if (!badge) {
+            badge = document.createElement('span');
+            badge.className = 'grade-badge';
+            badge.title = 'Grade';
+            item.querySelector('.homework-subject').appendChild(badge);
+        }
+        badge.textContent = `🎓 ${entry.grade}`;
+    } catch (error) {
+        console.error('Error saving grade:', error);
+    }
+}
+
+// ========== Post-completion Feedback ==========
+
+const feedbackDialog = document.getElementById('feedback-dialog');
+const feedbackForm = document.getElementById('feedback-form');
+let feedbackEntryId = null;
+
+function openFeedbackDialog(entryId) {
+    feedbackEntryId = entryId;
+    feedbackForm.reset();
+    feedbackDialog.showModal();
+}
+
+document.getElementById('feedback-skip').addEventListener('click', () => {
+    feedbackDialog.close();
+    feedbackEntryId = null;
+});
+
+feedbackForm.addEventListener('submit', async (e) => {
+    e.preventDefault();
+    if (!feedbackEntryId) return;
+    const body = {};
+    const minutes = parseInt(document.getElementById('feedback-minutes').value);
+    if (minutes > 0) body.actual_minutes = minutes;
+    const difficulty = feedbackForm.querySelector('input[name="difficulty"]:checked');
+    if (difficulty) body.difficulty = parseInt(difficulty.value);
+    if (Object.keys(body).length > 0) {
+        try {
+            await fetch(`/api/entries/${feedbackEntryId}`, {
+                method: 'PATCH',
+                headers: { 'Content-Type': 'application/json' },
+                body: JSON.stringify(body)
+            });
         } catch (error) {
-            this.checked = !isChecked;
-            item.classList.toggle('completed');
-            updateCompletedCount(isChecked ? -1 : 1);
-            if (isChecked) dateGroup.classList.remove('collapsed');
-            console.error('Error updating completion:', error);
+            console.error('Error saving feedback:', error);
         }
-    });
+    }
+    feedbackDialog.close();
+    feedbackEntryId = null;
 });
 
 // ========== Delete Functionality ==========
@@ -919,28 +2108,30 @@ const deleteCancelBtn = document.getElementById('delete-cancel');
 let pendingDeleteId = null;
 let pendingDeleteHasChildren = false;
 
-document.querySelectorAll('.delete-btn').forEach(btn => {
-    btn.addEventListener('click', async function(e) {
-        e.stopPropagation();
-        pendingDeleteId = this.getAttribute('data-entry-id');
-        try {
-            const response = await fetch(`/api/entries/${pendingDeleteId}/children`);
-            const children = await response.json();
-            pendingDeleteHasChildren = children.length > 0;
-            if (pendingDeleteHasChildren) {
-                deleteMessage.textContent = `This entry has ${children.length} study session(s) linked to it.`;
-                deleteChildrenNote.style.display = 'block';
-                deleteConfirmInput.value = '';
-            } else {
-                deleteMessage.textContent = 'Are you sure you want to delete this entry?';
-                deleteChildrenNote.style.display = 'none';
+function bindDeleteButtons(root) {
+    root.querySelectorAll('.delete-btn').forEach(btn => {
+        btn.addEventListener('click', async function(e) {
+            e.stopPropagation();
+            pendingDeleteId = this.getAttribute('data-entry-id');
+            try {
+                const response = await fetch(`/api/entries/${pendingDeleteId}/children`);
+                const children = await response.json();
+                pendingDeleteHasChildren = children.length > 0;
+                if (pendingDeleteHasChildren) {
+                    deleteMessage.textContent = `This entry has ${children.length} study session(s) linked to it.`;
+                    deleteChildrenNote.style.display = 'block';
+                    deleteConfirmInput.value = '';
+                } else {
+                    deleteMessage.textContent = 'Are you sure you want to delete this entry?';
+                    deleteChildrenNote.style.display = 'none';
+                }
+                deleteDialog.showModal();
+            } catch (error) {
+                console.error('Error checking children:', error);
             }
-            deleteDialog.showModal();
-        } catch (error) {
-            console.error('Error checking children:', error);
-        }
+        });
     });
-});
+}
 
 deleteCancelBtn.addEventListener('click', () => {
     deleteDialog.close();
@@ -962,14 +2153,14 @@ deleteConfirmBtn.addEventListener('click', async () => {
             } else {
                 await fetch(`/api/entries/${pendingDeleteId}`, { method: 'DELETE' });
             }
-            location.reload();
+            refreshList();
         } catch (error) {
             console.error('Delete error:', error);
         }
     } else {
         try {
             await fetch(`/api/entries/${pendingDeleteId}`, { method: 'DELETE' });
-            location.reload();
+            refreshList();
         } catch (error) {
             console.error('Delete error:', error);
         }
@@ -992,66 +2183,80 @@ let draggedItem = null;
 let draggedEntryId = null;
 let targetDate = null;
 
-document.querySelectorAll('.homework-item').forEach(item => {
-    item.addEventListener('dragstart', function(e) {
-        draggedItem = this;
-        draggedEntryId = this.getAttribute('data-entry-id');
-        this.classList.add('dragging');
-        e.dataTransfer.effectAllowed = 'move';
-    });
-    item.addEventListener('dragend', function() {
-        this.classList.remove('dragging');
-        document.querySelectorAll('.date-group').forEach(g => g.classList.remove('drag-over'));
+function bindDragItems(root) {
+    root.querySelectorAll('.homework-item').forEach(item => {
+        item.addEventListener('dragstart', function(e) {
+            draggedItem = this;
+            draggedEntryId = this.getAttribute('data-entry-id');
+            this.classList.add('dragging');
+            e.dataTransfer.effectAllowed = 'move';
+        });
+        item.addEventListener('dragend', function() {
+            this.classList.remove('dragging');
+            document.querySelectorAll('.date-group').forEach(g => g.classList.remove('drag-over'));
+        });
     });
-});
+}
 
-document.querySelectorAll('.date-group').forEach(group => {
-    group.addEventListener('dragover', function(e) {
-        e.preventDefault();
-        e.dataTransfer.dropEffect = 'move';
-        this.classList.add('drag-over');
-    });
-    group.addEventListener('dragleave', function(e) {
-        if (!this.contains(e.relatedTarget)) this.classList.remove('drag-over');
+function bindDropTargets(root) {
+    root.querySelectorAll('.date-group').forEach(group => {
+        group.addEventListener('dragover', function(e) {
+            e.preventDefault();
+            e.dataTransfer.dropEffect = 'move';
+            this.classList.add('drag-over');
+        });
+        group.addEventListener('dragleave', function(e) {
+            if (!this.contains(e.relatedTarget)) this.classList.remove('drag-over');
+        });
+        group.addEventListener('drop', function(e) {
+            e.preventDefault();
+            this.classList.remove('drag-over');
+            if (!draggedItem) return;
+            targetDate = this.getAttribute('data-date');
+            const sourceDate = draggedItem.closest('.date-group').getAttribute('data-date');
+            if (targetDate === sourceDate) { draggedItem = null; return; }
+            positionDialog.showModal();
+        });
     });
-    group.addEventListener('drop', function(e) {
-        e.preventDefault();
-        this.classList.remove('drag-over');
-        if (!draggedItem) return;
-        targetDate = this.getAttribute('data-date');
-        const sourceDate = draggedItem.closest('.date-group').getAttribute('data-date');
-        if (targetDate === sourceDate) { draggedItem = null; return; }
-        positionDialog.showModal();
+}
+
+// After a create or move, ask whether to keep an entry the server says
+// overloads its day; `undo` reverts it if not.
+async function confirmBusyDay(entry, undo) {
+    if (!entry.warning || confirm(`${entry.warning.message} — continue?`)) return;
+    try {
+        await undo();
+    } catch (error) {
+        console.error('Error undoing change:', error);
+    }
+}
+
+function postMove(entryId, move) {
+    return fetch(`/api/entries/${entryId}/move`, {
+        method: 'POST',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify(move)
     });
-});
+}
 
+// Move the dragged entry to the top or bottom of the target day in one
+// request; the server renumbers the day.
 async function moveEntry(position) {
     if (!draggedEntryId || !targetDate) return;
+    const move = { date: targetDate };
+    if (position === 'top') move.index = 0;
+    const group = draggedItem.closest('.date-group');
+    const back = {
+        date: group.getAttribute('data-date'),
+        index: [...group.querySelectorAll('.homework-item')].indexOf(draggedItem),
+    };
+    const entryId = draggedEntryId;
     try {
-        const entriesResponse = await fetch('/api/entries');
-        const entries = await entriesResponse.json();
-        const targetEntries = entries.filter(e => e.date === targetDate);
-        let newPosition;
-        if (position === 'top') {
-            newPosition = 0;
-            for (const entry of targetEntries) {
-                await fetch(`/api/entries/${entry.id}`, {
-                    method: 'PUT',
-                    headers: { 'Content-Type': 'application/json' },
-                    body: JSON.stringify({ position: entry.position + 1 })
-                });
-            }
-        } else {
-            newPosition = targetEntries.length > 0
-                ? Math.max(...targetEntries.map(e => e.position)) + 1
-                : 0;
-        }
-        await fetch(`/api/entries/${draggedEntryId}`, {
-            method: 'PUT',
-            headers: { 'Content-Type': 'application/json' },
-            body: JSON.stringify({ date: targetDate, position: newPosition })
-        });
-        location.reload();
+        const response = await postMove(entryId, move);
+        if (!response.ok) throw new Error(`HTTP ${response.status}`);
+        draggedItem = null; draggedEntryId = null; targetDate = null;
+        await confirmBusyDay(await response.json(), () => postMove(entryId, back));
+        refreshList();
     } catch (error) {
         console.error('Error moving entry:', error);
     }
@@ -1077,13 +2282,17 @@ const addEntryDialog = document.getElementById('add-entry-dialog');
 const addEntryForm = document.getElementById('add-entry-form');
 const addEntryCancelBtn = document.getElementById('add-entry-cancel');
 
-addEntryBtn.addEventListener('click', () => {
-    const today = new Date().toISOString().split('T')[0];
-    document.getElementById('new-entry-date').value = today;
+// Open the add dialog with an empty entry on `date` (`YYYY-MM-DD`)
+function openAddEntryDialog(date) {
+    document.getElementById('new-entry-date').value = date;
     document.getElementById('new-entry-subject').value = '';
     document.getElementById('new-entry-type').value = 'compiti';
     document.getElementById('new-entry-task').value = '';
     addEntryDialog.showModal();
+}
+
+addEntryBtn?.addEventListener('click', () => {
+    openAddEntryDialog(new Date().toISOString().split('T')[0]);
 });
 
 addEntryCancelBtn.addEventListener('click', () => { addEntryDialog.close(); });
@@ -1096,14 +2305,21 @@ addEntryForm.addEventListener('submit', async (e) => {
         entry_type: document.getElementById('new-entry-type').value,
         task: document.getElementById('new-entry-task').value
     };
+    const estimate = parseInt(document.getElementById('new-entry-estimate').value);
+    if (estimate > 0) entry.estimated_minutes = estimate;
     try {
         const response = await fetch('/api/entries', {
             method: 'POST',
             headers: { 'Content-Type': 'application/json' },
             body: JSON.stringify(entry)
         });
-        if (response.ok) { addEntryDialog.close(); location.reload(); }
-        else { console.error('Failed to create entry'); }
+        if (response.ok) {
+            addEntryDialog.close();
+            const created = await response.json();
+            await confirmBusyDay(created, () =>
+                fetch(`/api/entries/${created.id}/cascade`, { method: 'DELETE' }));
+            refreshList(rangeIncluding(entry.date));
+        } else { console.error('Failed to create entry'); }
     } catch (error) {
         console.error('Error creating entry:', error);
     }
@@ -1113,6 +2329,532 @@ addEntryDialog.addEventListener('click', (e) => {
     if (e.target === addEntryDialog) addEntryDialog.close();
 });
 
+// ========== Entry Links ==========
+
+// All links from /api/links; drawn under each list item by renderLinks()
+let entryLinks = [];
+let linkingFrom = null;
+const linkHint = document.createElement('div');
+linkHint.className = 'link-hint';
+linkHint.hidden = true;
+document.body.appendChild(linkHint);
+
+async function loadLinks() {
+    try {
+        const response = await fetch('/api/links');
+        if (!response.ok) throw new Error(`HTTP ${response.status}`);
+        entryLinks = await response.json();
+    } catch (error) {
+        console.error('Error loading links:', error);
+        return;
+    }
+    renderLinks(document);
+}
+
+function listItem(entryId) {
+    return document.querySelector(`.homework-item[data-entry-id="${entryId}"]`);
+}
+
+// "Subject: task" for a list item, shortened
+function entryLabel(item) {
+    const subject = item.querySelector('.homework-subject')?.childNodes[0]?.textContent.trim() || '';
+    const task = item.querySelector('.homework-task')?.textContent.trim() || '';
+    const text = subject ? `${subject}: ${task}` : task;
+    return text.length > 50 ? text.slice(0, 49) + '…' : text;
+}
+
+function renderLinks(root) {
+    root.querySelectorAll('.entry-links').forEach(el => el.remove());
+    root.querySelectorAll('.homework-item').forEach(item => {
+        const id = item.dataset.entryId;
+        const lines = [];
+        entryLinks.filter(l => l.from_id === id || l.to_id === id).forEach(link => {
+            const outgoing = link.from_id === id;
+            const other = listItem(outgoing ? link.to_id : link.from_id);
+            if (!other) return;
+            const label = link.relation === 'related'
+                ? '🔗 Related:'
+                : (outgoing ? '⛓ Needs first:' : '⛓ Needed for:');
+            const pending = link.relation === 'depends_on' && outgoing && !other.classList.contains('completed');
+            const href = '#entry-group-' + (other.closest('.date-group')?.dataset.date || '');
+            lines.push(`<div class="entry-link${pending ? ' pending' : ''}">${label} `
+                + `<a href="${href}" data-scroll-to="${other.dataset.entryId}">${escapeHtml(entryLabel(other))}</a>`
+                + `<button type="button" class="link-remove" data-link-id="${link.id}" title="Remove link" aria-label="Remove link">×</button></div>`);
+        });
+        if (lines.length === 0) return;
+        const container = document.createElement('div');
+        container.className = 'entry-links';
+        container.innerHTML = lines.join('');
+        item.querySelector('.homework-content').appendChild(container);
+    });
+    root.querySelectorAll('.link-remove').forEach(btn => {
+        btn.addEventListener('click', async function(e) {
+            e.stopPropagation();
+            try {
+                const response = await fetch(`/api/links/${this.dataset.linkId}`, { method: 'DELETE' });
+                if (!response.ok) throw new Error(`HTTP ${response.status}`);
+            } catch (error) {
+                console.error('Error removing link:', error);
+            }
+            loadLinks();
+        });
+    });
+}
+
+function stopLinking() {
+    document.querySelectorAll('.homework-item.link-source').forEach(el => el.classList.remove('link-source'));
+    document.body.classList.remove('linking');
+    linkHint.hidden = true;
+    linkingFrom = null;
+}
+
+// First 🔗 click picks the entry, the second picks what it depends on
+// (shift-click: just related). Clicking the same entry again cancels.
+function bindLinkButtons(root) {
+    root.querySelectorAll('.link-btn').forEach(btn => {
+        btn.addEventListener('click', async function(e) {
+            e.stopPropagation();
+            const entryId = this.dataset.entryId;
+            if (linkingFrom === null) {
+                linkingFrom = entryId;
+                listItem(entryId)?.classList.add('link-source');
+                document.body.classList.add('linking');
+                linkHint.textContent = 'Click 🔗 on the entry this depends on (shift-click: related). Esc cancels.';
+                linkHint.hidden = false;
+                return;
+            }
+            const from = linkingFrom;
+            const relation = e.shiftKey ? 'related' : 'depends_on';
+            stopLinking();
+            if (from === entryId) return;
+            try {
+                const response = await fetch(`/api/entries/${from}/links`, {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({ to_id: entryId, relation })
+                });
+                if (!response.ok) {
+                    alert(await errorMessage(response));
+                    return;
+                }
+            } catch (error) {
+                console.error('Error creating link:', error);
+                return;
+            }
+            loadLinks();
+        });
+    });
+}
+
+document.addEventListener('keydown', e => {
+    if (e.key === 'Escape' && linkingFrom !== null) stopLinking();
+});
+
+// Before completing `entryId`, ask if something it depends on isn't done.
+function confirmOutOfOrder(entryId) {
+    const pending = entryLinks
+        .filter(l => l.relation === 'depends_on' && l.from_id === entryId)
+        .map(l => listItem(l.to_id))
+        .filter(item => item && !item.classList.contains('completed'))
+        .map(item => `• ${entryLabel(item)}`);
+    if (pending.length === 0) return true;
+    return confirm(`This depends on entries that aren't done yet:\n${pending.join('\n')}\n\nMark it done anyway?`);
+}
+
+loadLinks();
+
+// ========== Reactions ==========
+
+// All reactions from /api/reactions; drawn on completed items by renderReactions()
+let entryReactions = [];
+const REACTION_EMOJI = ['⭐', '👍', '🎉'];
+
+async function loadReactions() {
+    try {
+        const response = await fetch('/api/reactions');
+        if (!response.ok) throw new Error(`HTTP ${response.status}`);
+        entryReactions = await response.json();
+    } catch (error) {
+        console.error('Error loading reactions:', error);
+        return;
+    }
+    renderReactions(document);
+}
+
+function reactionHtml(reaction) {
+    const text = [reaction.emoji, reaction.message].filter(Boolean).map(escapeHtml).join(' ');
+    const author = reaction.author
+        ? `<span class="reaction-author">— ${escapeHtml(reaction.author)}</span>`
+        : '';
+    const remove = READ_ONLY
+        ? ''
+        : `<button type="button" class="reaction-remove" data-reaction-id="${reaction.id}" title="Remove" aria-label="Remove reaction">×</button>`;
+    return `<span class="reaction" title="${escapeHtml(reaction.created_at)}">${text}${author}${remove}</span>`;
+}
+
+function renderReactions(root) {
+    root.querySelectorAll('.entry-reactions').forEach(el => el.remove());
+    root.querySelectorAll('.homework-item').forEach(item => {
+        const reactions = entryReactions.filter(r => r.entry_id === item.dataset.entryId);
+        const canReact = !READ_ONLY && item.classList.contains('completed');
+        if (reactions.length === 0 && !canReact) return;
+        let html = reactions.map(reactionHtml).join('');
+        if (canReact) {
+            html += '<button type="button" class="react-btn" title="React or encourage">+ 🎉</button>'
+                + '<span class="reaction-picker" hidden>'
+                + REACTION_EMOJI.map(emoji => `<button type="button" data-emoji="${emoji}">${emoji}</button>`).join('')
+                + '<button type="button" data-message="1" title="Write a message">✏️</button></span>';
+        }
+        const container = document.createElement('div');
+        container.className = 'entry-reactions';
+        container.innerHTML = html;
+        item.querySelector('.homework-content').appendChild(container);
+    });
+    root.querySelectorAll('.react-btn').forEach(btn => {
+        btn.addEventListener('click', function(e) {
+            e.stopPropagation();
+            const picker = this.nextElementSibling;
+            picker.hidden = !picker.hidden;
+        });
+    });
+    root.querySelectorAll('.reaction-picker button').forEach(btn => {
+        btn.addEventListener('click', function(e) {
+            e.stopPropagation();
+            const entryId = this.closest('.homework-item').dataset.entryId;
+            if (this.dataset.message) {
+                const message = prompt('A few words of encouragement:');
+                if (message && message.trim()) addReaction(entryId, { message });
+            } else {
+                addReaction(entryId, { emoji: this.dataset.emoji });
+            }
+        });
+    });
+    root.querySelectorAll('.reaction-remove').forEach(btn => {
+        btn.addEventListener('click', async function(e) {
+            e.stopPropagation();
+            try {
+                const response = await fetch(`/api/reactions/${this.dataset.reactionId}`, { method: 'DELETE' });
+                if (!response.ok) throw new Error(`HTTP ${response.status}`);
+            } catch (error) {
+                console.error('Error removing reaction:', error);
+            }
+            loadReactions();
+        });
+    });
+}
+
+// Signed with the name set for presence, if any
+async function addReaction(entryId, reaction) {
+    const author = localStorage.getItem('compitutto-name') || null;
+    try {
+        const response = await fetch(`/api/entries/${entryId}/reactions`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ ...reaction, author })
+        });
+        if (!response.ok) {
+            alert(await errorMessage(response));
+            return;
+        }
+    } catch (error) {
+        console.error('Error adding reaction:', error);
+        return;
+    }
+    loadReactions();
+}
+
+loadReactions();
+
+// ========== Subject Resources ==========
+
+// Study links per subject from /api/resources, keyed by lowercased subject
+let subjectResources = {};
+
+async function loadResources() {
+    try {
+        const response = await fetch('/api/resources');
+        if (!response.ok) throw new Error(`HTTP ${response.status}`);
+        subjectResources = {};
+        (await response.json()).forEach(resource => {
+            const key = resource.subject.trim().toLowerCase();
+            (subjectResources[key] = subjectResources[key] || []).push(resource);
+        });
+    } catch (error) {
+        console.error('Error loading resources:', error);
+        return;
+    }
+    renderResources(document);
+}
+
+// A resource url with `{page}` filled from "pag. 112" / "p. 112" in the task;
+// without a page number the placeholder is dropped
+function resourceUrl(url, task) {
+    if (!url.includes('{page}')) return url;
+    const match = task.match(/\bp(?:ag(?:ina|g)?)?\.?\s*(\d+)/i);
+    return url.split('{page}').join(match ? match[1] : '');
+}
+
+function renderResources(root) {
+    root.querySelectorAll('.resource-links').forEach(el => el.remove());
+    root.querySelectorAll('.homework-item[data-subject]').forEach(item => {
+        const resources = subjectResources[item.dataset.subject.trim().toLowerCase()];
+        if (!resources) return;
+        const task = item.querySelector('.homework-task')?.textContent || '';
+        const container = document.createElement('div');
+        container.className = 'resource-links';
+        resources.forEach(resource => {
+            const link = document.createElement('a');
+            link.href = resourceUrl(resource.url, task);
+            link.target = '_blank';
+            link.rel = 'noopener';
+            link.textContent = '📚 ' + resource.title;
+            link.addEventListener('click', e => e.stopPropagation());
+            container.appendChild(link);
+        });
+        item.querySelector('.homework-content').appendChild(container);
+    });
+}
+
+loadResources();
+
+// ========== Subject Icons ==========
+
+// Icon per subject from /api/subjects/icons (built-in or set there), keyed
+// by lowercased subject; drawn before the subject on cards, calendar chips
+// and the sidebar
+let subjectIcons = {};
+
+async function loadSubjectIcons() {
+    try {
+        const response = await fetch('/api/subjects/icons');
+        if (!response.ok) throw new Error(`HTTP ${response.status}`);
+        subjectIcons = {};
+        (await response.json()).forEach(({ subject, icon }) => {
+            subjectIcons[subject.trim().toLowerCase()] = icon;
+        });
+    } catch (error) {
+        console.error('Error loading subject icons:', error);
+        return;
+    }
+    renderSubjectIcons(document);
+    if (!calendarView.classList.contains('hidden')) {
+        renderCalendar();
+        if (selectedDate) renderSidebar(selectedDate);
+    }
+}
+
+// The icon span for `subject`, as HTML; empty when it has none
+function subjectIcon(subject) {
+    const icon = subjectIcons[(subject || '').trim().toLowerCase()];
+    return icon ? `<span class="subject-icon" aria-hidden="true">${escapeHtml(icon)}</span>` : '';
+}
+
+function renderSubjectIcons(root) {
+    root.querySelectorAll('.homework-item[data-subject]').forEach(item => {
+        const subject = item.querySelector('.homework-subject');
+        if (!subject) return;
+        subject.querySelector('.subject-icon')?.remove();
+        subject.insertAdjacentHTML('afterbegin', subjectIcon(item.dataset.subject));
+    });
+}
+
+loadSubjectIcons();
+
+// ========== Share Links ==========
+
+// 📤 makes a week-long read-only link to one entry and hands it to the
+// system share sheet, or copies it when there isn't one.
+function bindShareButtons(root) {
+    root.querySelectorAll('.share-btn').forEach(btn => {
+        btn.addEventListener('click', async function(e) {
+            e.stopPropagation();
+            const item = listItem(this.dataset.entryId);
+            try {
+                const response = await fetch(`/api/entries/${this.dataset.entryId}/share`, {
+                    method: 'POST',
+                    headers: { 'Content-Type': 'application/json' },
+                    body: JSON.stringify({})
+                });
+                if (!response.ok) {
+                    alert(await errorMessage(response));
+                    return;
+                }
+                const link = await response.json();
+                const url = new URL(link.url, window.location.origin).href;
+                const title = item ? entryLabel(item) : 'Compitutto';
+                if (navigator.share) {
+                    await navigator.share({ title, url });
+                } else if (navigator.clipboard) {
+                    await navigator.clipboard.writeText(url);
+                    alert(`Link copied (valid until ${new Date(link.expires_at).toLocaleDateString()}):\n${url}`);
+                } else {
+                    prompt('Share this link:', url);
+                }
+            } catch (error) {
+                if (error.name !== 'AbortError') console.error('Error sharing entry:', error);
+            }
+        });
+    });
+}
+
+// ========== List Date Window ==========
+
+// The server renders the weeks around today set in Settings; these widen
+// or replace the window.
+const LIST_LOAD_STEP_DAYS = 28;
+
+// The dates the list currently covers, or null for the full static list.
+function listRange() {
+    const content = listView.querySelector('.list-content');
+    if (!content || !content.dataset.from) return null;
+    const range = { from: content.dataset.from, to: content.dataset.to };
+    if (content.dataset.hidden) range.hidden = 'true';
+    return range;
+}
+
+function shiftDate(dateStr, days) {
+    const date = new Date(dateStr + 'T00:00:00Z');
+    date.setUTCDate(date.getUTCDate() + days);
+    return date.toISOString().slice(0, 10);
+}
+
+// The current range, widened to take in `dateStr` if it falls outside.
+function rangeIncluding(dateStr) {
+    const range = listRange();
+    if (!range || !dateStr) return range;
+    return {
+        from: dateStr < range.from ? dateStr : range.from,
+        to: dateStr > range.to ? dateStr : range.to
+    };
+}
+
+function bindLoadMore(root) {
+    root.querySelectorAll('.load-more').forEach(btn => {
+        btn.addEventListener('click', () => {
+            const range = listRange();
+            if (!range) return;
+            btn.disabled = true;
+            if (btn.dataset.direction === 'older') {
+                refreshList({ from: shiftDate(range.from, -LIST_LOAD_STEP_DAYS), to: range.to });
+            } else {
+                // Newer groups go above what's on screen; keep it in place
+                refreshList({ from: range.from, to: shiftDate(range.to, LIST_LOAD_STEP_DAYS) }, true);
+            }
+        });
+    });
+}
+
+// The From/to pickers above the list, "Show all" and "Reset" (back to
+// the server's default window)
+function bindListRange(root) {
+    const form = root.querySelector('#list-range');
+    if (!form) return;
+    form.addEventListener('submit', e => {
+        e.preventDefault();
+        const from = form.elements.from.value, to = form.elements.to.value;
+        if (!from || !to) return;
+        refreshList(from <= to ? { from, to } : { from: to, to: from });
+    });
+    form.querySelector('.list-range-all')?.addEventListener('click', () => refreshList({ all: 'true' }));
+    form.querySelector('.list-range-reset').addEventListener('click', () => refreshList({}));
+}
+
+// "N hidden until later" shows the entries set to show up later, marked,
+// and hides them again
+function bindShowHidden(root) {
+    root.querySelector('#show-hidden')?.addEventListener('click', () => {
+        const range = listRange() || {};
+        if (range.hidden) {
+            delete range.hidden;
+        } else {
+            range.hidden = 'true';
+        }
+        refreshList(range);
+    });
+}
+
+// ========== Soft List Refresh ==========
+
+// List handlers are bound per render, since refreshList() swaps the list out.
+function bindListView(root) {
+    bindDateHeaders(root);
+    bindCollapsePast(root);
+    markCompletionKinds(root);
+    if (READ_ONLY) {
+        lockItems(root);
+    } else {
+        bindCheckboxes(root);
+        bindDeleteButtons(root);
+        bindLinkButtons(root);
+        bindShareButtons(root);
+        bindDragItems(root);
+        bindDropTargets(root);
+    }
+    bindLoadMore(root);
+    bindListRange(root);
+    bindShowHidden(root);
+    renderLinks(root);
+    renderReactions(root);
+    renderResources(root);
+    renderSubjectIcons(root);
+}
+
+// Read-only mode: nothing in the list can be ticked or dragged
+function lockItems(root) {
+    root.querySelectorAll('.homework-checkbox').forEach(checkbox => { checkbox.disabled = true; });
+    root.querySelectorAll('[draggable="true"]').forEach(el => { el.draggable = false; });
+}
+
+// Re-render the list from /fragments/list without reloading the page,
+// keeping the date window, the scroll position and any groups the user
+// opened or closed. With `keepContent`, the scroll position follows the
+// content when groups are added above it.
+async function refreshList(range = listRange(), keepContent = false) {
+    const scrollY = window.scrollY;
+    const heightBefore = listView.scrollHeight;
+    const collapsed = new Map();
+    listView.querySelectorAll('.date-group').forEach(group => {
+        collapsed.set(group.dataset.date, group.classList.contains('collapsed'));
+    });
+    try {
+        const params = new URLSearchParams(range || {});
+        Object.entries(listFilters).forEach(([key, values]) => {
+            if (values.length) params.set(key, values.join(','));
+        });
+        const query = params.toString();
+        const response = await fetch('/fragments/list' + (query ? '?' + query : ''));
+        if (!response.ok) throw new Error(`HTTP ${response.status}`);
+        listView.innerHTML = await response.text();
+    } catch (error) {
+        console.error('Error refreshing list:', error);
+        return;
+    }
+    listView.querySelectorAll('.date-group').forEach(group => {
+        if (collapsed.has(group.dataset.date)) {
+            setCollapsed(group, collapsed.get(group.dataset.date));
+        }
+    });
+    const content = listView.querySelector('.list-content');
+    if (content) {
+        document.getElementById('completed-count').textContent = content.dataset.completed;
+        document.getElementById('total-count').textContent = content.dataset.total;
+    }
+    bindListView(listView);
+    loadLinks();
+    loadGoals();
+    loadPlan();
+    loadForecast();
+    window.scrollTo(0, keepContent ? scrollY + listView.scrollHeight - heightBefore : scrollY);
+
+    // The calendar caches months; drop them so it picks up the change
+    loadedMonths.clear();
+    entriesByDate = {};
+    activitiesByDate = {};
+    if (!calendarView.classList.contains('hidden')) refreshCalendar();
+}
+
+bindListView(document);
+
 // ========== View Toggle ==========
 
 const listViewBtn = document.getElementById('list-view-btn');
@@ -1122,23 +2864,117 @@ const calendarView = document.getElementById('calendar-view');
 
 function showListView() {
     listView.classList.remove('hidden');
+    filterBar.classList.remove('hidden');
     calendarView.classList.add('hidden');
     listViewBtn.classList.add('active');
     calendarViewBtn.classList.remove('active');
-    localStorage.setItem('preferredView', 'list');
+    updatePlanVisibility();
 }
 
 function showCalendarView() {
     listView.classList.add('hidden');
+    filterBar.classList.add('hidden');
     calendarView.classList.remove('hidden');
     listViewBtn.classList.remove('active');
     calendarViewBtn.classList.add('active');
-    localStorage.setItem('preferredView', 'calendar');
-    renderCalendar();
+    updatePlanVisibility();
+    refreshCalendar();
 }
 
-listViewBtn.addEventListener('click', showListView);
-calendarViewBtn.addEventListener('click', showCalendarView);
+// Remember the last view picked as the default_view setting
+function saveDefaultView(view) {
+    document.body.dataset.defaultView = view;
+    if (READ_ONLY) return;
+    fetch('/api/settings', {
+        method: 'PUT',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ default_view: view })
+    }).catch(err => console.error('Failed to save default view:', err));
+}
+
+listViewBtn.addEventListener('click', () => {
+    showListView();
+    saveDefaultView('list');
+});
+calendarViewBtn.addEventListener('click', () => {
+    showCalendarView();
+    saveDefaultView('calendar');
+});
+
+// ========== Filter Bar ==========
+
+// Active chips, remembered per device: { subject: [...], type: [...], status: [...] }.
+// refreshList() sends them to /fragments/list, which filters server-side.
+const FILTERS_KEY = 'compitutto-filters';
+const filterBar = document.getElementById('filter-bar');
+let listFilters = loadFilters();
+
+function loadFilters() {
+    const empty = { subject: [], type: [], status: [] };
+    try {
+        const saved = JSON.parse(localStorage.getItem(FILTERS_KEY) || '{}');
+        return Object.fromEntries(Object.keys(empty).map(key => [
+            key, Array.isArray(saved[key]) ? saved[key] : []
+        ]));
+    } catch (e) {
+        return empty;
+    }
+}
+
+function filtersActive() {
+    return Object.values(listFilters).some(values => values.length > 0);
+}
+
+function syncFilterChips() {
+    filterBar.querySelectorAll('.filter-chip').forEach(chip => {
+        chip.classList.toggle('active', listFilters[chip.dataset.filter].includes(chip.dataset.value));
+    });
+    document.getElementById('filter-clear').hidden = !filtersActive();
+}
+
+function setFilters(filters) {
+    listFilters = filters;
+    localStorage.setItem(FILTERS_KEY, JSON.stringify(listFilters));
+    syncFilterChips();
+    refreshList();
+}
+
+filterBar.addEventListener('click', (e) => {
+    if (e.target.closest('#filter-clear')) {
+        setFilters({ subject: [], type: [], status: [] });
+        return;
+    }
+    const chip = e.target.closest('.filter-chip');
+    if (!chip) return;
+    const values = listFilters[chip.dataset.filter];
+    const next = values.includes(chip.dataset.value)
+        ? values.filter(value => value !== chip.dataset.value)
+        : [...values, chip.dataset.value];
+    setFilters({ ...listFilters, [chip.dataset.filter]: next });
+});
+
+// Subject chips come from the server; without one (static build) the bar stays hidden
+fetch('/api/subjects')
+    .then(response => response.ok ? response.json() : Promise.reject(response.status))
+    .then(report => {
+        const group = document.getElementById('filter-subjects');
+        // Keep saved subjects that have no entries left clearable
+        const subjects = new Set(report.subjects.map(s => s.subject).concat(listFilters.subject));
+        subjects.forEach(subject => {
+            const chip = document.createElement('button');
+            chip.type = 'button';
+            chip.className = 'filter-chip';
+            chip.dataset.filter = 'subject';
+            chip.dataset.value = subject;
+            chip.textContent = subject;
+            group.appendChild(chip);
+        });
+        filterBar.hidden = false;
+        filterBar.classList.toggle('hidden', listView.classList.contains('hidden'));
+        syncFilterChips();
+        if (filtersActive()) refreshList();
+    })
+    .catch(() => {});
 
 // ========== Calendar ==========
 
@@ -1155,11 +2991,47 @@ let currentYear = parseInt(calMonthYear.dataset.year);
 let currentMonth = parseInt(calMonthYear.dataset.month);
 let selectedDate = null;
 let entriesByDate = {};
+let holidaysByDate = {};
+let activitiesByDate = {};
+const loadedMonths = new Map();
+
+function loadMonth(year, month) {
+    if (month < 1) { month = 12; year--; }
+    if (month > 12) { month = 1; year++; }
+    const key = `${year}-${String(month).padStart(2, '0')}`;
+    if (!loadedMonths.has(key)) {
+        const request = fetch(`/api/calendar?year=${year}&month=${month}`)
+            .then(response => {
+                if (!response.ok) throw new Error(`HTTP ${response.status}`);
+                return response.json();
+            })
+            .then(data => {
+                Object.assign(entriesByDate, data.entries);
+                Object.assign(holidaysByDate, data.holidays);
+                Object.assign(activitiesByDate, data.activities || {});
+            })
+            .catch(error => {
+                loadedMonths.delete(key);
+                console.error(`Failed to load calendar month ${key}:`, error);
+            });
+        loadedMonths.set(key, request);
+    }
+    return loadedMonths.get(key);
+}
 
-try {
-    entriesByDate = JSON.parse(calendarDays.dataset.entries || '{}');
-} catch (e) {
-    console.error('Failed to parse entries:', e);
+// Render immediately with what's cached, then fetch the visible month plus
+// its neighbours (for the leading/trailing cells and the next navigation).
+function refreshCalendar() {
+    renderCalendar();
+    Promise.all([
+        loadMonth(currentYear, currentMonth - 1),
+        loadMonth(currentYear, currentMonth),
+        loadMonth(currentYear, currentMonth + 1),
+    ]).then(() => {
+        if (calendarView.classList.contains('hidden')) return;
+        renderCalendar();
+        if (selectedDate) renderSidebar(selectedDate);
+    });
 }
 
 const monthNames = [
@@ -1170,6 +3042,9 @@ const dayNames = ['Sunday', 'Monday', 'Tuesday', 'Wednesday', 'Thursday', 'Frida
 
 function formatDateForSidebar(dateStr) {
     const date = new Date(dateStr + 'T00:00:00');
+    if (document.documentElement.lang !== 'en') {
+        return date.toLocaleDateString(document.documentElement.lang, { weekday: 'long', month: 'long', day: 'numeric' });
+    }
     return `${dayNames[date.getDay()]}, ${monthNames[date.getMonth()]} ${date.getDate()}`;
 }
 
@@ -1183,12 +3058,17 @@ function selectDay(dateStr) {
 
 function renderSidebar(dateStr) {
     const entries = entriesByDate[dateStr] || [];
-    sidebarDate.textContent = formatDateForSidebar(dateStr);
+    const holiday = holidaysByDate[dateStr];
+    sidebarDate.textContent = formatDateForSidebar(dateStr) + (holiday ? ` · ${holiday}` : '');
+    let html = READ_ONLY ? '' : '<button type="button" class="sidebar-add">+ Add entry for this day</button>';
+    html += (activitiesByDate[dateStr] || [])
+        .map(activity => `<div class="sidebar-activity">🏃 ${escapeHtml(activityLabel(activity))}</div>`)
+        .join('');
     if (entries.length === 0) {
-        sidebarContent.innerHTML = '<p class="sidebar-empty">No entries for this day</p>';
+        sidebarContent.innerHTML = html + '<p class="sidebar-empty">No entries for this day</p>';
+        bindSidebarActions(dateStr);
         return;
     }
-    let html = '';
     entries.forEach(entry => {
         const completedClass = entry.completed ? ' completed' : '';
         const checkedAttr = entry.completed ? ' checked' : '';
@@ -1196,13 +3076,19 @@ function renderSidebar(dateStr) {
         const typeAttr = typeLower ? ` data-type="${typeLower}"` : '';
         const typeHtml = entry.entry_type ? `<span class="sidebar-entry-type" data-type="${typeLower}">${escapeHtml(entry.entry_type)}</span>` : '';
         html += `
-            <div class="sidebar-entry${completedClass}" data-entry-id="${entry.id}"${typeAttr}>
+            <div class="sidebar-entry${completedClass}${highlightClasses(entry)}" data-entry-id="${entry.id}"${typeAttr}${accentAttrs(entry)}>
                 <div class="sidebar-entry-header">
-                    <input type="checkbox" class="sidebar-entry-checkbox" data-entry-id="${entry.id}"${checkedAttr}>
-                    <span class="sidebar-entry-subject">${escapeHtml(entry.subject)}</span>
+                    <input type="checkbox" class="sidebar-entry-checkbox" data-entry-id="${entry.id}" aria-label="Done: ${escapeAttr(entry.subject)}"${checkedAttr}${READ_ONLY ? ' disabled' : ''}>
+                    <span class="sidebar-entry-subject">${entry.pinned ? '<span class="pin-badge">📌</span>' : ''}${subjectIcon(entry.subject)}${escapeHtml(entry.subject)}</span>
                     ${typeHtml}
                 </div>
-                <div class="sidebar-entry-task">${escapeHtml(entry.task)}</div>
+                ${entry.end_date ? `<div class="sidebar-entry-until">Until ${entry.end_date}</div>` : ''}
+                <div class="sidebar-entry-task">${renderTask(entry.task)}</div>
+                ${READ_ONLY || entry.continued ? '' : `
+                <div class="sidebar-entry-actions">
+                    <button type="button" class="sidebar-edit-btn" data-entry-id="${entry.id}" title="Edit">✎ Edit</button>
+                    <button type="button" class="delete-btn" data-entry-id="${entry.id}" title="Delete">🗑️</button>
+                </div>`}
             </div>
         `;
     });
@@ -1210,6 +3096,85 @@ function renderSidebar(dateStr) {
     sidebarContent.querySelectorAll('.sidebar-entry-checkbox').forEach(checkbox => {
         checkbox.addEventListener('change', handleSidebarCheckbox);
     });
+    bindSidebarActions(dateStr);
+}
+
+// The sidebar's add, edit and delete controls (none in read-only mode).
+// Delete reuses the list's confirmation dialog.
+function bindSidebarActions(dateStr) {
+    sidebarContent.querySelector('.sidebar-add')?.addEventListener('click', () => openAddEntryDialog(dateStr));
+    bindDeleteButtons(sidebarContent);
+    sidebarContent.querySelectorAll('.sidebar-edit-btn').forEach(btn => {
+        btn.addEventListener('click', () => openSidebarEdit(btn.closest('.sidebar-entry'), dateStr));
+    });
+}
+
+// Swap a sidebar entry's buttons for a small form: its day and estimate,
+// saved with one PATCH (which also takes a test's study sessions along)
+function openSidebarEdit(el, dateStr) {
+    const entry = (entriesByDate[dateStr] || []).find(e => e.id === el.dataset.entryId);
+    if (!entry) return;
+    const actions = el.querySelector('.sidebar-entry-actions');
+    actions.innerHTML = `
+        <form class="sidebar-edit">
+            <label>Date <input type="date" name="date" value="${entry.date}" required></label>
+            <label>Minutes <input type="number" name="estimate" min="1" max="600" value="${entry.estimated_minutes || ''}"></label>
+            <button type="submit">Save</button>
+            <button type="button" class="sidebar-edit-cancel">Cancel</button>
+        </form>`;
+    const form = actions.querySelector('form');
+    form.querySelector('.sidebar-edit-cancel').addEventListener('click', () => renderSidebar(dateStr));
+    form.addEventListener('submit', e => {
+        e.preventDefault();
+        const update = {};
+        const date = form.elements.date.value;
+        if (date && date !== entry.date) update.date = date;
+        const estimate = parseInt(form.elements.estimate.value);
+        if (estimate > 0 && estimate !== entry.estimated_minutes) update.estimated_minutes = estimate;
+        if (Object.keys(update).length === 0) {
+            renderSidebar(dateStr);
+            return;
+        }
+        saveSidebarEdit(entry, update);
+    });
+}
+
+async function saveSidebarEdit(entry, update) {
+    try {
+        const response = await fetch(`/api/entries/${entry.id}`, {
+            method: 'PATCH',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(update)
+        });
+        if (!response.ok) {
+            alert(await errorMessage(response));
+            return;
+        }
+        await confirmBusyDay(await response.json(), () => fetch(`/api/entries/${entry.id}`, {
+            method: 'PATCH',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ date: entry.date })
+        }));
+        await refreshList(rangeIncluding(update.date || entry.date));
+    } catch (error) {
+        console.error('Error updating entry:', error);
+    }
+}
+
+// "17:00–18:30 Nuoto", or just the name for all-day activities
+function activityLabel(activity) {
+    const time = activity.start_time ? `${activity.start_time}–${activity.end_time || ''} ` : '';
+    return time + activity.name;
+}
+
+// " pinned accented" for a calendar entry, matching the list's classes
+function highlightClasses(entry) {
+    return (entry.pinned ? ' pinned' : '') + (entry.color ? ' accented' : '');
+}
+
+// data-color and the --accent style for an entry with a color override
+function accentAttrs(entry) {
+    return entry.color ? ` data-color="${escapeAttr(entry.color)}" style="--accent: ${escapeAttr(entry.color)}"` : '';
 }
 
 function escapeHtml(text) {
@@ -1218,16 +3183,90 @@ function escapeHtml(text) {
     return div.innerHTML;
 }
 
+// ========== Task Text ==========
+
+// Markdown-lite for task text: bullet/numbered lines, **bold** and links.
+// Mirrors render_task() in html/text.rs; keep the two in step.
+const TASK_URL = /\b(?:https?:\/\/|www\.)[^\s<>"']+/gi;
+
+function escapeAttr(text) {
+    return escapeHtml(text).replace(/"/g, '&quot;');
+}
+
+// Drop sentence punctuation after a URL, and a closing paren it didn't open.
+function trimUrl(url) {
+    for (;;) {
+        let trimmed = url.replace(/[.,;:!?]+$/, '');
+        if (trimmed.endsWith(')')) {
+            const opens = (trimmed.match(/\(/g) || []).length;
+            const closes = (trimmed.match(/\)/g) || []).length;
+            if (opens < closes) trimmed = trimmed.slice(0, -1);
+        }
+        if (trimmed.length === url.length) return url;
+        url = trimmed;
+    }
+}
+
+function linkifyTask(text) {
+    let html = '';
+    let last = 0;
+    for (const match of text.matchAll(TASK_URL)) {
+        const url = trimUrl(match[0]);
+        const href = url.toLowerCase().startsWith('www.') ? 'https://' + url : url;
+        html += escapeHtml(text.slice(last, match.index))
+            + `<a class="task-link" href="${escapeAttr(href)}" target="_blank" rel="noopener noreferrer">${escapeHtml(url)}</a>`;
+        last = match.index + url.length;
+    }
+    return html + escapeHtml(text.slice(last));
+}
+
+function renderTaskInline(text) {
+    let html = '';
+    let rest = text;
+    for (;;) {
+        const start = rest.indexOf('**');
+        const end = start < 0 ? -1 : rest.indexOf('**', start + 2);
+        if (end <= start + 2) break;
+        html += linkifyTask(rest.slice(0, start))
+            + `<strong>${linkifyTask(rest.slice(start + 2, end))}</strong>`;
+        rest = rest.slice(end + 2);
+    }
+    return html + linkifyTask(rest);
+}
+
+function renderTask(text) {
+    const blocks = [];
+    let afterBlank = false;
+    for (const raw of (text || '').split('\n')) {
+        const line = raw.trim();
+        if (!line) { afterBlank = true; continue; }
+        const last = afterBlank ? null : blocks[blocks.length - 1];
+        afterBlank = false;
+        const bullet = line.match(/^[-*•] \s*(.*)$/);
+        const numbered = line.match(/^\d{1,3}[.)] \s*(.*)$/);
+        const [kind, content] = bullet ? ['ul', bullet[1]] : numbered ? ['ol', numbered[1]] : ['text', line];
+        if (last && last.kind === kind) last.lines.push(content);
+        else blocks.push({ kind, lines: [content] });
+    }
+    const renderLines = lines => lines.map(renderTaskInline).join('<br>');
+    if (blocks.length === 1 && blocks[0].kind === 'text') return renderLines(blocks[0].lines);
+    return blocks.map(block => block.kind === 'text'
+        ? `<p>${renderLines(block.lines)}</p>`
+        : `<${block.kind}>${block.lines.map(item => `<li>${renderTaskInline(item)}</li>`).join('')}</${block.kind}>`
+    ).join('');
+}
+
 async function handleSidebarCheckbox(e) {
     const entryId = e.target.dataset.entryId;
     const isChecked = e.target.checked;
+    if (isChecked && !confirmOutOfOrder(entryId)) {
+        e.target.checked = false;
+        return;
+    }
     const entryEl = e.target.closest('.sidebar-entry');
     if (isChecked) { entryEl.classList.add('completed'); updateCompletedCount(1); }
     else { entryEl.classList.remove('completed'); updateCompletedCount(-1); }
-    if (selectedDate && entriesByDate[selectedDate]) {
-        const entry = entriesByDate[selectedDate].find(e => e.id === entryId);
-        if (entry) entry.completed = isChecked;
-    }
+    calendarCopies(entryId).forEach(entry => { entry.completed = isChecked; });
     renderCalendar();
     if (selectedDate) {
         const dayEl = document.querySelector(`.cal-day[data-date="${selectedDate}"]`);
@@ -1235,7 +3274,7 @@ async function handleSidebarCheckbox(e) {
     }
     try {
         const response = await fetch(`/api/entries/${entryId}`, {
-            method: 'PUT',
+            method: 'PATCH',
             headers: { 'Content-Type': 'application/json' },
             body: JSON.stringify({ completed: isChecked })
         });
@@ -1243,11 +3282,10 @@ async function handleSidebarCheckbox(e) {
             e.target.checked = !isChecked;
             entryEl.classList.toggle('completed');
             updateCompletedCount(isChecked ? -1 : 1);
-            if (selectedDate && entriesByDate[selectedDate]) {
-                const entry = entriesByDate[selectedDate].find(e => e.id === entryId);
-                if (entry) entry.completed = !isChecked;
-            }
+            calendarCopies(entryId).forEach(entry => { entry.completed = !isChecked; });
             console.error('Failed to update completion state');
+        } else if ((await response.json()).day_completed) {
+            celebrateDay(selectedDate);
         }
     } catch (error) {
         e.target.checked = !isChecked;
@@ -1309,6 +3347,10 @@ function renderCalendar() {
     calendarDays.querySelectorAll('.cal-day').forEach(dayEl => {
         dayEl.addEventListener('click', () => selectDay(dayEl.dataset.date));
     });
+    if (!READ_ONLY) {
+        bindCalendarDrag(calendarDays);
+        bindCalendarToggles(calendarDays);
+    }
 }
 
 function renderCalendarDay(day, dateStr, isOtherMonth, isToday = false, isSelected = false, maxEntries = 2) {
@@ -1319,13 +3361,24 @@ function renderCalendarDay(day, dateStr, isOtherMonth, isToday = false, isSelect
     if (isToday) classes += ' today';
     if (hasEntries) classes += ' has-entries';
     if (isSelected) classes += ' selected';
-    let html = `<div class="${classes}" data-date="${dateStr}">`;
+    const holiday = holidaysByDate[dateStr];
+    if (holiday !== undefined) classes += ' holiday';
+    const titleAttr = holiday ? ` title="${escapeHtml(holiday)}"` : '';
+    let html = `<div class="${classes}" data-date="${dateStr}"${titleAttr}>`;
     html += `<div class="cal-day-number">${day}</div>`;
+    (activitiesByDate[dateStr] || []).forEach(activity => {
+        html += `<div class="cal-activity" title="${escapeAttr(activityLabel(activity))}">${escapeHtml(activity.name)}</div>`;
+    });
     entries.slice(0, maxEntries).forEach(entry => {
         const completedClass = entry.completed ? ' completed' : '';
         const typeAttr = entry.entry_type ? ` data-type="${entry.entry_type.toLowerCase()}"` : '';
-        html += `<div class="cal-entry${completedClass}"${typeAttr}>`;
-        html += `<span class="cal-entry-subject">${escapeHtml(entry.subject)}</span>`;
+        const title = (entry.completed ? 'Mark as not done' : 'Mark as done') + (entry.end_date ? ` (until ${entry.end_date})` : '');
+        // Later days of a multi-day event can't be dragged; the first one moves it
+        const continuedClass = entry.continued ? ' continued' : '';
+        html += `<div class="cal-entry${completedClass}${continuedClass}${highlightClasses(entry)}"${typeAttr}${accentAttrs(entry)} draggable="${!READ_ONLY && !entry.continued}" data-entry-id="${entry.id}" title="${title}">`;
+        html += `<span class="cal-entry-check">${entry.completed ? '✓' : '○'}</span>`;
+        if (entry.pinned) html += '<span class="pin-badge">📌</span>';
+        html += `<span class="cal-entry-subject">${subjectIcon(entry.subject)}${escapeHtml(entry.subject)}</span>`;
         html += '</div>';
     });
     if (entries.length > maxEntries) {
@@ -1335,6 +3388,133 @@ function renderCalendarDay(day, dateStr, isOtherMonth, isToday = false, isSelect
     return html;
 }
 
+// ========== Calendar Completion Toggle ==========
+
+function bindCalendarToggles(root) {
+    root.querySelectorAll('.cal-entry[data-entry-id]').forEach(entryEl => {
+        entryEl.addEventListener('click', function(e) {
+            e.stopPropagation(); // don't also select the day
+            toggleCalendarEntry(this.dataset.entryId, this.closest('.cal-day').dataset.date);
+        });
+    });
+}
+
+// Every cached copy of an entry; multi-day events sit on each of their days
+function calendarCopies(entryId) {
+    return Object.values(entriesByDate).flat().filter(e => e.id === entryId);
+}
+
+// Mirror a completion change onto the (hidden) list view item.
+function syncListItem(entryId, isChecked) {
+    const item = document.querySelector(`.homework-item[data-entry-id="${entryId}"]`);
+    if (!item) return;
+    const checkbox = item.querySelector('.homework-checkbox');
+    if (checkbox) checkbox.checked = isChecked;
+    item.classList.toggle('completed', isChecked);
+}
+
+// Flip an entry's completion from the month grid: update the cached month
+// data and every view right away, then PATCH; revert everything on failure.
+async function toggleCalendarEntry(entryId, dateStr) {
+    const entry = (entriesByDate[dateStr] || []).find(e => e.id === entryId);
+    if (!entry) return;
+    const apply = (completed) => {
+        calendarCopies(entryId).forEach(copy => { copy.completed = completed; });
+        syncListItem(entryId, completed);
+        updateCompletedCount(completed ? 1 : -1);
+        renderCalendar();
+        if (selectedDate) renderSidebar(selectedDate);
+    };
+    const isChecked = !entry.completed;
+    if (isChecked && !confirmOutOfOrder(entryId)) return;
+    apply(isChecked);
+
+    try {
+        const response = await fetch(`/api/entries/${entryId}`, {
+            method: 'PATCH',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ completed: isChecked })
+        });
+        if (!response.ok) throw new Error(`HTTP ${response.status}`);
+        if ((await response.json()).day_completed) celebrateDay(dateStr);
+    } catch (error) {
+        console.error('Error updating completion:', error);
+        apply(!isChecked);
+    }
+}
+
+// ========== Calendar Drag and Drop ==========
+
+let calDragged = null;
+
+function bindCalendarDrag(root) {
+    root.querySelectorAll('.cal-entry[draggable="true"]').forEach(entryEl => {
+        entryEl.addEventListener('dragstart', function(e) {
+            calDragged = { id: this.dataset.entryId, from: this.closest('.cal-day').dataset.date };
+            this.classList.add('dragging');
+            e.dataTransfer.effectAllowed = 'move';
+            e.dataTransfer.setData('text/plain', calDragged.id);
+        });
+        entryEl.addEventListener('dragend', function() {
+            this.classList.remove('dragging');
+            root.querySelectorAll('.cal-day.drag-over').forEach(d => d.classList.remove('drag-over'));
+            calDragged = null;
+        });
+    });
+    root.querySelectorAll('.cal-day').forEach(dayEl => {
+        dayEl.addEventListener('dragover', function(e) {
+            if (!calDragged) return;
+            e.preventDefault();
+            e.dataTransfer.dropEffect = 'move';
+            this.classList.add('drag-over');
+        });
+        dayEl.addEventListener('dragleave', function(e) {
+            if (!this.contains(e.relatedTarget)) this.classList.remove('drag-over');
+        });
+        dayEl.addEventListener('drop', function(e) {
+            e.preventDefault();
+            this.classList.remove('drag-over');
+            if (!calDragged) return;
+            const { id, from } = calDragged;
+            calDragged = null;
+            if (this.dataset.date !== from) moveCalendarEntry(id, from, this.dataset.date);
+        });
+    });
+}
+
+// Move an entry to another day: update the cached month data right away,
+// then move it to the bottom of the target day on the server.
+// On failure the entry goes back where it was.
+async function moveCalendarEntry(entryId, fromDate, toDate) {
+    const source = entriesByDate[fromDate] || [];
+    const index = source.findIndex(e => e.id === entryId);
+    if (index === -1) return;
+    const [entry] = source.splice(index, 1);
+    const target = entriesByDate[toDate] || (entriesByDate[toDate] = []);
+    const oldPosition = entry.position;
+    entry.position = target.length > 0 ? Math.max(...target.map(e => e.position)) + 1 : 0;
+    target.push(entry);
+    renderCalendar();
+    if (selectedDate) renderSidebar(selectedDate);
+
+    try {
+        const response = await postMove(entryId, { date: toDate });
+        if (!response.ok) throw new Error(`HTTP ${response.status}`);
+        await confirmBusyDay(await response.json(), () =>
+            postMove(entryId, { date: fromDate, index }));
+        // Moving a test also moves its study sessions; re-sync the list
+        // (which drops the calendar cache and re-fetches the months).
+        refreshList();
+    } catch (error) {
+        console.error('Error moving entry:', error);
+        target.splice(target.indexOf(entry), 1);
+        entry.position = oldPosition;
+        source.splice(index, 0, entry);
+        renderCalendar();
+        if (selectedDate) renderSidebar(selectedDate);
+    }
+}
+
 sidebarClose.addEventListener('click', () => {
     selectedDate = null;
     document.querySelectorAll('.cal-day.selected').forEach(el => el.classList.remove('selected'));
@@ -1345,13 +3525,13 @@ sidebarClose.addEventListener('click', () => {
 calPrev.addEventListener('click', () => {
     currentMonth--;
     if (currentMonth < 1) { currentMonth = 12; currentYear--; }
-    renderCalendar();
+    refreshCalendar();
 });
 
 calNext.addEventListener('click', () => {
     currentMonth++;
     if (currentMonth > 12) { currentMonth = 1; currentYear++; }
-    renderCalendar();
+    refreshCalendar();
 });
 
 let resizeTimeout;
@@ -1362,10 +3542,10 @@ window.addEventListener('resize', () => {
     }, 150);
 });
 
-if (localStorage.getItem('preferredView') === 'calendar') {
+if (document.body.dataset.defaultView === 'calendar') {
     showCalendarView();
 } else if (!calendarView.classList.contains('hidden')) {
-    renderCalendar();
+    refreshCalendar();
 }
 
 // ========== Due-link scrolling ==========
@@ -1377,7 +3557,7 @@ document.addEventListener('click', function(e) {
     const targetId = link.dataset.scrollTo;
     const targetDate = link.getAttribute('href').replace('#entry-group-', '');
     const group = document.getElementById('entry-group-' + targetDate);
-    if (group && group.classList.contains('collapsed')) group.classList.remove('collapsed');
+    if (group && group.classList.contains('collapsed')) setCollapsed(group, false);
     const entry = document.querySelector('[data-entry-id="' + targetId + '"]');
     if (entry) {
         entry.scrollIntoView({ behavior: 'smooth', block: 'center' });
@@ -1387,4 +3567,275 @@ document.addEventListener('click', function(e) {
         group.scrollIntoView({ behavior: 'smooth', block: 'start' });
     }
 });
+
+// ========== Pin & Color Menu ==========
+
+// Right-click (or, on a touch screen, long-press) an entry in either view to
+// pin it to the top of its day, give it its own accent color, copy it to
+// another day or hide it until later.
+const ACCENT_COLORS = ['#ff0096', '#ff3333', '#ffaa00', '#ffee00', '#33ff99', '#00ffff', '#3366ff', '#9933ff'];
+const entryMenu = document.createElement('div');
+entryMenu.className = 'entry-menu';
+entryMenu.hidden = true;
+document.body.appendChild(entryMenu);
+
+function closeEntryMenu() {
+    entryMenu.hidden = true;
+}
+
+function openEntryMenu(el, x, y) {
+    const id = el.dataset.entryId;
+    const pinned = el.classList.contains('pinned');
+    const current = el.dataset.color || '';
+    let html = `<button type="button" class="menu-item" data-pinned="${!pinned}">${pinned ? '📌 Unpin' : '📌 Pin to top of day'}</button>`;
+    html += '<div class="menu-swatches">';
+    ACCENT_COLORS.forEach(color => {
+        const cls = color === current ? ' current' : '';
+        html += `<button type="button" class="menu-swatch${cls}" data-color="${color}" style="background: ${color}" title="${color}"></button>`;
+    });
+    html += '</div>';
+    if (current) html += '<button type="button" class="menu-item" data-color="">↺ Default color</button>';
+    if (el.dataset.generated !== 'true') {
+        html += '<button type="button" class="menu-item" data-action="duplicate">⧉ Duplicate to date…</button>';
+        html += '<button type="button" class="menu-item" data-action="hide">🙈 Hide until…</button>';
+    }
+    if (el.dataset.visibleFrom) {
+        html += '<button type="button" class="menu-item" data-visible-from="">👁 Show now</button>';
+    }
+    entryMenu.innerHTML = html;
+    entryMenu.hidden = false;
+    // Keep the menu on screen
+    const rect = entryMenu.getBoundingClientRect();
+    entryMenu.style.left = Math.min(x, window.innerWidth - rect.width - 8) + 'px';
+    entryMenu.style.top = Math.min(y, window.innerHeight - rect.height - 8) + 'px';
+    entryMenu.querySelectorAll('button').forEach(btn => {
+        btn.addEventListener('click', () => {
+            closeEntryMenu();
+            const dateStr = el.closest('[data-date]')?.dataset.date;
+            if (btn.dataset.action === 'duplicate') {
+                duplicateEntry(id, dateStr);
+                return;
+            }
+            if (btn.dataset.action === 'hide') {
+                hideEntryUntil(id, el.dataset.visibleFrom, dateStr);
+                return;
+            }
+            const update = btn.dataset.pinned !== undefined
+                ? { pinned: btn.dataset.pinned === 'true' }
+                : btn.dataset.visibleFrom !== undefined
+                    ? { visible_from: null }
+                    : { color: btn.dataset.color || null };
+            highlightEntry(id, update, dateStr);
+        });
+    });
+}
+
+// Copy an entry to a day picked by the user (a week later by default),
+// not completed
+async function duplicateEntry(id, dateStr) {
+    const suggested = dateStr ? shiftDate(dateStr, 7) : new Date().toISOString().slice(0, 10);
+    const date = prompt('Duplicate to date (YYYY-MM-DD):', suggested)?.trim();
+    if (!date) return;
+    try {
+        const response = await fetch(`/api/entries/${id}/duplicate`, {
+            method: 'POST',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify({ date })
+        });
+        if (!response.ok) {
+            alert(await errorMessage(response));
+            return;
+        }
+        const copy = await response.json();
+        await confirmBusyDay(copy, () =>
+            fetch(`/api/entries/${copy.id}/cascade`, { method: 'DELETE' }));
+        await refreshList(rangeIncluding(date));
+    } catch (error) {
+        console.error('Error duplicating entry:', error);
+    }
+}
+
+// Keep an entry out of the list and dashboard until a day picked by the
+// user (two weeks before it's due by default)
+function hideEntryUntil(id, current, dateStr) {
+    const suggested = current || (dateStr ? shiftDate(dateStr, -14) : '');
+    const day = prompt('Hide until (YYYY-MM-DD):', suggested)?.trim();
+    if (!day) return;
+    highlightEntry(id, { visible_from: day }, dateStr);
+}
+
+async function highlightEntry(id, update, dateStr) {
+    try {
+        const response = await fetch(`/api/entries/${id}`, {
+            method: 'PATCH',
+            headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(update)
+        });
+        if (!response.ok) {
+            alert(await errorMessage(response));
+            return;
+        }
+        await refreshList(rangeIncluding(dateStr));
+    } catch (error) {
+        console.error('Error updating entry:', error);
+    }
+}
+
+const MENU_TARGETS = '.homework-item, .cal-entry[data-entry-id], .sidebar-entry';
+// How long a finger has to rest on an entry, and how far it may wander
+const LONG_PRESS_MS = 500;
+const LONG_PRESS_SLOP = 10;
+let longPress = null;
+
+// Touch browsers that don't turn a long-press into a contextmenu event
+// (iOS Safari) get the menu from this timer instead
+function startLongPress(e) {
+    const el = e.touches.length === 1 && e.target.closest(MENU_TARGETS);
+    if (!el) return;
+    const { clientX: x, clientY: y } = e.touches[0];
+    longPress = {
+        x, y, opened: false,
+        timer: setTimeout(() => {
+            longPress.opened = true;
+            openEntryMenu(el, x, y);
+        }, LONG_PRESS_MS)
+    };
+}
+
+function cancelLongPress(e) {
+    if (!longPress) return;
+    const touch = e.touches && e.touches[0];
+    if (touch && Math.hypot(touch.clientX - longPress.x, touch.clientY - longPress.y) < LONG_PRESS_SLOP) return;
+    clearTimeout(longPress.timer);
+    if (!longPress.opened) longPress = null;
+}
+
+function endLongPress(e) {
+    if (!longPress) return;
+    clearTimeout(longPress.timer);
+    // The click that follows would tick the entry or close the menu again
+    if (longPress.opened) e.preventDefault();
+    longPress = null;
+}
+
+if (!READ_ONLY) {
+    document.addEventListener('contextmenu', e => {
+        const el = e.target.closest(MENU_TARGETS);
+        if (!el) return;
+        e.preventDefault();
+        // Already opened by the long-press timer
+        if (longPress && longPress.opened) return;
+        openEntryMenu(el, e.clientX, e.clientY);
+    });
+    document.addEventListener('touchstart', startLongPress, { passive: true });
+    document.addEventListener('touchmove', cancelLongPress, { passive: true });
+    document.addEventListener('touchend', endLongPress);
+    document.addEventListener('touchcancel', endLongPress);
+    document.addEventListener('click', e => {
+        if (!entryMenu.contains(e.target)) closeEntryMenu();
+    });
+    document.addEventListener('keydown', e => {
+        if (e.key === 'Escape') closeEntryMenu();
+    });
+    window.addEventListener('scroll', closeEntryMenu, { passive: true });
+}
+
+// ========== Live Presence ==========
+
+// One session per tab; the name is shared by every tab in this browser
+const liveSession = sessionStorage.getItem('compitutto-session')
+    || (crypto.randomUUID ? crypto.randomUUID() : String(Math.random()).slice(2));
+sessionStorage.setItem('compitutto-session', liveSession);
+const presenceEl = document.getElementById('presence');
+let liveSource = null;
+
+function connectLive() {
+    if (!window.EventSource) return;
+    if (liveSource) liveSource.close();
+    const params = new URLSearchParams({
+        session: liveSession,
+        name: localStorage.getItem('compitutto-name') || ''
+    });
+    liveSource = new EventSource('/api/live?' + params);
+    liveSource.addEventListener('presence', e => renderPresence(JSON.parse(e.data).viewers));
+    liveSource.addEventListener('entry', e => applyRemoteCompletion(JSON.parse(e.data)));
+}
+
+function renderPresence(viewers) {
+    if (!presenceEl) return;
+    const names = [...new Set(viewers.filter(v => v.session !== liveSession).map(v => v.name))];
+    presenceEl.classList.toggle('others', names.length > 0);
+    presenceEl.textContent = names.length === 0
+        ? '👤 Just you'
+        : `👥 ${names.join(', ')} ${names.length === 1 ? 'is' : 'are'} viewing`;
+}
+
+// Another session ticked an entry on or off: mirror it without reloading.
+// Changes this page already shows (including its own) are ignored.
+function applyRemoteCompletion({ id, completed }) {
+    const item = document.querySelector(`.homework-item[data-entry-id="${id}"]`);
+    const checkbox = item?.querySelector('.homework-checkbox');
+    if (checkbox && checkbox.checked !== completed) {
+        checkbox.checked = completed;
+        item.classList.toggle('completed', completed);
+        updateCompletedCount(completed ? 1 : -1);
+        if (completed) {
+            checkAndCollapseIfAllCompleted(item.closest('.date-group'));
+        } else {
+            expandDateGroup(item.closest('.date-group'));
+        }
+    }
+    let cached = false;
+    for (const entries of Object.values(entriesByDate)) {
+        const entry = entries.find(e => e.id === id);
+        if (entry && entry.completed !== completed) {
+            entry.completed = completed;
+            cached = true;
+        }
+    }
+    if (cached && !calendarView.classList.contains('hidden')) {
+        renderCalendar();
+        if (selectedDate) renderSidebar(selectedDate);
+    }
+}
+
+presenceEl?.addEventListener('click', () => {
+    const name = prompt('Your name, as others see it:', localStorage.getItem('compitutto-name') || '');
+    if (name === null) return;
+    // Cut like the server does (live::MAX_NAME_LEN), so reactions aren't refused
+    localStorage.setItem('compitutto-name', name.trim().slice(0, 40));
+    connectLive();
+});
+
+// Feature flags from /api/features; a page only sets up what the server
+// has switched on
+async function loadFeatures() {
+    try {
+        const response = await fetch('/api/features');
+        if (!response.ok) throw new Error(`HTTP ${response.status}`);
+        return Object.fromEntries((await response.json()).map(f => [f.name, f.enabled]));
+    } catch (error) {
+        console.error('Error loading features:', error);
+        return {};
+    }
+}
+
+loadFeatures().then(features => {
+    if (features.live === false) {
+        if (presenceEl) presenceEl.hidden = true;
+        return;
+    }
+    connectLive();
+});
+
+// ========== Pending imports ==========
+
+if (!READ_ONLY) fetch('/api/imports')
+    .then(response => response.ok ? response.json() : [])
+    .then(pending => {
+        if (pending.length === 0) return;
+        document.getElementById('imports-count').textContent = `(${pending.length})`;
+        document.getElementById('imports-link').style.display = '';
+    })
+    .catch(() => {});
 "#;
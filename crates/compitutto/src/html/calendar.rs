@@ -1,17 +1,56 @@
 //! Calendar view rendering (Rust-side HTML structure only).
-//! The actual day-cell rendering is done client-side in JavaScript.
+//! The actual day-cell rendering is done client-side in JavaScript, which
+//! fetches each month's entries from `/api/calendar`.
 
+use chrono::NaiveDate;
 use maud::{html, Markup};
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
 use crate::types::HomeworkEntry;
 
+/// Compact entry shape consumed by the JS calendar renderer.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CalendarEntry {
+    pub id: String,
+    pub subject: String,
+    pub task: String,
+    pub entry_type: String,
+    pub completed: bool,
+    /// Order within the day; used to append dropped entries at the bottom
+    pub position: i32,
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Last day of a multi-day event
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<String>,
+    /// Shown on a later day of a multi-day event, not the one it starts on
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub continued: bool,
+}
+
+impl From<&HomeworkEntry> for CalendarEntry {
+    fn from(entry: &HomeworkEntry) -> Self {
+        Self {
+            id: entry.id.clone(),
+            subject: entry.subject.clone(),
+            task: entry.task.clone(),
+            entry_type: entry.entry_type.clone(),
+            completed: entry.completed,
+            position: entry.position,
+            pinned: entry.pinned,
+            color: entry.color.clone(),
+            end_date: entry.end_date.clone(),
+            continued: false,
+        }
+    }
+}
+
 /// Render the calendar layout shell: header with prev/next, the day-name grid,
 /// the empty days container (populated by JS), and the sidebar.
-pub fn render_calendar(
-    entries: &[HomeworkEntry],
-    by_date: &BTreeMap<&str, Vec<&HomeworkEntry>>,
-) -> Markup {
+pub fn render_calendar(entries: &[HomeworkEntry]) -> Markup {
     // Determine which month to show initially — the most recent entry's month.
     let reference_date = entries
         .iter()
@@ -39,7 +78,7 @@ pub fn render_calendar(
                         div.cal-day-header { (day) }
                     }
                 }
-                div.calendar-days #"calendar-days" data-entries=(entries_to_json(by_date)) {}
+                div.calendar-days #"calendar-days" {}
             }
             aside.calendar-sidebar #"calendar-sidebar" {
                 div.sidebar-header {
@@ -73,28 +112,53 @@ pub fn month_name(month: u32) -> &'static str {
     }
 }
 
-/// Serialize entries grouped by date into a JSON string for the JS calendar renderer.
-pub fn entries_to_json(by_date: &BTreeMap<&str, Vec<&HomeworkEntry>>) -> String {
-    use std::collections::HashMap;
+/// Group entries by date for the JS calendar renderer, pinned ones first.
+/// Multi-day events are listed on every day through their `end_date`.
+pub fn group_by_date(entries: &[HomeworkEntry]) -> BTreeMap<String, Vec<CalendarEntry>> {
+    let mut by_date: BTreeMap<String, Vec<CalendarEntry>> = BTreeMap::new();
+    for entry in entries {
+        let calendar_entry = CalendarEntry::from(entry);
+        for day in later_days(entry) {
+            by_date.entry(day).or_default().push(CalendarEntry {
+                continued: true,
+                ..calendar_entry.clone()
+            });
+        }
+        by_date
+            .entry(entry.date.clone())
+            .or_default()
+            .push(calendar_entry);
+    }
+    for day in by_date.values_mut() {
+        day.sort_by_key(|e| !e.pinned);
+    }
+    by_date
+}
 
-    let map: HashMap<&str, Vec<_>> = by_date
-        .iter()
-        .map(|(date, items)| {
-            let entries: Vec<_> = items
-                .iter()
-                .map(|e| {
-                    serde_json::json!({
-                        "id": e.id,
-                        "subject": e.subject,
-                        "task": e.task,
-                        "entry_type": e.entry_type,
-                        "completed": e.completed
-                    })
-                })
-                .collect();
-            (*date, entries)
-        })
-        .collect();
+/// The days after the first of a multi-day event, through its end date
+fn later_days(entry: &HomeworkEntry) -> Vec<String> {
+    let parse = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok();
+    let (Some(start), Some(end)) = (
+        parse(&entry.date),
+        entry.end_date.as_deref().and_then(parse),
+    ) else {
+        return Vec::new();
+    };
+    start
+        .iter_days()
+        .skip(1)
+        .take_while(|day| *day <= end)
+        .map(|day| day.to_string())
+        .collect()
+}
 
-    serde_json::to_string(&map).unwrap_or_else(|_| "{}".to_string())
+/// First and last day of the given month, or `None` if it is out of range.
+pub fn month_bounds(year: i32, month: u32) -> Option<(NaiveDate, NaiveDate)> {
+    let first = NaiveDate::from_ymd_opt(year, month, 1)?;
+    let next_month = if month == 12 {
+        NaiveDate::from_ymd_opt(year + 1, 1, 1)?
+    } else {
+        NaiveDate::from_ymd_opt(year, month + 1, 1)?
+    };
+    Some((first, next_month.pred_opt()?))
 }
@@ -0,0 +1,209 @@
+//! Dashboard landing page: today, tomorrow, the rest of the week and
+//! upcoming tests at a glance. The full list lives at `/list`.
+
+use chrono::NaiveDate;
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+use std::collections::BTreeMap;
+
+use super::assets::CSS;
+use super::settings::SETTINGS_CSS;
+use super::text;
+use crate::types::{Dashboard, HomeworkEntry, Settings};
+
+/// Render the dashboard as a full HTML string. `read_only` drops the link to
+/// the settings page.
+pub fn render_dashboard_page(
+    dashboard: &Dashboard,
+    settings: &Settings,
+    read_only: bool,
+) -> String {
+    let counts = &dashboard.counts;
+    let markup: Markup = html! {
+        (DOCTYPE)
+        html lang=(settings.language.code()) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                title { "Compitutto" }
+                style { (PreEscaped(CSS)) (PreEscaped(SETTINGS_CSS)) (PreEscaped(DASHBOARD_CSS)) }
+            }
+            body class={
+                (settings.theme.body_class())
+                @if read_only { " read-only" }
+            } {
+                div.container {
+                    header.header {
+                        div.header-left {
+                            h1 { "Compitutto" }
+                            div.stats { (format_day(&dashboard.today)) }
+                        }
+                        div.header-right {
+                            a.nav-link href="/list" { "📋 All entries" }
+                            a.nav-link href="/stats" { "📊 Stats" }
+                            a.nav-link href="/register" { "📒 Register" }
+                            @if !read_only {
+                                a.nav-link href="/settings" { "⚙ Settings" }
+                            }
+                        }
+                    }
+                    div.dashboard-counters {
+                        (counter("Overdue", counts.overdue, counts.overdue > 0))
+                        (counter("Open today", counts.open_today, false))
+                        (counter("Open this week", counts.open_week, false))
+                        (counter("Done this week", counts.completed_week, false))
+                        (counter("Tests ahead", dashboard.upcoming_tests.len(), false))
+                    }
+                    @if counts.hidden > 0 {
+                        a.dashboard-hidden href="/list?hidden=true" {
+                            "🙈 " (counts.hidden) " hidden until later"
+                        }
+                    }
+                    div.dashboard-columns {
+                        (column("today", "Today", &dashboard.due_today, false, &dashboard.cross_listed))
+                        (column("tomorrow", "Tomorrow", &dashboard.due_tomorrow, false, &dashboard.cross_listed))
+                        (column("week", "This week", &dashboard.this_week, true, &dashboard.cross_listed))
+                        (column("tests", "Upcoming tests", &dashboard.upcoming_tests, true, &dashboard.cross_listed))
+                    }
+                }
+            }
+        }
+    };
+    markup.into_string()
+}
+
+fn counter(label: &str, value: usize, alert: bool) -> Markup {
+    html! {
+        div.dashboard-counter.alert[alert] {
+            span.counter-value { (value) }
+            span.counter-label { (label) }
+        }
+    }
+}
+
+/// One column; `show_dates` for columns spanning several days.
+/// Primaries in `cross_listed` get the list's 🔀 badge.
+fn column(
+    id: &str,
+    title: &str,
+    entries: &[HomeworkEntry],
+    show_dates: bool,
+    cross_listed: &BTreeMap<String, Vec<String>>,
+) -> Markup {
+    html! {
+        section.dashboard-column #{"dashboard-" (id)} {
+            h2 { (title) span.column-count { (entries.len()) } }
+            @if entries.is_empty() {
+                p.column-empty { "Nothing here." }
+            }
+            @for entry in entries {
+                a.dashboard-entry.completed[entry.completed]
+                    href={"/list#entry-group-" (entry.date)}
+                    data-entry-id=(entry.id)
+                {
+                    div.homework-subject {
+                        (entry.subject)
+                        @if !entry.entry_type.is_empty() {
+                            span.homework-type data-type=(entry.entry_type.to_lowercase()) {
+                                (entry.entry_type)
+                            }
+                        }
+                        @if let Some(others) = cross_listed.get(&entry.id) {
+                            span.cross-badge title="Posted under each of these subjects; shown once" {
+                                "🔀 " (entry.subject)
+                                @for other in others { " + " (other) }
+                            }
+                        }
+                    }
+                    @if show_dates {
+                        div.entry-date { (format_day(&entry.date)) }
+                    }
+                    div.homework-task { (text::render_task(&entry.task)) }
+                }
+            }
+        }
+    }
+}
+
+/// `"Wednesday 15 January"`, or the raw string if it isn't a date.
+fn format_day(date: &str) -> String {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|d| d.format("%A %-d %B").to_string())
+        .unwrap_or_else(|_| date.to_string())
+}
+
+const DASHBOARD_CSS: &str = r#"
+.header-right { gap: 8px; }
+.dashboard-counters { display: flex; flex-wrap: wrap; gap: 12px; margin: 24px 0; }
+.dashboard-counter { flex: 1 1 120px; padding: 14px 16px; border-radius: 10px; background: rgba(255,255,255,0.04); display: flex; flex-direction: column; gap: 4px; }
+.dashboard-counter.alert { background: rgba(255,80,80,0.15); }
+.dashboard-counter.alert .counter-value { color: #ff6b6b; }
+.counter-value { font-size: 1.8em; font-weight: 700; }
+.counter-label { color: #888; font-size: 0.75em; text-transform: uppercase; letter-spacing: 0.08em; }
+.dashboard-hidden { display: block; margin: -12px 0 20px; color: #888; font-size: 0.85em; text-decoration: none; }
+.dashboard-hidden:hover { color: #00ffff; }
+.dashboard-columns { display: grid; grid-template-columns: repeat(auto-fit, minmax(220px, 1fr)); gap: 16px; }
+.dashboard-column h2 { font-size: 1em; text-transform: uppercase; letter-spacing: 0.08em; color: #aaa; margin-bottom: 12px; display: flex; justify-content: space-between; }
+.column-count { color: #666; }
+.column-empty { color: #666; font-size: 0.9em; }
+.dashboard-entry { display: block; padding: 10px 12px; margin-bottom: 8px; border-radius: 8px; background: rgba(255,255,255,0.04); color: inherit; text-decoration: none; }
+.dashboard-entry:hover { background: rgba(255,255,255,0.08); }
+.dashboard-entry.completed { opacity: 0.5; }
+.dashboard-entry.completed .homework-task { text-decoration: line-through; }
+.dashboard-entry .entry-date { color: #888; font-size: 0.8em; margin: 2px 0 4px; }
+body.theme-light .dashboard-counter, body.theme-light .dashboard-entry { background: rgba(0,0,0,0.04); }
+body.theme-light .dashboard-entry:hover { background: rgba(0,0,0,0.08); }
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::DashboardCounts;
+
+    #[test]
+    fn test_render_dashboard_page() {
+        let mut done = HomeworkEntry::new(
+            "compiti".to_string(),
+            "2025-01-15".to_string(),
+            "Storia".to_string(),
+            "Leggere <pag. 4>".to_string(),
+        );
+        done.completed = true;
+        let test = HomeworkEntry::new(
+            "verifica".to_string(),
+            "2025-01-21".to_string(),
+            "Scienze".to_string(),
+            "Capitolo 2".to_string(),
+        );
+        let dashboard = Dashboard {
+            today: "2025-01-15".to_string(),
+            due_today: vec![done],
+            due_tomorrow: vec![],
+            this_week: vec![test.clone()],
+            upcoming_tests: vec![test],
+            counts: DashboardCounts {
+                overdue: 2,
+                hidden: 3,
+                ..Default::default()
+            },
+            cross_listed: BTreeMap::new(),
+        };
+
+        let page = render_dashboard_page(&dashboard, &Settings::default(), false);
+        assert!(page.contains("Wednesday 15 January"));
+        assert!(page.contains(
+            r#"<div class="dashboard-counter alert"><span class="counter-value">2</span>"#
+        ));
+        assert!(page
+            .contains(r#"class="dashboard-entry completed" href="/list#entry-group-2025-01-15""#));
+        assert!(page.contains("Leggere &lt;pag. 4&gt;"));
+        assert!(page.contains(r#"<div class="entry-date">Tuesday 21 January</div>"#));
+        assert!(page.contains("Nothing here."));
+        assert!(page.contains(r#"href="/list?hidden=true">🙈 3 hidden until later</a>"#));
+        assert!(page.contains(r#"href="/list""#));
+        assert!(page.contains(r#"href="/settings""#));
+
+        let page = render_dashboard_page(&dashboard, &Settings::default(), true);
+        assert!(page.contains(r#"<body class="theme-dark read-only">"#));
+        assert!(!page.contains(r#"href="/settings""#));
+    }
+}
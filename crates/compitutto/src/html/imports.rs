@@ -0,0 +1,218 @@
+//! Import review page: staged entries waiting to be accepted or rejected.
+
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+use std::collections::BTreeMap;
+
+use super::assets::CSS;
+use super::settings::SETTINGS_CSS;
+use crate::types::PendingImport;
+
+/// Render the import review page as a full HTML string. `photos` maps a
+/// captured draft's `source_id` to its photo's attachment id.
+pub fn render_imports_page(pending: &[PendingImport], photos: &BTreeMap<String, i64>) -> String {
+    let markup: Markup = html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                title { "Compitutto — Imports" }
+                style { (PreEscaped(CSS)) (PreEscaped(SETTINGS_CSS)) (PreEscaped(IMPORTS_CSS)) }
+            }
+            body {
+                div.container {
+                    header.header {
+                        div.header-left {
+                            h1 { "Compitutto" }
+                        }
+                        div.header-right {
+                            a.nav-link href="/" { "← Back" }
+                        }
+                    }
+                    div.settings-page.imports-page {
+                        h2 { "Imports" }
+                        section.settings-section {
+                            p.settings-desc {
+                                "New and changed entries from export files wait here until you "
+                                "accept them. Rejected entries won't be offered again."
+                            }
+                            div.settings-actions.capture-actions {
+                                label.capture-button {
+                                    "Add a photo of the diary"
+                                    input #"capture-photo" type="file" accept="image/*" capture="environment";
+                                }
+                                span #"capture-status" {}
+                            }
+                            p.settings-desc.imports-empty #"imports-empty"
+                                style=[(!pending.is_empty()).then_some("display:none")] {
+                                "Nothing to review."
+                            }
+                            @if !pending.is_empty() {
+                                div.settings-actions.imports-actions {
+                                    button #"accept-all" type="button" {
+                                        "Accept all (" (pending.len()) ")"
+                                    }
+                                    span #"imports-status" {}
+                                }
+                                ul.import-list {
+                                    @for item in pending {
+                                        li.import-row data-id=(item.id) {
+                                            div.import-meta {
+                                                span.import-badge.(if item.is_change() { "changed" } else { "new" }) {
+                                                    @if item.is_change() { "changed" } @else { "new" }
+                                                }
+                                                span.import-date { (item.date) }
+                                                span.import-subject { (item.subject) }
+                                                span.import-type { (item.entry_type) }
+                                            }
+                                            div.import-task { (item.task) }
+                                            @if let Some(photo) = photos.get(&item.source_id) {
+                                                a.import-photo href={ "/api/attachments/" (photo) "/file" } target="_blank" {
+                                                    img src={ "/api/attachments/" (photo) "/file" } alt="Diary page" loading="lazy";
+                                                }
+                                            }
+                                            @if let Some(previous) = &item.previous_task {
+                                                div.import-previous { "was: " (previous) }
+                                            }
+                                            div.import-buttons {
+                                                button.import-accept type="button" { "Accept" }
+                                                button.import-reject type="button" { "Reject" }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                script { (PreEscaped(IMPORTS_JS)) }
+            }
+        }
+    };
+    markup.into_string()
+}
+
+const IMPORTS_CSS: &str = r#"
+.imports-page { max-width: 800px; }
+.imports-actions { margin-top: 0; margin-bottom: 24px; }
+#accept-all {
+    padding: 12px 32px;
+    background: linear-gradient(135deg, #ffaa00, #ff6600);
+    color: #000; font-weight: 900; border: none; border-radius: 4px;
+    cursor: pointer; font-size: 0.95em; letter-spacing: 0.05em; text-transform: uppercase;
+}
+#accept-all:hover { opacity: 0.85; }
+#imports-status { font-size: 0.85em; color: #33ff99; }
+.import-list { list-style: none; display: flex; flex-direction: column; gap: 12px; }
+.import-row {
+    padding: 14px 18px;
+    border: 1px solid rgba(255,255,255,0.12);
+    border-radius: 6px;
+    background: rgba(255,255,255,0.04);
+}
+.import-meta { display: flex; gap: 12px; align-items: center; font-size: 0.8em; color: #888; margin-bottom: 6px; }
+.import-subject { color: #fff; font-weight: 700; }
+.import-badge { text-transform: uppercase; letter-spacing: 0.08em; font-weight: 900; font-size: 0.85em; }
+.import-badge.new { color: #33ff99; }
+.import-badge.changed { color: #ffaa00; }
+.import-task { line-height: 1.5; }
+.import-previous { margin-top: 6px; font-size: 0.85em; color: #888; text-decoration: line-through; }
+.import-buttons { display: flex; gap: 10px; margin-top: 12px; }
+.import-buttons button {
+    padding: 6px 16px;
+    border-radius: 4px;
+    border: 1px solid rgba(255,255,255,0.2);
+    background: rgba(255,255,255,0.08);
+    color: #fff; font-weight: 700; cursor: pointer;
+}
+.import-photo img { display: block; max-width: 100%; max-height: 320px; margin-top: 10px; border-radius: 4px; }
+.capture-actions { margin-top: 0; margin-bottom: 24px; }
+.capture-button {
+    padding: 8px 18px;
+    border-radius: 4px;
+    border: 1px dashed rgba(255,255,255,0.3);
+    color: #fff; font-weight: 700; cursor: pointer;
+}
+.capture-button:hover { border-color: #ffaa00; }
+.capture-button input { display: none; }
+#capture-status { font-size: 0.85em; color: #33ff99; }
+.import-accept:hover { border-color: #33ff99; background: rgba(51,255,153,0.15); }
+.import-reject:hover { border-color: #ff3366; background: rgba(255,51,102,0.15); }
+"#;
+
+const IMPORTS_JS: &str = r#"
+function removeRow(row) {
+    row.remove();
+    const remaining = document.querySelectorAll('.import-row').length;
+    const acceptAll = document.getElementById('accept-all');
+    if (remaining === 0) {
+        document.getElementById('imports-empty').style.display = '';
+        if (acceptAll) acceptAll.style.display = 'none';
+    } else if (acceptAll) {
+        acceptAll.textContent = `Accept all (${remaining})`;
+    }
+}
+
+async function resolveImport(row, action) {
+    row.querySelectorAll('button').forEach(b => b.disabled = true);
+    try {
+        const res = await fetch(`/api/imports/${encodeURIComponent(row.dataset.id)}/${action}`, { method: 'POST' });
+        if (res.ok) {
+            removeRow(row);
+            return;
+        }
+    } catch (e) {}
+    row.querySelectorAll('button').forEach(b => b.disabled = false);
+    document.getElementById('imports-status').textContent = '✗ Error updating import';
+}
+
+document.querySelectorAll('.import-row').forEach(row => {
+    row.querySelector('.import-accept').addEventListener('click', () => resolveImport(row, 'accept'));
+    row.querySelector('.import-reject').addEventListener('click', () => resolveImport(row, 'reject'));
+});
+
+document.getElementById('capture-photo').addEventListener('change', async (e) => {
+    const file = e.target.files[0];
+    if (!file) return;
+    const status = document.getElementById('capture-status');
+    status.textContent = 'Uploading…';
+    try {
+        const res = await fetch('/api/capture', {
+            method: 'POST',
+            headers: { 'Content-Type': file.type },
+            body: file,
+        });
+        if (res.ok) {
+            location.reload();
+            return;
+        }
+        const err = await res.json().catch(() => ({}));
+        status.textContent = `✗ ${err.message || 'Error uploading photo'}`;
+    } catch (err) {
+        status.textContent = '✗ Network error';
+    }
+    e.target.value = '';
+});
+
+const acceptAllBtn = document.getElementById('accept-all');
+if (acceptAllBtn) {
+    acceptAllBtn.addEventListener('click', async () => {
+        const status = document.getElementById('imports-status');
+        acceptAllBtn.disabled = true;
+        try {
+            const res = await fetch('/api/imports/accept-all', { method: 'POST' });
+            if (res.ok) {
+                const data = await res.json();
+                document.querySelectorAll('.import-row').forEach(removeRow);
+                status.textContent = `✓ Accepted ${data.accepted}`;
+            } else {
+                status.textContent = '✗ Error accepting imports';
+            }
+        } catch (e) {
+            status.textContent = '✗ Network error';
+        } finally {
+            acceptAllBtn.disabled = false;
+        }
+    });
+}
+"#;
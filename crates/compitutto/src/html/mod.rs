@@ -3,59 +3,155 @@
 //! Organised into submodules:
 //!   - `assets`   — CSS and JavaScript constants
 //!   - `calendar` — Calendar view (month grid + sidebar)
+//!   - `dashboard` — Dashboard landing page (today, tomorrow, week, tests)
+//!   - `imports`  — Import review page
 //!   - `settings` — Settings page
+//!   - `share`    — Read-only page for one shared entry
+//!   - `simple`   — Simple view for younger children (today's plan, big cards)
+//!   - `stats`    — Stats page (estimated vs actual time)
+//!   - `text`     — Markdown-lite task text (lists, bold, links)
 
 pub mod assets;
 pub mod calendar;
+pub mod dashboard;
+pub mod imports;
+pub mod register;
 pub mod settings;
-
+pub mod share;
+pub mod simple;
+pub mod stats;
+pub mod text;
+
+pub use dashboard::render_dashboard_page;
+pub use imports::render_imports_page;
+pub use register::render_register_page;
 pub use settings::render_settings_page;
+pub use simple::render_simple_page;
+pub use stats::{render_compare_page, render_stats_page, render_teachers_page};
 
 use anyhow::Result;
 use chrono::NaiveDate;
 use maud::{html, Markup, PreEscaped, DOCTYPE};
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 use std::fs;
 use std::path::Path;
 
-use crate::types::HomeworkEntry;
+use crate::types::{HomeworkEntry, Settings};
 
 use assets::{CSS, JAVASCRIPT};
 use calendar::render_calendar;
 
 /// Write a full HTML page to disk.
 pub fn generate_html(entries: &[HomeworkEntry], path: &Path) -> Result<()> {
-    let html = render_page(entries);
+    let html = render_page(entries, &Settings::default());
     fs::write(path, html.into_string())?;
     Ok(())
 }
 
-/// Render the main homework list page.
-pub fn render_page(entries: &[HomeworkEntry]) -> Markup {
-    // Group entries by date
-    let mut by_date: BTreeMap<&str, Vec<&HomeworkEntry>> = BTreeMap::new();
-    for entry in entries {
-        by_date.entry(&entry.date).or_default().push(entry);
+/// The date window the list view shows, and what lies outside it.
+///
+/// The server renders only the weeks around today set in the settings;
+/// "Load older/newer", the date pickers and "Show all" change the window
+/// through `/fragments/list?from=..&to=..` (or `?all=true`).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ListWindow {
+    /// First and last day shown (inclusive, `YYYY-MM-DD`)
+    pub from: String,
+    pub to: String,
+    pub has_older: bool,
+    pub has_newer: bool,
+    /// Counts over every entry, not just the window, for the header
+    pub total: usize,
+    pub completed: usize,
+    /// Other subjects a shown entry was also posted under, by id; their
+    /// copies are left out (see `crosslist`)
+    pub cross_listed: HashMap<String, Vec<String>>,
+    /// Shown entries dated on a day their subject has no lesson, by id,
+    /// with the reason (see `timetable`)
+    pub off_schedule: HashMap<String, String>,
+    /// Open entries hidden until a later day (`visible_from`)
+    pub hidden: usize,
+    /// With `?hidden=true`, today: hidden entries are shown, marked
+    pub showing_hidden: Option<String>,
+}
+
+/// A page saying a page couldn't be shown, with `message` and a way back
+pub fn render_error_page(message: &str) -> String {
+    html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                title { "Compitutto — Error" }
+                style { (PreEscaped(CSS)) }
+            }
+            body {
+                div.container {
+                    header.header {
+                        div.header-left {
+                            h1 { "Compitutto" }
+                        }
+                        div.header-right {
+                            a.nav-link href="/" { "← Back" }
+                        }
+                    }
+                    div.empty-state {
+                        h2 { "This page can't be shown" }
+                        p { (message) }
+                    }
+                }
+            }
+        }
     }
+    .into_string()
+}
 
-    // Build an id -> entry lookup for linking lavoro items to their parent compiti
-    let entry_by_id: std::collections::HashMap<&str, &HomeworkEntry> =
-        entries.iter().map(|e| (e.id.as_str(), e)).collect();
+/// Render the main homework list page with every entry (static build).
+pub fn render_page(entries: &[HomeworkEntry], settings: &Settings) -> Markup {
+    render_page_window(entries, None, settings, false)
+}
 
-    let total_count = entries.len();
-    let completed_count = entries.iter().filter(|e| e.completed).count();
+/// Render the main homework list page, honouring the display settings.
+/// With a `window`, `entries` are the ones inside it. `read_only` leaves out
+/// the editing controls (see `serve --read-only`).
+pub fn render_page_window(
+    entries: &[HomeworkEntry],
+    window: Option<&ListWindow>,
+    settings: &Settings,
+    read_only: bool,
+) -> Markup {
+    let (total_count, completed_count) = match window {
+        Some(w) => (w.total, w.completed),
+        None => (
+            entries.len(),
+            entries.iter().filter(|e| e.completed).count(),
+        ),
+    };
 
     html! {
         (DOCTYPE)
-        html lang="en" {
+        html lang=(settings.language.code()) {
             head {
                 meta charset="UTF-8";
                 meta name="viewport" content="width=device-width, initial-scale=1.0";
                 title { "Compitutto" }
                 style { (PreEscaped(CSS)) }
             }
-            body {
+            body class={
+                (settings.theme.body_class())
+                @if settings.hide_completed { " hide-completed" }
+                @if read_only { " read-only" }
+            } data-default-view=(settings.default_view.as_str())
+              data-happened-types=(settings.happened_types().join(" "))
+              data-ask-grade=[settings.ask_grade.then_some("true")] {
                 div.container {
+                    // Shown by JS when /api/version reports a newer release
+                    div.update-banner #"update-banner" hidden {
+                        span #"update-text" {}
+                        a #"update-link" target="_blank" rel="noopener" { "What's new" }
+                        button.update-dismiss #"update-dismiss" type="button" title="Dismiss" aria-label="Dismiss" { "✕" }
+                    }
                     header.header {
                         div.header-left {
                             h1 { "Compitutto" }
@@ -65,31 +161,77 @@ pub fn render_page(entries: &[HomeworkEntry]) -> Markup {
                                 span #"total-count" { (total_count) }
                                 " completed"
                             }
+                            button.presence #"presence" type="button" title="Who else has Compitutto open. Click to set your name." {
+                                "👤 Just you"
+                            }
+                            @if !settings.weekly_goals.is_empty() {
+                                div.goals-widget #"goals-widget" title="This week's goals (completed compiti and study sessions)" {
+                                    @for goal in &settings.weekly_goals {
+                                        span.goal data-subject=(goal.subject.to_lowercase()) {
+                                            (goal.subject) " "
+                                            span.goal-done { "0" }
+                                            "/" (goal.target)
+                                        }
+                                    }
+                                }
+                            }
+                            div.forecast-widget #"forecast-widget" hidden {}
                         }
                         div.view-toggle {
                             button.view-btn.active #"list-view-btn" type="button" { "List" }
                             button.view-btn #"calendar-view-btn" type="button" { "Calendar" }
-                            a.view-btn href="/settings" { "⚙ Settings" }
+                            @if !read_only {
+                                a.view-btn #"imports-link" href="/imports" style="display:none" {
+                                    "📥 Imports "
+                                    span #"imports-count" {}
+                                }
+                            }
+                            a.view-btn href="/" { "🏠 Dashboard" }
+                            a.view-btn href="/stats" { "📊 Stats" }
+                            a.view-btn href="/register" { "📒 Register" }
+                            @if !read_only {
+                                a.view-btn href="/settings" { "⚙ Settings" }
+                            }
                         }
                     }
-                    div.list-view #"list-view" {
-                        @if entries.is_empty() {
-                            div.empty-state {
-                                p { "No homework entries found." }
-                            }
-                        } @else {
-                            @for (date, items) in by_date.iter().rev() {
-                                (render_date_group(date, items, &entry_by_id))
+                    // Filled from /api/plan/today; stays hidden in the static build
+                    section.plan-panel.hidden #"plan-panel" {
+                        div.plan-header {
+                            h2 { "Start here" }
+                            span.plan-budget #"plan-budget" {}
+                        }
+                        ol.plan-items #"plan-items" {}
+                        div.plan-later #"plan-later" {}
+                    }
+                    // Subject chips come from /api/subjects; stays hidden in
+                    // the static build
+                    div.filter-bar #"filter-bar" hidden {
+                        div.filter-group #"filter-subjects" {}
+                        div.filter-group {
+                            @for entry_type in FILTER_TYPES {
+                                button.filter-chip type="button" data-filter="type" data-value=(entry_type) {
+                                    (entry_type)
+                                }
                             }
                         }
+                        div.filter-group {
+                            button.filter-chip type="button" data-filter="status" data-value="pending" { "Pending" }
+                            button.filter-chip type="button" data-filter="status" data-value="done" { "Done" }
+                        }
+                        button.filter-clear #"filter-clear" type="button" hidden { "✕ Clear" }
+                    }
+                    div.list-view #"list-view" {
+                        (render_list(entries, window))
                     }
                     div.calendar-view.hidden #"calendar-view" {
-                        (render_calendar(entries, &by_date))
+                        (render_calendar(entries))
                     }
                 }
 
                 // Floating add button
-                button.add-entry-btn #"add-entry-btn" type="button" title="Add new entry" { "+" }
+                @if !read_only {
+                    button.add-entry-btn #"add-entry-btn" type="button" title="Add new entry" aria-label="Add new entry" { "+" }
+                }
 
                 // Delete confirmation dialog
                 dialog #"delete-dialog" {
@@ -117,6 +259,32 @@ pub fn render_page(entries: &[HomeworkEntry]) -> Markup {
                     }
                 }
 
+                // Post-completion feedback dialog
+                dialog #"feedback-dialog" {
+                    h3 { "How did it go?" }
+                    form #"feedback-form" {
+                        div.form-group {
+                            label for="feedback-minutes" { "Minutes it took" }
+                            input #"feedback-minutes" type="number" min="1" step="5";
+                        }
+                        div.form-group {
+                            label { "Difficulty" }
+                            div.difficulty-options {
+                                @for level in 1..=5u8 {
+                                    label.difficulty-option {
+                                        input type="radio" name="difficulty" value=(level);
+                                        span { (level) }
+                                    }
+                                }
+                            }
+                        }
+                        div.dialog-buttons {
+                            button.btn-cancel #"feedback-skip" type="button" { "Skip" }
+                            button.btn-primary type="submit" { "Save" }
+                        }
+                    }
+                }
+
                 // Add entry dialog
                 dialog #"add-entry-dialog" {
                     h3 { "Add New Entry" }
@@ -158,6 +326,10 @@ pub fn render_page(entries: &[HomeworkEntry]) -> Markup {
                             label for="new-entry-task" { "Task" }
                             textarea #"new-entry-task" rows="3" placeholder="Task description..." required {}
                         }
+                        div.form-group {
+                            label for="new-entry-estimate" { "Estimated minutes (optional)" }
+                            input #"new-entry-estimate" type="number" min="1" step="5";
+                        }
                         div.dialog-buttons {
                             button.btn-cancel #"add-entry-cancel" type="button" { "Cancel" }
                             button.btn-primary type="submit" { "Add Entry" }
@@ -171,28 +343,130 @@ pub fn render_page(entries: &[HomeworkEntry]) -> Markup {
     }
 }
 
+/// Entry types with a chip in the list's filter bar
+const FILTER_TYPES: &[&str] = &["compiti", "verifica", "studio"];
+
+/// Render the list view contents: every date group, newest first.
+///
+/// Served on its own from `/fragments/list` so the page can swap the list in
+/// place after an add/move/delete. The wrapper carries the counts for the
+/// header and, with a `window`, its dates and the load older/newer buttons.
+pub fn render_list(entries: &[HomeworkEntry], window: Option<&ListWindow>) -> Markup {
+    // Group entries by date
+    let mut by_date: BTreeMap<&str, Vec<&HomeworkEntry>> = BTreeMap::new();
+    for entry in entries {
+        by_date.entry(&entry.date).or_default().push(entry);
+    }
+    for items in by_date.values_mut() {
+        items.sort_by_key(|e| !e.pinned);
+    }
+
+    // Build an id -> entry lookup for linking lavoro items to their parent compiti
+    let entry_by_id: std::collections::HashMap<&str, &HomeworkEntry> =
+        entries.iter().map(|e| (e.id.as_str(), e)).collect();
+    let no_cross_listings = HashMap::new();
+    let no_off_schedule = HashMap::new();
+    let cross_listed = window.map_or(&no_cross_listings, |w| &w.cross_listed);
+    let off_schedule = window.map_or(&no_off_schedule, |w| &w.off_schedule);
+    let hidden_on = window.and_then(|w| w.showing_hidden.as_deref());
+
+    let (total_count, completed_count) = match window {
+        Some(w) => (w.total, w.completed),
+        None => (
+            entries.len(),
+            entries.iter().filter(|e| e.completed).count(),
+        ),
+    };
+
+    html! {
+        div.list-content
+            data-total=(total_count)
+            data-completed=(completed_count)
+            data-from=[window.map(|w| w.from.as_str())]
+            data-to=[window.map(|w| w.to.as_str())]
+            data-hidden=[hidden_on.map(|_| "true")]
+        {
+            @if let Some(w) = window {
+                form.list-range #"list-range" {
+                    label { "From " input type="date" name="from" value=(w.from) required; }
+                    label { "to " input type="date" name="to" value=(w.to) required; }
+                    button type="submit" { "Show" }
+                    @if w.has_older || w.has_newer {
+                        button.list-range-all type="button" { "Show all" }
+                    }
+                    button.list-range-reset type="button" title="Back to the dates set in Settings" { "Reset" }
+                }
+            }
+            @if !entries.is_empty() || window.is_some_and(|w| w.hidden > 0) {
+                div.list-tools {
+                    @if !entries.is_empty() {
+                        button.collapse-past #"collapse-past" type="button" { "Collapse past days" }
+                    }
+                    @if let Some(w) = window.filter(|w| w.hidden > 0) {
+                        button.show-hidden #"show-hidden" type="button" title="Entries set to show up later" {
+                            @if w.showing_hidden.is_some() {
+                                "🙈 Hide the " (w.hidden) " for later"
+                            } @else {
+                                "🙈 " (w.hidden) " hidden until later"
+                            }
+                        }
+                    }
+                }
+            }
+            @if window.is_some_and(|w| w.has_newer) {
+                button.load-more #"load-newer" type="button" data-direction="newer" { "↑ Load newer" }
+            }
+            @if entries.is_empty() {
+                div.empty-state {
+                    @if window.is_some_and(|w| w.total > 0) {
+                        p { "No homework entries in these weeks." }
+                    } @else {
+                        p { "No homework entries found." }
+                    }
+                }
+            } @else {
+                @for (date, items) in by_date.iter().rev() {
+                    (render_date_group(date, items, &entry_by_id, cross_listed, off_schedule, hidden_on))
+                }
+            }
+            @if window.is_some_and(|w| w.has_older) {
+                button.load-more #"load-older" type="button" data-direction="older" { "↓ Load older" }
+            }
+        }
+    }
+}
+
 /// Render a single date group (header + all homework items for that date).
 fn render_date_group(
     date: &str,
     items: &[&HomeworkEntry],
     entry_by_id: &std::collections::HashMap<&str, &HomeworkEntry>,
+    cross_listed: &HashMap<String, Vec<String>>,
+    off_schedule: &HashMap<String, String>,
+    hidden_on: Option<&str>,
 ) -> Markup {
-    let all_completed = items.iter().all(|item| item.completed);
+    let done = items.iter().filter(|item| item.completed).count();
+    let all_completed = done == items.len();
     let group_class = if all_completed {
         "date-group collapsed"
     } else {
         "date-group"
     };
+    let items_id = format!("date-items-{}", date);
     html! {
         div class=(group_class) data-date=(date) id={"entry-group-" (date)} {
-            div.date-header {
-                span.collapse-indicator { "▼" }
-                "📅 "
-                (NaiveDate::parse_from_str(date, "%Y-%m-%d")
-                    .map(|d| format!("{} {}", d.format("%A"), date))
-                    .unwrap_or_else(|_| date.to_string()))
+            div.date-header role="button" tabindex="0" aria-expanded=(!all_completed) aria-controls=(items_id) {
+                span.collapse-indicator aria-hidden="true" { "▼" }
+                span.date-label {
+                    "📅 "
+                    (NaiveDate::parse_from_str(date, "%Y-%m-%d")
+                        .map(|d| format!("{} {}", d.format("%A"), date))
+                        .unwrap_or_else(|_| date.to_string()))
+                }
+                // What a collapsed day still has to show
+                span.date-count title="Done" { (done) "/" (items.len()) }
             }
-            div.date-items {
+            div.date-items id=(items_id) {
                 @for item in items.iter() {
                     @let entry_id = &item.id;
                     @let stable_id = item.stable_id();
@@ -222,26 +496,40 @@ fn render_date_group(
                         if is_completed { cls.push_str(" completed"); }
                         if is_lavoro   { cls.push_str(" lavoro-item"); }
                         if is_compiti  { cls.push_str(" compiti-due-item"); }
+                        if item.pinned { cls.push_str(" pinned"); }
+                        if item.color.is_some() { cls.push_str(" accented"); }
                         cls
                     };
                     div
                         class=(item_class)
                         data-entry-id=(entry_id)
                         data-stable-id=(stable_id)
+                        data-subject=(item.subject)
+                        data-type=(item.entry_type.to_lowercase())
                         data-generated=[is_generated.then_some("true")]
                         data-orphaned=[is_orphaned.then_some("true")]
                         data-parent-id=[parent_info.as_ref().map(|(id, _)| id.as_str())]
                         data-lavoro-id=[lavoro_child_id.as_deref()]
+                        data-color=[item.color.as_deref()]
+                        data-visible-from=[item.visible_from.as_deref()]
+                        style=[item.color.as_deref().map(|c| format!("--accent: {c}"))]
                         draggable="true"
                     {
                         input.homework-checkbox
                             type="checkbox"
                             id={"entry-" (stable_id)}
                             data-entry-id=(entry_id)
+                            aria-label={"Done: " (item.subject) ", " (item.entry_type)}
                             checked[is_completed];
                         div.homework-content {
                             div.homework-subject {
+                                @if item.pinned {
+                                    span.pin-badge title="Pinned" { "📌" }
+                                }
                                 (item.subject)
+                                @if let Some(topic) = &item.topic {
+                                    span.homework-topic { "→ " (topic) }
+                                }
                                 @if !item.entry_type.is_empty() {
                                     @let type_lower = item.entry_type.to_lowercase();
                                     span.homework-type data-type=(type_lower) {
@@ -250,14 +538,52 @@ fn render_date_group(
                                         @else { (item.entry_type) }
                                     }
                                 }
+                                @if let Some(until) = item.until() {
+                                    span.until-date title="Last day of the event" { "(until " (until) ")" }
+                                }
+                                @if let Some(grade) = &item.grade {
+                                    span.grade-badge title="Grade" { "🎓 " (grade) }
+                                }
                                 @if is_generated {
                                     span.auto-badge { "auto" }
                                 }
                                 @if is_orphaned {
                                     span.orphan-badge { "orphaned" }
                                 }
+                                @if let Some(others) = cross_listed.get(entry_id) {
+                                    span.cross-badge title="Posted under each of these subjects; shown once" {
+                                        "🔀 " (item.subject)
+                                        @for other in others { " + " (other) }
+                                    }
+                                }
+                                @if let Some(reason) = off_schedule.get(entry_id) {
+                                    span.schedule-badge title=(reason) { "📅 no lesson that day" }
+                                }
+                            }
+                            div.homework-task { (text::render_task(&item.task)) }
+                            @if let Some(original) = item.original_date() {
+                                div.original-date title="Date in the school's export; moved since" {
+                                    "originally " (original)
+                                }
+                            }
+                            @if let Some(until) = item.visible_from.as_deref().filter(|d| hidden_on.is_some_and(|today| *d > today)) {
+                                div.visible-from title="Hidden from the list and dashboard until then" {
+                                    "🙈 hidden until " (until)
+                                }
+                            }
+                            @if let Some(days) = item.notice_days() {
+                                div.assigned-date title=(assigned_title(item)) {
+                                    (notice_text(days))
+                                }
+                            }
+                            @if let Some(file) = &item.source_file {
+                                div.source-file {
+                                    a href={"/api/entries/" (entry_id) "/source-file"}
+                                        title={"Download " (file) ", the export this came from"} {
+                                        "📄 view original export"
+                                    }
+                                }
                             }
-                            div.homework-task { (item.task) }
                             @if let Some((parent_id, parent_date)) = parent_info {
                                 div.due-link {
                                     "📅 Due: "
@@ -269,7 +595,9 @@ fn render_date_group(
                                 }
                             }
                         }
-                        button.delete-btn type="button" data-entry-id=(entry_id) title="Delete entry" { "🗑" }
+                        button.share-btn type="button" data-entry-id=(entry_id) title="Share a read-only link" aria-label="Share a read-only link" { "📤" }
+                        button.link-btn type="button" data-entry-id=(entry_id) title="Link to another entry" aria-label="Link to another entry" { "🔗" }
+                        button.delete-btn type="button" data-entry-id=(entry_id) title="Delete entry" aria-label="Delete entry" { "🗑" }
                     }
                 }
             }
@@ -277,9 +605,28 @@ fn render_date_group(
     }
 }
 
+/// How much notice an entry came with, e.g. "assigned 5 days before due"
+fn notice_text(days: i64) -> String {
+    match days {
+        0 => "assigned the day it was due".to_string(),
+        1 => "assigned 1 day before due".to_string(),
+        d if d > 1 => format!("assigned {} days before due", d),
+        _ => "posted after it was due".to_string(),
+    }
+}
+
+/// Tooltip for the notice line: when and by whom the entry was posted
+fn assigned_title(entry: &HomeworkEntry) -> String {
+    let at = entry.assigned_at.as_deref().unwrap_or_default();
+    match &entry.teacher {
+        Some(teacher) => format!("Posted {} by {}", at, teacher),
+        None => format!("Posted {}", at),
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::calendar::{entries_to_json, month_name, render_calendar};
+    use super::calendar::{group_by_date, month_bounds, month_name, render_calendar};
     use super::*;
     use tempfile::TempDir;
 
@@ -297,7 +644,7 @@ mod tests {
     #[test]
     fn test_render_page_empty_entries() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("<!DOCTYPE html>"));
         assert!(html.contains("<html"));
         assert!(html.contains("Compitutto"));
@@ -313,7 +660,7 @@ mod tests {
             "Matematica",
             "Pag. 100 es. 1-5",
         )];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("Matematica"));
         assert!(html.contains("Pag. 100 es. 1-5"));
         assert!(html.contains("2025-01-15"));
@@ -327,12 +674,32 @@ mod tests {
             make_entry("compiti", "2025-01-15", "Matematica", "Task 1"),
             make_entry("nota", "2025-01-15", "Italiano", "Task 2"),
         ];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("Matematica"));
         assert!(html.contains("Italiano"));
         assert_eq!(html.matches(r#"class="date-group""#).count(), 1);
     }
 
+    #[test]
+    fn test_render_list_date_group_structure() {
+        let mut done = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        done.completed = true;
+        let entries = vec![
+            done,
+            make_entry("nota", "2025-01-15", "Italiano", "Task 2"),
+            make_entry("compiti", "2025-01-16", "Storia", "Task 3"),
+        ];
+        let html = render_list(&entries, None).into_string();
+        assert!(html.contains(r#"aria-controls="date-items-2025-01-15""#));
+        assert!(html.contains(r#"<div class="date-items" id="date-items-2025-01-15">"#));
+        assert!(html.contains(r#"<span class="date-count" title="Done">1/2</span>"#));
+        assert!(html.contains(r#"id="collapse-past""#));
+
+        assert!(!render_list(&[], None)
+            .into_string()
+            .contains("collapse-past"));
+    }
+
     #[test]
     fn test_render_page_multiple_dates() {
         let entries = vec![
@@ -340,7 +707,7 @@ mod tests {
             make_entry("nota", "2025-01-16", "Italiano", "Task 2"),
             make_entry("compiti", "2025-01-17", "INGLESE", "Task 3"),
         ];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("2025-01-15"));
         assert!(html.contains("2025-01-16"));
         assert!(html.contains("2025-01-17"));
@@ -350,11 +717,11 @@ mod tests {
     #[test]
     fn test_render_page_has_required_elements() {
         let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("<!DOCTYPE html>"));
         assert!(html.contains("<html lang=\"en\""));
         assert!(html.contains("<head>"));
-        assert!(html.contains("<body>"));
+        assert!(html.contains("<body class=\"theme-dark\" data-default-view=\"list\""));
         assert!(html.contains("charset=\"UTF-8\""));
         assert!(html.contains("viewport"));
         assert!(html.contains("<title>Compitutto</title>"));
@@ -362,10 +729,35 @@ mod tests {
         assert!(html.contains("<script>"));
     }
 
+    #[test]
+    fn test_render_page_contrast_theme_and_labels() {
+        let entries = vec![make_entry("verifica", "2025-01-15", "Matematica", "Cap. 3")];
+        let settings = Settings {
+            theme: crate::types::Theme::Contrast,
+            ..Settings::default()
+        };
+        let html = render_page(&entries, &settings).into_string();
+        assert!(html.contains("<body class=\"theme-contrast\""));
+        assert!(html.contains(r#"aria-label="Done: Matematica, verifica""#));
+        assert!(html.contains(r#"aria-label="Delete entry""#));
+        assert!(html.contains(r#"role="button" tabindex="0" aria-expanded="true""#));
+    }
+
+    #[test]
+    fn test_render_page_has_filter_bar() {
+        let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
+        let html = render_page(&entries, &Settings::default()).into_string();
+        // Hidden until JS fills in the subjects
+        assert!(html.contains(r#"<div class="filter-bar" id="filter-bar" hidden>"#));
+        assert!(html.contains(r#"data-filter="type" data-value="verifica""#));
+        assert!(html.contains(r#"data-filter="status" data-value="pending""#));
+        assert!(html.contains(r#"id="filter-subjects""#));
+    }
+
     #[test]
     fn test_render_page_has_checkboxes() {
         let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("homework-checkbox"));
         assert!(html.contains("type=\"checkbox\""));
         assert!(html.contains("data-entry-id"));
@@ -379,7 +771,7 @@ mod tests {
             "Matematica",
             "<script>alert('xss')</script>",
         )];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(!html.contains("<script>alert('xss')</script>"));
         assert!(html.contains("&lt;script&gt;"));
     }
@@ -392,14 +784,14 @@ mod tests {
             "Matematica",
             "Esercizi con àèìòù & simboli",
         )];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("Esercizi con àèìòù &amp; simboli"));
     }
 
     #[test]
     fn test_render_page_empty_entry_type() {
         let entries = vec![make_entry("", "2025-01-15", "Matematica", "Task 1")];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("Matematica"));
         assert!(html.contains("Task 1"));
         assert!(!html.contains("<span class=\"homework-type\">"));
@@ -408,7 +800,7 @@ mod tests {
     #[test]
     fn test_render_page_entry_type_badge() {
         let entries = vec![make_entry("nota", "2025-01-15", "Matematica", "Task 1")];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("homework-type"));
         assert!(html.contains("nota"));
     }
@@ -420,14 +812,14 @@ mod tests {
             make_entry("nota", "2025-01-15", "Italiano", "Task 2"),
             make_entry("compiti", "2025-01-16", "INGLESE", "Task 3"),
         ];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.matches("date-group").count() >= 2);
     }
 
     #[test]
     fn test_render_page_css_included() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("font-family"));
         assert!(html.contains("background"));
         assert!(html.contains(".homework-item"));
@@ -437,7 +829,7 @@ mod tests {
     #[test]
     fn test_render_page_javascript_included() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("homework-checkbox"));
         assert!(html.contains("/api/entries"));
         assert!(html.contains("dragstart"));
@@ -453,7 +845,15 @@ mod tests {
             make_entry("nota", "2025-01-15", "Italiano", "Task 2"),
         ];
         let refs: Vec<&HomeworkEntry> = entries.iter().collect();
-        let html = render_date_group("2025-01-15", &refs, &Default::default()).into_string();
+        let html = render_date_group(
+            "2025-01-15",
+            &refs,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )
+        .into_string();
         assert!(html.contains("date-group"));
         assert!(html.contains("2025-01-15"));
         assert!(html.contains("Matematica"));
@@ -467,7 +867,15 @@ mod tests {
             make_entry("nota", "2025-01-15", "Italiano", "Task 2"),
         ];
         let refs: Vec<&HomeworkEntry> = entries.iter().collect();
-        let html = render_date_group("2025-01-15", &refs, &Default::default()).into_string();
+        let html = render_date_group(
+            "2025-01-15",
+            &refs,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )
+        .into_string();
         let entry1_id = entries[0].stable_id();
         let entry2_id = entries[1].stable_id();
         assert!(html.contains(&format!("entry-{}", entry1_id)));
@@ -481,9 +889,25 @@ mod tests {
         let entry1 = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
         let entry2 = make_entry("nota", "2025-01-16", "Italiano", "Task 2");
         let refs1: Vec<&HomeworkEntry> = vec![&entry1, &entry2];
-        let html1 = render_date_group("2025-01-15", &refs1, &Default::default()).into_string();
+        let html1 = render_date_group(
+            "2025-01-15",
+            &refs1,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )
+        .into_string();
         let refs2: Vec<&HomeworkEntry> = vec![&entry2, &entry1];
-        let html2 = render_date_group("2025-01-15", &refs2, &Default::default()).into_string();
+        let html2 = render_date_group(
+            "2025-01-15",
+            &refs2,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )
+        .into_string();
         let entry1_id = entry1.stable_id();
         assert!(html1.contains(&format!("entry-{}", entry1_id)));
         assert!(html2.contains(&format!("entry-{}", entry1_id)));
@@ -549,14 +973,14 @@ mod tests {
             "Matematica",
             &long_task,
         )];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains(&long_task));
     }
 
     #[test]
     fn test_render_page_unicode_content() {
         let entries = vec![make_entry("compiti", "2025-01-15", "日本語", "任务描述 🎉")];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("日本語"));
         assert!(html.contains("任务描述"));
     }
@@ -573,7 +997,7 @@ mod tests {
                 )
             })
             .collect();
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains(">100<"));
         assert!(html.contains("SUBJECT_0"));
         assert!(html.contains("SUBJECT_99"));
@@ -584,7 +1008,7 @@ mod tests {
     #[test]
     fn test_render_page_has_add_button() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("add-entry-btn"));
         assert!(html.contains(r#"id="add-entry-btn""#));
     }
@@ -592,7 +1016,7 @@ mod tests {
     #[test]
     fn test_render_page_has_delete_dialog() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("delete-dialog"));
         assert!(html.contains("delete-confirm"));
         assert!(html.contains("delete-cancel"));
@@ -601,7 +1025,7 @@ mod tests {
     #[test]
     fn test_render_page_has_position_dialog() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("position-dialog"));
         assert!(html.contains("position-top"));
         assert!(html.contains("position-bottom"));
@@ -610,7 +1034,7 @@ mod tests {
     #[test]
     fn test_render_page_has_add_entry_dialog() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("add-entry-dialog"));
         assert!(html.contains("add-entry-form"));
         assert!(html.contains("new-entry-date"));
@@ -623,16 +1047,79 @@ mod tests {
     fn test_render_date_group_has_delete_buttons() {
         let entries = [make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
         let refs: Vec<&HomeworkEntry> = entries.iter().collect();
-        let html = render_date_group("2025-01-15", &refs, &Default::default()).into_string();
+        let html = render_date_group(
+            "2025-01-15",
+            &refs,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )
+        .into_string();
         assert!(html.contains("delete-btn"));
         assert!(html.contains(r#"title="Delete entry""#));
     }
 
+    #[test]
+    fn test_render_date_group_has_link_buttons() {
+        let entries = [make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
+        let refs: Vec<&HomeworkEntry> = entries.iter().collect();
+        let html = render_date_group(
+            "2025-01-15",
+            &refs,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )
+        .into_string();
+        assert!(html.contains(&format!(
+            r#"class="link-btn" type="button" data-entry-id="{}""#,
+            entries[0].id
+        )));
+
+        let page = render_page(&entries, &Settings::default()).into_string();
+        assert!(page.contains("function confirmOutOfOrder"));
+        assert!(page.contains("fetch('/api/links')"));
+    }
+
+    #[test]
+    fn test_render_date_group_has_subject_for_resources() {
+        let entries = [make_entry(
+            "verifica",
+            "2025-01-15",
+            "Lingua Inglese",
+            "Unit 3",
+        )];
+        let refs: Vec<&HomeworkEntry> = entries.iter().collect();
+        let html = render_date_group(
+            "2025-01-15",
+            &refs,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )
+        .into_string();
+        assert!(html.contains(r#"data-subject="Lingua Inglese""#));
+
+        let page = render_page(&entries, &Settings::default()).into_string();
+        assert!(page.contains("fetch('/api/resources')"));
+    }
+
     #[test]
     fn test_render_date_group_draggable() {
         let entries = [make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
         let refs: Vec<&HomeworkEntry> = entries.iter().collect();
-        let html = render_date_group("2025-01-15", &refs, &Default::default()).into_string();
+        let html = render_date_group(
+            "2025-01-15",
+            &refs,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )
+        .into_string();
         assert!(html.contains(r#"draggable="true""#));
     }
 
@@ -640,7 +1127,15 @@ mod tests {
     fn test_render_date_group_data_date() {
         let entries = [make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
         let refs: Vec<&HomeworkEntry> = entries.iter().collect();
-        let html = render_date_group("2025-01-15", &refs, &Default::default()).into_string();
+        let html = render_date_group(
+            "2025-01-15",
+            &refs,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )
+        .into_string();
         assert!(html.contains(r#"data-date="2025-01-15""#));
     }
 
@@ -649,7 +1144,15 @@ mod tests {
         let mut entry = make_entry("studio", "2025-01-15", "Matematica", "Study for: Test");
         entry.parent_id = Some("parent123".to_string());
         let refs: Vec<&HomeworkEntry> = vec![&entry];
-        let html = render_date_group("2025-01-15", &refs, &Default::default()).into_string();
+        let html = render_date_group(
+            "2025-01-15",
+            &refs,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )
+        .into_string();
         assert!(html.contains(r#"data-generated="true""#));
         assert!(html.contains("auto-badge"));
         assert!(html.contains("auto"));
@@ -659,7 +1162,15 @@ mod tests {
     fn test_render_date_group_orphaned_entry() {
         let entry = make_entry("studio", "2025-01-15", "Matematica", "Study for: Test");
         let refs: Vec<&HomeworkEntry> = vec![&entry];
-        let html = render_date_group("2025-01-15", &refs, &Default::default()).into_string();
+        let html = render_date_group(
+            "2025-01-15",
+            &refs,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )
+        .into_string();
         assert!(html.contains(r#"data-orphaned="true""#));
         assert!(html.contains("orphan-badge"));
         assert!(html.contains("orphaned"));
@@ -670,17 +1181,110 @@ mod tests {
         let mut entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
         entry.completed = true;
         let refs: Vec<&HomeworkEntry> = vec![&entry];
-        let html = render_date_group("2025-01-15", &refs, &Default::default()).into_string();
+        let html = render_date_group(
+            "2025-01-15",
+            &refs,
+            &Default::default(),
+            &Default::default(),
+            &Default::default(),
+            None,
+        )
+        .into_string();
         assert!(html.contains("homework-item") && html.contains("completed"));
         assert!(html.contains("checked"));
     }
 
+    #[test]
+    fn test_render_list_pinned_entry_first_with_accent() {
+        let first = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        let mut pinned = make_entry("verifica", "2025-01-15", "Storia", "Task 2");
+        pinned.pinned = true;
+        pinned.color = Some("#33ff99".to_string());
+        let html = render_list(&[first, pinned], None).into_string();
+        assert!(html.find("Storia").unwrap() < html.find("Matematica").unwrap());
+        assert!(html.contains("homework-item pinned accented"));
+        assert!(html.contains(r#"style="--accent: #33ff99""#));
+        assert!(html.contains("pin-badge"));
+    }
+
+    #[test]
+    fn test_render_list_original_date_on_moved_entries() {
+        let mut moved = make_entry("compiti", "2025-01-17", "Storia", "Cap. 3");
+        moved.source_date = Some("2025-01-15".to_string());
+        let mut unmoved = make_entry("compiti", "2025-01-17", "Inglese", "Unit 4");
+        unmoved.source_date = Some("2025-01-17".to_string());
+        let html = render_list(&[moved, unmoved], None).into_string();
+        assert_eq!(html.matches("class=\"original-date\"").count(), 1);
+        assert!(html.contains("originally 2025-01-15"));
+    }
+
+    #[test]
+    fn test_render_list_notice() {
+        let mut posted = make_entry("compiti", "2025-01-20", "Storia", "Cap. 3");
+        posted.assigned_at = Some("2025-01-15 17:59:10".to_string());
+        posted.teacher = Some("ROSSI MARIA".to_string());
+        let mut same_day = make_entry("compiti", "2025-01-21", "Inglese", "Unit 4");
+        same_day.assigned_at = Some("2025-01-21".to_string());
+        let by_hand = make_entry("compiti", "2025-01-22", "Arte", "Colori");
+        let html = render_list(&[posted, same_day, by_hand], None).into_string();
+        assert_eq!(html.matches("class=\"assigned-date\"").count(), 2);
+        assert!(html.contains("assigned 5 days before due"));
+        assert!(html.contains(r#"title="Posted 2025-01-15 17:59:10 by ROSSI MARIA""#));
+        assert!(html.contains("assigned the day it was due"));
+    }
+
+    #[test]
+    fn test_render_list_source_file() {
+        let mut imported = make_entry("compiti", "2025-01-20", "Storia", "Cap. 3");
+        imported.source_file = Some("export_20250110_080000.xls".to_string());
+        let by_hand = make_entry("compiti", "2025-01-22", "Arte", "Colori");
+        let html = render_list(&[imported.clone(), by_hand], None).into_string();
+        assert_eq!(html.matches("class=\"source-file\"").count(), 1);
+        assert!(html.contains(&format!(
+            r#"href="/api/entries/{}/source-file""#,
+            imported.id
+        )));
+        assert!(html.contains("Download export_20250110_080000.xls"));
+    }
+
+    #[test]
+    fn test_render_list_until_on_multi_day_events() {
+        let mut trip = make_entry("nota", "2025-02-05", "Geografia", "Gita a Torino");
+        trip.end_date = Some("2025-02-10".to_string());
+        let mut moved_past_end = make_entry("nota", "2025-02-12", "Arte", "Mostra");
+        moved_past_end.end_date = Some("2025-02-10".to_string());
+        let html = render_list(&[trip, moved_past_end], None).into_string();
+        assert_eq!(html.matches("class=\"until-date\"").count(), 1);
+        assert!(html.contains("(until 2025-02-10)"));
+    }
+
+    #[test]
+    fn test_render_page_marks_happened_types_and_grades() {
+        let mut test = make_entry("verifica", "2025-01-15", "Storia", "Cap. 5");
+        test.completed = true;
+        test.grade = Some("7½".to_string());
+        let html = render_page(&[test], &Settings::default()).into_string();
+        assert!(html.contains(r#"data-happened-types="interrogazione verifica""#));
+        assert!(html.contains(r#"data-ask-grade="true""#));
+        assert!(html.contains(r#"data-type="verifica""#));
+        assert!(html.contains("🎓 7½"));
+
+        let settings = Settings {
+            ask_grade: false,
+            completion: Default::default(),
+            ..Settings::default()
+        };
+        let html = render_page(&[], &settings).into_string();
+        assert!(html.contains(r#"data-happened-types="""#));
+        assert!(!html.contains("data-ask-grade"));
+    }
+
     // ========== CSS/JS content tests ==========
 
     #[test]
     fn test_render_page_css_has_generated_styling() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("[data-generated=\"true\"]"));
         assert!(html.contains("auto-badge"));
     }
@@ -688,7 +1292,7 @@ mod tests {
     #[test]
     fn test_render_page_css_has_orphaned_styling() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("[data-orphaned=\"true\"]"));
         assert!(html.contains("orphan-badge"));
     }
@@ -696,7 +1300,7 @@ mod tests {
     #[test]
     fn test_render_page_css_has_drag_styling() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains(".dragging"));
         assert!(html.contains(".drag-over"));
     }
@@ -704,7 +1308,7 @@ mod tests {
     #[test]
     fn test_render_page_css_has_delete_button_styling() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains(".delete-btn"));
     }
 
@@ -713,7 +1317,7 @@ mod tests {
     #[test]
     fn test_render_page_has_view_toggle() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("view-toggle"));
         assert!(html.contains("list-view-btn"));
         assert!(html.contains("calendar-view-btn"));
@@ -722,7 +1326,7 @@ mod tests {
     #[test]
     fn test_render_page_has_list_view() {
         let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains(r#"id="list-view""#));
         assert!(html.contains(r#"class="list-view""#));
     }
@@ -730,7 +1334,7 @@ mod tests {
     #[test]
     fn test_render_page_has_calendar_view() {
         let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains(r#"id="calendar-view""#));
         assert!(html.contains("calendar-layout"));
         assert!(html.contains("calendar-main"));
@@ -740,14 +1344,14 @@ mod tests {
     #[test]
     fn test_render_page_calendar_view_hidden_by_default() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains(r#"class="calendar-view hidden""#));
     }
 
     #[test]
     fn test_render_page_has_calendar_navigation() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("cal-prev"));
         assert!(html.contains("cal-next"));
         assert!(html.contains("cal-month-year"));
@@ -756,7 +1360,7 @@ mod tests {
     #[test]
     fn test_render_page_has_calendar_day_headers() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("Mon"));
         assert!(html.contains("Tue"));
         assert!(html.contains("Sat"));
@@ -764,21 +1368,17 @@ mod tests {
     }
 
     #[test]
-    fn test_render_page_calendar_contains_entries_data() {
-        let entries = vec![
-            make_entry("compiti", "2025-01-15", "Matematica", "Task 1"),
-            make_entry("nota", "2025-01-16", "Italiano", "Task 2"),
-        ];
-        let html = render_page(&entries).into_string();
-        assert!(html.contains("data-entries="));
-        assert!(html.contains("Matematica"));
-        assert!(html.contains("Italiano"));
+    fn test_render_page_calendar_does_not_embed_entries() {
+        let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
+        let html = render_page(&entries, &Settings::default()).into_string();
+        assert!(!html.contains("data-entries="));
+        assert!(html.contains("/api/calendar"));
     }
 
     #[test]
     fn test_render_page_css_has_view_toggle_styling() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains(".view-toggle"));
         assert!(html.contains(".view-btn"));
         assert!(html.contains(".view-btn.active"));
@@ -787,7 +1387,7 @@ mod tests {
     #[test]
     fn test_render_page_css_has_calendar_styling() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains(".calendar-view"));
         assert!(html.contains(".calendar-main"));
         assert!(html.contains(".cal-day"));
@@ -797,22 +1397,39 @@ mod tests {
     #[test]
     fn test_render_page_javascript_has_view_toggle() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("showListView"));
         assert!(html.contains("showCalendarView"));
-        assert!(html.contains("localStorage"));
+        assert!(html.contains("saveDefaultView"));
     }
 
     #[test]
     fn test_render_page_javascript_has_calendar_rendering() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("renderCalendar"));
         assert!(html.contains("renderCalendarDay"));
         assert!(html.contains("calPrev"));
         assert!(html.contains("calNext"));
     }
 
+    #[test]
+    fn test_render_page_calendar_entries_toggle_completion() {
+        let html = render_page(&[], &Settings::default()).into_string();
+        assert!(html.contains("bindCalendarToggles(calendarDays)"));
+        assert!(html.contains("function toggleCalendarEntry"));
+        assert!(html.contains(".cal-entry-check"));
+    }
+
+    #[test]
+    fn test_render_page_sidebar_edits_entries() {
+        let html = render_page(&[], &Settings::default()).into_string();
+        assert!(html.contains("openAddEntryDialog(dateStr)"));
+        assert!(html.contains("bindDeleteButtons(sidebarContent)"));
+        assert!(html.contains("function openSidebarEdit"));
+        assert!(html.contains("body.read-only .sidebar-entry-actions"));
+    }
+
     // ========== Reverse chronological order ==========
 
     #[test]
@@ -822,7 +1439,7 @@ mod tests {
             make_entry("nota", "2025-01-15", "Italiano", "Task 2"),
             make_entry("compiti", "2025-01-20", "INGLESE", "Task 3"),
         ];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         let pos_10 = html.find("2025-01-10").unwrap();
         let pos_15 = html.find("2025-01-15").unwrap();
         let pos_20 = html.find("2025-01-20").unwrap();
@@ -848,33 +1465,78 @@ mod tests {
     }
 
     #[test]
-    fn test_entries_to_json() {
+    fn test_group_by_date() {
         let entries = [
             make_entry("compiti", "2025-01-15", "Matematica", "Task 1"),
             make_entry("nota", "2025-01-15", "Italiano", "Task 2"),
+            make_entry("compiti", "2025-01-16", "Storia", "Task 3"),
         ];
-        let refs: Vec<&HomeworkEntry> = entries.iter().collect();
-        let mut by_date: BTreeMap<&str, Vec<&HomeworkEntry>> = BTreeMap::new();
-        by_date.insert("2025-01-15", refs);
-        let json = entries_to_json(&by_date);
-        assert!(json.contains("2025-01-15"));
-        assert!(json.contains("Matematica"));
-        assert!(json.contains("Italiano"));
+        let by_date = group_by_date(&entries);
+        assert_eq!(by_date.len(), 2);
+        assert_eq!(by_date["2025-01-15"].len(), 2);
+        assert_eq!(by_date["2025-01-15"][0].subject, "Matematica");
+        assert_eq!(by_date["2025-01-16"][0].entry_type, "compiti");
+    }
+
+    #[test]
+    fn test_group_by_date_pinned_first() {
+        let mut pinned = make_entry("verifica", "2025-01-15", "Storia", "Task 2");
+        pinned.pinned = true;
+        let entries = [
+            make_entry("compiti", "2025-01-15", "Matematica", "Task 1"),
+            pinned,
+        ];
+        let by_date = group_by_date(&entries);
+        assert_eq!(by_date["2025-01-15"][0].subject, "Storia");
+        assert!(by_date["2025-01-15"][0].pinned);
+    }
+
+    #[test]
+    fn test_group_by_date_spans_multi_day_events() {
+        let mut trip = make_entry("nota", "2025-01-30", "Geografia", "Gita");
+        trip.end_date = Some("2025-02-02".to_string());
+        let entries = [
+            trip,
+            make_entry("compiti", "2025-02-01", "Storia", "Cap. 2"),
+        ];
+        let by_date = group_by_date(&entries);
+        let days: Vec<&str> = by_date.keys().map(|d| d.as_str()).collect();
+        assert_eq!(
+            days,
+            vec!["2025-01-30", "2025-01-31", "2025-02-01", "2025-02-02"]
+        );
+        assert!(!by_date["2025-01-30"][0].continued);
+        assert!(by_date["2025-02-02"][0].continued);
+        assert_eq!(by_date["2025-02-01"].len(), 2);
+        assert_eq!(
+            by_date["2025-02-01"][0].end_date.as_deref(),
+            Some("2025-02-02")
+        );
+    }
+
+    #[test]
+    fn test_group_by_date_empty() {
+        assert!(group_by_date(&[]).is_empty());
     }
 
     #[test]
-    fn test_entries_to_json_empty() {
-        let by_date: BTreeMap<&str, Vec<&HomeworkEntry>> = BTreeMap::new();
-        assert_eq!(entries_to_json(&by_date), "{}");
+    fn test_month_bounds() {
+        let (first, last) = month_bounds(2024, 2).unwrap();
+        assert_eq!(first.to_string(), "2024-02-01");
+        assert_eq!(last.to_string(), "2024-02-29");
+
+        let (first, last) = month_bounds(2025, 12).unwrap();
+        assert_eq!(first.to_string(), "2025-12-01");
+        assert_eq!(last.to_string(), "2025-12-31");
+
+        assert!(month_bounds(2025, 0).is_none());
+        assert!(month_bounds(2025, 13).is_none());
     }
 
     #[test]
     fn test_render_calendar_basic() {
         let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
-        let refs: Vec<&HomeworkEntry> = entries.iter().collect();
-        let mut by_date: BTreeMap<&str, Vec<&HomeworkEntry>> = BTreeMap::new();
-        by_date.insert("2025-01-15", refs);
-        let html = render_calendar(&entries, &by_date).into_string();
+        let html = render_calendar(&entries).into_string();
         assert!(html.contains("calendar-layout"));
         assert!(html.contains("calendar-main"));
         assert!(html.contains("calendar-header"));
@@ -886,10 +1548,7 @@ mod tests {
     #[test]
     fn test_render_calendar_month_from_entries() {
         let entries = vec![make_entry("compiti", "2025-03-15", "Matematica", "Task 1")];
-        let refs: Vec<&HomeworkEntry> = entries.iter().collect();
-        let mut by_date: BTreeMap<&str, Vec<&HomeworkEntry>> = BTreeMap::new();
-        by_date.insert("2025-03-15", refs);
-        let html = render_calendar(&entries, &by_date).into_string();
+        let html = render_calendar(&entries).into_string();
         assert!(html.contains("March"));
         assert!(html.contains("2025"));
     }
@@ -899,7 +1558,7 @@ mod tests {
     #[test]
     fn test_render_page_has_header_structure() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains("header"));
         assert!(html.contains("header-left"));
     }
@@ -907,8 +1566,243 @@ mod tests {
     #[test]
     fn test_render_page_css_has_full_width_container() {
         let entries: Vec<HomeworkEntry> = vec![];
-        let html = render_page(&entries).into_string();
+        let html = render_page(&entries, &Settings::default()).into_string();
         assert!(html.contains(".container"));
         assert!(html.contains("width: 100%"));
     }
+
+    #[test]
+    fn test_render_list_matches_page_list() {
+        let entries = vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10"),
+            make_entry("nota", "2025-01-16", "Italiano", "Libro"),
+        ];
+        let list = render_list(&entries, None).into_string();
+        let page = render_page(&entries, &Settings::default()).into_string();
+        assert!(page.contains(&list));
+        assert!(list.contains(r#"data-total="2""#));
+        assert!(list.contains(r#"data-completed="0""#));
+        // Newest date first
+        assert!(list.find("2025-01-16").unwrap() < list.find("2025-01-15").unwrap());
+    }
+
+    #[test]
+    fn test_render_page_has_hidden_plan_panel() {
+        let page = render_page(&[], &Settings::default()).into_string();
+        assert!(page.contains(r#"<section class="plan-panel hidden" id="plan-panel">"#));
+        assert!(page.find(r#"id="plan-panel""#).unwrap() < page.find(r#"id="list-view""#).unwrap());
+    }
+
+    #[test]
+    fn test_render_page_has_hidden_update_banner() {
+        let page = render_page(&[], &Settings::default()).into_string();
+        assert!(page.contains(r#"<div class="update-banner" id="update-banner" hidden>"#));
+    }
+
+    #[test]
+    fn test_render_page_goals_widget() {
+        let page = render_page(&[], &Settings::default()).into_string();
+        assert!(!page.contains(r#"id="goals-widget""#));
+
+        let settings = Settings {
+            weekly_goals: vec![crate::types::WeeklyGoal {
+                subject: "Matematica".to_string(),
+                target: 3,
+            }],
+            ..Settings::default()
+        };
+        let page = render_page(&[], &settings).into_string();
+        assert!(page.contains(r#"id="goals-widget""#));
+        assert!(page.contains(
+            r#"<span class="goal" data-subject="matematica">Matematica <span class="goal-done">0</span>/3</span>"#
+        ));
+    }
+
+    #[test]
+    fn test_render_list_window_has_load_controls() {
+        let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10")];
+        let window = ListWindow {
+            from: "2025-01-01".to_string(),
+            to: "2025-02-12".to_string(),
+            has_older: true,
+            has_newer: false,
+            total: 40,
+            completed: 12,
+            cross_listed: HashMap::from([(entries[0].id.clone(), vec!["Fisica".to_string()])]),
+            off_schedule: HashMap::from([(
+                entries[0].id.clone(),
+                "Matematica has no lesson on Wednesdays (2025-01-15); is the date right?"
+                    .to_string(),
+            )]),
+            hidden: 2,
+            showing_hidden: None,
+        };
+        let list = render_list(&entries, Some(&window)).into_string();
+        assert!(list.contains("🔀 Matematica + Fisica</span>"));
+        assert!(list.contains(
+            r#"title="Matematica has no lesson on Wednesdays (2025-01-15); is the date right?">📅 no lesson that day</span>"#
+        ));
+        assert!(list.contains(r#"data-total="40""#));
+        assert!(list.contains(r#"data-completed="12""#));
+        assert!(list.contains(r#"data-from="2025-01-01" data-to="2025-02-12""#));
+        assert!(list.contains(r#"id="load-older""#));
+        assert!(!list.contains(r#"id="load-newer""#));
+        assert!(list.contains(r#"name="from" value="2025-01-01""#));
+        assert!(list.contains("list-range-all"));
+        assert!(list.contains("🙈 2 hidden until later"));
+        assert!(!list.contains("data-hidden"));
+
+        let page =
+            render_page_window(&entries, Some(&window), &Settings::default(), false).into_string();
+        assert!(page.contains(r#"<span id="total-count">40</span>"#));
+
+        // Shown anyway, a hidden entry says until when
+        let mut later = make_entry("compiti", "2025-03-20", "Italiano", "Relazione sul libro");
+        later.visible_from = Some("2025-03-01".to_string());
+        let window = ListWindow {
+            showing_hidden: Some("2025-01-15".to_string()),
+            ..window
+        };
+        let list = render_list(&[later], Some(&window)).into_string();
+        assert!(list.contains(r#"data-hidden="true""#));
+        assert!(list.contains(r#"data-visible-from="2025-03-01""#));
+        assert!(list.contains("🙈 hidden until 2025-03-01"));
+        assert!(list.contains("🙈 Hide the 2 for later"));
+
+        // The static page has everything and no controls
+        let list = render_list(&entries, None).into_string();
+        assert!(!list.contains("cross-badge"));
+        assert!(!list.contains("schedule-badge"));
+        assert!(!list.contains("data-from"));
+        assert!(!list.contains("load-more"));
+    }
+
+    #[test]
+    fn test_render_page_uses_soft_refresh() {
+        let html = render_page(&[], &Settings::default()).into_string();
+        assert!(html.contains("refreshList"));
+        assert!(!html.contains("location.reload"));
+    }
+
+    // ========== Feedback & stats tests ==========
+
+    #[test]
+    fn test_render_page_has_feedback_dialog() {
+        let entries: Vec<HomeworkEntry> = vec![];
+        let html = render_page(&entries, &Settings::default()).into_string();
+        assert!(html.contains(r#"id="feedback-dialog""#));
+        assert!(html.contains(r#"id="feedback-minutes""#));
+        assert!(html.contains(r#"name="difficulty""#));
+        assert!(html.contains("openFeedbackDialog"));
+        assert!(html.contains(r#"href="/stats""#));
+    }
+
+    #[test]
+    fn test_render_stats_page() {
+        let stats = vec![crate::types::SubjectTimeStats {
+            subject: "Matematica".to_string(),
+            completed: 4,
+            with_feedback: 2,
+            avg_estimated_minutes: Some(20.0),
+            avg_actual_minutes: Some(32.4),
+            avg_difficulty: Some(3.5),
+            estimate_ratio: Some(1.62),
+        }];
+        let topics = vec![crate::types::TopicStats {
+            subject: "Matematica".to_string(),
+            topic: "Geometria".to_string(),
+            entries: 3,
+            tests: 1,
+            completed: 2,
+            minutes: 95,
+        }];
+        let notice = vec![crate::types::NoticeStats {
+            subject: "Matematica".to_string(),
+            teacher: Some("ROSSI MARIA".to_string()),
+            entries: 4,
+            tests: 1,
+            avg_days: 2.4,
+            shortest_days: 1,
+            short_notice: 2,
+        }];
+        let days = crate::types::DaysCompletedReport {
+            month: "2025-01".to_string(),
+            days: vec![crate::types::DayCompleted {
+                date: "2025-01-15".to_string(),
+                completed_at: "2025-01-15 17:40:00".to_string(),
+                entries: 3,
+            }],
+            streak: 2,
+            best_streak: 5,
+        };
+        let html = render_stats_page(&stats, &topics, &notice, &days);
+        assert!(html.contains("January 2025"));
+        assert!(html.contains(r#"title="3 done""#));
+        assert!(html.contains("🔥 2 days in a row · best 5"));
+        assert!(html.contains(r#"href="/stats?month=2024-12#all-done""#));
+        assert!(html.contains("Matematica"));
+        assert!(html.contains("Geometria"));
+        assert!(html.contains("95 min"));
+        assert!(html.contains("20 min"));
+        assert!(html.contains("32 min"));
+        assert!(html.contains("1.6×"));
+        assert!(html.contains("under-estimate"));
+        assert!(html.contains("3.5 / 5"));
+        assert!(html.contains("ROSSI MARIA"));
+        assert!(html.contains("2.4 d"));
+    }
+
+    #[test]
+    fn test_render_teachers_page() {
+        let report = crate::types::TeacherReport {
+            from: "2024-09-01".to_string(),
+            to: "2025-06-30".to_string(),
+            teachers: vec![crate::types::TeacherWorkload {
+                teacher: "ROSSI MARIA".to_string(),
+                subjects: vec!["Fisica".to_string(), "Matematica".to_string()],
+                entries: 6,
+                tests: 3,
+                avg_lead_days: Some(4.5),
+                short_notice: 1,
+                weekdays: [4, 0, 1, 1, 0, 0, 0],
+                test_weekdays: [3, 0, 0, 0, 0, 0, 0],
+                clustered_on: Some("Monday".to_string()),
+            }],
+            unattributed: 2,
+        };
+        let html = render_teachers_page(&report);
+        assert!(html.contains("Teacher workload, 2024-09-01 – 2025-06-30"));
+        assert!(html.contains("Fisica, Matematica"));
+        assert!(html.contains("4.5 d"));
+        assert!(html.contains("ROSSI MARIA: 4 of 6 entries due on a Monday, 3 of them tests"));
+        assert!(html
+            .contains(r#"href="/stats/teachers?period=2024-09-01..2025-06-30&amp;download=true""#));
+        assert!(html.contains("2 more entries"));
+        assert!(html.contains("@media print"));
+        assert!(html.contains(r#"class="short-notice""#));
+        assert!(html.contains(r#"class="clustered" title="3 tests""#));
+
+        let empty = crate::types::TeacherReport {
+            teachers: vec![],
+            unattributed: 0,
+            ..report
+        };
+        assert!(render_teachers_page(&empty).contains("No entries name a teacher"));
+    }
+
+    #[test]
+    fn test_render_stats_page_empty() {
+        let days = crate::types::DaysCompletedReport {
+            month: "2025-02".to_string(),
+            days: vec![],
+            streak: 0,
+            best_streak: 0,
+        };
+        let html = render_stats_page(&[], &[], &[], &days);
+        assert!(html.contains("No streak yet"));
+        assert!(!html.contains("🌟"));
+        assert!(html.contains("Nothing completed yet."));
+        assert!(html.contains("No posting dates yet"));
+        assert!(html.contains("No topics yet."));
+    }
 }
@@ -0,0 +1,196 @@
+//! Register page: the absences and disciplinary notes mirrored from the
+//! portal (see `register`). Read-only, like the tables behind it.
+
+use chrono::NaiveDate;
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+
+use super::assets::CSS;
+use super::settings::SETTINGS_CSS;
+use super::stats::STATS_CSS;
+use crate::types::{AbsencesSnapshot, NotesSnapshot, Settings};
+
+/// Render the register page as a full HTML string.
+pub fn render_register_page(
+    absences: &AbsencesSnapshot,
+    notes: &NotesSnapshot,
+    settings: &Settings,
+) -> String {
+    let count = |kind: &str| {
+        absences
+            .absences
+            .iter()
+            .filter(|a| a.kind.to_lowercase().starts_with(kind))
+            .count()
+    };
+    let unjustified = absences.absences.iter().filter(|a| !a.justified).count();
+
+    let markup: Markup = html! {
+        (DOCTYPE)
+        html lang=(settings.language.code()) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                title { "Compitutto — Register" }
+                style { (PreEscaped(CSS)) (PreEscaped(SETTINGS_CSS)) (PreEscaped(STATS_CSS)) (PreEscaped(REGISTER_CSS)) }
+            }
+            body class=(settings.theme.body_class()) {
+                div.container {
+                    header.header {
+                        div.header-left {
+                            h1 { "Compitutto" }
+                        }
+                        div.header-right {
+                            a.nav-link href="/" { "🏠 Dashboard" }
+                            a.nav-link href="/list" { "📋 All entries" }
+                        }
+                    }
+                    div.settings-page.stats-page {
+                        h2 { "Register" }
+                        section.settings-section #"register-absences" {
+                            h3 { "Absences" }
+                            (fetched_note(absences.fetched.as_deref(), "absences"))
+                            @if !absences.absences.is_empty() {
+                                p.register-summary {
+                                    (count("assenz")) " absences · "
+                                    (count("ritard")) " late entrances · "
+                                    (count("uscit")) " early exits"
+                                    @if unjustified > 0 {
+                                        " · " span.unjustified { (unjustified) " to justify" }
+                                    }
+                                }
+                                table.stats-table {
+                                    thead {
+                                        tr {
+                                            th { "Date" }
+                                            th { "Kind" }
+                                            th { "Justified" }
+                                            th { "Note" }
+                                        }
+                                    }
+                                    tbody {
+                                        @for absence in &absences.absences {
+                                            tr {
+                                                td { (format_date(&absence.date)) }
+                                                td { (absence.kind) }
+                                                @if absence.justified {
+                                                    td { "Yes" }
+                                                } @else {
+                                                    td.unjustified { "No" }
+                                                }
+                                                td { (absence.note) }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        section.settings-section #"register-notes" {
+                            h3 { "Disciplinary notes" }
+                            (fetched_note(notes.fetched.as_deref(), "notes"))
+                            @if !notes.notes.is_empty() {
+                                table.stats-table {
+                                    thead {
+                                        tr {
+                                            th { "Date" }
+                                            th { "Teacher" }
+                                            th { "Kind" }
+                                            th { "Note" }
+                                        }
+                                    }
+                                    tbody {
+                                        @for note in &notes.notes {
+                                            tr {
+                                                td { (format_date(&note.date)) }
+                                                td { (note.teacher) }
+                                                td { (note.kind) }
+                                                td { (note.text) }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+    markup.into_string()
+}
+
+/// When the snapshot was taken, or how to get one
+fn fetched_note(fetched: Option<&str>, kind: &str) -> Markup {
+    html! {
+        p.settings-desc {
+            @match fetched {
+                Some(day) => { "As on the portal on " (format_date(day)) "." }
+                None => {
+                    "Nothing fetched yet. Run "
+                    code { "raschietto fetch --export " (kind) }
+                    " to mirror them here."
+                }
+            }
+        }
+    }
+}
+
+fn format_date(date: &str) -> String {
+    NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map(|d| d.format("%a %-d %b %Y").to_string())
+        .unwrap_or_else(|_| date.to_string())
+}
+
+const REGISTER_CSS: &str = r#"
+.register-summary { margin-bottom: 12px; }
+.register-summary .unjustified, .stats-table td.unjustified { color: #ffaa00; font-weight: 700; }
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{Absence, DisciplineNote};
+
+    #[test]
+    fn test_render_register_page() {
+        let absences = AbsencesSnapshot {
+            fetched: Some("2025-01-20".to_string()),
+            absences: vec![
+                Absence {
+                    date: "2025-01-15".to_string(),
+                    kind: "Ritardo".to_string(),
+                    justified: false,
+                    note: "Entrata alle 09:05".to_string(),
+                },
+                Absence {
+                    date: "2025-01-13".to_string(),
+                    kind: "Assenza".to_string(),
+                    justified: true,
+                    note: String::new(),
+                },
+            ],
+        };
+        let notes = NotesSnapshot {
+            fetched: None,
+            notes: vec![],
+        };
+        let page = render_register_page(&absences, &notes, &Settings::default());
+        assert!(page.contains("As on the portal on Mon 20 Jan 2025."));
+        assert!(page.contains("1 absences · 1 late entrances · 0 early exits"));
+        assert!(page.contains(r#"<span class="unjustified">1 to justify</span>"#));
+        assert!(page.contains("<td>Wed 15 Jan 2025</td>"));
+        assert!(page.contains("raschietto fetch --export notes"));
+
+        let notes = NotesSnapshot {
+            fetched: Some("2025-01-20".to_string()),
+            notes: vec![DisciplineNote {
+                date: "2025-01-14".to_string(),
+                teacher: "ROSSI MARIA".to_string(),
+                kind: "Nota disciplinare".to_string(),
+                text: "Disturba <sempre>".to_string(),
+            }],
+        };
+        let page = render_register_page(&AbsencesSnapshot::default(), &notes, &Settings::default());
+        assert!(page.contains("raschietto fetch --export absences"));
+        assert!(page.contains("Disturba &lt;sempre&gt;"));
+    }
+}
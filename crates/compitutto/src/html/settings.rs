@@ -3,9 +3,27 @@
 use maud::{html, Markup, PreEscaped, DOCTYPE};
 
 use super::assets::CSS;
+use crate::types::{Completion, Language, SessionPosition, Settings, Theme, View};
+use crate::version;
 
 /// Render the settings page as a full HTML string.
-pub fn render_settings_page(work_days: &[u32], days_ahead: u32, study_days: u32) -> String {
+pub fn render_settings_page(settings: &Settings) -> String {
+    let work_days = &settings.work_days;
+    let days_ahead = settings.homework_days_ahead;
+    let study_days = settings.study_days_before;
+    let review_imports = settings.review_imports;
+    let budget = settings.daily_budget_minutes;
+    let day_entries = settings.day_limit_entries;
+    let day_minutes = settings.day_limit_minutes;
+    let days_back = settings.list_days_back;
+    let days_forward = settings.list_days_ahead;
+    // The agenda's own types, plus any other type the setting mentions
+    let mut completion_types: Vec<&str> = vec!["compiti", "nota", "verifica", "interrogazione"];
+    for entry_type in settings.completion.keys() {
+        if !completion_types.contains(&entry_type.as_str()) {
+            completion_types.push(entry_type);
+        }
+    }
     let weekdays: &[(u32, &str)] = &[
         (1u32, "Monday"),
         (2u32, "Tuesday"),
@@ -16,14 +34,14 @@ pub fn render_settings_page(work_days: &[u32], days_ahead: u32, study_days: u32)
 
     let markup: Markup = html! {
         (DOCTYPE)
-        html lang="en" {
+        html lang=(settings.language.code()) {
             head {
                 meta charset="UTF-8";
                 meta name="viewport" content="width=device-width, initial-scale=1.0";
                 title { "Compitutto — Settings" }
                 style { (PreEscaped(CSS)) (PreEscaped(SETTINGS_CSS)) }
             }
-            body {
+            body class=(settings.theme.body_class()) {
                 div.container {
                     header.header {
                         div.header-left {
@@ -36,6 +54,67 @@ pub fn render_settings_page(work_days: &[u32], days_ahead: u32, study_days: u32)
                     div.settings-page {
                         h2 { "Settings" }
 
+                        // ── Display ────────────────────────────────────────
+                        section.settings-section {
+                            h3 { "Display" }
+                            p.settings-desc { "Which view the main page opens in, and how it looks." }
+                            div.radio-group data-setting="default_view" {
+                                @for (val, label) in [(View::List, "List"), (View::Calendar, "Calendar")] {
+                                    @let checked = settings.default_view == val;
+                                    label class={"radio-option" @if checked { " checked" }} {
+                                        input type="radio" name="default_view"
+                                            value=(val.as_str()) checked[checked];
+                                        span { (label) }
+                                    }
+                                }
+                            }
+                            div.radio-group data-setting="theme" {
+                                @for (val, label) in [(Theme::Dark, "Dark"), (Theme::Light, "Light"), (Theme::Contrast, "High contrast")] {
+                                    @let checked = settings.theme == val;
+                                    label class={"radio-option" @if checked { " checked" }} {
+                                        input type="radio" name="theme"
+                                            value=(val.as_str()) checked[checked];
+                                        span { (label) }
+                                    }
+                                }
+                            }
+                            div.radio-group data-setting="language" {
+                                @for (val, label) in [(Language::En, "English"), (Language::It, "Italiano")] {
+                                    @let checked = settings.language == val;
+                                    label class={"radio-option" @if checked { " checked" }} {
+                                        input type="radio" name="language"
+                                            value=(val.code()) checked[checked];
+                                        span { (label) }
+                                    }
+                                }
+                            }
+                            label class={"day-toggle" @if settings.hide_completed { " checked" }} #"hide-completed-toggle" {
+                                input type="checkbox" name="hide_completed" checked[settings.hide_completed];
+                                span { "Hide completed entries" }
+                            }
+                        }
+
+                        // ── List dates ─────────────────────────────────────
+                        section.settings-section {
+                            h3 { "List dates" }
+                            p.settings-desc {
+                                "The list opens on these days around today. Load older/newer, "
+                                "the date pickers and Show all above the list reach the rest."
+                            }
+                            div.stepper-row {
+                                button #"list-back-dec" type="button" { "−" }
+                                span #"list-back-value" data-value=(days_back) { (days_back) }
+                                button #"list-back-inc" type="button" { "+" }
+                                span.stepper-hint { "days back" }
+                            }
+                            div.stepper-row {
+                                button #"list-ahead-dec" type="button" { "−" }
+                                span #"list-ahead-value" data-value=(days_forward) { (days_forward) }
+                                button #"list-ahead-inc" type="button" { "+" }
+                                span.stepper-hint { "days ahead" }
+                            }
+                        }
+
                         // ── Work days ──────────────────────────────────────
                         section.settings-section {
                             h3 { "Work days" }
@@ -97,12 +176,167 @@ pub fn render_settings_page(work_days: &[u32], days_ahead: u32, study_days: u32)
                                 "How many study-session reminders to generate before a test. "
                                 "Minimum is 3."
                             }
+                            label class={"day-toggle" @if settings.study_sessions { " checked" }} #"study-sessions-toggle" {
+                                input type="checkbox" name="study_sessions" checked[settings.study_sessions];
+                                span { "Generate study sessions" }
+                            }
                             div.stepper-row {
                                 button #"study-days-dec" type="button" { "−" }
                                 span #"study-days-value" data-value=(study_days) { (study_days) }
                                 button #"study-days-inc" type="button" { "+" }
                                 span.stepper-hint { "(min 3)" }
                             }
+                            label class={"day-toggle" @if settings.split_study_topics { " checked" }} #"split-topics-toggle" {
+                                input type="checkbox" name="split_study_topics" checked[settings.split_study_topics];
+                                span { "Split chapters and pages across sessions" }
+                            }
+                            p.settings-desc {
+                                "Where a new session goes in its day's list. By priority puts it "
+                                "after the day's compiti and sessions for earlier tests."
+                            }
+                            div.radio-group data-setting="study_session_position" {
+                                @for (val, label) in [(SessionPosition::Top, "Top"), (SessionPosition::Bottom, "Bottom"), (SessionPosition::Priority, "By priority")] {
+                                    @let checked = settings.study_session_position == val;
+                                    label class={"radio-option" @if checked { " checked" }} {
+                                        input type="radio" name="study_session_position"
+                                            value=(val.as_str()) checked[checked];
+                                        span { (label) }
+                                    }
+                                }
+                            }
+                        }
+
+                        // ── Weekly goals ───────────────────────────────────
+                        section.settings-section {
+                            h3 { "Weekly goals" }
+                            p.settings-desc {
+                                "How many compiti or study sessions per subject to complete each "
+                                "week (Monday to Sunday). Progress shows in the header."
+                            }
+                            div #"goals-list" {
+                                @for goal in &settings.weekly_goals {
+                                    (goal_row(&goal.subject, goal.target))
+                                }
+                            }
+                            template #"goal-row-template" { (goal_row("", 3)) }
+                            button.add-goal-btn #"add-goal" type="button" { "+ Add goal" }
+                        }
+
+                        // ── Completion by type ─────────────────────────────
+                        section.settings-section {
+                            h3 { "What ticking off means" }
+                            p.settings-desc {
+                                "Checked types are events: ticking one off means it happened "
+                                "rather than that work is done, it isn't crossed out, and it "
+                                "can take a grade. Unchecked types are work to get done."
+                            }
+                            div.work-days-grid {
+                                @for entry_type in &completion_types {
+                                    @let happened = settings.completion_for(entry_type) == Completion::Happened;
+                                    label class={"day-toggle" @if happened { " checked" }} {
+                                        input
+                                            type="checkbox"
+                                            name="happened_type"
+                                            checked[happened]
+                                            data-type=(entry_type);
+                                        span { (entry_type) }
+                                    }
+                                }
+                            }
+                            label class={"day-toggle" @if settings.ask_grade { " checked" }} #"ask-grade-toggle" {
+                                input type="checkbox" name="ask_grade" checked[settings.ask_grade];
+                                span { "Ask for the grade when one is ticked off" }
+                            }
+                        }
+
+                        // ── Daily time budget ──────────────────────────────
+                        section.settings-section {
+                            h3 { "Daily time budget" }
+                            p.settings-desc {
+                                "How many minutes of work the \"Start here\" plan fills each day. "
+                                "Entries without an estimate count as 30 minutes."
+                            }
+                            div.stepper-row {
+                                button #"budget-dec" type="button" { "−" }
+                                span #"budget-value" data-value=(budget) { (budget) }
+                                button #"budget-inc" type="button" { "+" }
+                                span.stepper-hint { "minutes (15–600)" }
+                            }
+                        }
+
+                        // ── Busy days ──────────────────────────────────────
+                        section.settings-section {
+                            h3 { "Busy days" }
+                            p.settings-desc {
+                                "Adding or moving an entry onto a day that already has a test, "
+                                "or that would go over these limits, asks before keeping it. "
+                                "0 turns a limit off."
+                            }
+                            div.stepper-row {
+                                button #"day-entries-dec" type="button" { "−" }
+                                span #"day-entries-value" data-value=(day_entries) { (day_entries) }
+                                button #"day-entries-inc" type="button" { "+" }
+                                span.stepper-hint { "entries per day" }
+                            }
+                            div.stepper-row {
+                                button #"day-minutes-dec" type="button" { "−" }
+                                span #"day-minutes-value" data-value=(day_minutes) { (day_minutes) }
+                                button #"day-minutes-inc" type="button" { "+" }
+                                span.stepper-hint { "minutes of work per day" }
+                            }
+                        }
+
+                        // ── Updates ────────────────────────────────────────
+                        section.settings-section {
+                            h3 { "Updates" }
+                            p.settings-desc {
+                                "Running version " (version::VERSION) " (" (version::GIT_SHA) "). "
+                                "When on, the server asks GitHub once a day whether a newer "
+                                "release exists and the main page shows a banner if so."
+                            }
+                            label class={"day-toggle" @if settings.check_updates { " checked" }} #"check-updates-toggle" {
+                                input type="checkbox" name="check_updates" checked[settings.check_updates];
+                                span { "Check for updates" }
+                            }
+                        }
+
+                        // ── Import review ──────────────────────────────────
+                        section.settings-section {
+                            h3 { "Review imports" }
+                            p.settings-desc {
+                                "When on, new and changed entries from export files wait on the "
+                                "Imports page until you accept them."
+                            }
+                            label class={"day-toggle" @if review_imports { " checked" }} #"review-imports-toggle" {
+                                input type="checkbox" name="review_imports" checked[review_imports];
+                                span { "Review before importing" }
+                            }
+                        }
+
+                        // ── Companion apps ─────────────────────────────────
+                        section.settings-section {
+                            h3 { "Companion apps" }
+                            p.settings-desc {
+                                "Sites and browser extensions allowed to use the API from a "
+                                "browser, one origin per line (https://app.example.org, "
+                                "chrome-extension://…). * allows any site, without cookies."
+                            }
+                            textarea.origins-input #"cors-origins" rows="3" spellcheck="false"
+                                placeholder="https://app.example.org" {
+                                (settings.cors_origins.join("\n"))
+                            }
+                        }
+
+                        // ── This device ────────────────────────────────────
+                        section.settings-section {
+                            h3 { "Simple view" }
+                            p.settings-desc {
+                                "Today's plan as big cards with large checkboxes, and nothing to "
+                                "drag, edit or delete, for a younger child. It's set per device: "
+                                "open this page on theirs and switch it on there, and the main "
+                                "page opens it from then on. Its \"Grown-ups\" link switches back."
+                            }
+                            a.nav-link href="/simple?device=true" { "Use the simple view on this device" }
                         }
 
                         // ── Save ───────────────────────────────────────────
@@ -133,7 +367,19 @@ pub fn render_settings_page(work_days: &[u32], days_ahead: u32, study_days: u32)
     markup.into_string()
 }
 
-const SETTINGS_CSS: &str = r#"
+/// One editable row of the weekly goals list.
+fn goal_row(subject: &str, target: u32) -> Markup {
+    html! {
+        div.goal-row {
+            input.goal-subject type="text" placeholder="Subject" value=(subject);
+            input.goal-target type="number" min="1" max="50" value=(target);
+            span.goal-unit { "per week" }
+            button.goal-remove type="button" title="Remove goal" { "✕" }
+        }
+    }
+}
+
+pub(super) const SETTINGS_CSS: &str = r#"
 .header-right { display: flex; align-items: center; }
 .nav-link {
     color: #fff;
@@ -152,6 +398,7 @@ const SETTINGS_CSS: &str = r#"
 .settings-desc { color: #aaa; font-size: 0.9em; line-height: 1.6; margin-bottom: 20px; }
 
 .work-days-grid { display: flex; flex-wrap: wrap; gap: 12px; }
+.work-days-grid + .day-toggle { margin-top: 20px; }
 .day-toggle {
     display: flex; align-items: center; gap: 8px;
     padding: 10px 18px;
@@ -168,6 +415,10 @@ const SETTINGS_CSS: &str = r#"
 .always-badge { font-size: 0.65em; text-transform: uppercase; letter-spacing: 0.08em; color: #888; margin-left: 4px; }
 
 .radio-group { display: flex; gap: 12px; flex-wrap: wrap; }
+.radio-group + .radio-group, .radio-group + .day-toggle { margin-top: 16px; }
+.stepper-row + .day-toggle { margin-top: 20px; }
+.stepper-row + .stepper-row { margin-top: 12px; }
+#hide-completed-toggle, #study-sessions-toggle, #split-topics-toggle { display: inline-flex; }
 .radio-option {
     display: flex; align-items: center; gap: 8px;
     padding: 10px 20px;
@@ -210,8 +461,56 @@ const SETTINGS_CSS: &str = r#"
     cursor: pointer; font-size: 0.95em; letter-spacing: 0.05em; text-transform: uppercase;
 }
 #reprocess-btn:hover { opacity: 0.85; }
+
+body.theme-light .nav-link { color: #111; border-color: rgba(0,0,0,0.2); }
+body.theme-light .settings-section { border-color: rgba(0,0,0,0.1); }
+body.theme-light .settings-section h3 { color: #111; }
+body.theme-light .settings-desc { color: #555; }
+body.theme-light .day-toggle,
+body.theme-light .radio-option,
+body.theme-light .stepper-row button { background: #fff; border-color: rgba(0,0,0,0.15); color: #111; }
+.goal-row { display: flex; align-items: center; gap: 10px; margin-bottom: 10px; }
+.goal-row input {
+    padding: 8px 10px;
+    background: rgba(255,255,255,0.04);
+    border: 1px solid rgba(255,255,255,0.15);
+    border-radius: 4px;
+    color: inherit; font: inherit;
+}
+.goal-subject { flex: 1; }
+.origins-input {
+    width: 100%; box-sizing: border-box;
+    padding: 8px 10px;
+    background: rgba(255,255,255,0.04);
+    border: 1px solid rgba(255,255,255,0.15);
+    border-radius: 4px;
+    color: inherit; font: inherit; font-family: monospace;
+}
+.goal-target { width: 70px; }
+.goal-unit { font-size: 0.8em; color: #888; }
+.goal-remove, .add-goal-btn {
+    background: transparent;
+    border: 1px solid rgba(255,255,255,0.15);
+    border-radius: 4px;
+    color: inherit; cursor: pointer;
+    padding: 6px 10px;
+}
+.goal-remove:hover, .add-goal-btn:hover { border-color: #ffaa00; }
+body.theme-light .goal-row input,
+body.theme-light .origins-input,
+body.theme-light .goal-remove,
+body.theme-light .add-goal-btn { background: #fff; border-color: rgba(0,0,0,0.15); }
 #reprocess-btn:disabled { opacity: 0.5; cursor: default; }
 #reprocess-status { font-size: 0.85em; color: #00ffff; }
+body.theme-contrast .settings-section { border-color: #fff; }
+body.theme-contrast .settings-desc,
+body.theme-contrast .stepper-hint,
+body.theme-contrast .goal-unit { color: #fff; }
+body.theme-contrast .day-toggle,
+body.theme-contrast .radio-option,
+body.theme-contrast .stepper-row button { background: #000; border: 2px solid #fff; color: #fff; min-height: 44px; }
+body.theme-contrast .day-toggle.checked,
+body.theme-contrast .radio-option.checked { background: #fff; color: #000; }
 "#;
 
 const SETTINGS_JS: &str = r#"
@@ -224,7 +523,7 @@ document.querySelectorAll('.day-toggle:not(.always-on)').forEach(label => {
 
 document.querySelectorAll('.radio-option').forEach(label => {
     label.addEventListener('click', () => {
-        document.querySelectorAll('.radio-option').forEach(l => l.classList.remove('checked'));
+        label.closest('.radio-group').querySelectorAll('.radio-option').forEach(l => l.classList.remove('checked'));
         label.classList.add('checked');
         label.querySelector('input').checked = true;
     });
@@ -241,6 +540,46 @@ document.getElementById('study-days-inc').addEventListener('click', () => {
     studyDaysEl.dataset.value = v + 1; studyDaysEl.textContent = v + 1;
 });
 
+const budgetEl = document.getElementById('budget-value');
+const BUDGET_STEP = 15, MIN_BUDGET = 15, MAX_BUDGET = 600;
+function stepBudget(delta) {
+    const v = Math.min(MAX_BUDGET, Math.max(MIN_BUDGET, parseInt(budgetEl.dataset.value) + delta));
+    budgetEl.dataset.value = v; budgetEl.textContent = v;
+}
+document.getElementById('budget-dec').addEventListener('click', () => stepBudget(-BUDGET_STEP));
+document.getElementById('budget-inc').addEventListener('click', () => stepBudget(BUDGET_STEP));
+
+const dayEntriesEl = document.getElementById('day-entries-value');
+const dayMinutesEl = document.getElementById('day-minutes-value');
+function stepLimit(el, delta, max) {
+    const v = Math.min(max, Math.max(0, parseInt(el.dataset.value) + delta));
+    el.dataset.value = v; el.textContent = v;
+}
+document.getElementById('day-entries-dec').addEventListener('click', () => stepLimit(dayEntriesEl, -1, 20));
+document.getElementById('day-entries-inc').addEventListener('click', () => stepLimit(dayEntriesEl, 1, 20));
+document.getElementById('day-minutes-dec').addEventListener('click', () => stepLimit(dayMinutesEl, -30, 600));
+document.getElementById('day-minutes-inc').addEventListener('click', () => stepLimit(dayMinutesEl, 30, 600));
+
+const listBackEl = document.getElementById('list-back-value');
+const listAheadEl = document.getElementById('list-ahead-value');
+document.getElementById('list-back-dec').addEventListener('click', () => stepLimit(listBackEl, -7, 365));
+document.getElementById('list-back-inc').addEventListener('click', () => stepLimit(listBackEl, 7, 365));
+document.getElementById('list-ahead-dec').addEventListener('click', () => stepLimit(listAheadEl, -7, 365));
+document.getElementById('list-ahead-inc').addEventListener('click', () => stepLimit(listAheadEl, 7, 365));
+
+const goalsList = document.getElementById('goals-list');
+const goalTemplate = document.getElementById('goal-row-template');
+function bindGoalRow(row) {
+    row.querySelector('.goal-remove').addEventListener('click', () => row.remove());
+}
+goalsList.querySelectorAll('.goal-row').forEach(bindGoalRow);
+document.getElementById('add-goal').addEventListener('click', () => {
+    const row = goalTemplate.content.firstElementChild.cloneNode(true);
+    goalsList.appendChild(row);
+    bindGoalRow(row);
+    row.querySelector('.goal-subject').focus();
+});
+
 const reprocessSection = document.getElementById('reprocess-section');
 
 document.getElementById('reprocess-btn').addEventListener('click', async () => {
@@ -268,6 +607,8 @@ document.getElementById('reprocess-btn').addEventListener('click', async () => {
     }
 });
 
+const corsOriginsEl = document.getElementById('cors-origins');
+
 document.getElementById('save-settings').addEventListener('click', async () => {
     const status = document.getElementById('save-status');
     status.textContent = '';
@@ -282,30 +623,59 @@ document.getElementById('save-settings').addEventListener('click', async () => {
 
     const studyDays = parseInt(studyDaysEl.dataset.value);
 
+    const isOn = id => document.getElementById(id).classList.contains('checked');
+    const choice = name => document.querySelector(`input[name="${name}"]:checked`)?.value;
+
+    const settings = {
+        default_view: choice('default_view'),
+        theme: choice('theme'),
+        language: choice('language'),
+        hide_completed: isOn('hide-completed-toggle'),
+        list_days_back: parseInt(listBackEl.dataset.value),
+        list_days_ahead: parseInt(listAheadEl.dataset.value),
+        study_sessions: isOn('study-sessions-toggle'),
+        study_days_before: studyDays,
+        split_study_topics: isOn('split-topics-toggle'),
+        study_session_position: choice('study_session_position'),
+        homework_days_ahead: daysAhead,
+        work_days: workDays,
+        review_imports: isOn('review-imports-toggle'),
+        weekly_goals: Array.from(goalsList.querySelectorAll('.goal-row'))
+            .map(row => ({
+                subject: row.querySelector('.goal-subject').value.trim(),
+                target: parseInt(row.querySelector('.goal-target').value) || 0,
+            }))
+            .filter(goal => goal.subject && goal.target > 0),
+        daily_budget_minutes: parseInt(budgetEl.dataset.value),
+        day_limit_entries: parseInt(dayEntriesEl.dataset.value),
+        day_limit_minutes: parseInt(dayMinutesEl.dataset.value),
+        check_updates: isOn('check-updates-toggle'),
+        completion: Object.fromEntries(
+            Array.from(document.querySelectorAll('input[name="happened_type"]'))
+                .map(cb => [cb.dataset.type, cb.closest('.day-toggle').classList.contains('checked') ? 'happened' : 'done'])
+        ),
+        ask_grade: isOn('ask-grade-toggle'),
+        cors_origins: corsOriginsEl.value.split('\n').map(o => o.trim()).filter(Boolean),
+    };
+
     try {
-        const results = await Promise.all([
-            fetch('/api/settings/work-days', {
-                method: 'PUT', headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ days: workDays }),
-            }),
-            fetch('/api/settings/homework-days-ahead', {
-                method: 'PUT', headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ value: daysAhead }),
-            }),
-            fetch('/api/settings/study-days-before', {
-                method: 'PUT', headers: { 'Content-Type': 'application/json' },
-                body: JSON.stringify({ value: studyDays }),
-            }),
-        ]);
-
-        if (results.every(r => r.ok)) {
+        const res = await fetch('/api/settings', {
+            method: 'PUT', headers: { 'Content-Type': 'application/json' },
+            body: JSON.stringify(settings),
+        });
+
+        if (res.ok) {
+            const saved = await res.json();
+            document.body.className = `theme-${saved.theme}`;
+            document.documentElement.lang = saved.language;
+            corsOriginsEl.value = saved.cors_origins.join('\n');
             status.textContent = '✓ Saved';
             setTimeout(() => { status.textContent = ''; }, 3000);
             // Reveal the reprocess section now that settings changed
             reprocessSection.style.display = 'block';
             reprocessSection.scrollIntoView({ behavior: 'smooth', block: 'nearest' });
         } else {
-            status.textContent = '✗ Error saving one or more settings';
+            status.textContent = '✗ Error saving settings';
         }
     } catch (e) {
         status.textContent = '✗ Network error';
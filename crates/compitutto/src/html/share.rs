@@ -0,0 +1,126 @@
+//! Standalone read-only page for one shared entry (`/share/{stable_id}`).
+//!
+//! Meant to be opened by someone who isn't using the app, so it carries its
+//! own small stylesheet, no scripts and no links back into the app.
+
+use chrono::NaiveDate;
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+
+use super::text;
+use crate::types::HomeworkEntry;
+
+/// Marker `details::merge_details` puts before each attachment name.
+const ATTACHMENT_PREFIX: &str = "📎 ";
+
+/// Render the share page for `entry`.
+pub fn render_share_page(entry: &HomeworkEntry) -> String {
+    let (task, attachments) = split_attachments(&entry.task);
+    let date = NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d")
+        .map(|d| d.format("%A %-d %B %Y").to_string())
+        .unwrap_or_else(|_| entry.date.clone());
+
+    page(
+        &format!("{} — {}", entry.subject, entry.date),
+        html! {
+            article.share-card {
+                div.share-meta {
+                    span.share-subject { (entry.subject) }
+                    @if !entry.entry_type.is_empty() {
+                        span.share-type { (entry.entry_type) }
+                    }
+                }
+                h1.share-date { (date) }
+                div.share-task { (text::render_task(&task)) }
+                @if !attachments.is_empty() {
+                    h2 { "Attachments" }
+                    ul.share-attachments {
+                        @for name in &attachments { li { (name) } }
+                    }
+                }
+                @if entry.completed {
+                    p.share-done { "✓ Done" }
+                }
+            }
+        },
+    )
+}
+
+/// Page shown for an unknown, mismatched or expired link.
+pub fn render_share_unavailable() -> String {
+    page(
+        "Link unavailable",
+        html! {
+            article.share-card {
+                h1.share-date { "This link is no longer available" }
+                p { "It may have expired. Ask for a new one." }
+            }
+        },
+    )
+}
+
+fn page(title: &str, body: Markup) -> String {
+    html! {
+        (DOCTYPE)
+        html lang="it" {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                meta name="robots" content="noindex, nofollow";
+                meta name="referrer" content="no-referrer";
+                title { (title) }
+                style { (PreEscaped(SHARE_CSS)) }
+            }
+            body { (body) }
+        }
+    }
+    .into_string()
+}
+
+/// Task text without the trailing `📎 name` lines, and those names.
+fn split_attachments(task: &str) -> (String, Vec<String>) {
+    let mut lines = Vec::new();
+    let mut attachments = Vec::new();
+    for line in task.lines() {
+        match line.trim().strip_prefix(ATTACHMENT_PREFIX) {
+            Some(name) => attachments.push(name.trim().to_string()),
+            None => lines.push(line),
+        }
+    }
+    (lines.join("\n").trim_end().to_string(), attachments)
+}
+
+const SHARE_CSS: &str = r#"
+body { margin: 0; padding: 24px 16px; background: #f4f1ea; color: #222; font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif; line-height: 1.5; }
+.share-card { max-width: 560px; margin: 0 auto; background: #fff; border-radius: 12px; padding: 24px; box-shadow: 0 2px 12px rgba(0,0,0,0.08); }
+.share-meta { display: flex; gap: 8px; align-items: center; font-size: 0.85em; text-transform: uppercase; letter-spacing: 0.06em; color: #666; }
+.share-subject { font-weight: 700; color: #222; }
+.share-type { padding: 2px 8px; border-radius: 10px; background: #eee; }
+.share-date { font-size: 1.4em; margin: 8px 0 16px; }
+.share-task { font-size: 1.05em; }
+.share-task a { color: #0a58ca; }
+.share-card h2 { font-size: 0.9em; text-transform: uppercase; letter-spacing: 0.06em; color: #666; margin-top: 24px; }
+.share-attachments { padding-left: 20px; }
+.share-done { color: #2e7d32; font-weight: 700; }
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_render_share_page() {
+        let entry = HomeworkEntry::new(
+            "verifica".to_string(),
+            "2025-01-21".to_string(),
+            "Scienze".to_string(),
+            "Capitolo 2 <tutto>\n📎 schema.pdf\n📎 esercizi.docx".to_string(),
+        );
+        let page = render_share_page(&entry);
+        assert!(page.contains("Tuesday 21 January 2025"));
+        assert!(page.contains("Capitolo 2 &lt;tutto&gt;"));
+        assert!(page.contains("<li>schema.pdf</li><li>esercizi.docx</li>"));
+        assert!(page.contains("noindex"));
+        assert!(!page.contains("<script"));
+        assert!(!page.contains("📎"));
+    }
+}
@@ -0,0 +1,215 @@
+//! Simple view (`/simple`) for a younger child: today's plan as big cards,
+//! one emoji per subject and a giant checkbox each, and nothing else (no
+//! calendar, dragging, editing or deleting).
+//!
+//! It's chosen per device with a cookie (see `server::simple_page_handler`),
+//! so a parent's browser keeps the full interface on the same instance. The
+//! cards are `plan::build_plan`'s items, in its order; ticking one off
+//! PATCHes the entry, and its "Do it" reminder or compiti with it.
+
+use chrono::NaiveDate;
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+
+use super::text;
+use crate::plan::Plan;
+use crate::subjects::SubjectIcons;
+use crate::types::{HomeworkEntry, Settings};
+
+/// Icon for a subject without one
+const DEFAULT_ICON: &str = "📚";
+
+/// Render the simple view of `plan`. `entries` are the ones it was built
+/// from, for the links between compiti and their reminders.
+pub fn render_simple_page(
+    plan: &Plan,
+    entries: &[HomeworkEntry],
+    icons: &SubjectIcons,
+    settings: &Settings,
+    read_only: bool,
+) -> String {
+    let date = NaiveDate::parse_from_str(&plan.date, "%Y-%m-%d")
+        .map(|d| d.format("%A %-d %B").to_string())
+        .unwrap_or_else(|_| plan.date.clone());
+    let items: Vec<_> = plan.items.iter().chain(&plan.later).collect();
+
+    let markup: Markup = html! {
+        (DOCTYPE)
+        html lang=(settings.language.code()) {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                title { "Today" }
+                style { (PreEscaped(SIMPLE_CSS)) }
+            }
+            body {
+                main.simple {
+                    h1.simple-date { (date) }
+                    @if items.is_empty() {
+                        p.simple-all-done { "🎉 Nothing left for today!" }
+                    } @else {
+                        p.simple-all-done hidden { "🎉 All done!" }
+                    }
+                    ul.simple-cards {
+                        @for item in &items {
+                            li.simple-card data-entry-id=(item.id)
+                                data-linked-id=[linked_id(&item.id, entries)] {
+                                label {
+                                    @if !read_only {
+                                        input.simple-check type="checkbox"
+                                            aria-label=(format!("{} done", item.subject));
+                                    }
+                                    span.simple-icon aria-hidden="true" {
+                                        (icons.icon(&item.subject).unwrap_or(DEFAULT_ICON))
+                                    }
+                                    span.simple-text {
+                                        span.simple-subject { (item.subject) }
+                                        span.simple-task { (text::render_task(&item.task)) }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    a.simple-exit href="/simple?device=false" { "Grown-ups: full view" }
+                }
+                @if !read_only {
+                    script { (PreEscaped(SIMPLE_JS)) }
+                }
+            }
+        }
+    };
+    markup.into_string()
+}
+
+/// The entry ticked along with `id`: a "Do it" reminder's compiti, or a
+/// compiti's reminder (the list keeps the two in step the same way)
+fn linked_id<'a>(id: &str, entries: &'a [HomeworkEntry]) -> Option<&'a str> {
+    let entry = entries.iter().find(|e| e.id == id)?;
+    match entry.entry_type.as_str() {
+        "lavoro" => entry.parent_id.as_deref(),
+        "compiti" => entries
+            .iter()
+            .find(|e| e.entry_type == "lavoro" && e.parent_id.as_deref() == Some(id))
+            .map(|e| e.id.as_str()),
+        _ => None,
+    }
+}
+
+const SIMPLE_CSS: &str = r#"
+* { box-sizing: border-box; }
+body { margin: 0; background: #fff8e7; color: #222; font-family: -apple-system, BlinkMacSystemFont, "Segoe UI", Roboto, sans-serif; }
+.simple { max-width: 720px; margin: 0 auto; padding: 28px 16px 48px; }
+.simple-date { font-size: 2.2em; margin: 0 0 24px; text-align: center; }
+.simple-all-done { font-size: 2em; font-weight: 800; text-align: center; color: #2e7d32; margin: 40px 0; }
+.simple-cards { list-style: none; margin: 0; padding: 0; display: flex; flex-direction: column; gap: 18px; }
+.simple-card label { display: flex; align-items: center; gap: 18px; padding: 22px; background: #fff; border: 4px solid #ffd166; border-radius: 24px; cursor: pointer; transition: opacity 0.3s, transform 0.3s; }
+.simple-check { flex: none; width: 64px; height: 64px; margin: 0; accent-color: #2e7d32; cursor: pointer; }
+.simple-icon { flex: none; font-size: 3.2em; line-height: 1; }
+.simple-text { display: flex; flex-direction: column; gap: 6px; min-width: 0; }
+.simple-subject { font-size: 1.5em; font-weight: 800; }
+.simple-task { font-size: 1.25em; line-height: 1.4; overflow-wrap: anywhere; }
+.simple-task a { color: #0a58ca; }
+.simple-card.done label { opacity: 0.45; transform: scale(0.97); border-color: #8bc34a; }
+.simple-card.done .simple-task { text-decoration: line-through; }
+.simple-exit { display: block; margin-top: 56px; text-align: center; font-size: 0.8em; color: #999; }
+"#;
+
+const SIMPLE_JS: &str = r#"
+function updateAllDone() {
+    const open = document.querySelectorAll('.simple-card:not(.done)').length;
+    document.querySelector('.simple-all-done').hidden = open > 0;
+}
+
+async function setCompleted(id, completed) {
+    const response = await fetch(`/api/entries/${id}`, {
+        method: 'PATCH',
+        headers: { 'Content-Type': 'application/json' },
+        body: JSON.stringify({ completed })
+    });
+    if (!response.ok) throw new Error(`HTTP ${response.status}`);
+}
+
+document.querySelectorAll('.simple-check').forEach(checkbox => {
+    checkbox.addEventListener('change', async function() {
+        const card = this.closest('.simple-card');
+        const completed = this.checked;
+        card.classList.toggle('done', completed);
+        updateAllDone();
+        try {
+            await setCompleted(card.dataset.entryId, completed);
+            // Best effort, as in the list
+            if (card.dataset.linkedId) setCompleted(card.dataset.linkedId, completed).catch(() => {});
+        } catch (error) {
+            this.checked = !completed;
+            card.classList.toggle('done', !completed);
+            updateAllDone();
+            console.error('Failed to update completion:', error);
+        }
+    });
+});
+"#;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plan::build_plan;
+
+    fn entry(entry_type: &str, date: &str, subject: &str, task: &str) -> HomeworkEntry {
+        HomeworkEntry::new(
+            entry_type.to_string(),
+            date.to_string(),
+            subject.to_string(),
+            task.to_string(),
+        )
+    }
+
+    #[test]
+    fn test_render_simple_page() {
+        let compiti = entry("compiti", "2025-01-21", "Matematica", "Pag. 12 <es. 3>");
+        let mut reminder = entry("lavoro", "2025-01-20", "Matematica", "Pag. 12 <es. 3>");
+        reminder.parent_id = Some(compiti.id.clone());
+        let other = entry("compiti", "2025-01-21", "Sconosciuta", "Ricerca");
+        let later = entry("compiti", "2025-01-24", "Storia", "Capitolo 4");
+        let reminder_id = reminder.id.clone();
+        let entries = vec![compiti, reminder, other, later];
+        let today = NaiveDate::from_ymd_opt(2025, 1, 20).unwrap();
+        let plan = build_plan(&entries, today, 120);
+
+        let page = render_simple_page(
+            &plan,
+            &entries,
+            &SubjectIcons::default(),
+            &Settings::default(),
+            false,
+        );
+        assert!(page.contains("Monday 20 January"));
+        assert!(page.contains("Pag. 12 &lt;es. 3&gt;"));
+        // The compiti and its reminder are one card, ticked together
+        assert_eq!(page.matches("class=\"simple-card\"").count(), 2);
+        assert!(page.contains(&format!("data-linked-id=\"{}\"", reminder_id)));
+        assert!(page.contains("📐"));
+        assert!(page.contains(DEFAULT_ICON));
+        assert!(!page.contains("Capitolo 4"));
+        assert!(page.contains("simple-check"));
+        assert!(page.contains("<p class=\"simple-all-done\" hidden>"));
+
+        let read_only = render_simple_page(
+            &plan,
+            &entries,
+            &SubjectIcons::default(),
+            &Settings::default(),
+            true,
+        );
+        assert!(!read_only.contains("simple-check"));
+        assert!(!read_only.contains("<script"));
+
+        let done = build_plan(&[], today, 120);
+        let page = render_simple_page(
+            &done,
+            &[],
+            &SubjectIcons::default(),
+            &Settings::default(),
+            false,
+        );
+        assert!(page.contains("Nothing left for today!"));
+    }
+}
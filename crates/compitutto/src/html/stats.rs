@@ -0,0 +1,510 @@
+//! Stats page rendering: the month's "all done" days and streak, estimated
+//! vs actual time per subject, the work per topic, the notice teachers
+//! give, the side-by-side comparison of two periods at `/stats/compare`,
+//! and the printable per-teacher report at `/stats/teachers`.
+
+use chrono::{Datelike, Months, NaiveDate};
+use maud::{html, Markup, PreEscaped, DOCTYPE};
+
+use super::assets::CSS;
+use super::calendar::{month_bounds, month_name};
+use super::settings::SETTINGS_CSS;
+use crate::teachers::WEEKDAYS;
+use crate::types::{
+    DaysCompletedReport, NoticeStats, PeriodCounts, StatsComparison, SubjectTimeStats,
+    TeacherReport, TopicStats,
+};
+
+/// Render the stats page as a full HTML string.
+pub fn render_stats_page(
+    stats: &[SubjectTimeStats],
+    topics: &[TopicStats],
+    notice: &[NoticeStats],
+    days: &DaysCompletedReport,
+) -> String {
+    let markup: Markup = html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                title { "Compitutto — Stats" }
+                style { (PreEscaped(CSS)) (PreEscaped(SETTINGS_CSS)) (PreEscaped(STATS_CSS)) }
+            }
+            body {
+                div.container {
+                    header.header {
+                        div.header-left {
+                            h1 { "Compitutto" }
+                        }
+                        div.header-right {
+                            a.nav-link href="/" { "← Back" }
+                        }
+                    }
+                    div.settings-page.stats-page {
+                        h2 { "Stats" }
+                        (all_done_section(days))
+                        section.settings-section {
+                            h3 { "Estimated vs actual time" }
+                            p.settings-desc {
+                                "After ticking an entry off you can record how long it took "
+                                "and how hard it was. A ratio above 1× means that subject "
+                                "usually takes longer than planned."
+                            }
+                            @if stats.is_empty() {
+                                p.settings-desc { "Nothing completed yet." }
+                            } @else {
+                                table.stats-table {
+                                    thead {
+                                        tr {
+                                            th { "Subject" }
+                                            th { "Done" }
+                                            th { "Rated" }
+                                            th { "Est. avg" }
+                                            th { "Actual avg" }
+                                            th { "Ratio" }
+                                            th { "Difficulty" }
+                                        }
+                                    }
+                                    tbody {
+                                        @for row in stats {
+                                            @let under = row.estimate_ratio.is_some_and(|r| r > 1.2);
+                                            tr {
+                                                td { (row.subject) }
+                                                td { (row.completed) }
+                                                td { (row.with_feedback) }
+                                                td { (format_minutes(row.avg_estimated_minutes)) }
+                                                td { (format_minutes(row.avg_actual_minutes)) }
+                                                td class=[under.then_some("under-estimate")] {
+                                                    @match row.estimate_ratio {
+                                                        Some(r) => { (format!("{:.1}×", r)) }
+                                                        None => { "—" }
+                                                    }
+                                                }
+                                                td {
+                                                    @match row.avg_difficulty {
+                                                        Some(d) => { (format!("{:.1} / 5", d)) }
+                                                        None => { "—" }
+                                                    }
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        section.settings-section {
+                            h3 { "Topics" }
+                            p.settings-desc {
+                                "Tasks starting with a topic, like \"Geometria: esercizi "
+                                "pag. 50\", are counted under it. Time is the recorded "
+                                "time, or the estimate where there is none."
+                            }
+                            @if topics.is_empty() {
+                                p.settings-desc { "No topics yet." }
+                            } @else {
+                                table.stats-table {
+                                    thead {
+                                        tr {
+                                            th { "Subject" }
+                                            th { "Topic" }
+                                            th { "Entries" }
+                                            th { "Tests" }
+                                            th { "Done" }
+                                            th { "Time" }
+                                        }
+                                    }
+                                    tbody {
+                                        @for row in topics {
+                                            tr {
+                                                td { (row.subject) }
+                                                td { (row.topic) }
+                                                td { (row.entries) }
+                                                td { (row.tests) }
+                                                td { (row.completed) }
+                                                td { (format_minutes(Some(row.minutes as f64))) }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        section.settings-section {
+                            h3 { "Notice given" }
+                            p.settings-desc {
+                                "Days between a teacher posting homework or a test on the "
+                                "register and its due date. Short notice counts entries posted "
+                                "a day or less before."
+                            }
+                            @if notice.is_empty() {
+                                p.settings-desc { "No posting dates yet: they come with the next export." }
+                            } @else {
+                                table.stats-table {
+                                    thead {
+                                        tr {
+                                            th { "Subject" }
+                                            th { "Teacher" }
+                                            th { "Entries" }
+                                            th { "Tests" }
+                                            th { "Average" }
+                                            th { "Shortest" }
+                                            th { "Short notice" }
+                                        }
+                                    }
+                                    tbody {
+                                        @for row in notice {
+                                            tr {
+                                                td { (row.subject) }
+                                                td { (row.teacher.as_deref().unwrap_or("—")) }
+                                                td { (row.entries) }
+                                                td { (row.tests) }
+                                                td { (format_days(Some(row.avg_days))) }
+                                                td { (row.shortest_days) " d" }
+                                                td class=[(row.short_notice > 0).then_some("short-notice")] {
+                                                    (row.short_notice)
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        section.settings-section {
+                            h3 { "Teacher workload" }
+                            p.settings-desc {
+                                "Entries, tests, notice and the weekdays things are due on, per "
+                                "teacher, as a page to print for a parent-teacher meeting."
+                            }
+                            a.nav-link href="/stats/teachers" { "🧑‍🏫 Teacher report" }
+                        }
+                        section.settings-section {
+                            h3 { "Compare terms" }
+                            p.settings-desc {
+                                "Entries, tests, completion and lead time per subject, "
+                                "first quadrimestre against the second."
+                            }
+                            a.nav-link href="/stats/compare" { "📊 Compare" }
+                        }
+                    }
+                }
+            }
+        }
+    };
+    markup.into_string()
+}
+
+/// Render the comparison of two periods as a full HTML string.
+pub fn render_compare_page(comparison: &StatsComparison) -> String {
+    let (a, b) = (&comparison.a, &comparison.b);
+    let markup: Markup = html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                title { "Compitutto — Compare" }
+                style { (PreEscaped(CSS)) (PreEscaped(SETTINGS_CSS)) (PreEscaped(STATS_CSS)) }
+            }
+            body {
+                div.container {
+                    header.header {
+                        div.header-left {
+                            h1 { "Compitutto" }
+                        }
+                        div.header-right {
+                            a.nav-link href="/stats" { "← Stats" }
+                        }
+                    }
+                    div.settings-page.stats-page {
+                        h2 { "Compare periods" }
+                        form.compare-form method="get" action="/stats/compare" {
+                            label { "A " input type="text" name="a" value={(a.from) ".." (a.to)}; }
+                            label { "B " input type="text" name="b" value={(b.from) ".." (b.to)}; }
+                            button type="submit" { "Compare" }
+                        }
+                        p.settings-desc {
+                            "Periods are written " code { "YYYY-MM-DD..YYYY-MM-DD" } ". Lead time "
+                            "counts the days between an entry being posted (or showing up, when "
+                            "the export doesn't say) and its due date."
+                        }
+                        table.stats-table.compare-table {
+                            thead {
+                                tr {
+                                    th rowspan="2" { "Subject" }
+                                    th colspan="2" { "Entries" }
+                                    th colspan="2" { "Tests" }
+                                    th colspan="2" { "Done" }
+                                    th colspan="2" { "Lead time" }
+                                }
+                                tr {
+                                    @for _ in 0..4 { th { "A" } th { "B" } }
+                                }
+                            }
+                            tbody {
+                                (compare_row("All subjects", Some(&a.total), Some(&b.total), true))
+                                @for subject in comparison.subjects() {
+                                    (compare_row(subject, a.subject(subject), b.subject(subject), false))
+                                }
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+    markup.into_string()
+}
+
+/// Render the per-teacher workload report as a full HTML string, styled to
+/// print (or save as PDF) on white paper.
+pub fn render_teachers_page(report: &TeacherReport) -> String {
+    let period = format!("{}..{}", report.from, report.to);
+    let markup: Markup = html! {
+        (DOCTYPE)
+        html lang="en" {
+            head {
+                meta charset="UTF-8";
+                meta name="viewport" content="width=device-width, initial-scale=1.0";
+                title { "Compitutto — Teacher workload " (report.from) " – " (report.to) }
+                style {
+                    (PreEscaped(CSS)) (PreEscaped(SETTINGS_CSS)) (PreEscaped(STATS_CSS))
+                    (PreEscaped(TEACHERS_CSS))
+                }
+            }
+            body {
+                div.container {
+                    header.header {
+                        div.header-left {
+                            h1 { "Compitutto" }
+                        }
+                        div.header-right {
+                            a.nav-link href="/stats" { "← Stats" }
+                        }
+                    }
+                    div.settings-page.stats-page {
+                        h2 { "Teacher workload, " (report.from) " – " (report.to) }
+                        div.report-actions {
+                            form.compare-form method="get" action="/stats/teachers" {
+                                label { "Period " input type="text" name="period" value=(period); }
+                                button type="submit" { "Show" }
+                            }
+                            button type="button" onclick="window.print()" { "🖨 Print or save as PDF" }
+                            a.nav-link href={ "/stats/teachers?period=" (period) "&download=true" } {
+                                "⬇ Download"
+                            }
+                        }
+                        p.settings-desc {
+                            "What each teacher posted on the register for this period, by the "
+                            "date they set. Notice is the days between posting and the due "
+                            "date; short notice counts entries posted a day or less before."
+                        }
+                        @if report.teachers.is_empty() {
+                            p.settings-desc {
+                                "No entries name a teacher in this period: teachers come with "
+                                "exports that have an author column."
+                            }
+                        } @else {
+                            table.stats-table.teachers-table {
+                                thead {
+                                    tr {
+                                        th { "Teacher" }
+                                        th { "Subjects" }
+                                        th { "Entries" }
+                                        th { "Tests" }
+                                        th { "Notice" }
+                                        th { "Short notice" }
+                                        @for weekday in WEEKDAYS {
+                                            th title=(weekday) { (&weekday[..3]) }
+                                        }
+                                    }
+                                }
+                                tbody {
+                                    @for row in &report.teachers {
+                                        tr {
+                                            td { (row.teacher) }
+                                            td { (row.subjects.join(", ")) }
+                                            td { (row.entries) }
+                                            td { (row.tests) }
+                                            td { (format_days(row.avg_lead_days)) }
+                                            td class=[(row.short_notice > 0).then_some("short-notice")] {
+                                                (row.short_notice)
+                                            }
+                                            @for (day, weekday) in WEEKDAYS.iter().enumerate() {
+                                                @let clustered = row.clustered_on.as_deref() == Some(*weekday);
+                                                td class=[clustered.then_some("clustered")]
+                                                    title={ (row.test_weekdays[day]) " tests" } {
+                                                    (row.weekdays[day])
+                                                }
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                            @let clustered: Vec<_> = report.teachers.iter()
+                                .filter_map(|t| t.clustered_on.as_deref().map(|day| (t, day)))
+                                .collect();
+                            @if !clustered.is_empty() {
+                                h3 { "Due on the same weekday" }
+                                ul.teachers-notes {
+                                    @for (row, day) in clustered {
+                                        @let index = WEEKDAYS.iter().position(|w| *w == day).unwrap_or_default();
+                                        li {
+                                            (row.teacher) ": " (row.weekdays[index]) " of "
+                                            (row.entries) " entries due on a " (day)
+                                            @if row.test_weekdays[index] > 0 {
+                                                ", " (row.test_weekdays[index]) " of them tests"
+                                            }
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                        @if report.unattributed > 0 {
+                            p.settings-desc {
+                                (report.unattributed) " more entries in this period don't say "
+                                "who posted them and are left out."
+                            }
+                        }
+                    }
+                }
+            }
+        }
+    };
+    markup.into_string()
+}
+
+fn compare_row(
+    label: &str,
+    a: Option<&PeriodCounts>,
+    b: Option<&PeriodCounts>,
+    total: bool,
+) -> Markup {
+    let empty = PeriodCounts::default();
+    let (a, b) = (a.unwrap_or(&empty), b.unwrap_or(&empty));
+    html! {
+        tr.compare-total[total] {
+            td { (label) }
+            td { (a.entries) } td { (b.entries) }
+            td { (a.tests) } td { (b.tests) }
+            td { (format_rate(a.completion_rate)) } td { (format_rate(b.completion_rate)) }
+            td { (format_days(a.avg_lead_days)) } td { (format_days(b.avg_lead_days)) }
+        }
+    }
+}
+
+/// The month's calendar with every all-done day starred, the streak and
+/// links to the months around it
+fn all_done_section(report: &DaysCompletedReport) -> Markup {
+    let first =
+        NaiveDate::parse_from_str(&format!("{}-01", report.month), "%Y-%m-%d").unwrap_or_default();
+    let last = month_bounds(first.year(), first.month()).map_or(first, |(_, last)| last);
+    let month = |date: Option<NaiveDate>| date.map(|d| d.format("%Y-%m").to_string());
+    let previous = month(first.checked_sub_months(Months::new(1)));
+    let next = month(first.checked_add_months(Months::new(1)));
+    let blanks = first.weekday().num_days_from_monday();
+    html! {
+        section.settings-section #"all-done" {
+            h3 { "All done" }
+            p.settings-desc {
+                "A star for every day whose homework and tests were all ticked off. "
+                "Days with nothing due don't break a streak."
+            }
+            p.streak {
+                @if report.streak > 0 {
+                    "🔥 " (report.streak) (if report.streak == 1 { " day" } else { " days" }) " in a row"
+                } @else {
+                    "No streak yet — finish today to start one"
+                }
+                @if report.best_streak > report.streak {
+                    " · best " (report.best_streak)
+                }
+            }
+            div.all-done-nav {
+                @if let Some(previous) = previous {
+                    a.nav-link href={ "/stats?month=" (previous) "#all-done" } { "‹" }
+                }
+                strong { (month_name(first.month())) " " (first.year()) }
+                @if let Some(next) = next {
+                    a.nav-link href={ "/stats?month=" (next) "#all-done" } { "›" }
+                }
+            }
+            div.all-done-calendar {
+                @for weekday in ["Mon", "Tue", "Wed", "Thu", "Fri", "Sat", "Sun"] {
+                    div.all-done-weekday { (weekday) }
+                }
+                @for _ in 0..blanks {
+                    div {}
+                }
+                @for day in first.iter_days().take_while(|d| *d <= last) {
+                    @let date = day.to_string();
+                    @let done = report.days.iter().find(|d| d.date == date);
+                    @if let Some(done) = done {
+                        div.all-done-day.done title={ (done.entries) " done" } {
+                            span { (day.day()) } "🌟"
+                        }
+                    } @else {
+                        div.all-done-day { span { (day.day()) } }
+                    }
+                }
+            }
+        }
+    }
+}
+
+/// Format a completion rate, e.g. `"75%"` or `"—"`.
+fn format_rate(rate: Option<f64>) -> String {
+    match rate {
+        Some(r) => format!("{}%", (r * 100.0).round() as i64),
+        None => "—".to_string(),
+    }
+}
+
+/// Format an average day count, e.g. `"4.5 d"` or `"—"`.
+fn format_days(days: Option<f64>) -> String {
+    match days {
+        Some(d) => format!("{:.1} d", d),
+        None => "—".to_string(),
+    }
+}
+
+/// Format an average minute count for display, e.g. `"25 min"` or `"—"`.
+fn format_minutes(minutes: Option<f64>) -> String {
+    match minutes {
+        Some(m) => format!("{} min", m.round() as i64),
+        None => "—".to_string(),
+    }
+}
+
+pub(super) const STATS_CSS: &str = r#"
+.stats-page { max-width: 900px; }
+.stats-table { width: 100%; border-collapse: collapse; font-size: 0.9em; }
+.stats-table th, .stats-table td { padding: 10px 12px; text-align: left; border-bottom: 1px solid rgba(255,255,255,0.07); }
+.stats-table th { color: #888; font-weight: 700; text-transform: uppercase; font-size: 0.75em; letter-spacing: 0.08em; }
+.stats-table td.under-estimate, .stats-table td.short-notice { color: #ffaa00; font-weight: 700; }
+.compare-form { display: flex; flex-wrap: wrap; gap: 12px; align-items: center; margin-bottom: 12px; }
+.compare-form input { width: 200px; }
+.compare-table th[colspan], .compare-table thead tr:last-child th { text-align: center; }
+.compare-table tr.compare-total td { font-weight: 700; }
+.streak { font-size: 1.1em; font-weight: 700; margin-bottom: 8px; }
+.all-done-nav { display: flex; gap: 12px; align-items: center; margin-bottom: 8px; }
+.all-done-calendar { display: grid; grid-template-columns: repeat(7, 1fr); gap: 4px; max-width: 420px; }
+.all-done-weekday { color: #888; font-size: 0.75em; text-align: center; text-transform: uppercase; }
+.all-done-day { min-height: 44px; padding: 4px; border-radius: 8px; background: rgba(255,255,255,0.03); text-align: center; font-size: 0.85em; color: #888; }
+.all-done-day span { display: block; }
+.all-done-day.done { background: rgba(255,200,0,0.15); color: inherit; font-weight: 700; }
+"#;
+
+const TEACHERS_CSS: &str = r#"
+.report-actions { display: flex; flex-wrap: wrap; gap: 12px; align-items: center; margin-bottom: 12px; }
+.report-actions .compare-form { margin-bottom: 0; }
+.teachers-table th, .teachers-table td { padding: 8px 6px; }
+.teachers-table td.clustered { background: rgba(255,170,0,0.15); font-weight: 700; }
+.teachers-notes { margin: 8px 0 16px 20px; }
+@media print {
+    body { background: #fff; color: #000; }
+    .header-right, .report-actions { display: none; }
+    .stats-table th, .stats-table td { color: #000; border-bottom-color: #ccc; }
+    .stats-table td.short-notice { color: #000; text-decoration: underline; }
+    .teachers-table td.clustered { background: none; color: #000; outline: 2px solid #000; }
+}
+"#;
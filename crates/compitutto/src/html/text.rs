@@ -0,0 +1,212 @@
+//! Markdown-lite rendering for task text.
+//!
+//! Teacher notes are plain text, but long ones carry structure: bullet or
+//! numbered lines, `**bold**` and URLs. This turns just those into HTML:
+//!
+//!   - lines starting with `- `, `* ` or `• ` become a bulleted list,
+//!     `1. ` / `1) ` a numbered one
+//!   - `**text**` becomes bold
+//!   - `http(s)://…` and `www.…` become links opening in a new tab
+//!   - other line breaks are kept; blank lines separate paragraphs
+//!
+//! Everything else is escaped text. No HTML in the task is passed through,
+//! and link targets are always http(s), so the output is safe to embed.
+//! `renderTask()` in assets.rs does the same for the calendar sidebar; keep
+//! the two in step.
+
+use maud::{html, Markup};
+use regex::Regex;
+use std::sync::LazyLock;
+
+static URL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r#"(?i)\b(?:https?://|www\.)[^\s<>"']+"#).unwrap());
+
+/// A run of lines rendered the same way.
+#[derive(Debug, PartialEq)]
+enum Block<'a> {
+    Text(Vec<&'a str>),
+    Bullets(Vec<&'a str>),
+    Numbered(Vec<&'a str>),
+}
+
+/// Render task text as safe HTML.
+pub fn render_task(task: &str) -> Markup {
+    let blocks = parse_blocks(task);
+    // A plain one-paragraph task renders without a wrapper, as before
+    if let [Block::Text(lines)] = blocks.as_slice() {
+        return render_lines(lines);
+    }
+    html! {
+        @for block in &blocks {
+            @match block {
+                Block::Text(lines) => p { (render_lines(lines)) },
+                Block::Bullets(items) => ul { @for item in items { li { (render_inline(item)) } } },
+                Block::Numbered(items) => ol { @for item in items { li { (render_inline(item)) } } },
+            }
+        }
+    }
+}
+
+fn render_lines(lines: &[&str]) -> Markup {
+    html! {
+        @for (i, line) in lines.iter().enumerate() {
+            @if i > 0 { br; }
+            (render_inline(line))
+        }
+    }
+}
+
+fn parse_blocks(task: &str) -> Vec<Block<'_>> {
+    let mut blocks: Vec<Block> = Vec::new();
+    let mut after_blank = false;
+    for line in task.lines().map(str::trim) {
+        if line.is_empty() {
+            after_blank = true;
+            continue;
+        }
+        let last = if after_blank { None } else { blocks.last_mut() };
+        after_blank = false;
+        match (bullet_item(line), numbered_item(line), last) {
+            (Some(item), _, Some(Block::Bullets(items))) => items.push(item),
+            (Some(item), _, _) => blocks.push(Block::Bullets(vec![item])),
+            (_, Some(item), Some(Block::Numbered(items))) => items.push(item),
+            (_, Some(item), _) => blocks.push(Block::Numbered(vec![item])),
+            (None, None, Some(Block::Text(lines))) => lines.push(line),
+            (None, None, _) => blocks.push(Block::Text(vec![line])),
+        }
+    }
+    blocks
+}
+
+fn bullet_item(line: &str) -> Option<&str> {
+    ["- ", "* ", "• "]
+        .iter()
+        .find_map(|marker| line.strip_prefix(marker))
+        .map(str::trim_start)
+}
+
+fn numbered_item(line: &str) -> Option<&str> {
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    if digits == 0 || digits > 3 {
+        return None;
+    }
+    let rest = &line[digits..];
+    rest.strip_prefix(". ")
+        .or_else(|| rest.strip_prefix(") "))
+        .map(str::trim_start)
+}
+
+/// `**bold**` spans, with links inside and outside them.
+fn render_inline(text: &str) -> Markup {
+    let mut parts = Vec::new();
+    let mut rest = text;
+    while let Some(start) = rest.find("**") {
+        let Some(len) = rest[start + 2..].find("**").filter(|&len| len > 0) else {
+            break;
+        };
+        parts.push((false, &rest[..start]));
+        parts.push((true, &rest[start + 2..start + 2 + len]));
+        rest = &rest[start + 2 + len + 2..];
+    }
+    parts.push((false, rest));
+
+    html! {
+        @for (bold, part) in parts {
+            @if bold { strong { (linkify(part)) } } @else { (linkify(part)) }
+        }
+    }
+}
+
+fn linkify(text: &str) -> Markup {
+    let mut pieces = Vec::new();
+    let mut last = 0;
+    for found in URL.find_iter(text) {
+        let url = trim_url(found.as_str());
+        pieces.push((false, &text[last..found.start()]));
+        pieces.push((true, url));
+        last = found.start() + url.len();
+    }
+    pieces.push((false, &text[last..]));
+
+    html! {
+        @for (is_url, piece) in pieces {
+            @if is_url {
+                @let href = if piece.to_ascii_lowercase().starts_with("www.") {
+                    format!("https://{}", piece)
+                } else {
+                    piece.to_string()
+                };
+                a.task-link href=(href) target="_blank" rel="noopener noreferrer" { (piece) }
+            } @else {
+                (piece)
+            }
+        }
+    }
+}
+
+/// Drop sentence punctuation after a URL, and a closing paren it didn't open.
+fn trim_url(url: &str) -> &str {
+    let mut url = url;
+    loop {
+        let trimmed = url.trim_end_matches(['.', ',', ';', ':', '!', '?']);
+        let trimmed = match trimmed.strip_suffix(')') {
+            Some(inner) if trimmed.matches('(').count() < trimmed.matches(')').count() => inner,
+            _ => trimmed,
+        };
+        if trimmed.len() == url.len() {
+            return url;
+        }
+        url = trimmed;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn render(task: &str) -> String {
+        render_task(task).into_string()
+    }
+
+    #[test]
+    fn test_render_task_plain_text_is_escaped() {
+        assert_eq!(render("Pag. 100 es. 1-5"), "Pag. 100 es. 1-5");
+        assert_eq!(
+            render("<script>alert(1)</script> & \"x\""),
+            "&lt;script&gt;alert(1)&lt;/script&gt; &amp; &quot;x&quot;"
+        );
+        assert_eq!(render("Riga uno\nRiga due"), "Riga uno<br>Riga due");
+    }
+
+    #[test]
+    fn test_render_task_lists_and_paragraphs() {
+        assert_eq!(
+            render("Per domani:\n- es. 1\n* es. 2\n\n1. leggere\n2) riassumere"),
+            "<p>Per domani:</p><ul><li>es. 1</li><li>es. 2</li></ul>\
+             <ol><li>leggere</li><li>riassumere</li></ol>"
+        );
+        // "**" at the start of a line is bold, not a bullet; 2025 isn't an item number
+        assert_eq!(
+            render("**Importante** portare il libro"),
+            "<strong>Importante</strong> portare il libro"
+        );
+        assert_eq!(render("2025. Anno nuovo"), "2025. Anno nuovo");
+        assert_eq!(render("**non chiuso"), "**non chiuso");
+    }
+
+    #[test]
+    fn test_render_task_links() {
+        assert_eq!(
+            render("Vedi https://example.com/a?b=1&c=2."),
+            "Vedi <a class=\"task-link\" href=\"https://example.com/a?b=1&amp;c=2\" target=\"_blank\" \
+             rel=\"noopener noreferrer\">https://example.com/a?b=1&amp;c=2</a>."
+        );
+        assert!(render("(su www.treccani.it)")
+            .contains("href=\"https://www.treccani.it\" target=\"_blank\" rel=\"noopener noreferrer\">www.treccani.it</a>)"));
+        assert!(render("https://it.wikipedia.org/wiki/Roma_(città)")
+            .contains(">https://it.wikipedia.org/wiki/Roma_(città)</a>"));
+        // Only http(s) targets: nothing else becomes a link
+        assert!(!render("javascript:alert(1) ftp://x").contains("<a"));
+        assert!(render("**https://example.com**").starts_with("<strong><a "));
+    }
+}
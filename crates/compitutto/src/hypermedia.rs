@@ -0,0 +1,389 @@
+//! Links between API resources, HAL style.
+//!
+//! Entries returned by `/api/entries` and friends carry a `_links` object
+//! (`self`, `children`, `parent` for study sessions and reminders,
+//! `cascade-delete`, `move`, `duplicate`), so a generic client can follow
+//! an entry's relationships without knowing the URL patterns. `GET /api`
+//! lists the collections and the filters they take, as a starting point.
+//!
+//! `_links` is the HAL name; it keeps these apart from the entry links of
+//! `/api/entries/{id}/links` ("depends on", "related").
+
+use serde::{Deserialize, Serialize};
+
+use crate::types::HomeworkEntry;
+
+/// A link to follow; `method` when it isn't a GET
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Link {
+    pub href: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+}
+
+impl Link {
+    fn get(href: String) -> Self {
+        Link { href, method: None }
+    }
+
+    fn with_method(href: String, method: &str) -> Self {
+        Link {
+            href,
+            method: Some(method.to_string()),
+        }
+    }
+}
+
+/// `_links` of an entry
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EntryLinks {
+    #[serde(rename = "self")]
+    pub this: Link,
+    pub children: Link,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub parent: Option<Link>,
+    #[serde(rename = "cascade-delete")]
+    pub cascade_delete: Link,
+    #[serde(rename = "move")]
+    pub move_to: Link,
+    pub duplicate: Link,
+}
+
+impl EntryLinks {
+    pub fn for_entry(entry: &HomeworkEntry) -> Self {
+        let href = entry_href(&entry.id);
+        EntryLinks {
+            children: Link::get(format!("{}/children", href)),
+            parent: entry.parent_id.as_deref().map(|p| Link::get(entry_href(p))),
+            cascade_delete: Link::with_method(format!("{}/cascade", href), "DELETE"),
+            move_to: Link::with_method(format!("{}/move", href), "POST"),
+            duplicate: Link::with_method(format!("{}/duplicate", href), "POST"),
+            this: Link::get(href),
+        }
+    }
+}
+
+/// `/api/entries/{id}`; ids are UUIDs or hex hashes, safe in a path
+fn entry_href(id: &str) -> String {
+    format!("/api/entries/{}", id)
+}
+
+/// An entry response (`HomeworkEntry`, or `CheckedEntry` with its warning)
+/// plus the entry's `_links`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Linked<T> {
+    #[serde(flatten)]
+    pub entry: T,
+    #[serde(rename = "_links")]
+    pub links: EntryLinks,
+}
+
+impl Linked<HomeworkEntry> {
+    pub fn new(entry: HomeworkEntry) -> Self {
+        let links = EntryLinks::for_entry(&entry);
+        Linked { entry, links }
+    }
+
+    pub fn all(entries: Vec<HomeworkEntry>) -> Vec<Self> {
+        entries.into_iter().map(Linked::new).collect()
+    }
+}
+
+/// One collection in `GET /api`
+#[derive(Debug, Clone, Serialize)]
+pub struct Collection {
+    pub name: &'static str,
+    pub href: &'static str,
+    pub methods: &'static [&'static str],
+    /// Query parameters it understands
+    pub filters: &'static [&'static str],
+    pub description: &'static str,
+}
+
+/// Filters of `/api/entries` and the exports (`EntriesQuery`)
+const ENTRY_FILTERS: &[&str] = &["from", "to", "subject", "type", "topic", "q", "status"];
+
+/// What `GET /api` lists. Single resources and actions are reached through
+/// entries' `_links`.
+pub const COLLECTIONS: &[Collection] = &[
+    Collection {
+        name: "entries",
+        href: "/api/entries",
+        methods: &["GET", "POST"],
+        filters: ENTRY_FILTERS,
+        description: "Homework entries; subject, type and topic take comma-separated lists",
+    },
+    Collection {
+        name: "export-csv",
+        href: "/api/export.csv",
+        methods: &["GET"],
+        filters: ENTRY_FILTERS,
+        description: "Entries as a CSV download",
+    },
+    Collection {
+        name: "export-ics",
+        href: "/api/export.ics",
+        methods: &["GET"],
+        filters: ENTRY_FILTERS,
+        description: "Entries as an iCalendar feed",
+    },
+    Collection {
+        name: "calendar",
+        href: "/api/calendar",
+        methods: &["GET"],
+        filters: &["year", "month"],
+        description: "One month of entries grouped by date, with holidays and activities",
+    },
+    Collection {
+        name: "subjects",
+        href: "/api/subjects",
+        methods: &["GET"],
+        filters: &[],
+        description: "Subjects in use and likely duplicates",
+    },
+    Collection {
+        name: "subject-icons",
+        href: "/api/subjects/icons",
+        methods: &["GET"],
+        filters: &[],
+        description: "Icons of the subjects in use; PUT /api/subjects/{subject}/icon sets one",
+    },
+    Collection {
+        name: "resources",
+        href: "/api/resources",
+        methods: &["GET"],
+        filters: &[],
+        description: "Every subject's study links",
+    },
+    Collection {
+        name: "attachments",
+        href: "/api/attachments",
+        methods: &["GET"],
+        filters: &[],
+        description: "Photo attachments, newest first",
+    },
+    Collection {
+        name: "source-files",
+        href: "/api/source-files",
+        methods: &["GET"],
+        filters: &[],
+        description:
+            "Export files entries were imported from; /api/source-files/{name} downloads one",
+    },
+    Collection {
+        name: "capture",
+        href: "/api/capture",
+        methods: &["POST"],
+        filters: &["date", "subject", "type"],
+        description: "A photo of the paper diary, as the body, staged as a draft to review",
+    },
+    Collection {
+        name: "links",
+        href: "/api/links",
+        methods: &["GET"],
+        filters: &[],
+        description: "Links between entries",
+    },
+    Collection {
+        name: "reactions",
+        href: "/api/reactions",
+        methods: &["GET"],
+        filters: &[],
+        description: "Reactions to completed entries",
+    },
+    Collection {
+        name: "holidays",
+        href: "/api/holidays",
+        methods: &["GET"],
+        filters: &[],
+        description: "School holidays",
+    },
+    Collection {
+        name: "activities",
+        href: "/api/activities",
+        methods: &["GET"],
+        filters: &["from", "to"],
+        description: "After-school activities",
+    },
+    Collection {
+        name: "plan",
+        href: "/api/plan/today",
+        methods: &["GET"],
+        filters: &[],
+        description: "Where to start today, within the daily budget",
+    },
+    Collection {
+        name: "forecast",
+        href: "/api/forecast",
+        methods: &["GET"],
+        filters: &["weeks"],
+        description: "Workload per week",
+    },
+    Collection {
+        name: "imports",
+        href: "/api/imports",
+        methods: &["GET"],
+        filters: &[],
+        description: "Imports waiting for review",
+    },
+    Collection {
+        name: "absences",
+        href: "/api/absences",
+        methods: &["GET"],
+        filters: &[],
+        description: "Absences from the register",
+    },
+    Collection {
+        name: "notes",
+        href: "/api/notes",
+        methods: &["GET"],
+        filters: &[],
+        description: "Disciplinary and other notes from the register",
+    },
+    Collection {
+        name: "stats-time",
+        href: "/api/stats/time",
+        methods: &["GET"],
+        filters: &[],
+        description: "Time and difficulty per subject",
+    },
+    Collection {
+        name: "stats-topics",
+        href: "/api/stats/topics",
+        methods: &["GET"],
+        filters: &[],
+        description: "Entries per subject and topic",
+    },
+    Collection {
+        name: "stats-notice",
+        href: "/api/stats/notice",
+        methods: &["GET"],
+        filters: &[],
+        description: "Days of notice per subject and teacher",
+    },
+    Collection {
+        name: "stats-days-completed",
+        href: "/api/stats/days-completed",
+        methods: &["GET"],
+        filters: &["month"],
+        description: "Days with every entry done in a month, and the streak",
+    },
+    Collection {
+        name: "stats-goals",
+        href: "/api/stats/goals",
+        methods: &["GET"],
+        filters: &["week"],
+        description: "Weekly goal attainment",
+    },
+    Collection {
+        name: "stats-compare",
+        href: "/api/stats/compare",
+        methods: &["GET"],
+        filters: &["a", "b"],
+        description: "Two periods side by side",
+    },
+    Collection {
+        name: "stats-teachers",
+        href: "/api/stats/teachers",
+        methods: &["GET"],
+        filters: &["period"],
+        description: "Entries, tests, notice and due weekdays per teacher",
+    },
+    Collection {
+        name: "jobs",
+        href: "/api/jobs",
+        methods: &["GET"],
+        filters: &[],
+        description: "Background jobs and their last runs",
+    },
+    Collection {
+        name: "settings",
+        href: "/api/settings",
+        methods: &["GET", "PUT"],
+        filters: &[],
+        description: "All settings; PUT takes any subset",
+    },
+    Collection {
+        name: "features",
+        href: "/api/features",
+        methods: &["GET", "PUT"],
+        filters: &[],
+        description: "Feature flags; PUT takes {name: bool} for any of them",
+    },
+    Collection {
+        name: "version",
+        href: "/api/version",
+        methods: &["GET"],
+        filters: &[],
+        description: "Build info and the latest release",
+    },
+];
+
+/// Response of `GET /api`
+#[derive(Debug, Clone, Serialize)]
+pub struct ApiIndex {
+    #[serde(rename = "_links")]
+    pub links: IndexLinks,
+    pub collections: &'static [Collection],
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct IndexLinks {
+    #[serde(rename = "self")]
+    pub this: Link,
+    /// `/api/entries/{id}`, for filling in an id
+    pub entry: TemplatedLink,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct TemplatedLink {
+    pub href: &'static str,
+    pub templated: bool,
+}
+
+pub fn api_index() -> ApiIndex {
+    ApiIndex {
+        links: IndexLinks {
+            this: Link::get("/api".to_string()),
+            entry: TemplatedLink {
+                href: "/api/entries/{id}",
+                templated: true,
+            },
+        },
+        collections: COLLECTIONS,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_entry_links() {
+        let test = HomeworkEntry::new(
+            "verifica".to_string(),
+            "2025-01-20".to_string(),
+            "Matematica".to_string(),
+            "Verifica sui limiti".to_string(),
+        );
+        let mut session = HomeworkEntry::new(
+            "studio".to_string(),
+            "2025-01-18".to_string(),
+            "Matematica".to_string(),
+            "Ripasso".to_string(),
+        );
+        session.parent_id = Some(test.id.clone());
+
+        let json = serde_json::to_value(Linked::new(test.clone())).unwrap();
+        assert_eq!(json["id"], test.id.as_str());
+        let links = &json["_links"];
+        let href = format!("/api/entries/{}", test.id);
+        assert_eq!(links["self"]["href"], href.as_str());
+        assert_eq!(links["children"]["href"], format!("{}/children", href));
+        assert_eq!(links["cascade-delete"]["method"], "DELETE");
+        assert!(links.get("parent").is_none());
+
+        let links = EntryLinks::for_entry(&session);
+        assert_eq!(links.parent, Some(Link::get(href)));
+    }
+}
@@ -0,0 +1,412 @@
+//! Periodic background work inside the server (purges, maintenance, backups,
+//! OCR of photo attachments, and later reminders and digests).
+//!
+//! Every job is listed in [`JOBS`] with a cron schedule in server local time.
+//! `start` gives each one a tokio task that sleeps until the next matching
+//! minute, runs it on a blocking thread (under the connection lock, unless
+//! it's a `Task::Shared` job that takes the lock itself) and records the run
+//! in `job_runs`. A job whose run was missed while the server was down runs once
+//! at startup. `/api/jobs` reports schedules and last runs, and
+//! `POST /api/jobs/{name}/run` runs one straight away.
+//!
+//! Schedules use the five cron fields (`minute hour day-of-month month
+//! day-of-week`) with `*`, lists, ranges and `*/step`, or `@hourly`,
+//! `@daily`, `@weekly`, `@monthly`. As in cron, when both day fields are
+//! restricted a day matching either one counts.
+
+use anyhow::{anyhow, bail, Context, Result};
+use chrono::{
+    DateTime, Datelike, Duration, Local, NaiveDate, NaiveDateTime, TimeZone, Timelike, Utc,
+};
+use rusqlite::Connection;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, error, info, warn};
+
+use crate::backup;
+use crate::db;
+use crate::ocr;
+use crate::server::AppState;
+use crate::types::{JobRun, JobStatus};
+
+/// Longest single sleep, so a suspended laptop or a DST change is noticed
+/// within a minute
+const MAX_SLEEP_SECS: u64 = 60;
+
+/// A periodic job. `run` gets the database and returns a one-line summary.
+pub struct Job {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub schedule: &'static str,
+    pub run: Task,
+}
+
+/// How a job gets the database
+pub enum Task {
+    /// Holds the connection lock for the whole run
+    Db(fn(&Connection) -> Result<String>),
+    /// Takes the lock only while it reads or writes, for jobs that spend
+    /// most of their time elsewhere (running OCR)
+    Shared(fn(&Mutex<Connection>) -> Result<String>),
+}
+
+/// Every background job. Add new ones here.
+pub const JOBS: &[Job] = &[
+    Job {
+        name: "purge_share_links",
+        description: "Delete share links that have expired",
+        schedule: "@daily",
+        run: Task::Db(purge_share_links),
+    },
+    Job {
+        name: "ocr_attachments",
+        description: "Read the text in photo attachments for search",
+        schedule: "*/10 * * * *",
+        run: Task::Shared(ocr::process_pending),
+    },
+    Job {
+        name: "optimize_db",
+        description: "Refresh SQLite's query planner statistics",
+        schedule: "30 3 * * 0",
+        run: Task::Db(optimize_db),
+    },
+    Job {
+        name: "compact_positions",
+        description: "Renumber each day's entry positions without gaps, keeping their order",
+        schedule: "45 3 * * 0",
+        run: Task::Db(compact_positions),
+    },
+    Job {
+        name: "backup_db",
+        description: "Back up the database to data/backups and rotate old backups",
+        schedule: "15 2 * * *",
+        run: Task::Db(backup::run_job),
+    },
+];
+
+pub fn find(name: &str) -> Option<&'static Job> {
+    JOBS.iter().find(|job| job.name == name)
+}
+
+// ========== Jobs ==========
+
+fn purge_share_links(conn: &Connection) -> Result<String> {
+    let deleted = db::delete_expired_share_links(conn, Utc::now())?;
+    Ok(format!("Deleted {} expired share links", deleted))
+}
+
+fn optimize_db(conn: &Connection) -> Result<String> {
+    db::optimize(conn)?;
+    Ok("Optimized".to_string())
+}
+
+fn compact_positions(conn: &Connection) -> Result<String> {
+    let compacted = db::compact_positions(conn)?;
+    Ok(format!(
+        "Renumbered {} entries on {} days",
+        compacted.entries, compacted.days
+    ))
+}
+
+// ========== Runner ==========
+
+/// Spawn a task per job. Jobs with an invalid schedule are logged and skipped.
+pub fn start(state: Arc<AppState>) {
+    for job in JOBS {
+        let schedule = match Schedule::parse(job.schedule) {
+            Ok(schedule) => schedule,
+            Err(e) => {
+                error!(job = job.name, error = %e, "Invalid job schedule; job disabled");
+                continue;
+            }
+        };
+        let state = state.clone();
+        tokio::spawn(async move {
+            if missed_run(&state, job, &schedule) {
+                info!(job = job.name, "Catching up on a missed run");
+                run(state.clone(), job).await;
+            }
+            loop {
+                let now = Local::now().naive_local();
+                let Some(next) = schedule.next_after(now) else {
+                    warn!(job = job.name, "Schedule never matches; job stopped");
+                    return;
+                };
+                debug!(job = job.name, next = %next, "Next run");
+                // Sleep in short steps so wall-clock jumps are noticed
+                while Local::now().naive_local() < next {
+                    let wait = (next - Local::now().naive_local())
+                        .to_std()
+                        .unwrap_or_default()
+                        .min(std::time::Duration::from_secs(MAX_SLEEP_SECS));
+                    tokio::time::sleep(wait).await;
+                }
+                run(state.clone(), job).await;
+            }
+        });
+    }
+}
+
+/// Whether a scheduled run should have happened since the last recorded one.
+/// A job that never ran counts as missed, so new jobs run once right away.
+fn missed_run(state: &AppState, job: &Job, schedule: &Schedule) -> bool {
+    let last = {
+        let conn = state.conn.lock().unwrap();
+        db::get_job_runs(&conn)
+            .unwrap_or_default()
+            .into_iter()
+            .find(|run| run.name == job.name)
+    };
+    let Some(last) = last else {
+        return true;
+    };
+    let Ok(started) = DateTime::parse_from_rfc3339(&last.started_at) else {
+        return true;
+    };
+    let started = started.with_timezone(&Local).naive_local();
+    schedule
+        .next_after(started)
+        .is_some_and(|due| due <= Local::now().naive_local())
+}
+
+/// Run `job` now and record the run
+pub async fn run(state: Arc<AppState>, job: &'static Job) -> JobRun {
+    let started_at = Utc::now().to_rfc3339();
+    let shared = state.clone();
+    let result = tokio::task::spawn_blocking(move || match job.run {
+        Task::Db(run) => run(&shared.conn.lock().unwrap()),
+        Task::Shared(run) => run(&shared.conn),
+    })
+    .await
+    .unwrap_or_else(|e| Err(anyhow!("Job panicked: {}", e)));
+    let conn = state.conn.lock().unwrap();
+    let mut run = JobRun {
+        name: job.name.to_string(),
+        started_at,
+        finished_at: Utc::now().to_rfc3339(),
+        ok: result.is_ok(),
+        message: match &result {
+            Ok(summary) => summary.clone(),
+            Err(e) => format!("{:#}", e),
+        },
+        runs: 1,
+    };
+    match &result {
+        Ok(summary) => info!(job = job.name, summary = %summary, "Job finished"),
+        Err(e) => error!(job = job.name, error = %e, "Job failed"),
+    }
+    if let Err(e) = db::record_job_run(&conn, &run) {
+        warn!(job = job.name, error = %e, "Failed to record job run");
+    }
+    if let Some(stored) = db::get_job_runs(&conn)
+        .unwrap_or_default()
+        .into_iter()
+        .find(|r| r.name == job.name)
+    {
+        run.runs = stored.runs;
+    }
+    run
+}
+
+/// Every job with its schedule, next run and last run
+pub fn statuses(conn: &Connection, now: NaiveDateTime) -> Result<Vec<JobStatus>> {
+    let runs = db::get_job_runs(conn)?;
+    Ok(JOBS
+        .iter()
+        .map(|job| JobStatus {
+            name: job.name.to_string(),
+            description: job.description.to_string(),
+            schedule: job.schedule.to_string(),
+            next_run: Schedule::parse(job.schedule)
+                .ok()
+                .and_then(|s| s.next_after(now))
+                .and_then(|next| Local.from_local_datetime(&next).earliest())
+                .map(|next| next.to_rfc3339()),
+            last_run: runs.iter().find(|run| run.name == job.name).cloned(),
+        })
+        .collect())
+}
+
+// ========== Schedules ==========
+
+/// A parsed cron expression; each field is a bitmask of allowed values
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Schedule {
+    minutes: u64,
+    hours: u64,
+    days: u64,
+    months: u64,
+    weekdays: u64,
+    /// Day-of-month / day-of-week weren't `*`
+    days_restricted: bool,
+    weekdays_restricted: bool,
+}
+
+/// Give up looking for a match after this many years (e.g. "0 0 31 2 *")
+const SEARCH_YEARS: i32 = 5;
+
+impl Schedule {
+    pub fn parse(expr: &str) -> Result<Self> {
+        let expr = match expr.trim() {
+            "@hourly" => "0 * * * *",
+            "@daily" | "@midnight" => "0 0 * * *",
+            "@weekly" => "0 0 * * 0",
+            "@monthly" => "0 0 1 * *",
+            other => other,
+        };
+        let fields: Vec<&str> = expr.split_whitespace().collect();
+        let [minute, hour, day, month, weekday] = fields[..] else {
+            bail!(
+                "Expected 5 fields (minute hour day month weekday): {}",
+                expr
+            );
+        };
+        // Sunday is 0 or 7
+        let mut weekdays = parse_field(weekday, 0, 7).context("day of week")?;
+        if weekdays & (1 << 7) != 0 {
+            weekdays |= 1;
+        }
+        Ok(Self {
+            minutes: parse_field(minute, 0, 59).context("minute")?,
+            hours: parse_field(hour, 0, 23).context("hour")?,
+            days: parse_field(day, 1, 31).context("day of month")?,
+            months: parse_field(month, 1, 12).context("month")?,
+            weekdays,
+            days_restricted: day != "*",
+            weekdays_restricted: weekday != "*",
+        })
+    }
+
+    fn matches_day(&self, date: NaiveDate) -> bool {
+        let day = self.days & (1 << date.day()) != 0;
+        let weekday = self.weekdays & (1 << date.weekday().num_days_from_sunday()) != 0;
+        match (self.days_restricted, self.weekdays_restricted) {
+            (true, true) => day || weekday,
+            _ => day && weekday,
+        }
+    }
+
+    /// The first matching minute strictly after `after`
+    pub fn next_after(&self, after: NaiveDateTime) -> Option<NaiveDateTime> {
+        let mut t = after.with_second(0)?.with_nanosecond(0)? + Duration::minutes(1);
+        let limit = after.year() + SEARCH_YEARS;
+        while t.year() <= limit {
+            if self.months & (1 << t.month()) == 0 {
+                let (year, month) = if t.month() == 12 {
+                    (t.year() + 1, 1)
+                } else {
+                    (t.year(), t.month() + 1)
+                };
+                t = NaiveDate::from_ymd_opt(year, month, 1)?.and_hms_opt(0, 0, 0)?;
+            } else if !self.matches_day(t.date()) {
+                t = t.date().succ_opt()?.and_hms_opt(0, 0, 0)?;
+            } else if self.hours & (1 << t.hour()) == 0 {
+                t = t.with_minute(0)? + Duration::hours(1);
+            } else if self.minutes & (1 << t.minute()) == 0 {
+                t += Duration::minutes(1);
+            } else {
+                return Some(t);
+            }
+        }
+        None
+    }
+}
+
+/// One cron field as a bitmask over `min..=max`
+fn parse_field(field: &str, min: u32, max: u32) -> Result<u64> {
+    let mut mask = 0u64;
+    for part in field.split(',') {
+        let (range, step) = match part.split_once('/') {
+            Some((range, step)) => (range, step.parse::<u32>().context("bad step")?),
+            None => (part, 1),
+        };
+        if step == 0 {
+            bail!("step can't be 0: {}", part);
+        }
+        let (start, end) = if range == "*" {
+            (min, max)
+        } else if let Some((start, end)) = range.split_once('-') {
+            (start.parse()?, end.parse()?)
+        } else {
+            let value: u32 = range
+                .parse()
+                .with_context(|| format!("bad value: {}", part))?;
+            // "5/15" means from 5 to the end in steps of 15
+            (value, if part.contains('/') { max } else { value })
+        };
+        if start < min || end > max || start > end {
+            bail!("{} is outside {}-{}", part, min, max);
+        }
+        for value in (start..=end).step_by(step as usize) {
+            mask |= 1 << value;
+        }
+    }
+    Ok(mask)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn at(s: &str) -> NaiveDateTime {
+        NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M").unwrap()
+    }
+
+    fn next(expr: &str, after: &str) -> Option<String> {
+        Schedule::parse(expr)
+            .unwrap()
+            .next_after(at(after))
+            .map(|t| t.format("%Y-%m-%d %H:%M").to_string())
+    }
+
+    #[test]
+    fn test_schedule_next_after() {
+        // 2025-01-15 is a Wednesday
+        assert_eq!(
+            next("@daily", "2025-01-15 10:30").unwrap(),
+            "2025-01-16 00:00"
+        );
+        assert_eq!(
+            next("*/15 * * * *", "2025-01-15 10:30").unwrap(),
+            "2025-01-15 10:45"
+        );
+        assert_eq!(
+            next("30 3 * * 0", "2025-01-15 10:30").unwrap(),
+            "2025-01-19 03:30"
+        );
+        assert_eq!(
+            next("0 8 * * 1-5", "2025-01-17 09:00").unwrap(),
+            "2025-01-20 08:00"
+        );
+        assert_eq!(
+            next("0 0 1 * *", "2025-12-05 00:00").unwrap(),
+            "2026-01-01 00:00"
+        );
+        // Both day fields restricted: either matches (the 20th, or a Friday)
+        assert_eq!(
+            next("0 9 20 * 5", "2025-01-15 10:00").unwrap(),
+            "2025-01-17 09:00"
+        );
+        // Sunday as 7, and a strictly-later match
+        assert_eq!(
+            next("0 0 * * 7", "2025-01-19 00:00").unwrap(),
+            "2025-01-26 00:00"
+        );
+        assert_eq!(next("0 0 31 2 *", "2025-01-01 00:00"), None);
+    }
+
+    #[test]
+    fn test_schedule_parse_errors() {
+        for bad in [
+            "* * * *",
+            "60 * * * *",
+            "* 24 * * *",
+            "*/0 * * * *",
+            "a * * * *",
+        ] {
+            assert!(Schedule::parse(bad).is_err(), "{bad}");
+        }
+        for job in JOBS {
+            assert!(Schedule::parse(job.schedule).is_ok(), "{}", job.name);
+        }
+    }
+}
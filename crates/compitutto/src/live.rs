@@ -0,0 +1,202 @@
+//! Live updates between open pages, over server-sent events (`/api/live`).
+//!
+//! Every open list page keeps an `EventSource` on `/api/live`, identified by
+//! a per-tab session id and a display name the browser remembers. The server
+//! pushes two events to everyone:
+//!
+//! - `presence`: who is connected, whenever someone joins or leaves
+//! - `entry`: an entry's new completion state, after any `PATCH` or `PUT` that changes it
+//!
+//! Nothing here is persisted; a restart just means everyone reconnects.
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// Events a slow client can fall behind by before it skips ahead
+const CHANNEL_CAPACITY: usize = 64;
+
+/// Longest display name kept; longer names are cut
+pub const MAX_NAME_LEN: usize = 40;
+
+/// Name shown for a session that didn't pick one
+pub const DEFAULT_NAME: &str = "Someone";
+
+/// One connected session
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+pub struct Viewer {
+    pub session: String,
+    pub name: String,
+}
+
+/// Something every open page should hear about
+#[derive(Debug, Clone, Serialize, PartialEq, Eq)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum LiveEvent {
+    Presence { viewers: Vec<Viewer> },
+    Entry { id: String, completed: bool },
+}
+
+impl LiveEvent {
+    /// SSE event name, which the page listens for
+    pub fn name(&self) -> &'static str {
+        match self {
+            LiveEvent::Presence { .. } => "presence",
+            LiveEvent::Entry { .. } => "entry",
+        }
+    }
+}
+
+/// Connected sessions and the channel that fans events out to them
+pub struct Live {
+    tx: broadcast::Sender<LiveEvent>,
+    /// Session id -> (name, open connections)
+    viewers: Mutex<BTreeMap<String, (String, usize)>>,
+}
+
+impl Default for Live {
+    fn default() -> Self {
+        Self {
+            tx: broadcast::channel(CHANNEL_CAPACITY).0,
+            viewers: Mutex::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl Live {
+    /// Send `event` to every connected page (a no-op when nobody is connected)
+    pub fn publish(&self, event: LiveEvent) {
+        let _ = self.tx.send(event);
+    }
+
+    /// Connected sessions, sorted by name
+    pub fn viewers(&self) -> Vec<Viewer> {
+        let viewers = self.viewers.lock().unwrap();
+        let mut list: Vec<Viewer> = viewers
+            .iter()
+            .map(|(session, (name, _))| Viewer {
+                session: session.clone(),
+                name: name.clone(),
+            })
+            .collect();
+        list.sort_by(|a, b| a.name.cmp(&b.name));
+        list
+    }
+
+    /// Register a connection for `session` and announce the new viewer list.
+    /// The connection counts until the returned [`Subscription`] is dropped.
+    pub fn join(self: &Arc<Self>, session: &str, name: &str) -> Subscription {
+        let rx = self.tx.subscribe();
+        {
+            let mut viewers = self.viewers.lock().unwrap();
+            let viewer = viewers
+                .entry(session.to_string())
+                .or_insert_with(|| (String::new(), 0));
+            viewer.0 = clean_name(name);
+            viewer.1 += 1;
+        }
+        self.publish_presence();
+        Subscription {
+            live: Arc::clone(self),
+            session: session.to_string(),
+            rx,
+        }
+    }
+
+    fn leave(&self, session: &str) {
+        {
+            let mut viewers = self.viewers.lock().unwrap();
+            if let Some(viewer) = viewers.get_mut(session) {
+                viewer.1 -= 1;
+                if viewer.1 == 0 {
+                    viewers.remove(session);
+                }
+            }
+        }
+        self.publish_presence();
+    }
+
+    fn publish_presence(&self) {
+        self.publish(LiveEvent::Presence {
+            viewers: self.viewers(),
+        });
+    }
+}
+
+/// One open `/api/live` stream; leaves the viewer list when dropped
+pub struct Subscription {
+    live: Arc<Live>,
+    session: String,
+    pub rx: broadcast::Receiver<LiveEvent>,
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.live.leave(&self.session);
+    }
+}
+
+/// Trimmed, length-capped display name, or [`DEFAULT_NAME`]
+fn clean_name(name: &str) -> String {
+    let name: String = name.trim().chars().take(MAX_NAME_LEN).collect();
+    if name.is_empty() {
+        DEFAULT_NAME.to_string()
+    } else {
+        name
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(live: &Live) -> Vec<String> {
+        live.viewers().into_iter().map(|v| v.name).collect()
+    }
+
+    #[test]
+    fn test_join_and_leave() {
+        let live = Arc::new(Live::default());
+        let mut mamma = live.join("a", "Mamma");
+        assert_eq!(
+            mamma.rx.try_recv().unwrap(),
+            LiveEvent::Presence {
+                viewers: vec![Viewer {
+                    session: "a".to_string(),
+                    name: "Mamma".to_string()
+                }]
+            }
+        );
+
+        let papa = live.join("b", "  ");
+        // A second tab in the same session counts once
+        let mamma_tab = live.join("a", "Mamma");
+        assert_eq!(names(&live), vec!["Mamma", DEFAULT_NAME]);
+
+        drop(mamma_tab);
+        assert_eq!(names(&live), vec!["Mamma", DEFAULT_NAME]);
+        drop(papa);
+        assert_eq!(names(&live), vec!["Mamma"]);
+
+        // Everyone hears about the changes, entry updates included
+        live.publish(LiveEvent::Entry {
+            id: "x".to_string(),
+            completed: true,
+        });
+        let events: Vec<&str> = std::iter::from_fn(|| mamma.rx.try_recv().ok())
+            .map(|e| e.name())
+            .collect();
+        assert_eq!(
+            events,
+            vec!["presence", "presence", "presence", "presence", "entry"]
+        );
+    }
+
+    #[test]
+    fn test_clean_name() {
+        assert_eq!(clean_name(" Nonna "), "Nonna");
+        assert_eq!(clean_name(""), DEFAULT_NAME);
+        assert_eq!(clean_name(&"x".repeat(100)).len(), MAX_NAME_LEN);
+    }
+}
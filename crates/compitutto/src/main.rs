@@ -1,15 +1,46 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use std::collections::HashMap;
+use std::io::IsTerminal;
 use std::path::PathBuf;
-use tracing::info;
+use tracing::{info, warn};
 use tracing_subscriber::{fmt, EnvFilter};
 
+mod activities;
+mod agenda;
+mod backup;
+mod capture;
+mod cors;
+mod crosslist;
 mod data;
 mod db;
+mod details;
+mod due;
+mod error;
+mod export;
+mod features;
+mod forecast;
+#[cfg(feature = "graphql")]
+mod graphql;
+mod holidays;
 mod html;
+mod hypermedia;
+mod jobs;
+mod live;
+mod ocr;
+mod overload;
 mod parser;
+mod patch;
+mod plan;
+mod register;
+mod schema;
 mod server;
+mod streak;
+mod subjects;
+mod teachers;
+mod timetable;
 mod types;
+mod version;
 
 #[derive(Parser, Debug)]
 #[command(name = "compitutto")]
@@ -34,6 +65,11 @@ enum Commands {
         /// Port to listen on
         #[arg(short, long, default_value = "9000")]
         port: u16,
+
+        /// Refuse every change and hide the editing controls (e.g. for a
+        /// wall-mounted tablet)
+        #[arg(long)]
+        read_only: bool,
     },
 
     /// Process files and generate static HTML (no server)
@@ -43,16 +79,189 @@ enum Commands {
     Parse {
         /// Path to the Excel XML file
         file: PathBuf,
+        /// Print the entries as canonical JSON (as in the golden files under
+        /// tests/fixtures/) instead of logging them
+        #[arg(long)]
+        json: bool,
+        /// Print the parse report (rows parsed, skipped and why, unknown
+        /// headers) as JSON instead of the entries
+        #[arg(long, conflicts_with = "json")]
+        report: bool,
+    },
+
+    /// Print pending entries for the next few days; exits 1 if a test is among them
+    Due {
+        /// Only tomorrow (the default)
+        #[arg(long, conflicts_with = "days")]
+        tomorrow: bool,
+
+        /// Number of days after today to include
+        #[arg(long, default_value = "1")]
+        days: u32,
+
+        /// Plain output even on a terminal (also honours NO_COLOR)
+        #[arg(long)]
+        no_color: bool,
+    },
+
+    /// Check an entries JSON file against the published schema
+    Validate {
+        /// Path to the JSON file (a list of entries, as from /api/entries)
+        #[arg(required_unless_present = "print_schema")]
+        file: Option<PathBuf>,
+
+        /// Print the JSON Schema instead
+        #[arg(long)]
+        print_schema: bool,
+    },
+
+    /// Import school holidays from an iCal feed, replacing any imported before
+    Holidays {
+        /// URL (http/https) or path of the .ics file
+        source: String,
+    },
+
+    /// Import after-school activities from an iCal feed, replacing any
+    /// imported before. A URL is remembered and re-fetched by the server.
+    Activities {
+        /// URL (http/https) or path of the .ics file
+        #[arg(required_unless_present = "unsubscribe")]
+        source: Option<String>,
+
+        /// Stop re-fetching the subscribed URL (keeps the activities)
+        #[arg(long, conflicts_with = "source")]
+        unsubscribe: bool,
+    },
+
+    /// List subjects or merge near-duplicates
+    Subjects {
+        #[command(subcommand)]
+        action: SubjectsCommand,
+    },
+
+    /// List the nightly database backups or restore one
+    Backups {
+        #[command(subcommand)]
+        action: BackupsCommand,
+    },
+
+    /// Show or create database migrations
+    Migrate {
+        #[command(subcommand)]
+        action: MigrateCommand,
+    },
+
+    /// Correct a recurring typo or encoding artifact in entry tasks, e.g.
+    /// --find "Ã¨" --replace "è". Prints every change; logged unless
+    /// --dry-run
+    FixText {
+        /// Text to replace (exact, case-sensitive)
+        #[arg(long)]
+        find: String,
+
+        /// What to put instead (default: remove it)
+        #[arg(long, default_value = "")]
+        replace: String,
+
+        /// Only entries of this subject (any case)
+        #[arg(long)]
+        subject: Option<String>,
+
+        /// Show what would change without changing anything
+        #[arg(long)]
+        dry_run: bool,
+    },
+
+    /// Database maintenance (the server also runs it on a schedule)
+    Db {
+        #[command(subcommand)]
+        action: DbCommand,
+    },
+
+    /// Print the stored entries for another app to import
+    Export {
+        /// csv, ics, or the CSV that todoist / ticktick import (entries still
+        /// to do, a project per subject)
+        #[arg(long, value_enum, default_value_t = ExportFormat::Csv)]
+        format: ExportFormat,
+
+        /// First date to include (YYYY-MM-DD)
+        #[arg(long)]
+        from: Option<chrono::NaiveDate>,
+
+        /// Last date to include (YYYY-MM-DD)
+        #[arg(long)]
+        to: Option<chrono::NaiveDate>,
     },
 }
 
+#[derive(clap::ValueEnum, Clone, Copy, Debug, PartialEq, Eq)]
+enum ExportFormat {
+    Csv,
+    Ics,
+    Todoist,
+    Ticktick,
+}
+
+#[derive(Subcommand, Debug)]
+enum SubjectsCommand {
+    /// Subjects in use with entry counts, and likely duplicates
+    List,
+
+    /// Rename subject FROM to INTO on every entry and map it on future imports
+    Merge {
+        /// Subject to merge away (any case), e.g. "Inglese"
+        from: String,
+        /// Subject to keep, e.g. "Lingua Inglese"
+        into: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum BackupsCommand {
+    /// Backups in data/backups, newest first
+    List,
+
+    /// Replace the database with a backup (stop the server first). The
+    /// current database is backed up before it's overwritten.
+    Restore {
+        /// File name from `backups list`, or a path to a backup file
+        backup: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum MigrateCommand {
+    /// Applied and pending migrations (nothing is applied; the server and
+    /// the other commands do that on start)
+    Status,
+
+    /// Create an empty db/migrations/<timestamp>_<name>.sql
+    New {
+        /// What it does, e.g. "add due time"
+        name: String,
+    },
+}
+
+#[derive(Subcommand, Debug)]
+enum DbCommand {
+    /// Renumber each day's entry positions from 0, without gaps or
+    /// duplicates; the order doesn't change
+    CompactPositions,
+}
+
 fn init_tracing(log_level: &str) {
     let filter = EnvFilter::try_from_default_env()
         .unwrap_or_else(|_| EnvFilter::new(log_level))
         .add_directive("hyper=warn".parse().unwrap())
         .add_directive("tower_http=warn".parse().unwrap());
 
-    fmt().with_env_filter(filter).with_target(false).init();
+    // Logs go to stderr so stdout stays clean for `parse --json` and `due`
+    fmt()
+        .with_env_filter(filter)
+        .with_target(false)
+        .with_writer(std::io::stderr)
+        .init();
 }
 
 #[tokio::main]
@@ -63,11 +272,11 @@ async fn main() -> Result<()> {
 
     match args.command {
         // Default to serve if no command specified
-        None | Some(Commands::Serve { port: 9000 }) => {
-            server::serve(9000, args.output).await?;
+        None => {
+            server::serve(9000, args.output, false).await?;
         }
-        Some(Commands::Serve { port }) => {
-            server::serve(port, args.output).await?;
+        Some(Commands::Serve { port, read_only }) => {
+            server::serve(port, args.output, read_only).await?;
         }
         Some(Commands::Build) => {
             let entries = data::parse_all_exports()?;
@@ -75,8 +284,73 @@ async fn main() -> Result<()> {
             html::generate_html(&entries, &html_path)?;
             info!(path = %html_path.display(), "HTML saved");
         }
-        Some(Commands::Parse { file }) => {
-            let entries = parser::parse_excel_xml(&file)?;
+        Some(Commands::Holidays { source }) => {
+            import_holidays(&source, &args.output).await?;
+        }
+        Some(Commands::Activities {
+            source,
+            unsubscribe,
+        }) => {
+            import_activities(source.as_deref(), unsubscribe, &args.output).await?;
+        }
+        Some(Commands::Subjects { action }) => {
+            subjects_command(action, &args.output)?;
+        }
+        Some(Commands::Backups { action }) => {
+            backups_command(action, &args.output)?;
+        }
+        Some(Commands::Migrate { action }) => {
+            migrate_command(action, &args.output)?;
+        }
+        Some(Commands::FixText {
+            find,
+            replace,
+            subject,
+            dry_run,
+        }) => {
+            fix_text_command(&find, &replace, subject.as_deref(), dry_run, &args.output)?;
+        }
+        Some(Commands::Db { action }) => {
+            db_command(action, &args.output)?;
+        }
+        Some(Commands::Export { format, from, to }) => {
+            print!("{}", export_entries(format, from, to, &args.output)?);
+        }
+        Some(Commands::Due {
+            tomorrow,
+            days,
+            no_color,
+        }) => {
+            let days = if tomorrow { 1 } else { days };
+            if print_due(days, no_color, &args.output)? {
+                std::process::exit(1);
+            }
+        }
+        Some(Commands::Validate { file, print_schema }) => {
+            if print_schema {
+                print!("{}", schema::ENTRIES_SCHEMA);
+            } else if let Some(file) = file {
+                let entries = schema::load_entries(&file)?;
+                info!(count = entries.len(), file = %file.display(), "Entries file is valid");
+            }
+        }
+        Some(Commands::Parse { file, json, report }) => {
+            let subjects =
+                subjects::SubjectPipeline::load(std::path::Path::new(subjects::RULES_FILE))?;
+            let (mut entries, parse_report) = parser::parse_with_report(&file, &subjects)?;
+            if report {
+                println!("{}", serde_json::to_string_pretty(&parse_report)?);
+                return Ok(());
+            }
+            data::log_report(&file, &parse_report);
+            if let Some(sidecar) = details::load_sidecar(&file)? {
+                let merged = details::merge_details(&mut entries, &sidecar);
+                info!(merged, "Merged entry details");
+            }
+            if json {
+                print!("{}", parser::to_canonical_json(&entries)?);
+                return Ok(());
+            }
             info!(count = entries.len(), file = %file.display(), "Found entries");
             for entry in &entries {
                 info!(
@@ -91,3 +365,264 @@ async fn main() -> Result<()> {
 
     Ok(())
 }
+
+/// Print what's due in the next `days` days. Returns whether a test is due.
+fn print_due(days: u32, no_color: bool, output_dir: &std::path::Path) -> Result<bool> {
+    let data_dir = output_dir.join("data");
+    server::ensure_data_dir(&data_dir)?;
+    let conn = db::init_db(&data_dir.join("homework.db"), &server::get_migrations_dir())?;
+    let today = chrono::Local::now().date_naive();
+    let report = due::build_report(&db::get_all_entries(&conn)?, today, days);
+
+    let color =
+        !no_color && std::env::var_os("NO_COLOR").is_none() && std::io::stdout().is_terminal();
+    print!("{}", report.render(color));
+    Ok(report.has_test())
+}
+
+/// The stored entries between `from` and `to` in `format`
+fn export_entries(
+    format: ExportFormat,
+    from: Option<chrono::NaiveDate>,
+    to: Option<chrono::NaiveDate>,
+    output_dir: &std::path::Path,
+) -> Result<String> {
+    let data_dir = output_dir.join("data");
+    server::ensure_data_dir(&data_dir)?;
+    let conn = db::init_db(&data_dir.join("homework.db"), &server::get_migrations_dir())?;
+    let filter = db::EntryFilter {
+        from: from.map(|d| d.format("%Y-%m-%d").to_string()),
+        to: to.map(|d| d.format("%Y-%m-%d").to_string()),
+        ..Default::default()
+    };
+    let entries = db::get_entries_filtered(&conn, &filter)?;
+    info!(count = entries.len(), "Exporting entries");
+    let icons = subjects::SubjectIcons::load(&conn)?;
+    let now = chrono::Utc::now();
+    Ok(match format {
+        ExportFormat::Csv => export::to_csv(&entries, &icons),
+        ExportFormat::Ics => export::to_ics(&entries, &icons, now),
+        ExportFormat::Todoist => export::to_todoist(&entries),
+        ExportFormat::Ticktick => export::to_ticktick(&entries, now),
+    })
+}
+
+fn subjects_command(action: SubjectsCommand, output_dir: &std::path::Path) -> Result<()> {
+    let data_dir = output_dir.join("data");
+    server::ensure_data_dir(&data_dir)?;
+    let conn = db::init_db(&data_dir.join("homework.db"), &server::get_migrations_dir())?;
+    match action {
+        SubjectsCommand::List => {
+            let counts = db::get_subject_counts(&conn)?;
+            for count in &counts {
+                println!("{:>5}  {}", count.entries, count.subject);
+            }
+            for suggestion in subjects::suggest_merges(&counts) {
+                println!(
+                    "Possible duplicate: compitutto subjects merge \"{}\" \"{}\"",
+                    suggestion.from, suggestion.into
+                );
+            }
+        }
+        SubjectsCommand::Merge { from, into } => {
+            let rules_path = std::path::Path::new(subjects::RULES_FILE);
+            let merge = subjects::merge(&conn, rules_path, &from, &into)?;
+            println!(
+                "Merged \"{}\" into \"{}\": {} entries, {} pending imports (alias saved to {})",
+                merge.from,
+                merge.into,
+                merge.entries,
+                merge.pending,
+                rules_path.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+fn migrate_command(action: MigrateCommand, output_dir: &std::path::Path) -> Result<()> {
+    let migrations_dir = server::get_migrations_dir();
+    match action {
+        MigrateCommand::Status => {
+            let db_path = output_dir.join("data").join("homework.db");
+            let conn = if db_path.exists() {
+                db::open_db(&db_path)?
+            } else {
+                // Nothing applied yet; don't create the file just to say so
+                rusqlite::Connection::open_in_memory()?
+            };
+            let status = db::migration_status(&conn, &migrations_dir)?;
+            for migration in &status {
+                let state = match &migration.applied_at {
+                    Some(at) => format!("applied {}", at),
+                    None => "pending".to_string(),
+                };
+                let missing = if migration.on_disk {
+                    ""
+                } else {
+                    "  (not in this build)"
+                };
+                println!("{:<22}  {}{}", state, migration.version, missing);
+            }
+            let pending = status.iter().filter(|m| m.applied_at.is_none()).count();
+            println!(
+                "{} applied, {} pending ({})",
+                status.len() - pending,
+                pending,
+                db_path.display()
+            );
+        }
+        MigrateCommand::New { name } => {
+            let now = chrono::Utc::now().naive_utc();
+            let path = db::new_migration(&migrations_dir, &name, now)?;
+            println!("{}", path.display());
+        }
+    }
+    Ok(())
+}
+
+fn fix_text_command(
+    find: &str,
+    replace: &str,
+    subject: Option<&str>,
+    dry_run: bool,
+    output_dir: &std::path::Path,
+) -> Result<()> {
+    anyhow::ensure!(!find.is_empty(), "--find can't be empty");
+    anyhow::ensure!(find != replace, "--find and --replace are the same");
+    let data_dir = output_dir.join("data");
+    server::ensure_data_dir(&data_dir)?;
+    let conn = db::init_db(&data_dir.join("homework.db"), &server::get_migrations_dir())?;
+    let report = db::fix_text(&conn, find, replace, subject, dry_run)?;
+    for change in &report.changes {
+        println!("{}  {}", change.date, change.subject);
+        println!("  - {}", change.before);
+        println!("  + {}", change.after);
+    }
+    if dry_run {
+        println!(
+            "{} entries would change (dry run, nothing written)",
+            report.changes.len()
+        );
+    } else {
+        println!("Fixed {} entries", report.changes.len());
+    }
+    Ok(())
+}
+
+fn db_command(action: DbCommand, output_dir: &std::path::Path) -> Result<()> {
+    let data_dir = output_dir.join("data");
+    server::ensure_data_dir(&data_dir)?;
+    let conn = db::init_db(&data_dir.join("homework.db"), &server::get_migrations_dir())?;
+    match action {
+        DbCommand::CompactPositions => {
+            let compacted = db::compact_positions(&conn)?;
+            println!(
+                "Renumbered {} entries on {} days",
+                compacted.entries, compacted.days
+            );
+        }
+    }
+    Ok(())
+}
+
+fn backups_command(action: BackupsCommand, output_dir: &std::path::Path) -> Result<()> {
+    let data_dir = output_dir.join("data");
+    server::ensure_data_dir(&data_dir)?;
+    let mut conn = db::init_db(&data_dir.join("homework.db"), &server::get_migrations_dir())?;
+    let dir = backup::backup_dir(&conn)?;
+    match action {
+        BackupsCommand::List => {
+            let backups = backup::list(&dir)?;
+            if backups.is_empty() {
+                println!("No backups in {} yet", dir.display());
+            }
+            for b in &backups {
+                println!(
+                    "{}  {}  {:>8.1} KiB",
+                    b.name(),
+                    b.taken.format("%Y-%m-%d %H:%M"),
+                    b.bytes as f64 / 1024.0
+                );
+            }
+        }
+        BackupsCommand::Restore { backup: name } => {
+            let path = backup::resolve(&dir, &name);
+            let now = chrono::Local::now().naive_local();
+            let previous = backup::restore(&mut conn, &path, now)?;
+            println!(
+                "Restored {} (the database as it was is in {})",
+                path.display(),
+                previous.display()
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Replace the stored activities with those in `source` and subscribe to it
+/// if it's a URL. A file replaces any subscription, which would otherwise
+/// overwrite it on the next refresh.
+async fn import_activities(
+    source: Option<&str>,
+    unsubscribe: bool,
+    output_dir: &std::path::Path,
+) -> Result<()> {
+    let data_dir = output_dir.join("data");
+    server::ensure_data_dir(&data_dir)?;
+    let conn = db::init_db(&data_dir.join("homework.db"), &server::get_migrations_dir())?;
+    if unsubscribe {
+        db::set_activities_url(&conn, None)?;
+        info!("Unsubscribed from the activities feed");
+        return Ok(());
+    }
+    let Some(source) = source else {
+        return Ok(());
+    };
+
+    let parsed = activities::parse_ical(&holidays::read_source(source).await?)?;
+    let stored = db::replace_activities(&conn, &parsed)?;
+    info!(count = stored, source, "Imported activities");
+    if holidays::is_url(source) {
+        db::set_activities_url(&conn, Some(source))?;
+        info!(
+            hours = activities::REFRESH_HOURS,
+            "Subscribed; the server re-fetches the feed periodically"
+        );
+    } else {
+        db::set_activities_url(&conn, None)?;
+    }
+    info!("Use Reprocess on the settings page to move study sessions off busy days");
+    Ok(())
+}
+
+/// Replace the stored holidays with those in `source`, and point out
+/// existing entries that fall on one.
+async fn import_holidays(source: &str, output_dir: &std::path::Path) -> Result<()> {
+    let text = holidays::read_source(source).await?;
+    let parsed = holidays::parse_ical(&text)?;
+
+    let data_dir = output_dir.join("data");
+    server::ensure_data_dir(&data_dir)?;
+    let conn = db::init_db(&data_dir.join("homework.db"), &server::get_migrations_dir())?;
+    let stored = db::replace_holidays(&conn, &parsed)?;
+    info!(count = stored, source, "Imported holidays");
+
+    let dates: HashMap<String, String> = parsed.into_iter().map(|h| (h.date, h.name)).collect();
+    for entry in db::get_all_entries(&conn)? {
+        if entry.is_generated() || entry.is_orphaned() {
+            continue;
+        }
+        if let Some(holiday) = dates.get(&entry.date) {
+            warn!(
+                date = %entry.date,
+                holiday = %holiday,
+                subject = %entry.subject,
+                task = %entry.task,
+                "Entry is dated on a holiday; check the export's dates"
+            );
+        }
+    }
+    info!("Use Reprocess on the settings page to move study sessions off holidays");
+    Ok(())
+}
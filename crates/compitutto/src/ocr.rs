@@ -0,0 +1,222 @@
+//! OCR for photo attachments (whiteboard pictures), so a search also finds
+//! homework that only exists as a photo.
+//!
+//! raschietto's `--details` saves photos next to the sidecar and the import
+//! records them in `attachments` as `pending`. The `ocr_attachments` job (see
+//! `jobs`) runs an external command on a few of them at a time and stores the
+//! text, which the `entries_fts` index picks up.
+//!
+//! The command comes from `COMPITUTTO_OCR_COMMAND` (default
+//! `tesseract {file} stdout -l ita+eng`). `{file}` is replaced by the photo's
+//! path (appended if missing), the command's stdout is the text, and it runs
+//! without a shell.
+
+use anyhow::{anyhow, bail, Context, Result};
+use rusqlite::Connection;
+use std::io::Read;
+use std::path::Path;
+use std::process::{Command, Stdio};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::db;
+use crate::features::{self, Feature};
+
+pub const COMMAND_ENV: &str = "COMPITUTTO_OCR_COMMAND";
+pub const DEFAULT_COMMAND: &str = "tesseract {file} stdout -l ita+eng";
+
+/// Photos per run
+const BATCH: usize = 5;
+
+/// Longest a single photo may take
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+/// The configured OCR command line
+pub fn command_template() -> String {
+    std::env::var(COMMAND_ENV)
+        .ok()
+        .filter(|c| !c.trim().is_empty())
+        .unwrap_or_else(|| DEFAULT_COMMAND.to_string())
+}
+
+/// Program and arguments for reading `file` with `template`
+fn command_line(template: &str, file: &Path) -> Result<Vec<String>> {
+    let file = file.to_string_lossy();
+    let mut args: Vec<String> = template
+        .split_whitespace()
+        .map(|arg| arg.replace("{file}", &file))
+        .collect();
+    if args.is_empty() {
+        bail!("{} is empty", COMMAND_ENV);
+    }
+    if !template.contains("{file}") {
+        args.push(file.into_owned());
+    }
+    Ok(args)
+}
+
+/// Run the OCR command on `file` and return the text it printed, tidied
+pub fn recognize(template: &str, file: &Path) -> Result<String> {
+    let args = command_line(template, file)?;
+    let mut child = Command::new(&args[0])
+        .args(&args[1..])
+        .stdin(Stdio::null())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", args[0]))?;
+
+    // Read the pipes on their own threads so a chatty command can't block
+    let mut stdout = child.stdout.take().context("No stdout")?;
+    let mut stderr = child.stderr.take().context("No stderr")?;
+    let out = std::thread::spawn(move || {
+        let mut text = String::new();
+        stdout.read_to_string(&mut text).map(|_| text)
+    });
+    let err = std::thread::spawn(move || {
+        let mut text = String::new();
+        let _ = stderr.read_to_string(&mut text);
+        text
+    });
+
+    let started = Instant::now();
+    let status = loop {
+        if let Some(status) = child.try_wait()? {
+            break status;
+        }
+        if started.elapsed() > TIMEOUT {
+            let _ = child.kill();
+            let _ = child.wait();
+            bail!("{} took longer than {:?}", args[0], TIMEOUT);
+        }
+        std::thread::sleep(Duration::from_millis(50));
+    };
+
+    let text = out
+        .join()
+        .map_err(|_| anyhow!("Failed to read {} output", args[0]))?
+        .context("OCR output is not valid UTF-8")?;
+    if !status.success() {
+        let stderr = err.join().unwrap_or_default();
+        let reason = stderr.lines().find(|l| !l.trim().is_empty()).unwrap_or("");
+        bail!("{} failed ({}): {}", args[0], status, reason.trim());
+    }
+    Ok(tidy(&text))
+}
+
+/// Trim each line and drop blank ones
+fn tidy(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Job: OCR the next few pending photos. A photo that can't be read is
+/// marked failed; a missing OCR program fails the run and leaves them pending.
+/// The database is only locked to read the batch and store each result, not
+/// while the OCR command runs.
+pub fn process_pending(conn: &Mutex<Connection>) -> Result<String> {
+    let pending = {
+        let conn = conn.lock().unwrap();
+        if !features::enabled(&conn, Feature::Ocr) {
+            return Ok("OCR is turned off".to_string());
+        }
+        db::get_pending_ocr(&conn, BATCH)?
+    };
+    if pending.is_empty() {
+        return Ok("No photos to read".to_string());
+    }
+    let template = command_template();
+    let (mut done, mut failed) = (0, 0);
+    for attachment in pending {
+        let result = match attachment.file.as_deref().map(Path::new) {
+            Some(file) if file.exists() => recognize(&template, file),
+            Some(file) => Err(anyhow!("File not found: {}", file.display())),
+            None => Err(anyhow!("No local copy")),
+        };
+        match result {
+            Ok(text) => {
+                db::set_ocr_result(&conn.lock().unwrap(), attachment.id, Ok(&text))?;
+                done += 1;
+            }
+            Err(e) if is_missing_program(&e) => {
+                return Err(e.context(format!(
+                    "Install tesseract or set {}; photos stay pending",
+                    COMMAND_ENV
+                )));
+            }
+            Err(e) => {
+                let reason = format!("{:#}", e);
+                db::set_ocr_result(&conn.lock().unwrap(), attachment.id, Err(&reason))?;
+                failed += 1;
+            }
+        }
+    }
+    Ok(format!("Read {} photos, {} failed", done, failed))
+}
+
+/// Read one photo now rather than waiting for the job (a captured diary
+/// page). `None` when the OCR program isn't installed, so the photo stays
+/// pending; otherwise the text or why it couldn't be read.
+pub fn recognize_now(file: &Path) -> Option<Result<String, String>> {
+    match recognize(&command_template(), file) {
+        Ok(text) => Some(Ok(text)),
+        Err(e) if is_missing_program(&e) => None,
+        Err(e) => Some(Err(format!("{:#}", e))),
+    }
+}
+
+fn is_missing_program(error: &anyhow::Error) -> bool {
+    error
+        .root_cause()
+        .downcast_ref::<std::io::Error>()
+        .is_some_and(|e| e.kind() == std::io::ErrorKind::NotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_command_line() {
+        let file = Path::new("data/attachments/a.jpg");
+        assert_eq!(
+            command_line(DEFAULT_COMMAND, file).unwrap(),
+            vec![
+                "tesseract",
+                "data/attachments/a.jpg",
+                "stdout",
+                "-l",
+                "ita+eng"
+            ]
+        );
+        assert_eq!(
+            command_line("ocrit --lang it", file).unwrap(),
+            vec!["ocrit", "--lang", "it", "data/attachments/a.jpg"]
+        );
+        assert!(command_line("  ", file).is_err());
+    }
+
+    #[test]
+    fn test_tidy() {
+        assert_eq!(
+            tidy("  Pag.  112\n\n\n es. 4 e 5 \n\u{c}"),
+            "Pag. 112\nes. 4 e 5"
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_recognize_with_command() {
+        let file = Path::new("lavagna.jpg");
+        assert_eq!(
+            recognize("echo Esercizi   pag. 40", file).unwrap(),
+            "Esercizi pag. 40 lavagna.jpg"
+        );
+        assert!(recognize("false", file).is_err());
+        let missing = recognize("compitutto-no-such-ocr {file}", file).unwrap_err();
+        assert!(is_missing_program(&missing));
+    }
+}
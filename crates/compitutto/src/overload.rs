@@ -0,0 +1,166 @@
+//! Soft limits for busy days.
+//!
+//! Creating or moving an entry onto a day that already has a test, or that
+//! would go over `Settings::day_limit_entries` entries or
+//! `Settings::day_limit_minutes` minutes of work, still succeeds; the
+//! response carries a [`DayWarning`] and the page asks whether to keep the
+//! change. A limit of 0 turns that check off.
+
+use serde::{Deserialize, Serialize};
+
+use crate::data::is_test_or_quiz;
+use crate::plan::DEFAULT_ITEM_MINUTES;
+use crate::types::{HomeworkEntry, Settings};
+
+/// Why an entry's new day looks overloaded
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct DayWarning {
+    pub date: String,
+    /// Tests already on the day, besides the entry itself
+    pub tests: usize,
+    /// Open entries on the day, the entry included
+    pub entries: usize,
+    /// Estimated minutes of work on the day, the entry included
+    pub minutes: u32,
+    /// "This day already has 2 tests", for showing as is
+    pub message: String,
+}
+
+/// An entry as returned by create and move, with the day's warning if any
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CheckedEntry {
+    #[serde(flatten)]
+    pub entry: HomeworkEntry,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub warning: Option<DayWarning>,
+}
+
+/// Work time an entry adds to its day: the estimate, or the plan's default
+/// for compiti, reminders and study sessions without one
+fn minutes(entry: &HomeworkEntry) -> u32 {
+    match entry.entry_type.as_str() {
+        "compiti" | "lavoro" | "studio" => entry.estimated_minutes.unwrap_or(DEFAULT_ITEM_MINUTES),
+        _ => entry.estimated_minutes.unwrap_or(0),
+    }
+}
+
+fn plural(n: usize, one: &str, many: &str) -> String {
+    format!("{} {}", n, if n == 1 { one } else { many })
+}
+
+/// Check `entry` against the other entries on its day (`day`, which may
+/// include the entry itself). Completed entries don't count.
+pub fn check_day(
+    day: &[HomeworkEntry],
+    entry: &HomeworkEntry,
+    settings: &Settings,
+) -> Option<DayWarning> {
+    let others: Vec<&HomeworkEntry> = day
+        .iter()
+        .filter(|e| e.id != entry.id && e.date == entry.date && !e.completed)
+        .collect();
+    let tests = others
+        .iter()
+        .filter(|e| !e.is_generated() && is_test_or_quiz(e))
+        .count();
+    let entries = others.len() + 1;
+    let minutes: u32 = others.iter().map(|e| minutes(e)).sum::<u32>() + minutes(entry);
+
+    let mut reasons = Vec::new();
+    if tests > 0 {
+        reasons.push(format!("already has {}", plural(tests, "test", "tests")));
+    }
+    let limit = settings.day_limit_entries as usize;
+    if limit > 0 && entries > limit {
+        reasons.push(format!("would have {} entries (limit {})", entries, limit));
+    }
+    let limit = settings.day_limit_minutes;
+    if limit > 0 && minutes > limit {
+        reasons.push(format!(
+            "would need about {} minutes of work (limit {})",
+            minutes, limit
+        ));
+    }
+    if reasons.is_empty() {
+        return None;
+    }
+    Some(DayWarning {
+        date: entry.date.clone(),
+        tests,
+        entries,
+        minutes,
+        message: format!("This day {}", reasons.join(" and ")),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(entry_type: &str, task: &str, minutes: Option<u32>) -> HomeworkEntry {
+        let mut entry = HomeworkEntry::new(
+            entry_type.to_string(),
+            "2025-01-20".to_string(),
+            "Matematica".to_string(),
+            task.to_string(),
+        );
+        entry.estimated_minutes = minutes;
+        entry
+    }
+
+    #[test]
+    fn test_check_day_tests() {
+        let settings = Settings::default();
+        let day = vec![
+            entry("verifica", "Verifica sui limiti", None),
+            entry("interrogazione", "Interrogazione di storia", None),
+        ];
+        let new = entry("compiti", "Pag. 10", Some(20));
+        let warning = check_day(&day, &new, &settings).unwrap();
+        assert_eq!(warning.tests, 2);
+        assert_eq!(warning.entries, 3);
+        assert_eq!(warning.message, "This day already has 2 tests");
+
+        // A test doesn't warn about itself
+        assert!(check_day(&day[..1], &day[0], &settings).is_none());
+        assert!(check_day(&[], &new, &settings).is_none());
+    }
+
+    #[test]
+    fn test_check_day_limits() {
+        let settings = Settings {
+            day_limit_entries: 2,
+            day_limit_minutes: 60,
+            ..Settings::default()
+        };
+        let mut done = entry("compiti", "Esercizi", Some(120));
+        done.completed = true;
+        let day = vec![entry("compiti", "Pag. 10", Some(40)), done];
+
+        let new = entry("compiti", "Pag. 20", None);
+        let warning = check_day(&day, &new, &settings).unwrap();
+        assert_eq!(
+            (warning.entries, warning.minutes),
+            (2, 40 + DEFAULT_ITEM_MINUTES)
+        );
+        assert_eq!(
+            warning.message,
+            "This day would need about 70 minutes of work (limit 60)"
+        );
+
+        let day = [day, vec![entry("nota", "Portare il libro", None)]].concat();
+        let warning = check_day(&day, &new, &settings).unwrap();
+        assert_eq!(
+            warning.message,
+            "This day would have 3 entries (limit 2) and would need about 70 minutes of work (limit 60)"
+        );
+
+        let off = Settings {
+            day_limit_entries: 0,
+            day_limit_minutes: 0,
+            ..Settings::default()
+        };
+        assert!(check_day(&day, &new, &off).is_none());
+    }
+}
@@ -1,12 +1,16 @@
 use anyhow::{Context, Result};
 use calamine::{open_workbook_auto, Data, Reader};
+use chrono::{Datelike, NaiveDate, NaiveDateTime};
 use quick_xml::escape::unescape;
 use quick_xml::events::Event;
 use quick_xml::reader::Reader as XmlReader;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs;
 use std::path::Path;
+use tracing::debug;
 
+use crate::subjects::SubjectPipeline;
 use crate::types::HomeworkEntry;
 
 /// Keywords that indicate a test/exam entry (case-insensitive)
@@ -26,47 +30,46 @@ fn detect_entry_type(task: &str, original_type: &str) -> String {
 
 /// Parse an Excel file and extract homework entries.
 /// Supports SpreadsheetML XML format (.xls with XML content) and modern Excel formats (.xlsx, .xlsb, .ods)
+#[cfg(test)]
 pub fn parse_excel_xml(path: &Path) -> Result<Vec<HomeworkEntry>> {
-    // First try to read the file to check if it's SpreadsheetML XML
-    let content = fs::read_to_string(path).context("Failed to read file")?;
+    parse_with_report(path, &SubjectPipeline::default()).map(|(entries, _)| entries)
+}
+
+/// Like `parse_excel_xml`, detecting missing subjects with `subjects`, and
+/// with a report of what became of each row
+pub fn parse_with_report(
+    path: &Path,
+    subjects: &SubjectPipeline,
+) -> Result<(Vec<HomeworkEntry>, ParseReport)> {
+    // First read the file to check if it's SpreadsheetML XML. xlsx files are
+    // zip archives, so this can't assume UTF-8.
+    let bytes = fs::read(path).context("Failed to read file")?;
 
     // Check if it's SpreadsheetML XML format
-    if content.starts_with("<?xml") || content.contains("<Workbook") {
-        return parse_spreadsheet_ml(&content);
+    if let Ok(content) = std::str::from_utf8(&bytes) {
+        if content.starts_with("<?xml") || content.contains("<Workbook") {
+            return parse_spreadsheet_ml(content, subjects);
+        }
     }
 
     // Otherwise try calamine for modern Excel formats
-    parse_with_calamine(path)
+    parse_with_calamine(path, subjects)
 }
 
 /// Parse SpreadsheetML XML format (used by older Excel exports)
-fn parse_spreadsheet_ml(content: &str) -> Result<Vec<HomeworkEntry>> {
+fn parse_spreadsheet_ml(
+    content: &str,
+    subjects: &SubjectPipeline,
+) -> Result<(Vec<HomeworkEntry>, ParseReport)> {
     let rows = parse_spreadsheet_rows(content)?;
-
-    if rows.is_empty() {
-        anyhow::bail!("No data rows found in file");
-    }
-
-    // First row is headers
-    let headers = &rows[0];
-
-    // Map column indices
-    let col_indices = map_columns(headers);
-
-    // Parse data rows into entries
-    let mut entries = Vec::new();
-
-    for row in rows.iter().skip(1) {
-        if let Some(entry) = parse_row(row, &col_indices) {
-            entries.push(entry);
-        }
-    }
-
-    Ok(entries)
+    parse_rows(&rows, subjects)
 }
 
 /// Parse with calamine for modern Excel formats
-fn parse_with_calamine(path: &Path) -> Result<Vec<HomeworkEntry>> {
+fn parse_with_calamine(
+    path: &Path,
+    subjects: &SubjectPipeline,
+) -> Result<(Vec<HomeworkEntry>, ParseReport)> {
     let mut workbook =
         open_workbook_auto(path).with_context(|| format!("Failed to open file: {:?}", path))?;
 
@@ -88,26 +91,116 @@ fn parse_with_calamine(path: &Path) -> Result<Vec<HomeworkEntry>> {
         .map(|row| row.iter().map(cell_to_string).collect())
         .collect();
 
+    parse_rows(&rows, subjects)
+}
+
+/// Turn sheet rows into entries, picking the registro format from the header
+fn parse_rows(
+    rows: &[Vec<String>],
+    subjects: &SubjectPipeline,
+) -> Result<(Vec<HomeworkEntry>, ParseReport)> {
     if rows.is_empty() {
         anyhow::bail!("No data rows found in file");
     }
 
-    // First row is headers
-    let headers = &rows[0];
-
-    // Map column indices
-    let col_indices = map_columns(headers);
+    let (format, header_row) = ExportFormat::detect(rows);
+    let headers = &rows[header_row];
+    let col_indices = format.map_columns(headers);
+    debug!(?format, header_row, "Detected export format");
+
+    let mut report = ParseReport {
+        format: format.as_str().to_string(),
+        header_row: header_row + 1,
+        unknown_headers: headers
+            .iter()
+            .enumerate()
+            .filter(|(i, h)| !h.trim().is_empty() && !col_indices.values().any(|c| c == i))
+            .map(|(_, h)| h.trim().to_string())
+            .collect(),
+        missing_columns: ["date", "subject", "task"]
+            .into_iter()
+            .filter(|c| !col_indices.contains_key(c))
+            .map(String::from)
+            .collect(),
+        ..Default::default()
+    };
 
-    // Parse data rows into entries
     let mut entries = Vec::new();
-
-    for row in rows.iter().skip(1) {
-        if let Some(entry) = parse_row(row, &col_indices) {
-            entries.push(entry);
+    for (i, row) in rows.iter().enumerate().skip(header_row + 1) {
+        // Sheet rows count from 1
+        let row_number = i + 1;
+        report.rows_total += 1;
+        if row.iter().all(|cell| cell.trim().is_empty()) {
+            report.blank += 1;
+            continue;
+        }
+        match parse_row_as(format, row, &col_indices, subjects) {
+            Ok(entry) => {
+                if NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d").is_err() {
+                    report.warnings.push(RowIssue {
+                        row: row_number,
+                        reason: format!("date {:?} is not a date", entry.date),
+                    });
+                } else if entry.subject.is_empty() {
+                    report.warnings.push(RowIssue {
+                        row: row_number,
+                        reason: "no subject, and none found in the task".to_string(),
+                    });
+                } else if let Some(reason) = subjects.timetable().check(&entry) {
+                    report.warnings.push(RowIssue {
+                        row: row_number,
+                        reason,
+                    });
+                }
+                entries.push(entry);
+            }
+            Err(reason) => report.skipped.push(RowIssue {
+                row: row_number,
+                reason: reason.to_string(),
+            }),
         }
     }
+    report.parsed = entries.len();
+    Ok((entries, report))
+}
 
-    Ok(entries)
+// ========== Parse report ==========
+
+/// What a parse made of an export's rows. Rows are numbered as in the
+/// spreadsheet, from 1.
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ParseReport {
+    /// `classeviva`, `argo` or `nuvola`
+    pub format: String,
+    pub header_row: usize,
+    /// Rows below the header, blank ones included
+    pub rows_total: usize,
+    pub parsed: usize,
+    /// Rows with every cell empty, which are ignored
+    pub blank: usize,
+    /// Rows that didn't become an entry, and why
+    pub skipped: Vec<RowIssue>,
+    /// Rows that became an entry but look wrong
+    pub warnings: Vec<RowIssue>,
+    /// Header cells that don't map to any column
+    pub unknown_headers: Vec<String>,
+    /// Of `date`, `subject` and `task`, those with no column in the header
+    pub missing_columns: Vec<String>,
+}
+
+/// A row that was skipped or looks wrong
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct RowIssue {
+    pub row: usize,
+    pub reason: String,
+}
+
+impl ParseReport {
+    /// Nothing skipped, nothing suspicious, every column found. Unknown
+    /// headers don't count: exports carry plenty of columns nobody needs.
+    pub fn is_clean(&self) -> bool {
+        self.skipped.is_empty() && self.warnings.is_empty() && self.missing_columns.is_empty()
+    }
 }
 
 /// Parse SpreadsheetML XML into rows of cell values
@@ -121,6 +214,8 @@ fn parse_spreadsheet_rows(xml: &str) -> Result<Vec<Vec<String>>> {
     let mut in_row = false;
     let mut in_cell = false;
     let mut in_data = false;
+    // Whether the current cell's value has been pushed (from its Data)
+    let mut cell_done = false;
     let mut current_text = String::new();
 
     loop {
@@ -132,16 +227,13 @@ fn parse_spreadsheet_rows(xml: &str) -> Result<Vec<Vec<String>>> {
                         in_row = true;
                         current_row = Vec::new();
                     }
-                    b"Cell" => {
-                        if in_row {
-                            in_cell = true;
-                        }
+                    b"Cell" if in_row => {
+                        in_cell = true;
+                        cell_done = false;
                     }
-                    b"Data" => {
-                        if in_cell {
-                            in_data = true;
-                            current_text.clear();
-                        }
+                    b"Data" if in_cell => {
+                        in_data = true;
+                        current_text.clear();
                     }
                     _ => {}
                 }
@@ -156,7 +248,7 @@ fn parse_spreadsheet_rows(xml: &str) -> Result<Vec<Vec<String>>> {
                         in_row = false;
                     }
                     b"Cell" => {
-                        if in_cell && !in_data {
+                        if in_cell && !cell_done {
                             // Empty cell
                             current_row.push(String::new());
                         }
@@ -166,36 +258,42 @@ fn parse_spreadsheet_rows(xml: &str) -> Result<Vec<Vec<String>>> {
                         if in_data {
                             current_row.push(current_text.trim().to_string());
                             current_text.clear();
+                            cell_done = true;
                         }
                         in_data = false;
                     }
                     _ => {}
                 }
             }
-            Ok(Event::Text(e)) => {
-                if in_data {
-                    if let Ok(decoded) = e.decode() {
-                        if let Ok(text) = unescape(&decoded) {
-                            current_text.push_str(&text);
-                        }
+            // Self-closing empty cells: <Cell/>, <Data ss:Type="String"/>
+            Ok(Event::Empty(e)) => match e.local_name().as_ref() {
+                b"Cell" if in_row => current_row.push(String::new()),
+                b"Data" if in_cell && !cell_done => {
+                    current_row.push(String::new());
+                    cell_done = true;
+                }
+                _ => {}
+            },
+            Ok(Event::Text(e)) if in_data => {
+                if let Ok(decoded) = e.decode() {
+                    if let Ok(text) = unescape(&decoded) {
+                        current_text.push_str(&text);
                     }
                 }
             }
-            Ok(Event::GeneralRef(e)) => {
-                // Handle entity references like &amp;
-                if in_data {
-                    if let Ok(decoded) = e.decode() {
-                        // Resolve predefined XML entities
-                        let resolved = match decoded.as_ref() {
-                            "amp" => "&",
-                            "lt" => "<",
-                            "gt" => ">",
-                            "quot" => "\"",
-                            "apos" => "'",
-                            _ => "",
-                        };
-                        current_text.push_str(resolved);
-                    }
+            // Handle entity references like &amp;
+            Ok(Event::GeneralRef(e)) if in_data => {
+                if let Ok(decoded) = e.decode() {
+                    // Resolve predefined XML entities
+                    let resolved = match decoded.as_ref() {
+                        "amp" => "&",
+                        "lt" => "<",
+                        "gt" => ">",
+                        "quot" => "\"",
+                        "apos" => "'",
+                        _ => "",
+                    };
+                    current_text.push_str(resolved);
                 }
             }
             Ok(Event::Eof) => break,
@@ -240,15 +338,162 @@ fn cell_to_string(cell: &Data) -> String {
     }
 }
 
-/// Map header names to column indices
+// ========== Export formats ==========
+
+/// Which registro an export comes from.
+///
+/// ClasseViva is the default. Argo DidUp and Nuvola exports are recognised
+/// by their header row, which may sit below a title row or two:
+///
+///   - Argo: a "Data assegnazione" column next to "Materia"; the due date is
+///     "Data consegna" and every row is homework
+///   - Nuvola: a "Data consegna" / "Scadenza" column next to "Materia" (and
+///     no assegnazione); "Tipo" says compito, verifica, …
+///
+/// Both write dates as `DD/MM/YYYY`, sometimes with a weekday or a time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    ClasseViva,
+    Argo,
+    Nuvola,
+}
+
+/// Rows searched for an Argo/Nuvola header before assuming ClasseViva
+const HEADER_SEARCH_ROWS: usize = 10;
+
+impl ExportFormat {
+    /// Find the format and the index of its header row. Rows below the
+    /// first are only sniffed when the first isn't a ClasseViva header, so
+    /// a task that mentions "materia" and "consegna" can't pass for one.
+    pub fn detect(rows: &[Vec<String>]) -> (Self, usize) {
+        let Some(first) = rows.first() else {
+            return (ExportFormat::ClasseViva, 0);
+        };
+        if let Some(format) = Self::sniff(first) {
+            return (format, 0);
+        }
+        let columns = map_columns(first);
+        if ["date", "subject", "task"]
+            .iter()
+            .all(|c| columns.contains_key(c))
+        {
+            return (ExportFormat::ClasseViva, 0);
+        }
+        rows.iter()
+            .take(HEADER_SEARCH_ROWS)
+            .enumerate()
+            .skip(1)
+            .find_map(|(i, row)| Self::sniff(row).map(|format| (format, i)))
+            .unwrap_or((ExportFormat::ClasseViva, 0))
+    }
+
+    /// Recognise an Argo or Nuvola header row
+    fn sniff(headers: &[String]) -> Option<Self> {
+        let headers: Vec<String> = headers.iter().map(|h| header_key(h)).collect();
+        let has = |needle: &str| headers.iter().any(|h| h.contains(needle));
+        if !has("materia") {
+            return None;
+        }
+        if has("assegnazione") {
+            Some(ExportFormat::Argo)
+        } else if has("consegna") || has("scadenza") {
+            Some(ExportFormat::Nuvola)
+        } else {
+            None
+        }
+    }
+
+    /// Map header names to column indices for this format
+    pub fn map_columns(self, headers: &[String]) -> HashMap<&'static str, usize> {
+        if self == ExportFormat::ClasseViva {
+            return map_columns(headers);
+        }
+        let mut indices = HashMap::new();
+        for (i, header) in headers.iter().enumerate() {
+            let key = header_key(header);
+            if key.contains("consegna") || key.contains("scadenza") {
+                indices.entry("date").or_insert(i);
+            }
+            if key.contains("materia") {
+                indices.entry("subject").or_insert(i);
+            }
+            if key.contains("descrizione")
+                || key.contains("compiti")
+                || key.contains("compito")
+                || key.contains("attività")
+                || key.contains("argomento")
+            {
+                indices.entry("task").or_insert(i);
+            }
+            if self == ExportFormat::Nuvola && (key == "tipo" || key.starts_with("tipo ")) {
+                indices.entry("type").or_insert(i);
+            }
+        }
+        indices
+    }
+
+    pub fn as_str(self) -> &'static str {
+        match self {
+            ExportFormat::ClasseViva => "classeviva",
+            ExportFormat::Argo => "argo",
+            ExportFormat::Nuvola => "nuvola",
+        }
+    }
+
+    /// Entry type for a row's raw type cell
+    fn entry_type(self, raw: &str) -> String {
+        let raw = raw.trim().to_lowercase();
+        match self {
+            ExportFormat::ClasseViva => raw,
+            ExportFormat::Argo => "compiti".to_string(),
+            ExportFormat::Nuvola if raw.is_empty() || raw.starts_with("compit") => {
+                "compiti".to_string()
+            }
+            ExportFormat::Nuvola => raw,
+        }
+    }
+}
+
+/// Lowercased header with `_` as spaces, for matching
+fn header_key(header: &str) -> String {
+    header.trim().to_lowercase().replace('_', " ")
+}
+
+/// Normalize an Italian-style date (`15/01/2025`, `Mer 15/01/2025 08:00`,
+/// `15-01-25`) to YYYY-MM-DD. Unrecognised text passes through.
+pub fn normalize_italian_date(date: &str) -> String {
+    const FORMATS: &[&str] = &[
+        "%Y-%m-%d", "%d/%m/%Y", "%d-%m-%Y", "%d.%m.%Y", "%d/%m/%y", "%d-%m-%y",
+    ];
+    date.split_whitespace()
+        .find_map(|token| {
+            // Spreadsheet date cells come through as ISO datetimes
+            let token = token.split('T').next().unwrap_or(token);
+            FORMATS.iter().find_map(|format| {
+                let parsed = NaiveDate::parse_from_str(token, format).ok()?;
+                // "%Y" happily reads "25" as year 25
+                (parsed.year() > 1900).then(|| parsed.format("%Y-%m-%d").to_string())
+            })
+        })
+        .unwrap_or_else(|| normalize_date(date))
+}
+
+/// Map ClasseViva header names to column indices
 fn map_columns(headers: &[String]) -> HashMap<&'static str, usize> {
     let mut indices = HashMap::new();
 
     for (i, header) in headers.iter().enumerate() {
         let lower = header.to_lowercase();
 
-        // Date column
-        if lower.contains("data") || lower.contains("inizio") || lower.contains("date") {
+        // Date column, the end date of events spanning several days, and
+        // when the teacher posted the entry
+        if lower.contains("fine") {
+            if lower.contains("data") || lower.contains("date") {
+                indices.entry("end").or_insert(i);
+            }
+        } else if lower.contains("inserimento") {
+            indices.entry("assigned").or_insert(i);
+        } else if lower.contains("data") || lower.contains("inizio") || lower.contains("date") {
             indices.entry("date").or_insert(i);
         }
 
@@ -266,6 +511,11 @@ fn map_columns(headers: &[String]) -> HashMap<&'static str, usize> {
             indices.entry("task").or_insert(i);
         }
 
+        // Who posted it
+        if lower == "autore" {
+            indices.entry("teacher").or_insert(i);
+        }
+
         // Type column (but not "tipo evento")
         if lower == "tipo" || (lower.contains("tipo") && !lower.contains("evento")) {
             indices.entry("type").or_insert(i);
@@ -275,40 +525,107 @@ fn map_columns(headers: &[String]) -> HashMap<&'static str, usize> {
     indices
 }
 
-/// Parse a single row into a HomeworkEntry
-fn parse_row(row: &[String], col_indices: &HashMap<&'static str, usize>) -> Option<HomeworkEntry> {
-    let get_col = |key: &str| -> String {
-        col_indices
-            .get(key)
-            .and_then(|&i| row.get(i))
-            .map(|s| s.trim().to_string())
-            .unwrap_or_default()
-    };
+/// Trimmed cell for a mapped column, empty if unmapped or missing
+fn column(row: &[String], col_indices: &HashMap<&'static str, usize>, key: &str) -> String {
+    col_indices
+        .get(key)
+        .and_then(|&i| row.get(i))
+        .map(|s| s.trim().to_string())
+        .unwrap_or_default()
+}
 
-    let raw_type = get_col("type");
-    let date = normalize_date(&get_col("date"));
-    let mut subject = get_col("subject");
-    let task = get_col("task");
+/// Parse a single ClasseViva row into a HomeworkEntry, or why it isn't one
+fn parse_row(
+    row: &[String],
+    col_indices: &HashMap<&'static str, usize>,
+    subjects: &SubjectPipeline,
+) -> Result<HomeworkEntry, &'static str> {
+    let get_col = |key: &str| column(row, col_indices, key);
+    let mut entry = build_entry(
+        &get_col("type"),
+        normalize_date(&get_col("date")),
+        get_col("subject"),
+        get_col("task"),
+        subjects,
+    )?;
+    entry.end_date = end_date(&entry.date, &normalize_date(&get_col("end")));
+    entry.assigned_at = assigned_at(&get_col("assigned"));
+    entry.teacher = Some(get_col("teacher")).filter(|t| !t.is_empty());
+    Ok(entry)
+}
+
+/// `data_inserimento` as `YYYY-MM-DD HH:MM:SS`, or just the date when the
+/// cell has no time; `None` when it isn't a date
+fn assigned_at(cell: &str) -> Option<String> {
+    let cell = cell.trim().replacen('T', " ", 1);
+    if let Ok(at) = NaiveDateTime::parse_from_str(&cell, "%Y-%m-%d %H:%M:%S%.f") {
+        return Some(at.format("%Y-%m-%d %H:%M:%S").to_string());
+    }
+    NaiveDate::parse_from_str(&normalize_date(&cell), "%Y-%m-%d")
+        .ok()
+        .map(|d| d.to_string())
+}
 
-    // Only include entries with meaningful data
+/// `end` when it's a date after `start`; same-day events have no end date
+fn end_date(start: &str, end: &str) -> Option<String> {
+    let parse = |s: &str| NaiveDate::parse_from_str(s, "%Y-%m-%d").ok();
+    (parse(end)? > parse(start)?).then(|| end.to_string())
+}
+
+/// Parse a row of an export in `format`
+fn parse_row_as(
+    format: ExportFormat,
+    row: &[String],
+    col_indices: &HashMap<&'static str, usize>,
+    subjects: &SubjectPipeline,
+) -> Result<HomeworkEntry, &'static str> {
+    if format == ExportFormat::ClasseViva {
+        return parse_row(row, col_indices, subjects);
+    }
+    let get_col = |key: &str| column(row, col_indices, key);
+    build_entry(
+        &format.entry_type(&get_col("type")),
+        normalize_italian_date(&get_col("date")),
+        get_col("subject"),
+        get_col("task"),
+        subjects,
+    )
+}
+
+/// Build an entry from cleaned-up cells, or say why the row isn't one: a
+/// row without a task has nothing to do, whatever else it names
+fn build_entry(
+    raw_type: &str,
+    date: String,
+    mut subject: String,
+    task: String,
+    subjects: &SubjectPipeline,
+) -> Result<HomeworkEntry, &'static str> {
     if task.is_empty() && subject.is_empty() {
-        return None;
+        return Err("no task or subject");
+    }
+    if task.is_empty() {
+        return Err("no task");
     }
 
     // Detect entry type based on task content (e.g., verifica, prova, test)
-    let entry_type = detect_entry_type(&task, &raw_type);
+    let entry_type = detect_entry_type(&task, raw_type);
 
     // If subject is empty, try to extract it from the task text
     if subject.is_empty() {
-        if let Some(extracted) = extract_subject_from_task(&task) {
+        if let Some(extracted) = subjects.detect(&task) {
             subject = extracted;
         }
     } else {
         // Normalize subject (title case + overrides like "Seconda Lingua Comunitaria" -> "Tedesco")
         subject = normalize_subject(&subject);
     }
+    // Aliases from merged subjects ("Inglese" -> "Lingua Inglese")
+    let subject = subjects.canonical(&subject);
 
-    Some(HomeworkEntry::new(entry_type, date, subject, task))
+    let mut entry = HomeworkEntry::new(entry_type, date, subject, task);
+    entry.topic = crate::subjects::detect_topic(&entry.task, &entry.subject);
+    Ok(entry)
 }
 
 /// Subject name overrides - maps variations to canonical names
@@ -329,35 +646,6 @@ fn normalize_subject(subject: &str) -> String {
     title_cased
 }
 
-/// Known subjects that can be extracted from task text
-const KNOWN_SUBJECTS: &[(&str, &str)] = &[
-    // Italian subject names -> canonical form (title case)
-    ("matematica", "Matematica"),
-    ("aritmetica", "Matematica"),
-    ("geometria", "Matematica"),
-    ("italiano", "Italiano"),
-    ("antologia", "Italiano"),
-    ("storia", "Storia"),
-    ("geografia", "Geografia"),
-    ("inglese", "Lingua Inglese"),
-    ("english", "Lingua Inglese"),
-    ("verbi irregolari", "Lingua Inglese"), // English irregular verbs
-    ("tedesco", "Tedesco"),
-    ("deutsch", "Tedesco"),
-    ("arte", "Arte e Immagine"),
-    ("disegno", "Arte e Immagine"),
-    ("tecnologia", "Tecnologia"),
-    ("proiezioni ortogonali", "Tecnologia"),
-    ("scienze", "Scienze"),
-    ("lavoisier", "Scienze"), // Lavoisier's law = chemistry/science
-    ("musica", "Musica"),
-    ("ed. fisica", "Educazione Fisica"),
-    ("educazione fisica", "Educazione Fisica"),
-    ("religione", "Religione"),
-    ("ed. civica", "Educazione Civica"),
-    ("educazione civica", "Educazione Civica"),
-];
-
 /// Convert a string to title case (e.g., "MATEMATICA" -> "Matematica")
 pub fn to_title_case(s: &str) -> String {
     s.split_whitespace()
@@ -372,75 +660,14 @@ pub fn to_title_case(s: &str) -> String {
         .join(" ")
 }
 
-/// Try to extract a subject from the task text
+/// Try to extract a subject from the task text with the built-in keyword
+/// heuristic only (no config rules)
 ///
 /// Looks for patterns like "verifica di SUBJECT", "test di SUBJECT", etc.
+#[cfg(test)]
 pub fn extract_subject_from_task(task: &str) -> Option<String> {
-    let task_lower = task.to_lowercase();
-
-    // Pattern 1: "verifica/test/interrogazione di/su SUBJECT"
-    // e.g., "Verifica di matematica", "test di storia"
-    let prefixes = [
-        "verifica di ",
-        "verifica su ",
-        "test di ",
-        "test su ",
-        "interrogazione di ",
-        "interrogazione su ",
-        "prova di ",
-        "prova su ",
-        "esame di ",
-        "esame su ",
-    ];
-
-    for prefix in prefixes {
-        if let Some(pos) = task_lower.find(prefix) {
-            let after_prefix = &task_lower[pos + prefix.len()..];
-            // Look for a known subject in what follows
-            for (keyword, canonical) in KNOWN_SUBJECTS {
-                if after_prefix.starts_with(keyword) {
-                    return Some(canonical.to_string());
-                }
-            }
-        }
-    }
-
-    // Pattern 2: Check if task starts with a subject name followed by colon
-    // e.g., "Geometria: pag. 293..."
-    for (keyword, canonical) in KNOWN_SUBJECTS {
-        if let Some(after) = task_lower.strip_prefix(keyword) {
-            // Check if followed by colon or space
-            if after.starts_with(':') || after.starts_with(' ') {
-                return Some(canonical.to_string());
-            }
-        }
-    }
-
-    // Pattern 3: Look for subject keywords anywhere in the text
-    // but only if they appear in a context suggesting it's the subject
-    // e.g., "verifica ed. civica" or "portare libro di storia"
-    for (keyword, canonical) in KNOWN_SUBJECTS {
-        if task_lower.contains(keyword) {
-            // Additional heuristics to avoid false positives
-            // Only match if it looks like a test/assignment context
-            let test_context = task_lower.contains("verifica")
-                || task_lower.contains("test")
-                || task_lower.contains("interrogazione")
-                || task_lower.contains("prova")
-                || task_lower.contains("portare")
-                || task_lower.contains("libro di")
-                || task_lower.contains("quaderno")
-                || task_lower.contains("scritto")
-                || task_lower.contains("in inglese")
-                || task_lower.contains("attività");
-
-            if test_context {
-                return Some(canonical.to_string());
-            }
-        }
-    }
-
-    None
+    use crate::subjects::{KeywordDetector, SubjectDetector};
+    KeywordDetector.detect(task)
 }
 
 /// Normalize date to YYYY-MM-DD format
@@ -456,6 +683,43 @@ fn normalize_date(date: &str) -> String {
     date_part.to_string()
 }
 
+// ========== Canonical output ==========
+
+/// What the parser extracted from one row, without the fields that change
+/// on every run (`id`, timestamps). Output of `compitutto parse --json` and
+/// of the golden files in `tests/fixtures/`.
+#[derive(Debug, Serialize)]
+pub struct CanonicalEntry<'a> {
+    #[serde(rename = "type")]
+    pub entry_type: &'a str,
+    pub date: &'a str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<&'a str>,
+    pub subject: &'a str,
+    pub task: &'a str,
+    pub source_id: Option<&'a str>,
+}
+
+impl<'a> From<&'a HomeworkEntry> for CanonicalEntry<'a> {
+    fn from(entry: &'a HomeworkEntry) -> Self {
+        Self {
+            entry_type: &entry.entry_type,
+            date: &entry.date,
+            end_date: entry.end_date.as_deref(),
+            subject: &entry.subject,
+            task: &entry.task,
+            source_id: entry.source_id.as_deref(),
+        }
+    }
+}
+
+/// Entries as pretty-printed canonical JSON, in file order, with a trailing
+/// newline. The same file always gives the same bytes.
+pub fn to_canonical_json(entries: &[HomeworkEntry]) -> Result<String> {
+    let canonical: Vec<CanonicalEntry> = entries.iter().map(CanonicalEntry::from).collect();
+    Ok(serde_json::to_string_pretty(&canonical)? + "\n")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -680,6 +944,132 @@ mod tests {
         assert_eq!(entries[0].task, "Valid task");
     }
 
+    // ========== ParseReport tests ==========
+
+    #[test]
+    fn test_parse_report_counts_rows() {
+        let xml = r#"<?xml version="1.0"?>
+<Workbook xmlns="urn:schemas-microsoft-com:office:spreadsheet">
+<Worksheet ss:Name="Table1">
+<Table>
+<Row>
+<Cell><Data ss:Type="String">tipo</Data></Cell>
+<Cell><Data ss:Type="String">data_inizio</Data></Cell>
+<Cell><Data ss:Type="String">materia</Data></Cell>
+<Cell><Data ss:Type="String">nota</Data></Cell>
+<Cell><Data ss:Type="String">docente</Data></Cell>
+</Row>
+<Row>
+<Cell><Data ss:Type="String">compiti</Data></Cell>
+<Cell><Data ss:Type="String">2025-01-15</Data></Cell>
+<Cell><Data ss:Type="String"></Data></Cell>
+<Cell><Data ss:Type="String"></Data></Cell>
+</Row>
+<Row><Cell/><Cell><Data ss:Type="String"> </Data></Cell></Row>
+<Row>
+<Cell><Data ss:Type="String">compiti</Data></Cell>
+<Cell><Data ss:Type="String">domani</Data></Cell>
+<Cell><Data ss:Type="String">MATEMATICA</Data></Cell>
+<Cell><Data ss:Type="String">Pag. 12</Data></Cell>
+</Row>
+<Row>
+<Cell><Data ss:Type="String">compiti</Data></Cell>
+<Cell><Data ss:Type="String">2025-01-16</Data></Cell>
+<Cell><Data ss:Type="String">MATEMATICA</Data></Cell>
+<Cell><Data ss:Type="String">Valid task</Data></Cell>
+</Row>
+<Row>
+<Cell><Data ss:Type="String">compiti</Data></Cell>
+<Cell><Data ss:Type="String">2025-01-17</Data></Cell>
+<Cell><Data ss:Type="String">STORIA</Data></Cell>
+<Cell><Data ss:Type="String"></Data></Cell>
+</Row>
+<Row>
+<Cell><Data ss:Type="String">compiti</Data></Cell>
+<Cell><Data ss:Type="String">2025-01-17</Data></Cell>
+<Cell><Data ss:Type="String"></Data></Cell>
+<Cell><Data ss:Type="String">Leggere il libro</Data></Cell>
+</Row>
+</Table>
+</Worksheet>
+</Workbook>"#;
+
+        let file = create_test_xml_file(xml);
+        let (entries, report) =
+            parse_with_report(file.path(), &SubjectPipeline::default()).unwrap();
+
+        assert_eq!(entries.len(), 3);
+        assert_eq!(report.format, "classeviva");
+        assert_eq!(report.header_row, 1);
+        assert_eq!(report.rows_total, 6);
+        assert_eq!(report.parsed, 3);
+        assert_eq!(report.blank, 1);
+        assert_eq!(
+            report.skipped,
+            vec![
+                RowIssue {
+                    row: 2,
+                    reason: "no task or subject".to_string()
+                },
+                RowIssue {
+                    row: 6,
+                    reason: "no task".to_string()
+                }
+            ]
+        );
+        let rows: Vec<usize> = report.warnings.iter().map(|w| w.row).collect();
+        assert_eq!(rows, vec![4, 7]);
+        assert_eq!(
+            report.warnings[1].reason,
+            "no subject, and none found in the task"
+        );
+        assert_eq!(report.unknown_headers, vec!["docente"]);
+        assert!(report.missing_columns.is_empty());
+        assert!(!report.is_clean());
+    }
+
+    #[test]
+    fn test_parse_report_off_timetable() {
+        let file = create_test_xml_file(&minimal_excel_xml());
+        let rules = NamedTempFile::new().unwrap();
+        let parse = |timetable: &str| {
+            std::fs::write(rules.path(), format!(r#"{{"timetable": {}}}"#, timetable)).unwrap();
+            let subjects = SubjectPipeline::load(rules.path()).unwrap();
+            parse_with_report(file.path(), &subjects).unwrap().1
+        };
+
+        // 2025-01-15 is a Wednesday
+        let report = parse(r#"{"mon": ["Matematica"], "fri": ["matematica"]}"#);
+        assert_eq!(report.parsed, 1);
+        assert_eq!(
+            report.warnings,
+            vec![RowIssue {
+                row: 2,
+                reason: "Matematica has no lesson on Wednesdays (2025-01-15); is the date right?"
+                    .to_string()
+            }]
+        );
+        assert!(parse(r#"{"wednesday": ["Matematica"]}"#).is_clean());
+        // Only subjects in the timetable are checked
+        assert!(parse(r#"{"mon": ["Storia"]}"#).is_clean());
+    }
+
+    #[test]
+    fn test_parse_report_clean_and_missing_columns() {
+        let file = create_test_xml_file(&minimal_excel_xml());
+        let (_, report) = parse_with_report(file.path(), &SubjectPipeline::default()).unwrap();
+        assert!(report.is_clean());
+        assert_eq!(report.parsed, 1);
+
+        let rows = vec![
+            vec!["materia".to_string(), "nota".to_string()],
+            vec!["MATEMATICA".to_string(), "Pag. 12".to_string()],
+        ];
+        let (_, report) = parse_rows(&rows, &SubjectPipeline::default()).unwrap();
+        assert_eq!(report.missing_columns, vec!["date"]);
+        assert!(!report.is_clean());
+    }
+
     #[test]
     fn test_parse_excel_xml_with_special_characters() {
         let xml = r#"<?xml version="1.0"?>
@@ -731,13 +1121,7 @@ mod tests {
 
         let rows = parse_spreadsheet_rows(xml).unwrap();
 
-        assert_eq!(rows.len(), 2);
-        // Parser adds empty string for Cell end when no Data was inside
-        // The actual values are at positions 0 and 2 (with empty strings between)
-        assert!(rows[0].contains(&"A1".to_string()));
-        assert!(rows[0].contains(&"B1".to_string()));
-        assert!(rows[1].contains(&"A2".to_string()));
-        assert!(rows[1].contains(&"B2".to_string()));
+        assert_eq!(rows, vec![vec!["A1", "B1"], vec!["A2", "B2"]]);
     }
 
     #[test]
@@ -772,6 +1156,27 @@ mod tests {
         assert!(rows.is_empty());
     }
 
+    #[test]
+    fn test_parse_spreadsheet_rows_empty_cells_keep_columns() {
+        let xml = r#"<?xml version="1.0"?>
+<Workbook>
+<Worksheet>
+<Table>
+<Row>
+<Cell><Data>A1</Data></Cell>
+<Cell/>
+<Cell></Cell>
+<Cell><Data ss:Type="String"/></Cell>
+<Cell><Data>E1</Data></Cell>
+</Row>
+</Table>
+</Worksheet>
+</Workbook>"#;
+
+        let rows = parse_spreadsheet_rows(xml).unwrap();
+        assert_eq!(rows, vec![vec!["A1", "", "", "", "E1"]]);
+    }
+
     // ========== cell_to_string tests ==========
 
     #[test]
@@ -980,7 +1385,7 @@ mod tests {
         indices.insert("subject", 2);
         indices.insert("task", 3);
 
-        let entry = parse_row(&row, &indices).unwrap();
+        let entry = parse_row(&row, &indices, &SubjectPipeline::default()).unwrap();
 
         assert_eq!(entry.entry_type, "compiti");
         assert_eq!(entry.date, "2025-01-15");
@@ -998,10 +1403,10 @@ mod tests {
         indices.insert("subject", 5); // Out of bounds
         indices.insert("task", 6); // Out of bounds
 
-        let result = parse_row(&row, &indices);
+        let result = parse_row(&row, &indices, &SubjectPipeline::default());
 
-        // Should return None because task and subject are empty
-        assert!(result.is_none());
+        // Skipped because task and subject are empty
+        assert_eq!(result.unwrap_err(), "no task or subject");
     }
 
     #[test]
@@ -1019,7 +1424,7 @@ mod tests {
         indices.insert("subject", 2);
         indices.insert("task", 3);
 
-        let entry = parse_row(&row, &indices).unwrap();
+        let entry = parse_row(&row, &indices, &SubjectPipeline::default()).unwrap();
         assert_eq!(entry.task, "Task only");
     }
 
@@ -1038,8 +1443,8 @@ mod tests {
         indices.insert("subject", 2);
         indices.insert("task", 3);
 
-        let entry = parse_row(&row, &indices).unwrap();
-        assert_eq!(entry.subject, "Matematica");
+        let result = parse_row(&row, &indices, &SubjectPipeline::default());
+        assert_eq!(result.unwrap_err(), "no task");
     }
 
     #[test]
@@ -1057,7 +1462,7 @@ mod tests {
         indices.insert("subject", 2);
         indices.insert("task", 3);
 
-        let entry = parse_row(&row, &indices).unwrap();
+        let entry = parse_row(&row, &indices, &SubjectPipeline::default()).unwrap();
 
         assert_eq!(entry.entry_type, "compiti");
         assert_eq!(entry.date, "2025-01-15");
@@ -1080,7 +1485,7 @@ mod tests {
         indices.insert("subject", 2);
         indices.insert("task", 3);
 
-        let entry = parse_row(&row, &indices).unwrap();
+        let entry = parse_row(&row, &indices, &SubjectPipeline::default()).unwrap();
         assert_eq!(entry.date, "2025-01-15");
     }
 
@@ -1148,6 +1553,73 @@ xmlns:html="http://www.w3.org/TR/REC-html40">
         assert_eq!(entries[0].date, "2025-12-01");
         assert_eq!(entries[0].subject, "Tedesco"); // "SECONDA LINGUA COMUNITARIA" -> "Tedesco"
         assert_eq!(entries[0].task, "Ü 15 auf Seite 118");
+        // data_fine on the same day isn't a range
+        assert_eq!(entries[0].end_date, None);
+        assert_eq!(
+            entries[0].assigned_at.as_deref(),
+            Some("2025-11-30 17:59:10")
+        );
+        assert_eq!(entries[0].teacher.as_deref(), Some("DE STEFANI DEBORA"));
+        assert_eq!(entries[0].notice_days(), Some(1));
+    }
+
+    #[test]
+    fn test_assigned_at() {
+        assert_eq!(
+            assigned_at("2025-11-30T17:59:10.250").as_deref(),
+            Some("2025-11-30 17:59:10")
+        );
+        assert_eq!(assigned_at(" 2025-11-30 ").as_deref(), Some("2025-11-30"));
+        assert_eq!(assigned_at(""), None);
+        assert_eq!(assigned_at("ieri"), None);
+    }
+
+    #[test]
+    fn test_parse_multi_day_event() {
+        let xml = r#"<?xml version="1.0"?>
+<Workbook xmlns="urn:schemas-microsoft-com:office:spreadsheet">
+<Worksheet ss:Name="Table1">
+<Table>
+<Row>
+<Cell><Data ss:Type="String">data_fine</Data></Cell>
+<Cell><Data ss:Type="String">data_inizio</Data></Cell>
+<Cell><Data ss:Type="String">ora_fine</Data></Cell>
+<Cell><Data ss:Type="String">nota</Data></Cell>
+<Cell><Data ss:Type="String">tipo</Data></Cell>
+<Cell><Data ss:Type="String">materia</Data></Cell>
+</Row>
+<Row>
+<Cell><Data ss:Type="String">2025-02-10 00:00:00</Data></Cell>
+<Cell><Data ss:Type="String">2025-02-05</Data></Cell>
+<Cell><Data ss:Type="String">18:00:00</Data></Cell>
+<Cell><Data ss:Type="String">Settimana bianca</Data></Cell>
+<Cell><Data ss:Type="String">nota</Data></Cell>
+<Cell><Data ss:Type="String">SCIENZE MOTORIE</Data></Cell>
+</Row>
+<Row>
+<Cell><Data ss:Type="String">2025-02-01</Data></Cell>
+<Cell><Data ss:Type="String">2025-02-03</Data></Cell>
+<Cell><Data ss:Type="String">13:00:00</Data></Cell>
+<Cell><Data ss:Type="String">Esercizi</Data></Cell>
+<Cell><Data ss:Type="String">compiti</Data></Cell>
+<Cell><Data ss:Type="String">MATEMATICA</Data></Cell>
+</Row>
+</Table>
+</Worksheet>
+</Workbook>"#;
+
+        let file = create_test_xml_file(xml);
+        let entries = parse_excel_xml(file.path()).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].date, "2025-02-05");
+        assert_eq!(entries[0].end_date.as_deref(), Some("2025-02-10"));
+        // An end before the start is ignored
+        assert_eq!(entries[1].date, "2025-02-03");
+        assert_eq!(entries[1].end_date, None);
+        assert!(to_canonical_json(&entries[..1])
+            .unwrap()
+            .contains(r#""end_date": "2025-02-10""#));
     }
 
     // ========== normalize_subject tests ==========
@@ -1298,7 +1770,7 @@ xmlns:html="http://www.w3.org/TR/REC-html40">
         indices.insert("subject", 2);
         indices.insert("task", 3);
 
-        let entry = parse_row(&row, &indices).unwrap();
+        let entry = parse_row(&row, &indices, &SubjectPipeline::default()).unwrap();
         assert_eq!(entry.subject, "Matematica");
     }
 
@@ -1318,7 +1790,7 @@ xmlns:html="http://www.w3.org/TR/REC-html40">
         indices.insert("subject", 2);
         indices.insert("task", 3);
 
-        let entry = parse_row(&row, &indices).unwrap();
+        let entry = parse_row(&row, &indices, &SubjectPipeline::default()).unwrap();
         // Should keep the original subject (title cased), not extract from task
         assert_eq!(entry.subject, "Italiano");
     }
@@ -1397,7 +1869,7 @@ xmlns:html="http://www.w3.org/TR/REC-html40">
         indices.insert("subject", 2);
         indices.insert("task", 3);
 
-        let entry = parse_row(&row, &indices).unwrap();
+        let entry = parse_row(&row, &indices, &SubjectPipeline::default()).unwrap();
         assert_eq!(entry.entry_type, "verifica");
     }
 
@@ -1416,7 +1888,7 @@ xmlns:html="http://www.w3.org/TR/REC-html40">
         indices.insert("subject", 2);
         indices.insert("task", 3);
 
-        let entry = parse_row(&row, &indices).unwrap();
+        let entry = parse_row(&row, &indices, &SubjectPipeline::default()).unwrap();
         assert_eq!(entry.entry_type, "verifica");
     }
 
@@ -1435,7 +1907,188 @@ xmlns:html="http://www.w3.org/TR/REC-html40">
         indices.insert("subject", 2);
         indices.insert("task", 3);
 
-        let entry = parse_row(&row, &indices).unwrap();
+        let entry = parse_row(&row, &indices, &SubjectPipeline::default()).unwrap();
         assert_eq!(entry.entry_type, "compiti");
     }
+
+    // ========== Export format tests ==========
+
+    /// SpreadsheetML workbook with the given rows of string cells
+    fn workbook(rows: &[&[&str]]) -> String {
+        let rows: String = rows
+            .iter()
+            .map(|cells| {
+                let cells: String = cells
+                    .iter()
+                    .map(|c| format!("<Cell><Data ss:Type=\"String\">{}</Data></Cell>", c))
+                    .collect();
+                format!("<Row>{}</Row>\n", cells)
+            })
+            .collect();
+        format!(
+            "<?xml version=\"1.0\"?>\n<Workbook xmlns=\"urn:schemas-microsoft-com:office:spreadsheet\">\n\
+             <Worksheet ss:Name=\"Sheet1\">\n<Table>\n{}</Table>\n</Worksheet>\n</Workbook>",
+            rows
+        )
+    }
+
+    fn strings(cells: &[&str]) -> Vec<String> {
+        cells.iter().map(|c| c.to_string()).collect()
+    }
+
+    #[test]
+    fn test_export_format_detect() {
+        let classeviva = vec![strings(&["tipo", "data_inizio", "materia", "nota"])];
+        assert_eq!(
+            ExportFormat::detect(&classeviva),
+            (ExportFormat::ClasseViva, 0)
+        );
+
+        let argo = vec![
+            strings(&["Compiti assegnati - 2A"]),
+            strings(&[]),
+            strings(&["Data assegnazione", "Data consegna", "Materia", "Compiti"]),
+        ];
+        assert_eq!(ExportFormat::detect(&argo), (ExportFormat::Argo, 2));
+
+        let nuvola = vec![strings(&["Materia", "Tipo", "Scadenza", "Descrizione"])];
+        assert_eq!(ExportFormat::detect(&nuvola), (ExportFormat::Nuvola, 0));
+
+        // A ClasseViva row whose cells read like a Nuvola header
+        let classeviva = vec![
+            strings(&["tipo", "data_inizio", "materia", "nota"]),
+            strings(&[
+                "compiti",
+                "2025-01-15",
+                "Materia alternativa",
+                "Consegna entro venerdì",
+            ]),
+        ];
+        assert_eq!(
+            ExportFormat::detect(&classeviva),
+            (ExportFormat::ClasseViva, 0)
+        );
+        assert_eq!(ExportFormat::detect(&[]), (ExportFormat::ClasseViva, 0));
+    }
+
+    #[test]
+    fn test_normalize_italian_date() {
+        assert_eq!(normalize_italian_date("15/01/2025"), "2025-01-15");
+        assert_eq!(normalize_italian_date("Mer 15/01/2025 08:00"), "2025-01-15");
+        assert_eq!(normalize_italian_date("5-1-25"), "2025-01-05");
+        assert_eq!(normalize_italian_date("15.01.2025"), "2025-01-15");
+        assert_eq!(normalize_italian_date("2025-01-15T00:00:00"), "2025-01-15");
+        assert_eq!(normalize_italian_date("domani"), "domani");
+    }
+
+    #[test]
+    fn test_parse_argo_export() {
+        let xml = workbook(&[
+            &["Compiti assegnati - Classe 2A"],
+            &[
+                "Data assegnazione",
+                "Data consegna",
+                "Materia",
+                "Compiti",
+                "Docente",
+            ],
+            &[
+                "13/01/2025",
+                "15/01/2025",
+                "MATEMATICA",
+                "Pag. 100 es. 1-5",
+                "ROSSI",
+            ],
+            &[
+                "14/01/2025",
+                "Gio 16/01/2025",
+                "ITALIANO",
+                "Verifica capitolo 3",
+                "BIANCHI",
+            ],
+            &["14/01/2025", "17/01/2025", "", "", "BIANCHI"],
+        ]);
+        let file = create_test_xml_file(&xml);
+        let entries = parse_excel_xml(file.path()).unwrap();
+
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[0].entry_type, "compiti");
+        assert_eq!(entries[0].date, "2025-01-15");
+        assert_eq!(entries[0].subject, "Matematica");
+        assert_eq!(entries[0].task, "Pag. 100 es. 1-5");
+        // Tests are still recognised from the text
+        assert_eq!(entries[1].entry_type, "verifica");
+        assert_eq!(entries[1].date, "2025-01-16");
+    }
+
+    #[test]
+    fn test_parse_nuvola_export() {
+        let xml = workbook(&[
+            &["Materia", "Tipo", "Data consegna", "Descrizione"],
+            &["Storia", "Compito", "20/01/2025", "Leggere pag. 40"],
+            &["Scienze", "Verifica", "21/01/2025", "Capitolo 2"],
+            &["Inglese", "", "22-01-2025", "Workbook p. 12"],
+        ]);
+        let file = create_test_xml_file(&xml);
+        let entries = parse_excel_xml(file.path()).unwrap();
+
+        let summary: Vec<(&str, &str, &str)> = entries
+            .iter()
+            .map(|e| (e.entry_type.as_str(), e.date.as_str(), e.subject.as_str()))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("compiti", "2025-01-20", "Storia"),
+                ("verifica", "2025-01-21", "Scienze"),
+                ("compiti", "2025-01-22", "Inglese"),
+            ]
+        );
+    }
+
+    // ========== Golden files ==========
+
+    /// Every export in tests/fixtures/ parses to exactly its `<file>.json`.
+    /// After an intended change, rewrite them with
+    /// `UPDATE_GOLDENS=1 cargo test -p compitutto golden` and review the diff.
+    #[test]
+    fn test_golden_fixtures() {
+        let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures");
+        let update = std::env::var_os("UPDATE_GOLDENS").is_some();
+        let mut exports: Vec<_> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|path| {
+                matches!(
+                    path.extension().and_then(|e| e.to_str()),
+                    Some("xls" | "xlsx")
+                )
+            })
+            .collect();
+        exports.sort();
+        assert!(
+            exports.len() >= 5,
+            "fixtures missing from {}",
+            dir.display()
+        );
+
+        for export in &exports {
+            let entries =
+                parse_excel_xml(export).unwrap_or_else(|e| panic!("{}: {:#}", export.display(), e));
+            let actual = to_canonical_json(&entries).unwrap();
+            let mut golden = export.clone().into_os_string();
+            golden.push(".json");
+            if update {
+                fs::write(&golden, &actual).unwrap();
+                continue;
+            }
+            let expected = fs::read_to_string(&golden).unwrap_or_default();
+            assert_eq!(
+                actual,
+                expected,
+                "{} no longer matches its golden file",
+                export.display()
+            );
+        }
+    }
 }
@@ -0,0 +1,361 @@
+//! Changing an entry over the API: `PATCH /api/entries/{id}` with a JSON
+//! merge patch (RFC 7396) and `PUT /api/entries/{id}` with the whole entry.
+//!
+//! Both work on the entry as `GET /api/entries/{id}` returns it. A patch is
+//! merged into it: a member replaces that field, `null` clears it, and
+//! anything left out stays as it is. A PUT body is the entry as it should
+//! be: writable fields left out go back to their defaults (not done, not
+//! pinned, position 0, no color, ...), so sending the same body twice
+//! leaves the same entry. `date` is the only field a PUT must have.
+//!
+//! Only the [`WRITABLE`] fields change. The [`READ_ONLY`] ones may be sent
+//! back as a GET gave them, but not with another value; `updated_at`,
+//! `_links`, `warning` and `day_completed` are ignored. Any other member, a value of the
+//! wrong type, or one that fails the checks below is rejected with the
+//! field's name, which the server answers with a 422.
+
+use chrono::NaiveDate;
+use serde::de::DeserializeOwned;
+use serde_json::{Map, Value};
+
+use crate::db::EntryUpdate;
+use crate::types::{is_hex_color, HomeworkEntry, MAX_GRADE_LEN};
+
+/// Fields a PATCH or PUT can change
+pub const WRITABLE: &[&str] = &[
+    "date",
+    "completed",
+    "position",
+    "estimated_minutes",
+    "actual_minutes",
+    "difficulty",
+    "pinned",
+    "color",
+    "grade",
+    "visible_from",
+];
+
+/// Fields that can be sent but must keep their stored value
+pub const READ_ONLY: &[&str] = &[
+    "id",
+    "source_id",
+    "type",
+    "subject",
+    "topic",
+    "task",
+    "parent_id",
+    "created_at",
+    "source_date",
+    "end_date",
+    "assigned_at",
+    "teacher",
+    "source_file",
+];
+
+/// Members of a response that aren't the entry's own
+const IGNORED: &[&str] = &["updated_at", "_links", "warning", "day_completed"];
+
+/// A member that can't be applied, and why
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+impl FieldError {
+    fn new(field: &str, message: impl Into<String>) -> Self {
+        FieldError {
+            field: field.to_string(),
+            message: message.into(),
+        }
+    }
+}
+
+/// RFC 7396: merge `patch` into `target`
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    let Value::Object(patch) = patch else {
+        *target = patch.clone();
+        return;
+    };
+    if !target.is_object() {
+        *target = Value::Object(Map::new());
+    }
+    if let Value::Object(target) = target {
+        for (key, value) in patch {
+            if value.is_null() {
+                target.remove(key);
+            } else {
+                merge_patch(target.entry(key.as_str()).or_insert(Value::Null), value);
+            }
+        }
+    }
+}
+
+/// What a merge `patch` changes in `stored`
+pub fn apply_patch(stored: &HomeworkEntry, patch: &Value) -> Result<EntryUpdate, FieldError> {
+    let patch = members(patch)?;
+    let mut wanted = Value::Object(stored_value(stored));
+    merge_patch(&mut wanted, &Value::Object(patch));
+    changes(stored, &wanted)
+}
+
+/// What replacing `stored` with `body` changes
+pub fn apply_put(stored: &HomeworkEntry, body: &Value) -> Result<EntryUpdate, FieldError> {
+    let body = members(body)?;
+    let mut wanted = stored_value(stored);
+    for field in WRITABLE {
+        wanted.remove(*field);
+    }
+    for (key, value) in body {
+        if !value.is_null() {
+            wanted.insert(key, value);
+        }
+    }
+    changes(stored, &Value::Object(wanted))
+}
+
+/// The object's members, without the ignored ones; unknown ones are an error
+fn members(body: &Value) -> Result<Map<String, Value>, FieldError> {
+    let Value::Object(body) = body else {
+        return Err(FieldError::new("", "The body must be a JSON object"));
+    };
+    let mut members = Map::new();
+    for (key, value) in body {
+        if IGNORED.contains(&key.as_str()) {
+            continue;
+        }
+        if !WRITABLE.contains(&key.as_str()) && !READ_ONLY.contains(&key.as_str()) {
+            return Err(FieldError::new(key, format!("Unknown field {}", key)));
+        }
+        members.insert(key.clone(), value.clone());
+    }
+    Ok(members)
+}
+
+/// `stored` as an object, the way the API shows it
+fn stored_value(stored: &HomeworkEntry) -> Map<String, Value> {
+    match serde_json::to_value(stored) {
+        Ok(Value::Object(map)) => map,
+        _ => Map::new(),
+    }
+}
+
+/// `name` in `wanted` as a `T`; `None` when it's missing
+fn field<T: DeserializeOwned>(wanted: &Value, name: &str) -> Result<Option<T>, FieldError> {
+    wanted
+        .get(name)
+        .filter(|v| !v.is_null())
+        .map(|v| serde_json::from_value(v.clone()))
+        .transpose()
+        .map_err(|e| FieldError::new(name, format!("{}: {}", name, e)))
+}
+
+/// A `YYYY-MM-DD` field
+fn date_field(wanted: &Value, name: &str) -> Result<Option<String>, FieldError> {
+    field::<String>(wanted, name)?
+        .map(|d| match NaiveDate::parse_from_str(&d, "%Y-%m-%d") {
+            Ok(day) => Ok(day.to_string()),
+            Err(_) => Err(FieldError::new(
+                name,
+                format!("{} must be YYYY-MM-DD", name),
+            )),
+        })
+        .transpose()
+}
+
+/// `value` when it differs from `stored`
+fn changed<T: PartialEq>(value: T, stored: &T) -> Option<T> {
+    (value != *stored).then_some(value)
+}
+
+/// The update that turns `stored` into `wanted`, after checking every field
+fn changes(stored: &HomeworkEntry, wanted: &Value) -> Result<EntryUpdate, FieldError> {
+    let current = stored_value(stored);
+    for name in READ_ONLY {
+        if wanted.get(*name) != current.get(*name) {
+            return Err(FieldError::new(name, format!("{} can't be changed", name)));
+        }
+    }
+
+    let date =
+        date_field(wanted, "date")?.ok_or_else(|| FieldError::new("date", "date is required"))?;
+    let difficulty = field::<u8>(wanted, "difficulty")?;
+    if difficulty.is_some_and(|d| !(1..=5).contains(&d)) {
+        return Err(FieldError::new(
+            "difficulty",
+            "Difficulty must be between 1 and 5",
+        ));
+    }
+    let color = field::<String>(wanted, "color")?;
+    if color.as_deref().is_some_and(|c| !is_hex_color(c)) {
+        return Err(FieldError::new("color", "Color must be #rrggbb"));
+    }
+    // Hidden past its due date, an entry would never show as due or overdue
+    let visible_from = date_field(wanted, "visible_from")?;
+    if visible_from
+        .as_deref()
+        .is_some_and(|from| from > date.as_str())
+    {
+        return Err(FieldError::new(
+            "visible_from",
+            "visible_from can't be after the entry's date",
+        ));
+    }
+    let grade = field::<String>(wanted, "grade")?.map(|g| g.trim().to_string());
+    match &grade {
+        Some(g) if g.is_empty() => {
+            return Err(FieldError::new("grade", "grade is empty; null clears it"));
+        }
+        Some(g) if g.chars().count() > MAX_GRADE_LEN => {
+            return Err(FieldError::new(
+                "grade",
+                format!("A grade is at most {} characters", MAX_GRADE_LEN),
+            ));
+        }
+        _ => {}
+    }
+
+    Ok(EntryUpdate {
+        date: changed(date, &stored.date),
+        completed: changed(
+            field(wanted, "completed")?.unwrap_or_default(),
+            &stored.completed,
+        ),
+        position: changed(
+            field(wanted, "position")?.unwrap_or_default(),
+            &stored.position,
+        ),
+        task: None,
+        estimated_minutes: changed(
+            field(wanted, "estimated_minutes")?,
+            &stored.estimated_minutes,
+        ),
+        actual_minutes: changed(field(wanted, "actual_minutes")?, &stored.actual_minutes),
+        difficulty: changed(difficulty, &stored.difficulty),
+        pinned: changed(field(wanted, "pinned")?.unwrap_or_default(), &stored.pinned),
+        color: changed(color.map(|c| c.to_lowercase()), &stored.color),
+        grade: changed(grade, &stored.grade),
+        visible_from: changed(visible_from, &stored.visible_from),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn stored() -> HomeworkEntry {
+        let mut entry = HomeworkEntry::new(
+            "verifica".to_string(),
+            "2025-01-20".to_string(),
+            "Storia".to_string(),
+            "Cap. 5".to_string(),
+        );
+        entry.pinned = true;
+        entry.color = Some("#ffaa00".to_string());
+        entry.estimated_minutes = Some(30);
+        entry
+    }
+
+    #[test]
+    fn test_merge_patch() {
+        // The examples of RFC 7396, appendix A
+        for (target, patch, result) in [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (
+                json!({"a": {"b": "c"}}),
+                json!({"a": {"b": "d", "c": null}}),
+                json!({"a": {"b": "d"}}),
+            ),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (
+                json!({"e": null}),
+                json!({"a": 1}),
+                json!({"e": null, "a": 1}),
+            ),
+            (
+                json!([1, 2]),
+                json!({"a": "b", "c": null}),
+                json!({"a": "b"}),
+            ),
+        ] {
+            let mut target = target;
+            merge_patch(&mut target, &patch);
+            assert_eq!(target, result);
+        }
+    }
+
+    #[test]
+    fn test_apply_patch() {
+        let entry = stored();
+        let update = apply_patch(
+            &entry,
+            &json!({"completed": true, "color": null, "difficulty": 3, "_links": {}}),
+        )
+        .unwrap();
+        assert_eq!(update.completed, Some(true));
+        assert_eq!(update.color, Some(None));
+        assert_eq!(update.difficulty, Some(Some(3)));
+        // Left out, so left alone
+        assert_eq!(update.pinned, None);
+        assert_eq!(update.estimated_minutes, None);
+        assert_eq!(update.date, None);
+
+        // A GET's body sent back as it was changes nothing
+        let same = apply_patch(&entry, &serde_json::to_value(&entry).unwrap()).unwrap();
+        assert_eq!(same.color, None);
+        assert_eq!(same.completed, None);
+
+        for (patch, field) in [
+            (json!({"colour": "#ffffff"}), "colour"),
+            (json!({"completed": "yes"}), "completed"),
+            (json!({"difficulty": 6}), "difficulty"),
+            (json!({"color": "red"}), "color"),
+            (json!({"date": "20/01/2025"}), "date"),
+            (json!({"grade": "  "}), "grade"),
+            (json!({"subject": "Arte"}), "subject"),
+            (json!({"date": null}), "date"),
+            (json!({"visible_from": "2025-01-21"}), "visible_from"),
+            (json!(["completed"]), ""),
+        ] {
+            assert_eq!(apply_patch(&entry, &patch).unwrap_err().field, field);
+        }
+    }
+
+    #[test]
+    fn test_apply_put() {
+        let entry = stored();
+        let update = apply_put(
+            &entry,
+            &json!({"id": entry.id, "date": "2025-01-22", "completed": true}),
+        )
+        .unwrap();
+        assert_eq!(update.date.as_deref(), Some("2025-01-22"));
+        assert_eq!(update.completed, Some(true));
+        // Left out of a PUT, so back to the defaults
+        assert_eq!(update.pinned, Some(false));
+        assert_eq!(update.color, Some(None));
+        assert_eq!(update.estimated_minutes, Some(None));
+
+        let same = apply_put(&entry, &serde_json::to_value(&entry).unwrap()).unwrap();
+        assert_eq!(same.pinned, None);
+        assert_eq!(same.color, None);
+
+        let error = apply_put(&entry, &json!({"completed": true})).unwrap_err();
+        assert_eq!(error.field, "date");
+        let error = apply_put(&entry, &json!({"date": "2025-01-20", "id": "other"})).unwrap_err();
+        assert_eq!(error.field, "id");
+    }
+}
@@ -0,0 +1,222 @@
+//! The "Start here" plan: what to work on today, in order, within the
+//! daily time budget.
+//!
+//! Overdue work comes first (oldest first), then today's homework (the
+//! "Do it" reminders for today and compiti due today or tomorrow), then study
+//! sessions for today and tomorrow. Each item takes its estimated minutes, or
+//! [`DEFAULT_ITEM_MINUTES`] without an estimate. Items are taken in that
+//! order while they fit in `daily_budget_minutes`; the rest go to `later`.
+
+use chrono::{Duration, NaiveDate};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+use crate::types::HomeworkEntry;
+
+/// Time assumed for an item without an estimate
+pub const DEFAULT_ITEM_MINUTES: u32 = 30;
+
+/// Unfinished work older than this is left out of the plan
+const OVERDUE_DAYS: i64 = 14;
+
+/// Why an item is in the plan
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum PlanReason {
+    Overdue,
+    Today,
+    Study,
+}
+
+/// One thing to do today
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlanItem {
+    pub id: String,
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    pub subject: String,
+    pub task: String,
+    /// The entry's date (`YYYY-MM-DD`)
+    pub date: String,
+    pub minutes: u32,
+    /// `minutes` came from an estimate rather than the default
+    pub estimated: bool,
+    pub reason: PlanReason,
+}
+
+/// Response of `GET /api/plan/today`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Plan {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub budget_minutes: u32,
+    /// Sum of `items[].minutes`
+    pub planned_minutes: u32,
+    /// In order; the first one is where to start
+    pub items: Vec<PlanItem>,
+    /// What didn't fit in the budget, in the same order
+    pub later: Vec<PlanItem>,
+}
+
+/// Build today's plan from every entry.
+pub fn build_plan(entries: &[HomeworkEntry], today: NaiveDate, budget_minutes: u32) -> Plan {
+    let by_id: HashMap<&str, &HomeworkEntry> = entries.iter().map(|e| (e.id.as_str(), e)).collect();
+    let day = |offset: i64| {
+        (today + Duration::days(offset))
+            .format("%Y-%m-%d")
+            .to_string()
+    };
+    let (oldest, yesterday, today_str, tomorrow) = (day(-OVERDUE_DAYS), day(-1), day(0), day(1));
+
+    let open = entries.iter().filter(|e| !e.completed);
+    let mut overdue: Vec<&HomeworkEntry> = open
+        .clone()
+        .filter(|e| matches!(e.entry_type.as_str(), "compiti" | "lavoro" | "studio"))
+        .filter(|e| e.date >= oldest && e.date <= yesterday)
+        .collect();
+    overdue.sort_by(|a, b| a.date.cmp(&b.date));
+    let homework = open.clone().filter(|e| match e.entry_type.as_str() {
+        "lavoro" => e.date == today_str,
+        "compiti" => e.date == today_str || e.date == tomorrow,
+        _ => false,
+    });
+    let study =
+        open.filter(|e| e.entry_type == "studio" && (e.date == today_str || e.date == tomorrow));
+
+    let candidates = overdue
+        .into_iter()
+        .map(|e| (e, PlanReason::Overdue))
+        .chain(homework.map(|e| (e, PlanReason::Today)))
+        .chain(study.map(|e| (e, PlanReason::Study)));
+
+    let mut plan = Plan {
+        date: today_str.clone(),
+        budget_minutes,
+        planned_minutes: 0,
+        items: Vec::new(),
+        later: Vec::new(),
+    };
+    // A compiti and its "Do it" reminder are the same work; plan it once
+    let mut covered: HashSet<&str> = HashSet::new();
+    for (entry, reason) in candidates {
+        let parent = entry
+            .parent_id
+            .as_deref()
+            .filter(|_| entry.entry_type == "lavoro");
+        if covered.contains(entry.id.as_str()) || parent.is_some_and(|p| covered.contains(p)) {
+            continue;
+        }
+        covered.insert(&entry.id);
+        if let Some(parent) = parent {
+            covered.insert(parent);
+        }
+
+        // A reminder has no estimate of its own; use its compiti's
+        let estimate = entry
+            .estimated_minutes
+            .or_else(|| parent.and_then(|p| by_id.get(p)?.estimated_minutes));
+        let item = PlanItem {
+            id: entry.id.clone(),
+            entry_type: entry.entry_type.clone(),
+            subject: entry.subject.clone(),
+            task: entry.task.clone(),
+            date: entry.date.clone(),
+            minutes: estimate.unwrap_or(DEFAULT_ITEM_MINUTES),
+            estimated: estimate.is_some(),
+            reason,
+        };
+        // Always start with something, even if it alone is over budget
+        if plan.items.is_empty() || plan.planned_minutes + item.minutes <= budget_minutes {
+            plan.planned_minutes += item.minutes;
+            plan.items.push(item);
+        } else {
+            plan.later.push(item);
+        }
+    }
+    plan
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(id: &str, entry_type: &str, date: &str, minutes: Option<u32>) -> HomeworkEntry {
+        let mut entry = HomeworkEntry::with_id(
+            id.to_string(),
+            entry_type.to_string(),
+            date.to_string(),
+            "Matematica".to_string(),
+            format!("Task {}", id),
+        );
+        entry.estimated_minutes = minutes;
+        entry
+    }
+
+    fn ids(items: &[PlanItem]) -> Vec<&str> {
+        items.iter().map(|i| i.id.as_str()).collect()
+    }
+
+    #[test]
+    fn test_build_plan_orders_overdue_today_study() {
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let mut done = entry("done", "compiti", "2025-01-16", None);
+        done.completed = true;
+        let entries = vec![
+            entry("study", "studio", "2025-01-16", Some(20)),
+            entry("due-tomorrow", "compiti", "2025-01-16", Some(40)),
+            entry("late", "compiti", "2025-01-13", None),
+            entry("later-late", "lavoro", "2025-01-14", Some(10)),
+            entry("too-old", "compiti", "2024-12-01", None),
+            entry("test", "verifica", "2025-01-15", None),
+            entry("next-week", "compiti", "2025-01-22", None),
+            done,
+        ];
+
+        let plan = build_plan(&entries, today, 120);
+        assert_eq!(plan.date, "2025-01-15");
+        assert_eq!(
+            ids(&plan.items),
+            vec!["late", "later-late", "due-tomorrow", "study"]
+        );
+        assert_eq!(plan.items[0].reason, PlanReason::Overdue);
+        assert_eq!(plan.items[0].minutes, DEFAULT_ITEM_MINUTES);
+        assert!(!plan.items[0].estimated);
+        assert_eq!(plan.items[2].reason, PlanReason::Today);
+        assert_eq!(plan.items[3].reason, PlanReason::Study);
+        assert_eq!(plan.planned_minutes, 100);
+        assert!(plan.later.is_empty());
+    }
+
+    #[test]
+    fn test_build_plan_respects_budget() {
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let entries = vec![
+            entry("big", "compiti", "2025-01-14", Some(90)),
+            entry("medium", "compiti", "2025-01-16", Some(45)),
+            entry("small", "studio", "2025-01-15", Some(15)),
+        ];
+        let plan = build_plan(&entries, today, 60);
+        // The first item goes in even over budget; what fits after it follows
+        assert_eq!(ids(&plan.items), vec!["big"]);
+        assert_eq!(ids(&plan.later), vec!["medium", "small"]);
+
+        let plan = build_plan(&entries, today, 110);
+        assert_eq!(ids(&plan.items), vec!["big", "small"]);
+        assert_eq!(plan.planned_minutes, 105);
+        assert_eq!(ids(&plan.later), vec!["medium"]);
+    }
+
+    #[test]
+    fn test_build_plan_counts_compiti_and_reminder_once() {
+        let today = NaiveDate::from_ymd_opt(2025, 1, 15).unwrap();
+        let compiti = entry("compiti", "compiti", "2025-01-16", Some(50));
+        let mut reminder = entry("reminder", "lavoro", "2025-01-15", None);
+        reminder.parent_id = Some("compiti".to_string());
+
+        let plan = build_plan(&[reminder, compiti], today, 120);
+        assert_eq!(ids(&plan.items), vec!["reminder"]);
+        // The reminder borrows its compiti's estimate
+        assert_eq!(plan.items[0].minutes, 50);
+        assert!(plan.items[0].estimated);
+    }
+}
@@ -0,0 +1,208 @@
+//! Absences and disciplinary notes from the register, mirrored locally.
+//!
+//! `raschietto fetch --export absences,notes` writes `absences_*.json` and
+//! `notes_*.json` into data/ next to the agenda export. Each file is a
+//! snapshot of the whole portal page, so only the newest of each kind is
+//! imported, replacing the previous one. The tables are never edited here;
+//! `/register` and `/api/absences`, `/api/notes` only read them.
+
+use anyhow::{Context, Result};
+use rusqlite::Connection;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+use crate::db;
+use crate::parser::normalize_italian_date;
+use crate::types::{AbsencesSnapshot, NotesSnapshot};
+
+pub const ABSENCES_PREFIX: &str = "absences_";
+pub const NOTES_PREFIX: &str = "notes_";
+
+/// Whether `path` is a snapshot raschietto wrote
+pub fn is_register_file(path: &Path) -> bool {
+    path.file_name().and_then(|n| n.to_str()).is_some_and(|n| {
+        (n.starts_with(ABSENCES_PREFIX) || n.starts_with(NOTES_PREFIX)) && n.ends_with(".json")
+    })
+}
+
+/// The `<prefix>*.json` files in `data_dir`, most recently written first
+fn snapshots(data_dir: &Path, prefix: &str) -> Result<Vec<PathBuf>> {
+    if !data_dir.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files: Vec<(SystemTime, PathBuf)> = std::fs::read_dir(data_dir)
+        .with_context(|| format!("Failed to read {}", data_dir.display()))?
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            is_register_file(p)
+                && p.file_name()
+                    .and_then(|n| n.to_str())
+                    .is_some_and(|n| n.starts_with(prefix))
+        })
+        .map(|p| {
+            let modified = p
+                .metadata()
+                .and_then(|m| m.modified())
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            (modified, p)
+        })
+        .collect();
+    // Same mtime (e.g. copied together): the later name wins
+    files.sort();
+    Ok(files.into_iter().rev().map(|(_, path)| path).collect())
+}
+
+/// Read an `absences_*.json` snapshot, with dates as YYYY-MM-DD
+pub fn load_absences(path: &Path) -> Result<AbsencesSnapshot> {
+    let mut snapshot: AbsencesSnapshot = read_json(path)?;
+    for absence in &mut snapshot.absences {
+        absence.date = normalize_italian_date(&absence.date);
+    }
+    Ok(snapshot)
+}
+
+/// Read a `notes_*.json` snapshot, with dates as YYYY-MM-DD
+pub fn load_notes(path: &Path) -> Result<NotesSnapshot> {
+    let mut snapshot: NotesSnapshot = read_json(path)?;
+    for note in &mut snapshot.notes {
+        note.date = normalize_italian_date(&note.date);
+    }
+    Ok(snapshot)
+}
+
+fn read_json<T: serde::de::DeserializeOwned>(path: &Path) -> Result<T> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    serde_json::from_str(&json).with_context(|| format!("Invalid register file {}", path.display()))
+}
+
+/// Replace the absences and notes with the newest snapshots in `data_dir`.
+/// A kind without any readable file is left alone. Returns how many of
+/// each were imported.
+pub fn import_newest(conn: &Connection, data_dir: &Path) -> Result<(Option<usize>, Option<usize>)> {
+    let absences = import_first(data_dir, ABSENCES_PREFIX, |path| {
+        Ok(db::replace_absences(conn, &load_absences(path)?)?)
+    })?;
+    let notes = import_first(data_dir, NOTES_PREFIX, |path| {
+        Ok(db::replace_discipline_notes(conn, &load_notes(path)?)?)
+    })?;
+    if absences.is_some() || notes.is_some() {
+        info!(?absences, ?notes, "Mirrored register");
+    }
+    Ok((absences, notes))
+}
+
+/// Import the newest `<prefix>*.json` that `import` accepts. A file that
+/// can't be read (half-written, not JSON) is logged and the next older one
+/// tried, so it doesn't hold up this kind or the other.
+fn import_first(
+    data_dir: &Path,
+    prefix: &str,
+    import: impl Fn(&Path) -> Result<usize>,
+) -> Result<Option<usize>> {
+    for path in snapshots(data_dir, prefix)? {
+        match import(&path) {
+            Ok(count) => return Ok(Some(count)),
+            Err(e) => warn!(
+                file = %path.display(),
+                error = %format!("{:#}", e),
+                "Skipped register snapshot"
+            ),
+        }
+    }
+    Ok(None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn setup_test_db() -> (TempDir, Connection) {
+        let temp_dir = TempDir::new().unwrap();
+        let migrations = Path::new(env!("CARGO_MANIFEST_DIR")).join("db/migrations");
+        let conn = db::init_db(&temp_dir.path().join("test.db"), &migrations).unwrap();
+        (temp_dir, conn)
+    }
+
+    #[test]
+    fn test_is_register_file() {
+        assert!(is_register_file(Path::new("data/absences_20250115.json")));
+        assert!(is_register_file(Path::new("notes_mario_2025-01-08.json")));
+        assert!(!is_register_file(Path::new("data/grades_20250115.json")));
+        assert!(!is_register_file(Path::new(
+            "data/notes_20250115.json.part"
+        )));
+        assert!(!is_register_file(Path::new("data/export_20250115.xls")));
+    }
+
+    #[test]
+    fn test_import_newest_replaces_snapshot() {
+        let (temp_dir, conn) = setup_test_db();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        assert_eq!(import_newest(&conn, &data_dir).unwrap(), (None, None));
+
+        std::fs::write(
+            data_dir.join("absences_20250115.json"),
+            r#"{"fetched":"2025-01-15","absences":[
+                {"date":"13/01/2025","kind":"Assenza","justified":false},
+                {"date":"15/01/2025","kind":"Ritardo","justified":true,"note":"09:05"}
+            ]}"#,
+        )
+        .unwrap();
+        std::fs::write(
+            data_dir.join("notes_20250115.json"),
+            r#"{"fetched":"2025-01-15","notes":[
+                {"date":"14/01/2025","teacher":"ROSSI MARIA","text":"Disturba la lezione"}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(import_newest(&conn, &data_dir).unwrap(), (Some(2), Some(1)));
+
+        let absences = db::get_absences(&conn).unwrap();
+        assert_eq!(absences.fetched.as_deref(), Some("2025-01-15"));
+        let dates: Vec<&str> = absences.absences.iter().map(|a| a.date.as_str()).collect();
+        assert_eq!(dates, vec!["2025-01-15", "2025-01-13"]);
+        assert!(absences.absences[0].justified);
+        assert_eq!(absences.absences[0].note, "09:05");
+        let notes = db::get_discipline_notes(&conn).unwrap();
+        assert_eq!(notes.notes[0].teacher, "ROSSI MARIA");
+        assert_eq!(notes.notes[0].kind, "");
+
+        // A later snapshot replaces the earlier one (the absence got justified)
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(
+            data_dir.join("absences_20250120.json"),
+            r#"{"fetched":"2025-01-20","absences":[
+                {"date":"13/01/2025","kind":"Assenza","justified":true}
+            ]}"#,
+        )
+        .unwrap();
+        assert_eq!(import_newest(&conn, &data_dir).unwrap(), (Some(1), Some(1)));
+        let absences = db::get_absences(&conn).unwrap();
+        assert_eq!(absences.fetched.as_deref(), Some("2025-01-20"));
+        assert_eq!(absences.absences.len(), 1);
+        assert!(absences.absences[0].justified);
+
+        // An unreadable newer file falls back to the one before it, and
+        // doesn't stop the absences from being imported
+        std::thread::sleep(std::time::Duration::from_millis(20));
+        std::fs::write(data_dir.join("notes_20250121.json"), "not json").unwrap();
+        std::fs::write(
+            data_dir.join("absences_20250121.json"),
+            r#"{"fetched":"2025-01-21","absences":[]}"#,
+        )
+        .unwrap();
+        assert_eq!(import_newest(&conn, &data_dir).unwrap(), (Some(0), Some(1)));
+
+        // An empty page still says when it was read
+        let absences = db::get_absences(&conn).unwrap();
+        assert_eq!(absences.fetched.as_deref(), Some("2025-01-21"));
+        assert!(absences.absences.is_empty());
+        let notes = db::get_discipline_notes(&conn).unwrap();
+        assert_eq!(notes.fetched.as_deref(), Some("2025-01-15"));
+    }
+}
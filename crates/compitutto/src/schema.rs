@@ -0,0 +1,285 @@
+//! The JSON format for homework entries, and validation against it.
+//!
+//! Entries as JSON are what `GET /api/entries` returns. The format is
+//! published as a JSON Schema in `schema/homework.schema.json`, so scripts
+//! and other tools can produce files that compitutto accepts.
+//! `compitutto validate <file>` checks a hand-edited or generated file and
+//! points at the line or field that's wrong, rather than failing somewhere
+//! later with a bare serde error.
+
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use serde_json::Value;
+use std::collections::HashSet;
+use std::fmt;
+use std::path::Path;
+
+use crate::types::{is_hex_color, HomeworkEntry};
+
+/// The published schema, for `compitutto validate --print-schema`.
+pub const ENTRIES_SCHEMA: &str = include_str!("../schema/homework.schema.json");
+
+/// Fields an entry may have; anything else is most likely a typo.
+const KNOWN_FIELDS: &[&str] = &[
+    "id",
+    "source_id",
+    "type",
+    "date",
+    "end_date",
+    "subject",
+    "topic",
+    "task",
+    "completed",
+    "position",
+    "parent_id",
+    "created_at",
+    "updated_at",
+    "estimated_minutes",
+    "actual_minutes",
+    "difficulty",
+    "pinned",
+    "color",
+    "grade",
+    "source_date",
+    "assigned_at",
+    "teacher",
+    "visible_from",
+    "source_file",
+    // Added by the API (`hypermedia`), ignored when read back
+    "_links",
+];
+
+/// One problem found in an entries file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ValidationError {
+    /// Where in the document, e.g. `[3].date` (empty for the whole document)
+    pub path: String,
+    /// 1-based line, when the JSON parser knows it
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ValidationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match (self.line, self.path.is_empty()) {
+            (Some(line), true) => write!(f, "line {}: {}", line, self.message),
+            (Some(line), false) => write!(f, "line {}, {}: {}", line, self.path, self.message),
+            (None, true) => write!(f, "{}", self.message),
+            (None, false) => write!(f, "{}: {}", self.path, self.message),
+        }
+    }
+}
+
+impl ValidationError {
+    fn at(path: String, message: impl Into<String>) -> Self {
+        Self {
+            path,
+            line: None,
+            message: message.into(),
+        }
+    }
+}
+
+/// Check an entries document, returning the entries or every problem found.
+///
+/// Syntax and type errors stop at the first one (serde can't go on past it)
+/// and carry a line number. Past that, every entry is checked and all
+/// problems are reported together.
+pub fn validate(text: &str) -> std::result::Result<Vec<HomeworkEntry>, Vec<ValidationError>> {
+    let mut de = serde_json::Deserializer::from_str(text);
+    let entries: Vec<HomeworkEntry> = match serde_path_to_error::deserialize(&mut de) {
+        Ok(entries) => entries,
+        Err(e) => {
+            let path = e.path().to_string();
+            let inner = e.into_inner();
+            return Err(vec![ValidationError {
+                path: if path == "." { String::new() } else { path },
+                line: Some(inner.line()),
+                message: strip_position(&inner.to_string()),
+            }]);
+        }
+    };
+    if let Err(e) = de.end() {
+        return Err(vec![ValidationError {
+            path: String::new(),
+            line: Some(e.line()),
+            message: strip_position(&e.to_string()),
+        }]);
+    }
+
+    let mut errors = Vec::new();
+
+    // Unknown fields are accepted by serde, so look at the raw objects
+    if let Ok(Value::Array(items)) = serde_json::from_str::<Value>(text) {
+        for (i, item) in items.iter().enumerate() {
+            let Value::Object(fields) = item else {
+                continue;
+            };
+            for key in fields.keys() {
+                if !KNOWN_FIELDS.contains(&key.as_str()) {
+                    errors.push(ValidationError::at(
+                        format!("[{}].{}", i, key),
+                        "unknown field",
+                    ));
+                }
+            }
+        }
+    }
+
+    let mut seen = HashSet::new();
+    for (i, entry) in entries.iter().enumerate() {
+        let field = |name: &str| format!("[{}].{}", i, name);
+        if entry.id.trim().is_empty() {
+            errors.push(ValidationError::at(field("id"), "must not be empty"));
+        } else if !seen.insert(entry.id.as_str()) {
+            errors.push(ValidationError::at(
+                field("id"),
+                format!("duplicate id {:?}", entry.id),
+            ));
+        }
+        if entry.entry_type.trim().is_empty() {
+            errors.push(ValidationError::at(field("type"), "must not be empty"));
+        }
+        // Dates are compared as strings, so they must be zero-padded too
+        for (name, value) in [
+            ("date", Some(&entry.date)),
+            ("end_date", entry.end_date.as_ref()),
+            ("source_date", entry.source_date.as_ref()),
+            ("visible_from", entry.visible_from.as_ref()),
+        ] {
+            let Some(value) = value else { continue };
+            let date = NaiveDate::parse_from_str(value, "%Y-%m-%d");
+            if date.map(|d| d.to_string()).as_deref() != Ok(value.as_str()) {
+                errors.push(ValidationError::at(
+                    field(name),
+                    format!("{:?} is not a YYYY-MM-DD date", value),
+                ));
+            }
+        }
+        for (name, value) in [
+            ("created_at", &entry.created_at),
+            ("updated_at", &entry.updated_at),
+        ] {
+            if !value.is_empty() && chrono::DateTime::parse_from_rfc3339(value).is_err() {
+                errors.push(ValidationError::at(
+                    field(name),
+                    format!("{:?} is not an RFC 3339 timestamp", value),
+                ));
+            }
+        }
+        if let Some(difficulty) = entry.difficulty {
+            if !(1..=5).contains(&difficulty) {
+                errors.push(ValidationError::at(
+                    field("difficulty"),
+                    format!("{} is outside 1-5", difficulty),
+                ));
+            }
+        }
+        if let Some(color) = entry.color.as_deref().filter(|c| !is_hex_color(c)) {
+            errors.push(ValidationError::at(
+                field("color"),
+                format!("{:?} is not a #rrggbb color", color),
+            ));
+        }
+    }
+
+    if errors.is_empty() {
+        Ok(entries)
+    } else {
+        Err(errors)
+    }
+}
+
+/// Read and validate an entries file.
+pub fn load_entries(path: &Path) -> Result<Vec<HomeworkEntry>> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read entries file: {}", path.display()))?;
+    match validate(&text) {
+        Ok(entries) => Ok(entries),
+        Err(errors) => {
+            let lines: Vec<String> = errors.iter().map(|e| format!("  {}", e)).collect();
+            bail!(
+                "Invalid entries file {}:\n{}",
+                path.display(),
+                lines.join("\n")
+            )
+        }
+    }
+}
+
+/// serde_json appends " at line L column C"; the line is reported separately.
+fn strip_position(message: &str) -> String {
+    match message.rfind(" at line ") {
+        Some(i) => message[..i].to_string(),
+        None => message.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_accepts_api_output() {
+        let mut entry = HomeworkEntry::new(
+            "compiti".to_string(),
+            "2025-01-15".to_string(),
+            "Matematica".to_string(),
+            "Pag. 10".to_string(),
+        );
+        entry.difficulty = Some(3);
+        let text = serde_json::to_string_pretty(&vec![entry.clone()]).unwrap();
+        assert_eq!(validate(&text).unwrap(), vec![entry]);
+
+        // Every documented property is one the validator knows
+        let schema: Value = serde_json::from_str(ENTRIES_SCHEMA).unwrap();
+        let properties = schema["$defs"]["entry"]["properties"].as_object().unwrap();
+        let mut documented: Vec<&str> = properties.keys().map(String::as_str).collect();
+        let mut known = KNOWN_FIELDS.to_vec();
+        documented.sort();
+        known.sort();
+        assert_eq!(documented, known);
+    }
+
+    #[test]
+    fn test_validate_reports_line_and_field() {
+        let text = "[\n  {\"id\": \"a\", \"type\": \"compiti\", \"date\": \"2025-01-15\",\n   \
+                    \"subject\": \"Storia\", \"task\": 5}\n]";
+        let errors = validate(text).unwrap_err();
+        assert_eq!(errors.len(), 1);
+        assert_eq!(errors[0].path, "[0].task");
+        assert_eq!(errors[0].line, Some(3));
+        assert!(errors[0]
+            .to_string()
+            .starts_with("line 3, [0].task: invalid type"));
+
+        let errors = validate("[{\"id\": \"a\",]").unwrap_err();
+        assert_eq!(errors[0].line, Some(1));
+    }
+
+    #[test]
+    fn test_validate_collects_entry_problems() {
+        let text = r#"[
+            {"id": "a", "type": "compiti", "date": "2025-1-15", "subject": "", "task": "x"},
+            {"id": "a", "type": "", "date": "2025-02-30", "subject": "", "task": "y",
+             "difficulty": 9, "color": "red", "complete": true}
+        ]"#;
+        let errors: Vec<String> = validate(text)
+            .unwrap_err()
+            .iter()
+            .map(|e| e.to_string())
+            .collect();
+        assert_eq!(
+            errors,
+            vec![
+                "[1].complete: unknown field",
+                "[0].date: \"2025-1-15\" is not a YYYY-MM-DD date",
+                "[1].id: duplicate id \"a\"",
+                "[1].type: must not be empty",
+                "[1].date: \"2025-02-30\" is not a YYYY-MM-DD date",
+                "[1].difficulty: 9 is outside 1-5",
+                "[1].color: \"red\" is not a #rrggbb color",
+            ]
+        );
+    }
+}
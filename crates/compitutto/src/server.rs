@@ -1,28 +1,75 @@
 use axum::{
-    extract::{Path as AxumPath, State},
-    http::StatusCode,
-    response::{Html, IntoResponse},
-    routing::{delete, get, post},
+    body::Bytes,
+    extract::{DefaultBodyLimit, Path as AxumPath, Query, Request, State},
+    http::{header, HeaderMap, Method, StatusCode},
+    middleware::{self, Next},
+    response::{
+        sse::{Event, KeepAlive, Sse},
+        Html, IntoResponse, Redirect, Response,
+    },
+    routing::{delete, get, post, put},
     Json, Router,
 };
+use chrono::{Datelike, NaiveDate};
+use futures_util::stream;
 use notify_debouncer_mini::{new_debouncer, notify::RecursiveMode, DebounceEventResult};
 use rusqlite::Connection;
 use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashSet};
+use std::convert::Infallible;
 use std::net::SocketAddr;
 use std::path::Path;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::time::Duration;
-use tracing::{debug, error, info};
+use tokio::sync::broadcast::error::RecvError;
+use tracing::{debug, error, info, warn};
 
+use crate::activities;
+use crate::agenda;
+use crate::capture;
+use crate::cors;
 use crate::data::{self, generate_study_sessions, generate_work_reminder, is_test_or_quiz};
 use crate::db::{self, EntryUpdate};
+use crate::error::{ApiError, ApiJson};
+use crate::export;
+use crate::features::{self, Feature};
+use crate::forecast;
+#[cfg(feature = "graphql")]
+use crate::graphql;
 use crate::html;
-use crate::types::HomeworkEntry;
+use crate::hypermedia::{self, Linked};
+use crate::jobs;
+use crate::live::{Live, LiveEvent};
+use crate::ocr;
+use crate::overload::{self, CheckedEntry};
+use crate::patch;
+use crate::plan;
+use crate::register;
+use crate::streak;
+use crate::subjects;
+use crate::teachers;
+use crate::timetable::Timetable;
+use crate::types::{
+    Activity, Attachment, Completion, DaysCompletedReport, GoalsReport, HomeworkEntry,
+    LinkRelation, NewAttachment, PendingImport, Settings, SettingsUpdate, SourceFile,
+    StatsComparison, SubjectIcon, SubjectsReport, TeacherReport, MAX_REACTION_AUTHOR,
+    MAX_REACTION_MESSAGE, REACTION_EMOJI,
+};
+use crate::version;
 
 /// Application state shared across requests
 pub struct AppState {
     pub conn: Mutex<Connection>,
+    pub live: Arc<Live>,
+    /// Subject rules file, where merges record their aliases
+    pub rules_path: PathBuf,
+    /// Where `POST /api/capture` saves photos of the diary
+    pub captures_dir: PathBuf,
+    /// `serve --read-only`: refuse every change and hide the editing controls
+    pub read_only: bool,
+    /// Latest release found by the update check
+    pub latest_release: Mutex<Option<version::Release>>,
 }
 
 impl AppState {
@@ -30,6 +77,11 @@ impl AppState {
     pub fn new(conn: Connection) -> Self {
         Self {
             conn: Mutex::new(conn),
+            live: Arc::new(Live::default()),
+            rules_path: PathBuf::from(subjects::RULES_FILE),
+            captures_dir: PathBuf::from("data").join(capture::CAPTURES_DIR),
+            read_only: false,
+            latest_release: Mutex::new(None),
         }
     }
 }
@@ -43,13 +95,30 @@ pub struct CreateEntryRequest {
     pub subject: String,
     pub task: String,
     pub position: Option<i32>,
+    pub estimated_minutes: Option<u32>,
+}
+
+/// An entry as returned by PATCH and PUT, with `day_completed` when ticking
+/// it off finished its day (the page celebrates)
+#[derive(Debug, Serialize)]
+pub struct UpdatedEntry<T> {
+    #[serde(flatten)]
+    pub entry: T,
+    #[serde(skip_serializing_if = "std::ops::Not::not")]
+    pub day_completed: bool,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct UpdateEntryRequest {
-    pub date: Option<String>,
-    pub completed: Option<bool>,
-    pub position: Option<i32>,
+pub struct MoveEntryRequest {
+    pub date: String,
+    /// Place on the target day (0 = top); absent means the bottom
+    pub index: Option<usize>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DuplicateEntryRequest {
+    /// Day the copy goes on (`YYYY-MM-DD`)
+    pub date: String,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -65,10 +134,268 @@ pub struct CascadeDeleteResponse {
     pub deleted_count: usize,
 }
 
+/// The list view's date window and the filter bar's chips. `subject` and
+/// `type` take comma-separated lists, `status` is `pending` or `done`.
+/// `all=true` widens the window to every stored date.
+#[derive(Debug, Default, Deserialize)]
+pub struct ListQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    #[serde(default)]
+    pub all: bool,
+    /// Include entries hidden until a later day
+    #[serde(default)]
+    pub hidden: bool,
+    pub subject: Option<String>,
+    #[serde(rename = "type")]
+    pub entry_type: Option<String>,
+    pub status: Option<String>,
+}
+
+/// Filters shared by `/api/entries` and the CSV/ICS exports. `subject`,
+/// `type` and `topic` take comma-separated lists; `q` searches tasks and
+/// photo text; `status` is `pending` or `done`.
+#[derive(Debug, Default, Deserialize)]
+pub struct EntriesQuery {
+    pub from: Option<String>,
+    pub to: Option<String>,
+    pub subject: Option<String>,
+    #[serde(rename = "type")]
+    pub entry_type: Option<String>,
+    pub topic: Option<String>,
+    pub q: Option<String>,
+    pub status: Option<String>,
+}
+
+/// Comma-separated query values, trimmed, without empty ones
+fn query_list(value: &Option<String>) -> Vec<String> {
+    value
+        .iter()
+        .flat_map(|v| v.split(','))
+        .map(|v| v.trim().to_string())
+        .filter(|v| !v.is_empty())
+        .collect()
+}
+
+/// `status=pending` or `status=done`; both, or neither, means any
+fn status_filter(status: &Option<String>) -> Result<Option<bool>, ApiError> {
+    let mut pending = false;
+    let mut done = false;
+    for value in query_list(status) {
+        match value.to_lowercase().as_str() {
+            "pending" => pending = true,
+            "done" => done = true,
+            _ => {
+                return Err(ApiError::validation("status must be pending or done")
+                    .with_details(serde_json::json!({ "status": value })))
+            }
+        }
+    }
+    Ok((pending != done).then_some(done))
+}
+
+impl EntriesQuery {
+    fn filter(&self) -> Result<db::EntryFilter, ApiError> {
+        let date = |value: &Option<String>| match value.as_deref() {
+            Some(d) if NaiveDate::parse_from_str(d, "%Y-%m-%d").is_err() => Err(
+                ApiError::validation("Invalid date format, expected YYYY-MM-DD"),
+            ),
+            _ => Ok(value.clone()),
+        };
+        Ok(db::EntryFilter {
+            from: date(&self.from)?,
+            to: date(&self.to)?,
+            subjects: query_list(&self.subject),
+            types: query_list(&self.entry_type),
+            topics: query_list(&self.topic),
+            q: self.q.clone(),
+            completed: status_filter(&self.status)?,
+        })
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct GoalsQuery {
+    /// Any day of the week to report (default: today)
+    pub week: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct DaysCompletedQuery {
+    /// `YYYY-MM` (default: this month)
+    pub month: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ForecastQuery {
+    /// Weeks to forecast, from the current one (default 4, at most 12)
+    pub weeks: Option<u32>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct LiveQuery {
+    /// Per-tab id, so several tabs of one person count once
+    pub session: Option<String>,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CompareQuery {
+    /// First period, `YYYY-MM-DD..YYYY-MM-DD` (default: first quadrimestre)
+    pub a: Option<String>,
+    /// Second period (default: second quadrimestre)
+    pub b: Option<String>,
+}
+
+/// `/stats/teachers` and `/api/stats/teachers`
+#[derive(Debug, Default, Deserialize)]
+pub struct TeachersQuery {
+    /// `YYYY-MM-DD..YYYY-MM-DD` (default: this school year)
+    pub period: Option<String>,
+    /// Send the page as a file to save or share
+    #[serde(default)]
+    pub download: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CalendarQuery {
+    pub year: i32,
+    pub month: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CalendarMonthResponse {
+    pub year: i32,
+    pub month: u32,
+    pub entries: BTreeMap<String, Vec<html::calendar::CalendarEntry>>,
+    /// Holiday name by date, for shading
+    pub holidays: BTreeMap<String, String>,
+    /// After-school activities by date
+    #[serde(default)]
+    pub activities: BTreeMap<String, Vec<Activity>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateLinkRequest {
+    pub to_id: String,
+    #[serde(default)]
+    pub relation: LinkRelation,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateReactionRequest {
+    /// One of [`REACTION_EMOJI`]
+    #[serde(default)]
+    pub emoji: Option<String>,
+    #[serde(default)]
+    pub message: Option<String>,
+    /// Who left it, e.g. "Mamma"
+    #[serde(default)]
+    pub author: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MergeSubjectsRequest {
+    pub from: String,
+    pub into: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct FixTextRequest {
+    pub find: String,
+    #[serde(default)]
+    pub replace: String,
+    /// Only entries of this subject (any case)
+    #[serde(default)]
+    pub subject: Option<String>,
+    /// Only report what would change
+    #[serde(default)]
+    pub dry_run: bool,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateResourceRequest {
+    /// Defaults to the url
+    #[serde(default)]
+    pub title: String,
+    pub url: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SetIconRequest {
+    /// An emoji, at most `subjects::MAX_ICON_CHARS` characters
+    pub icon: String,
+}
+
+/// Share links last a week unless asked otherwise, and at most this long
+const SHARE_DEFAULT_DAYS: u32 = 7;
+const SHARE_MAX_DAYS: u32 = 90;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct CreateShareRequest {
+    /// Days until the link stops working (default 7, at most 90)
+    #[serde(default)]
+    pub days: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ShareLinkResponse {
+    /// Path of the share page, token included
+    pub url: String,
+    pub expires_at: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SimpleQuery {
+    /// `true` makes the simple view this device's main page, `false` goes
+    /// back to the full one
+    pub device: Option<bool>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ShareQuery {
+    #[serde(default)]
+    pub token: String,
+}
+
+/// What's known about a photographed diary page; all optional
+#[derive(Debug, Default, Deserialize)]
+pub struct CaptureQuery {
+    /// Due date (default: today)
+    pub date: Option<String>,
+    #[serde(default)]
+    pub subject: String,
+    /// Entry type (default: `compiti`)
+    #[serde(rename = "type")]
+    pub entry_type: Option<String>,
+}
+
+/// The staged draft and its photo
+#[derive(Debug, Serialize, Deserialize)]
+pub struct CaptureResponse {
+    pub draft: PendingImport,
+    pub photo: Attachment,
+}
+
+/// One side of a link, seen from the entry whose links were asked for
+#[derive(Debug, Serialize, Deserialize)]
+pub struct LinkedEntry {
+    pub link_id: i64,
+    pub relation: LinkRelation,
+    /// The asked-for entry is the link's `from` side (for `depends_on`: it
+    /// depends on `entry`; otherwise `entry` depends on it)
+    pub outgoing: bool,
+    pub entry: HomeworkEntry,
+}
+
 /// Create the router with all routes
 pub fn create_router(state: Arc<AppState>) -> Router {
-    Router::new()
-        .route("/", get(index_handler))
+    let router = Router::new()
+        .route("/", get(dashboard_handler))
+        .route("/list", get(list_page_handler))
+        .route("/simple", get(simple_page_handler))
+        .route("/fragments/list", get(list_fragment_handler))
+        .route("/api", get(api_index_handler))
         .route(
             "/api/entries",
             get(entries_handler).post(create_entry_handler),
@@ -76,14 +403,106 @@ pub fn create_router(state: Arc<AppState>) -> Router {
         .route(
             "/api/entries/{id}",
             get(get_entry_handler)
-                .put(update_entry_handler)
+                .put(put_entry_handler)
+                .patch(patch_entry_handler)
                 .delete(delete_entry_handler),
         )
+        .route("/api/export.csv", get(export_csv_handler))
+        .route("/api/export.ics", get(export_ics_handler))
         .route("/api/entries/{id}/children", get(get_children_handler))
+        .route(
+            "/api/entries/{id}/attachments",
+            get(entry_attachments_handler),
+        )
+        .route("/api/attachments", get(attachments_handler))
+        .route("/api/attachments/{id}/file", get(attachment_file_handler))
+        .route("/api/source-files", get(source_files_handler))
+        .route("/api/source-files/{name}", get(source_file_handler))
+        .route(
+            "/api/entries/{id}/source-file",
+            get(entry_source_file_handler),
+        )
+        .route(
+            "/api/capture",
+            post(capture_handler).layer(DefaultBodyLimit::max(capture::MAX_PHOTO_BYTES)),
+        )
+        .route("/api/entries/{id}/move", post(move_entry_handler))
+        .route("/api/entries/{id}/duplicate", post(duplicate_entry_handler))
         .route("/api/entries/{id}/cascade", delete(cascade_delete_handler))
+        .route(
+            "/api/entries/{id}/links",
+            get(entry_links_handler).post(create_link_handler),
+        )
+        .route("/api/entries/{id}/share", post(create_share_handler))
+        .route("/share/{stable_id}", get(share_page_handler))
+        .route("/api/links", get(links_handler))
+        .route("/api/links/{id}", delete(delete_link_handler))
+        .route(
+            "/api/entries/{id}/reactions",
+            get(entry_reactions_handler).post(create_reaction_handler),
+        )
+        .route("/api/reactions", get(reactions_handler))
+        .route("/api/reactions/{id}", delete(delete_reaction_handler))
+        .route("/api/subjects", get(subjects_handler))
+        .route("/api/subjects/merge", post(merge_subjects_handler))
+        .route("/api/subjects/merges", get(subject_merges_handler))
+        .route("/api/text/fix", post(fix_text_handler))
+        .route("/api/text/fixes", get(text_fixes_handler))
+        .route("/api/subjects/icons", get(subject_icons_handler))
+        .route(
+            "/api/subjects/{subject}/icon",
+            put(set_subject_icon_handler).delete(delete_subject_icon_handler),
+        )
+        .route("/api/resources", get(resources_handler))
+        .route(
+            "/api/subjects/{subject}/resources",
+            get(subject_resources_handler).post(create_resource_handler),
+        )
+        .route(
+            "/api/subjects/{subject}/resources/{id}",
+            delete(delete_resource_handler),
+        )
+        .route("/api/calendar", get(calendar_handler))
+        .route("/api/holidays", get(holidays_handler))
+        .route("/api/activities", get(activities_handler))
+        .route("/api/agenda/today", get(agenda_today_handler))
+        .route("/api/agenda/{date}", get(agenda_handler))
+        .route("/api/plan/today", get(plan_today_handler))
+        .route("/api/forecast", get(forecast_handler))
+        .route("/api/version", get(version_handler))
         .route("/api/refresh", get(refresh_handler))
+        .route("/api/live", get(live_handler))
         .route("/api/reprocess", post(reprocess_handler))
+        .route("/api/jobs", get(jobs_handler))
+        .route("/api/jobs/{name}/run", post(run_job_handler))
+        .route("/imports", get(imports_page_handler))
+        .route("/api/imports", get(pending_imports_handler))
+        .route("/api/imports/report", get(imports_report_handler))
+        .route("/api/imports/accept-all", post(accept_all_imports_handler))
+        .route("/api/imports/{id}/accept", post(accept_import_handler))
+        .route("/api/imports/{id}/reject", post(reject_import_handler))
+        .route("/register", get(register_page_handler))
+        .route("/api/absences", get(absences_handler))
+        .route("/api/notes", get(discipline_notes_handler))
+        .route("/stats", get(stats_page_handler))
+        .route("/api/stats/time", get(time_stats_handler))
+        .route("/api/stats/goals", get(goals_stats_handler))
+        .route("/api/stats/topics", get(topic_stats_handler))
+        .route("/api/stats/notice", get(notice_stats_handler))
+        .route("/api/stats/days-completed", get(days_completed_handler))
+        .route("/stats/compare", get(compare_page_handler))
+        .route("/stats/teachers", get(teachers_page_handler))
+        .route("/api/stats/teachers", get(teachers_stats_handler))
+        .route("/api/stats/compare", get(compare_stats_handler))
         .route("/settings", get(settings_page_handler))
+        .route(
+            "/api/settings",
+            get(get_settings_handler).put(update_settings_handler),
+        )
+        .route(
+            "/api/features",
+            get(get_features_handler).put(update_features_handler),
+        )
         .route(
             "/api/settings/work-days",
             get(get_work_days_handler).put(set_work_days_handler),
@@ -96,11 +515,90 @@ pub fn create_router(state: Arc<AppState>) -> Router {
             "/api/settings/study-days-before",
             get(get_study_days_before_handler).put(set_study_days_before_handler),
         )
+        .route(
+            "/api/settings/review-imports",
+            get(get_review_imports_handler).put(set_review_imports_handler),
+        );
+    #[cfg(feature = "graphql")]
+    let router = router.route("/graphql", post(graphql::graphql_handler));
+    router
+        .layer(middleware::from_fn_with_state(
+            state.clone(),
+            read_only_guard,
+        ))
+        .layer(middleware::from_fn_with_state(state.clone(), cors_guard))
         .with_state(state)
 }
 
+/// GET routes refused in read-only mode: they change data or only exist to
+/// change it
+const READ_ONLY_BLOCKED: &[&str] = &["/api/refresh", "/settings", "/imports"];
+
+/// In read-only mode, answer 403 to anything but reads. `/graphql` takes
+/// its queries as POSTs, so it's let through and refuses mutations itself.
+async fn read_only_guard(
+    State(state): State<Arc<AppState>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if state.read_only {
+        let read = (matches!(*request.method(), Method::GET | Method::HEAD)
+            && !READ_ONLY_BLOCKED.contains(&request.uri().path()))
+            || (cfg!(feature = "graphql") && request.uri().path() == "/graphql");
+        if !read {
+            return ApiError::read_only().into_response();
+        }
+    }
+    next.run(request).await
+}
+
+/// Let the origins in the `cors_origins` setting call the API from a
+/// browser. Preflight requests are answered here, outside the read-only
+/// guard, which would refuse an OPTIONS.
+async fn cors_guard(State(state): State<Arc<AppState>>, request: Request, next: Next) -> Response {
+    let Some(origin) = request.headers().get(header::ORIGIN).cloned() else {
+        return next.run(request).await;
+    };
+    let origins = {
+        let conn = state.conn.lock().unwrap();
+        db::get_cors_origins(&conn).unwrap_or_else(|e| {
+            error!(error = %e, "Failed to read the allowed origins");
+            Vec::new()
+        })
+    };
+    let allowed = cors::response_headers(&origins, &origin);
+
+    let preflight = request.method() == Method::OPTIONS
+        && request
+            .headers()
+            .contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+    let mut response = if preflight {
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        if cors::is_allowed(&allowed) {
+            response
+                .headers_mut()
+                .extend(cors::preflight_headers(request.headers(), state.read_only));
+        } else {
+            debug!(?origin, "Refused a preflight from an origin not allowed");
+        }
+        response
+    } else {
+        next.run(request).await
+    };
+    // A handler's own `Vary` stays; `Origin` is added to it, not swapped in
+    let headers = response.headers_mut();
+    for (name, value) in &allowed {
+        if name == header::VARY {
+            headers.append(name, value.clone());
+        } else {
+            headers.insert(name, value.clone());
+        }
+    }
+    response
+}
+
 /// Initialize server state by setting up the database
-pub fn init_server_state(output_dir: PathBuf) -> anyhow::Result<Arc<AppState>> {
+pub fn init_server_state(output_dir: PathBuf, read_only: bool) -> anyhow::Result<Arc<AppState>> {
     // Determine paths
     let db_path = output_dir.join("data").join("homework.db");
     let migrations_dir = get_migrations_dir();
@@ -110,19 +608,21 @@ pub fn init_server_state(output_dir: PathBuf) -> anyhow::Result<Arc<AppState>> {
     // Initialize database
     let conn = db::init_db(&db_path, &migrations_dir)?;
 
+    import_register(&conn);
+
     // Process any export files and import new entries
     debug!("Scanning for export files");
-    match data::parse_all_exports() {
-        Ok(entries) => {
-            let imported = db::import_entries(&conn, &entries)?;
+    match data::parse_exports() {
+        Ok(parsed) => {
+            let imported = import_exports(&conn, &parsed)?;
             if imported > 0 {
                 info!(count = imported, "Imported entries from exports");
             }
 
             let today = chrono::Local::now().date_naive();
-            let work_days = db::get_work_days(&conn).unwrap_or_else(|_| vec![1, 2, 3, 4, 5]);
-            let days_ahead = db::get_homework_days_ahead(&conn).unwrap_or(2);
-            let study_days = db::get_study_days_before(&conn).unwrap_or(4);
+            let settings = generation_settings(&conn);
+            let holidays = db::get_holiday_dates(&conn).unwrap_or_default();
+            let activity_minutes = db::get_activity_minutes(&conn).unwrap_or_default();
 
             // Generate auto-entries (study sessions + work reminders) from the
             // DB entries so parent_id references are always valid.
@@ -130,19 +630,24 @@ pub fn init_server_state(output_dir: PathBuf) -> anyhow::Result<Arc<AppState>> {
             let mut study_sessions_created = 0;
             let mut work_reminders_created = 0;
             for entry in &db_entries {
-                if is_test_or_quiz(entry) {
-                    let sessions = generate_study_sessions(entry, today, study_days);
-                    for session in sessions {
-                        if db::insert_entry_if_not_exists(&conn, &session)? {
-                            study_sessions_created += 1;
-                        }
-                    }
+                if settings.study_sessions && is_test_or_quiz(entry) {
+                    let sessions = generate_study_sessions(
+                        entry,
+                        today,
+                        settings.study_days_before,
+                        &holidays,
+                        &activity_minutes,
+                        settings.split_study_topics,
+                    );
+                    study_sessions_created += db::insert_generated(&conn, entry, &sessions)?;
                 }
-                if let Some(reminder) = generate_work_reminder(entry, today, &work_days, days_ahead)
-                {
-                    if db::insert_entry_if_not_exists(&conn, &reminder)? {
-                        work_reminders_created += 1;
-                    }
+                if let Some(reminder) = generate_work_reminder(
+                    entry,
+                    today,
+                    &settings.work_days,
+                    settings.homework_days_ahead,
+                ) {
+                    work_reminders_created += db::insert_generated(&conn, entry, &[reminder])?;
                 }
             }
             if study_sessions_created > 0 {
@@ -161,11 +666,14 @@ pub fn init_server_state(output_dir: PathBuf) -> anyhow::Result<Arc<AppState>> {
     let total = db::count_entries(&conn)?;
     info!(count = total, "Database initialized");
 
-    Ok(Arc::new(AppState::new(conn)))
+    Ok(Arc::new(AppState {
+        read_only,
+        ..AppState::new(conn)
+    }))
 }
 
 /// Get the migrations directory path
-fn get_migrations_dir() -> PathBuf {
+pub fn get_migrations_dir() -> PathBuf {
     // In development, use the relative path from the crate
     // This could be made configurable for production deployments
     let manifest_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap_or_else(|_| ".".to_string());
@@ -178,13 +686,20 @@ pub fn create_server_addr(port: u16) -> SocketAddr {
 }
 
 /// Start the web server with file watching
-pub async fn serve(port: u16, output_dir: PathBuf) -> anyhow::Result<()> {
-    let state = init_server_state(output_dir)?;
+pub async fn serve(port: u16, output_dir: PathBuf, read_only: bool) -> anyhow::Result<()> {
+    let state = init_server_state(output_dir, read_only)?;
+    if read_only {
+        info!("Read-only mode: changes are refused");
+    }
 
     // Start file watcher
     let watcher_state = state.clone();
     start_file_watcher(watcher_state)?;
 
+    jobs::start(state.clone());
+    activities::start_subscription(state.clone());
+    version::start_update_check(state.clone());
+
     let app = create_router(state);
 
     let addr = create_server_addr(port);
@@ -215,6 +730,131 @@ pub fn ensure_data_dir(data_dir: &Path) -> anyhow::Result<bool> {
     }
 }
 
+/// Import parsed export entries, or stage them for review when the
+/// `review_imports` setting is on, and record their attachments (queuing
+/// photos for OCR). Returns the number imported directly.
+fn import_exports(conn: &Connection, parsed: &data::ParsedExports) -> anyhow::Result<usize> {
+    let entries = &parsed.entries;
+    warn_holiday_entries(conn, entries);
+    let added = db::record_attachments(conn, &parsed.attachments)?;
+    if added > 0 {
+        info!(count = added, "Recorded new attachments");
+    }
+    let imported = if db::get_review_imports(conn).unwrap_or(true) {
+        let staged = db::stage_imports(conn, entries)?;
+        if staged > 0 {
+            let pending = db::count_pending_imports(conn).unwrap_or(staged);
+            info!(count = staged, pending, "Staged entries for import review");
+        }
+        0
+    } else {
+        db::import_entries(conn, entries)?
+    };
+    db::record_source_dates(conn, entries)?;
+    db::record_end_dates(conn, entries)?;
+    db::record_assignments(conn, entries)?;
+    db::record_source_files(conn, entries)?;
+    link_cross_listed(conn)?;
+    let topics = db::fill_topics(conn)?;
+    if topics > 0 {
+        debug!(count = topics, "Filled in topics");
+    }
+    Ok(imported)
+}
+
+/// Cross-list entries posted under two subjects (see `crosslist`), before
+/// anything is generated for them, so only the primary gets study sessions
+fn link_cross_listed(conn: &Connection) -> db::Result<usize> {
+    let cross_listed = db::link_cross_listed(conn)?;
+    if cross_listed > 0 {
+        info!(
+            count = cross_listed,
+            "Linked entries posted under two subjects"
+        );
+    }
+    Ok(cross_listed)
+}
+
+/// Mirror the newest readable absences and notes snapshots in data/. A
+/// failed import is logged and leaves the previous snapshot in place.
+fn import_register(conn: &Connection) {
+    if let Err(e) = register::import_newest(conn, Path::new("data")) {
+        warn!(error = %format!("{:#}", e), "Failed to import register snapshot");
+    }
+}
+
+/// Log new export entries dated on a school holiday; usually the parser
+/// picked up the wrong date.
+fn warn_holiday_entries(conn: &Connection, entries: &[HomeworkEntry]) {
+    let holidays: BTreeMap<String, String> = match db::get_holidays(conn) {
+        Ok(holidays) => holidays.into_iter().map(|h| (h.date, h.name)).collect(),
+        Err(e) => {
+            debug!(error = %e, "Failed to load holidays");
+            return;
+        }
+    };
+    if holidays.is_empty() {
+        return;
+    }
+    for entry in entries {
+        let Some(holiday) = holidays.get(&entry.date) else {
+            continue;
+        };
+        let is_new = entry
+            .source_id
+            .as_deref()
+            .is_some_and(|id| !db::is_known_source_id(conn, id).unwrap_or(true));
+        if is_new {
+            warn!(
+                date = %entry.date,
+                holiday = %holiday,
+                subject = %entry.subject,
+                task = %entry.task,
+                "Entry is dated on a holiday; check the export's dates"
+            );
+        }
+    }
+}
+
+/// Settings as entry generation sees them: no study sessions while the
+/// `study_sessions` feature is off, whatever the setting says
+fn generation_settings(conn: &Connection) -> Settings {
+    let mut settings = db::get_settings(conn).unwrap_or_default();
+    settings.study_sessions &= features::enabled(conn, Feature::StudySessions);
+    settings
+}
+
+/// Generate study sessions / work reminder for a single entry. A
+/// cross-listed copy gets none; its primary has them.
+fn generate_auto_entries(conn: &Connection, entry: &HomeworkEntry) {
+    if db::is_cross_listed_copy(conn, &entry.id).unwrap_or(false) {
+        return;
+    }
+    let today = chrono::Local::now().date_naive();
+    let settings = generation_settings(conn);
+    let holidays = db::get_holiday_dates(conn).unwrap_or_default();
+    let activity_minutes = db::get_activity_minutes(conn).unwrap_or_default();
+    if settings.study_sessions && is_test_or_quiz(entry) {
+        let sessions = generate_study_sessions(
+            entry,
+            today,
+            settings.study_days_before,
+            &holidays,
+            &activity_minutes,
+            settings.split_study_topics,
+        );
+        let _ = db::insert_generated(conn, entry, &sessions);
+    }
+    if let Some(reminder) = generate_work_reminder(
+        entry,
+        today,
+        &settings.work_days,
+        settings.homework_days_ahead,
+    ) {
+        let _ = db::insert_generated(conn, entry, &[reminder]);
+    }
+}
+
 /// Describes the result of processing a file change event
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum RefreshResult {
@@ -255,26 +895,36 @@ pub fn process_refresh(state: &AppState) -> RefreshResult {
     };
 
     let old_count = db::count_entries(&conn).unwrap_or(0);
+    import_register(&conn);
 
-    match data::parse_all_exports() {
-        Ok(entries) => {
-            let imported = db::import_entries(&conn, &entries).unwrap_or(0);
+    match data::parse_exports() {
+        Ok(parsed) => {
+            let imported = import_exports(&conn, &parsed).unwrap_or(0);
 
             let today = chrono::Local::now().date_naive();
-            let work_days = db::get_work_days(&conn).unwrap_or_else(|_| vec![1, 2, 3, 4, 5]);
-            let days_ahead = db::get_homework_days_ahead(&conn).unwrap_or(2);
-            let study_days = db::get_study_days_before(&conn).unwrap_or(4);
+            let settings = generation_settings(&conn);
+            let holidays = db::get_holiday_dates(&conn).unwrap_or_default();
+            let activity_minutes = db::get_activity_minutes(&conn).unwrap_or_default();
             let db_entries = db::get_all_entries(&conn).unwrap_or_default();
             for entry in &db_entries {
-                if is_test_or_quiz(entry) {
-                    let sessions = generate_study_sessions(entry, today, study_days);
-                    for session in sessions {
-                        let _ = db::insert_entry_if_not_exists(&conn, &session);
-                    }
+                if settings.study_sessions && is_test_or_quiz(entry) {
+                    let sessions = generate_study_sessions(
+                        entry,
+                        today,
+                        settings.study_days_before,
+                        &holidays,
+                        &activity_minutes,
+                        settings.split_study_topics,
+                    );
+                    let _ = db::insert_generated(&conn, entry, &sessions);
                 }
-                if let Some(reminder) = generate_work_reminder(entry, today, &work_days, days_ahead)
-                {
-                    let _ = db::insert_entry_if_not_exists(&conn, &reminder);
+                if let Some(reminder) = generate_work_reminder(
+                    entry,
+                    today,
+                    &settings.work_days,
+                    settings.homework_days_ahead,
+                ) {
+                    let _ = db::insert_generated(&conn, entry, &[reminder]);
                 }
             }
 
@@ -320,7 +970,9 @@ fn start_file_watcher(state: Arc<AppState>) -> anyhow::Result<()> {
             Duration::from_secs(2),
             move |result: DebounceEventResult| {
                 if let Ok(events) = result {
-                    let has_export = events.iter().any(|e| is_export_file(&e.path));
+                    let has_export = events
+                        .iter()
+                        .any(|e| is_export_file(&e.path) || register::is_register_file(&e.path));
 
                     if has_export {
                         let _ = tx_clone.blocking_send(());
@@ -344,6 +996,11 @@ fn start_file_watcher(state: Arc<AppState>) -> anyhow::Result<()> {
     // Spawn a task to handle file change notifications
     tokio::spawn(async move {
         while rx.recv().await.is_some() {
+            let watching = features::enabled(&state.conn.lock().unwrap(), Feature::Watcher);
+            if !watching {
+                debug!("Changes in data/ ignored: the watcher feature is off");
+                continue;
+            }
             info!("Detected changes in data/");
             let result = process_refresh(&state);
             result.log();
@@ -353,33 +1010,349 @@ fn start_file_watcher(state: Arc<AppState>) -> anyhow::Result<()> {
     Ok(())
 }
 
-/// Serve the main HTML page
-async fn index_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+/// Reject a `ListQuery` whose dates or status `load_list_window` can't use
+fn check_list_query(query: &ListQuery) -> Result<(), ApiError> {
+    let valid = |d: &Option<String>| {
+        d.as_deref()
+            .is_none_or(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").is_ok())
+    };
+    if !valid(&query.from) || !valid(&query.to) {
+        return Err(ApiError::validation("Dates must be YYYY-MM-DD"));
+    }
+    if let (Some(from), Some(to)) = (&query.from, &query.to) {
+        if from > to {
+            return Err(ApiError::validation("from is after to"));
+        }
+    }
+    status_filter(&query.status).map(|_| ())
+}
+
+/// The timetable in the subject rules file, or none when it can't be read
+/// (the list still shows, without the no-lesson badges)
+fn load_timetable(state: &AppState) -> Timetable {
+    Timetable::load(&state.rules_path).unwrap_or_else(|e| {
+        warn!(error = %e, "Failed to load the timetable");
+        Timetable::default()
+    })
+}
+
+/// Resolve the list view's date window and load the entries inside it.
+///
+/// Without `from`/`to`, the window spans `settings.list_days_back` days
+/// before today to `settings.list_days_ahead` after. Today is clamped into
+/// the stored dates first, so during the holidays the list still opens on
+/// the last weeks of school rather than on nothing. With `all`, it spans
+/// every stored date. Entries `timetable` doesn't expect that day are
+/// marked.
+fn load_list_window(
+    conn: &Connection,
+    query: &ListQuery,
+    settings: &Settings,
+    timetable: &Timetable,
+) -> anyhow::Result<(Vec<HomeworkEntry>, html::ListWindow)> {
+    let parse = |s: &Option<String>| {
+        s.as_deref()
+            .map(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d"))
+            .transpose()
+    };
+    let (mut from, mut to) = (parse(&query.from)?, parse(&query.to)?);
+
+    let bounds = db::get_date_bounds(conn)?;
+    let mut anchor = chrono::Local::now().date_naive();
+    if let Some((min, max)) = &bounds {
+        if let (Ok(min), Ok(max)) = (
+            NaiveDate::parse_from_str(min, "%Y-%m-%d"),
+            NaiveDate::parse_from_str(max, "%Y-%m-%d"),
+        ) {
+            anchor = anchor.clamp(min, max);
+            if query.all {
+                (from, to) = (Some(min), Some(max));
+            }
+        }
+    }
+    let back = chrono::Duration::days(settings.list_days_back as i64);
+    let ahead = chrono::Duration::days(settings.list_days_ahead as i64);
+    let from = from.unwrap_or(anchor - back).to_string();
+    let to = to.unwrap_or(anchor + ahead).to_string();
+    anyhow::ensure!(from <= to, "from is after to");
+
+    let today = chrono::Local::now().date_naive().to_string();
+    let filter = db::EntryFilter {
+        from: Some(from.clone()),
+        to: Some(to.clone()),
+        subjects: query_list(&query.subject),
+        types: query_list(&query.entry_type),
+        completed: status_filter(&query.status).map_err(|e| anyhow::anyhow!(e.message))?,
+        visible_on: (!query.hidden).then(|| today.clone()),
+        // A copy shows as a badge on its primary; filtered by subject, the
+        // primary may not be listed, so the copy stays
+        skip_cross_listed_copies: query_list(&query.subject).is_empty(),
+        ..Default::default()
+    };
+    let entries = db::get_entries_filtered(conn, &filter)?;
+    let (mut cross_listed, _) = db::get_cross_listings(conn)?;
+    let shown: HashSet<&str> = entries.iter().map(|e| e.id.as_str()).collect();
+    cross_listed.retain(|primary, _| shown.contains(primary.as_str()));
+    let off_schedule = entries
+        .iter()
+        .filter_map(|e| Some((e.id.clone(), timetable.check(e)?)))
+        .collect();
+    let (total, completed) = db::count_completion(conn)?;
+    let (has_older, has_newer) = match &bounds {
+        Some((min, max)) => (*min < from, *max > to),
+        None => (false, false),
+    };
+    let window = html::ListWindow {
+        from,
+        to,
+        has_older,
+        has_newer,
+        total,
+        completed,
+        cross_listed,
+        off_schedule,
+        hidden: db::count_hidden(conn, &today)?,
+        showing_hidden: query.hidden.then_some(today),
+    };
+    Ok((entries, window))
+}
+
+/// `error` as a page, for handlers that serve HTML to a browser; the API
+/// answers with the JSON body instead
+fn error_page(error: ApiError) -> Response {
+    (
+        error.code.status(),
+        Html(html::render_error_page(&error.message)),
+    )
+        .into_response()
+}
+
+/// Dashboard landing page: today, tomorrow, this week and upcoming tests
+async fn dashboard_handler(
+    State(state): State<Arc<AppState>>,
+    headers: HeaderMap,
+) -> impl IntoResponse {
+    if prefers_simple_view(&headers) {
+        return Redirect::to("/simple").into_response();
+    }
+    let conn = state.conn.lock().unwrap();
+    let today = chrono::Local::now().date_naive();
+    match db::get_dashboard(&conn, today) {
+        Ok(dashboard) => {
+            let settings = db::get_settings(&conn).unwrap_or_default();
+            Html(html::render_dashboard_page(
+                &dashboard,
+                &settings,
+                state.read_only,
+            ))
+            .into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to build dashboard");
+            error_page(ApiError::from_db(&e, "Database error"))
+        }
+    }
+}
+
+/// Cookie that makes a device open the simple view instead of the dashboard
+pub const SIMPLE_VIEW_COOKIE: &str = "compitutto_view";
+
+/// A year; the cookie outlives browser restarts but not the device
+const SIMPLE_VIEW_MAX_AGE: u32 = 365 * 24 * 60 * 60;
+
+fn prefers_simple_view(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .any(|cookie| cookie.trim() == format!("{}=simple", SIMPLE_VIEW_COOKIE))
+}
+
+/// Serve the simple view (today's plan, big cards), or with `device`,
+/// switch this device to or from it with a cookie
+async fn simple_page_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<SimpleQuery>,
+) -> impl IntoResponse {
+    if let Some(simple) = query.device {
+        let (cookie, to) = if simple {
+            (
+                format!(
+                    "{}=simple; Path=/; Max-Age={}; SameSite=Lax",
+                    SIMPLE_VIEW_COOKIE, SIMPLE_VIEW_MAX_AGE
+                ),
+                "/simple",
+            )
+        } else {
+            (
+                format!("{}=; Path=/; Max-Age=0; SameSite=Lax", SIMPLE_VIEW_COOKIE),
+                "/",
+            )
+        };
+        return ([(header::SET_COOKIE, cookie)], Redirect::to(to)).into_response();
+    }
+
+    let today = chrono::Local::now().date_naive();
+    let conn = state.conn.lock().unwrap();
+    let settings = db::get_settings(&conn).unwrap_or_default();
+    // Hidden entries stay out of the plan, as they do on the dashboard
+    let filter = db::EntryFilter {
+        visible_on: Some(today.to_string()),
+        skip_cross_listed_copies: true,
+        ..Default::default()
+    };
+    let loaded = db::get_entries_filtered(&conn, &filter)
+        .and_then(|entries| Ok((entries, subjects::SubjectIcons::load(&conn)?)));
+    match loaded {
+        Ok((entries, icons)) => {
+            let plan = plan::build_plan(&entries, today, settings.daily_budget_minutes);
+            Html(html::render_simple_page(
+                &plan,
+                &entries,
+                &icons,
+                &settings,
+                state.read_only,
+            ))
+            .into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to build the simple view");
+            error_page(ApiError::from_db(&e, "Database error"))
+        }
+    }
+}
+
+/// Serve the full homework list page, on the settings' date window unless
+/// `from`/`to` or `all` pick another
+async fn list_page_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListQuery>,
+) -> impl IntoResponse {
+    if let Err(error) = check_list_query(&query) {
+        return error_page(error);
+    }
+    let timetable = load_timetable(&state);
     let conn = state.conn.lock().unwrap();
-    match db::get_all_entries(&conn) {
-        Ok(entries) => {
-            let markup = html::render_page(&entries);
+    let settings = db::get_settings(&conn).unwrap_or_default();
+    match load_list_window(&conn, &query, &settings, &timetable) {
+        Ok((entries, window)) => {
+            let markup =
+                html::render_page_window(&entries, Some(&window), &settings, state.read_only);
             Html(markup.into_string()).into_response()
         }
         Err(e) => {
             error!(error = %e, "Failed to get entries");
-            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+            error_page(ApiError::internal("Database error"))
         }
     }
 }
 
-/// Return all entries as JSON
-async fn entries_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+/// Serve the list view contents for a date window, for swapping in without a
+/// page reload or widening the list with "Load older/newer"
+async fn list_fragment_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListQuery>,
+) -> impl IntoResponse {
+    if let Err(error) = check_list_query(&query) {
+        return error.into_response();
+    }
+
+    let timetable = load_timetable(&state);
     let conn = state.conn.lock().unwrap();
-    match db::get_all_entries(&conn) {
-        Ok(entries) => Json(entries).into_response(),
+    let settings = db::get_settings(&conn).unwrap_or_default();
+    match load_list_window(&conn, &query, &settings, &timetable) {
+        Ok((entries, window)) => {
+            Html(html::render_list(&entries, Some(&window)).into_string()).into_response()
+        }
         Err(e) => {
             error!(error = %e, "Failed to get entries");
-            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+            ApiError::internal("Database error").into_response()
         }
     }
 }
 
+/// Return all entries as JSON
+async fn entries_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EntriesQuery>,
+) -> impl IntoResponse {
+    match filtered_entries(&state, &query) {
+        Ok(entries) => Json(Linked::all(entries)).into_response(),
+        Err(error) => error.into_response(),
+    }
+}
+
+/// Collections and their filters, for clients that discover the API
+async fn api_index_handler() -> impl IntoResponse {
+    Json(hypermedia::api_index())
+}
+
+/// Entries matching `query`, or the status and message to reply with
+fn filtered_entries(
+    state: &AppState,
+    query: &EntriesQuery,
+) -> Result<Vec<HomeworkEntry>, ApiError> {
+    let filter = query.filter()?;
+    let conn = state.conn.lock().unwrap();
+    db::get_entries_filtered(&conn, &filter).map_err(|e| {
+        error!(error = %e, "Failed to get entries");
+        ApiError::from_db(&e, "Database error")
+    })
+}
+
+/// Subject icons for an export; the built-in ones if the database can't
+/// say
+fn subject_icons(state: &AppState) -> subjects::SubjectIcons {
+    let conn = state.conn.lock().unwrap();
+    subjects::SubjectIcons::load(&conn).unwrap_or_else(|e| {
+        warn!(error = %e, "Failed to load subject icons");
+        subjects::SubjectIcons::default()
+    })
+}
+
+/// `/api/entries` filtered, as a CSV download
+async fn export_csv_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EntriesQuery>,
+) -> impl IntoResponse {
+    match filtered_entries(&state, &query) {
+        Ok(entries) => (
+            [
+                (header::CONTENT_TYPE, "text/csv; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"compitutto.csv\"",
+                ),
+            ],
+            export::to_csv(&entries, &subject_icons(&state)),
+        )
+            .into_response(),
+        Err(error) => error.into_response(),
+    }
+}
+
+/// `/api/entries` filtered, as an iCalendar feed of all-day events
+async fn export_ics_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<EntriesQuery>,
+) -> impl IntoResponse {
+    match filtered_entries(&state, &query) {
+        Ok(entries) => (
+            [
+                (header::CONTENT_TYPE, "text/calendar; charset=utf-8"),
+                (
+                    header::CONTENT_DISPOSITION,
+                    "attachment; filename=\"compitutto.ics\"",
+                ),
+            ],
+            export::to_ics(&entries, &subject_icons(&state), chrono::Utc::now()),
+        )
+            .into_response(),
+        Err(error) => error.into_response(),
+    }
+}
+
 /// Get a single entry by ID
 async fn get_entry_handler(
     State(state): State<Arc<AppState>>,
@@ -387,11 +1360,11 @@ async fn get_entry_handler(
 ) -> impl IntoResponse {
     let conn = state.conn.lock().unwrap();
     match db::get_entry(&conn, &id) {
-        Ok(Some(entry)) => Json(entry).into_response(),
-        Ok(None) => (StatusCode::NOT_FOUND, "Entry not found").into_response(),
+        Ok(Some(entry)) => Json(Linked::new(entry)).into_response(),
+        Ok(None) => ApiError::not_found("Entry not found").into_response(),
         Err(e) => {
             error!(error = %e, id = %id, "Failed to get entry");
-            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+            ApiError::from_db(&e, "Database error").into_response()
         }
     }
 }
@@ -399,12 +1372,13 @@ async fn get_entry_handler(
 /// Create a new entry
 async fn create_entry_handler(
     State(state): State<Arc<AppState>>,
-    Json(req): Json<CreateEntryRequest>,
+    ApiJson(req): ApiJson<CreateEntryRequest>,
 ) -> impl IntoResponse {
     let conn = state.conn.lock().unwrap();
 
     // Create the entry
     let mut entry = HomeworkEntry::new(req.entry_type, req.date.clone(), req.subject, req.task);
+    entry.estimated_minutes = req.estimated_minutes;
 
     // Set position if provided, otherwise put at end of day
     entry.position = match req.position {
@@ -415,65 +1389,270 @@ async fn create_entry_handler(
     match db::insert_entry(&conn, &entry) {
         Ok(()) => {
             // If it's a test/compiti, generate study sessions / work reminders
-            {
-                let today = chrono::Local::now().date_naive();
-                let work_days = db::get_work_days(&conn).unwrap_or_else(|_| vec![1, 2, 3, 4, 5]);
-                let days_ahead = db::get_homework_days_ahead(&conn).unwrap_or(2);
-                let study_days = db::get_study_days_before(&conn).unwrap_or(4);
-                if is_test_or_quiz(&entry) {
-                    let sessions = generate_study_sessions(&entry, today, study_days);
-                    for session in sessions {
-                        let _ = db::insert_entry_if_not_exists(&conn, &session);
-                    }
-                }
-                if let Some(reminder) =
-                    generate_work_reminder(&entry, today, &work_days, days_ahead)
-                {
-                    let _ = db::insert_entry_if_not_exists(&conn, &reminder);
-                }
-            }
+            generate_auto_entries(&conn, &entry);
             debug!(id = %entry.id, subject = %entry.subject, "Entry created");
-            (StatusCode::CREATED, Json(entry)).into_response()
+            (StatusCode::CREATED, Json(checked_entry(&conn, entry))).into_response()
+        }
+        Err(e) if db::is_unique_violation(&e) => {
+            ApiError::conflict("An identical entry already exists on that date").into_response()
         }
         Err(e) => {
             error!(error = %e, "Failed to create entry");
-            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to create entry").into_response()
+            ApiError::from_db(&e, "Failed to create entry").into_response()
         }
     }
 }
 
-/// Update an existing entry
-async fn update_entry_handler(
+/// Merge a JSON merge patch (RFC 7396) into an entry (see `patch`)
+async fn patch_entry_handler(
     State(state): State<Arc<AppState>>,
     AxumPath(id): AxumPath<String>,
-    Json(req): Json<UpdateEntryRequest>,
-) -> impl IntoResponse {
-    let conn = state.conn.lock().unwrap();
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> Response {
+    update_entry_with(&state, &id, |stored| patch::apply_patch(stored, &body))
+}
+
+/// Replace an entry's writable fields with the ones in a full
+/// representation (see `patch`)
+async fn put_entry_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> Response {
+    update_entry_with(&state, &id, |stored| patch::apply_put(stored, &body))
+}
 
-    let updates = EntryUpdate {
-        date: req.date,
-        completed: req.completed,
-        position: req.position,
-        task: None,
+/// Apply the changes `wanted` finds against the stored entry. A moved test
+/// takes its study sessions along, and the response carries the day's
+/// warning then.
+fn update_entry_with(
+    state: &AppState,
+    id: &str,
+    wanted: impl FnOnce(&HomeworkEntry) -> Result<EntryUpdate, patch::FieldError>,
+) -> Response {
+    let conn = state.conn.lock().unwrap();
+    let stored = match db::get_entry(&conn, id) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return ApiError::not_found("Entry not found").into_response(),
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to get entry");
+            return ApiError::from_db(&e, "Database error").into_response();
+        }
+    };
+    let updates = match wanted(&stored) {
+        Ok(updates) => updates,
+        Err(e) => {
+            return ApiError::unprocessable(e.message)
+                .with_details(serde_json::json!({ "field": e.field }))
+                .into_response()
+        }
     };
 
-    match db::update_entry(&conn, &id, &updates) {
+    if let Some(Some(_)) = updates.grade {
+        let happened = match db::get_settings(&conn) {
+            Ok(settings) => settings.completion_for(&stored.entry_type) == Completion::Happened,
+            Err(e) => {
+                error!(error = %e, id = %id, "Failed to check entry completion");
+                return ApiError::from_db(&e, "Database error").into_response();
+            }
+        };
+        if !happened {
+            return ApiError::unprocessable(
+                "Only entries that complete as \"happened\" take a grade",
+            )
+            .with_details(serde_json::json!({ "field": "grade" }))
+            .into_response();
+        }
+    }
+
+    match db::update_entry(&conn, id, &updates) {
         Ok(true) => {
             debug!(id = %id, "Entry updated");
             // Return the updated entry
-            match db::get_entry(&conn, &id) {
-                Ok(Some(entry)) => Json(entry).into_response(),
+            match db::get_entry(&conn, id) {
+                Ok(Some(entry)) => {
+                    let moved = entry.date != stored.date;
+                    if moved {
+                        reschedule_study_sessions(&conn, &entry, &stored.date);
+                    }
+                    let mut day_completed = false;
+                    if let Some(completed) = updates.completed {
+                        state.live.publish(LiveEvent::Entry {
+                            id: entry.id.clone(),
+                            completed: entry.completed,
+                        });
+                        day_completed = track_day(&conn, &entry.date, completed);
+                    }
+                    if moved {
+                        Json(UpdatedEntry {
+                            entry: checked_entry(&conn, entry),
+                            day_completed,
+                        })
+                        .into_response()
+                    } else {
+                        Json(UpdatedEntry {
+                            entry: Linked::new(entry),
+                            day_completed,
+                        })
+                        .into_response()
+                    }
+                }
                 _ => StatusCode::OK.into_response(),
             }
         }
-        Ok(false) => (StatusCode::NOT_FOUND, "Entry not found").into_response(),
+        Ok(false) => ApiError::not_found("Entry not found").into_response(),
         Err(e) => {
             error!(error = %e, id = %id, "Failed to update entry");
-            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to update entry").into_response()
+            ApiError::from_db(&e, "Failed to update entry").into_response()
+        }
+    }
+}
+
+/// Record `date` as all done once its last entry is ticked off, or as open
+/// again when one is unticked. Whether it's all done now; best effort.
+pub fn track_day(conn: &Connection, date: &str, completed: bool) -> bool {
+    let tracked = if completed {
+        db::record_day_completed(conn, date)
+    } else {
+        db::reopen_day(conn, date).map(|()| false)
+    };
+    tracked.unwrap_or_else(|e| {
+        warn!(error = %e, date = %date, "Failed to record the day's completion");
+        false
+    })
+}
+
+/// Move an entry to a day and place in one call; the server renumbers the
+/// day, so drag-drop needs no per-entry position updates
+async fn move_entry_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+    ApiJson(req): ApiJson<MoveEntryRequest>,
+) -> impl IntoResponse {
+    if NaiveDate::parse_from_str(&req.date, "%Y-%m-%d").is_err() {
+        return ApiError::validation("Date must be YYYY-MM-DD").into_response();
+    }
+
+    let conn = state.conn.lock().unwrap();
+    let old_date = match db::get_entry(&conn, &id) {
+        Ok(Some(entry)) => entry.date,
+        Ok(None) => return ApiError::not_found("Entry not found").into_response(),
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to get entry");
+            return ApiError::from_db(&e, "Database error").into_response();
+        }
+    };
+
+    let moved =
+        db::move_entry(&conn, &id, &req.date, req.index).and_then(|_| db::get_entry(&conn, &id));
+    match moved {
+        Ok(Some(entry)) => {
+            debug!(id = %id, date = %entry.date, position = entry.position, "Entry moved");
+            if entry.date == old_date {
+                return Json(Linked::new(entry)).into_response();
+            }
+            reschedule_study_sessions(&conn, &entry, &old_date);
+            Json(checked_entry(&conn, entry)).into_response()
+        }
+        Ok(None) => ApiError::not_found("Entry not found").into_response(),
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to move entry");
+            ApiError::from_db(&e, "Failed to move entry").into_response()
+        }
+    }
+}
+
+/// Copy an entry's type, subject, task, topic and estimate onto another day,
+/// not completed and at the bottom of that day. Study sessions and reminders
+/// are left out: duplicating their test or homework regenerates them.
+async fn duplicate_entry_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+    ApiJson(req): ApiJson<DuplicateEntryRequest>,
+) -> impl IntoResponse {
+    if NaiveDate::parse_from_str(&req.date, "%Y-%m-%d").is_err() {
+        return ApiError::validation("Date must be YYYY-MM-DD").into_response();
+    }
+
+    let conn = state.conn.lock().unwrap();
+    let original = match db::get_entry(&conn, &id) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return ApiError::not_found("Entry not found").into_response(),
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to get entry");
+            return ApiError::from_db(&e, "Database error").into_response();
+        }
+    };
+    if original.is_generated() {
+        return ApiError::validation(
+            "Study sessions and reminders can't be duplicated; duplicate their entry instead",
+        )
+        .into_response();
+    }
+
+    let mut copy = HomeworkEntry::new(
+        original.entry_type,
+        req.date.clone(),
+        original.subject,
+        original.task,
+    );
+    copy.topic = original.topic;
+    copy.estimated_minutes = original.estimated_minutes;
+    copy.position = db::get_max_position_for_date(&conn, &req.date).unwrap_or(-1) + 1;
+
+    match db::insert_entry(&conn, &copy) {
+        Ok(()) => {
+            generate_auto_entries(&conn, &copy);
+            debug!(id = %id, copy = %copy.id, date = %copy.date, "Entry duplicated");
+            (StatusCode::CREATED, Json(checked_entry(&conn, copy))).into_response()
+        }
+        Err(e) if db::is_unique_violation(&e) => {
+            ApiError::conflict("An identical entry already exists on that date").into_response()
         }
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to duplicate entry");
+            ApiError::from_db(&e, "Failed to duplicate entry").into_response()
+        }
+    }
+}
+
+/// `entry` with a warning if its day is now overloaded (see `overload`)
+pub fn checked_entry(conn: &Connection, entry: HomeworkEntry) -> Linked<CheckedEntry> {
+    let settings = db::get_settings(conn).unwrap_or_default();
+    let warning = match db::get_entries_in_range(conn, &entry.date, &entry.date) {
+        Ok(day) => overload::check_day(&day, &entry, &settings),
+        Err(e) => {
+            error!(error = %e, date = %entry.date, "Failed to check the day's load");
+            None
+        }
+    };
+    let links = hypermedia::EntryLinks::for_entry(&entry);
+    Linked {
+        entry: CheckedEntry { entry, warning },
+        links,
     }
 }
 
+/// After a test moves from `old_date`, shift its incomplete study sessions by
+/// the same number of days and fill in any sessions the new date makes room for.
+pub fn reschedule_study_sessions(conn: &Connection, entry: &HomeworkEntry, old_date: &str) {
+    if !is_test_or_quiz(entry) {
+        return;
+    }
+    let (Ok(old), Ok(new)) = (
+        chrono::NaiveDate::parse_from_str(old_date, "%Y-%m-%d"),
+        chrono::NaiveDate::parse_from_str(&entry.date, "%Y-%m-%d"),
+    ) else {
+        return;
+    };
+
+    match db::shift_study_sessions(conn, &entry.id, (new - old).num_days()) {
+        Ok(moved) => debug!(id = %entry.id, moved, "Rescheduled study sessions"),
+        Err(e) => error!(error = %e, id = %entry.id, "Failed to reschedule study sessions"),
+    }
+    generate_auto_entries(conn, entry);
+}
+
 /// Delete an entry (orphans its children)
 async fn delete_entry_handler(
     State(state): State<Arc<AppState>>,
@@ -496,10 +1675,10 @@ async fn delete_entry_handler(
             })
             .into_response()
         }
-        Ok(false) => (StatusCode::NOT_FOUND, "Entry not found").into_response(),
+        Ok(false) => ApiError::not_found("Entry not found").into_response(),
         Err(e) => {
             error!(error = %e, id = %id, "Failed to delete entry");
-            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete entry").into_response()
+            ApiError::from_db(&e, "Failed to delete entry").into_response()
         }
     }
 }
@@ -511,10 +1690,10 @@ async fn get_children_handler(
 ) -> impl IntoResponse {
     let conn = state.conn.lock().unwrap();
     match db::get_children(&conn, &id) {
-        Ok(children) => Json(children).into_response(),
+        Ok(children) => Json(Linked::all(children)).into_response(),
         Err(e) => {
             error!(error = %e, id = %id, "Failed to get children");
-            (StatusCode::INTERNAL_SERVER_ERROR, "Database error").into_response()
+            ApiError::from_db(&e, "Database error").into_response()
         }
     }
 }
@@ -536,398 +1715,4993 @@ async fn cascade_delete_handler(
         }
         Err(e) => {
             error!(error = %e, id = %id, "Failed to cascade delete");
-            (StatusCode::INTERNAL_SERVER_ERROR, "Failed to delete").into_response()
+            ApiError::from_db(&e, "Failed to delete").into_response()
         }
     }
 }
 
-/// Refresh data from disk (re-process export files)
-async fn refresh_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    info!("Manual refresh triggered");
+// ========== Entry links ==========
 
+/// All links between entries
+async fn links_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     let conn = state.conn.lock().unwrap();
-
-    match data::parse_all_exports() {
-        Ok(entries) => {
-            let imported = db::import_entries(&conn, &entries).unwrap_or(0);
-            let today = chrono::Local::now().date_naive();
-            let work_days = db::get_work_days(&conn).unwrap_or_else(|_| vec![1, 2, 3, 4, 5]);
-            let days_ahead = db::get_homework_days_ahead(&conn).unwrap_or(2);
-            let study_days = db::get_study_days_before(&conn).unwrap_or(4);
-            let db_entries = db::get_all_entries(&conn).unwrap_or_default();
-            let mut study_sessions_created = 0;
-            let mut work_reminders_created = 0;
-            for entry in &db_entries {
-                if is_test_or_quiz(entry) {
-                    let sessions = generate_study_sessions(entry, today, study_days);
-                    for session in sessions {
-                        if db::insert_entry_if_not_exists(&conn, &session).unwrap_or(false) {
-                            study_sessions_created += 1;
-                        }
-                    }
-                }
-                if let Some(reminder) = generate_work_reminder(entry, today, &work_days, days_ahead)
-                {
-                    if db::insert_entry_if_not_exists(&conn, &reminder).unwrap_or(false) {
-                        work_reminders_created += 1;
-                    }
-                }
-            }
-            if imported > 0 || study_sessions_created > 0 || work_reminders_created > 0 {
-                info!(
-                    imported,
-                    study_sessions = study_sessions_created,
-                    work_reminders = work_reminders_created,
-                    "Refresh complete"
-                );
-            }
-            "OK"
-        }
+    match db::get_links(&conn) {
+        Ok(links) => Json(links).into_response(),
         Err(e) => {
-            error!(error = %e, "Refresh failed");
-            "ERROR"
+            error!(error = %e, "Failed to get links");
+            ApiError::from_db(&e, "Database error").into_response()
         }
     }
 }
 
-// ========== Reprocess handler ==========
-
-/// Delete all future auto-generated entries and regenerate them using the
-/// current settings (work days, homework_days_ahead, study_days_before).
-/// Past entries and their completed state are never touched.
-async fn reprocess_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+/// An entry's links, with the entry at the other end of each
+async fn entry_links_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+) -> impl IntoResponse {
     let conn = state.conn.lock().unwrap();
-    let today = chrono::Local::now()
-        .date_naive()
-        .format("%Y-%m-%d")
-        .to_string();
-
-    // 1. Delete future generated entries
-    let deleted = match db::delete_future_generated_entries(&conn, &today) {
-        Ok(n) => n,
+    match db::entry_exists(&conn, &id) {
+        Ok(true) => {}
+        Ok(false) => return ApiError::not_found("Entry not found").into_response(),
         Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to delete generated entries: {}", e),
-            )
-                .into_response();
+            error!(error = %e, id = %id, "Failed to get entry");
+            return ApiError::from_db(&e, "Database error").into_response();
         }
-    };
-    info!(deleted, "Deleted future generated entries for reprocess");
-
-    // 2. Regenerate from all current DB entries
-    let today_naive = chrono::Local::now().date_naive();
-    let work_days = db::get_work_days(&conn).unwrap_or_else(|_| vec![1, 2, 3, 4, 5]);
-    let days_ahead = db::get_homework_days_ahead(&conn).unwrap_or(2);
-    let study_days = db::get_study_days_before(&conn).unwrap_or(4);
-
-    let db_entries = match db::get_all_entries(&conn) {
-        Ok(e) => e,
+    }
+    let links = match db::get_links_for_entry(&conn, &id) {
+        Ok(links) => links,
         Err(e) => {
-            return (
-                StatusCode::INTERNAL_SERVER_ERROR,
-                format!("Failed to load entries: {}", e),
-            )
-                .into_response();
+            error!(error = %e, id = %id, "Failed to get links");
+            return ApiError::from_db(&e, "Database error").into_response();
         }
     };
 
-    let mut created = 0usize;
-    for entry in &db_entries {
-        if is_test_or_quiz(entry) {
-            let sessions = generate_study_sessions(entry, today_naive, study_days);
-            for session in sessions {
-                if db::insert_entry_if_not_exists(&conn, &session).unwrap_or(false) {
-                    created += 1;
-                }
+    let linked: Vec<LinkedEntry> = links
+        .into_iter()
+        .filter_map(|link| {
+            let outgoing = link.from_id == id;
+            let other = if outgoing { &link.to_id } else { &link.from_id };
+            let entry = db::get_entry(&conn, other).ok().flatten()?;
+            Some(LinkedEntry {
+                link_id: link.id,
+                relation: link.relation,
+                outgoing,
+                entry,
+            })
+        })
+        .collect();
+    Json(linked).into_response()
+}
+
+/// Link an entry to another. `depends_on` links may not form a cycle.
+async fn create_link_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+    ApiJson(req): ApiJson<CreateLinkRequest>,
+) -> impl IntoResponse {
+    if req.to_id == id {
+        return ApiError::validation("An entry can't link to itself").into_response();
+    }
+
+    let conn = state.conn.lock().unwrap();
+    for entry_id in [&id, &req.to_id] {
+        match db::entry_exists(&conn, entry_id) {
+            Ok(true) => {}
+            Ok(false) => return ApiError::not_found("Entry not found").into_response(),
+            Err(e) => {
+                error!(error = %e, id = %entry_id, "Failed to get entry");
+                return ApiError::from_db(&e, "Database error").into_response();
             }
         }
-        if let Some(reminder) = generate_work_reminder(entry, today_naive, &work_days, days_ahead) {
-            if db::insert_entry_if_not_exists(&conn, &reminder).unwrap_or(false) {
-                created += 1;
+    }
+
+    if req.relation == LinkRelation::DependsOn {
+        match db::depends_on_reaches(&conn, &req.to_id, &id) {
+            Ok(false) => {}
+            Ok(true) => {
+                return ApiError::conflict("That entry already depends on this one").into_response()
+            }
+            Err(e) => {
+                error!(error = %e, id = %id, "Failed to check link cycle");
+                return ApiError::from_db(&e, "Database error").into_response();
             }
         }
     }
 
-    info!(deleted, created, "Reprocess complete");
-
-    (
-        StatusCode::OK,
-        Json(serde_json::json!({ "deleted": deleted, "created": created })),
-    )
-        .into_response()
-}
-
-// ========== Settings handlers ==========
-
-#[derive(Debug, Serialize, Deserialize)]
-struct WorkDaysRequest {
-    days: Vec<u32>,
-}
-
-#[derive(Debug, Serialize, Deserialize)]
-struct WorkDaysResponse {
-    days: Vec<u32>,
-}
+    if req.relation == LinkRelation::CrossListed {
+        match db::cross_list_conflict(&conn, &id, &req.to_id) {
+            Ok(None) => {}
+            Ok(Some(reason)) => return ApiError::conflict(reason).into_response(),
+            Err(e) => {
+                error!(error = %e, id = %id, "Failed to check cross-listing");
+                return ApiError::from_db(&e, "Database error").into_response();
+            }
+        }
+    }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SingleValueRequest {
-    value: u32,
+    let created = match req.relation {
+        LinkRelation::CrossListed => db::cross_list(&conn, &id, &req.to_id),
+        relation => db::create_link(&conn, &id, &req.to_id, relation),
+    };
+    match created {
+        Ok(link) => {
+            debug!(from = %link.from_id, to = %link.to_id, relation = link.relation.as_str(), "Link created");
+            (StatusCode::CREATED, Json(link)).into_response()
+        }
+        Err(e) if db::is_unique_violation(&e) => {
+            ApiError::conflict("These entries are already linked").into_response()
+        }
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to create link");
+            ApiError::from_db(&e, "Failed to create link").into_response()
+        }
+    }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct SingleValueResponse {
-    value: u32,
-}
+/// Create an expiring read-only share link for an entry
+async fn create_share_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+    ApiJson(req): ApiJson<CreateShareRequest>,
+) -> impl IntoResponse {
+    let days = req.days.unwrap_or(SHARE_DEFAULT_DAYS);
+    if !(1..=SHARE_MAX_DAYS).contains(&days) {
+        return ApiError::validation(format!("days must be between 1 and {}", SHARE_MAX_DAYS))
+            .into_response();
+    }
 
-async fn settings_page_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     let conn = state.conn.lock().unwrap();
-    let work_days = db::get_work_days(&conn).unwrap_or_else(|_| vec![1, 2, 3, 4, 5]);
-    let days_ahead = db::get_homework_days_ahead(&conn).unwrap_or(2);
-    let study_days = db::get_study_days_before(&conn).unwrap_or(4);
-    Html(html::render_settings_page(
-        &work_days, days_ahead, study_days,
-    ))
-}
+    let entry = match db::get_entry(&conn, &id) {
+        Ok(Some(entry)) => entry,
+        Ok(None) => return ApiError::not_found("Entry not found").into_response(),
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to get entry");
+            return ApiError::from_db(&e, "Database error").into_response();
+        }
+    };
 
-async fn get_work_days_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
-    let conn = state.conn.lock().unwrap();
-    let days = db::get_work_days(&conn).unwrap_or_else(|_| vec![1, 2, 3, 4, 5]);
-    Json(WorkDaysResponse { days })
+    let now = chrono::Utc::now();
+    if let Err(e) = db::delete_expired_share_links(&conn, now) {
+        warn!(error = %e, "Failed to delete expired share links");
+    }
+    match db::create_share_link(&conn, &id, now + chrono::Duration::days(days as i64)) {
+        Ok(link) => {
+            debug!(id = %id, expires_at = %link.expires_at, "Share link created");
+            let response = ShareLinkResponse {
+                url: format!("/share/{}?token={}", entry.stable_id(), link.token),
+                expires_at: link.expires_at,
+            };
+            (StatusCode::CREATED, Json(response)).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to create share link");
+            ApiError::from_db(&e, "Failed to create share link").into_response()
+        }
+    }
 }
 
-async fn set_work_days_handler(
+/// Read-only page for one shared entry. The token must be unexpired and
+/// belong to the entry the path names; anything else gets the same page.
+async fn share_page_handler(
     State(state): State<Arc<AppState>>,
-    Json(body): Json<WorkDaysRequest>,
+    AxumPath(stable_id): AxumPath<String>,
+    Query(query): Query<ShareQuery>,
 ) -> impl IntoResponse {
-    let conn = state.conn.lock().unwrap();
-    match db::set_work_days(&conn, &body.days) {
-        Ok(()) => (StatusCode::OK, Json(WorkDaysResponse { days: body.days })).into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed: {}", e)).into_response(),
-    }
-}
+    let unavailable = |status: StatusCode| {
+        (status, Html(html::share::render_share_unavailable())).into_response()
+    };
 
-async fn get_homework_days_ahead_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     let conn = state.conn.lock().unwrap();
-    let value = db::get_homework_days_ahead(&conn).unwrap_or(2);
-    Json(SingleValueResponse { value })
+    let link = match db::get_share_link(&conn, &query.token) {
+        Ok(Some(link)) => link,
+        Ok(None) => return unavailable(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!(error = %e, "Failed to get share link");
+            return ApiError::from_db(&e, "Database error").into_response();
+        }
+    };
+    if link.is_expired(chrono::Utc::now()) {
+        return unavailable(StatusCode::GONE);
+    }
+    match db::get_entry(&conn, &link.entry_id) {
+        Ok(Some(entry)) if entry.stable_id() == stable_id => {
+            Html(html::share::render_share_page(&entry)).into_response()
+        }
+        Ok(_) => unavailable(StatusCode::NOT_FOUND),
+        Err(e) => {
+            error!(error = %e, "Failed to get shared entry");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
 }
 
-async fn set_homework_days_ahead_handler(
+/// Remove a link (the entries stay)
+async fn delete_link_handler(
     State(state): State<Arc<AppState>>,
-    Json(body): Json<SingleValueRequest>,
+    AxumPath(id): AxumPath<i64>,
 ) -> impl IntoResponse {
     let conn = state.conn.lock().unwrap();
-    let clamped = body.value.clamp(1, 2);
-    match db::set_homework_days_ahead(&conn, clamped) {
-        Ok(()) => (StatusCode::OK, Json(SingleValueResponse { value: clamped })).into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed: {}", e)).into_response(),
+    match db::split_cross_listed(&conn, id) {
+        Ok(Some(link)) => return Json(link).into_response(),
+        Ok(None) => {}
+        Err(e) => {
+            error!(error = %e, id, "Failed to split cross-listed entries");
+            return ApiError::from_db(&e, "Failed to delete link").into_response();
+        }
+    }
+    match db::delete_link(&conn, id) {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => ApiError::not_found("Link not found").into_response(),
+        Err(e) => {
+            error!(error = %e, id, "Failed to delete link");
+            ApiError::from_db(&e, "Failed to delete link").into_response()
+        }
     }
 }
 
-async fn get_study_days_before_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+// ========== Reactions ==========
+
+/// Every reaction, oldest first
+async fn reactions_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
     let conn = state.conn.lock().unwrap();
-    let value = db::get_study_days_before(&conn).unwrap_or(4);
-    Json(SingleValueResponse { value })
+    match db::get_reactions(&conn) {
+        Ok(reactions) => Json(reactions).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get reactions");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
 }
 
-async fn set_study_days_before_handler(
+/// An entry's reactions, oldest first
+async fn entry_reactions_handler(
     State(state): State<Arc<AppState>>,
-    Json(body): Json<SingleValueRequest>,
+    AxumPath(id): AxumPath<String>,
 ) -> impl IntoResponse {
     let conn = state.conn.lock().unwrap();
-    let clamped = body.value.max(3);
-    match db::set_study_days_before(&conn, clamped) {
-        Ok(()) => (StatusCode::OK, Json(SingleValueResponse { value: clamped })).into_response(),
-        Err(e) => (StatusCode::INTERNAL_SERVER_ERROR, format!("Failed: {}", e)).into_response(),
+    match db::entry_exists(&conn, &id) {
+        Ok(true) => {}
+        Ok(false) => return ApiError::not_found("Entry not found").into_response(),
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to get entry");
+            return ApiError::from_db(&e, "Database error").into_response();
+        }
+    }
+    match db::get_reactions_for_entry(&conn, &id) {
+        Ok(reactions) => Json(reactions).into_response(),
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to get reactions");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use super::*;
-    use axum::body::Body;
-    use axum::http::{Method, Request, StatusCode};
-    use http_body_util::BodyExt;
-    use std::sync::Mutex as StdMutex;
-    use tempfile::TempDir;
+/// React to a completed entry with an emoji, a short message, or both
+async fn create_reaction_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+    ApiJson(req): ApiJson<CreateReactionRequest>,
+) -> impl IntoResponse {
+    let non_empty = |s: Option<String>| s.map(|s| s.trim().to_string()).filter(|s| !s.is_empty());
+    let emoji = non_empty(req.emoji);
+    let message = non_empty(req.message);
+    let author = non_empty(req.author);
+
+    if emoji.is_none() && message.is_none() {
+        return ApiError::validation("A reaction needs an emoji or a message").into_response();
+    }
+    if let Some(emoji) = &emoji {
+        if !REACTION_EMOJI.contains(&emoji.as_str()) {
+            return ApiError::validation(format!(
+                "Emoji must be one of {}",
+                REACTION_EMOJI.join(" ")
+            ))
+            .with_details(serde_json::json!({ "allowed": REACTION_EMOJI }))
+            .into_response();
+        }
+    }
+    if message
+        .as_ref()
+        .is_some_and(|m| m.chars().count() > MAX_REACTION_MESSAGE)
+    {
+        return ApiError::validation(format!(
+            "Messages are at most {} characters",
+            MAX_REACTION_MESSAGE
+        ))
+        .into_response();
+    }
+    if author
+        .as_ref()
+        .is_some_and(|a| a.chars().count() > MAX_REACTION_AUTHOR)
+    {
+        return ApiError::validation(format!(
+            "Author names are at most {} characters",
+            MAX_REACTION_AUTHOR
+        ))
+        .into_response();
+    }
+
+    let conn = state.conn.lock().unwrap();
+    match db::get_entry(&conn, &id) {
+        Ok(Some(entry)) if entry.completed => {}
+        Ok(Some(_)) => {
+            return ApiError::conflict("Only completed entries can get reactions").into_response()
+        }
+        Ok(None) => return ApiError::not_found("Entry not found").into_response(),
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to get entry");
+            return ApiError::from_db(&e, "Database error").into_response();
+        }
+    }
+
+    match db::add_reaction(
+        &conn,
+        &id,
+        emoji.as_deref(),
+        message.as_deref(),
+        author.as_deref(),
+    ) {
+        Ok(reaction) => {
+            debug!(id = %id, reaction_id = reaction.id, "Reaction added");
+            (StatusCode::CREATED, Json(reaction)).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to add reaction");
+            ApiError::from_db(&e, "Failed to add reaction").into_response()
+        }
+    }
+}
+
+async fn delete_reaction_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<i64>,
+) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::delete_reaction(&conn, id) {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => ApiError::not_found("Reaction not found").into_response(),
+        Err(e) => {
+            error!(error = %e, id, "Failed to delete reaction");
+            ApiError::from_db(&e, "Failed to delete reaction").into_response()
+        }
+    }
+}
+
+// ========== Background jobs ==========
+
+/// Every background job with its schedule, next run and last run
+async fn jobs_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match jobs::statuses(&conn, chrono::Local::now().naive_local()) {
+        Ok(statuses) => Json(statuses).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get job runs");
+            ApiError::internal("Database error").into_response()
+        }
+    }
+}
+
+/// Run a job now, outside its schedule
+async fn run_job_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(name): AxumPath<String>,
+) -> impl IntoResponse {
+    match jobs::find(&name) {
+        Some(job) => Json(jobs::run(state, job).await).into_response(),
+        None => ApiError::not_found("Job not found").into_response(),
+    }
+}
+
+// ========== Subjects ==========
+
+/// Subjects in use with entry counts, plus likely duplicates to merge
+async fn subjects_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_subject_counts(&conn) {
+        Ok(subjects) => Json(SubjectsReport {
+            suggestions: subjects::suggest_merges(&subjects),
+            subjects,
+        })
+        .into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get subjects");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Rename one subject to another everywhere, and map it on future imports
+async fn merge_subjects_handler(
+    State(state): State<Arc<AppState>>,
+    ApiJson(req): ApiJson<MergeSubjectsRequest>,
+) -> impl IntoResponse {
+    let (from, into) = (req.from.trim(), req.into.trim());
+    if from.is_empty() || into.is_empty() {
+        return ApiError::validation("from and into are required").into_response();
+    }
+    if from == into {
+        return ApiError::validation("from and into are the same subject").into_response();
+    }
+
+    let conn = state.conn.lock().unwrap();
+    match subjects::merge(&conn, &state.rules_path, from, into) {
+        Ok(merge) => Json(merge).into_response(),
+        Err(e) => {
+            error!(error = %e, from = %from, into = %into, "Failed to merge subjects");
+            ApiError::internal("Failed to merge subjects").into_response()
+        }
+    }
+}
+
+/// Past subject merges, newest first
+async fn subject_merges_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_subject_merges(&conn) {
+        Ok(merges) => Json(merges).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get subject merges");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+// ========== Text fixes ==========
+
+/// Replace a typo or encoding artifact in entry tasks, or preview it with
+/// `dry_run`
+async fn fix_text_handler(
+    State(state): State<Arc<AppState>>,
+    ApiJson(req): ApiJson<FixTextRequest>,
+) -> impl IntoResponse {
+    if req.find.is_empty() {
+        return ApiError::validation("find is required").into_response();
+    }
+    if req.find == req.replace {
+        return ApiError::validation("find and replace are the same").into_response();
+    }
+
+    let conn = state.conn.lock().unwrap();
+    match db::fix_text(
+        &conn,
+        &req.find,
+        &req.replace,
+        req.subject.as_deref(),
+        req.dry_run,
+    ) {
+        Ok(report) => {
+            if let Some(fix) = &report.fix {
+                info!(find = %fix.find, replace = %fix.replace, entries = fix.entries, "Fixed text");
+            }
+            Json(report).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, find = %req.find, "Failed to fix text");
+            ApiError::from_db(&e, "Failed to fix text").into_response()
+        }
+    }
+}
+
+/// Past text fixes, newest first
+async fn text_fixes_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_text_fixes(&conn) {
+        Ok(fixes) => Json(fixes).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get text fixes");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Every subject in use that has an icon, built-in or set here
+async fn subject_icons_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let report = db::get_subject_counts(&conn).and_then(|counts| {
+        let icons = subjects::SubjectIcons::load(&conn)?;
+        Ok(icons.report(&counts))
+    });
+    match report {
+        Ok(icons) => Json(icons).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get subject icons");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Give a subject its own icon instead of the built-in one
+async fn set_subject_icon_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(subject): AxumPath<String>,
+    ApiJson(req): ApiJson<SetIconRequest>,
+) -> impl IntoResponse {
+    let (subject, icon) = (subject.trim(), req.icon.trim());
+    if subject.is_empty() {
+        return ApiError::validation("Subject is required").into_response();
+    }
+    if !subjects::valid_icon(icon) {
+        return ApiError::validation(format!(
+            "icon must be an emoji of at most {} characters, without spaces",
+            subjects::MAX_ICON_CHARS
+        ))
+        .into_response();
+    }
+
+    let conn = state.conn.lock().unwrap();
+    match db::set_subject_icon(&conn, subject, icon) {
+        Ok(()) => {
+            debug!(subject = %subject, icon = %icon, "Subject icon set");
+            Json(SubjectIcon {
+                subject: subject.to_string(),
+                icon: icon.to_string(),
+                custom: true,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            error!(error = %e, subject = %subject, "Failed to set subject icon");
+            ApiError::from_db(&e, "Failed to set subject icon").into_response()
+        }
+    }
+}
+
+/// Back to the built-in icon (or none)
+async fn delete_subject_icon_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(subject): AxumPath<String>,
+) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::delete_subject_icon(&conn, &subject) {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => ApiError::not_found("Subject has no icon of its own").into_response(),
+        Err(e) => {
+            error!(error = %e, subject = %subject, "Failed to delete subject icon");
+            ApiError::from_db(&e, "Failed to delete subject icon").into_response()
+        }
+    }
+}
+
+// ========== Attachments ==========
+
+/// Every attachment with its OCR status and text
+async fn attachments_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_attachments(&conn) {
+        Ok(attachments) => Json(attachments).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get attachments");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// One entry's attachments
+async fn entry_attachments_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_entry_attachments(&conn, &id) {
+        Ok(attachments) => Json(attachments).into_response(),
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to get attachments");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// A local copy of an attachment (a photo from the portal or a captured
+/// diary page)
+async fn attachment_file_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<i64>,
+) -> impl IntoResponse {
+    let file = {
+        let conn = state.conn.lock().unwrap();
+        match db::get_attachment(&conn, id) {
+            Ok(attachment) => attachment.file,
+            Err(e) => {
+                error!(error = %e, id, "Failed to get attachment");
+                return ApiError::from_db(&e, "Database error").into_response();
+            }
+        }
+    };
+    let Some(file) = file.map(PathBuf::from) else {
+        return ApiError::not_found("Attachment has no local copy").into_response();
+    };
+    match tokio::fs::read(&file).await {
+        Ok(bytes) => (
+            [(header::CONTENT_TYPE, capture::content_type_for(&file))],
+            bytes,
+        )
+            .into_response(),
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+            ApiError::not_found("Attachment file is missing").into_response()
+        }
+        Err(e) => {
+            error!(error = %e, file = %file.display(), "Failed to read attachment");
+            ApiError::internal("Failed to read attachment").into_response()
+        }
+    }
+}
+
+/// Export files in data/ with how many entries came from each, then those
+/// entries name that are gone from data/
+async fn source_files_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let counts = {
+        let conn = state.conn.lock().unwrap();
+        match db::count_entries_by_source_file(&conn) {
+            Ok(counts) => counts,
+            Err(e) => {
+                error!(error = %e, "Failed to count entries by export file");
+                return ApiError::from_db(&e, "Database error").into_response();
+            }
+        }
+    };
+    match data::find_all_exports() {
+        Ok(files) => Json(source_files(&files, counts)).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to list export files");
+            ApiError::internal("Failed to list export files").into_response()
+        }
+    }
+}
+
+fn source_files(files: &[PathBuf], mut counts: BTreeMap<String, usize>) -> Vec<SourceFile> {
+    let mut listed: Vec<SourceFile> = files
+        .iter()
+        .filter_map(|path| {
+            let name = path.file_name()?.to_string_lossy().into_owned();
+            let metadata = std::fs::metadata(path).ok();
+            Some(SourceFile {
+                entries: counts.remove(&name).unwrap_or(0),
+                name,
+                available: true,
+                bytes: metadata.as_ref().map(|m| m.len()),
+                modified: metadata
+                    .and_then(|m| m.modified().ok())
+                    .map(|t| chrono::DateTime::<chrono::Utc>::from(t).to_rfc3339()),
+            })
+        })
+        .collect();
+    listed.extend(counts.into_iter().map(|(name, entries)| SourceFile {
+        name,
+        available: false,
+        bytes: None,
+        modified: None,
+        entries,
+    }));
+    listed
+}
+
+/// Download an export file from data/ as the school published it
+async fn source_file_handler(AxumPath(name): AxumPath<String>) -> Response {
+    serve_source_file(&name).await
+}
+
+/// Download the export file an entry was imported from
+async fn entry_source_file_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+) -> Response {
+    let file = {
+        let conn = state.conn.lock().unwrap();
+        match db::get_entry(&conn, &id) {
+            Ok(Some(entry)) => entry.source_file,
+            Ok(None) => return ApiError::not_found("Entry not found").into_response(),
+            Err(e) => {
+                error!(error = %e, id = %id, "Failed to get entry");
+                return ApiError::from_db(&e, "Database error").into_response();
+            }
+        }
+    };
+    match file {
+        Some(name) => serve_source_file(&name).await,
+        None => ApiError::not_found("Entry wasn't imported from an export file").into_response(),
+    }
+}
+
+/// Only names `data::find_all_exports` lists are served, so a name can't
+/// reach outside data/ or anything in it but exports
+async fn serve_source_file(name: &str) -> Response {
+    let path = match data::find_all_exports() {
+        Ok(files) => files
+            .into_iter()
+            .find(|path| path.file_name().is_some_and(|n| n == name)),
+        Err(e) => {
+            error!(error = %e, "Failed to list export files");
+            return ApiError::internal("Failed to list export files").into_response();
+        }
+    };
+    let Some(path) = path else {
+        return ApiError::not_found("Export file isn't in data/ any more").into_response();
+    };
+    let content_type = if name.ends_with(".xlsx") {
+        "application/vnd.openxmlformats-officedocument.spreadsheetml.sheet"
+    } else {
+        "application/vnd.ms-excel"
+    };
+    match tokio::fs::read(&path).await {
+        Ok(bytes) => (
+            [
+                (header::CONTENT_TYPE, content_type.to_string()),
+                (
+                    header::CONTENT_DISPOSITION,
+                    format!("attachment; filename=\"{}\"", name.replace('"', "_")),
+                ),
+            ],
+            bytes,
+        )
+            .into_response(),
+        Err(e) => {
+            error!(error = %e, file = %path.display(), "Failed to read export file");
+            ApiError::internal("Failed to read export file").into_response()
+        }
+    }
+}
+
+/// Stage a draft entry from a photo of the diary page, sent as the request
+/// body, reading its text first when OCR is on (see `capture`)
+async fn capture_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CaptureQuery>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> impl IntoResponse {
+    let content_type = headers
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .unwrap_or_default();
+    let Some(extension) = capture::photo_extension(content_type) else {
+        return ApiError::validation(format!(
+            "Send the photo as the body, with Content-Type one of {}",
+            capture::accepted_types()
+        ))
+        .into_response();
+    };
+    if body.is_empty() {
+        return ApiError::validation("The photo is empty").into_response();
+    }
+    let date = match query.date.as_deref() {
+        Some(date) if NaiveDate::parse_from_str(date, "%Y-%m-%d").is_err() => {
+            return ApiError::validation("Date must be YYYY-MM-DD").into_response();
+        }
+        Some(date) => date.to_string(),
+        None => chrono::Local::now().date_naive().to_string(),
+    };
+    let entry_type = query
+        .entry_type
+        .filter(|t| !t.trim().is_empty())
+        .unwrap_or_else(|| "compiti".to_string());
+
+    let mut draft = HomeworkEntry::new(
+        entry_type,
+        date,
+        query.subject.trim().to_string(),
+        String::new(),
+    );
+    let source_id = format!("{}{}", capture::SOURCE_PREFIX, draft.id);
+    let file = match capture::save_photo(&state.captures_dir, &source_id, extension, &body) {
+        Ok(file) => file,
+        Err(e) => {
+            error!(error = %e, "Failed to save captured photo");
+            return ApiError::internal("Failed to save the photo").into_response();
+        }
+    };
+
+    // Read outside the lock: OCR can take a while
+    let ocr_on = features::enabled(&state.conn.lock().unwrap(), Feature::Ocr);
+    let ocr = if ocr_on {
+        let file = file.clone();
+        tokio::task::spawn_blocking(move || ocr::recognize_now(&file))
+            .await
+            .unwrap_or(None)
+    } else {
+        None
+    };
+    draft.task = capture::draft_task(ocr.as_ref().and_then(|r| r.as_deref().ok()));
+
+    let photo = NewAttachment {
+        source_id,
+        name: capture::PHOTO_NAME.to_string(),
+        url: file
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        file: Some(file.to_string_lossy().into_owned()),
+    };
+    let conn = state.conn.lock().unwrap();
+    let staged = db::stage_capture(&conn, &draft, &photo)
+        .inspect_err(|_| {
+            // Not staged, so nothing refers to the photo: don't keep it
+            if let Err(e) = std::fs::remove_file(&file) {
+                warn!(error = %e, file = %file.display(), "Failed to remove captured photo");
+            }
+        })
+        .and_then(|id| {
+            if let Some(result) = &ocr {
+                db::set_ocr_result(&conn, id, result.as_deref().map_err(String::as_str))?;
+            }
+            let photo = db::get_attachment(&conn, id)?;
+            let draft = db::get_pending_imports(&conn)?
+                .into_iter()
+                .find(|p| p.id == draft.id)
+                .ok_or_else(|| db::DbError::NotFound("Pending import".to_string()))?;
+            Ok(CaptureResponse { draft, photo })
+        });
+    match staged {
+        Ok(response) => {
+            info!(id = %response.draft.id, read = ocr.is_some(), "Diary photo staged for review");
+            (StatusCode::CREATED, Json(response)).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to stage captured photo");
+            ApiError::from_db(&e, "Failed to stage the photo").into_response()
+        }
+    }
+}
+
+// ========== Subject resources ==========
+
+/// Every subject's study links, for drawing quick links on the list
+async fn resources_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_resources(&conn) {
+        Ok(resources) => Json(resources).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get resources");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// One subject's study links
+async fn subject_resources_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(subject): AxumPath<String>,
+) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_resources_for_subject(&conn, &subject) {
+        Ok(resources) => Json(resources).into_response(),
+        Err(e) => {
+            error!(error = %e, subject = %subject, "Failed to get resources");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Add a study link to a subject. Only http(s) urls, since they end up as
+/// links on the page.
+async fn create_resource_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(subject): AxumPath<String>,
+    ApiJson(req): ApiJson<CreateResourceRequest>,
+) -> impl IntoResponse {
+    let url = req.url.trim();
+    if subject.trim().is_empty() {
+        return ApiError::validation("Subject is required").into_response();
+    }
+    if !(url.starts_with("https://") || url.starts_with("http://")) {
+        return ApiError::validation("url must start with http:// or https://").into_response();
+    }
+    let title = match req.title.trim() {
+        "" => url,
+        title => title,
+    };
+
+    let conn = state.conn.lock().unwrap();
+    match db::create_resource(&conn, &subject, title, url) {
+        Ok(resource) => {
+            debug!(subject = %resource.subject, id = resource.id, "Resource added");
+            (StatusCode::CREATED, Json(resource)).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, subject = %subject, "Failed to add resource");
+            ApiError::from_db(&e, "Failed to add resource").into_response()
+        }
+    }
+}
+
+/// Remove one of a subject's study links
+async fn delete_resource_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath((subject, id)): AxumPath<(String, i64)>,
+) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::delete_resource(&conn, &subject, id) {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => ApiError::not_found("Resource not found").into_response(),
+        Err(e) => {
+            error!(error = %e, id, "Failed to delete resource");
+            ApiError::from_db(&e, "Failed to delete resource").into_response()
+        }
+    }
+}
+
+/// Return one month of entries grouped by date, for the calendar view
+async fn calendar_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CalendarQuery>,
+) -> impl IntoResponse {
+    let Some((first, last)) = html::calendar::month_bounds(query.year, query.month) else {
+        return ApiError::validation("Invalid year or month").into_response();
+    };
+
+    let conn = state.conn.lock().unwrap();
+    let (from, to) = (first.to_string(), last.to_string());
+    let result = db::get_entries_overlapping(&conn, &from, &to).and_then(|entries| {
+        Ok((
+            entries,
+            db::get_holidays_in_range(&conn, &from, &to)?,
+            db::get_activities_in_range(&conn, &from, &to)?,
+        ))
+    });
+    match result {
+        Ok((entries, holidays, activities)) => {
+            let mut by_date: BTreeMap<String, Vec<Activity>> = BTreeMap::new();
+            for activity in activities {
+                by_date
+                    .entry(activity.date.clone())
+                    .or_default()
+                    .push(activity);
+            }
+            Json(CalendarMonthResponse {
+                year: query.year,
+                month: query.month,
+                entries: html::calendar::group_by_date(&entries)
+                    .into_iter()
+                    .filter(|(date, _)| (from.as_str()..=to.as_str()).contains(&date.as_str()))
+                    .collect(),
+                holidays: holidays.into_iter().map(|h| (h.date, h.name)).collect(),
+                activities: by_date,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to get calendar entries");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// All imported school holidays
+async fn holidays_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_holidays(&conn) {
+        Ok(holidays) => Json(holidays).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get holidays");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Imported activities between `from` and `to` (default: everything)
+async fn activities_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ListQuery>,
+) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let from = query.from.as_deref().unwrap_or("0000-01-01");
+    let to = query.to.as_deref().unwrap_or("9999-12-31");
+    match db::get_activities_in_range(&conn, from, to) {
+        Ok(activities) => Json(activities).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get activities");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Spoken summary of today's entries
+async fn agenda_today_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    agenda_response(&state, chrono::Local::now().date_naive())
+}
+
+/// Spoken summary of the entries on `date` (`YYYY-MM-DD`)
+async fn agenda_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(date): AxumPath<String>,
+) -> impl IntoResponse {
+    match chrono::NaiveDate::parse_from_str(&date, "%Y-%m-%d") {
+        Ok(date) => agenda_response(&state, date),
+        Err(_) => ApiError::validation("Invalid date, expected YYYY-MM-DD").into_response(),
+    }
+}
+
+fn agenda_response(state: &AppState, date: chrono::NaiveDate) -> axum::response::Response {
+    let day = date.format("%Y-%m-%d").to_string();
+    let conn = state.conn.lock().unwrap();
+    let filter = db::EntryFilter {
+        from: Some(day.clone()),
+        to: Some(day.clone()),
+        skip_cross_listed_copies: true,
+        ..Default::default()
+    };
+    match db::get_entries_filtered(&conn, &filter) {
+        Ok(entries) => Json(agenda::build_agenda(date, &entries)).into_response(),
+        Err(e) => {
+            error!(error = %e, date = %day, "Failed to get agenda entries");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Suggested order of work for today, within the daily time budget
+async fn plan_today_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let today = chrono::Local::now().date_naive();
+    let conn = state.conn.lock().unwrap();
+    let budget = db::get_daily_budget_minutes(&conn).unwrap_or(120);
+    // Hidden entries stay out, as in the simple view's plan
+    let filter = db::EntryFilter {
+        visible_on: Some(today.to_string()),
+        skip_cross_listed_copies: true,
+        ..Default::default()
+    };
+    match db::get_entries_filtered(&conn, &filter) {
+        Ok(entries) => Json(plan::build_plan(&entries, today, budget)).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get entries for today's plan");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Workload of the coming weeks, study sessions and reminders still to be
+/// generated included
+async fn forecast_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<ForecastQuery>,
+) -> impl IntoResponse {
+    let weeks = query.weeks.unwrap_or(forecast::DEFAULT_WEEKS);
+    if weeks == 0 || weeks > forecast::MAX_WEEKS {
+        return ApiError::validation(format!(
+            "weeks must be between 1 and {}",
+            forecast::MAX_WEEKS
+        ))
+        .into_response();
+    }
+    let today = chrono::Local::now().date_naive();
+    let conn = state.conn.lock().unwrap();
+    let filter = db::EntryFilter {
+        skip_cross_listed_copies: true,
+        ..Default::default()
+    };
+    let entries = match db::get_entries_filtered(&conn, &filter) {
+        Ok(entries) => entries,
+        Err(e) => {
+            error!(error = %e, "Failed to get entries for the forecast");
+            return ApiError::from_db(&e, "Database error").into_response();
+        }
+    };
+    let settings = generation_settings(&conn);
+    let holidays = db::get_holiday_dates(&conn).unwrap_or_default();
+    let activity_minutes = db::get_activity_minutes(&conn).unwrap_or_default();
+    let projected =
+        forecast::project_auto_entries(&entries, &settings, today, &holidays, &activity_minutes);
+    Json(forecast::build_forecast(
+        &entries,
+        &projected,
+        &activity_minutes,
+        today,
+        weeks,
+    ))
+    .into_response()
+}
+
+/// Build info, and the latest release when the update check found one
+async fn version_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let (schema_version, check_updates) = {
+        let conn = state.conn.lock().unwrap();
+        let settings = db::get_settings(&conn).unwrap_or_default();
+        match db::get_schema_version(&conn) {
+            Ok(v) => (v, settings.check_updates),
+            Err(e) => {
+                error!(error = %e, "Failed to get schema version");
+                return ApiError::from_db(&e, "Database error").into_response();
+            }
+        }
+    };
+    // Turning the setting off hides a release found earlier too
+    let latest = check_updates
+        .then(|| state.latest_release.lock().unwrap().clone())
+        .flatten();
+    Json(version::VersionInfo::new(schema_version, latest)).into_response()
+}
+
+/// What a manual refresh did
+#[derive(Debug, Serialize)]
+struct RefreshSummary {
+    imported: usize,
+    study_sessions: usize,
+    work_reminders: usize,
+    /// What became of each export file's rows, skipped ones included
+    reports: Vec<data::FileReport>,
+}
+
+/// Refresh data from disk (re-process export files)
+async fn refresh_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    info!("Manual refresh triggered");
+
+    let conn = state.conn.lock().unwrap();
+    import_register(&conn);
+
+    match data::parse_exports() {
+        Ok(parsed) => {
+            let imported = import_exports(&conn, &parsed).unwrap_or(0);
+            let today = chrono::Local::now().date_naive();
+            let settings = generation_settings(&conn);
+            let holidays = db::get_holiday_dates(&conn).unwrap_or_default();
+            let activity_minutes = db::get_activity_minutes(&conn).unwrap_or_default();
+            let db_entries = db::get_all_entries(&conn).unwrap_or_default();
+            let mut study_sessions_created = 0;
+            let mut work_reminders_created = 0;
+            for entry in &db_entries {
+                if settings.study_sessions && is_test_or_quiz(entry) {
+                    let sessions = generate_study_sessions(
+                        entry,
+                        today,
+                        settings.study_days_before,
+                        &holidays,
+                        &activity_minutes,
+                        settings.split_study_topics,
+                    );
+                    study_sessions_created +=
+                        db::insert_generated(&conn, entry, &sessions).unwrap_or(0);
+                }
+                if let Some(reminder) = generate_work_reminder(
+                    entry,
+                    today,
+                    &settings.work_days,
+                    settings.homework_days_ahead,
+                ) {
+                    work_reminders_created +=
+                        db::insert_generated(&conn, entry, &[reminder]).unwrap_or(0);
+                }
+            }
+            if imported > 0 || study_sessions_created > 0 || work_reminders_created > 0 {
+                info!(
+                    imported,
+                    study_sessions = study_sessions_created,
+                    work_reminders = work_reminders_created,
+                    "Refresh complete"
+                );
+            }
+            Json(RefreshSummary {
+                imported,
+                study_sessions: study_sessions_created,
+                work_reminders: work_reminders_created,
+                reports: parsed.reports,
+            })
+            .into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Refresh failed");
+            ApiError::internal("Failed to parse export files").into_response()
+        }
+    }
+}
+
+// ========== Reprocess handler ==========
+
+/// Delete all future auto-generated entries and regenerate them using the
+/// current settings (work days, homework_days_ahead, study_days_before).
+/// Past entries and their completed state are never touched.
+async fn reprocess_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let today = chrono::Local::now()
+        .date_naive()
+        .format("%Y-%m-%d")
+        .to_string();
+
+    // 1. Delete future generated entries
+    let deleted = match db::delete_future_generated_entries(&conn, &today) {
+        Ok(n) => n,
+        Err(e) => {
+            error!(error = %e, "Failed to delete generated entries for reprocess");
+            return ApiError::from_db(&e, "Failed to delete generated entries").into_response();
+        }
+    };
+    info!(deleted, "Deleted future generated entries for reprocess");
+
+    // 2. Regenerate from all current DB entries
+    let today_naive = chrono::Local::now().date_naive();
+    let settings = generation_settings(&conn);
+    let holidays = db::get_holiday_dates(&conn).unwrap_or_default();
+    let activity_minutes = db::get_activity_minutes(&conn).unwrap_or_default();
+
+    let db_entries = match db::get_all_entries(&conn) {
+        Ok(e) => e,
+        Err(e) => {
+            error!(error = %e, "Failed to load entries for reprocess");
+            return ApiError::from_db(&e, "Failed to load entries").into_response();
+        }
+    };
+
+    let mut created = 0usize;
+    for entry in &db_entries {
+        if settings.study_sessions && is_test_or_quiz(entry) {
+            let sessions = generate_study_sessions(
+                entry,
+                today_naive,
+                settings.study_days_before,
+                &holidays,
+                &activity_minutes,
+                settings.split_study_topics,
+            );
+            created += db::insert_generated(&conn, entry, &sessions).unwrap_or(0);
+        }
+        if let Some(reminder) = generate_work_reminder(
+            entry,
+            today_naive,
+            &settings.work_days,
+            settings.homework_days_ahead,
+        ) {
+            created += db::insert_generated(&conn, entry, &[reminder]).unwrap_or(0);
+        }
+    }
+
+    info!(deleted, created, "Reprocess complete");
+
+    (
+        StatusCode::OK,
+        Json(serde_json::json!({ "deleted": deleted, "created": created })),
+    )
+        .into_response()
+}
+
+// ========== Import review handlers ==========
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct AcceptAllResponse {
+    pub accepted: usize,
+}
+
+async fn imports_page_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let pending = db::get_pending_imports(&conn).and_then(|pending| {
+        let photos: BTreeMap<String, i64> = db::get_attachments(&conn)?
+            .into_iter()
+            .filter(|a| a.source_id.starts_with(capture::SOURCE_PREFIX) && a.file.is_some())
+            .map(|a| (a.source_id, a.id))
+            .collect();
+        Ok((pending, photos))
+    });
+    match pending {
+        Ok((pending, photos)) => Html(html::render_imports_page(&pending, &photos)).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get pending imports");
+            error_page(ApiError::from_db(&e, "Database error"))
+        }
+    }
+}
+
+/// List staged imports awaiting review
+async fn pending_imports_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_pending_imports(&conn) {
+        Ok(pending) => Json(pending).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get pending imports");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Parse the export files again and report what became of their rows
+async fn imports_report_handler() -> impl IntoResponse {
+    match data::find_all_exports() {
+        Ok(files) if files.is_empty() => {
+            return Json(Vec::<data::FileReport>::new()).into_response()
+        }
+        Ok(_) => {}
+        Err(e) => {
+            error!(error = %e, "Failed to list export files");
+            return ApiError::internal("Failed to list export files").into_response();
+        }
+    }
+    match data::parse_exports() {
+        Ok(parsed) => Json(parsed.reports).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to parse export files");
+            ApiError::internal("Failed to parse export files").into_response()
+        }
+    }
+}
+
+/// Accept one staged import and generate its auto-entries
+async fn accept_import_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::accept_pending_import(&conn, &id) {
+        Ok(Some(entry)) => {
+            // Staged imports reach `entries` only here, so link them here too
+            if let Err(e) = link_cross_listed(&conn) {
+                warn!(error = %e, "Failed to link cross-listed entries");
+            }
+            generate_auto_entries(&conn, &entry);
+            debug!(id = %entry.id, "Import accepted");
+            Json(entry).into_response()
+        }
+        Ok(None) => ApiError::not_found("Pending import not found").into_response(),
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to accept import");
+            ApiError::from_db(&e, "Failed to accept import").into_response()
+        }
+    }
+}
+
+/// Reject one staged import
+async fn reject_import_handler(
+    State(state): State<Arc<AppState>>,
+    AxumPath(id): AxumPath<String>,
+) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::reject_pending_import(&conn, &id) {
+        Ok(true) => StatusCode::NO_CONTENT.into_response(),
+        Ok(false) => ApiError::not_found("Pending import not found").into_response(),
+        Err(e) => {
+            error!(error = %e, id = %id, "Failed to reject import");
+            ApiError::from_db(&e, "Failed to reject import").into_response()
+        }
+    }
+}
+
+/// Accept every staged import
+async fn accept_all_imports_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let pending = match db::get_pending_imports(&conn) {
+        Ok(pending) => pending,
+        Err(e) => {
+            error!(error = %e, "Failed to get pending imports");
+            return ApiError::from_db(&e, "Database error").into_response();
+        }
+    };
+
+    let mut accepted = Vec::new();
+    for item in &pending {
+        match db::accept_pending_import(&conn, &item.id) {
+            Ok(Some(entry)) => accepted.push(entry),
+            Ok(None) => {}
+            Err(e) => error!(error = %e, id = %item.id, "Failed to accept import"),
+        }
+    }
+    // Linked first, so a copy among them gets no study sessions
+    if let Err(e) = link_cross_listed(&conn) {
+        warn!(error = %e, "Failed to link cross-listed entries");
+    }
+    for entry in &accepted {
+        generate_auto_entries(&conn, entry);
+    }
+    info!(count = accepted.len(), "Accepted all pending imports");
+    Json(AcceptAllResponse {
+        accepted: accepted.len(),
+    })
+    .into_response()
+}
+
+// ========== Live updates ==========
+
+/// Server-sent events for presence and completion changes (see `live`).
+/// `?session=` identifies the tab, `?name=` is shown to the others. 404
+/// while the `live` feature is off.
+async fn live_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<LiveQuery>,
+) -> Response {
+    if !features::enabled(&state.conn.lock().unwrap(), Feature::Live) {
+        return ApiError::not_found("Live updates are turned off").into_response();
+    }
+    let session = query
+        .session
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+    let subscription = state
+        .live
+        .join(&session, query.name.as_deref().unwrap_or_default());
+    debug!(session = %session, "Live client connected");
+
+    let events = stream::unfold(subscription, |mut subscription| async move {
+        loop {
+            match subscription.rx.recv().await {
+                Ok(event) => {
+                    let sse = Event::default()
+                        .event(event.name())
+                        .json_data(&event)
+                        .unwrap_or_default();
+                    return Some((Ok::<_, Infallible>(sse), subscription));
+                }
+                // Missed events are only a stale view; carry on with the next
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    Sse::new(events)
+        .keep_alive(KeepAlive::default())
+        .into_response()
+}
+
+// ========== Register handlers ==========
+
+/// Read-only view of the absences and disciplinary notes
+async fn register_page_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let register = db::get_absences(&conn)
+        .and_then(|absences| Ok((absences, db::get_discipline_notes(&conn)?)));
+    match register {
+        Ok((absences, notes)) => {
+            let settings = db::get_settings(&conn).unwrap_or_default();
+            Html(html::render_register_page(&absences, &notes, &settings)).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to load register");
+            error_page(ApiError::from_db(&e, "Database error"))
+        }
+    }
+}
+
+async fn absences_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_absences(&conn) {
+        Ok(absences) => Json(absences).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get absences");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+async fn discipline_notes_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_discipline_notes(&conn) {
+        Ok(notes) => Json(notes).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to get notes");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+// ========== Stats handlers ==========
+
+async fn stats_page_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DaysCompletedQuery>,
+) -> impl IntoResponse {
+    let Some(month) = parse_month(query.month.as_deref()) else {
+        return error_page(ApiError::validation("month must be YYYY-MM"));
+    };
+    let conn = state.conn.lock().unwrap();
+    let stats = db::get_subject_time_stats(&conn).and_then(|time| {
+        Ok((
+            time,
+            db::get_topic_stats(&conn)?,
+            db::get_notice_stats(&conn)?,
+            days_completed_report(&conn, month)?,
+        ))
+    });
+    match stats {
+        Ok((time, topics, notice, days)) => {
+            Html(html::render_stats_page(&time, &topics, &notice, &days)).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to compute stats");
+            error_page(ApiError::from_db(&e, "Database error"))
+        }
+    }
+}
+
+/// Estimated vs actual time per subject
+async fn time_stats_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_subject_time_stats(&conn) {
+        Ok(stats) => Json(stats).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to compute stats");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Entries, tests and time per subject and topic, busiest first
+async fn topic_stats_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_topic_stats(&conn) {
+        Ok(stats) => Json(stats).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to compute topic stats");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Days of notice per subject and teacher, least first
+async fn notice_stats_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_notice_stats(&conn) {
+        Ok(stats) => Json(stats).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to compute notice stats");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Days all done in `?month=YYYY-MM` (default this month) and the streak
+async fn days_completed_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<DaysCompletedQuery>,
+) -> impl IntoResponse {
+    let Some(month) = parse_month(query.month.as_deref()) else {
+        return ApiError::validation("month must be YYYY-MM").into_response();
+    };
+    let conn = state.conn.lock().unwrap();
+    match days_completed_report(&conn, month) {
+        Ok(report) => Json(report).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to load completed days");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// First day of a `YYYY-MM` month, this month's when `None`
+pub fn parse_month(month: Option<&str>) -> Option<NaiveDate> {
+    match month {
+        Some(month) => NaiveDate::parse_from_str(&format!("{}-01", month.trim()), "%Y-%m-%d").ok(),
+        None => chrono::Local::now().date_naive().with_day(1),
+    }
+}
+
+/// The all-done days of the month starting on `first`, and the streaks
+pub fn days_completed_report(
+    conn: &Connection,
+    first: NaiveDate,
+) -> db::Result<DaysCompletedReport> {
+    let (from, to) =
+        html::calendar::month_bounds(first.year(), first.month()).unwrap_or((first, first));
+    let today = chrono::Local::now().date_naive();
+    let (streak, best_streak) = streak::streaks(&db::get_day_outcomes(conn)?, today);
+    Ok(DaysCompletedReport {
+        month: first.format("%Y-%m").to_string(),
+        days: db::get_days_completed(conn, &from.to_string(), &to.to_string())?,
+        streak,
+        best_streak,
+    })
+}
+
+/// Weekly goal attainment for the week containing `?week=YYYY-MM-DD` (default
+/// this week)
+async fn goals_stats_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<GoalsQuery>,
+) -> impl IntoResponse {
+    let day = match query.week.as_deref() {
+        Some(week) => match NaiveDate::parse_from_str(week, "%Y-%m-%d") {
+            Ok(day) => day,
+            Err(_) => return ApiError::validation("Invalid week date").into_response(),
+        },
+        None => chrono::Local::now().date_naive(),
+    };
+    let week = day.week(chrono::Weekday::Mon);
+    let (from, to) = (week.first_day().to_string(), week.last_day().to_string());
+
+    let conn = state.conn.lock().unwrap();
+    let result = db::get_weekly_goals(&conn)
+        .and_then(|goals| db::get_goal_progress(&conn, &goals, &from, &to));
+    match result {
+        Ok(goals) => Json(GoalsReport {
+            met: goals.iter().filter(|g| g.met).count(),
+            week_start: from,
+            week_end: to,
+            goals,
+        })
+        .into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to compute goal progress");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Parse a `YYYY-MM-DD..YYYY-MM-DD` period
+fn parse_period(value: &str) -> Option<(NaiveDate, NaiveDate)> {
+    let (from, to) = value.split_once("..")?;
+    let from = NaiveDate::parse_from_str(from.trim(), "%Y-%m-%d").ok()?;
+    let to = NaiveDate::parse_from_str(to.trim(), "%Y-%m-%d").ok()?;
+    (from <= to).then_some((from, to))
+}
+
+/// The two quadrimestri of the school year containing `today`: September to
+/// January, and February to June.
+fn default_terms(today: NaiveDate) -> [(NaiveDate, NaiveDate); 2] {
+    let start = if today.month() >= 9 {
+        today.year()
+    } else {
+        today.year() - 1
+    };
+    let date = |y, m, d| NaiveDate::from_ymd_opt(y, m, d).unwrap();
+    [
+        (date(start, 9, 1), date(start + 1, 1, 31)),
+        (date(start + 1, 2, 1), date(start + 1, 6, 30)),
+    ]
+}
+
+/// Stats for the two periods in `query`, or the status and message to reply with
+fn compare_stats(state: &AppState, query: &CompareQuery) -> Result<StatsComparison, ApiError> {
+    let [term_a, term_b] = default_terms(chrono::Local::now().date_naive());
+    let period = |value: &Option<String>, default| match value.as_deref() {
+        Some(value) => parse_period(value).ok_or(ApiError::validation(
+            "Invalid period, expected YYYY-MM-DD..YYYY-MM-DD",
+        )),
+        None => Ok(default),
+    };
+    let (a, b) = (period(&query.a, term_a)?, period(&query.b, term_b)?);
+
+    let conn = state.conn.lock().unwrap();
+    let stats = |(from, to): (NaiveDate, NaiveDate)| {
+        db::get_period_stats(&conn, &from.to_string(), &to.to_string())
+    };
+    stats(a)
+        .and_then(|a| Ok(StatsComparison { a, b: stats(b)? }))
+        .map_err(|e| {
+            error!(error = %e, "Failed to compute period stats");
+            ApiError::from_db(&e, "Database error")
+        })
+}
+
+/// Two periods side by side, `?a=` and `?b=` as `YYYY-MM-DD..YYYY-MM-DD`
+/// (default: this school year's quadrimestri)
+async fn compare_stats_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CompareQuery>,
+) -> impl IntoResponse {
+    match compare_stats(&state, &query) {
+        Ok(comparison) => Json(comparison).into_response(),
+        Err(error) => error.into_response(),
+    }
+}
+
+async fn compare_page_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<CompareQuery>,
+) -> impl IntoResponse {
+    match compare_stats(&state, &query) {
+        Ok(comparison) => Html(html::render_compare_page(&comparison)).into_response(),
+        Err(error) => error_page(error),
+    }
+}
+
+/// The per-teacher workload report for `query`'s period
+fn teacher_report(state: &AppState, query: &TeachersQuery) -> Result<TeacherReport, ApiError> {
+    let [(from, _), (_, to)] = default_terms(chrono::Local::now().date_naive());
+    let (from, to) = match query.period.as_deref() {
+        Some(period) => parse_period(period).ok_or(ApiError::validation(
+            "Invalid period, expected YYYY-MM-DD..YYYY-MM-DD",
+        ))?,
+        None => (from, to),
+    };
+    let conn = state.conn.lock().unwrap();
+    // Moved entries are reported on the date the teacher gave, which can
+    // fall outside the range they're stored under now
+    db::get_all_entries(&conn)
+        .map(|entries| teachers::report(&entries, from, to))
+        .map_err(|e| {
+            error!(error = %e, "Failed to compute the teacher report");
+            ApiError::from_db(&e, "Database error")
+        })
+}
+
+/// Entries, tests, notice and weekdays per teacher, `?period=` as
+/// `YYYY-MM-DD..YYYY-MM-DD` (default: this school year)
+async fn teachers_stats_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TeachersQuery>,
+) -> impl IntoResponse {
+    match teacher_report(&state, &query) {
+        Ok(report) => Json(report).into_response(),
+        Err(error) => error.into_response(),
+    }
+}
+
+/// The teacher report as a page to print, or with `download=true` a file
+async fn teachers_page_handler(
+    State(state): State<Arc<AppState>>,
+    Query(query): Query<TeachersQuery>,
+) -> impl IntoResponse {
+    let report = match teacher_report(&state, &query) {
+        Ok(report) => report,
+        Err(error) => return error_page(error),
+    };
+    let page = html::render_teachers_page(&report);
+    if !query.download {
+        return Html(page).into_response();
+    }
+    (
+        [
+            (header::CONTENT_TYPE, "text/html; charset=utf-8".to_string()),
+            (
+                header::CONTENT_DISPOSITION,
+                format!(
+                    "attachment; filename=\"teachers_{}_{}.html\"",
+                    report.from, report.to
+                ),
+            ),
+        ],
+        page,
+    )
+        .into_response()
+}
+
+// ========== Settings handlers ==========
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WorkDaysRequest {
+    days: Vec<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct WorkDaysResponse {
+    days: Vec<u32>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SingleValueRequest {
+    value: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SingleValueResponse {
+    value: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ToggleRequest {
+    enabled: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+struct ToggleResponse {
+    enabled: bool,
+}
+
+async fn settings_page_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let settings = db::get_settings(&conn).unwrap_or_default();
+    Html(html::render_settings_page(&settings))
+}
+
+async fn get_settings_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::get_settings(&conn) {
+        Ok(settings) => Json(settings).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to load settings");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Update any subset of the settings; omitted fields keep their values
+async fn update_settings_handler(
+    State(state): State<Arc<AppState>>,
+    ApiJson(update): ApiJson<SettingsUpdate>,
+) -> impl IntoResponse {
+    let origins = update.cors_origins.iter().flatten();
+    if let Some(bad) = origins
+        .filter(|o| !o.trim().is_empty())
+        .find(|o| cors::normalize_origin(o).is_none())
+    {
+        return ApiError::validation(format!(
+            "Not an origin: {} (expected scheme://host[:port] or *)",
+            bad
+        ))
+        .into_response();
+    }
+
+    let conn = state.conn.lock().unwrap();
+    let result = db::get_settings(&conn).and_then(|mut settings| {
+        settings.apply(update);
+        db::save_settings(&conn, &settings)?;
+        db::get_settings(&conn)
+    });
+    match result {
+        Ok(settings) => {
+            info!(?settings, "Updated settings");
+            Json(settings).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to save settings");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Every feature flag, whether it's on and where that came from
+async fn get_features_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match features::load(&conn) {
+        Ok(flags) => Json(flags).into_response(),
+        Err(e) => {
+            error!(error = %e, "Failed to load feature flags");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+/// Switch any of the flags, e.g. `{"watcher": false}`. A flag set through
+/// its environment variable keeps that value until the variable goes.
+async fn update_features_handler(
+    State(state): State<Arc<AppState>>,
+    ApiJson(update): ApiJson<BTreeMap<String, bool>>,
+) -> impl IntoResponse {
+    if let Some(unknown) = update.keys().find(|k| Feature::parse(k).is_none()) {
+        return ApiError::validation(format!("Unknown feature: {}", unknown)).into_response();
+    }
+
+    let conn = state.conn.lock().unwrap();
+    let result = db::get_features(&conn).and_then(|mut stored| {
+        stored.extend(update.clone());
+        db::set_features(&conn, &stored)?;
+        features::load(&conn)
+    });
+    match result {
+        Ok(flags) => {
+            info!(?update, "Updated feature flags");
+            Json(flags).into_response()
+        }
+        Err(e) => {
+            error!(error = %e, "Failed to save feature flags");
+            ApiError::from_db(&e, "Database error").into_response()
+        }
+    }
+}
+
+async fn get_work_days_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let days = db::get_work_days(&conn).unwrap_or_else(|_| vec![1, 2, 3, 4, 5]);
+    Json(WorkDaysResponse { days })
+}
+
+async fn set_work_days_handler(
+    State(state): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<WorkDaysRequest>,
+) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::set_work_days(&conn, &body.days) {
+        Ok(()) => (StatusCode::OK, Json(WorkDaysResponse { days: body.days })).into_response(),
+        Err(e) => ApiError::from_db(&e, format!("Failed: {}", e)).into_response(),
+    }
+}
+
+async fn get_homework_days_ahead_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let value = db::get_homework_days_ahead(&conn).unwrap_or(2);
+    Json(SingleValueResponse { value })
+}
+
+async fn set_homework_days_ahead_handler(
+    State(state): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<SingleValueRequest>,
+) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let clamped = body.value.clamp(1, 2);
+    match db::set_homework_days_ahead(&conn, clamped) {
+        Ok(()) => (StatusCode::OK, Json(SingleValueResponse { value: clamped })).into_response(),
+        Err(e) => ApiError::from_db(&e, format!("Failed: {}", e)).into_response(),
+    }
+}
+
+async fn get_study_days_before_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let value = db::get_study_days_before(&conn).unwrap_or(4);
+    Json(SingleValueResponse { value })
+}
+
+async fn set_study_days_before_handler(
+    State(state): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<SingleValueRequest>,
+) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let clamped = body.value.max(3);
+    match db::set_study_days_before(&conn, clamped) {
+        Ok(()) => (StatusCode::OK, Json(SingleValueResponse { value: clamped })).into_response(),
+        Err(e) => ApiError::from_db(&e, format!("Failed: {}", e)).into_response(),
+    }
+}
+
+async fn get_review_imports_handler(State(state): State<Arc<AppState>>) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    let enabled = db::get_review_imports(&conn).unwrap_or(true);
+    Json(ToggleResponse { enabled })
+}
+
+async fn set_review_imports_handler(
+    State(state): State<Arc<AppState>>,
+    ApiJson(body): ApiJson<ToggleRequest>,
+) -> impl IntoResponse {
+    let conn = state.conn.lock().unwrap();
+    match db::set_review_imports(&conn, body.enabled) {
+        Ok(()) => (
+            StatusCode::OK,
+            Json(ToggleResponse {
+                enabled: body.enabled,
+            }),
+        )
+            .into_response(),
+        Err(e) => ApiError::from_db(&e, format!("Failed: {}", e)).into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::types::{
+        Holiday, JobRun, JobStatus, Language, SubjectMerge, SubjectResource, TextFix,
+        TextFixReport, Theme, View,
+    };
+    use axum::body::Body;
+    use axum::http::{Method, Request, StatusCode};
+    use http_body_util::BodyExt;
+    use std::collections::{HashMap, HashSet};
+    use std::sync::Mutex as StdMutex;
+    use tempfile::TempDir;
     use tower::ServiceExt;
 
-    // Mutex to prevent concurrent directory changes in tests
-    static DIR_LOCK: StdMutex<()> = StdMutex::new(());
+    // Mutex to prevent concurrent directory changes in tests
+    static DIR_LOCK: StdMutex<()> = StdMutex::new(());
+
+    /// Helper to create test entries
+    fn make_entry(entry_type: &str, date: &str, subject: &str, task: &str) -> HomeworkEntry {
+        HomeworkEntry::new(
+            entry_type.to_string(),
+            date.to_string(),
+            subject.to_string(),
+            task.to_string(),
+        )
+    }
+
+    /// Setup a test database with optional entries
+    fn setup_test_db(entries: &[HomeworkEntry]) -> (TempDir, Connection) {
+        let temp_dir = TempDir::new().unwrap();
+        let db_path = temp_dir.path().join("test.db");
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+
+        db::write_test_migrations(&migrations_dir);
+
+        let conn = db::init_db(&db_path, &migrations_dir).unwrap();
+
+        for entry in entries {
+            db::insert_entry(&conn, entry).unwrap();
+        }
+
+        (temp_dir, conn)
+    }
+
+    /// Helper to create a test app state with a database
+    fn test_state(entries: Vec<HomeworkEntry>) -> (TempDir, Arc<AppState>) {
+        let (temp_dir, conn) = setup_test_db(&entries);
+        let state = Arc::new(AppState::new(conn));
+        (temp_dir, state)
+    }
+
+    /// Helper to create a test Excel XML file
+    fn create_test_export(path: &std::path::Path, entries: &[(&str, &str, &str, &str)]) {
+        let mut rows = String::from(
+            r#"<Row><Cell><Data ss:Type="String">tipo</Data></Cell><Cell><Data ss:Type="String">data_inizio</Data></Cell><Cell><Data ss:Type="String">materia</Data></Cell><Cell><Data ss:Type="String">nota</Data></Cell></Row>"#,
+        );
+        for (tipo, date, subject, task) in entries {
+            rows.push_str(&format!(
+                r#"<Row><Cell><Data ss:Type="String">{}</Data></Cell><Cell><Data ss:Type="String">{}</Data></Cell><Cell><Data ss:Type="String">{}</Data></Cell><Cell><Data ss:Type="String">{}</Data></Cell></Row>"#,
+                tipo, date, subject, task
+            ));
+        }
+        let xml = format!(
+            r#"<?xml version="1.0"?><Workbook xmlns="urn:schemas-microsoft-com:office:spreadsheet"><Worksheet ss:Name="Table1"><Table>{}</Table></Worksheet></Workbook>"#,
+            rows
+        );
+        std::fs::write(path, xml).unwrap();
+    }
+
+    /// Helper to get response body as string
+    async fn body_to_string(body: Body) -> String {
+        let bytes = body.collect().await.unwrap().to_bytes();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    /// Helper to run async test with changed directory
+    ///
+    /// The std guard is held across the await on purpose: it serializes
+    /// cwd changes between tests, which run on separate runtimes.
+    #[allow(clippy::await_holding_lock)]
+    async fn with_temp_dir_async<F, Fut, T>(temp_dir: &TempDir, f: F) -> T
+    where
+        F: FnOnce() -> Fut,
+        Fut: std::future::Future<Output = T>,
+    {
+        let _lock = DIR_LOCK.lock().unwrap();
+        let original_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(temp_dir.path()).unwrap();
+        let result = f().await;
+        std::env::set_current_dir(original_dir).unwrap();
+        result
+    }
+
+    // ========== AppState tests ==========
+
+    #[test]
+    fn test_app_state_db_access() {
+        let entries = vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Task 1"),
+            make_entry("nota", "2025-01-16", "Italiano", "Task 2"),
+        ];
+        let (_temp_dir, state) = test_state(entries);
+
+        let conn = state.conn.lock().unwrap();
+        let read_entries = db::get_all_entries(&conn).unwrap();
+        assert_eq!(read_entries.len(), 2);
+    }
+
+    // ========== Router tests ==========
+
+    #[test]
+    fn test_create_router() {
+        let (_temp_dir, state) = test_state(vec![]);
+        // Router created successfully - routes are tested via handler tests
+        let _router = create_router(state);
+    }
+
+    // ========== dashboard_handler tests ==========
+
+    #[tokio::test]
+    async fn test_dashboard_handler() {
+        let today = chrono::Local::now().date_naive();
+        let day = |offset: i64| (today + chrono::Duration::days(offset)).to_string();
+        let (_temp_dir, state) = test_state(vec![
+            make_entry("compiti", &day(0), "Matematica", "Due today"),
+            make_entry("compiti", &day(1), "Italiano", "Due tomorrow"),
+            make_entry("verifica", &day(4), "Scienze", "Verifica cap. 2"),
+            make_entry("compiti", &day(-3), "Storia", "Forgotten"),
+        ]);
+
+        let response = create_router(state)
+            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_to_string(response.into_body()).await;
+        let column = |id: &str| {
+            let start = body.find(&format!(r#"id="dashboard-{}""#, id)).unwrap();
+            let end = body[start..].find("</section>").unwrap();
+            body[start..start + end].to_string()
+        };
+        assert!(column("today").contains("Due today"));
+        assert!(column("tomorrow").contains("Due tomorrow"));
+        assert!(column("week").contains("Verifica cap. 2"));
+        assert!(column("tests").contains("Verifica cap. 2"));
+        assert!(!body.contains("Forgotten"));
+        assert!(body.contains(r#"<div class="dashboard-counter alert"><span class="counter-value">1</span><span class="counter-label">Overdue"#));
+    }
+
+    // ========== list_page_handler tests ==========
+
+    #[tokio::test]
+    async fn test_list_page_handler_empty_entries() {
+        let (_temp_dir, state) = test_state(vec![]);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(Request::builder().uri("/list").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_to_string(response.into_body()).await;
+        assert!(body.contains("<!DOCTYPE html>"));
+        assert!(body.contains("Compitutto"));
+        assert!(body.contains("No homework entries found"));
+    }
+
+    #[tokio::test]
+    async fn test_list_page_handler_with_entries() {
+        let entries = vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Pag. 100"),
+            make_entry("nota", "2025-01-16", "Italiano", "Verifica"),
+        ];
+        let (_temp_dir, state) = test_state(entries);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(Request::builder().uri("/list").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_to_string(response.into_body()).await;
+        assert!(body.contains("Matematica"));
+        assert!(body.contains("Italiano"));
+        assert!(body.contains("Pag. 100"));
+        assert!(body.contains("Verifica"));
+        assert!(body.contains("2025-01-15"));
+        assert!(body.contains("2025-01-16"));
+    }
+
+    #[tokio::test]
+    async fn test_register_routes() {
+        let (_temp_dir, state) = test_state(vec![]);
+        {
+            let conn = state.conn.lock().unwrap();
+            db::replace_absences(
+                &conn,
+                &crate::types::AbsencesSnapshot {
+                    fetched: Some("2025-01-20".to_string()),
+                    absences: vec![crate::types::Absence {
+                        date: "2025-01-15".to_string(),
+                        kind: "Ritardo".to_string(),
+                        justified: false,
+                        note: String::new(),
+                    }],
+                },
+            )
+            .unwrap();
+        }
+        let get = |uri: &str| {
+            create_router(state.clone())
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+
+        let response = get("/api/absences").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let json: serde_json::Value =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(json["fetched"], "2025-01-20");
+        assert_eq!(json["absences"][0]["kind"], "Ritardo");
+
+        let response = get("/api/notes").await.unwrap();
+        let body = body_to_string(response.into_body()).await;
+        assert_eq!(body, r#"{"fetched":null,"notes":[]}"#);
+
+        let response = get("/register").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        assert!(body.contains("1 to justify"));
+    }
+
+    #[tokio::test]
+    async fn test_read_only_mode() {
+        let entries = vec![make_entry(
+            "compiti",
+            "2025-01-15",
+            "Matematica",
+            "Pag. 100",
+        )];
+        let (_temp_dir, conn) = setup_test_db(&entries);
+        let state = Arc::new(AppState {
+            read_only: true,
+            ..AppState::new(conn)
+        });
+        let request = |method: Method, uri: &str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from("{}"))
+                    .unwrap(),
+            )
+        };
+
+        let response = request(Method::GET, "/api/entries").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let response = request(Method::GET, "/list").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        assert!(body.contains("read-only"));
+        assert!(body.contains("Pag. 100"));
+        assert!(!body.contains(r#"id="add-entry-btn""#));
+
+        // Writes and the GET routes that change things are refused
+        for (method, uri) in [
+            (Method::POST, "/api/entries"),
+            (Method::DELETE, "/api/entries/1"),
+            (Method::PUT, "/api/settings"),
+            (Method::GET, "/api/refresh"),
+            (Method::GET, "/settings"),
+        ] {
+            let response = request(method, uri).await.unwrap();
+            assert_eq!(response.status(), StatusCode::FORBIDDEN, "{}", uri);
+            let body = body_to_string(response.into_body()).await;
+            assert_eq!(
+                body, r#"{"code":"read_only","message":"Read-only mode"}"#,
+                "{}",
+                uri
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_list_page_handler_content_type() {
+        let (_temp_dir, state) = test_state(vec![]);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(Request::builder().uri("/list").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+
+        let content_type = response.headers().get("content-type").unwrap();
+        assert!(content_type.to_str().unwrap().contains("text/html"));
+    }
+
+    // ========== entries_handler tests ==========
+
+    #[tokio::test]
+    async fn test_list_fragment_handler() {
+        let mut done = make_entry("compiti", "2025-01-16", "Italiano", "Tema");
+        done.completed = true;
+        let (_temp_dir, state) = test_state(vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Task 1"),
+            done,
+        ]);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/fragments/list")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        assert!(body.starts_with(r#"<div class="list-content""#));
+        assert!(body.contains(r#"data-total="2""#));
+        assert!(body.contains(r#"data-completed="1""#));
+        assert!(body.contains("Task 1"));
+        assert!(!body.contains("<html"));
+    }
+
+    async fn get_list_fragment(state: Arc<AppState>, uri: &str) -> (StatusCode, String) {
+        let response = create_router(state)
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, body_to_string(response.into_body()).await)
+    }
+
+    #[tokio::test]
+    async fn test_list_fragment_handler_date_window() {
+        let (_temp_dir, state) = test_state(vec![
+            make_entry("compiti", "2024-11-04", "Storia", "Old task"),
+            make_entry("compiti", "2025-01-15", "Matematica", "Mid task"),
+            make_entry("compiti", "2025-03-10", "Inglese", "New task"),
+        ]);
+
+        // Default window is anchored on the latest entry here (today is
+        // later than every entry)
+        let (status, body) = get_list_fragment(state.clone(), "/fragments/list").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("New task"));
+        assert!(!body.contains("Mid task"));
+        assert!(body.contains(r#"data-total="3""#));
+        assert!(body.contains(r#"id="load-older""#));
+        assert!(!body.contains(r#"id="load-newer""#));
+
+        let (status, body) = get_list_fragment(
+            state.clone(),
+            "/fragments/list?from=2025-01-01&to=2025-01-31",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Mid task"));
+        assert!(!body.contains("Old task"));
+        assert!(!body.contains("New task"));
+        assert!(body.contains(r#"data-from="2025-01-01" data-to="2025-01-31""#));
+        assert!(body.contains(r#"id="load-older""#));
+        assert!(body.contains(r#"id="load-newer""#));
+
+        let (status, _) = get_list_fragment(state.clone(), "/fragments/list?from=01/01/2025").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) =
+            get_list_fragment(state, "/fragments/list?from=2025-02-01&to=2025-01-01").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_list_window_from_settings_and_all() {
+        let (_temp_dir, state) = test_state(vec![
+            make_entry("compiti", "2024-11-04", "Storia", "Old task"),
+            make_entry("compiti", "2025-01-15", "Matematica", "Mid task"),
+            make_entry("compiti", "2025-03-10", "Inglese", "New task"),
+        ]);
+        {
+            let conn = state.conn.lock().unwrap();
+            let settings = Settings {
+                list_days_back: 60,
+                ..Settings::default()
+            };
+            db::save_settings(&conn, &settings).unwrap();
+        }
+
+        // Anchored on the latest entry, 60 days back reaches mid-January
+        let (status, body) = get_list_fragment(state.clone(), "/list").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("New task"));
+        assert!(body.contains("Mid task"));
+        assert!(!body.contains("Old task"));
+
+        let (_, body) = get_list_fragment(state.clone(), "/fragments/list?all=true").await;
+        assert!(body.contains("Old task"));
+        assert!(body.contains(r#"data-from="2024-11-04" data-to="2025-03-10""#));
+        assert!(!body.contains(r#"id="load-older""#));
+        assert!(!body.contains("list-range-all"));
+
+        let (status, _) = get_list_fragment(state, "/list?from=2025-13-01").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_list_fragment_off_schedule_badge() {
+        // 2025-01-15 is a Wednesday, 2025-01-16 a Thursday
+        let (temp_dir, conn) = setup_test_db(&[
+            make_entry("compiti", "2025-01-15", "Matematica", "On a lesson day"),
+            make_entry("verifica", "2025-01-16", "Matematica", "Typo day"),
+        ]);
+        let rules_path = temp_dir.path().join("subjects.json");
+        let state = Arc::new(AppState {
+            rules_path: rules_path.clone(),
+            ..AppState::new(conn)
+        });
+
+        // No timetable, no badges
+        let (status, body) = get_list_fragment(state.clone(), "/fragments/list?all=true").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(!body.contains("schedule-badge"));
+
+        std::fs::write(&rules_path, r#"{"timetable": {"wed": ["Matematica"]}}"#).unwrap();
+        let (_, body) = get_list_fragment(state, "/fragments/list?all=true").await;
+        assert_eq!(body.matches("📅 no lesson that day").count(), 1);
+        assert!(body.contains("Matematica has no lesson on Thursdays (2025-01-16)"));
+    }
+
+    #[tokio::test]
+    async fn test_list_fragment_handler_filters() {
+        let mut done = make_entry("verifica", "2025-01-16", "Italiano", "Tema in classe");
+        done.completed = true;
+        let (_temp_dir, state) = test_state(vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Pag. 12"),
+            make_entry("studio", "2025-01-15", "Storia", "Capitolo 3"),
+            done,
+        ]);
+        let window = "/fragments/list?from=2025-01-01&to=2025-01-31";
+
+        let (status, body) = get_list_fragment(
+            state.clone(),
+            &format!("{window}&subject=matematica,Storia"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Pag. 12"));
+        assert!(body.contains("Capitolo 3"));
+        assert!(!body.contains("Tema in classe"));
+        // Counts in the header stay those of the whole list
+        assert!(body.contains(r#"data-total="3""#));
+
+        let (_, body) =
+            get_list_fragment(state.clone(), &format!("{window}&type=studio,verifica")).await;
+        assert!(!body.contains("Pag. 12"));
+        assert!(body.contains("Capitolo 3"));
+        assert!(body.contains("Tema in classe"));
+
+        let (_, body) = get_list_fragment(state.clone(), &format!("{window}&status=done")).await;
+        assert!(body.contains("Tema in classe"));
+        assert!(!body.contains("Capitolo 3"));
+
+        let (_, body) = get_list_fragment(
+            state.clone(),
+            &format!("{window}&type=studio,compiti&status=pending"),
+        )
+        .await;
+        assert!(body.contains("Pag. 12"));
+        assert!(body.contains("Capitolo 3"));
+
+        // Both statuses is no filter at all
+        let (_, body) =
+            get_list_fragment(state.clone(), &format!("{window}&status=pending,done")).await;
+        assert!(body.contains("Tema in classe"));
+        assert!(body.contains("Pag. 12"));
+
+        let (status, _) = get_list_fragment(state, &format!("{window}&status=later")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_entries_handler_empty() {
+        let (_temp_dir, state) = test_state(vec![]);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/entries")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_to_string(response.into_body()).await;
+        assert_eq!(body, "[]");
+    }
+
+    #[tokio::test]
+    async fn test_entries_handler_with_data() {
+        let entries = vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Task 1"),
+            make_entry("nota", "2025-01-16", "Italiano", "Task 2"),
+        ];
+        let (_temp_dir, state) = test_state(entries);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/entries")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_to_string(response.into_body()).await;
+        let parsed: Vec<HomeworkEntry> = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(parsed.len(), 2);
+        assert_eq!(parsed[0].subject, "Matematica");
+        assert_eq!(parsed[1].subject, "Italiano");
+    }
+
+    #[tokio::test]
+    async fn test_entries_handler_json_content_type() {
+        let (_temp_dir, state) = test_state(vec![]);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/entries")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let content_type = response.headers().get("content-type").unwrap();
+        assert!(content_type.to_str().unwrap().contains("application/json"));
+    }
+
+    #[tokio::test]
+    async fn test_entries_handler_serialization() {
+        let entries = vec![make_entry(
+            "compiti",
+            "2025-01-15",
+            "Matematica",
+            "Special chars: àèìòù & \"quotes\"",
+        )];
+        let (_temp_dir, state) = test_state(entries);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/entries")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let body = body_to_string(response.into_body()).await;
+        let parsed: Vec<HomeworkEntry> = serde_json::from_str(&body).unwrap();
+
+        assert_eq!(parsed[0].task, "Special chars: àèìòù & \"quotes\"");
+    }
+
+    // ========== refresh_handler tests ==========
+
+    #[tokio::test]
+    async fn test_refresh_handler_no_data() {
+        // Create a temp directory with no export files and no existing data
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+
+        // Setup migrations for the test database
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+        db::write_test_migrations(&migrations_dir);
+
+        let db_path = data_dir.join("homework.db");
+        let conn = db::init_db(&db_path, &migrations_dir).unwrap();
+        let state = Arc::new(AppState::new(conn));
+        let app = create_router(state);
+
+        let response = with_temp_dir_async(&temp_dir, || async {
+            app.oneshot(
+                Request::builder()
+                    .uri("/api/refresh")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+        })
+        .await;
+
+        // No data available
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+        let body = body_to_string(response.into_body()).await;
+        let error: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(error["code"], "internal");
+        assert_eq!(error["message"], "Failed to parse export files");
+    }
+
+    #[tokio::test]
+    async fn test_refresh_handler_with_export_file() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+
+        // Setup migrations
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+        db::write_test_migrations(&migrations_dir);
+
+        // Create export file
+        create_test_export(
+            &data_dir.join("export_test.xls"),
+            &[("compiti", "2025-01-15", "Matematica", "Task 1")],
+        );
+
+        let db_path = data_dir.join("homework.db");
+        let conn = db::init_db(&db_path, &migrations_dir).unwrap();
+        db::set_review_imports(&conn, false).unwrap();
+        let state = Arc::new(AppState::new(conn));
+        let app = create_router(state.clone());
+
+        let response = with_temp_dir_async(&temp_dir, || async {
+            app.oneshot(
+                Request::builder()
+                    .uri("/api/refresh")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+        })
+        .await;
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body: serde_json::Value =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(body["imported"], 1);
+        assert_eq!(body["reports"][0]["file"], "export_test.xls");
+        assert_eq!(body["reports"][0]["parsed"], 1);
+
+        // Verify database was updated
+        let conn = state.conn.lock().unwrap();
+        let db_entries = db::get_all_entries(&conn).unwrap();
+        assert_eq!(db_entries.len(), 1);
+    }
+
+    // ========== 404 tests ==========
+
+    #[tokio::test]
+    async fn test_unknown_route_returns_404() {
+        let (_temp_dir, state) = test_state(vec![]);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/unknown/route")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    // ========== Concurrent access tests ==========
+
+    #[test]
+    fn test_concurrent_db_access() {
+        let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
+        let (_temp_dir, state) = test_state(entries);
+
+        // Simulate multiple sequential reads (Mutex doesn't allow true concurrent access)
+        for _ in 0..10 {
+            let conn = state.conn.lock().unwrap();
+            let count = db::count_entries(&conn).unwrap();
+            assert_eq!(count, 1);
+        }
+    }
+
+    // ========== is_export_file tests ==========
+
+    #[test]
+    fn test_is_export_file_valid() {
+        assert!(is_export_file(Path::new("export_2025.xls")));
+        assert!(is_export_file(Path::new("export_homework.xls")));
+        assert!(is_export_file(Path::new("export_.xls")));
+        assert!(is_export_file(Path::new("/path/to/export_data.xls")));
+        assert!(is_export_file(Path::new("data/export_test.xls")));
+    }
+
+    #[test]
+    fn test_is_export_file_xlsx() {
+        // .xlsx files should also match (contains ".xls")
+        assert!(is_export_file(Path::new("export_2025.xlsx")));
+    }
+
+    #[test]
+    fn test_is_export_file_invalid_prefix() {
+        assert!(!is_export_file(Path::new("homework.xls")));
+        assert!(!is_export_file(Path::new("data.xls")));
+        assert!(!is_export_file(Path::new("xport_file.xls")));
+        assert!(!is_export_file(Path::new("Export_file.xls"))); // Case sensitive
+    }
+
+    #[test]
+    fn test_is_export_file_invalid_extension() {
+        assert!(!is_export_file(Path::new("export_data.txt")));
+        assert!(!is_export_file(Path::new("export_data.csv")));
+        assert!(!is_export_file(Path::new("export_data.json")));
+        assert!(!is_export_file(Path::new("export_data")));
+    }
+
+    #[test]
+    fn test_is_export_file_edge_cases() {
+        assert!(!is_export_file(Path::new("")));
+        assert!(!is_export_file(Path::new("/")));
+        assert!(!is_export_file(Path::new("/path/to/")));
+        assert!(!is_export_file(Path::new(".")));
+        assert!(!is_export_file(Path::new("..")));
+    }
+
+    // ========== ensure_data_dir tests ==========
+
+    #[test]
+    fn test_ensure_data_dir_creates_new() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("new_data_dir");
+
+        assert!(!data_dir.exists());
+        let created = ensure_data_dir(&data_dir).unwrap();
+        assert!(created);
+        assert!(data_dir.exists());
+    }
+
+    #[test]
+    fn test_ensure_data_dir_already_exists() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("existing_dir");
+        std::fs::create_dir(&data_dir).unwrap();
+
+        assert!(data_dir.exists());
+        let created = ensure_data_dir(&data_dir).unwrap();
+        assert!(!created);
+        assert!(data_dir.exists());
+    }
+
+    #[test]
+    fn test_ensure_data_dir_nested() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("a").join("b").join("c");
+
+        assert!(!data_dir.exists());
+        let created = ensure_data_dir(&data_dir).unwrap();
+        assert!(created);
+        assert!(data_dir.exists());
+    }
+
+    // ========== create_server_addr tests ==========
+
+    #[test]
+    fn test_create_server_addr() {
+        let addr = create_server_addr(8080);
+        assert_eq!(addr.port(), 8080);
+        assert_eq!(addr.ip().to_string(), "127.0.0.1");
+    }
+
+    #[test]
+    fn test_create_server_addr_different_ports() {
+        assert_eq!(create_server_addr(3000).port(), 3000);
+        assert_eq!(create_server_addr(0).port(), 0);
+        assert_eq!(create_server_addr(65535).port(), 65535);
+    }
+
+    // ========== init_server_state tests ==========
+    // Note: init_server_state requires CARGO_MANIFEST_DIR to find migrations,
+    // so we test the components separately rather than the full function.
+
+    // ========== RefreshResult tests ==========
+
+    #[test]
+    fn test_refresh_result_updated() {
+        let result = RefreshResult::Updated {
+            old_count: 5,
+            new_count: 10,
+        };
+        assert_eq!(
+            result,
+            RefreshResult::Updated {
+                old_count: 5,
+                new_count: 10
+            }
+        );
+
+        // Just ensure log doesn't panic
+        result.log();
+    }
+
+    #[test]
+    fn test_refresh_result_no_change() {
+        let result = RefreshResult::NoChange { count: 5 };
+        assert_eq!(result, RefreshResult::NoChange { count: 5 });
+        result.log();
+    }
+
+    #[test]
+    fn test_refresh_result_error() {
+        let result = RefreshResult::Error("test error".to_string());
+        assert_eq!(result, RefreshResult::Error("test error".to_string()));
+        result.log();
+    }
+
+    #[test]
+    fn test_refresh_result_debug() {
+        let result = RefreshResult::Updated {
+            old_count: 1,
+            new_count: 2,
+        };
+        let debug_str = format!("{:?}", result);
+        assert!(debug_str.contains("Updated"));
+    }
+
+    #[test]
+    fn test_refresh_result_clone() {
+        let result = RefreshResult::Updated {
+            old_count: 1,
+            new_count: 2,
+        };
+        let cloned = result.clone();
+        assert_eq!(result, cloned);
+    }
+
+    // ========== process_refresh tests ==========
+
+    #[test]
+    fn test_process_refresh_with_new_entries() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+
+        // Setup migrations
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+        db::write_test_migrations(&migrations_dir);
+
+        // Create database with no entries, importing without review
+        let db_path = data_dir.join("homework.db");
+        let conn = db::init_db(&db_path, &migrations_dir).unwrap();
+        db::set_review_imports(&conn, false).unwrap();
+        let state = AppState::new(conn);
+
+        // Create export file with one entry
+        create_test_export(
+            &data_dir.join("export_test.xls"),
+            &[("compiti", "2025-01-15", "Matematica", "Task 1")],
+        );
+
+        let _lock = DIR_LOCK.lock().unwrap();
+        let original_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(temp_dir.path()).unwrap();
+
+        let result = process_refresh(&state);
+
+        std::env::set_current_dir(original_dir).unwrap();
+
+        match result {
+            RefreshResult::Updated {
+                old_count,
+                new_count,
+            } => {
+                assert_eq!(old_count, 0);
+                assert_eq!(new_count, 1);
+            }
+            _ => panic!("Expected Updated result, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_process_refresh_no_change() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+        db::write_test_migrations(&migrations_dir);
+
+        // Create export file
+        create_test_export(
+            &data_dir.join("export_test.xls"),
+            &[("compiti", "2025-01-15", "Matematica", "Task 1")],
+        );
+
+        // Create database with same entries already imported
+        let db_path = data_dir.join("homework.db");
+        let conn = db::init_db(&db_path, &migrations_dir).unwrap();
+        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        db::insert_entry(&conn, &entry).unwrap();
+        let state = AppState::new(conn);
+
+        let _lock = DIR_LOCK.lock().unwrap();
+        let original_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(temp_dir.path()).unwrap();
+
+        let result = process_refresh(&state);
+
+        std::env::set_current_dir(original_dir).unwrap();
+
+        match result {
+            RefreshResult::NoChange { count } => {
+                assert_eq!(count, 1);
+            }
+            _ => panic!("Expected NoChange result, got {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_process_refresh_error() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        // No export files - will cause error
+
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+        db::write_test_migrations(&migrations_dir);
+
+        let db_path = data_dir.join("homework.db");
+        let conn = db::init_db(&db_path, &migrations_dir).unwrap();
+        let state = AppState::new(conn);
+
+        let _lock = DIR_LOCK.lock().unwrap();
+        let original_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(temp_dir.path()).unwrap();
+
+        let result = process_refresh(&state);
+
+        std::env::set_current_dir(original_dir).unwrap();
+
+        match result {
+            RefreshResult::Error(msg) => {
+                assert!(!msg.is_empty());
+            }
+            _ => panic!("Expected Error result, got {:?}", result),
+        }
+    }
+
+    // ========== New API endpoint tests ==========
+
+    #[tokio::test]
+    async fn test_get_entry_handler() {
+        let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
+        let entry_id = entries[0].id.clone();
+        let (_temp_dir, state) = test_state(entries);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/api/entries/{}", entry_id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_to_string(response.into_body()).await;
+        let parsed: HomeworkEntry = serde_json::from_str(&body).unwrap();
+        assert_eq!(parsed.id, entry_id);
+    }
+
+    #[tokio::test]
+    async fn test_entry_links_and_api_index() {
+        let test = make_entry("verifica", "2025-01-20", "Matematica", "Verifica");
+        let mut session = make_entry("studio", "2025-01-18", "Matematica", "Ripasso");
+        session.parent_id = Some(test.id.clone());
+        let (test_id, session_id) = (test.id.clone(), session.id.clone());
+        let (_temp_dir, state) = test_state(vec![test, session]);
+
+        let (_, body) = get_status_and_body(&state, &format!("/api/entries/{}", session_id)).await;
+        let session: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let parent = session["_links"]["parent"]["href"].as_str().unwrap();
+        assert_eq!(parent, format!("/api/entries/{}", test_id));
+
+        // Following the links works without knowing the URL patterns
+        let (status, body) = get_status_and_body(&state, parent).await;
+        assert_eq!(status, StatusCode::OK);
+        let test: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert!(test["_links"].get("parent").is_none());
+        let children = test["_links"]["children"]["href"].as_str().unwrap();
+        let (_, body) = get_status_and_body(&state, children).await;
+        let children: serde_json::Value = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            children[0]["_links"]["self"]["href"],
+            session["_links"]["self"]["href"]
+        );
+        assert_eq!(test["_links"]["cascade-delete"]["method"], "DELETE");
+
+        // The list still reads back as entries
+        let (_, body) = get_status_and_body(&state, "/api/entries").await;
+        assert!(crate::schema::validate(&body).is_ok());
+
+        let (status, body) = get_status_and_body(&state, "/api").await;
+        assert_eq!(status, StatusCode::OK);
+        let index: serde_json::Value = serde_json::from_str(&body).unwrap();
+        let collections = index["collections"].as_array().unwrap();
+        assert_eq!(collections[0]["href"], "/api/entries");
+        assert!(collections[0]["filters"]
+            .as_array()
+            .unwrap()
+            .contains(&serde_json::json!("subject")));
+        for collection in collections {
+            let href = collection["href"].as_str().unwrap();
+            let (status, _) = get_status_and_body(&state, href).await;
+            assert_ne!(status, StatusCode::NOT_FOUND, "{}", href);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_calendar_handler_returns_only_requested_month() {
+        let mut entries = vec![
+            make_entry("compiti", "2025-01-31", "Matematica", "January"),
+            make_entry("compiti", "2025-02-03", "Italiano", "February 1"),
+            make_entry("nota", "2025-02-03", "Storia", "February 2"),
+            make_entry("compiti", "2025-03-01", "Inglese", "March"),
+        ];
+        entries[2].position = 3;
+        let (_temp_dir, state) = test_state(entries);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/calendar?year=2025&month=2")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_to_string(response.into_body()).await;
+        let parsed: CalendarMonthResponse = serde_json::from_str(&body).unwrap();
+        assert_eq!(parsed.year, 2025);
+        assert_eq!(parsed.month, 2);
+        assert_eq!(parsed.entries.len(), 1);
+        assert_eq!(parsed.entries["2025-02-03"].len(), 2);
+        // Positions let the calendar append dropped entries at the bottom
+        let positions: Vec<i32> = parsed.entries["2025-02-03"]
+            .iter()
+            .map(|e| e.position)
+            .collect();
+        assert_eq!(positions, vec![0, 3]);
+    }
+
+    #[tokio::test]
+    async fn test_calendar_handler_includes_holidays() {
+        let (_temp_dir, state) = test_state(vec![]);
+        {
+            let conn = state.conn.lock().unwrap();
+            let holidays: Vec<Holiday> = [("2025-02-28", "Carnevale"), ("2025-03-03", "Carnevale")]
+                .into_iter()
+                .map(|(date, name)| Holiday {
+                    date: date.to_string(),
+                    name: name.to_string(),
+                })
+                .collect();
+            db::replace_holidays(&conn, &holidays).unwrap();
+        }
+
+        let response = create_router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/calendar?year=2025&month=2")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        let body = body_to_string(response.into_body()).await;
+        let parsed: CalendarMonthResponse = serde_json::from_str(&body).unwrap();
+        assert_eq!(parsed.holidays.len(), 1);
+        assert_eq!(parsed.holidays["2025-02-28"], "Carnevale");
+    }
+
+    #[tokio::test]
+    async fn test_calendar_and_activities_routes() {
+        let (_temp_dir, state) = test_state(vec![]);
+        {
+            let conn = state.conn.lock().unwrap();
+            let feed = "BEGIN:VCALENDAR\nBEGIN:VEVENT\nDTSTART:20250225T170000\n\
+                        DTEND:20250225T183000\nRRULE:FREQ=WEEKLY;COUNT=2\n\
+                        SUMMARY:Nuoto\nEND:VEVENT\nEND:VCALENDAR\n";
+            let parsed = activities::parse_ical(feed).unwrap();
+            db::replace_activities(&conn, &parsed).unwrap();
+        }
+
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .uri("/api/calendar?year=2025&month=2")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = body_to_string(response.into_body()).await;
+        let parsed: CalendarMonthResponse = serde_json::from_str(&body).unwrap();
+        assert_eq!(parsed.activities.len(), 1);
+        assert_eq!(parsed.activities["2025-02-25"][0].name, "Nuoto");
+        assert_eq!(parsed.activities["2025-02-25"][0].minutes, 90);
+
+        let response = create_router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/activities?from=2025-03-01")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        let activities: Vec<Activity> = serde_json::from_str(&body).unwrap();
+        let dates: Vec<&str> = activities.iter().map(|a| a.date.as_str()).collect();
+        assert_eq!(dates, vec!["2025-03-04"]);
+    }
+
+    // ========== move_entry_handler tests ==========
+
+    #[tokio::test]
+    async fn test_move_entry_handler() {
+        let mut entries = vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "A"),
+            make_entry("compiti", "2025-01-15", "Italiano", "B"),
+            make_entry("compiti", "2025-01-16", "Storia", "C"),
+        ];
+        entries[1].position = 1;
+        let moved_id = entries[2].id.clone();
+        let (_temp_dir, state) = test_state(entries);
+
+        let post_move = |body: &'static str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(format!("/api/entries/{}/move", moved_id))
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+        };
+        let response = post_move(r#"{"date": "2025-01-15", "index": 0}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let entry: HomeworkEntry =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!((entry.date.as_str(), entry.position), ("2025-01-15", 0));
+
+        let tasks: Vec<(String, i32)> =
+            db::get_entries_in_range(&state.conn.lock().unwrap(), "2025-01-15", "2025-01-15")
+                .unwrap()
+                .into_iter()
+                .map(|e| (e.task, e.position))
+                .collect();
+        assert_eq!(
+            tasks,
+            vec![
+                ("C".to_string(), 0),
+                ("A".to_string(), 1),
+                ("B".to_string(), 2)
+            ]
+        );
+
+        assert_eq!(
+            post_move(r#"{"date": "15/01/2025"}"#)
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::BAD_REQUEST
+        );
+    }
+
+    #[tokio::test]
+    async fn test_duplicate_entry() {
+        let mut original = make_entry("compiti", "2025-01-15", "Matematica", "Esercizi pag. 50");
+        original.completed = true;
+        original.estimated_minutes = Some(30);
+        let id = original.id.clone();
+        let mut reminder = make_entry("lavoro", "2025-01-13", "Matematica", "Do it: pag. 50");
+        reminder.parent_id = Some(id.clone());
+        let reminder_id = reminder.id.clone();
+        let (_temp_dir, state) = test_state(vec![original, reminder]);
+        let duplicate = |id: &str, date: &str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(format!("/api/entries/{}/duplicate", id))
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(format!(r#"{{"date": "{}"}}"#, date)))
+                    .unwrap(),
+            )
+        };
+
+        let response = duplicate(&id, "2025-01-22").await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = body_to_string(response.into_body()).await;
+        let copy: HomeworkEntry = serde_json::from_str(&body).unwrap();
+        assert_ne!(copy.id, id);
+        assert_eq!(copy.date, "2025-01-22");
+        assert_eq!(copy.task, "Esercizi pag. 50");
+        assert_eq!(copy.estimated_minutes, Some(30));
+        assert!(!copy.completed);
+
+        // Same day twice is the same entry
+        let response = duplicate(&id, "2025-01-22").await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        let response = duplicate(&id, "next week").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = duplicate("missing", "2025-01-22").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        let response = duplicate(&reminder_id, "2025-01-29").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_move_onto_test_day_warns() {
+        let test = make_entry(
+            "verifica",
+            "2025-01-15",
+            "Matematica",
+            "Verifica sui limiti",
+        );
+        let homework = make_entry("compiti", "2025-01-16", "Storia", "Pag. 10");
+        let moved_id = homework.id.clone();
+        let (_temp_dir, state) = test_state(vec![test, homework]);
+
+        let post_move = |body: &'static str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(format!("/api/entries/{}/move", moved_id))
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+        };
+        let response = post_move(r#"{"date": "2025-01-15"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let moved: CheckedEntry =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(moved.entry.date, "2025-01-15");
+        let warning = moved.warning.unwrap();
+        assert_eq!(warning.tests, 1);
+        assert_eq!(warning.message, "This day already has 1 test");
+
+        // Moving back is quiet, and the field is left out
+        let response = post_move(r#"{"date": "2025-01-16"}"#).await.unwrap();
+        let body = body_to_string(response.into_body()).await;
+        assert!(!body.contains("warning"));
+    }
+
+    // ========== Share link tests ==========
+
+    async fn get_status_and_body(state: &Arc<AppState>, uri: &str) -> (StatusCode, String) {
+        let response = create_router(state.clone())
+            .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, body_to_string(response.into_body()).await)
+    }
+
+    #[tokio::test]
+    async fn test_share_link() {
+        let test = make_entry("verifica", "2025-01-21", "Scienze", "Capitolo 2");
+        let other = make_entry("compiti", "2025-01-22", "Storia", "Private");
+        let (test_id, other_stable) = (test.id.clone(), other.stable_id());
+        let (_temp_dir, state) = test_state(vec![test, other]);
+
+        let share = |id: String, body: &'static str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(format!("/api/entries/{}/share", id))
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+        };
+        let response = share(test_id.clone(), "{}").await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let link: ShareLinkResponse =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(
+            share(test_id.clone(), r#"{"days": 0}"#)
+                .await
+                .unwrap()
+                .status(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            share("nope".to_string(), "{}").await.unwrap().status(),
+            StatusCode::NOT_FOUND
+        );
+
+        let (status, body) = get_status_and_body(&state, &link.url).await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.contains("Capitolo 2"));
+        assert!(!body.contains("Private"));
+
+        // The token only opens the entry it was made for
+        let token = link.url.split("token=").nth(1).unwrap();
+        let wrong_entry = format!("/share/{}?token={}", other_stable, token);
+        assert_eq!(
+            get_status_and_body(&state, &wrong_entry).await.0,
+            StatusCode::NOT_FOUND
+        );
+        let no_token = link.url.split('?').next().unwrap();
+        assert_eq!(
+            get_status_and_body(&state, no_token).await.0,
+            StatusCode::NOT_FOUND
+        );
+
+        // Expired links stop working
+        state
+            .conn
+            .lock()
+            .unwrap()
+            .execute(
+                "UPDATE share_links SET expires_at = '2000-01-01T00:00:00Z'",
+                [],
+            )
+            .unwrap();
+        let (status, body) = get_status_and_body(&state, &link.url).await;
+        assert_eq!(status, StatusCode::GONE);
+        assert!(body.contains("no longer available"));
+    }
+
+    // ========== Entry link tests ==========
+
+    async fn post_link(state: &Arc<AppState>, from: &str, body: String) -> StatusCode {
+        create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(format!("/api/entries/{}/links", from))
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+            .await
+            .unwrap()
+            .status()
+    }
+
+    #[tokio::test]
+    async fn test_create_and_list_entry_links() {
+        let reading = make_entry("compiti", "2025-01-15", "Scienze", "Leggere cap. 3");
+        let report = make_entry("compiti", "2025-01-17", "Scienze", "Relazione");
+        let (reading_id, report_id) = (reading.id.clone(), report.id.clone());
+        let (_temp_dir, state) = test_state(vec![reading, report]);
+
+        let depends = format!(r#"{{"to_id": "{}"}}"#, reading_id);
+        assert_eq!(
+            post_link(&state, &report_id, depends.clone()).await,
+            StatusCode::CREATED
+        );
+        // Duplicate, reverse (cycle), self-link, unknown entry, bad relation
+        assert_eq!(
+            post_link(&state, &report_id, depends).await,
+            StatusCode::CONFLICT
+        );
+        let reverse = format!(r#"{{"to_id": "{}"}}"#, report_id);
+        assert_eq!(
+            post_link(&state, &reading_id, reverse.clone()).await,
+            StatusCode::CONFLICT
+        );
+        assert_eq!(
+            post_link(&state, &report_id, reverse).await,
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            post_link(&state, &report_id, r#"{"to_id": "nope"}"#.to_string()).await,
+            StatusCode::NOT_FOUND
+        );
+        let bad = format!(r#"{{"to_id": "{}", "relation": "blocks"}}"#, reading_id);
+        assert!(post_link(&state, &report_id, bad).await.is_client_error());
+
+        // Cross-listing: once, one way
+        let copy = format!(
+            r#"{{"to_id": "{}", "relation": "cross_listed"}}"#,
+            reading_id
+        );
+        assert_eq!(
+            post_link(&state, &report_id, copy).await,
+            StatusCode::CREATED
+        );
+        let back = format!(
+            r#"{{"to_id": "{}", "relation": "cross_listed"}}"#,
+            report_id
+        );
+        assert_eq!(
+            post_link(&state, &reading_id, back).await,
+            StatusCode::CONFLICT
+        );
+
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .uri(format!("/api/entries/{}/links", reading_id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let body = body_to_string(response.into_body()).await;
+        let linked: Vec<LinkedEntry> = serde_json::from_str(&body).unwrap();
+        assert_eq!(linked.len(), 2);
+        assert!(!linked[0].outgoing);
+        assert_eq!(linked[0].relation, LinkRelation::DependsOn);
+        assert_eq!(linked[0].entry.id, report_id);
+        assert_eq!(linked[1].relation, LinkRelation::CrossListed);
+    }
+
+    #[tokio::test]
+    async fn test_delete_entry_link() {
+        let a = make_entry("compiti", "2025-01-15", "Storia", "A");
+        let b = make_entry("compiti", "2025-01-16", "Storia", "B");
+        let (a_id, b_id) = (a.id.clone(), b.id.clone());
+        let (_temp_dir, state) = test_state(vec![a, b]);
+        let link_id = {
+            let conn = state.conn.lock().unwrap();
+            db::create_link(&conn, &a_id, &b_id, LinkRelation::Related)
+                .unwrap()
+                .id
+        };
+
+        let delete = |id: i64| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::DELETE)
+                    .uri(format!("/api/links/{}", id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+        assert_eq!(
+            delete(link_id).await.unwrap().status(),
+            StatusCode::NO_CONTENT
+        );
+        assert_eq!(
+            delete(link_id).await.unwrap().status(),
+            StatusCode::NOT_FOUND
+        );
+        let conn = state.conn.lock().unwrap();
+        assert!(db::get_links(&conn).unwrap().is_empty());
+        assert_eq!(db::count_entries(&conn).unwrap(), 2);
+    }
+
+    // ========== Reaction tests ==========
+
+    async fn post_reaction(state: &Arc<AppState>, id: &str, body: &str) -> (StatusCode, String) {
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(format!("/api/entries/{}/reactions", id))
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let status = response.status();
+        (status, body_to_string(response.into_body()).await)
+    }
+
+    #[tokio::test]
+    async fn test_create_list_and_delete_reactions() {
+        let mut done = make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10");
+        done.completed = true;
+        let open = make_entry("compiti", "2025-01-16", "Storia", "Cap. 3");
+        let (done_id, open_id) = (done.id.clone(), open.id.clone());
+        let (_temp_dir, state) = test_state(vec![done, open]);
+
+        let (status, body) = post_reaction(
+            &state,
+            &done_id,
+            r#"{"emoji": "⭐", "message": "  Bravissima!  ", "author": "Mamma"}"#,
+        )
+        .await;
+        assert_eq!(status, StatusCode::CREATED);
+        let reaction: crate::types::Reaction = serde_json::from_str(&body).unwrap();
+        assert_eq!(reaction.message.as_deref(), Some("Bravissima!"));
+
+        // Nothing to say, unknown emoji, too long, unknown entry, not done yet
+        let long = format!(
+            r#"{{"message": "{}"}}"#,
+            "a".repeat(MAX_REACTION_MESSAGE + 1)
+        );
+        let long_author = format!(
+            r#"{{"emoji": "👍", "author": "{}"}}"#,
+            "a".repeat(MAX_REACTION_AUTHOR + 1)
+        );
+        for (id, body, expected, code) in [
+            (
+                &done_id,
+                r#"{"message": "   "}"#,
+                StatusCode::BAD_REQUEST,
+                "validation",
+            ),
+            (
+                &done_id,
+                r#"{"emoji": "💩"}"#,
+                StatusCode::BAD_REQUEST,
+                "validation",
+            ),
+            (
+                &done_id,
+                long.as_str(),
+                StatusCode::BAD_REQUEST,
+                "validation",
+            ),
+            (
+                &done_id,
+                long_author.as_str(),
+                StatusCode::BAD_REQUEST,
+                "validation",
+            ),
+            (
+                &"nope".to_string(),
+                r#"{"emoji": "👍"}"#,
+                StatusCode::NOT_FOUND,
+                "not_found",
+            ),
+            (
+                &open_id,
+                r#"{"emoji": "👍"}"#,
+                StatusCode::CONFLICT,
+                "conflict",
+            ),
+        ] {
+            let (status, error) = post_reaction(&state, id, body).await;
+            assert_eq!(status, expected, "{}", body);
+            let error: serde_json::Value = serde_json::from_str(&error).unwrap();
+            assert_eq!(error["code"], code, "{}", body);
+            assert!(error["message"].is_string());
+        }
+        let (_, error) = post_reaction(&state, &done_id, r#"{"emoji": "💩"}"#).await;
+        let error: serde_json::Value = serde_json::from_str(&error).unwrap();
+        assert_eq!(error["details"]["allowed"][0], REACTION_EMOJI[0]);
+
+        let (status, body) =
+            get_status_and_body(&state, &format!("/api/entries/{}/reactions", done_id)).await;
+        assert_eq!(status, StatusCode::OK);
+        let reactions: Vec<crate::types::Reaction> = serde_json::from_str(&body).unwrap();
+        assert_eq!(reactions, vec![reaction.clone()]);
+
+        let delete = || {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::DELETE)
+                    .uri(format!("/api/reactions/{}", reaction.id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+        assert_eq!(delete().await.unwrap().status(), StatusCode::NO_CONTENT);
+        assert_eq!(delete().await.unwrap().status(), StatusCode::NOT_FOUND);
+        let (_, body) = get_status_and_body(&state, "/api/reactions").await;
+        assert_eq!(body, "[]");
+    }
+
+    #[tokio::test]
+    async fn test_capture_photo() {
+        let (temp_dir, conn) = setup_test_db(&[]);
+        // Keep the test from depending on tesseract
+        db::set_features(&conn, &BTreeMap::from([("ocr".to_string(), false)])).unwrap();
+        let captures_dir = temp_dir.path().join("captures");
+        let state = Arc::new(AppState {
+            captures_dir: captures_dir.clone(),
+            ..AppState::new(conn)
+        });
+
+        let post = |content_type: &str, body: &'static [u8]| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/capture?date=2025-01-20&subject=Storia")
+                    .header("content-type", content_type)
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+        };
+        let response = post("application/pdf", b"%PDF").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = post("image/png", b"").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        let response = post("image/png", b"PNG page").await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = body_to_string(response.into_body()).await;
+        let captured: CaptureResponse = serde_json::from_str(&body).unwrap();
+        assert_eq!(captured.draft.date, "2025-01-20");
+        assert_eq!(captured.draft.subject, "Storia");
+        assert_eq!(captured.draft.entry_type, "compiti");
+        assert_eq!(captured.draft.task, capture::UNREAD_TASK);
+        assert_eq!(captured.photo.source_id, captured.draft.source_id);
+        // Left for the OCR job
+        assert_eq!(captured.photo.ocr_status, "pending");
+        assert_eq!(std::fs::read_dir(&captures_dir).unwrap().count(), 1);
+
+        let uri = format!("/api/attachments/{}/file", captured.photo.id);
+        let response = create_router(state.clone())
+            .oneshot(Request::builder().uri(&uri).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "image/png");
+        assert_eq!(body_to_string(response.into_body()).await, "PNG page");
+
+        let (status, page) = get_status_and_body(&state, "/imports").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(page.contains(&uri));
+        let (status, _) = get_status_and_body(&state, "/api/attachments/999/file").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        // Not staged: the photo isn't kept either
+        state
+            .conn
+            .lock()
+            .unwrap()
+            .execute_batch(
+                "CREATE TRIGGER refuse BEFORE INSERT ON pending_imports
+                 BEGIN SELECT RAISE(ABORT, 'refused'); END",
+            )
+            .unwrap();
+        let response = post("image/png", b"PNG page").await.unwrap();
+        assert!(!response.status().is_success());
+        assert_eq!(std::fs::read_dir(&captures_dir).unwrap().count(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_jobs_handlers() {
+        let (_temp_dir, state) = test_state(vec![]);
+        let request = |method: Method, uri: &str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+
+        let response = request(Method::POST, "/api/jobs/purge_share_links/run")
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let run: JobRun =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert!(run.ok);
+        assert_eq!(run.message, "Deleted 0 expired share links");
+        request(Method::POST, "/api/jobs/purge_share_links/run")
+            .await
+            .unwrap();
+        let status = request(Method::POST, "/api/jobs/nope/run")
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, StatusCode::NOT_FOUND);
+
+        let response = request(Method::GET, "/api/jobs").await.unwrap();
+        let statuses: Vec<JobStatus> =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(statuses.len(), jobs::JOBS.len());
+        let purge = statuses
+            .iter()
+            .find(|s| s.name == "purge_share_links")
+            .unwrap();
+        assert_eq!(purge.last_run.as_ref().unwrap().runs, 2);
+        assert!(purge.next_run.is_some());
+        let optimize = statuses.iter().find(|s| s.name == "optimize_db").unwrap();
+        assert_eq!(optimize.last_run, None);
+    }
+
+    #[tokio::test]
+    async fn test_merge_subjects_handler() {
+        let entries = vec![
+            make_entry("compiti", "2025-01-15", "Inglese", "Unit 3"),
+            make_entry("compiti", "2025-01-16", "Lingua Inglese", "Unit 4"),
+            make_entry("compiti", "2025-01-16", "Lingua Inglese", "Unit 5"),
+        ];
+        let (temp_dir, conn) = setup_test_db(&entries);
+        let rules_path = temp_dir.path().join("subjects.json");
+        let state = Arc::new(AppState {
+            rules_path: rules_path.clone(),
+            ..AppState::new(conn)
+        });
+        let get = |uri: &'static str| {
+            create_router(state.clone())
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+
+        let response = get("/api/subjects").await.unwrap();
+        let report: SubjectsReport =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(report.subjects.len(), 2);
+        assert_eq!(report.suggestions[0].from, "Inglese");
+        assert_eq!(report.suggestions[0].into, "Lingua Inglese");
+
+        let merge = |body: &'static str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/api/subjects/merge")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+        };
+        let response = merge(r#"{"from": "Inglese", "into": "Lingua Inglese"}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let merged: SubjectMerge =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(merged.entries, 1);
+        let status = merge(r#"{"from": " Storia ", "into": "Storia"}"#)
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        // Logged, and future imports map the old name
+        let response = get("/api/subjects/merges").await.unwrap();
+        let log: Vec<SubjectMerge> =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(log, vec![merged]);
+        let pipeline = subjects::SubjectPipeline::load(&rules_path).unwrap();
+        assert_eq!(pipeline.canonical("Inglese"), "Lingua Inglese");
+        let conn = state.conn.lock().unwrap();
+        assert_eq!(db::get_subject_counts(&conn).unwrap()[0].entries, 3);
+    }
+
+    #[tokio::test]
+    async fn test_fix_text_handler() {
+        let entries = vec![
+            make_entry("compiti", "2025-01-15", "Italiano", "Leggere perchÃ¨"),
+            make_entry("compiti", "2025-01-16", "Storia", "Cap. 2 perchÃ¨"),
+        ];
+        let (_temp_dir, state) = test_state(entries);
+        let fix = |body: &'static str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri("/api/text/fix")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+        };
+
+        let response = fix(r#"{"find": "Ã¨", "replace": "é", "dry_run": true}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let preview: TextFixReport =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(preview.changes.len(), 2);
+        assert_eq!(preview.changes[0].after, "Leggere perché");
+        assert_eq!(preview.fix, None);
+
+        let response = fix(r#"{"find": "Ã¨", "replace": "é", "subject": "italiano"}"#)
+            .await
+            .unwrap();
+        let applied: TextFixReport =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(applied.changes.len(), 1);
+
+        for body in [r#"{"find": ""}"#, r#"{"find": "x", "replace": "x"}"#] {
+            assert_eq!(fix(body).await.unwrap().status(), StatusCode::BAD_REQUEST);
+        }
+
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .uri("/api/text/fixes")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let log: Vec<TextFix> =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(log, vec![applied.fix.unwrap()]);
+        let conn = state.conn.lock().unwrap();
+        let tasks: Vec<String> = db::get_all_entries(&conn)
+            .unwrap()
+            .into_iter()
+            .map(|e| e.task)
+            .collect();
+        assert_eq!(tasks, vec!["Leggere perché", "Cap. 2 perchÃ¨"]);
+    }
+
+    #[tokio::test]
+    async fn test_subject_resources() {
+        let (_temp_dir, state) = test_state(vec![]);
+        let post = |subject: &str, body: &str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::POST)
+                    .uri(format!("/api/subjects/{}/resources", subject))
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+        let response = post(
+            "Lingua%20Inglese",
+            r#"{"title": "Quizlet", "url": "https://quizlet.com/123"}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let quizlet: SubjectResource =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(quizlet.subject, "Lingua Inglese");
+
+        let response = post(
+            "Lingua%20Inglese",
+            r#"{"url": "https://drive.google.com/x"}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let status = post("Storia", r#"{"url": "javascript:alert(1)"}"#)
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .uri("/api/subjects/lingua%20inglese/resources")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let resources: Vec<SubjectResource> =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(resources.len(), 2);
+        // A missing title falls back to the url
+        assert_eq!(resources[1].title, "https://drive.google.com/x");
+
+        let delete = |subject: &str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::DELETE)
+                    .uri(format!(
+                        "/api/subjects/{}/resources/{}",
+                        subject, quizlet.id
+                    ))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
+        assert_eq!(
+            delete("Storia").await.unwrap().status(),
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(
+            delete("Lingua%20Inglese").await.unwrap().status(),
+            StatusCode::NO_CONTENT
+        );
+        let conn = state.conn.lock().unwrap();
+        assert_eq!(db::get_resources(&conn).unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_subject_icons() {
+        let (_temp_dir, state) = test_state(vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10"),
+            make_entry("compiti", "2025-01-15", "Coro", "Prove"),
+        ]);
+        async fn icons(state: &Arc<AppState>) -> Vec<SubjectIcon> {
+            let (status, body) = get_status_and_body(state, "/api/subjects/icons").await;
+            assert_eq!(status, StatusCode::OK);
+            serde_json::from_str(&body).unwrap()
+        }
+        let request = |method: Method, body: &str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri("/api/subjects/Coro/icon")
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+
+        // Only the built-in one to start with
+        let list = icons(&state).await;
+        assert_eq!(list.len(), 1);
+        assert_eq!((list[0].icon.as_str(), list[0].custom), ("📐", false));
+
+        let response = request(Method::PUT, r#"{"icon": "🎤"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let status = request(Method::PUT, r#"{"icon": "not an icon"}"#)
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let list = icons(&state).await;
+        assert_eq!(list[0].subject, "Coro");
+        assert_eq!((list[0].icon.as_str(), list[0].custom), ("🎤", true));
+
+        let (_, csv) = get_status_and_body(&state, "/api/export.csv?subject=Coro").await;
+        assert!(csv.ends_with(",Prove,no,🎤\r\n"));
+
+        let response = request(Method::DELETE, "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let response = request(Method::DELETE, "").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+        assert_eq!(icons(&state).await.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_created_test_skips_holidays_for_study_sessions() {
+        let today = chrono::Local::now().date_naive();
+        let test_date = today + chrono::Duration::days(10);
+        let (_temp_dir, state) = test_state(vec![]);
+        {
+            let conn = state.conn.lock().unwrap();
+            let day_before = Holiday {
+                date: (test_date - chrono::Duration::days(1)).to_string(),
+                name: "Ponte".to_string(),
+            };
+            db::replace_holidays(&conn, &[day_before]).unwrap();
+        }
+
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/entries")
+                    .header("content-type", "application/json")
+                    .body(Body::from(format!(
+                        r#"{{"entry_type":"verifica","date":"{}","subject":"Storia","task":"Verifica cap. 5"}}"#,
+                        test_date
+                    )))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+
+        let conn = state.conn.lock().unwrap();
+        let mut study_dates: Vec<String> = db::get_all_entries(&conn)
+            .unwrap()
+            .into_iter()
+            .filter(|e| e.entry_type == "studio")
+            .map(|e| e.date)
+            .collect();
+        study_dates.sort();
+        let expected: Vec<String> = [5, 4, 3, 2]
+            .into_iter()
+            .map(|d| (test_date - chrono::Duration::days(d)).to_string())
+            .collect();
+        assert_eq!(study_dates, expected);
+    }
+
+    #[tokio::test]
+    async fn test_calendar_handler_rejects_invalid_month() {
+        let (_temp_dir, state) = test_state(vec![]);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/calendar?year=2025&month=13")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_completing_a_day() {
+        let entries = vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Es. 1"),
+            make_entry("verifica", "2025-01-15", "Storia", "Cap. 3"),
+        ];
+        let ids: Vec<String> = entries.iter().map(|e| e.id.clone()).collect();
+        let (_temp_dir, state) = test_state(entries);
+        let tick = |id: &str, completed: bool| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::PATCH)
+                    .uri(format!("/api/entries/{}", id))
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(format!(r#"{{"completed": {}}}"#, completed)))
+                    .unwrap(),
+            )
+        };
+        let day_completed = |response: Response| async move {
+            let body = body_to_string(response.into_body()).await;
+            let parsed: serde_json::Value = serde_json::from_str(&body).unwrap();
+            parsed.get("day_completed").is_some()
+        };
+        let days = || {
+            let state = state.clone();
+            async move {
+                let response = create_router(state)
+                    .oneshot(
+                        Request::builder()
+                            .uri("/api/stats/days-completed?month=2025-01")
+                            .body(Body::empty())
+                            .unwrap(),
+                    )
+                    .await
+                    .unwrap();
+                let body = body_to_string(response.into_body()).await;
+                serde_json::from_str::<DaysCompletedReport>(&body).unwrap()
+            }
+        };
+
+        assert!(!day_completed(tick(&ids[0], true).await.unwrap()).await);
+        assert!(day_completed(tick(&ids[1], true).await.unwrap()).await);
+        let report = days().await;
+        assert_eq!(report.month, "2025-01");
+        assert_eq!(report.days.len(), 1);
+        assert_eq!(report.days[0].date, "2025-01-15");
+        assert_eq!(report.best_streak, 1);
+
+        // Unticked, the day is open again
+        assert!(!day_completed(tick(&ids[1], false).await.unwrap()).await);
+        assert!(days().await.days.is_empty());
+
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .uri("/api/stats/days-completed?month=January")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_update_entry_records_feedback() {
+        let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
+        let entry_id = entries[0].id.clone();
+        let (_temp_dir, state) = test_state(entries);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(Method::PATCH)
+                    .uri(format!("/api/entries/{}", entry_id))
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(
+                        r#"{"completed": true, "actual_minutes": 45, "difficulty": 3}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_to_string(response.into_body()).await;
+        let parsed: HomeworkEntry = serde_json::from_str(&body).unwrap();
+        assert!(parsed.completed);
+        assert_eq!(parsed.actual_minutes, Some(45));
+        assert_eq!(parsed.difficulty, Some(3));
+    }
+
+    #[tokio::test]
+    async fn test_create_duplicate_entry_conflicts() {
+        let (_temp_dir, state) = test_state(vec![make_entry(
+            "compiti",
+            "2025-01-15",
+            "Matematica",
+            "Task 1",
+        )]);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/entries")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        r#"{"date":"2025-01-15","subject":"Matematica","entry_type":"compiti","task":"Task 1"}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+    }
+
+    #[tokio::test]
+    async fn test_update_test_date_moves_study_sessions() {
+        let today = chrono::Local::now().date_naive();
+        let test_date = today + chrono::Duration::days(10);
+        let test = make_entry("nota", &test_date.to_string(), "Storia", "Verifica cap. 5");
+        let test_id = test.id.clone();
+        let sessions =
+            generate_study_sessions(&test, today, 4, &HashSet::new(), &HashMap::new(), false);
+        let mut entries = vec![test];
+        entries.extend(sessions);
+        // The session closest to the test is already done
+        entries[1].completed = true;
+        let done_id = entries[1].id.clone();
+        let done_date = entries[1].date.clone();
+        let (_temp_dir, state) = test_state(entries);
+        let app = create_router(state.clone());
+
+        let new_date = test_date + chrono::Duration::days(2);
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("PATCH")
+                    .uri(format!("/api/entries/{}", test_id))
+                    .header("content-type", "application/json")
+                    .body(Body::from(format!(r#"{{"date":"{}"}}"#, new_date)))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let conn = state.conn.lock().unwrap();
+        let children = db::get_children(&conn, &test_id).unwrap();
+        assert_eq!(children.len(), 4);
+        for child in &children {
+            let date = chrono::NaiveDate::parse_from_str(&child.date, "%Y-%m-%d").unwrap();
+            if child.id == done_id {
+                assert_eq!(child.date, done_date);
+            } else {
+                assert!(date < new_date);
+                assert!(date >= new_date - chrono::Duration::days(4));
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_entry_pin_and_color() {
+        let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
+        let entry_id = entries[0].id.clone();
+        let (_temp_dir, state) = test_state(entries);
+
+        let patch = |body: &'static str| {
+            Request::builder()
+                .method(Method::PATCH)
+                .uri(format!("/api/entries/{}", entry_id))
+                .header("Content-Type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        let response = create_router(state.clone())
+            .oneshot(patch(r##"{"pinned": true, "color": "#FFAA00"}"##))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        let parsed: HomeworkEntry = serde_json::from_str(&body).unwrap();
+        assert!(parsed.pinned);
+        assert_eq!(parsed.color.as_deref(), Some("#ffaa00"));
+
+        for bad in [r#"{"color": "red"}"#, r##"{"color": "#ffaa0"}"##] {
+            let response = create_router(state.clone())
+                .oneshot(patch(bad))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        }
+
+        let response = create_router(state.clone())
+            .oneshot(patch(r#"{"color": null}"#))
+            .await
+            .unwrap();
+        let body = body_to_string(response.into_body()).await;
+        let parsed: HomeworkEntry = serde_json::from_str(&body).unwrap();
+        assert!(parsed.pinned);
+        assert!(parsed.color.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_entry_visible_from() {
+        let entries = vec![make_entry("compiti", "2999-02-01", "Italiano", "Relazione")];
+        let entry_id = entries[0].id.clone();
+        let (_temp_dir, state) = test_state(entries);
+        let patch = |body: &'static str| {
+            Request::builder()
+                .method(Method::PATCH)
+                .uri(format!("/api/entries/{}", entry_id))
+                .header("Content-Type", "application/json")
+                .body(Body::from(body))
+                .unwrap()
+        };
+
+        // Far enough ahead to stay hidden whenever the test runs
+        let response = create_router(state.clone())
+            .oneshot(patch(r#"{"visible_from": "2999-01-01"}"#))
+            .await
+            .unwrap();
+        let body = body_to_string(response.into_body()).await;
+        let parsed: HomeworkEntry = serde_json::from_str(&body).unwrap();
+        assert_eq!(parsed.visible_from.as_deref(), Some("2999-01-01"));
+
+        let list = |uri: &'static str| {
+            let state = state.clone();
+            async move {
+                let response = create_router(state)
+                    .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap();
+                body_to_string(response.into_body()).await
+            }
+        };
+        let body = list("/fragments/list?all=true").await;
+        assert!(!body.contains("Relazione"));
+        assert!(body.contains("🙈 1 hidden until later"));
+        let body = list("/fragments/list?all=true&hidden=true").await;
+        assert!(body.contains("Relazione"));
+
+        let response = create_router(state.clone())
+            .oneshot(patch(r#"{"visible_from": "next week"}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        // Not past the due date, or it would never show as due
+        let response = create_router(state.clone())
+            .oneshot(patch(r#"{"visible_from": "2999-02-02"}"#))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let response = create_router(state.clone())
+            .oneshot(patch(r#"{"visible_from": null}"#))
+            .await
+            .unwrap();
+        let body = body_to_string(response.into_body()).await;
+        let parsed: HomeworkEntry = serde_json::from_str(&body).unwrap();
+        assert!(parsed.visible_from.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_entry_grade_only_for_happened_types() {
+        let test = make_entry("verifica", "2025-01-15", "Storia", "Cap. 5");
+        let homework = make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10");
+        let (test_id, homework_id) = (test.id.clone(), homework.id.clone());
+        let (_temp_dir, state) = test_state(vec![test, homework]);
+
+        let patch = |id: &str, body: &str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::PATCH)
+                    .uri(format!("/api/entries/{}", id))
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
+
+        let response = patch(&test_id, r#"{"completed": true, "grade": " 7½ "}"#)
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        let parsed: HomeworkEntry = serde_json::from_str(&body).unwrap();
+        assert!(parsed.completed);
+        assert_eq!(parsed.grade.as_deref(), Some("7½"));
+
+        // Compiti get done, not graded; grades are short; unknown entry
+        for (id, body, expected) in [
+            (
+                homework_id.as_str(),
+                r#"{"grade": "8"}"#,
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            (
+                test_id.as_str(),
+                r#"{"grade": "ottimo lavoro!"}"#,
+                StatusCode::UNPROCESSABLE_ENTITY,
+            ),
+            ("nope", r#"{"grade": "8"}"#, StatusCode::NOT_FOUND),
+        ] {
+            assert_eq!(
+                patch(id, body).await.unwrap().status(),
+                expected,
+                "{}",
+                body
+            );
+        }
+
+        // Configured as an event, compiti can take one too
+        {
+            let conn = state.conn.lock().unwrap();
+            let mut settings = db::get_settings(&conn).unwrap();
+            settings
+                .completion
+                .insert("compiti".to_string(), Completion::Happened);
+            db::save_settings(&conn, &settings).unwrap();
+        }
+        let response = patch(&homework_id, r#"{"grade": "8"}"#).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = patch(&test_id, r#"{"grade": null}"#).await.unwrap();
+        let body = body_to_string(response.into_body()).await;
+        let parsed: HomeworkEntry = serde_json::from_str(&body).unwrap();
+        assert!(parsed.grade.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_update_entry_rejects_invalid_difficulty() {
+        let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
+        let entry_id = entries[0].id.clone();
+        let (_temp_dir, state) = test_state(entries);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method(Method::PATCH)
+                    .uri(format!("/api/entries/{}", entry_id))
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(r#"{"difficulty": 0}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_put_entry_replaces_writable_fields() {
+        let mut entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        entry.pinned = true;
+        entry.estimated_minutes = Some(30);
+        let entry_id = entry.id.clone();
+        let (_temp_dir, state) = test_state(vec![entry]);
+        let put = |body: String| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(Method::PUT)
+                    .uri(format!("/api/entries/{}", entry_id))
+                    .header("Content-Type", "application/json")
+                    .body(Body::from(body))
+                    .unwrap(),
+            )
+        };
+
+        // Sent twice, the same body leaves the same entry
+        let body = format!(
+            r#"{{"id": "{}", "date": "2025-01-16", "completed": true, "subject": "Matematica"}}"#,
+            entry_id
+        );
+        for _ in 0..2 {
+            let response = put(body.clone()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = body_to_string(response.into_body()).await;
+            let parsed: HomeworkEntry = serde_json::from_str(&body).unwrap();
+            assert_eq!(parsed.date, "2025-01-16");
+            assert!(parsed.completed);
+            // Left out, so back to the default
+            assert!(!parsed.pinned);
+            assert_eq!(parsed.estimated_minutes, None);
+        }
+
+        // Not JSON at all: still an ApiError, not axum's plain text
+        let response = put("{\"date\": ".to_string()).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let error: serde_json::Value =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        assert_eq!(error["code"], "validation");
+
+        for (body, field) in [
+            (r#"{"date": "2025-01-16", "colour": "red"}"#, "colour"),
+            (r#"{"date": "2025-01-16", "subject": "Arte"}"#, "subject"),
+            (r#"{"completed": false}"#, "date"),
+        ] {
+            let response = put(body.to_string()).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+            let error: serde_json::Value =
+                serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+            assert_eq!(error["code"], "unprocessable");
+            assert_eq!(error["details"]["field"], field);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_time_stats_handler() {
+        let mut entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
+        entry.completed = true;
+        entry.estimated_minutes = Some(30);
+        entry.actual_minutes = Some(45);
+        let (_temp_dir, state) = test_state(vec![entry]);
+        let app = create_router(state);
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/stats/time")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let body = body_to_string(response.into_body()).await;
+        let stats: Vec<crate::types::SubjectTimeStats> = serde_json::from_str(&body).unwrap();
+        assert_eq!(stats.len(), 1);
+        assert_eq!(stats[0].estimate_ratio, Some(1.5));
+    }
+
+    #[tokio::test]
+    async fn test_goals_stats_handler() {
+        let mut session = make_entry("studio", "2025-01-14", "Matematica", "Study");
+        session.completed = true;
+        let mut homework = make_entry("compiti", "2025-01-16", "Matematica", "Pag. 10");
+        homework.completed = true;
+        let (_temp_dir, state) = test_state(vec![session, homework]);
+        {
+            let conn = state.conn.lock().unwrap();
+            db::set_weekly_goals(
+                &conn,
+                &[crate::types::WeeklyGoal {
+                    subject: "Matematica".to_string(),
+                    target: 3,
+                }],
+            )
+            .unwrap();
+        }
+
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .uri("/api/stats/goals?week=2025-01-15")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        let report: GoalsReport = serde_json::from_str(&body).unwrap();
+        assert_eq!(report.week_start, "2025-01-13");
+        assert_eq!(report.week_end, "2025-01-19");
+        assert_eq!(report.goals[0].done, 2);
+        assert_eq!(report.met, 0);
+
+        let response = create_router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/stats/goals?week=soon")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_topic_filter_and_stats() {
+        let mut geometry = make_entry("verifica", "2025-01-20", "Matematica", "Geometria: aree");
+        geometry.topic = Some("Geometria".to_string());
+        let mut algebra = make_entry("compiti", "2025-01-15", "Matematica", "Algebra: pag. 12");
+        algebra.topic = Some("Algebra".to_string());
+        let (_temp_dir, state) = test_state(vec![
+            geometry,
+            algebra,
+            make_entry("compiti", "2025-01-16", "Storia", "Cap. 5"),
+        ]);
+        let get = |uri: &str| {
+            create_router(state.clone())
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+
+        let response = get("/api/entries?topic=geometria").await.unwrap();
+        let body = body_to_string(response.into_body()).await;
+        let entries: Vec<HomeworkEntry> = serde_json::from_str(&body).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].topic.as_deref(), Some("Geometria"));
+
+        let response = get("/api/stats/topics").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        let stats: Vec<crate::types::TopicStats> = serde_json::from_str(&body).unwrap();
+        assert_eq!(stats.len(), 2);
+        assert!(stats.iter().all(|s| s.subject == "Matematica"));
+
+        let response = get("/stats").await.unwrap();
+        let body = body_to_string(response.into_body()).await;
+        assert!(body.contains("Algebra"));
+    }
+
+    #[tokio::test]
+    async fn test_stats_page_handler() {
+        let (_temp_dir, state) = test_state(vec![]);
+        let app = create_router(state.clone());
+
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/stats")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        assert!(body.contains("Estimated vs actual"));
+
+        // A bad request for a page gets a page, not JSON
+        let response = create_router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/stats?month=January")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let content_type = response.headers().get("content-type").unwrap();
+        assert!(content_type.to_str().unwrap().starts_with("text/html"));
+        let body = body_to_string(response.into_body()).await;
+        assert!(body.contains("month must be YYYY-MM"));
+    }
+
+    #[tokio::test]
+    async fn test_malformed_json_body_is_api_error() {
+        let (_temp_dir, state) = test_state(vec![]);
+        for (body, content_type) in [
+            ("{not json", "application/json"),
+            (r#"{"date": 5}"#, "application/json"),
+            (r#"{"date": "2025-01-15"}"#, "text/plain"),
+        ] {
+            let response = create_router(state.clone())
+                .oneshot(
+                    Request::builder()
+                        .method(Method::POST)
+                        .uri("/api/entries")
+                        .header("Content-Type", content_type)
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST, "{}", body);
+            let error: serde_json::Value =
+                serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+            assert_eq!(error["code"], "validation");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_filtered_exports() {
+        let (_temp_dir, state) = test_state(vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10"),
+            make_entry("verifica", "2025-01-20", "Matematica", "Cap. 2"),
+            make_entry("verifica", "2025-01-22", "Storia", "Cap. 5"),
+        ]);
+        let get = |uri: &str| {
+            create_router(state.clone())
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+
+        let response = get("/api/entries?subject=matematica&from=2025-01-16")
+            .await
+            .unwrap();
+        let body = body_to_string(response.into_body()).await;
+        let entries: Vec<HomeworkEntry> = serde_json::from_str(&body).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].task, "Cap. 2");
+
+        let response = get("/api/entries?q=cap&subject=storia").await.unwrap();
+        let body = body_to_string(response.into_body()).await;
+        let entries: Vec<HomeworkEntry> = serde_json::from_str(&body).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].task, "Cap. 5");
+
+        let response = get("/api/export.ics?type=verifica").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_TYPE],
+            "text/calendar; charset=utf-8"
+        );
+        let body = body_to_string(response.into_body()).await;
+        assert_eq!(body.matches("BEGIN:VEVENT").count(), 2);
+        assert!(!body.contains("Pag. 10"));
+
+        let response = get("/api/export.csv?subject=Matematica,Storia&type=compiti")
+            .await
+            .unwrap();
+        assert!(response.headers()[header::CONTENT_DISPOSITION]
+            .to_str()
+            .unwrap()
+            .contains("compitutto.csv"));
+        let body = body_to_string(response.into_body()).await;
+        assert_eq!(
+            body,
+            "date,type,subject,task,completed,icon\r\n2025-01-15,compiti,Matematica,Pag. 10,no,📐\r\n"
+        );
 
-    /// Helper to create test entries
-    fn make_entry(entry_type: &str, date: &str, subject: &str, task: &str) -> HomeworkEntry {
-        HomeworkEntry::new(
-            entry_type.to_string(),
-            date.to_string(),
-            subject.to_string(),
-            task.to_string(),
-        )
+        let response = get("/api/export.csv?to=tomorrow").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 
-    /// Setup a test database with optional entries
-    fn setup_test_db(entries: &[HomeworkEntry]) -> (TempDir, Connection) {
-        let temp_dir = TempDir::new().unwrap();
-        let db_path = temp_dir.path().join("test.db");
-        let migrations_dir = temp_dir.path().join("migrations");
-        std::fs::create_dir(&migrations_dir).unwrap();
+    #[tokio::test]
+    async fn test_live_handler() {
+        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10");
+        let id = entry.id.clone();
+        let (_temp_dir, state) = test_state(vec![entry]);
 
-        std::fs::write(
-            migrations_dir.join("001_initial_schema.sql"),
-            include_str!("../db/migrations/001_initial_schema.sql"),
-        )
-        .unwrap();
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .uri("/api/live?session=s1&name=Mamma")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let mut body = response.into_body();
+        let mut next_event = async || {
+            let frame = body.frame().await.unwrap().unwrap();
+            String::from_utf8(frame.into_data().unwrap().to_vec()).unwrap()
+        };
 
-        let conn = db::init_db(&db_path, &migrations_dir).unwrap();
+        let presence = next_event().await;
+        assert!(presence.starts_with("event: presence\n"));
+        assert!(presence.contains(r#""name":"Mamma""#));
 
-        for entry in entries {
-            db::insert_entry(&conn, entry).unwrap();
-        }
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method(Method::PATCH)
+                    .uri(format!("/api/entries/{}", id))
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"completed":true}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let update = next_event().await;
+        assert!(update.starts_with("event: entry\n"));
+        assert!(update.contains(&format!(r#""id":"{}","completed":true"#, id)));
 
-        (temp_dir, conn)
+        // Closing the stream leaves the viewer list
+        drop(body);
+        assert!(state.live.viewers().is_empty());
     }
 
-    /// Helper to create a test app state with a database
-    fn test_state(entries: Vec<HomeworkEntry>) -> (TempDir, Arc<AppState>) {
-        let (temp_dir, conn) = setup_test_db(&entries);
-        let state = Arc::new(AppState::new(conn));
-        (temp_dir, state)
-    }
+    #[tokio::test]
+    async fn test_feature_flags() {
+        let (_temp_dir, state) = test_state(vec![]);
+        let send = |method: Method, uri: &str, body: &str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri(uri)
+                    .header("content-type", "application/json")
+                    .body(Body::from(body.to_string()))
+                    .unwrap(),
+            )
+        };
 
-    /// Helper to create a test Excel XML file
-    fn create_test_export(path: &std::path::Path, entries: &[(&str, &str, &str, &str)]) {
-        let mut rows = String::from(
-            r#"<Row><Cell><Data ss:Type="String">tipo</Data></Cell><Cell><Data ss:Type="String">data_inizio</Data></Cell><Cell><Data ss:Type="String">materia</Data></Cell><Cell><Data ss:Type="String">nota</Data></Cell></Row>"#,
-        );
-        for (tipo, date, subject, task) in entries {
-            rows.push_str(&format!(
-                r#"<Row><Cell><Data ss:Type="String">{}</Data></Cell><Cell><Data ss:Type="String">{}</Data></Cell><Cell><Data ss:Type="String">{}</Data></Cell><Cell><Data ss:Type="String">{}</Data></Cell></Row>"#,
-                tipo, date, subject, task
-            ));
-        }
-        let xml = format!(
-            r#"<?xml version="1.0"?><Workbook xmlns="urn:schemas-microsoft-com:office:spreadsheet"><Worksheet ss:Name="Table1"><Table>{}</Table></Worksheet></Workbook>"#,
-            rows
+        let (status, body) = get_status_and_body(&state, "/api/features").await;
+        assert_eq!(status, StatusCode::OK);
+        let flags: Vec<features::FeatureState> = serde_json::from_str(&body).unwrap();
+        assert!(flags.iter().all(|f| f.enabled));
+
+        let response = send(
+            Method::PUT,
+            "/api/features",
+            r#"{"study_sessions": false, "live": false}"#,
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let flags: Vec<features::FeatureState> =
+            serde_json::from_str(&body_to_string(response.into_body()).await).unwrap();
+        let live = flags.iter().find(|f| f.name == "live").unwrap();
+        assert_eq!(
+            (live.enabled, live.source),
+            (false, features::Source::Settings)
         );
-        std::fs::write(path, xml).unwrap();
-    }
+        let status = send(Method::PUT, "/api/features", r#"{"auth": false}"#)
+            .await
+            .unwrap()
+            .status();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
 
-    /// Helper to get response body as string
-    async fn body_to_string(body: Body) -> String {
-        let bytes = body.collect().await.unwrap().to_bytes();
-        String::from_utf8(bytes.to_vec()).unwrap()
-    }
+        // Off means off, whatever the study_sessions setting says
+        let date = chrono::Local::now().date_naive() + chrono::Duration::days(10);
+        let response = send(
+            Method::POST,
+            "/api/entries",
+            &format!(
+                r#"{{"entry_type": "verifica", "date": "{}", "subject": "Storia", "task": "Cap. 5"}}"#,
+                date
+            ),
+        )
+        .await
+        .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(db::count_entries(&state.conn.lock().unwrap()).unwrap(), 1);
 
-    /// Helper to run async test with changed directory
-    async fn with_temp_dir_async<F, Fut, T>(temp_dir: &TempDir, f: F) -> T
-    where
-        F: FnOnce() -> Fut,
-        Fut: std::future::Future<Output = T>,
-    {
-        let _lock = DIR_LOCK.lock().unwrap();
-        let original_dir = std::env::current_dir().unwrap();
-        std::env::set_current_dir(temp_dir.path()).unwrap();
-        let result = f().await;
-        std::env::set_current_dir(original_dir).unwrap();
-        result
+        let (status, _) = get_status_and_body(&state, "/api/live?session=s1").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
     }
 
-    // ========== AppState tests ==========
+    #[tokio::test]
+    async fn test_compare_stats_handler() {
+        let mut done = make_entry("compiti", "2024-10-15", "Matematica", "Pag. 10");
+        done.completed = true;
+        let (_temp_dir, state) = test_state(vec![
+            done,
+            make_entry("verifica", "2024-11-20", "Matematica", "Cap. 2"),
+            make_entry("verifica", "2025-03-10", "Storia", "Cap. 5"),
+        ]);
+
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .uri("/api/stats/compare?a=2024-09-01..2025-01-31&b=2025-02-01..2025-06-30")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        let comparison: StatsComparison = serde_json::from_str(&body).unwrap();
+        assert_eq!(comparison.a.total.entries, 2);
+        assert_eq!(comparison.a.total.completion_rate, Some(0.5));
+        assert_eq!(comparison.b.total.tests, 1);
+        assert_eq!(comparison.subjects(), vec!["Matematica", "Storia"]);
 
-    #[test]
-    fn test_app_state_db_access() {
-        let entries = vec![
-            make_entry("compiti", "2025-01-15", "Matematica", "Task 1"),
-            make_entry("nota", "2025-01-16", "Italiano", "Task 2"),
-        ];
-        let (_temp_dir, state) = test_state(entries);
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .uri("/stats/compare?a=2024-09-01..2025-01-31&b=2025-02-01..2025-06-30")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        assert!(body.contains("All subjects"));
+        assert!(body.contains("<td>50%</td>"));
 
-        let conn = state.conn.lock().unwrap();
-        let read_entries = db::get_all_entries(&conn).unwrap();
-        assert_eq!(read_entries.len(), 2);
+        let response = create_router(state)
+            .oneshot(
+                Request::builder()
+                    .uri("/api/stats/compare?a=2025-02-01..2024-09-01")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 
-    // ========== Router tests ==========
+    #[tokio::test]
+    async fn test_teachers_stats_handler() {
+        let mut test = make_entry("verifica", "2024-11-20", "Matematica", "Cap. 2");
+        test.teacher = Some("ROSSI MARIA".to_string());
+        test.assigned_at = Some("2024-11-13 10:00:00".to_string());
+        let (_temp_dir, state) = test_state(vec![
+            test,
+            make_entry("compiti", "2024-11-21", "Storia", "Cap. 5"),
+        ]);
+        let get = |uri: &'static str| {
+            let state = state.clone();
+            async move {
+                create_router(state)
+                    .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap()
+            }
+        };
 
-    #[test]
-    fn test_create_router() {
-        let (_temp_dir, state) = test_state(vec![]);
-        let router = create_router(state);
-        // Router created successfully - routes are tested via handler tests
-        assert!(true, "Router created: {:?}", router);
-    }
+        let response = get("/api/stats/teachers?period=2024-09-01..2025-06-30").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        let report: TeacherReport = serde_json::from_str(&body).unwrap();
+        assert_eq!(report.teachers.len(), 1);
+        assert_eq!(report.teachers[0].tests, 1);
+        assert_eq!(report.teachers[0].avg_lead_days, Some(7.0));
+        assert_eq!(report.unattributed, 1);
+
+        let response = get("/stats/teachers?period=2024-09-01..2025-06-30&download=true").await;
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"teachers_2024-09-01_2025-06-30.html\""
+        );
+        let body = body_to_string(response.into_body()).await;
+        assert!(body.contains("ROSSI MARIA"));
+        assert!(body.contains("window.print()"));
 
-    // ========== index_handler tests ==========
+        let response = get("/api/stats/teachers?period=2025-06-30").await;
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
 
     #[tokio::test]
-    async fn test_index_handler_empty_entries() {
-        let (_temp_dir, state) = test_state(vec![]);
-        let app = create_router(state);
+    async fn test_source_files() {
+        let temp_dir = TempDir::new().unwrap();
+        let data_dir = temp_dir.path().join("data");
+        std::fs::create_dir(&data_dir).unwrap();
+        create_test_export(
+            &data_dir.join("export_20250110_080000.xls"),
+            &[("compiti", "2025-01-15", "Matematica", "Pag. 12")],
+        );
+        create_test_export(
+            &data_dir.join("export_20250112_080000.xls"),
+            &[
+                ("compiti", "2025-01-15", "Matematica", "Pag. 12"),
+                ("compiti", "2025-01-16", "Storia", "Cap. 3"),
+            ],
+        );
+        let (_db_dir, conn) = setup_test_db(&[]);
+        db::set_review_imports(&conn, false).unwrap();
+        let by_hand = make_entry("compiti", "2025-01-17", "Arte", "Disegno");
+        db::insert_entry(&conn, &by_hand).unwrap();
+        let mut gone = make_entry("compiti", "2024-12-01", "Arte", "Schizzo");
+        gone.source_file = Some("export_20241130_080000.xls".to_string());
+        db::insert_entry(&conn, &gone).unwrap();
+        let state = Arc::new(AppState::new(conn));
 
-        let response = app
-            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
-            .await
-            .unwrap();
+        let get = |uri: String| {
+            let state = state.clone();
+            async move {
+                create_router(state)
+                    .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+                    .await
+                    .unwrap()
+            }
+        };
+        let (listed, first, unknown, escape) = with_temp_dir_async(&temp_dir, || async {
+            data::parse_exports()
+                .map(|parsed| import_exports(&state.conn.lock().unwrap(), &parsed))
+                .unwrap()
+                .unwrap();
+            let listed = get("/api/source-files".to_string()).await;
+            let id = db::get_all_entries(&state.conn.lock().unwrap())
+                .unwrap()
+                .into_iter()
+                .find(|e| e.task == "Pag. 12")
+                .unwrap()
+                .id;
+            let first = get(format!("/api/entries/{}/source-file", id)).await;
+            let unknown = get(format!("/api/entries/{}/source-file", by_hand.id)).await;
+            let escape = get("/api/source-files/..%2Fdata%2Fhomework.db".to_string()).await;
+            (listed, first, unknown, escape)
+        })
+        .await;
 
-        assert_eq!(response.status(), StatusCode::OK);
+        let files: Vec<SourceFile> =
+            serde_json::from_str(&body_to_string(listed.into_body()).await).unwrap();
+        let summary: Vec<_> = files
+            .iter()
+            .map(|f| (f.name.as_str(), f.available, f.entries))
+            .collect();
+        assert_eq!(
+            summary,
+            vec![
+                ("export_20250110_080000.xls", true, 1),
+                ("export_20250112_080000.xls", true, 1),
+                ("export_20241130_080000.xls", false, 1),
+            ]
+        );
+        assert!(files[0].bytes.is_some_and(|b| b > 0));
 
-        let body = body_to_string(response.into_body()).await;
-        assert!(body.contains("<!DOCTYPE html>"));
-        assert!(body.contains("Compitutto"));
-        assert!(body.contains("No homework entries found"));
+        // The entry keeps the first export that had it
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(
+            first.headers()[header::CONTENT_DISPOSITION],
+            "attachment; filename=\"export_20250110_080000.xls\""
+        );
+        assert!(body_to_string(first.into_body()).await.contains("Pag. 12"));
+        assert_eq!(unknown.status(), StatusCode::NOT_FOUND);
+        assert_eq!(escape.status(), StatusCode::NOT_FOUND);
     }
 
     #[tokio::test]
-    async fn test_index_handler_with_entries() {
-        let entries = vec![
-            make_entry("compiti", "2025-01-15", "Matematica", "Pag. 100"),
-            make_entry("nota", "2025-01-16", "Italiano", "Verifica"),
-        ];
-        let (_temp_dir, state) = test_state(entries);
-        let app = create_router(state);
+    async fn test_simple_view() {
+        let today = chrono::Local::now().date_naive();
+        let mut hidden = make_entry("compiti", &today.to_string(), "Storia", "Cap. 9");
+        hidden.visible_from = Some((today + chrono::Duration::days(1)).to_string());
+        let (_temp_dir, state) = test_state(vec![
+            make_entry("compiti", &today.to_string(), "Matematica", "Pag. 40"),
+            hidden,
+        ]);
+        let get = |uri: &'static str, cookie: Option<&'static str>| {
+            let state = state.clone();
+            async move {
+                let mut request = Request::builder().uri(uri);
+                if let Some(cookie) = cookie {
+                    request = request.header(header::COOKIE, cookie);
+                }
+                create_router(state)
+                    .oneshot(request.body(Body::empty()).unwrap())
+                    .await
+                    .unwrap()
+            }
+        };
 
-        let response = app
-            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
-            .await
-            .unwrap();
+        let response = get("/simple?device=true", None).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], "/simple");
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.starts_with("compitutto_view=simple;"));
 
+        let response = get("/", Some("theme=dark; compitutto_view=simple")).await;
+        assert_eq!(response.status(), StatusCode::SEE_OTHER);
+        assert_eq!(response.headers()[header::LOCATION], "/simple");
+        let response = get("/", None).await;
         assert_eq!(response.status(), StatusCode::OK);
 
+        let response = get("/simple", None).await;
+        assert_eq!(response.status(), StatusCode::OK);
         let body = body_to_string(response.into_body()).await;
-        assert!(body.contains("Matematica"));
-        assert!(body.contains("Italiano"));
-        assert!(body.contains("Pag. 100"));
-        assert!(body.contains("Verifica"));
-        assert!(body.contains("2025-01-15"));
-        assert!(body.contains("2025-01-16"));
+        assert!(body.contains("Pag. 40"));
+        assert!(body.contains("simple-check"));
+        assert!(!body.contains("Cap. 9"));
+
+        let response = get("/simple?device=false", None).await;
+        assert_eq!(response.headers()[header::LOCATION], "/");
+        let cookie = response.headers()[header::SET_COOKIE].to_str().unwrap();
+        assert!(cookie.contains("Max-Age=0"));
     }
 
     #[tokio::test]
-    async fn test_index_handler_content_type() {
-        let (_temp_dir, state) = test_state(vec![]);
+    async fn test_plan_today_leaves_out_hidden_entries() {
+        let today = chrono::Local::now().date_naive();
+        let mut hidden = make_entry("compiti", &today.to_string(), "Storia", "Cap. 9");
+        hidden.visible_from = Some((today + chrono::Duration::days(1)).to_string());
+        let shown = make_entry("compiti", &today.to_string(), "Matematica", "Pag. 40");
+        let shown_id = shown.id.clone();
+        let (_temp_dir, state) = test_state(vec![shown, hidden]);
+
+        let (status, body) = get_status_and_body(&state, "/api/plan/today").await;
+        assert_eq!(status, StatusCode::OK);
+        let plan: plan::Plan = serde_json::from_str(&body).unwrap();
+        let ids: Vec<&str> = plan
+            .items
+            .iter()
+            .chain(&plan.later)
+            .map(|i| i.id.as_str())
+            .collect();
+        assert_eq!(ids, [shown_id.as_str()]);
+    }
+
+    #[test]
+    fn test_default_terms() {
+        let today = NaiveDate::from_ymd_opt(2025, 3, 10).unwrap();
+        let [a, b] = default_terms(today);
+        assert_eq!(a.0.to_string(), "2024-09-01");
+        assert_eq!(a.1.to_string(), "2025-01-31");
+        assert_eq!(b.1.to_string(), "2025-06-30");
+        let today = NaiveDate::from_ymd_opt(2025, 9, 15).unwrap();
+        assert_eq!(default_terms(today)[0].0.to_string(), "2025-09-01");
+        assert_eq!(
+            parse_period("2025-01-01 .. 2025-01-31").map(|p| p.1.day()),
+            Some(31)
+        );
+        assert_eq!(parse_period("2025-01-01"), None);
+    }
+
+    #[tokio::test]
+    async fn test_agenda_handler() {
+        let (_temp_dir, state) = test_state(vec![
+            make_entry("compiti", "2025-01-15", "Matematica", "Pag. 10"),
+            make_entry("verifica", "2025-01-15", "Storia", "Cap. 5"),
+            make_entry("compiti", "2025-01-16", "Italiano", "Tema"),
+        ]);
         let app = create_router(state);
 
         let response = app
-            .oneshot(Request::builder().uri("/").body(Body::empty()).unwrap())
+            .oneshot(
+                Request::builder()
+                    .uri("/api/agenda/2025-01-15")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
             .await
             .unwrap();
 
-        let content_type = response.headers().get("content-type").unwrap();
-        assert!(content_type.to_str().unwrap().contains("text/html"));
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        let agenda: agenda::Agenda = serde_json::from_str(&body).unwrap();
+        assert_eq!(agenda.total, 2);
+        assert_eq!(agenda.items[0].text, "Matematica homework: Pag. 10.");
+        assert!(agenda.summary.contains("1 homework and 1 test"));
     }
 
-    // ========== entries_handler tests ==========
-
     #[tokio::test]
-    async fn test_entries_handler_empty() {
+    async fn test_agenda_today_handler() {
         let (_temp_dir, state) = test_state(vec![]);
         let app = create_router(state);
 
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri("/api/entries")
+                    .uri("/api/agenda/today")
                     .body(Body::empty())
                     .unwrap(),
             )
@@ -935,24 +6709,35 @@ mod tests {
             .unwrap();
 
         assert_eq!(response.status(), StatusCode::OK);
-
         let body = body_to_string(response.into_body()).await;
-        assert_eq!(body, "[]");
+        let agenda: agenda::Agenda = serde_json::from_str(&body).unwrap();
+        assert_eq!(
+            agenda.date,
+            chrono::Local::now()
+                .date_naive()
+                .format("%Y-%m-%d")
+                .to_string()
+        );
+        assert!(agenda.summary.starts_with("Nothing on the agenda"));
     }
 
     #[tokio::test]
-    async fn test_entries_handler_with_data() {
-        let entries = vec![
-            make_entry("compiti", "2025-01-15", "Matematica", "Task 1"),
-            make_entry("nota", "2025-01-16", "Italiano", "Task 2"),
-        ];
-        let (_temp_dir, state) = test_state(entries);
-        let app = create_router(state);
+    async fn test_plan_today_handler() {
+        let today = chrono::Local::now().date_naive();
+        let mut entry = make_entry(
+            "compiti",
+            &today.format("%Y-%m-%d").to_string(),
+            "Matematica",
+            "Pag. 10",
+        );
+        entry.estimated_minutes = Some(45);
+        let (_temp_dir, state) = test_state(vec![entry]);
+        db::set_daily_budget_minutes(&state.conn.lock().unwrap(), 60).unwrap();
 
-        let response = app
+        let response = create_router(state)
             .oneshot(
                 Request::builder()
-                    .uri("/api/entries")
+                    .uri("/api/plan/today")
                     .body(Body::empty())
                     .unwrap(),
             )
@@ -960,162 +6745,315 @@ mod tests {
             .unwrap();
 
         assert_eq!(response.status(), StatusCode::OK);
-
         let body = body_to_string(response.into_body()).await;
-        let parsed: Vec<HomeworkEntry> = serde_json::from_str(&body).unwrap();
+        let plan: plan::Plan = serde_json::from_str(&body).unwrap();
+        assert_eq!(plan.budget_minutes, 60);
+        assert_eq!(plan.planned_minutes, 45);
+        assert_eq!(plan.items.len(), 1);
+        assert_eq!(plan.items[0].reason, plan::PlanReason::Today);
+    }
 
-        assert_eq!(parsed.len(), 2);
-        assert_eq!(parsed[0].subject, "Matematica");
-        assert_eq!(parsed[1].subject, "Italiano");
+    #[tokio::test]
+    async fn test_cross_listed_task_shows_once() {
+        let today = chrono::Local::now().date_naive().to_string();
+        let history = make_entry("compiti", &today, "Storia", "Ricerca sul Rinascimento");
+        let art = make_entry("compiti", &today, "Arte", "Ricerca sul Rinascimento");
+        let (art_id, history_id) = (art.id.clone(), history.id.clone());
+        let (_temp_dir, state) = test_state(vec![history, art]);
+        db::cross_list(&state.conn.lock().unwrap(), &art_id, &history_id).unwrap();
+
+        let (status, body) = get_status_and_body(&state, "/").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.matches("Ricerca sul Rinascimento").count(), 1);
+        assert!(body.contains("🔀 Storia + Arte"));
+        assert!(body.contains(
+            r#"<span class="counter-value">1</span><span class="counter-label">Open today"#
+        ));
+
+        let (status, body) = get_status_and_body(&state, "/api/plan/today").await;
+        assert_eq!(status, StatusCode::OK);
+        let plan: plan::Plan = serde_json::from_str(&body).unwrap();
+        let ids: Vec<&str> = plan
+            .items
+            .iter()
+            .chain(&plan.later)
+            .map(|i| i.id.as_str())
+            .collect();
+        assert_eq!(ids, [history_id.as_str()]);
     }
 
     #[tokio::test]
-    async fn test_entries_handler_json_content_type() {
+    async fn test_forecast_handler() {
+        let today = chrono::Local::now().date_naive();
+        let mut entry = make_entry(
+            "verifica",
+            &(today + chrono::Duration::days(14))
+                .format("%Y-%m-%d")
+                .to_string(),
+            "Storia",
+            "Verifica sul Medioevo",
+        );
+        entry.estimated_minutes = Some(30);
+        let (_temp_dir, state) = test_state(vec![entry]);
+        let get = |uri: &'static str| {
+            create_router(state.clone())
+                .oneshot(Request::builder().uri(uri).body(Body::empty()).unwrap())
+        };
+
+        let response = get("/api/forecast").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        let forecast: forecast::Forecast = serde_json::from_str(&body).unwrap();
+        assert_eq!(forecast.weeks.len(), forecast::DEFAULT_WEEKS as usize);
+        assert_eq!(forecast.weeks.iter().map(|w| w.tests).sum::<usize>(), 1);
+        // The test's study sessions aren't generated yet but are forecast
+        assert!(forecast.weeks.iter().map(|w| w.projected).sum::<usize>() > 0);
+
+        let response = get("/api/forecast?weeks=0").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        let response = get("/api/forecast?weeks=13").await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_version_handler() {
         let (_temp_dir, state) = test_state(vec![]);
-        let app = create_router(state);
+        let get_version = || async {
+            let response = create_router(state.clone())
+                .oneshot(
+                    Request::builder()
+                        .uri("/api/version")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            let body = body_to_string(response.into_body()).await;
+            serde_json::from_str::<version::VersionInfo>(&body).unwrap()
+        };
+
+        let info = get_version().await;
+        assert_eq!(info.version, version::VERSION);
+        let schema_version = db::get_schema_version(&state.conn.lock().unwrap()).unwrap();
+        assert!(schema_version.is_some());
+        assert_eq!(info.schema_version, schema_version);
+        assert!(info.latest.is_none());
+        assert!(!info.update_available);
+
+        // Off by default: nothing is offered until it's turned on
+        *state.latest_release.lock().unwrap() = Some(version::Release {
+            version: "99.0.0".to_string(),
+            url: "https://example.com/releases/v99.0.0".to_string(),
+        });
+        assert!(!get_version().await.update_available);
+        {
+            let conn = state.conn.lock().unwrap();
+            let mut settings = db::get_settings(&conn).unwrap();
+            settings.check_updates = true;
+            db::save_settings(&conn, &settings).unwrap();
+        }
 
-        let response = app
-            .oneshot(
-                Request::builder()
-                    .uri("/api/entries")
-                    .body(Body::empty())
-                    .unwrap(),
-            )
-            .await
-            .unwrap();
+        *state.latest_release.lock().unwrap() = Some(version::Release {
+            version: "99.0.0".to_string(),
+            url: "https://example.com/releases/v99.0.0".to_string(),
+        });
+        assert!(get_version().await.update_available);
 
-        let content_type = response.headers().get("content-type").unwrap();
-        assert!(content_type.to_str().unwrap().contains("application/json"));
+        // With the check off, a release found earlier isn't offered
+        {
+            let conn = state.conn.lock().unwrap();
+            let mut settings = db::get_settings(&conn).unwrap();
+            settings.check_updates = false;
+            db::save_settings(&conn, &settings).unwrap();
+        }
+        let info = get_version().await;
+        assert!(info.latest.is_none());
+        assert!(!info.update_available);
     }
 
     #[tokio::test]
-    async fn test_entries_handler_serialization() {
-        let entries = vec![make_entry(
-            "compiti",
-            "2025-01-15",
-            "Matematica",
-            "Special chars: àèìòù & \"quotes\"",
-        )];
-        let (_temp_dir, state) = test_state(entries);
+    async fn test_agenda_handler_rejects_invalid_date() {
+        let (_temp_dir, state) = test_state(vec![]);
         let app = create_router(state);
 
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri("/api/entries")
+                    .uri("/api/agenda/tomorrow")
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        let body = body_to_string(response.into_body()).await;
-        let parsed: Vec<HomeworkEntry> = serde_json::from_str(&body).unwrap();
-
-        assert_eq!(parsed[0].task, "Special chars: àèìòù & \"quotes\"");
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
     }
 
-    // ========== refresh_handler tests ==========
+    // ========== Import review tests ==========
 
-    #[tokio::test]
-    async fn test_refresh_handler_no_data() {
-        // Create a temp directory with no export files and no existing data
+    #[test]
+    fn test_process_refresh_stages_imports_for_review() {
         let temp_dir = TempDir::new().unwrap();
         let data_dir = temp_dir.path().join("data");
         std::fs::create_dir(&data_dir).unwrap();
 
-        // Setup migrations for the test database
         let migrations_dir = temp_dir.path().join("migrations");
         std::fs::create_dir(&migrations_dir).unwrap();
-        std::fs::write(
-            migrations_dir.join("001_initial_schema.sql"),
-            include_str!("../db/migrations/001_initial_schema.sql"),
-        )
-        .unwrap();
+        db::write_test_migrations(&migrations_dir);
 
         let db_path = data_dir.join("homework.db");
         let conn = db::init_db(&db_path, &migrations_dir).unwrap();
-        let state = Arc::new(AppState::new(conn));
-        let app = create_router(state);
+        let state = AppState::new(conn);
 
-        let response = with_temp_dir_async(&temp_dir, || async {
-            app.oneshot(
-                Request::builder()
-                    .uri("/api/refresh")
-                    .body(Body::empty())
-                    .unwrap(),
-            )
-            .await
-            .unwrap()
-        })
-        .await;
+        create_test_export(
+            &data_dir.join("export_test.xls"),
+            &[("compiti", "2025-01-15", "Matematica", "Task 1")],
+        );
 
-        assert_eq!(response.status(), StatusCode::OK);
+        let _lock = DIR_LOCK.lock().unwrap();
+        let original_dir = std::env::current_dir().unwrap();
+        std::env::set_current_dir(temp_dir.path()).unwrap();
 
-        let body = body_to_string(response.into_body()).await;
-        assert_eq!(body, "ERROR"); // No data available
+        let result = process_refresh(&state);
+
+        std::env::set_current_dir(original_dir).unwrap();
+
+        assert_eq!(result, RefreshResult::NoChange { count: 0 });
+        let conn = state.conn.lock().unwrap();
+        let pending = db::get_pending_imports(&conn).unwrap();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].task, "Task 1");
     }
 
     #[tokio::test]
-    async fn test_refresh_handler_with_export_file() {
+    async fn test_imports_report() {
         let temp_dir = TempDir::new().unwrap();
         let data_dir = temp_dir.path().join("data");
         std::fs::create_dir(&data_dir).unwrap();
+        let (_db_dir, state) = test_state(vec![]);
+
+        let report = with_temp_dir_async(&temp_dir, || async {
+            let app = create_router(state.clone());
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri("/api/imports/report")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            assert_eq!(body_to_string(response.into_body()).await, "[]");
+
+            create_test_export(
+                &data_dir.join("export_test.xls"),
+                &[
+                    ("compiti", "2025-01-15", "Matematica", "Task 1"),
+                    ("compiti", "2025-01-16", "", ""),
+                ],
+            );
+            let app = create_router(state.clone());
+            let response = app
+                .oneshot(
+                    Request::builder()
+                        .uri("/api/imports/report")
+                        .body(Body::empty())
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::OK);
+            body_to_string(response.into_body()).await
+        })
+        .await;
 
-        // Setup migrations
-        let migrations_dir = temp_dir.path().join("migrations");
-        std::fs::create_dir(&migrations_dir).unwrap();
-        std::fs::write(
-            migrations_dir.join("001_initial_schema.sql"),
-            include_str!("../db/migrations/001_initial_schema.sql"),
-        )
-        .unwrap();
+        let reports: serde_json::Value = serde_json::from_str(&report).unwrap();
+        assert_eq!(reports[0]["file"], "export_test.xls");
+        assert_eq!(reports[0]["format"], "classeviva");
+        assert_eq!(reports[0]["rows_total"], 2);
+        assert_eq!(reports[0]["parsed"], 1);
+        assert_eq!(reports[0]["skipped"][0]["row"], 3);
+        assert_eq!(reports[0]["skipped"][0]["reason"], "no task or subject");
+    }
 
-        // Create export file
-        create_test_export(
-            &data_dir.join("export_test.xls"),
-            &[("compiti", "2025-01-15", "Matematica", "Task 1")],
-        );
+    /// Stage one entry and return the state with its pending import id
+    fn staged_state(entry: HomeworkEntry) -> (TempDir, Arc<AppState>, String) {
+        let (temp_dir, state) = test_state(vec![]);
+        let id = {
+            let conn = state.conn.lock().unwrap();
+            db::stage_imports(&conn, &[entry]).unwrap();
+            db::get_pending_imports(&conn).unwrap()[0].id.clone()
+        };
+        (temp_dir, state, id)
+    }
 
-        let db_path = data_dir.join("homework.db");
-        let conn = db::init_db(&db_path, &migrations_dir).unwrap();
-        let state = Arc::new(AppState::new(conn));
+    #[tokio::test]
+    async fn test_accept_import_handler() {
+        let (_temp_dir, state, id) = staged_state(make_entry(
+            "nota",
+            "2099-03-20",
+            "Storia",
+            "Verifica cap. 5",
+        ));
         let app = create_router(state.clone());
 
-        let response = with_temp_dir_async(&temp_dir, || async {
-            app.oneshot(
+        let response = app
+            .oneshot(
                 Request::builder()
-                    .uri("/api/refresh")
+                    .method("POST")
+                    .uri(format!("/api/imports/{}/accept", id))
                     .body(Body::empty())
                     .unwrap(),
             )
             .await
-            .unwrap()
-        })
-        .await;
+            .unwrap();
 
         assert_eq!(response.status(), StatusCode::OK);
+        let conn = state.conn.lock().unwrap();
+        assert!(db::get_pending_imports(&conn).unwrap().is_empty());
+        let entries = db::get_all_entries(&conn).unwrap();
+        assert!(entries.iter().any(|e| e.id == id));
+        // Accepting a test generates its study sessions
+        assert!(entries
+            .iter()
+            .any(|e| e.parent_id.as_deref() == Some(id.as_str())));
+    }
 
-        let body = body_to_string(response.into_body()).await;
-        assert_eq!(body, "OK");
+    #[tokio::test]
+    async fn test_reject_import_handler() {
+        let (_temp_dir, state, id) =
+            staged_state(make_entry("compiti", "2025-01-15", "Matematica", "Task 1"));
+        let app = create_router(state.clone());
 
-        // Verify database was updated
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri(format!("/api/imports/{}/reject", id))
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
         let conn = state.conn.lock().unwrap();
-        let db_entries = db::get_all_entries(&conn).unwrap();
-        assert_eq!(db_entries.len(), 1);
+        assert!(db::get_pending_imports(&conn).unwrap().is_empty());
+        assert_eq!(db::count_entries(&conn).unwrap(), 0);
     }
 
-    // ========== 404 tests ==========
-
     #[tokio::test]
-    async fn test_unknown_route_returns_404() {
+    async fn test_accept_import_not_found() {
         let (_temp_dir, state) = test_state(vec![]);
         let app = create_router(state);
 
         let response = app
             .oneshot(
                 Request::builder()
-                    .uri("/unknown/route")
+                    .method("POST")
+                    .uri("/api/imports/nonexistent/accept")
                     .body(Body::empty())
                     .unwrap(),
             )
@@ -1125,323 +7063,350 @@ mod tests {
         assert_eq!(response.status(), StatusCode::NOT_FOUND);
     }
 
-    // ========== Concurrent access tests ==========
-
-    #[test]
-    fn test_concurrent_db_access() {
-        let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
-        let (_temp_dir, state) = test_state(entries);
-
-        // Simulate multiple sequential reads (Mutex doesn't allow true concurrent access)
-        for _ in 0..10 {
+    #[tokio::test]
+    async fn test_accept_all_imports_handler() {
+        let (_temp_dir, state) = test_state(vec![]);
+        {
             let conn = state.conn.lock().unwrap();
-            let count = db::count_entries(&conn).unwrap();
-            assert_eq!(count, 1);
+            db::stage_imports(
+                &conn,
+                &[
+                    make_entry("compiti", "2025-01-15", "Matematica", "Task 1"),
+                    make_entry("nota", "2025-01-16", "Italiano", "Portare il libro"),
+                ],
+            )
+            .unwrap();
         }
-    }
-
-    // ========== is_export_file tests ==========
-
-    #[test]
-    fn test_is_export_file_valid() {
-        assert!(is_export_file(Path::new("export_2025.xls")));
-        assert!(is_export_file(Path::new("export_homework.xls")));
-        assert!(is_export_file(Path::new("export_.xls")));
-        assert!(is_export_file(Path::new("/path/to/export_data.xls")));
-        assert!(is_export_file(Path::new("data/export_test.xls")));
-    }
-
-    #[test]
-    fn test_is_export_file_xlsx() {
-        // .xlsx files should also match (contains ".xls")
-        assert!(is_export_file(Path::new("export_2025.xlsx")));
-    }
-
-    #[test]
-    fn test_is_export_file_invalid_prefix() {
-        assert!(!is_export_file(Path::new("homework.xls")));
-        assert!(!is_export_file(Path::new("data.xls")));
-        assert!(!is_export_file(Path::new("xport_file.xls")));
-        assert!(!is_export_file(Path::new("Export_file.xls"))); // Case sensitive
-    }
-
-    #[test]
-    fn test_is_export_file_invalid_extension() {
-        assert!(!is_export_file(Path::new("export_data.txt")));
-        assert!(!is_export_file(Path::new("export_data.csv")));
-        assert!(!is_export_file(Path::new("export_data.json")));
-        assert!(!is_export_file(Path::new("export_data")));
-    }
-
-    #[test]
-    fn test_is_export_file_edge_cases() {
-        assert!(!is_export_file(Path::new("")));
-        assert!(!is_export_file(Path::new("/")));
-        assert!(!is_export_file(Path::new("/path/to/")));
-        assert!(!is_export_file(Path::new(".")));
-        assert!(!is_export_file(Path::new("..")));
-    }
-
-    // ========== ensure_data_dir tests ==========
-
-    #[test]
-    fn test_ensure_data_dir_creates_new() {
-        let temp_dir = tempfile::TempDir::new().unwrap();
-        let data_dir = temp_dir.path().join("new_data_dir");
-
-        assert!(!data_dir.exists());
-        let created = ensure_data_dir(&data_dir).unwrap();
-        assert!(created);
-        assert!(data_dir.exists());
-    }
-
-    #[test]
-    fn test_ensure_data_dir_already_exists() {
-        let temp_dir = tempfile::TempDir::new().unwrap();
-        let data_dir = temp_dir.path().join("existing_dir");
-        std::fs::create_dir(&data_dir).unwrap();
-
-        assert!(data_dir.exists());
-        let created = ensure_data_dir(&data_dir).unwrap();
-        assert!(!created);
-        assert!(data_dir.exists());
-    }
-
-    #[test]
-    fn test_ensure_data_dir_nested() {
-        let temp_dir = tempfile::TempDir::new().unwrap();
-        let data_dir = temp_dir.path().join("a").join("b").join("c");
-
-        assert!(!data_dir.exists());
-        let created = ensure_data_dir(&data_dir).unwrap();
-        assert!(created);
-        assert!(data_dir.exists());
-    }
-
-    // ========== create_server_addr tests ==========
-
-    #[test]
-    fn test_create_server_addr() {
-        let addr = create_server_addr(8080);
-        assert_eq!(addr.port(), 8080);
-        assert_eq!(addr.ip().to_string(), "127.0.0.1");
-    }
-
-    #[test]
-    fn test_create_server_addr_different_ports() {
-        assert_eq!(create_server_addr(3000).port(), 3000);
-        assert_eq!(create_server_addr(0).port(), 0);
-        assert_eq!(create_server_addr(65535).port(), 65535);
-    }
-
-    // ========== init_server_state tests ==========
-    // Note: init_server_state requires CARGO_MANIFEST_DIR to find migrations,
-    // so we test the components separately rather than the full function.
-
-    // ========== RefreshResult tests ==========
+        let app = create_router(state.clone());
 
-    #[test]
-    fn test_refresh_result_updated() {
-        let result = RefreshResult::Updated {
-            old_count: 5,
-            new_count: 10,
-        };
-        assert_eq!(
-            result,
-            RefreshResult::Updated {
-                old_count: 5,
-                new_count: 10
-            }
-        );
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/imports/accept-all")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
 
-        // Just ensure log doesn't panic
-        result.log();
-    }
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        let result: AcceptAllResponse = serde_json::from_str(&body).unwrap();
+        assert_eq!(result.accepted, 2);
 
-    #[test]
-    fn test_refresh_result_no_change() {
-        let result = RefreshResult::NoChange { count: 5 };
-        assert_eq!(result, RefreshResult::NoChange { count: 5 });
-        result.log();
+        let conn = state.conn.lock().unwrap();
+        assert_eq!(db::count_pending_imports(&conn).unwrap(), 0);
+        assert_eq!(db::count_entries(&conn).unwrap(), 2);
     }
 
-    #[test]
-    fn test_refresh_result_error() {
-        let result = RefreshResult::Error("test error".to_string());
-        assert_eq!(result, RefreshResult::Error("test error".to_string()));
-        result.log();
-    }
+    #[tokio::test]
+    async fn test_accept_all_links_cross_listed_before_generating() {
+        let date = (chrono::Local::now().date_naive() + chrono::Duration::days(10)).to_string();
+        let history = make_entry("verifica", &date, "Storia", "Verifica sul Rinascimento");
+        let art = make_entry("verifica", &date, "Arte", "Verifica sul Rinascimento");
+        let (_temp_dir, state) = test_state(vec![]);
+        db::stage_imports(&state.conn.lock().unwrap(), &[history, art]).unwrap();
 
-    #[test]
-    fn test_refresh_result_debug() {
-        let result = RefreshResult::Updated {
-            old_count: 1,
-            new_count: 2,
-        };
-        let debug_str = format!("{:?}", result);
-        assert!(debug_str.contains("Updated"));
-    }
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("POST")
+                    .uri("/api/imports/accept-all")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
 
-    #[test]
-    fn test_refresh_result_clone() {
-        let result = RefreshResult::Updated {
-            old_count: 1,
-            new_count: 2,
-        };
-        let cloned = result.clone();
-        assert_eq!(result, cloned);
+        // One task, so one set of study sessions: the primary's
+        let conn = state.conn.lock().unwrap();
+        let entries = db::get_all_entries(&conn).unwrap();
+        let (_, primaries) = db::get_cross_listings(&conn).unwrap();
+        assert_eq!(primaries.len(), 1);
+        let primary = primaries.values().next().unwrap();
+        let parents: HashSet<&str> = entries
+            .iter()
+            .filter_map(|e| e.parent_id.as_deref())
+            .collect();
+        assert_eq!(parents, HashSet::from([primary.as_str()]));
     }
 
-    // ========== process_refresh tests ==========
+    #[tokio::test]
+    async fn test_imports_page_handler() {
+        let (_temp_dir, state, _id) =
+            staged_state(make_entry("compiti", "2025-01-15", "Matematica", "Task 1"));
+        let app = create_router(state);
 
-    #[test]
-    fn test_process_refresh_with_new_entries() {
-        let temp_dir = TempDir::new().unwrap();
-        let data_dir = temp_dir.path().join("data");
-        std::fs::create_dir(&data_dir).unwrap();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/imports")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
 
-        // Setup migrations
-        let migrations_dir = temp_dir.path().join("migrations");
-        std::fs::create_dir(&migrations_dir).unwrap();
-        std::fs::write(
-            migrations_dir.join("001_initial_schema.sql"),
-            include_str!("../db/migrations/001_initial_schema.sql"),
-        )
-        .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        assert!(body.contains("Task 1"));
+        assert!(body.contains("Accept all (1)"));
+    }
 
-        // Create database with no entries
-        let db_path = data_dir.join("homework.db");
-        let conn = db::init_db(&db_path, &migrations_dir).unwrap();
-        let state = AppState::new(conn);
+    #[tokio::test]
+    async fn test_review_imports_setting_roundtrip() {
+        let (_temp_dir, state) = test_state(vec![]);
+        let app = create_router(state.clone());
 
-        // Create export file with one entry
-        create_test_export(
-            &data_dir.join("export_test.xls"),
-            &[("compiti", "2025-01-15", "Matematica", "Task 1")],
-        );
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/api/settings/review-imports")
+                    .header("content-type", "application/json")
+                    .body(Body::from(r#"{"enabled":false}"#))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
 
-        let _lock = DIR_LOCK.lock().unwrap();
-        let original_dir = std::env::current_dir().unwrap();
-        std::env::set_current_dir(temp_dir.path()).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let conn = state.conn.lock().unwrap();
+        assert!(!db::get_review_imports(&conn).unwrap());
+    }
 
-        let result = process_refresh(&state);
+    #[tokio::test]
+    async fn test_get_settings_returns_defaults() {
+        let (_temp_dir, state) = test_state(vec![]);
+        let app = create_router(state);
 
-        std::env::set_current_dir(original_dir).unwrap();
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/api/settings")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
 
-        match result {
-            RefreshResult::Updated {
-                old_count,
-                new_count,
-            } => {
-                assert_eq!(old_count, 0);
-                assert_eq!(new_count, 1);
-            }
-            _ => panic!("Expected Updated result, got {:?}", result),
-        }
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        let settings: Settings = serde_json::from_str(&body).unwrap();
+        assert_eq!(settings, Settings::default());
     }
 
-    #[test]
-    fn test_process_refresh_no_change() {
-        let temp_dir = TempDir::new().unwrap();
-        let data_dir = temp_dir.path().join("data");
-        std::fs::create_dir(&data_dir).unwrap();
-
-        let migrations_dir = temp_dir.path().join("migrations");
-        std::fs::create_dir(&migrations_dir).unwrap();
-        std::fs::write(
-            migrations_dir.join("001_initial_schema.sql"),
-            include_str!("../db/migrations/001_initial_schema.sql"),
-        )
-        .unwrap();
-
-        // Create export file
-        create_test_export(
-            &data_dir.join("export_test.xls"),
-            &[("compiti", "2025-01-15", "Matematica", "Task 1")],
-        );
+    #[tokio::test]
+    async fn test_put_settings_partial_update() {
+        let (_temp_dir, state) = test_state(vec![]);
 
-        // Create database with same entries already imported
-        let db_path = data_dir.join("homework.db");
-        let conn = db::init_db(&db_path, &migrations_dir).unwrap();
-        let entry = make_entry("compiti", "2025-01-15", "Matematica", "Task 1");
-        db::insert_entry(&conn, &entry).unwrap();
-        let state = AppState::new(conn);
+        let response = create_router(state.clone())
+            .oneshot(
+                Request::builder()
+                    .method("PUT")
+                    .uri("/api/settings")
+                    .header("content-type", "application/json")
+                    .body(Body::from(
+                        r#"{"theme":"light","default_view":"calendar","study_days_before":1}"#,
+                    ))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
 
-        let _lock = DIR_LOCK.lock().unwrap();
-        let original_dir = std::env::current_dir().unwrap();
-        std::env::set_current_dir(temp_dir.path()).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = body_to_string(response.into_body()).await;
+        let settings: Settings = serde_json::from_str(&body).unwrap();
+        assert_eq!(settings.theme, Theme::Light);
+        assert_eq!(settings.default_view, View::Calendar);
+        // Clamped to the minimum, like the per-key endpoint
+        assert_eq!(settings.study_days_before, 3);
+        // Untouched fields keep their values
+        assert_eq!(settings.work_days, vec![1, 2, 3, 4, 5]);
 
-        let result = process_refresh(&state);
+        let conn = state.conn.lock().unwrap();
+        assert_eq!(db::get_settings(&conn).unwrap(), settings);
+    }
 
-        std::env::set_current_dir(original_dir).unwrap();
+    #[tokio::test]
+    async fn test_put_settings_rejects_invalid_values() {
+        let (_temp_dir, state) = test_state(vec![]);
 
-        match result {
-            RefreshResult::NoChange { count } => {
-                assert_eq!(count, 1);
-            }
-            _ => panic!("Expected NoChange result, got {:?}", result),
+        for body in [
+            r#"{"theme":"neon"}"#,
+            r#"{"no_such_setting":true}"#,
+            r#"{"cors_origins":["app.example.org"]}"#,
+        ] {
+            let response = create_router(state.clone())
+                .oneshot(
+                    Request::builder()
+                        .method("PUT")
+                        .uri("/api/settings")
+                        .header("content-type", "application/json")
+                        .body(Body::from(body))
+                        .unwrap(),
+                )
+                .await
+                .unwrap();
+            assert!(response.status().is_client_error(), "{} accepted", body);
         }
     }
 
-    #[test]
-    fn test_process_refresh_error() {
-        let temp_dir = TempDir::new().unwrap();
-        let data_dir = temp_dir.path().join("data");
-        std::fs::create_dir(&data_dir).unwrap();
-        // No export files - will cause error
-
-        let migrations_dir = temp_dir.path().join("migrations");
-        std::fs::create_dir(&migrations_dir).unwrap();
-        std::fs::write(
-            migrations_dir.join("001_initial_schema.sql"),
-            include_str!("../db/migrations/001_initial_schema.sql"),
-        )
-        .unwrap();
+    #[tokio::test]
+    async fn test_cors() {
+        let (_temp_dir, state) = test_state(vec![]);
+        {
+            let conn = state.conn.lock().unwrap();
+            db::set_cors_origins(&conn, &["https://app.example.org".to_string()]).unwrap();
+        }
+        let request = |method: &str, origin: &str| {
+            create_router(state.clone()).oneshot(
+                Request::builder()
+                    .method(method)
+                    .uri("/api/entries")
+                    .header("origin", origin)
+                    .header("access-control-request-method", "POST")
+                    .header("access-control-request-headers", "content-type")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+        };
 
-        let db_path = data_dir.join("homework.db");
-        let conn = db::init_db(&db_path, &migrations_dir).unwrap();
-        let state = AppState::new(conn);
+        let response = request("OPTIONS", "https://app.example.org").await.unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        let headers = response.headers();
+        assert_eq!(
+            headers["access-control-allow-origin"],
+            "https://app.example.org"
+        );
+        assert_eq!(headers["access-control-allow-credentials"], "true");
+        assert!(headers["access-control-allow-methods"]
+            .to_str()
+            .unwrap()
+            .contains("POST"));
+        assert_eq!(headers["access-control-allow-headers"], "content-type");
 
-        let _lock = DIR_LOCK.lock().unwrap();
-        let original_dir = std::env::current_dir().unwrap();
-        std::env::set_current_dir(temp_dir.path()).unwrap();
+        let response = request("GET", "https://app.example.org").await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(
+            response.headers()["access-control-allow-origin"],
+            "https://app.example.org"
+        );
 
-        let result = process_refresh(&state);
+        // Anyone else gets no CORS headers, so the browser keeps the answer
+        for method in ["OPTIONS", "GET"] {
+            let response = request(method, "https://evil.example.com").await.unwrap();
+            assert!(!response
+                .headers()
+                .contains_key("access-control-allow-origin"));
+            assert_eq!(response.headers()["vary"], "origin");
+        }
 
-        std::env::set_current_dir(original_dir).unwrap();
+        // Preflights get through read-only mode, offering only reads
+        let (_read_only_dir, conn) = setup_test_db(&[]);
+        db::set_cors_origins(&conn, &["*".to_string()]).unwrap();
+        let read_only = Arc::new(AppState {
+            read_only: true,
+            ..AppState::new(conn)
+        });
+        let response = create_router(read_only)
+            .oneshot(
+                Request::builder()
+                    .method("OPTIONS")
+                    .uri("/api/entries")
+                    .header("origin", "https://app.example.org")
+                    .header("access-control-request-method", "GET")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()["access-control-allow-origin"], "*");
+        assert_eq!(
+            response.headers()["access-control-allow-methods"],
+            "GET, HEAD"
+        );
 
-        match result {
-            RefreshResult::Error(msg) => {
-                assert!(!msg.is_empty());
-            }
-            _ => panic!("Expected Error result, got {:?}", result),
-        }
+        // A handler's own Vary is kept alongside Origin
+        let app = Router::new()
+            .route(
+                "/varies",
+                get(|| async { ([(header::VARY, "accept-encoding")], "ok") }),
+            )
+            .layer(middleware::from_fn_with_state(state.clone(), cors_guard));
+        let response = app
+            .oneshot(
+                Request::builder()
+                    .uri("/varies")
+                    .header("origin", "https://app.example.org")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        let vary: Vec<_> = response.headers().get_all("vary").iter().collect();
+        assert_eq!(vary, ["accept-encoding", "origin"]);
     }
 
-    // ========== New API endpoint tests ==========
-
     #[tokio::test]
-    async fn test_get_entry_handler() {
-        let entries = vec![make_entry("compiti", "2025-01-15", "Matematica", "Task 1")];
-        let entry_id = entries[0].id.clone();
-        let (_temp_dir, state) = test_state(entries);
-        let app = create_router(state);
+    async fn test_study_sessions_setting_disables_generation() {
+        let (_temp_dir, state) = test_state(vec![]);
+        {
+            let conn = state.conn.lock().unwrap();
+            let mut settings = db::get_settings(&conn).unwrap();
+            settings.study_sessions = false;
+            db::save_settings(&conn, &settings).unwrap();
+        }
 
-        let response = app
+        let date = (chrono::Local::now().date_naive() + chrono::Duration::days(14))
+            .format("%Y-%m-%d")
+            .to_string();
+        let response = create_router(state.clone())
             .oneshot(
                 Request::builder()
-                    .uri(format!("/api/entries/{}", entry_id))
-                    .body(Body::empty())
+                    .method("POST")
+                    .uri("/api/entries")
+                    .header("content-type", "application/json")
+                    .body(Body::from(format!(
+                        r#"{{"entry_type":"verifica","date":"{}","subject":"Storia","task":"Cap. 5"}}"#,
+                        date
+                    )))
                     .unwrap(),
             )
             .await
             .unwrap();
 
-        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let conn = state.conn.lock().unwrap();
+        let entries = db::get_all_entries(&conn).unwrap();
+        assert!(entries.iter().all(|e| e.entry_type != "studio"));
+    }
+
+    #[tokio::test]
+    async fn test_list_page_handler_applies_display_settings() {
+        let (_temp_dir, state) = test_state(vec![]);
+        {
+            let conn = state.conn.lock().unwrap();
+            let mut settings = db::get_settings(&conn).unwrap();
+            settings.theme = Theme::Light;
+            settings.language = Language::It;
+            settings.default_view = View::Calendar;
+            settings.hide_completed = true;
+            db::save_settings(&conn, &settings).unwrap();
+        }
+
+        let response = create_router(state)
+            .oneshot(Request::builder().uri("/list").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
 
         let body = body_to_string(response.into_body()).await;
-        let parsed: HomeworkEntry = serde_json::from_str(&body).unwrap();
-        assert_eq!(parsed.id, entry_id);
+        assert!(body.contains(r#"<html lang="it""#));
+        assert!(body.contains(r#"class="theme-light hide-completed""#));
+        assert!(body.contains(r#"data-default-view="calendar""#));
     }
 
     #[tokio::test]
@@ -0,0 +1,55 @@
+//! "All done" days in a row, for the calendar on `/stats`.
+//!
+//! A day is all done while every entry on it is ticked off, worked out from
+//! the entries when read (`db::get_day_outcomes`). Only days with entries count towards a
+//! streak: a weekend with nothing due neither extends nor breaks it. Today
+//! doesn't break it while something is still pending, since the day isn't
+//! over; days after today are left out.
+
+use chrono::NaiveDate;
+
+/// `(current, best)` streaks, from every day with entries (oldest first)
+/// and whether it was all done
+pub fn streaks(days: &[(NaiveDate, bool)], today: NaiveDate) -> (usize, usize) {
+    let (mut run, mut best) = (0, 0);
+    for &(day, done) in days.iter().filter(|(day, _)| *day <= today) {
+        if done {
+            run += 1;
+            best = best.max(run);
+        } else if day < today {
+            run = 0;
+        }
+    }
+    (run, best)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(d: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, d).unwrap()
+    }
+
+    #[test]
+    fn test_streaks() {
+        let days = [
+            (day(6), true),
+            (day(7), true),
+            (day(8), true),
+            (day(9), false),
+            // Nothing was due from the 10th to the 12th
+            (day(13), true),
+            (day(14), true),
+            (day(15), false),
+            (day(16), true),
+        ];
+        // Today (the 15th) is still open; the 16th, done early, doesn't
+        // count yet
+        assert_eq!(streaks(&days, day(15)), (2, 3));
+        // Once it's over, an open day breaks the streak
+        assert_eq!(streaks(&days[..7], day(16)), (0, 3));
+        assert_eq!(streaks(&days, day(17)), (1, 3));
+        assert_eq!(streaks(&[], day(15)), (0, 0));
+    }
+}
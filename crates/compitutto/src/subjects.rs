@@ -0,0 +1,847 @@
+//! Subject detection for entries whose export row has no subject.
+//!
+//! Detection runs through a `SubjectPipeline`: an ordered list of
+//! `SubjectDetector`s where the first one to return a subject wins.
+//!
+//! - `RuleDetector` — regex rules from `data/subjects.json` (checked first)
+//! - `KeywordDetector` — the built-in Italian/English keyword heuristic
+//!
+//! Rules file format:
+//!
+//! ```json
+//! { "rules": [
+//!     { "pattern": "(?i)\\b(latino|versione)\\b", "subject": "Latino" },
+//!     { "pattern": "(?i)\\bgreco\\b", "subject": "Greco" }
+//! ] }
+//! ```
+//!
+//! Other detectors can be added with `SubjectPipeline::push` without touching
+//! the parser.
+//!
+//! The same file holds `aliases`, which map a subject name from the export to
+//! the one used here. `compitutto subjects merge` and `/api/subjects/merge`
+//! add them, so a merged name stays merged on the next import:
+//!
+//! ```json
+//! { "aliases": { "Inglese": "Lingua Inglese" } }
+//! ```
+//!
+//! And a `timetable` of each weekday's subjects, for spotting entries dated
+//! on a day without a lesson (see `timetable`).
+//!
+//! Topics are one level below subjects (Matematica → Geometria). Teachers
+//! often start the note with one, as in "Geometria: area del cerchio";
+//! [`detect_topic`] picks that prefix up.
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use rusqlite::Connection;
+use serde::Deserialize;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+use tracing::{debug, info};
+
+use crate::db;
+use crate::timetable::Timetable;
+use crate::types::{MergeSuggestion, SubjectCount, SubjectIcon, SubjectMerge};
+
+/// Default location of the rules file, relative to the working directory.
+pub const RULES_FILE: &str = "data/subjects.json";
+
+/// Something that can guess a subject from task text.
+pub trait SubjectDetector: Send + Sync {
+    /// Short name for logs
+    fn name(&self) -> &str;
+
+    /// Return the canonical subject name, or `None` to defer to the next detector
+    fn detect(&self, task: &str) -> Option<String>;
+}
+
+/// Detectors tried in order; the first match wins.
+pub struct SubjectPipeline {
+    detectors: Vec<Box<dyn SubjectDetector>>,
+    /// Lowercased export name -> name used here
+    aliases: HashMap<String, String>,
+    timetable: Timetable,
+}
+
+impl SubjectPipeline {
+    /// An empty pipeline
+    pub fn new() -> Self {
+        Self {
+            detectors: Vec::new(),
+            aliases: HashMap::new(),
+            timetable: Timetable::default(),
+        }
+    }
+
+    /// Built-in rules plus any regex rules and aliases from `rules_path`
+    /// (rules take precedence over the keywords). A missing rules file is
+    /// fine.
+    pub fn load(rules_path: &Path) -> Result<Self> {
+        let mut pipeline = Self::new();
+        if rules_path.exists() {
+            let file = read_rules_file(rules_path)?;
+            let rules = RuleDetector::from_rules(&file.rules)?;
+            info!(
+                count = rules.len(),
+                aliases = file.aliases.len(),
+                path = %rules_path.display(),
+                "Loaded subject rules"
+            );
+            pipeline.push(rules);
+            pipeline.aliases = file
+                .aliases
+                .into_iter()
+                .map(|(from, into)| (from.trim().to_lowercase(), into.trim().to_string()))
+                .collect();
+            pipeline.timetable = Timetable::from_days(&file.timetable);
+        }
+        pipeline.push(KeywordDetector);
+        debug!(detectors = ?pipeline.names(), "Subject detection pipeline");
+        Ok(pipeline)
+    }
+
+    /// Append a detector; it runs after the ones already added.
+    pub fn push(&mut self, detector: impl SubjectDetector + 'static) -> &mut Self {
+        self.detectors.push(Box::new(detector));
+        self
+    }
+
+    /// Detector names in order of precedence
+    pub fn names(&self) -> Vec<&str> {
+        self.detectors.iter().map(|d| d.name()).collect()
+    }
+
+    pub fn detect(&self, task: &str) -> Option<String> {
+        self.detectors.iter().find_map(|d| d.detect(task))
+    }
+
+    /// The weekly timetable, empty when the rules file has none
+    pub fn timetable(&self) -> &Timetable {
+        &self.timetable
+    }
+
+    /// `subject`, or what an alias maps it to
+    pub fn canonical(&self, subject: &str) -> String {
+        self.aliases
+            .get(&subject.trim().to_lowercase())
+            .cloned()
+            .unwrap_or_else(|| subject.to_string())
+    }
+}
+
+impl Default for SubjectPipeline {
+    /// Built-in keyword heuristic only
+    fn default() -> Self {
+        let mut pipeline = Self::new();
+        pipeline.push(KeywordDetector);
+        pipeline
+    }
+}
+
+// ========== Topics ==========
+
+/// Longest prefix taken as a topic, in characters
+const MAX_TOPIC_LEN: usize = 30;
+
+/// Words found before a colon that don't name a topic ("Esercizi: pag. 10"),
+/// alone or as the first word ("Verifica di geometria:", "Per domani:")
+const NOT_TOPICS: &[&str] = &[
+    "argomento",
+    "argomenti",
+    "attenzione",
+    "compiti",
+    "compito",
+    "consegna",
+    "da",
+    "domani",
+    "esercizi",
+    "esercizio",
+    "importante",
+    "interrogazione",
+    "leggere",
+    "libro",
+    "nota",
+    "note",
+    "pag",
+    "pagina",
+    "pagine",
+    "per",
+    "promemoria",
+    "prova",
+    "ricorda",
+    "ricordarsi",
+    "ripassare",
+    "ripasso",
+    "studiare",
+    "svolgere",
+    "test",
+    "verifica",
+];
+
+/// The topic `task` starts with, as in "Geometria: area del cerchio": up to
+/// three capitalised words before a colon, not starting with a generic word
+/// like "Esercizi" and not the subject itself.
+pub fn detect_topic(task: &str, subject: &str) -> Option<String> {
+    let (head, rest) = task.split_once(':')?;
+    let head = head.trim();
+    if rest.trim().is_empty()
+        || head.chars().count() > MAX_TOPIC_LEN
+        || head.split_whitespace().count() > 3
+        || !head.chars().next()?.is_uppercase()
+        || !head
+            .chars()
+            .all(|c| c.is_alphabetic() || c == ' ' || c == '\'' || c == '-')
+    {
+        return None;
+    }
+    let lower = head.to_lowercase();
+    let first = lower.split_whitespace().next()?;
+    if NOT_TOPICS.contains(&first) || lower == subject.trim().to_lowercase() {
+        return None;
+    }
+    Some(head.to_string())
+}
+
+// ========== Keyword heuristic ==========
+
+/// Known subjects that can be extracted from task text, keyword -> canonical
+/// name. Order matters: longer/more specific keywords come first.
+const KNOWN_SUBJECTS: &[(&str, &str)] = &[
+    // Italian subject names -> canonical form (title case)
+    ("matematica", "Matematica"),
+    ("aritmetica", "Matematica"),
+    ("geometria", "Matematica"),
+    ("italiano", "Italiano"),
+    ("antologia", "Italiano"),
+    ("storia", "Storia"),
+    ("geografia", "Geografia"),
+    ("inglese", "Lingua Inglese"),
+    ("english", "Lingua Inglese"),
+    ("verbi irregolari", "Lingua Inglese"), // English irregular verbs
+    ("tedesco", "Tedesco"),
+    ("deutsch", "Tedesco"),
+    ("arte", "Arte e Immagine"),
+    ("disegno", "Arte e Immagine"),
+    ("tecnologia", "Tecnologia"),
+    ("proiezioni ortogonali", "Tecnologia"),
+    ("scienze", "Scienze"),
+    ("lavoisier", "Scienze"), // Lavoisier's law = chemistry/science
+    ("musica", "Musica"),
+    ("ed. fisica", "Educazione Fisica"),
+    ("educazione fisica", "Educazione Fisica"),
+    ("religione", "Religione"),
+    ("ed. civica", "Educazione Civica"),
+    ("educazione civica", "Educazione Civica"),
+    // English names -> same canonical form
+    ("mathematics", "Matematica"),
+    ("maths", "Matematica"),
+    ("geometry", "Matematica"),
+    ("history", "Storia"),
+    ("geography", "Geografia"),
+    ("german", "Tedesco"),
+    ("technology", "Tecnologia"),
+    ("science", "Scienze"),
+    ("music", "Musica"),
+    ("physical education", "Educazione Fisica"),
+    ("civics", "Educazione Civica"),
+];
+
+/// "verifica di SUBJECT"-style prefixes
+const TEST_PREFIXES: &[&str] = &[
+    "verifica di ",
+    "verifica su ",
+    "test di ",
+    "test su ",
+    "interrogazione di ",
+    "interrogazione su ",
+    "prova di ",
+    "prova su ",
+    "esame di ",
+    "esame su ",
+    "test on ",
+    "quiz on ",
+    "exam on ",
+];
+
+/// Words suggesting a keyword found mid-text really is the subject
+const CONTEXT_WORDS: &[&str] = &[
+    "verifica",
+    "test",
+    "interrogazione",
+    "prova",
+    "portare",
+    "libro di",
+    "quaderno",
+    "scritto",
+    "in inglese",
+    "attività",
+    "quiz",
+    "exam",
+    "homework",
+];
+
+/// The built-in heuristic over `KNOWN_SUBJECTS`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct KeywordDetector;
+
+impl SubjectDetector for KeywordDetector {
+    fn name(&self) -> &str {
+        "keywords"
+    }
+
+    fn detect(&self, task: &str) -> Option<String> {
+        let task_lower = task.to_lowercase();
+
+        // Pattern 1: "verifica/test/interrogazione di/su SUBJECT"
+        // e.g., "Verifica di matematica", "test di storia"
+        for prefix in TEST_PREFIXES {
+            if let Some(pos) = task_lower.find(prefix) {
+                let after_prefix = &task_lower[pos + prefix.len()..];
+                // Look for a known subject in what follows
+                for (keyword, canonical) in KNOWN_SUBJECTS {
+                    if after_prefix.starts_with(keyword) {
+                        return Some(canonical.to_string());
+                    }
+                }
+            }
+        }
+
+        // Pattern 2: Check if task starts with a subject name followed by colon
+        // e.g., "Geometria: pag. 293..."
+        for (keyword, canonical) in KNOWN_SUBJECTS {
+            if let Some(after) = task_lower.strip_prefix(keyword) {
+                // Check if followed by colon or space
+                if after.starts_with(':') || after.starts_with(' ') {
+                    return Some(canonical.to_string());
+                }
+            }
+        }
+
+        // Pattern 3: Look for subject keywords anywhere in the text
+        // but only if they appear in a context suggesting it's the subject
+        // e.g., "verifica ed. civica" or "portare libro di storia"
+        let test_context = CONTEXT_WORDS.iter().any(|w| task_lower.contains(w));
+        if test_context {
+            for (keyword, canonical) in KNOWN_SUBJECTS {
+                if task_lower.contains(keyword) {
+                    return Some(canonical.to_string());
+                }
+            }
+        }
+
+        None
+    }
+}
+
+// ========== Regex rules ==========
+
+#[derive(Debug, Deserialize)]
+pub struct RulesFile {
+    #[serde(default)]
+    rules: Vec<RawRule>,
+    #[serde(default)]
+    aliases: BTreeMap<String, String>,
+    /// Weekday -> subjects with a lesson that day
+    #[serde(default)]
+    pub timetable: BTreeMap<String, Vec<String>>,
+}
+
+pub fn read_rules_file(path: &Path) -> Result<RulesFile> {
+    let text = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read subject rules: {}", path.display()))?;
+    serde_json::from_str(&text)
+        .with_context(|| format!("Invalid subject rules file: {}", path.display()))
+}
+
+#[derive(Debug, Deserialize)]
+struct RawRule {
+    pattern: String,
+    subject: String,
+}
+
+/// Regex rules from config, tried in file order.
+#[derive(Debug, Clone)]
+pub struct RuleDetector {
+    rules: Vec<(Regex, String)>,
+}
+
+impl RuleDetector {
+    /// Build from `(pattern, subject)` pairs.
+    pub fn new<'a>(rules: impl IntoIterator<Item = (&'a str, &'a str)>) -> Result<Self> {
+        let rules = rules
+            .into_iter()
+            .map(|(pattern, subject)| {
+                Regex::new(pattern)
+                    .with_context(|| format!("Invalid subject rule pattern: {}", pattern))
+                    .map(|re| (re, subject.to_string()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        Ok(Self { rules })
+    }
+
+    fn from_rules(rules: &[RawRule]) -> Result<Self> {
+        Self::new(
+            rules
+                .iter()
+                .map(|r| (r.pattern.as_str(), r.subject.as_str())),
+        )
+    }
+
+    pub fn len(&self) -> usize {
+        self.rules.len()
+    }
+}
+
+impl SubjectDetector for RuleDetector {
+    fn name(&self) -> &str {
+        "rules"
+    }
+
+    fn detect(&self, task: &str) -> Option<String> {
+        self.rules
+            .iter()
+            .find(|(re, _)| re.is_match(task))
+            .map(|(_, subject)| subject.clone())
+    }
+}
+
+// ========== Merging ==========
+
+/// Record in the rules file that subject `from` is `into`. Aliases that
+/// pointed at `from` now point at `into`, and an alias away from `into` is
+/// dropped, so aliases never chain or loop. Everything else in the file is
+/// kept.
+pub fn add_alias(rules_path: &Path, from: &str, into: &str) -> Result<()> {
+    let mut file: serde_json::Value = if rules_path.exists() {
+        let text = std::fs::read_to_string(rules_path)
+            .with_context(|| format!("Failed to read subject rules: {}", rules_path.display()))?;
+        serde_json::from_str(&text)
+            .with_context(|| format!("Invalid subject rules file: {}", rules_path.display()))?
+    } else {
+        serde_json::json!({})
+    };
+    let root = file
+        .as_object_mut()
+        .with_context(|| format!("Invalid subject rules file: {}", rules_path.display()))?;
+    let aliases = root
+        .entry("aliases")
+        .or_insert_with(|| serde_json::json!({}))
+        .as_object_mut()
+        .context("\"aliases\" in the subject rules file must be an object")?;
+
+    aliases.retain(|key, _| !key.trim().eq_ignore_ascii_case(into));
+    for target in aliases.values_mut() {
+        if target
+            .as_str()
+            .is_some_and(|t| t.trim().eq_ignore_ascii_case(from))
+        {
+            *target = into.into();
+        }
+    }
+    aliases.retain(|key, _| !key.trim().eq_ignore_ascii_case(from));
+    aliases.insert(from.to_string(), into.into());
+
+    if let Some(dir) = rules_path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(rules_path, serde_json::to_string_pretty(&file)? + "\n")
+        .with_context(|| format!("Failed to write subject rules: {}", rules_path.display()))
+}
+
+/// Merge subject `from` into `into`: rewrite the database (see
+/// `db::merge_subjects`) and add the alias to `rules_path` for future imports.
+pub fn merge(conn: &Connection, rules_path: &Path, from: &str, into: &str) -> Result<SubjectMerge> {
+    let (from, into) = (from.trim(), into.trim());
+    anyhow::ensure!(
+        !from.is_empty() && !into.is_empty(),
+        "Both subjects are required"
+    );
+    anyhow::ensure!(from != into, "\"{}\" is already called that", from);
+
+    let merge = db::merge_subjects(conn, from, into)?;
+    // Only once the database has it, so a failed merge leaves no alias
+    // behind; a case-only rename needs none, imports are title-cased anyway
+    if !from.eq_ignore_ascii_case(into) {
+        add_alias(rules_path, from, into).context("Merged the entries, but not the alias")?;
+    }
+    info!(
+        from = %merge.from,
+        into = %merge.into,
+        entries = merge.entries,
+        pending = merge.pending,
+        "Merged subjects"
+    );
+    Ok(merge)
+}
+
+/// Lowercased words of a subject name, with "lingua" and the like left out
+fn subject_words(subject: &str) -> HashSet<String> {
+    const FILLER: &[&str] = &["lingua", "e", "di", "ed.", "ed"];
+    subject
+        .split_whitespace()
+        .map(str::to_lowercase)
+        .filter(|w| !FILLER.contains(&w.as_str()))
+        .collect()
+}
+
+/// Pairs of subjects that are probably the same: one's words are all in the
+/// other ("Inglese" / "Lingua Inglese" / "INGLESE POTENZIATO"). Each pair
+/// suggests merging the less used name into the more used one.
+pub fn suggest_merges(counts: &[SubjectCount]) -> Vec<MergeSuggestion> {
+    let mut suggestions = Vec::new();
+    for (i, a) in counts.iter().enumerate() {
+        for b in &counts[i + 1..] {
+            let (words_a, words_b) = (subject_words(&a.subject), subject_words(&b.subject));
+            if words_a.is_empty() || words_b.is_empty() {
+                continue;
+            }
+            if !(words_a.is_subset(&words_b) || words_b.is_subset(&words_a)) {
+                continue;
+            }
+            let (from, into) = if a.entries < b.entries {
+                (a, b)
+            } else {
+                (b, a)
+            };
+            suggestions.push(MergeSuggestion {
+                from: from.subject.clone(),
+                into: into.subject.clone(),
+            });
+        }
+    }
+    suggestions
+}
+
+// ========== Icons ==========
+
+/// Built-in icons by canonical subject name. Subjects not listed here (and
+/// not named after one by a `KNOWN_SUBJECTS` keyword) have none until one is
+/// set through `/api/subjects/{subject}/icon`.
+const DEFAULT_ICONS: &[(&str, &str)] = &[
+    ("Matematica", "📐"),
+    ("Italiano", "📖"),
+    ("Storia", "🏛️"),
+    ("Geografia", "🌍"),
+    ("Lingua Inglese", "🇬🇧"),
+    ("Tedesco", "🇩🇪"),
+    ("Francese", "🇫🇷"),
+    ("Spagnolo", "🇪🇸"),
+    ("Latino", "🏺"),
+    ("Arte e Immagine", "🎨"),
+    ("Tecnologia", "🔧"),
+    ("Informatica", "💻"),
+    ("Scienze", "🧪"),
+    ("Fisica", "⚛️"),
+    ("Musica", "🎵"),
+    ("Educazione Fisica", "⚽"),
+    ("Religione", "🕊️"),
+    ("Educazione Civica", "⚖️"),
+];
+
+/// Longest icon accepted, in characters (flags and emoji with modifiers take
+/// several)
+pub const MAX_ICON_CHARS: usize = 8;
+
+/// Whether `icon` can be a subject's icon: a short run of non-space
+/// characters, meant for an emoji
+pub fn valid_icon(icon: &str) -> bool {
+    let icon = icon.trim();
+    !icon.is_empty()
+        && icon.chars().count() <= MAX_ICON_CHARS
+        && !icon.chars().any(|c| c.is_whitespace() || c.is_control())
+}
+
+/// The built-in icon for `subject`: its canonical name's, matched regardless
+/// of case, or that of a canonical subject a keyword names ("INGLESE
+/// POTENZIATO" → Lingua Inglese)
+pub fn default_icon(subject: &str) -> Option<&'static str> {
+    let lower = subject.trim().to_lowercase();
+    let icon_of = |name: &str| {
+        DEFAULT_ICONS
+            .iter()
+            .find(|(canonical, _)| canonical.eq_ignore_ascii_case(name))
+            .map(|&(_, icon)| icon)
+    };
+    icon_of(&lower).or_else(|| {
+        let padded = format!(" {} ", lower);
+        KNOWN_SUBJECTS
+            .iter()
+            .find(|(keyword, _)| padded.contains(&format!(" {} ", keyword)))
+            .and_then(|(_, canonical)| icon_of(canonical))
+    })
+}
+
+/// Subject icons: the ones set in the database, then the built-in defaults
+#[derive(Debug, Clone, Default)]
+pub struct SubjectIcons {
+    /// Lowercased subject -> icon set for it
+    custom: HashMap<String, String>,
+}
+
+impl SubjectIcons {
+    pub fn load(conn: &Connection) -> db::Result<Self> {
+        let custom = db::get_subject_icons(conn)?
+            .into_iter()
+            .map(|(subject, icon)| (subject.trim().to_lowercase(), icon))
+            .collect();
+        Ok(Self { custom })
+    }
+
+    pub fn icon(&self, subject: &str) -> Option<&str> {
+        self.custom
+            .get(&subject.trim().to_lowercase())
+            .map(String::as_str)
+            .or_else(|| default_icon(subject))
+    }
+
+    /// "📐 Matematica", or just the subject when it has no icon
+    pub fn label(&self, subject: &str) -> String {
+        match self.icon(subject) {
+            Some(icon) => format!("{} {}", icon, subject),
+            None => subject.to_string(),
+        }
+    }
+
+    /// The icon of every subject in `counts` that has one
+    pub fn report(&self, counts: &[SubjectCount]) -> Vec<SubjectIcon> {
+        counts
+            .iter()
+            .filter_map(|c| {
+                let icon = self.icon(&c.subject)?;
+                Some(SubjectIcon {
+                    subject: c.subject.clone(),
+                    icon: icon.to_string(),
+                    custom: self.custom.contains_key(&c.subject.trim().to_lowercase()),
+                })
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_keyword_detector_english_names() {
+        assert_eq!(
+            KeywordDetector.detect("History: read chapter 4"),
+            Some("Storia".to_string())
+        );
+        assert_eq!(
+            KeywordDetector.detect("Quiz on geography tomorrow"),
+            Some("Geografia".to_string())
+        );
+    }
+
+    #[test]
+    fn test_detect_topic() {
+        let topic = |task: &str| detect_topic(task, "Matematica");
+        assert_eq!(
+            topic("Geometria: area del cerchio"),
+            Some("Geometria".to_string())
+        );
+        assert_eq!(
+            topic("Calcolo letterale: es. 4-8"),
+            Some("Calcolo letterale".to_string())
+        );
+        // More than three words is a sentence, not a topic
+        assert_eq!(topic("Per domani fare gli esercizi: pag. 4"), None);
+        assert_eq!(topic("Esercizi: pag. 10"), None);
+        assert_eq!(topic("Verifica di geometria: triangoli"), None);
+        assert_eq!(topic("Per domani: pag. 10"), None);
+        assert_eq!(topic("Da fare: es. 3"), None);
+        assert_eq!(topic("Matematica: pag. 10"), None);
+        assert_eq!(topic("geometria: lowercase"), None);
+        assert_eq!(topic("Ore 10:30 in palestra"), None);
+        assert_eq!(topic("Algebra:"), None);
+        assert_eq!(topic("Pag. 10 es. 3"), None);
+    }
+
+    #[test]
+    fn test_rules_take_precedence_over_keywords() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("subjects.json");
+        std::fs::write(
+            &path,
+            r#"{ "rules": [
+                { "pattern": "(?i)\\b(latino|versione)\\b", "subject": "Latino" },
+                { "pattern": "(?i)storia romana", "subject": "Latino" }
+            ] }"#,
+        )
+        .unwrap();
+
+        let pipeline = SubjectPipeline::load(&path).unwrap();
+        assert_eq!(pipeline.names(), vec!["rules", "keywords"]);
+        assert_eq!(
+            pipeline.detect("Versione pag. 12"),
+            Some("Latino".to_string())
+        );
+        assert_eq!(
+            pipeline.detect("Verifica di storia romana"),
+            Some("Latino".to_string())
+        );
+        // Falls through to the built-in keywords
+        assert_eq!(
+            pipeline.detect("Verifica di matematica"),
+            Some("Matematica".to_string())
+        );
+        assert_eq!(pipeline.detect("Completare gli esercizi"), None);
+    }
+
+    #[test]
+    fn test_load_without_rules_file() {
+        let pipeline = SubjectPipeline::load(Path::new("/nonexistent/subjects.json")).unwrap();
+        assert_eq!(
+            pipeline.detect("Verifica di storia"),
+            Some("Storia".to_string())
+        );
+    }
+
+    #[test]
+    fn test_invalid_rule_pattern_is_an_error() {
+        let err = RuleDetector::new([("(unclosed", "Latino")]).unwrap_err();
+        assert!(err.to_string().contains("(unclosed"));
+    }
+
+    #[test]
+    fn test_custom_detector_can_be_pushed() {
+        struct Greek;
+        impl SubjectDetector for Greek {
+            fn name(&self) -> &str {
+                "greek"
+            }
+            fn detect(&self, task: &str) -> Option<String> {
+                task.contains('α').then(|| "Greco".to_string())
+            }
+        }
+
+        let mut pipeline = SubjectPipeline::default();
+        pipeline.push(Greek);
+        assert_eq!(
+            pipeline.detect("Tradurre λόγος α"),
+            Some("Greco".to_string())
+        );
+        assert_eq!(
+            pipeline.detect("Verifica di storia"),
+            Some("Storia".to_string())
+        );
+    }
+
+    #[test]
+    fn test_add_alias_keeps_rules_and_never_chains() {
+        let temp_dir = TempDir::new().unwrap();
+        let path = temp_dir.path().join("data").join("subjects.json");
+        add_alias(&path, "Inglese", "Lingua Inglese").unwrap();
+        let pipeline = SubjectPipeline::load(&path).unwrap();
+        assert_eq!(pipeline.canonical("INGLESE"), "Lingua Inglese");
+        assert_eq!(pipeline.canonical("Storia"), "Storia");
+
+        std::fs::write(
+            &path,
+            r#"{ "rules": [{ "pattern": "(?i)versione", "subject": "Latino" }],
+                 "aliases": { "Inglese": "Lingua Inglese" } }"#,
+        )
+        .unwrap();
+        // Merging the alias target on moves the old alias along with it
+        add_alias(&path, "Lingua Inglese", "English").unwrap();
+        let pipeline = SubjectPipeline::load(&path).unwrap();
+        assert_eq!(pipeline.canonical("Inglese"), "English");
+        assert_eq!(pipeline.canonical("Lingua Inglese"), "English");
+        assert_eq!(
+            pipeline.detect("Versione pag. 3"),
+            Some("Latino".to_string())
+        );
+
+        // And merging back drops the alias away from the new target
+        add_alias(&path, "English", "Lingua Inglese").unwrap();
+        let pipeline = SubjectPipeline::load(&path).unwrap();
+        assert_eq!(pipeline.canonical("Inglese"), "Lingua Inglese");
+        assert_eq!(pipeline.canonical("Lingua Inglese"), "Lingua Inglese");
+    }
+
+    #[test]
+    fn test_suggest_merges() {
+        let count = |subject: &str, entries| SubjectCount {
+            subject: subject.to_string(),
+            entries,
+        };
+        let suggestions = suggest_merges(&[
+            count("Inglese", 4),
+            count("Inglese Potenziato", 2),
+            count("Lingua Inglese", 30),
+            count("Storia", 12),
+        ]);
+        let pairs: Vec<(&str, &str)> = suggestions
+            .iter()
+            .map(|s| (s.from.as_str(), s.into.as_str()))
+            .collect();
+        assert_eq!(
+            pairs,
+            vec![
+                ("Inglese Potenziato", "Inglese"),
+                ("Inglese", "Lingua Inglese"),
+                ("Inglese Potenziato", "Lingua Inglese"),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_merge_adds_the_alias_after_the_database() {
+        let temp_dir = TempDir::new().unwrap();
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+        db::write_test_migrations(&migrations_dir);
+        let conn = db::init_db(&temp_dir.path().join("test.db"), &migrations_dir).unwrap();
+        let rules_path = temp_dir.path().join("subjects.json");
+
+        merge(&conn, &rules_path, "Inglese Potenziato", "Inglese").unwrap();
+        let text = std::fs::read_to_string(&rules_path).unwrap();
+        assert!(text.contains(r#""Inglese Potenziato": "Inglese""#));
+
+        // The database refuses: no alias for a merge that didn't happen
+        conn.execute_batch("DROP TABLE subject_merges").unwrap();
+        assert!(merge(&conn, &rules_path, "Arte", "Storia dell'Arte").is_err());
+        let text = std::fs::read_to_string(&rules_path).unwrap();
+        assert!(!text.contains("Arte"));
+    }
+
+    #[test]
+    fn test_subject_icons() {
+        assert_eq!(default_icon("MATEMATICA"), Some("📐"));
+        assert_eq!(default_icon("Inglese Potenziato"), Some("🇬🇧"));
+        assert_eq!(default_icon("ed. fisica"), Some("⚽"));
+        // Keywords count as whole words only
+        assert_eq!(default_icon("Cartellone"), None);
+
+        let temp_dir = TempDir::new().unwrap();
+        let migrations_dir = temp_dir.path().join("migrations");
+        std::fs::create_dir(&migrations_dir).unwrap();
+        db::write_test_migrations(&migrations_dir);
+        let conn = db::init_db(&temp_dir.path().join("test.db"), &migrations_dir).unwrap();
+        db::set_subject_icon(&conn, "matematica", "🧮").unwrap();
+        db::set_subject_icon(&conn, "Coro", "🎤").unwrap();
+
+        let icons = SubjectIcons::load(&conn).unwrap();
+        assert_eq!(icons.label("Matematica"), "🧮 Matematica");
+        assert_eq!(icons.label("Sconosciuta"), "Sconosciuta");
+        let count = |subject: &str| SubjectCount {
+            subject: subject.to_string(),
+            entries: 1,
+        };
+        let report = icons.report(&[count("Coro"), count("Sconosciuta"), count("Storia")]);
+        let rows: Vec<(&str, &str, bool)> = report
+            .iter()
+            .map(|i| (i.subject.as_str(), i.icon.as_str(), i.custom))
+            .collect();
+        assert_eq!(rows, vec![("Coro", "🎤", true), ("Storia", "🏛️", false)]);
+
+        assert!(valid_icon("🇮🇹"));
+        assert!(!valid_icon(" "));
+        assert!(!valid_icon("a b"));
+        assert!(!valid_icon("troppo lungo"));
+    }
+}
@@ -0,0 +1,181 @@
+//! Per-teacher workload, for `/stats/teachers`: a page meant to be printed
+//! (or saved as PDF) and taken to a parent-teacher meeting.
+//!
+//! Only entries the register attributes to a teacher (the export's `autore`)
+//! count, and generated study sessions and reminders never do. Entries are
+//! placed on the date the teacher gave them (`source_date`), so moving one
+//! doesn't change the report. Lead time is `HomeworkEntry::notice_days()`,
+//! as in the notice stats on `/stats`; entries from exports without a
+//! posting time count everywhere else. A teacher's entries cluster on a
+//! weekday when at least [`CLUSTER_SHARE`] of them, and [`CLUSTER_MIN`] or
+//! more, are due on it.
+
+use chrono::{Datelike, NaiveDate};
+use std::cmp::Reverse;
+use std::collections::{BTreeMap, BTreeSet};
+
+use crate::db::SHORT_NOTICE_DAYS;
+use crate::types::{HomeworkEntry, TeacherReport, TeacherWorkload};
+
+/// Share of a teacher's entries one weekday needs to stand out
+pub const CLUSTER_SHARE: f64 = 0.5;
+
+/// Fewest entries on one weekday that can stand out
+pub const CLUSTER_MIN: usize = 4;
+
+pub const WEEKDAYS: [&str; 7] = [
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+    "Sunday",
+];
+
+fn parse_date(date: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()
+}
+
+/// The workload of every teacher with entries due between `from` and `to`
+/// (inclusive), out of `entries`
+pub fn report(entries: &[HomeworkEntry], from: NaiveDate, to: NaiveDate) -> TeacherReport {
+    let mut by_teacher: BTreeMap<&str, Vec<(&HomeworkEntry, NaiveDate)>> = BTreeMap::new();
+    let mut unattributed = 0;
+    for entry in entries.iter().filter(|e| e.parent_id.is_none()) {
+        let Some(due) = parse_date(entry.source_date.as_deref().unwrap_or(&entry.date)) else {
+            continue;
+        };
+        if due < from || due > to {
+            continue;
+        }
+        match entry.teacher.as_deref().map(str::trim) {
+            Some(teacher) if !teacher.is_empty() => {
+                by_teacher.entry(teacher).or_default().push((entry, due))
+            }
+            _ => unattributed += 1,
+        }
+    }
+
+    let mut teachers: Vec<TeacherWorkload> = by_teacher
+        .into_iter()
+        .map(|(teacher, entries)| workload(teacher, &entries))
+        .collect();
+    teachers.sort_by_key(|t| Reverse(t.entries));
+    TeacherReport {
+        from: from.to_string(),
+        to: to.to_string(),
+        teachers,
+        unattributed,
+    }
+}
+
+fn workload(teacher: &str, entries: &[(&HomeworkEntry, NaiveDate)]) -> TeacherWorkload {
+    let subjects: BTreeSet<&str> = entries.iter().map(|(e, _)| e.subject.as_str()).collect();
+    let mut weekdays = [0; 7];
+    let mut test_weekdays = [0; 7];
+    let mut leads = Vec::new();
+    for (entry, due) in entries {
+        let weekday = due.weekday().num_days_from_monday() as usize;
+        weekdays[weekday] += 1;
+        if matches!(entry.entry_type.as_str(), "verifica" | "interrogazione") {
+            test_weekdays[weekday] += 1;
+        }
+        leads.extend(entry.notice_days());
+    }
+    let busiest = (0..7).max_by_key(|&d| (weekdays[d], Reverse(d)));
+    let clustered_on = busiest
+        .filter(|&d| {
+            weekdays[d] >= CLUSTER_MIN && weekdays[d] as f64 >= CLUSTER_SHARE * entries.len() as f64
+        })
+        .map(|d| WEEKDAYS[d].to_string());
+
+    TeacherWorkload {
+        teacher: teacher.to_string(),
+        subjects: subjects.into_iter().map(str::to_string).collect(),
+        entries: entries.len(),
+        tests: test_weekdays.iter().sum(),
+        avg_lead_days: (!leads.is_empty())
+            .then(|| leads.iter().sum::<i64>() as f64 / leads.len() as f64),
+        short_notice: leads.iter().filter(|&&d| d <= SHORT_NOTICE_DAYS).count(),
+        weekdays,
+        test_weekdays,
+        clustered_on,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(entry_type: &str, date: &str, teacher: Option<&str>, posted: &str) -> HomeworkEntry {
+        let mut entry = HomeworkEntry::new(
+            entry_type.to_string(),
+            date.to_string(),
+            "Matematica".to_string(),
+            format!("{} {}", entry_type, date),
+        );
+        entry.teacher = teacher.map(str::to_string);
+        entry.assigned_at = (!posted.is_empty()).then(|| posted.to_string());
+        entry
+    }
+
+    fn day(date: &str) -> NaiveDate {
+        date.parse().unwrap()
+    }
+
+    #[test]
+    fn test_report() {
+        let mut moved = entry("verifica", "2025-01-27", Some("ROSSI MARIO"), "2025-01-10");
+        moved.source_date = Some("2025-01-20".to_string());
+        let mut session = entry("studio", "2025-01-19", Some("ROSSI MARIO"), "");
+        session.parent_id = Some(moved.id.clone());
+        let mut storia = entry(
+            "compiti",
+            "2025-01-14",
+            Some("BIANCHI ANNA"),
+            "2025-01-13 18:30:00",
+        );
+        storia.subject = "Storia".to_string();
+        let entries = vec![
+            moved,
+            session,
+            // Mondays
+            entry("compiti", "2025-01-13", Some("ROSSI MARIO"), "2025-01-12"),
+            entry("compiti", "2025-01-27", Some("ROSSI MARIO"), ""),
+            entry("verifica", "2025-02-03", Some("ROSSI MARIO"), "2025-01-20"),
+            // A Thursday
+            entry("compiti", "2025-01-16", Some("ROSSI MARIO"), "2025-01-16"),
+            storia,
+            entry("compiti", "2025-01-15", None, ""),
+            // Outside the period
+            entry("compiti", "2025-03-03", Some("ROSSI MARIO"), ""),
+        ];
+
+        let report = report(&entries, day("2025-01-01"), day("2025-02-28"));
+        assert_eq!(report.unattributed, 1);
+        assert_eq!(report.teachers.len(), 2);
+
+        let rossi = &report.teachers[0];
+        assert_eq!(rossi.teacher, "ROSSI MARIO");
+        assert_eq!(rossi.subjects, vec!["Matematica"]);
+        assert_eq!(rossi.entries, 5);
+        assert_eq!(rossi.tests, 2);
+        // 10, 1, 14 and 0 days
+        assert_eq!(rossi.avg_lead_days, Some(6.25));
+        assert_eq!(rossi.short_notice, 2);
+        assert_eq!(rossi.weekdays, [4, 0, 0, 1, 0, 0, 0]);
+        assert_eq!(rossi.test_weekdays, [2, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(rossi.clustered_on.as_deref(), Some("Monday"));
+
+        let bianchi = &report.teachers[1];
+        assert_eq!(bianchi.subjects, vec!["Storia"]);
+        assert_eq!(bianchi.avg_lead_days, Some(1.0));
+        // Too few entries to say
+        assert_eq!(bianchi.clustered_on, None);
+
+        let empty = super::report(&entries, day("2024-09-01"), day("2024-12-31"));
+        assert!(empty.teachers.is_empty());
+        assert_eq!(empty.unattributed, 0);
+    }
+}
@@ -0,0 +1,192 @@
+//! The weekly timetable, to catch entries dated on a day their subject has
+//! no lesson (usually a typo in the register).
+//!
+//! It's the `timetable` object in `data/subjects.json`, next to the subject
+//! rules and aliases: each weekday's subjects, under its English or Italian
+//! name or the first three letters of it. Other keys are skipped with a
+//! warning; the check is only advice, so it never stops an import.
+//!
+//! ```json
+//! { "timetable": {
+//!     "mon": ["Matematica", "Italiano", "Storia"],
+//!     "tue": ["Lingua Inglese", "Matematica"]
+//! } }
+//! ```
+//!
+//! Subjects are matched in any case. A subject that isn't in the timetable
+//! at all is never flagged (nothing is known about it), nor are generated
+//! entries, own study entries or multi-day events. The parser adds a row
+//! warning to the import report, and the list view shows a badge.
+
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+use std::collections::{BTreeMap, HashMap};
+use std::path::Path;
+use tracing::warn;
+
+use crate::subjects;
+use crate::teachers::WEEKDAYS;
+use crate::types::HomeworkEntry;
+
+/// Italian weekday names, Monday first, accents dropped
+const GIORNI: [&str; 7] = [
+    "lunedi",
+    "martedi",
+    "mercoledi",
+    "giovedi",
+    "venerdi",
+    "sabato",
+    "domenica",
+];
+
+/// Types whose date isn't a lesson day: generated reminders and study
+/// sessions, and study planned by hand
+const UNCHECKED_TYPES: &[&str] = &["lavoro", "studio"];
+
+/// Weekdays with a lesson, per lowercased subject
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Timetable {
+    days: HashMap<String, [bool; 7]>,
+}
+
+impl Timetable {
+    /// Build from weekday name -> subjects. Unknown weekdays are skipped.
+    pub fn from_days(days: &BTreeMap<String, Vec<String>>) -> Self {
+        let mut timetable = Self::default();
+        for (day, subjects) in days {
+            let Some(weekday) = weekday_index(day) else {
+                warn!(day = %day, "Unknown weekday in the timetable; skipped");
+                continue;
+            };
+            for subject in subjects {
+                let subject = subject.trim().to_lowercase();
+                if !subject.is_empty() {
+                    timetable.days.entry(subject).or_default()[weekday] = true;
+                }
+            }
+        }
+        timetable
+    }
+
+    /// The timetable in the rules file at `rules_path`; empty when there's
+    /// no file or no timetable in it
+    pub fn load(rules_path: &Path) -> Result<Self> {
+        if !rules_path.exists() {
+            return Ok(Self::default());
+        }
+        Ok(Self::from_days(
+            &subjects::read_rules_file(rules_path)?.timetable,
+        ))
+    }
+
+    /// Why `entry`'s date looks wrong: its subject is in the timetable but
+    /// has no lesson that weekday. The date is the export's (`source_date`)
+    /// when the entry was moved since.
+    pub fn check(&self, entry: &HomeworkEntry) -> Option<String> {
+        if entry.parent_id.is_some()
+            || entry.until().is_some()
+            || UNCHECKED_TYPES.contains(&entry.entry_type.as_str())
+        {
+            return None;
+        }
+        let lessons = self.days.get(&entry.subject.trim().to_lowercase())?;
+        let date = entry.source_date.as_deref().unwrap_or(&entry.date);
+        let date = NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()?;
+        let weekday = date.weekday().num_days_from_monday() as usize;
+        if lessons[weekday] {
+            return None;
+        }
+        Some(format!(
+            "{} has no lesson on {}s ({}); is the date right?",
+            entry.subject.trim(),
+            WEEKDAYS[weekday],
+            date
+        ))
+    }
+}
+
+/// Monday = 0. Full English or Italian names (with or without the accent)
+/// or their first three letters, any case.
+fn weekday_index(day: &str) -> Option<usize> {
+    let day = day.trim().to_lowercase().replace('ì', "i");
+    let matches = |name: &str| day == name || (day.len() == 3 && name.starts_with(&day));
+    (0..7).find(|&i| matches(&WEEKDAYS[i].to_lowercase()) || matches(GIORNI[i]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(entry_type: &str, date: &str, subject: &str) -> HomeworkEntry {
+        HomeworkEntry::new(
+            entry_type.to_string(),
+            date.to_string(),
+            subject.to_string(),
+            "Esercizi".to_string(),
+        )
+    }
+
+    #[test]
+    fn test_check() {
+        let days = BTreeMap::from([
+            ("mon".to_string(), vec!["Matematica".to_string()]),
+            (
+                "Wednesday".to_string(),
+                vec!["matematica".to_string(), "Storia".to_string()],
+            ),
+        ]);
+        let timetable = Timetable::from_days(&days);
+
+        // 2025-01-13 is a Monday
+        assert_eq!(
+            timetable.check(&entry("compiti", "2025-01-13", "Matematica")),
+            None
+        );
+        assert_eq!(
+            timetable
+                .check(&entry("verifica", "2025-01-14", "Matematica"))
+                .unwrap(),
+            "Matematica has no lesson on Tuesdays (2025-01-14); is the date right?"
+        );
+        assert!(timetable
+            .check(&entry("compiti", "2025-01-13", " STORIA "))
+            .is_some());
+        // Not in the timetable: nothing to go on
+        assert_eq!(
+            timetable.check(&entry("compiti", "2025-01-14", "Arte")),
+            None
+        );
+        assert_eq!(
+            timetable.check(&entry("studio", "2025-01-14", "Matematica")),
+            None
+        );
+
+        // Moved since: the export's date is what counts
+        let mut moved = entry("compiti", "2025-01-14", "Matematica");
+        moved.source_date = Some("2025-01-15".to_string());
+        assert_eq!(timetable.check(&moved), None);
+
+        let mut event = entry("verifica", "2025-01-14", "Storia");
+        event.end_date = Some("2025-01-16".to_string());
+        assert_eq!(timetable.check(&event), None);
+
+        // Italian names work too; unknown days are skipped
+        let days = BTreeMap::from([
+            ("martedì".to_string(), vec!["Storia".to_string()]),
+            ("Gio".to_string(), vec!["Storia".to_string()]),
+            ("montag".to_string(), vec!["Storia".to_string()]),
+        ]);
+        let timetable = Timetable::from_days(&days);
+        assert_eq!(
+            timetable.check(&entry("compiti", "2025-01-14", "Storia")),
+            None
+        );
+        assert_eq!(
+            timetable.check(&entry("compiti", "2025-01-16", "Storia")),
+            None
+        );
+        assert!(timetable
+            .check(&entry("compiti", "2025-01-13", "Storia"))
+            .is_some());
+    }
+}
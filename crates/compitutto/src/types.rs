@@ -1,8 +1,15 @@
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use std::hash::{Hash, Hasher};
 
 /// A single homework entry
 #[derive(Debug, Clone, Serialize, Deserialize, Eq, PartialEq)]
+#[cfg_attr(
+    feature = "graphql",
+    derive(async_graphql::SimpleObject),
+    graphql(complex)
+)]
 pub struct HomeworkEntry {
     /// Unique identifier for this entry (UUID-like, changes if entry is recreated)
     pub id: String,
@@ -15,14 +22,25 @@ pub struct HomeworkEntry {
 
     /// Type of entry (e.g., "compiti", "nota", "studio")
     #[serde(rename = "type")]
+    #[cfg_attr(feature = "graphql", graphql(name = "type"))]
     pub entry_type: String,
 
     /// Due date in YYYY-MM-DD format
     pub date: String,
 
+    /// Last day of a multi-day event (the export's `data_fine`), set only
+    /// when it comes after `date`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_date: Option<String>,
+
     /// Subject name
     pub subject: String,
 
+    /// Topic within the subject ("Geometria" under Matematica), when the
+    /// task names one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub topic: Option<String>,
+
     /// Task description
     pub task: String,
 
@@ -45,6 +63,53 @@ pub struct HomeworkEntry {
     /// When this entry was last updated (RFC 3339 format)
     #[serde(default, skip_serializing_if = "String::is_empty")]
     pub updated_at: String,
+
+    /// Planned time in minutes, if the student set one
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub estimated_minutes: Option<u32>,
+
+    /// Time it actually took in minutes, recorded after completion
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub actual_minutes: Option<u32>,
+
+    /// Difficulty rating recorded after completion (1 = easy … 5 = very hard)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub difficulty: Option<u8>,
+
+    /// Pinned entries sort first within their day
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+
+    /// Accent color override as `#rrggbb`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+
+    /// Grade recorded once a test has happened, as written ("7½", "8-")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub grade: Option<String>,
+
+    /// Date the export gave this entry, kept when it's moved. `None` for
+    /// entries added by hand or generated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_date: Option<String>,
+
+    /// When the teacher posted it (the export's `data_inserimento`), as
+    /// `YYYY-MM-DD HH:MM:SS` or just the date
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub assigned_at: Option<String>,
+
+    /// Who posted it (the export's `autore`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub teacher: Option<String>,
+
+    /// Kept out of the list and dashboard before this day (`YYYY-MM-DD`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub visible_from: Option<String>,
+
+    /// Export file in data/ the entry was first imported from. `None` for
+    /// entries added by hand or generated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source_file: Option<String>,
 }
 
 impl HomeworkEntry {
@@ -59,13 +124,26 @@ impl HomeworkEntry {
             source_id: Some(source_id),
             entry_type,
             date,
+            end_date: None,
             subject,
+            topic: None,
             task,
             completed: false,
             position: 0,
             parent_id: None,
             created_at: now.clone(),
             updated_at: now,
+            estimated_minutes: None,
+            actual_minutes: None,
+            difficulty: None,
+            pinned: false,
+            color: None,
+            grade: None,
+            source_date: None,
+            assigned_at: None,
+            teacher: None,
+            visible_from: None,
+            source_file: None,
         }
     }
 
@@ -85,13 +163,26 @@ impl HomeworkEntry {
             source_id: Some(source_id),
             entry_type,
             date,
+            end_date: None,
             subject,
+            topic: None,
             task,
             completed: false,
             position: 0,
             parent_id: None,
             created_at: now.clone(),
             updated_at: now,
+            estimated_minutes: None,
+            actual_minutes: None,
+            difficulty: None,
+            pinned: false,
+            color: None,
+            grade: None,
+            source_date: None,
+            assigned_at: None,
+            teacher: None,
+            visible_from: None,
+            source_file: None,
         }
     }
 
@@ -130,12 +221,924 @@ impl HomeworkEntry {
         self.parent_id.is_some()
     }
 
+    /// The export's date for an entry that has since been moved off it
+    pub fn original_date(&self) -> Option<&str> {
+        self.source_date.as_deref().filter(|d| *d != self.date)
+    }
+
+    /// Days between the teacher posting the entry and the date the export
+    /// gave it (before any move); negative when it was posted after
+    pub fn notice_days(&self) -> Option<i64> {
+        let day = |s: &str| NaiveDate::parse_from_str(s.get(..10)?, "%Y-%m-%d").ok();
+        let assigned = day(self.assigned_at.as_deref()?)?;
+        let due = day(self.source_date.as_deref().unwrap_or(&self.date))?;
+        Some((due - assigned).num_days())
+    }
+
+    /// Last day of a multi-day event, as long as it still ends after `date`
+    pub fn until(&self) -> Option<&str> {
+        self.end_date.as_deref().filter(|d| *d > self.date.as_str())
+    }
+
     /// Check if this is an orphaned study session (was generated but parent deleted)
     pub fn is_orphaned(&self) -> bool {
         self.entry_type == "studio" && self.parent_id.is_none()
     }
 }
 
+/// A parsed export entry staged for review before it reaches `entries`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PendingImport {
+    /// Id the entry will get when accepted
+    pub id: String,
+    pub source_id: String,
+    #[serde(rename = "type")]
+    pub entry_type: String,
+    pub date: String,
+    pub subject: String,
+    pub task: String,
+
+    /// Existing entry (same date, subject and type) whose task this would
+    /// replace. `None` means the entry is new.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub replaces_id: Option<String>,
+
+    /// Current task of the entry being replaced, for showing the change
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub previous_task: Option<String>,
+
+    pub created_at: String,
+}
+
+impl PendingImport {
+    /// Whether this changes an existing entry rather than adding a new one
+    pub fn is_change(&self) -> bool {
+        self.replaces_id.is_some()
+    }
+}
+
+/// A school holiday (one row per day)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Holiday {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub name: String,
+}
+
+/// An after-school activity on one day, from an iCal feed
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Activity {
+    /// `YYYY-MM-DD`
+    pub date: String,
+    pub name: String,
+    /// `HH:MM`, absent for all-day events
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub start_time: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<String>,
+    /// How long it takes, for balancing study sessions
+    pub minutes: u32,
+}
+
+/// How two entries are linked
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum LinkRelation {
+    /// `from` should be done after `to`
+    #[default]
+    DependsOn,
+    /// Symmetric: just shown next to each other
+    Related,
+    /// `from` is the same task as `to`, posted under another subject
+    /// (see `crosslist`)
+    CrossListed,
+}
+
+impl LinkRelation {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            LinkRelation::DependsOn => "depends_on",
+            LinkRelation::Related => "related",
+            LinkRelation::CrossListed => "cross_listed",
+        }
+    }
+}
+
+/// A typed link between two entries
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct EntryLink {
+    pub id: i64,
+    pub from_id: String,
+    pub to_id: String,
+    pub relation: LinkRelation,
+    pub created_at: String,
+}
+
+/// Emoji a reaction can use
+pub const REACTION_EMOJI: &[&str] = &["⭐", "👍", "🎉"];
+
+/// Longest encouragement message, in characters
+pub const MAX_REACTION_MESSAGE: usize = 140;
+
+/// Longest reaction author, in characters: the "who's looking" name, which
+/// `live` cuts at the same length
+pub const MAX_REACTION_AUTHOR: usize = crate::live::MAX_NAME_LEN;
+
+/// A reaction or short encouragement on a completed entry
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Reaction {
+    pub id: i64,
+    pub entry_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub emoji: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub author: Option<String>,
+    pub created_at: String,
+}
+
+/// A study link for every entry of one subject (textbook, Quizlet set,
+/// class Drive folder). A `{page}` in `url` stands for the page number in the
+/// entry's task ("pag. 112").
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SubjectResource {
+    pub id: i64,
+    pub subject: String,
+    pub title: String,
+    pub url: String,
+    pub created_at: String,
+}
+
+/// The icon drawn next to a subject's name (`/api/subjects/icons`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SubjectIcon {
+    pub subject: String,
+    pub icon: String,
+    /// Set through the API rather than one of the built-in defaults
+    pub custom: bool,
+}
+
+/// A file attached to an entry on the portal (from raschietto's `--details`
+/// sidecar). Photos have a local `file`, OCR'd in the background into
+/// `ocr_text`, which the entry search covers.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Attachment {
+    pub id: i64,
+    pub source_id: String,
+    pub name: String,
+    pub url: String,
+    pub file: Option<String>,
+    /// `none` (not a photo), `pending`, `done` or `failed`
+    pub ocr_status: String,
+    pub ocr_text: Option<String>,
+    pub ocr_error: Option<String>,
+    pub created_at: String,
+    pub processed_at: Option<String>,
+}
+
+/// An attachment read from a sidecar, for `db::record_attachments`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NewAttachment {
+    /// The entry's source_id
+    pub source_id: String,
+    pub name: String,
+    pub url: String,
+    pub file: Option<String>,
+}
+
+/// An absence, late entrance or early exit from the register
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Absence {
+    pub date: String,
+    /// As on the portal: "Assenza", "Ritardo", "Uscita anticipata"
+    pub kind: String,
+    pub justified: bool,
+    #[serde(default)]
+    pub note: String,
+}
+
+/// A disciplinary note from the register
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DisciplineNote {
+    pub date: String,
+    #[serde(default)]
+    pub teacher: String,
+    #[serde(default)]
+    pub kind: String,
+    pub text: String,
+}
+
+/// The register's absences as last fetched (`absences_*.json`, /api/absences)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct AbsencesSnapshot {
+    /// Day raschietto read the page; `None` before the first import
+    pub fetched: Option<String>,
+    pub absences: Vec<Absence>,
+}
+
+/// The register's disciplinary notes as last fetched (`notes_*.json`, /api/notes)
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct NotesSnapshot {
+    pub fetched: Option<String>,
+    pub notes: Vec<DisciplineNote>,
+}
+
+/// How many entries use a subject name
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct SubjectCount {
+    pub subject: String,
+    pub entries: usize,
+}
+
+/// Two subject names that look like the same subject; `from` is the less
+/// used one
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct MergeSuggestion {
+    pub from: String,
+    pub into: String,
+}
+
+/// Subjects in use, with likely duplicates (`/api/subjects`)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct SubjectsReport {
+    pub subjects: Vec<SubjectCount>,
+    pub suggestions: Vec<MergeSuggestion>,
+}
+
+/// One logged subject merge
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SubjectMerge {
+    pub id: i64,
+    pub from: String,
+    pub into: String,
+    /// Entries rewritten
+    pub entries: usize,
+    /// Pending imports rewritten
+    pub pending: usize,
+    pub merged_at: String,
+}
+
+/// One entry a text fix rewrites (or would, on a dry run)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TextChange {
+    pub id: String,
+    pub date: String,
+    pub subject: String,
+    pub before: String,
+    pub after: String,
+}
+
+/// One logged bulk text correction
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TextFix {
+    pub id: i64,
+    pub find: String,
+    pub replace: String,
+    /// Only entries of this subject; `None` for every subject
+    pub subject: Option<String>,
+    /// Entries rewritten
+    pub entries: usize,
+    pub fixed_at: String,
+}
+
+/// What a text fix changed, or would change on a dry run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct TextFixReport {
+    pub dry_run: bool,
+    pub changes: Vec<TextChange>,
+    /// The log entry; `None` on a dry run or when nothing matched
+    pub fix: Option<TextFix>,
+}
+
+/// The last run of a background job
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JobRun {
+    pub name: String,
+    /// RFC 3339, UTC
+    pub started_at: String,
+    pub finished_at: String,
+    pub ok: bool,
+    /// What the job did, or why it failed
+    pub message: String,
+    /// Runs so far, this one included
+    pub runs: u64,
+}
+
+/// A background job as `/api/jobs` reports it
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct JobStatus {
+    pub name: String,
+    pub description: String,
+    /// Cron expression, in server local time
+    pub schedule: String,
+    /// RFC 3339, local time
+    pub next_run: Option<String>,
+    pub last_run: Option<JobRun>,
+}
+
+/// A read-only share link for one entry
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct ShareLink {
+    pub token: String,
+    pub entry_id: String,
+    /// RFC 3339, UTC
+    pub expires_at: String,
+    pub created_at: String,
+}
+
+impl ShareLink {
+    /// Whether the link has stopped working at `now`
+    pub fn is_expired(&self, now: DateTime<Utc>) -> bool {
+        DateTime::parse_from_rfc3339(&self.expires_at).map_or(true, |expires| expires <= now)
+    }
+}
+
+/// Estimated vs actual time for one subject, over its completed entries
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct SubjectTimeStats {
+    pub subject: String,
+
+    /// Number of completed entries
+    pub completed: usize,
+
+    /// Completed entries that have an actual time recorded
+    pub with_feedback: usize,
+
+    pub avg_estimated_minutes: Option<f64>,
+    pub avg_actual_minutes: Option<f64>,
+    pub avg_difficulty: Option<f64>,
+
+    /// Total actual / total estimated time over entries that have both.
+    /// Above 1.0 means this subject is usually underestimated.
+    pub estimate_ratio: Option<f64>,
+}
+
+/// How much work one topic of a subject brought
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct TopicStats {
+    pub subject: String,
+    pub topic: String,
+    pub entries: usize,
+    /// Entries whose task names a test (`data::is_test_or_quiz`)
+    pub tests: usize,
+    pub completed: usize,
+    /// Actual minutes, or the estimate where none was recorded
+    pub minutes: u32,
+}
+
+/// How much notice one teacher gives in one subject: days between posting
+/// an entry on the register and its due date
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct NoticeStats {
+    pub subject: String,
+    /// The export's `autore`; `None` for entries from exports without it
+    pub teacher: Option<String>,
+    pub entries: usize,
+    /// Entries whose task names a test (`data::is_test_or_quiz`)
+    pub tests: usize,
+    pub avg_days: f64,
+    pub shortest_days: i64,
+    /// Entries posted at most a day before they were due (or after)
+    pub short_notice: usize,
+}
+
+/// A weekly target for one subject, e.g. 3 study sessions of Matematica
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct WeeklyGoal {
+    pub subject: String,
+
+    /// Completed compiti/studio entries wanted per week
+    pub target: u32,
+}
+
+/// How far one weekly goal has got
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GoalProgress {
+    pub subject: String,
+    pub target: u32,
+
+    /// Completed compiti/studio entries dated this week
+    pub done: u32,
+
+    pub met: bool,
+}
+
+/// Response of `/api/stats/goals`: attainment for one Monday–Sunday week
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct GoalsReport {
+    pub week_start: String,
+    pub week_end: String,
+    pub goals: Vec<GoalProgress>,
+
+    /// Number of goals met
+    pub met: usize,
+}
+
+/// Counts and rates over a set of entries in one period
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq)]
+pub struct PeriodCounts {
+    pub entries: usize,
+
+    /// Verifiche and interrogazioni
+    pub tests: usize,
+
+    pub completed: usize,
+
+    /// Completed / entries, `None` when there are no entries
+    pub completion_rate: Option<f64>,
+
+    /// Average days between an entry appearing and its due date
+    pub avg_lead_days: Option<f64>,
+}
+
+/// One subject's counts in a period
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct SubjectPeriodStats {
+    pub subject: String,
+    #[serde(flatten)]
+    pub counts: PeriodCounts,
+}
+
+/// Activity between `from` and `to` (inclusive), overall and per subject
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PeriodStats {
+    pub from: String,
+    pub to: String,
+    #[serde(flatten)]
+    pub total: PeriodCounts,
+    pub subjects: Vec<SubjectPeriodStats>,
+}
+
+impl PeriodStats {
+    /// Counts for `subject`, if it had entries in this period
+    pub fn subject(&self, subject: &str) -> Option<&PeriodCounts> {
+        self.subjects
+            .iter()
+            .find(|s| s.subject == subject)
+            .map(|s| &s.counts)
+    }
+}
+
+/// Response of `/api/stats/compare`: two periods side by side
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StatsComparison {
+    pub a: PeriodStats,
+    pub b: PeriodStats,
+}
+
+/// What one teacher posted over a period, from the export's `autore`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TeacherWorkload {
+    pub teacher: String,
+    /// Subjects they posted entries in, sorted
+    pub subjects: Vec<String>,
+    pub entries: usize,
+    /// Entries whose task names a test (`data::is_test_or_quiz`)
+    pub tests: usize,
+    /// Days between posting and the due date, over entries whose posting
+    /// time the export gave
+    pub avg_lead_days: Option<f64>,
+    /// Entries posted a day or less before they were due (or after)
+    pub short_notice: usize,
+    /// Entries due on each weekday, Monday first
+    pub weekdays: [usize; 7],
+    /// Tests due on each weekday, Monday first
+    pub test_weekdays: [usize; 7],
+    /// The weekday most of their entries are due on, when one stands out
+    /// ("Monday")
+    pub clustered_on: Option<String>,
+}
+
+/// Response of `/api/stats/teachers`: per-teacher workload between `from`
+/// and `to`, most entries first
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct TeacherReport {
+    pub from: String,
+    pub to: String,
+    pub teachers: Vec<TeacherWorkload>,
+    /// Entries in the period whose export didn't name a teacher
+    pub unattributed: usize,
+}
+
+/// A day whose entries were all ticked off
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct DayCompleted {
+    pub date: String,
+    /// When the last of them was, UTC
+    pub completed_at: String,
+    pub entries: usize,
+}
+
+/// Response of `/api/stats/days-completed`: one month's all-done days and
+/// the streak up to today
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "graphql", derive(async_graphql::SimpleObject))]
+pub struct DaysCompletedReport {
+    /// `YYYY-MM`
+    pub month: String,
+    pub days: Vec<DayCompleted>,
+    /// Days with entries all done in a row, up to today
+    pub streak: usize,
+    pub best_streak: usize,
+}
+
+impl StatsComparison {
+    /// Every subject in either period, sorted
+    pub fn subjects(&self) -> Vec<&str> {
+        let mut subjects: Vec<&str> = self
+            .a
+            .subjects
+            .iter()
+            .chain(&self.b.subjects)
+            .map(|s| s.subject.as_str())
+            .collect();
+        subjects.sort_unstable();
+        subjects.dedup();
+        subjects
+    }
+}
+
+/// What the dashboard landing page shows for one day
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Dashboard {
+    /// The day the dashboard is for (`YYYY-MM-DD`)
+    pub today: String,
+    pub due_today: Vec<HomeworkEntry>,
+    pub due_tomorrow: Vec<HomeworkEntry>,
+    /// The rest of the coming week, after tomorrow
+    pub this_week: Vec<HomeworkEntry>,
+    /// Open tests and interrogations from today on, soonest first
+    pub upcoming_tests: Vec<HomeworkEntry>,
+    pub counts: DashboardCounts,
+    /// Subjects each shown primary is cross-listed under besides its own;
+    /// the copies themselves are left out
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub cross_listed: BTreeMap<String, Vec<String>>,
+}
+
+/// An export file entries were imported from, for `GET /api/source-files`
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct SourceFile {
+    /// File name, as in `HomeworkEntry::source_file`
+    pub name: String,
+    /// Whether it's still in data/ to download
+    pub available: bool,
+    /// Size in bytes, when available
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub bytes: Option<u64>,
+    /// Last modified (RFC 3339), when available
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub modified: Option<String>,
+    /// Stored entries first imported from it
+    pub entries: usize,
+}
+
+/// Headline numbers on the dashboard
+#[derive(Debug, Clone, Default, Serialize, Deserialize, PartialEq, Eq)]
+pub struct DashboardCounts {
+    /// Open entries dated before today
+    pub overdue: usize,
+    /// Open entries due today
+    pub open_today: usize,
+    /// Open entries from today through the end of the coming week
+    pub open_week: usize,
+    /// Entries in the coming week already done
+    pub completed_week: usize,
+    /// Open entries hidden until a later day (`visible_from`)
+    pub hidden: usize,
+}
+
+/// Which view the main page opens in
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum View {
+    #[default]
+    List,
+    Calendar,
+}
+
+impl View {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            View::List => "list",
+            View::Calendar => "calendar",
+        }
+    }
+}
+
+/// What ticking an entry off means for its type
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Completion {
+    /// The work is done (compiti, study sessions)
+    #[default]
+    Done,
+    /// It took place (a verifica or interrogazione); it can take a grade
+    Happened,
+}
+
+/// Longest grade accepted, in characters
+pub const MAX_GRADE_LEN: usize = 10;
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Theme {
+    #[default]
+    Dark,
+    Light,
+    /// Black and white with no motion, bigger targets and always-visible
+    /// controls, for low vision and colour blindness
+    Contrast,
+}
+
+impl Theme {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Theme::Dark => "dark",
+            Theme::Light => "light",
+            Theme::Contrast => "contrast",
+        }
+    }
+
+    /// Class put on `<body>` of every page
+    pub fn body_class(self) -> &'static str {
+        match self {
+            Theme::Dark => "theme-dark",
+            Theme::Light => "theme-light",
+            Theme::Contrast => "theme-contrast",
+        }
+    }
+}
+
+/// Where a new study session goes among the entries already on its day
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum SessionPosition {
+    /// Above everything else
+    #[default]
+    Top,
+    /// Below everything else
+    Bottom,
+    /// After the day's homework and any study session for an earlier test,
+    /// before the rest
+    Priority,
+}
+
+impl SessionPosition {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            SessionPosition::Top => "top",
+            SessionPosition::Bottom => "bottom",
+            SessionPosition::Priority => "priority",
+        }
+    }
+}
+
+/// UI language: sets `<html lang>` and how dates are formatted
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Language {
+    #[default]
+    En,
+    It,
+}
+
+impl Language {
+    pub fn code(self) -> &'static str {
+        match self {
+            Language::En => "en",
+            Language::It => "it",
+        }
+    }
+}
+
+/// User preferences, stored as key/value rows in the `settings` table.
+/// Missing keys fall back to the defaults below.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+pub struct Settings {
+    pub default_view: View,
+
+    /// Hide completed entries in the list and calendar
+    pub hide_completed: bool,
+
+    /// Days before today the list view opens with (0–365)
+    pub list_days_back: u32,
+
+    /// Days after today the list view opens with (0–365)
+    pub list_days_ahead: u32,
+
+    pub theme: Theme,
+    pub language: Language,
+
+    /// Generate study sessions before tests
+    pub study_sessions: bool,
+
+    /// Study sessions to generate before a test (minimum 3)
+    pub study_days_before: u32,
+
+    /// Give each study session its own part of a test's chapters or pages
+    pub split_study_topics: bool,
+
+    /// Where new study sessions go on their day
+    pub study_session_position: SessionPosition,
+
+    /// Days before the due date to place the work reminder (1 or 2)
+    pub homework_days_ahead: u32,
+
+    /// Weekdays (1=Mon … 5=Fri) allowed for work reminders
+    pub work_days: Vec<u32>,
+
+    /// Stage parsed exports for review instead of importing them directly
+    pub review_imports: bool,
+
+    /// Per-subject weekly goals shown in the header
+    pub weekly_goals: Vec<WeeklyGoal>,
+
+    /// Minutes of work the "Start here" plan fills per day (15–600)
+    pub daily_budget_minutes: u32,
+
+    /// Warn when a day would get more open entries than this (0 = never)
+    pub day_limit_entries: u32,
+
+    /// Warn when a day would need more minutes of work than this (0 = never)
+    pub day_limit_minutes: u32,
+
+    /// Check GitHub once a day for a newer release; off until the user
+    /// turns it on, since it contacts a third party
+    pub check_updates: bool,
+
+    /// Entry types whose completion isn't [`Completion::Done`]
+    pub completion: BTreeMap<String, Completion>,
+
+    /// Ask for the grade when a `happened` entry is ticked off
+    pub ask_grade: bool,
+
+    /// Origins a browser may call the API from (see `cors`); `*` for any
+    pub cors_origins: Vec<String>,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            default_view: View::List,
+            hide_completed: false,
+            list_days_back: 14,
+            list_days_ahead: 28,
+            theme: Theme::Dark,
+            language: Language::En,
+            study_sessions: true,
+            study_days_before: 4,
+            split_study_topics: true,
+            study_session_position: SessionPosition::Top,
+            homework_days_ahead: 2,
+            work_days: vec![1, 2, 3, 4, 5],
+            review_imports: true,
+            weekly_goals: Vec::new(),
+            daily_budget_minutes: 120,
+            day_limit_entries: 5,
+            day_limit_minutes: 180,
+            check_updates: false,
+            completion: default_completion(),
+            ask_grade: true,
+            cors_origins: Vec::new(),
+        }
+    }
+}
+
+/// Tests and oral exams happen rather than get done
+pub fn default_completion() -> BTreeMap<String, Completion> {
+    ["verifica", "interrogazione"]
+        .into_iter()
+        .map(|t| (t.to_string(), Completion::Happened))
+        .collect()
+}
+
+/// Body of `PUT /api/settings`: only the fields that are present change
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SettingsUpdate {
+    pub default_view: Option<View>,
+    pub hide_completed: Option<bool>,
+    pub list_days_back: Option<u32>,
+    pub list_days_ahead: Option<u32>,
+    pub theme: Option<Theme>,
+    pub language: Option<Language>,
+    pub study_sessions: Option<bool>,
+    pub study_days_before: Option<u32>,
+    pub split_study_topics: Option<bool>,
+    pub study_session_position: Option<SessionPosition>,
+    pub homework_days_ahead: Option<u32>,
+    pub work_days: Option<Vec<u32>>,
+    pub review_imports: Option<bool>,
+    pub weekly_goals: Option<Vec<WeeklyGoal>>,
+    pub daily_budget_minutes: Option<u32>,
+    pub day_limit_entries: Option<u32>,
+    pub day_limit_minutes: Option<u32>,
+    pub check_updates: Option<bool>,
+    pub completion: Option<BTreeMap<String, Completion>>,
+    pub ask_grade: Option<bool>,
+    pub cors_origins: Option<Vec<String>>,
+}
+
+impl Settings {
+    /// Apply a partial update
+    pub fn apply(&mut self, update: SettingsUpdate) {
+        if let Some(v) = update.default_view {
+            self.default_view = v;
+        }
+        if let Some(v) = update.hide_completed {
+            self.hide_completed = v;
+        }
+        if let Some(v) = update.list_days_back {
+            self.list_days_back = v;
+        }
+        if let Some(v) = update.list_days_ahead {
+            self.list_days_ahead = v;
+        }
+        if let Some(v) = update.theme {
+            self.theme = v;
+        }
+        if let Some(v) = update.language {
+            self.language = v;
+        }
+        if let Some(v) = update.study_sessions {
+            self.study_sessions = v;
+        }
+        if let Some(v) = update.study_days_before {
+            self.study_days_before = v;
+        }
+        if let Some(v) = update.split_study_topics {
+            self.split_study_topics = v;
+        }
+        if let Some(v) = update.study_session_position {
+            self.study_session_position = v;
+        }
+        if let Some(v) = update.homework_days_ahead {
+            self.homework_days_ahead = v;
+        }
+        if let Some(v) = update.work_days {
+            self.work_days = v;
+        }
+        if let Some(v) = update.review_imports {
+            self.review_imports = v;
+        }
+        if let Some(v) = update.weekly_goals {
+            self.weekly_goals = v;
+        }
+        if let Some(v) = update.daily_budget_minutes {
+            self.daily_budget_minutes = v;
+        }
+        if let Some(v) = update.day_limit_entries {
+            self.day_limit_entries = v;
+        }
+        if let Some(v) = update.day_limit_minutes {
+            self.day_limit_minutes = v;
+        }
+        if let Some(v) = update.check_updates {
+            self.check_updates = v;
+        }
+        if let Some(v) = update.completion {
+            self.completion = v;
+        }
+        if let Some(v) = update.ask_grade {
+            self.ask_grade = v;
+        }
+        if let Some(v) = update.cors_origins {
+            self.cors_origins = v;
+        }
+    }
+
+    /// What ticking off an entry of `entry_type` means
+    pub fn completion_for(&self, entry_type: &str) -> Completion {
+        self.completion
+            .get(&entry_type.to_lowercase())
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// Types that complete as [`Completion::Happened`]
+    pub fn happened_types(&self) -> Vec<&str> {
+        self.completion
+            .iter()
+            .filter(|(_, c)| **c == Completion::Happened)
+            .map(|(t, _)| t.as_str())
+            .collect()
+    }
+}
+
+/// Whether `color` is a `#rrggbb` hex color, as entry color overrides are
+pub fn is_hex_color(color: &str) -> bool {
+    color.len() == 7 && color.starts_with('#') && color[1..].chars().all(|c| c.is_ascii_hexdigit())
+}
+
 impl Hash for HomeworkEntry {
     fn hash<H: Hasher>(&self, state: &mut H) {
         self.date.hash(state);
@@ -465,4 +1468,32 @@ mod tests {
         }
         assert_eq!(ids.len(), 100);
     }
+
+    #[test]
+    fn test_settings_update_applies_only_present_fields() {
+        let mut settings = Settings::default();
+        let update: SettingsUpdate =
+            serde_json::from_str(r#"{"default_view":"calendar","theme":"light"}"#).unwrap();
+        settings.apply(update);
+
+        assert_eq!(settings.default_view, View::Calendar);
+        assert_eq!(settings.theme, Theme::Light);
+        assert_eq!(settings.language, Language::En);
+        assert!(settings.study_sessions);
+        assert_eq!(settings.work_days, vec![1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn test_settings_update_rejects_unknown_values() {
+        assert!(serde_json::from_str::<SettingsUpdate>(r#"{"theme":"neon"}"#).is_err());
+        assert!(serde_json::from_str::<SettingsUpdate>(r#"{"colour":"red"}"#).is_err());
+    }
+
+    #[test]
+    fn test_settings_serialization() {
+        let json = serde_json::to_string(&Settings::default()).unwrap();
+        assert!(json.contains(r#""default_view":"list""#));
+        assert!(json.contains(r#""language":"en""#));
+        assert!(json.contains(r#""hide_completed":false"#));
+    }
 }
@@ -0,0 +1,179 @@
+//! Build info for `/api/version`, and a daily check for newer releases.
+//!
+//! With the `check_updates` setting on, the server asks the GitHub releases
+//! API for the latest release once a day, and the page shows a banner while
+//! it's newer than the running build. The request carries nothing but the
+//! version in its User-Agent. The setting is off by default, so GitHub is
+//! never contacted until the user turns it on. `COMPITUTTO_RELEASES_URL` points the check at
+//! a fork's releases instead.
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use tracing::{debug, info, warn};
+
+use crate::db;
+use crate::server::AppState;
+
+/// Version of the running build
+pub const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Commit the build came from (see `build.rs`), `unknown` outside a checkout
+pub const GIT_SHA: &str = env!("COMPITUTTO_GIT_SHA");
+
+/// GitHub API endpoint for the newest published release
+const RELEASES_URL: &str = "https://api.github.com/repos/erskingardner/diario/releases/latest";
+
+/// How often the server checks for a new release
+const CHECK_HOURS: u64 = 24;
+
+/// A published release.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Release {
+    /// Without the leading `v`: `0.2.0`
+    pub version: String,
+    /// Release notes page
+    pub url: String,
+}
+
+/// Response of `GET /api/version`.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct VersionInfo {
+    pub version: String,
+    pub git_sha: String,
+    /// Newest applied migration, e.g. `015_activities`
+    pub schema_version: Option<String>,
+    /// Latest release seen by the update check, if it has run
+    pub latest: Option<Release>,
+    pub update_available: bool,
+}
+
+impl VersionInfo {
+    pub fn new(schema_version: Option<String>, latest: Option<Release>) -> Self {
+        let update_available = latest
+            .as_ref()
+            .is_some_and(|release| is_newer(&release.version, VERSION));
+        Self {
+            version: VERSION.to_string(),
+            git_sha: GIT_SHA.to_string(),
+            schema_version,
+            latest,
+            update_available,
+        }
+    }
+}
+
+/// Whether `candidate` is a later version than `current`. Both may start
+/// with `v`; a pre-release (`1.2.0-rc.1`) is never newer than its release.
+pub fn is_newer(candidate: &str, current: &str) -> bool {
+    fn parse(version: &str) -> (Vec<u64>, bool) {
+        let version = version.trim().trim_start_matches('v');
+        let (numbers, pre) = match version.split_once('-') {
+            Some((numbers, _)) => (numbers, true),
+            None => (version, false),
+        };
+        let numbers = numbers.split('.').map(|n| n.parse().unwrap_or(0)).collect();
+        (numbers, pre)
+    }
+    let (mut new, new_pre) = parse(candidate);
+    let (mut old, old_pre) = parse(current);
+    let len = new.len().max(old.len());
+    new.resize(len, 0);
+    old.resize(len, 0);
+    new > old || (new == old && old_pre && !new_pre)
+}
+
+/// Read a release from the GitHub API's JSON.
+pub fn parse_release(json: &str) -> Result<Release> {
+    #[derive(Deserialize)]
+    struct GithubRelease {
+        tag_name: String,
+        html_url: String,
+    }
+    let release: GithubRelease =
+        serde_json::from_str(json).context("Unexpected release JSON from GitHub")?;
+    Ok(Release {
+        version: release.tag_name.trim_start_matches('v').to_string(),
+        url: release.html_url,
+    })
+}
+
+/// Check for a new release now and every [`CHECK_HOURS`], while the
+/// `check_updates` setting is on.
+pub fn start_update_check(state: Arc<AppState>) {
+    tokio::spawn(async move {
+        loop {
+            check(&state).await;
+            tokio::time::sleep(std::time::Duration::from_secs(CHECK_HOURS * 3600)).await;
+        }
+    });
+}
+
+async fn check(state: &AppState) {
+    let enabled = {
+        let conn = state.conn.lock().unwrap();
+        db::get_settings(&conn)
+            .map(|s| s.check_updates)
+            .unwrap_or(false)
+    };
+    if !enabled {
+        debug!("Update check is off");
+        return;
+    }
+    match fetch_latest().await {
+        Ok(release) => {
+            if is_newer(&release.version, VERSION) {
+                info!(latest = %release.version, current = VERSION, "A newer release is available");
+            }
+            *state.latest_release.lock().unwrap() = Some(release);
+        }
+        Err(e) => warn!(error = %e, "Update check failed"),
+    }
+}
+
+async fn fetch_latest() -> Result<Release> {
+    let url = std::env::var("COMPITUTTO_RELEASES_URL").unwrap_or_else(|_| RELEASES_URL.into());
+    let json = reqwest::Client::new()
+        .get(&url)
+        // GitHub's API rejects requests without a User-Agent
+        .header("User-Agent", format!("compitutto/{}", VERSION))
+        .header("Accept", "application/vnd.github+json")
+        .send()
+        .await
+        .with_context(|| format!("Failed to fetch {}", url))?
+        .error_for_status()
+        .with_context(|| format!("Failed to fetch {}", url))?
+        .text()
+        .await?;
+    parse_release(&json)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_newer() {
+        assert!(is_newer("0.2.0", "0.1.0"));
+        assert!(is_newer("v0.1.1", "0.1.0"));
+        assert!(is_newer("1.0", "0.9.9"));
+        assert!(is_newer("0.2.0", "0.2.0-rc.1"));
+        assert!(!is_newer("0.1.0", "0.1.0"));
+        assert!(!is_newer("v0.1.0", "0.1"));
+        assert!(!is_newer("0.1.0", "0.2.0"));
+        assert!(!is_newer("0.2.0-rc.1", "0.2.0"));
+    }
+
+    #[test]
+    fn test_parse_release_and_version_info() {
+        let json = r#"{"tag_name": "v9.0.0", "html_url": "https://github.com/erskingardner/diario/releases/tag/v9.0.0", "draft": false}"#;
+        let release = parse_release(json).unwrap();
+        assert_eq!(release.version, "9.0.0");
+
+        let info = VersionInfo::new(Some("015_activities".to_string()), Some(release));
+        assert_eq!(info.version, VERSION);
+        assert!(info.update_available);
+        assert!(!VersionInfo::new(None, None).update_available);
+        assert!(parse_release("<html>rate limited</html>").is_err());
+    }
+}
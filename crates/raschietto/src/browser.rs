@@ -1,15 +1,19 @@
 //! Playwright browser setup and management.
 
 use anyhow::{Context, Result};
-use playwright::api::{Browser, BrowserContext, Playwright};
-use std::path::PathBuf;
+use playwright::api::{Browser, BrowserContext, Playwright, StorageState};
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use crate::network::NetworkOptions;
+
 /// Browser configuration options.
 #[derive(Debug, Clone, Default)]
 pub struct BrowserOptions {
     /// Whether to show the browser window (false = headless).
     pub headed: bool,
+    /// Extra CAs, certificate checking and proxy.
+    pub network: NetworkOptions,
 }
 
 /// Wrapper around Playwright browser instance.
@@ -17,32 +21,67 @@ pub struct BrowserSession {
     #[allow(dead_code)]
     playwright: Arc<Playwright>,
     browser: Browser,
+    /// Whether contexts skip certificate checks (`--ignore-https-errors`)
+    ignore_https_errors: bool,
 }
 
-/// Find Chromium executable from npx-installed Playwright browsers.
+/// Environment variable naming a Chromium executable to use instead of
+/// searching the Playwright caches (e.g. a distro `chromium` on a Raspberry Pi).
+pub const CHROMIUM_ENV: &str = "RASCHIETTO_CHROMIUM";
+
+/// Find the Chromium executable to launch.
 ///
-/// Searches the standard Playwright browser cache locations for an installed
-/// Chromium browser. Returns the path to the executable if found.
-fn find_chromium_executable() -> Option<PathBuf> {
-    let home = std::env::var("HOME").ok()?;
-
-    // Check macOS Playwright cache location
-    let cache_dir = PathBuf::from(&home).join("Library/Caches/ms-playwright");
-    if let Some(path) = find_chromium_in_cache(&cache_dir) {
-        return Some(path);
+/// `RASCHIETTO_CHROMIUM` wins; otherwise the Playwright browser caches are
+/// searched, then (on Linux) the distro's Chromium.
+pub fn find_chromium_executable() -> Option<PathBuf> {
+    if let Some(path) = std::env::var_os(CHROMIUM_ENV) {
+        return Some(PathBuf::from(path));
     }
+    playwright_cache_dirs()
+        .iter()
+        .find_map(|dir| find_chromium_in_cache(dir))
+        .or_else(find_system_chromium)
+}
 
-    // Check Linux Playwright cache location
-    let linux_cache_dir = PathBuf::from(&home).join(".cache/ms-playwright");
-    if let Some(path) = find_chromium_in_cache(&linux_cache_dir) {
-        return Some(path);
+/// Where `npx playwright install` puts browsers on this machine.
+pub fn playwright_cache_dirs() -> Vec<PathBuf> {
+    let mut dirs = Vec::new();
+    if let Some(path) = std::env::var_os("PLAYWRIGHT_BROWSERS_PATH") {
+        dirs.push(PathBuf::from(path));
     }
-
-    None
+    if let Some(home) = std::env::var_os("HOME") {
+        let home = PathBuf::from(home);
+        // macOS
+        dirs.push(home.join("Library/Caches/ms-playwright"));
+        // Linux
+        dirs.push(home.join(".cache/ms-playwright"));
+    }
+    // Windows
+    if let Some(local) = std::env::var_os("LOCALAPPDATA") {
+        dirs.push(PathBuf::from(local).join("ms-playwright"));
+    }
+    dirs
 }
 
+/// Executable paths inside a `chromium-NNNN` directory, for every platform
+/// Playwright ships builds for. Newer Playwright releases use the `*64`
+/// directory names.
+const CHROMIUM_CANDIDATES: &[&str] = &[
+    // macOS ARM64
+    "chrome-mac-arm64/Google Chrome for Testing.app/Contents/MacOS/Google Chrome for Testing",
+    // macOS Intel
+    "chrome-mac-x64/Google Chrome for Testing.app/Contents/MacOS/Google Chrome for Testing",
+    "chrome-mac/Google Chrome for Testing.app/Contents/MacOS/Google Chrome for Testing",
+    // Linux (x64 and arm64)
+    "chrome-linux64/chrome",
+    "chrome-linux/chrome",
+    // Windows
+    "chrome-win64/chrome.exe",
+    "chrome-win/chrome.exe",
+];
+
 /// Search for Chromium in a Playwright cache directory.
-fn find_chromium_in_cache(cache_dir: &PathBuf) -> Option<PathBuf> {
+pub fn find_chromium_in_cache(cache_dir: &Path) -> Option<PathBuf> {
     if !cache_dir.exists() {
         return None;
     }
@@ -61,25 +100,22 @@ fn find_chromium_in_cache(cache_dir: &PathBuf) -> Option<PathBuf> {
     chromium_dirs.sort_by_key(|d| std::cmp::Reverse(d.file_name()));
 
     let chromium_dir = chromium_dirs.first()?;
+    CHROMIUM_CANDIDATES
+        .iter()
+        .map(|candidate| chromium_dir.path().join(candidate))
+        .find(|path| path.exists())
+}
 
-    // Try platform-specific paths
-    let candidates = [
-        // macOS ARM64
-        "chrome-mac-arm64/Google Chrome for Testing.app/Contents/MacOS/Google Chrome for Testing",
-        // macOS Intel
-        "chrome-mac/Google Chrome for Testing.app/Contents/MacOS/Google Chrome for Testing",
-        // Linux
-        "chrome-linux/chrome",
-    ];
-
-    for candidate in candidates {
-        let path = chromium_dir.path().join(candidate);
-        if path.exists() {
-            return Some(path);
-        }
+/// The distro's Chromium, which is what Raspberry Pi OS and other ARM
+/// boards ship.
+fn find_system_chromium() -> Option<PathBuf> {
+    if !cfg!(target_os = "linux") {
+        return None;
     }
-
-    None
+    ["/usr/bin/chromium", "/usr/bin/chromium-browser"]
+        .iter()
+        .map(PathBuf::from)
+        .find(|path| path.exists())
 }
 
 impl BrowserSession {
@@ -91,15 +127,20 @@ impl BrowserSession {
         let playwright = Arc::new(playwright);
 
         // Find Chromium from npx-installed Playwright browsers
-        let chromium_path = find_chromium_executable().context(
-            "Chromium not found. Run 'npm install && npx playwright install chromium' first.",
-        )?;
+        let chromium_path = find_chromium_executable()
+            .context("Chromium not found. Run 'raschietto doctor --fix' to install it.")?;
 
-        let browser = playwright
+        let args = options.network.chromium_args();
+        let mut launcher = playwright
             .chromium()
             .launcher()
             .headless(!options.headed)
             .executable(&chromium_path)
+            .args(&args);
+        if let Some(proxy) = options.network.proxy_settings() {
+            launcher = launcher.proxy(proxy);
+        }
+        let browser = launcher
             .launch()
             .await
             .context("Failed to launch Chromium browser")?;
@@ -107,6 +148,7 @@ impl BrowserSession {
         Ok(Self {
             playwright,
             browser,
+            ignore_https_errors: options.network.ignore_https_errors(),
         })
     }
 
@@ -117,6 +159,20 @@ impl BrowserSession {
         self.browser
             .context_builder()
             .accept_downloads(true)
+            .ignore_https_errors(self.ignore_https_errors)
+            .build()
+            .await
+            .context("Failed to create browser context")
+    }
+
+    /// Create a browser context that starts with a saved session's cookies
+    /// and local storage.
+    pub async fn restore_context(&self, state: StorageState) -> Result<BrowserContext> {
+        self.browser
+            .context_builder()
+            .accept_downloads(true)
+            .ignore_https_errors(self.ignore_https_errors)
+            .storage_state(state)
             .build()
             .await
             .context("Failed to create browser context")
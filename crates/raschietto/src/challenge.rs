@@ -0,0 +1,155 @@
+//! CAPTCHA and other challenge pages in front of the login.
+//!
+//! After too many logins (or from an unusual network) Classe Viva may put a
+//! CAPTCHA or a "checking your browser" page where the login form should be.
+//! Nothing automatic can get past it, so instead of timing out on the
+//! username field the fetch stops right away with a [`ChallengeDetected`]
+//! error and a screenshot, and raschietto exits with [`EXIT_CODE`] so a
+//! cron wrapper can back off (or run `fetch --manual-login`) instead of
+//! retrying into a lockout.
+//!
+//! A page counts as a challenge when it shows an element matching the
+//! profile's `challenge` selector, or when its title, text or frame URLs
+//! contain one of [`MARKERS`]. Both only count what the user would see:
+//! invisible reCAPTCHA sits on ordinary login pages, with a hidden widget, a
+//! badge in the corner and a "protected by reCAPTCHA" line in the footer.
+
+use std::fmt;
+use std::path::PathBuf;
+
+/// Exit status of a fetch stopped by a challenge page (1 is any other
+/// failure, 2 a usage error)
+pub const EXIT_CODE: i32 = 3;
+
+/// Whether `el` takes up room on the page, outside the invisible
+/// reCAPTCHA badge. `getClientRects` rather than `offsetParent`, which is
+/// null for fixed-position overlays.
+const IS_SHOWN_JS: &str = r#"
+    const isShown = el => {
+        const rect = el.getBoundingClientRect();
+        return el.getClientRects().length > 0
+            && rect.width > 1 && rect.height > 1
+            && getComputedStyle(el).visibility !== 'hidden'
+            && !el.closest('.grecaptcha-badge')
+            && !(el.src || '').includes('size=invisible');
+    };
+"#;
+
+/// Title, visible text and the URLs of visible frames, for [`sniff`]:
+/// CAPTCHA widgets live in iframes whose text isn't the page's.
+pub fn page_text_js() -> String {
+    format!(
+        r#"() => {{
+            {IS_SHOWN_JS}
+            return [
+                document.title,
+                document.body ? document.body.innerText : '',
+                ...[...document.querySelectorAll('iframe[src]')].filter(isShown).map(el => el.src),
+            ].join('\n');
+        }}"#
+    )
+}
+
+/// Whether any element matching the selector passed in is visible
+pub fn shown_js() -> String {
+    format!(
+        r#"(selector) => {{
+            {IS_SHOWN_JS}
+            return [...document.querySelectorAll(selector)].some(isShown);
+        }}"#
+    )
+}
+
+/// Text that gives a challenge page away, and what to call it. The widget
+/// names only count as the URL of a visible frame: the bare word is on
+/// plenty of pages that aren't a challenge.
+pub const MARKERS: &[(&str, &str)] = &[
+    ("google.com/recaptcha", "reCAPTCHA"),
+    ("recaptcha.net/recaptcha", "reCAPTCHA"),
+    ("hcaptcha.com", "hCaptcha"),
+    ("challenges.cloudflare.com", "Cloudflare challenge"),
+    ("checking your browser", "browser check"),
+    ("verify you are human", "human verification"),
+    ("i'm not a robot", "CAPTCHA"),
+    ("non sono un robot", "CAPTCHA"),
+];
+
+/// What kind of challenge `page_text` shows, if any
+pub fn sniff(page_text: &str) -> Option<&'static str> {
+    let text = page_text.to_lowercase();
+    MARKERS
+        .iter()
+        .find(|(marker, _)| text.contains(marker))
+        .map(|&(_, kind)| kind)
+}
+
+/// The portal showed a challenge page instead of the login or the agenda.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChallengeDetected {
+    pub kind: String,
+    pub url: String,
+    /// Screenshot taken when it was found
+    pub screenshot: Option<PathBuf>,
+}
+
+impl fmt::Display for ChallengeDetected {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "The portal is showing a {} at {}; log in once with `fetch --manual-login`",
+            self.kind, self.url
+        )
+    }
+}
+
+impl std::error::Error for ChallengeDetected {}
+
+/// The challenge that stopped a run, anywhere in `error`'s context chain
+pub fn find(error: &anyhow::Error) -> Option<&ChallengeDetected> {
+    error
+        .chain()
+        .find_map(|cause| cause.downcast_ref::<ChallengeDetected>())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Context;
+
+    #[test]
+    fn test_sniff() {
+        assert_eq!(
+            sniff("Accedi\nhttps://www.google.com/recaptcha/api2/anchor?k=x"),
+            Some("reCAPTCHA")
+        );
+        assert_eq!(
+            sniff("Just a moment...\nChecking your browser before accessing"),
+            Some("browser check")
+        );
+        assert_eq!(sniff("Conferma di NON essere un robot"), Some("CAPTCHA"));
+        assert_eq!(sniff("Classe Viva\nCodice personale\nPassword"), None);
+        // A login page with invisible reCAPTCHA, or one that mentions it
+        assert_eq!(
+            sniff("Accedi\nThis site is protected by reCAPTCHA and the Google Privacy Policy"),
+            None
+        );
+        assert_eq!(sniff("Il captcha è scaduto, ricarica la pagina"), None);
+        assert_eq!(
+            sniff("Accedi\nhttps://newassets.hcaptcha.com/captcha/v1/abc/static/hcaptcha.html"),
+            Some("hCaptcha")
+        );
+    }
+
+    #[test]
+    fn test_find_through_context() {
+        let error = anyhow::Error::new(ChallengeDetected {
+            kind: "reCAPTCHA".to_string(),
+            url: "https://web.spaggiari.eu/home/app/default/login.php".to_string(),
+            screenshot: None,
+        });
+        let error = Err::<(), _>(error).context("Login failed").unwrap_err();
+        assert_eq!(find(&error).unwrap().kind, "reCAPTCHA");
+        assert!(format!("{:#}", error).contains("--manual-login"));
+        assert!(find(&anyhow::anyhow!("Login form did not appear")).is_none());
+    }
+}
@@ -0,0 +1,429 @@
+//! What changed in the agenda since the previous fetch.
+//!
+//! After each agenda export, `fetch` reads the export's rows and compares
+//! them with the rows saved by the previous run of the same profile:
+//!
+//! ```text
+//! data/export_20250115_073005.xls
+//! data/.raschietto-classeviva.manifest.json
+//! ```
+//!
+//! Only dates inside both runs' ranges are compared, so moving the range
+//! forward doesn't report every new day as added. A row whose date and
+//! subject match a vanished one but whose text differs is reported as
+//! changed. The manifest starts with a `.` so compitutto's watcher ignores it.
+//!
+//! Only SpreadsheetML exports (what Classe Viva sends) can be read; other
+//! formats skip the comparison.
+
+use anyhow::{bail, Context, Result};
+use chrono::{NaiveDate, NaiveDateTime};
+use quick_xml::events::Event;
+use quick_xml::reader::Reader;
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+
+use crate::download::{self, ExportFormat};
+use crate::scraper::DateRange;
+
+/// One agenda row, as exported.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, PartialOrd, Ord)]
+pub struct ExportRow {
+    pub date: NaiveDate,
+    pub subject: String,
+    /// The `tipo` column: "compiti", "nota", …
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub task: String,
+}
+
+/// Rows of the last agenda export, kept for the next run to compare with.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Manifest {
+    /// Export file name
+    pub file: String,
+    pub fetched: NaiveDateTime,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub rows: Vec<ExportRow>,
+}
+
+/// Rows added, removed and edited between two exports, by date.
+#[derive(Debug, Default, PartialEq)]
+pub struct Diff {
+    pub added: Vec<ExportRow>,
+    pub removed: Vec<ExportRow>,
+    /// `(before, after)`
+    pub changed: Vec<(ExportRow, ExportRow)>,
+}
+
+/// Where `profile`'s manifest lives in `output_dir`.
+pub fn manifest_path(output_dir: &Path, profile: &str) -> PathBuf {
+    output_dir.join(format!(".raschietto-{}.manifest.json", profile))
+}
+
+impl Manifest {
+    /// Load a manifest; `None` if there isn't one yet.
+    pub fn load(path: &Path) -> Result<Option<Self>> {
+        if !path.exists() {
+            return Ok(None);
+        }
+        let json =
+            std::fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+        let manifest =
+            serde_json::from_str(&json).with_context(|| format!("Failed to parse {:?}", path))?;
+        Ok(Some(manifest))
+    }
+
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        crate::download::write_atomic(path, json.as_bytes())
+    }
+
+    /// Rows dated between `from` and `to`, inclusive
+    fn rows_between(&self, from: NaiveDate, to: NaiveDate) -> Vec<&ExportRow> {
+        self.rows
+            .iter()
+            .filter(|r| r.date >= from && r.date <= to)
+            .collect()
+    }
+}
+
+/// Compare two runs over the dates both of them covered.
+pub fn diff(previous: &Manifest, current: &Manifest) -> Diff {
+    let from = previous.from.max(current.from);
+    let to = previous.to.min(current.to);
+    let before = previous.rows_between(from, to);
+    let mut after = current.rows_between(from, to);
+
+    // Identical rows are unchanged, however many times they appear
+    let mut gone = Vec::new();
+    for old in before {
+        match after.iter().position(|new| *new == old) {
+            Some(i) => {
+                after.remove(i);
+            }
+            None => gone.push(old),
+        }
+    }
+
+    // What's left on the same day and subject was edited
+    let mut diff = Diff::default();
+    for old in gone {
+        match after
+            .iter()
+            .position(|new| new.date == old.date && new.subject == old.subject)
+        {
+            Some(i) => diff.changed.push((old.clone(), after.remove(i).clone())),
+            None => diff.removed.push(old.clone()),
+        }
+    }
+    diff.added = after.into_iter().cloned().collect();
+    diff.added.sort();
+    diff.removed.sort();
+    diff.changed.sort();
+    diff
+}
+
+impl Diff {
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// One line per row: `+` added, `-` removed, `~` changed.
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        for row in &self.added {
+            let _ = writeln!(out, "  + {}", describe(row));
+        }
+        for row in &self.removed {
+            let _ = writeln!(out, "  - {}", describe(row));
+        }
+        for (old, new) in &self.changed {
+            let _ = writeln!(out, "  ~ {} → {}", describe(old), new.task);
+        }
+        out
+    }
+}
+
+fn describe(row: &ExportRow) -> String {
+    format!("{} {} ({}): {}", row.date, row.subject, row.kind, row.task)
+}
+
+/// Read the agenda rows of a downloaded export.
+pub fn read_rows(bytes: &[u8]) -> Result<Vec<ExportRow>> {
+    if download::sniff(bytes) != Some(ExportFormat::SpreadsheetMl) {
+        bail!("Only SpreadsheetML exports can be compared");
+    }
+    let xml = String::from_utf8_lossy(bytes);
+    let mut cells = spreadsheet_rows(&xml)?.into_iter();
+    let Some(headers) = cells.next() else {
+        return Ok(Vec::new());
+    };
+    let columns = Columns::find(&headers)?;
+    Ok(cells.filter_map(|row| columns.row(&row)).collect())
+}
+
+/// Column indices of the fields we compare, from the header row
+struct Columns {
+    date: usize,
+    subject: Option<usize>,
+    kind: Option<usize>,
+    task: Option<usize>,
+}
+
+impl Columns {
+    /// Same header names compitutto's parser looks for
+    fn find(headers: &[String]) -> Result<Self> {
+        let find = |matches: &dyn Fn(&str) -> bool| {
+            headers
+                .iter()
+                .position(|h| matches(&h.trim().to_lowercase()))
+        };
+        Ok(Self {
+            date: find(&|h| h.contains("data") || h.contains("inizio") || h.contains("date"))
+                .context("Export has no date column")?,
+            subject: find(&|h| h.contains("materia") || h.contains("subject")),
+            kind: find(&|h| h == "tipo" || (h.contains("tipo") && !h.contains("evento"))),
+            task: find(&|h| h.contains("nota") || h.contains("descrizione") || h.contains("task")),
+        })
+    }
+
+    fn row(&self, cells: &[String]) -> Option<ExportRow> {
+        let cell = |i: Option<usize>| {
+            i.and_then(|i| cells.get(i))
+                .map(|s| s.trim().to_string())
+                .unwrap_or_default()
+        };
+        let date = cell(Some(self.date));
+        // "2025-01-15" or "2025-01-15 08:00:00"
+        let date = NaiveDate::parse_from_str(date.get(..10)?, "%Y-%m-%d").ok()?;
+        let row = ExportRow {
+            date,
+            subject: cell(self.subject),
+            kind: cell(self.kind),
+            task: cell(self.task),
+        };
+        (!row.subject.is_empty() || !row.task.is_empty()).then_some(row)
+    }
+}
+
+/// Cell texts of each `<Row>`, one per `<Cell>`
+fn spreadsheet_rows(xml: &str) -> Result<Vec<Vec<String>>> {
+    let mut reader = Reader::from_str(xml);
+    let mut rows = Vec::new();
+    let mut row: Vec<String> = Vec::new();
+    // Text of the current cell's `<Data>`, once it starts
+    let mut cell: Option<String> = None;
+    let mut in_data = false;
+    loop {
+        match reader.read_event().context("Export is not valid XML")? {
+            Event::Start(e) => match e.local_name().as_ref() {
+                b"Row" => row.clear(),
+                b"Cell" => cell = None,
+                b"Data" => {
+                    in_data = true;
+                    cell = Some(String::new());
+                }
+                _ => {}
+            },
+            Event::Empty(e) if e.local_name().as_ref() == b"Cell" => row.push(String::new()),
+            Event::Text(e) if in_data => {
+                if let Some(text) = cell.as_mut() {
+                    text.push_str(&e.decode()?);
+                }
+            }
+            Event::GeneralRef(e) if in_data => {
+                if let Some(text) = cell.as_mut() {
+                    text.push_str(match e.decode()?.as_ref() {
+                        "amp" => "&",
+                        "lt" => "<",
+                        "gt" => ">",
+                        "quot" => "\"",
+                        "apos" => "'",
+                        _ => "",
+                    });
+                }
+            }
+            Event::End(e) => match e.local_name().as_ref() {
+                b"Data" => in_data = false,
+                b"Cell" => row.push(cell.take().unwrap_or_default()),
+                b"Row" if !row.is_empty() => rows.push(std::mem::take(&mut row)),
+                _ => {}
+            },
+            Event::Eof => break,
+            _ => {}
+        }
+    }
+    Ok(rows)
+}
+
+/// Compare the export at `path` with the previous run's and save it as the
+/// new manifest. `None` on the first run.
+pub fn compare_with_previous(
+    output_dir: &Path,
+    profile: &str,
+    range: &DateRange,
+    path: &Path,
+) -> Result<Option<Diff>> {
+    let bytes = std::fs::read(path).with_context(|| format!("Failed to read {:?}", path))?;
+    let current = Manifest {
+        file: path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default(),
+        fetched: chrono::Local::now().naive_local(),
+        from: range.from,
+        to: range.to,
+        rows: read_rows(&bytes)?,
+    };
+    let manifest = manifest_path(output_dir, profile);
+    let diff = Manifest::load(&manifest)?.map(|previous| diff(&previous, &current));
+    current.write(&manifest)?;
+    Ok(diff)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(date: &str, subject: &str, task: &str) -> ExportRow {
+        ExportRow {
+            date: date.parse().unwrap(),
+            subject: subject.to_string(),
+            kind: "compiti".to_string(),
+            task: task.to_string(),
+        }
+    }
+
+    fn manifest(from: &str, to: &str, rows: Vec<ExportRow>) -> Manifest {
+        Manifest {
+            file: "export.xls".to_string(),
+            fetched: "2025-01-15T07:30:00".parse().unwrap(),
+            from: from.parse().unwrap(),
+            to: to.parse().unwrap(),
+            rows,
+        }
+    }
+
+    #[test]
+    fn test_read_rows_spreadsheet_ml() {
+        let xml = br#"<?xml version="1.0"?>
+<Workbook xmlns="urn:schemas-microsoft-com:office:spreadsheet" xmlns:ss="urn:schemas-microsoft-com:office:spreadsheet">
+<Worksheet ss:Name="Table1"><Table>
+<Row><Cell><Data ss:Type="String">tipo_evento</Data></Cell><Cell><Data ss:Type="String">data_inizio</Data></Cell><Cell><Data ss:Type="String">nota</Data></Cell><Cell><Data ss:Type="String">tipo</Data></Cell><Cell><Data ss:Type="String">materia</Data></Cell></Row>
+<Row><Cell><Data ss:Type="String">Nota Agenda</Data></Cell><Cell><Data ss:Type="String">2025-01-13 08:00:00</Data></Cell><Cell><Data ss:Type="String">Es. 4 &amp; 5</Data></Cell><Cell><Data ss:Type="String">compiti</Data></Cell><Cell><Data ss:Type="String">MATEMATICA</Data></Cell></Row>
+<Row><Cell/><Cell><Data ss:Type="String">2025-01-14</Data></Cell><Cell><Data ss:Type="String"/></Cell><Cell/><Cell/></Row>
+</Table></Worksheet>
+</Workbook>
+"#;
+        let rows = read_rows(xml).unwrap();
+        assert_eq!(
+            rows,
+            vec![ExportRow {
+                date: "2025-01-13".parse().unwrap(),
+                subject: "MATEMATICA".to_string(),
+                kind: "compiti".to_string(),
+                task: "Es. 4 & 5".to_string(),
+            }]
+        );
+
+        assert!(read_rows(b"PK\x03\x04").is_err());
+    }
+
+    #[test]
+    fn test_diff_added_removed_changed() {
+        let previous = manifest(
+            "2025-01-10",
+            "2025-01-20",
+            vec![
+                row("2025-01-12", "Storia", "Cap. 3"),
+                row("2025-01-14", "Matematica", "Pag. 10"),
+                row("2025-01-15", "Italiano", "Tema"),
+                row("2025-01-15", "Italiano", "Tema"),
+            ],
+        );
+        let current = manifest(
+            "2025-01-10",
+            "2025-01-20",
+            vec![
+                row("2025-01-14", "Matematica", "Pag. 10 es. 1-5"),
+                row("2025-01-15", "Italiano", "Tema"),
+                row("2025-01-16", "Inglese", "Unit 4"),
+            ],
+        );
+
+        let diff = diff(&previous, &current);
+        assert_eq!(diff.added, vec![row("2025-01-16", "Inglese", "Unit 4")]);
+        assert_eq!(
+            diff.removed,
+            vec![
+                row("2025-01-12", "Storia", "Cap. 3"),
+                row("2025-01-15", "Italiano", "Tema"),
+            ]
+        );
+        assert_eq!(
+            diff.changed,
+            vec![(
+                row("2025-01-14", "Matematica", "Pag. 10"),
+                row("2025-01-14", "Matematica", "Pag. 10 es. 1-5"),
+            )]
+        );
+        assert_eq!(
+            diff.render().lines().next().unwrap(),
+            "  + 2025-01-16 Inglese (compiti): Unit 4"
+        );
+    }
+
+    #[test]
+    fn test_diff_only_compares_overlapping_dates() {
+        let previous = manifest(
+            "2025-01-01",
+            "2025-01-15",
+            vec![row("2025-01-02", "Storia", "Cap. 1")],
+        );
+        let current = manifest(
+            "2025-01-08",
+            "2025-01-22",
+            vec![row("2025-01-20", "Storia", "Cap. 2")],
+        );
+        assert!(diff(&previous, &current).is_empty());
+    }
+
+    #[test]
+    fn test_compare_with_previous_writes_manifest() {
+        let dir = std::env::temp_dir().join(format!("raschietto-changes-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        let export = |task: &str| {
+            format!(
+                "<?xml version=\"1.0\"?>\n<Workbook><Table>\
+                 <Row><Cell><Data>data</Data></Cell><Cell><Data>materia</Data></Cell><Cell><Data>nota</Data></Cell></Row>\
+                 <Row><Cell><Data>2025-01-14</Data></Cell><Cell><Data>STORIA</Data></Cell><Cell><Data>{}</Data></Cell></Row>\
+                 </Table></Workbook>\n",
+                task
+            )
+        };
+        let range = DateRange::new("2025-01-10".parse().unwrap(), "2025-01-20".parse().unwrap());
+        let path = dir.join("export_a.xls");
+
+        std::fs::write(&path, export("Cap. 3")).unwrap();
+        assert!(compare_with_previous(&dir, "classeviva", &range, &path)
+            .unwrap()
+            .is_none());
+        std::fs::write(&path, export("Cap. 4")).unwrap();
+        let diff = compare_with_previous(&dir, "classeviva", &range, &path)
+            .unwrap()
+            .unwrap();
+        assert_eq!(diff.changed.len(), 1);
+        assert_eq!(diff.changed[0].1.task, "Cap. 4");
+
+        let saved = Manifest::load(&manifest_path(&dir, "classeviva"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.file, "export_a.xls");
+        assert_eq!(saved.rows[0].task, "Cap. 4");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,9 +1,13 @@
 //! Configuration loading from environment variables.
 
-use anyhow::{Context, Result};
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::credentials::Vault;
 
 /// Classe Viva credentials loaded from environment.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Credentials {
     pub username: String,
     pub password: String,
@@ -26,6 +30,25 @@ impl Credentials {
 
         Ok(Self { username, password })
     }
+
+    /// Load credentials from the environment when both variables are set,
+    /// or else from `vault` (`raschietto credentials set`).
+    pub fn load(vault: &Vault) -> Result<Self> {
+        if let Ok(credentials) = Self::from_env() {
+            debug!("Using credentials from the environment");
+            return Ok(credentials);
+        }
+        match vault.load()? {
+            Some((credentials, store)) => {
+                debug!("Using credentials from {}", store.describe());
+                Ok(credentials)
+            }
+            None => bail!(
+                "No credentials: set CLASSEVIVA_USER and CLASSEVIVA_PASSWORD, \
+                 or run `raschietto credentials set`"
+            ),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,318 @@
+//! Keeping the portal login somewhere safer than `CLASSEVIVA_PASSWORD`:
+//! `raschietto credentials set/get/delete`.
+//!
+//! Two stores, one login per portal profile in each:
+//!
+//! - the OS keychain (macOS Keychain, Windows Credential Manager, the Secret
+//!   Service on Linux), through the `keyring` crate, under the service
+//!   `raschietto`;
+//! - an [age](https://age-encryption.org)-encrypted file,
+//!   `.raschietto/<profile>.credentials.age` by default, for machines
+//!   without a keychain (a headless server running cron). It's unlocked by a
+//!   key file (`--key-file`, an age identity: `set` creates one if it
+//!   doesn't exist) or else by a passphrase, from `RASCHIETTO_PASSPHRASE` or
+//!   asked for on the terminal. The file is age's own format, so
+//!   `age -d -i <key file>` opens it too.
+//!
+//! `CLASSEVIVA_USER` and `CLASSEVIVA_PASSWORD`, when both are set, still win
+//! (see `Credentials::load`), then the keychain, then the file.
+
+use age::secrecy::{ExposeSecret, SecretString};
+use anyhow::{anyhow, bail, Context, Result};
+use clap::ValueEnum;
+use std::fs;
+use std::io::{BufRead, IsTerminal, Write};
+use std::path::{Path, PathBuf};
+use tracing::{debug, warn};
+
+use crate::config::Credentials;
+use crate::session::{self, SESSION_DIR};
+
+/// Keychain service the logins are saved under, one account per profile
+pub const KEYRING_SERVICE: &str = "raschietto";
+
+/// Passphrase for the encrypted file, for runs with nobody to ask
+pub const PASSPHRASE_VAR: &str = "RASCHIETTO_PASSPHRASE";
+
+/// Default encrypted file for `profile`
+pub fn default_file(profile: &str) -> PathBuf {
+    Path::new(SESSION_DIR).join(format!("{}.credentials.age", profile))
+}
+
+/// Where a login is stored
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum Store {
+    /// The OS keychain
+    Keychain,
+    /// An age-encrypted file
+    File,
+}
+
+impl Store {
+    pub fn describe(self) -> &'static str {
+        match self {
+            Store::Keychain => "the OS keychain",
+            Store::File => "the encrypted file",
+        }
+    }
+}
+
+/// What locks the encrypted file
+enum Key {
+    Passphrase(String),
+    Identity(age::x25519::Identity),
+}
+
+/// The stored logins of one profile
+pub struct Vault {
+    profile: String,
+    file: PathBuf,
+    key_file: Option<PathBuf>,
+}
+
+impl Vault {
+    pub fn new(profile: &str, file: PathBuf, key_file: Option<PathBuf>) -> Self {
+        Self {
+            profile: profile.to_string(),
+            file,
+            key_file,
+        }
+    }
+
+    /// The keychain's login, or else the file's; `None` when neither has
+    /// one. A keychain that can't be reached counts as empty.
+    pub fn load(&self) -> Result<Option<(Credentials, Store)>> {
+        if let Some(credentials) = self.load_keychain()? {
+            return Ok(Some((credentials, Store::Keychain)));
+        }
+        if !self.file.exists() {
+            return Ok(None);
+        }
+        let encrypted =
+            fs::read(&self.file).with_context(|| format!("Failed to read {:?}", self.file))?;
+        let key = self.key(false)?;
+        let credentials =
+            decrypt(&encrypted, &key).with_context(|| format!("Couldn't open {:?}", self.file))?;
+        Ok(Some((credentials, Store::File)))
+    }
+
+    pub fn save(&self, store: Store, credentials: &Credentials) -> Result<()> {
+        match store {
+            Store::Keychain => {
+                let json = serde_json::to_string(credentials)?;
+                self.keychain()?
+                    .set_password(&json)
+                    .context("Couldn't save to the OS keychain (try --store file)")
+            }
+            Store::File => {
+                let key = self.key(true)?;
+                session::write_private(&self.file, &encrypt(credentials, &key)?)
+            }
+        }
+    }
+
+    /// Remove the login from `store`. Whether there was one.
+    pub fn delete(&self, store: Store) -> Result<bool> {
+        match store {
+            Store::Keychain => match self.keychain()?.delete_credential() {
+                Ok(()) => Ok(true),
+                Err(keyring::Error::NoEntry) => Ok(false),
+                Err(e) => Err(e).context("Couldn't delete from the OS keychain"),
+            },
+            Store::File if self.file.exists() => {
+                fs::remove_file(&self.file)
+                    .with_context(|| format!("Failed to delete {:?}", self.file))?;
+                Ok(true)
+            }
+            Store::File => Ok(false),
+        }
+    }
+
+    pub fn file(&self) -> &Path {
+        &self.file
+    }
+
+    fn keychain(&self) -> Result<keyring::Entry> {
+        keyring::Entry::new(KEYRING_SERVICE, &self.profile).context("Couldn't open the OS keychain")
+    }
+
+    fn load_keychain(&self) -> Result<Option<Credentials>> {
+        let json = match keyring::Entry::new(KEYRING_SERVICE, &self.profile)
+            .and_then(|entry| entry.get_password())
+        {
+            Ok(json) => json,
+            Err(keyring::Error::NoEntry) => return Ok(None),
+            Err(e) => {
+                debug!("OS keychain not available: {}", e);
+                return Ok(None);
+            }
+        };
+        serde_json::from_str(&json)
+            .map(Some)
+            .context("The keychain's raschietto entry isn't a login saved by raschietto")
+    }
+
+    /// The key file's identity, or a passphrase. Saving creates a missing
+    /// key file and asks for a new passphrase twice.
+    fn key(&self, saving: bool) -> Result<Key> {
+        if let Some(path) = &self.key_file {
+            if saving && !path.exists() {
+                let identity = age::x25519::Identity::generate();
+                session::write_private(path, key_file_contents(&identity).as_bytes())?;
+                warn!("Created key file {:?}; keep a copy somewhere safe", path);
+                return Ok(Key::Identity(identity));
+            }
+            let contents =
+                fs::read_to_string(path).with_context(|| format!("Failed to read {:?}", path))?;
+            return parse_identity(&contents)
+                .map(Key::Identity)
+                .with_context(|| format!("Invalid key file {:?}", path));
+        }
+        if let Some(passphrase) = std::env::var(PASSPHRASE_VAR).ok().filter(|p| !p.is_empty()) {
+            return Ok(Key::Passphrase(passphrase));
+        }
+        if !std::io::stdin().is_terminal() {
+            bail!(
+                "{:?} needs a passphrase: set {} or use --key-file",
+                self.file,
+                PASSPHRASE_VAR
+            );
+        }
+        let passphrase = rpassword::prompt_password("Passphrase: ")?;
+        if saving && rpassword::prompt_password("Passphrase again: ")? != passphrase {
+            bail!("The passphrases don't match");
+        }
+        if passphrase.is_empty() {
+            bail!("The passphrase is empty");
+        }
+        Ok(Key::Passphrase(passphrase))
+    }
+}
+
+/// A key file as `age-keygen` writes it
+fn key_file_contents(identity: &age::x25519::Identity) -> String {
+    format!(
+        "# created: {}\n# public key: {}\n{}\n",
+        chrono::Local::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, false),
+        identity.to_public(),
+        identity.to_string().expose_secret()
+    )
+}
+
+/// The identity in a key file; `#` lines are comments
+fn parse_identity(contents: &str) -> Result<age::x25519::Identity> {
+    let line = contents
+        .lines()
+        .map(str::trim)
+        .find(|line| !line.is_empty() && !line.starts_with('#'))
+        .ok_or_else(|| anyhow!("no key in it"))?;
+    line.parse().map_err(|e: &str| anyhow!("{}", e))
+}
+
+fn encrypt(credentials: &Credentials, key: &Key) -> Result<Vec<u8>> {
+    let json = serde_json::to_vec(credentials)?;
+    match key {
+        Key::Passphrase(passphrase) => {
+            let recipient = age::scrypt::Recipient::new(SecretString::from(passphrase.clone()));
+            age::encrypt(&recipient, &json)
+        }
+        Key::Identity(identity) => age::encrypt(&identity.to_public(), &json),
+    }
+    .context("Failed to encrypt the login")
+}
+
+fn decrypt(encrypted: &[u8], key: &Key) -> Result<Credentials> {
+    let json = match key {
+        Key::Passphrase(passphrase) => {
+            let identity = age::scrypt::Identity::new(SecretString::from(passphrase.clone()));
+            age::decrypt(&identity, encrypted)
+        }
+        Key::Identity(identity) => age::decrypt(identity, encrypted),
+    }
+    .context("wrong passphrase or key file?")?;
+    serde_json::from_slice(&json).context("It doesn't hold a login")
+}
+
+/// A line from the terminal (shown) or from stdin when it's piped
+pub fn read_line(prompt: &str) -> Result<String> {
+    if std::io::stdin().is_terminal() {
+        eprint!("{}", prompt);
+        std::io::stderr().flush()?;
+    }
+    let mut line = String::new();
+    std::io::stdin().lock().read_line(&mut line)?;
+    Ok(line.trim().to_string())
+}
+
+/// A password from the terminal (not echoed) or from stdin when it's piped
+pub fn read_password(prompt: &str) -> Result<String> {
+    if std::io::stdin().is_terminal() {
+        Ok(rpassword::prompt_password(prompt)?)
+    } else {
+        let mut line = String::new();
+        std::io::stdin().lock().read_line(&mut line)?;
+        Ok(line.trim_end_matches(['\r', '\n']).to_string())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn login() -> Credentials {
+        Credentials {
+            username: "S1234567".to_string(),
+            password: "correct horse".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_encrypt_with_key_file() {
+        let identity = age::x25519::Identity::generate();
+        let contents = key_file_contents(&identity);
+        assert!(contents.starts_with("# created: "));
+        let key = Key::Identity(parse_identity(&contents).unwrap());
+
+        let encrypted = encrypt(&login(), &key).unwrap();
+        assert!(!String::from_utf8_lossy(&encrypted).contains("correct horse"));
+        let decrypted = decrypt(&encrypted, &key).unwrap();
+        assert_eq!(decrypted.username, "S1234567");
+        assert_eq!(decrypted.password, "correct horse");
+
+        let other = Key::Identity(age::x25519::Identity::generate());
+        assert!(decrypt(&encrypted, &other).is_err());
+        assert!(parse_identity("# just a comment\n").is_err());
+        assert!(parse_identity("not a key").is_err());
+    }
+
+    #[test]
+    fn test_encrypt_with_passphrase() {
+        let key = Key::Passphrase("hunter2".to_string());
+        let encrypted = encrypt(&login(), &key).unwrap();
+        assert_eq!(decrypt(&encrypted, &key).unwrap().password, "correct horse");
+        assert!(decrypt(&encrypted, &Key::Passphrase("hunter3".to_string())).is_err());
+    }
+
+    #[test]
+    fn test_file_store() {
+        let dir =
+            std::env::temp_dir().join(format!("raschietto-credentials-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let vault = Vault::new(
+            "test",
+            dir.join("test.credentials.age"),
+            Some(dir.join("key.txt")),
+        );
+        vault.save(Store::File, &login()).unwrap();
+        // The key file was created for it
+        assert!(dir.join("key.txt").exists());
+
+        let encrypted = fs::read(vault.file()).unwrap();
+        let key = vault.key(false).unwrap();
+        assert_eq!(decrypt(&encrypted, &key).unwrap().username, "S1234567");
+
+        assert!(vault.delete(Store::File).unwrap());
+        assert!(!vault.delete(Store::File).unwrap());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,266 @@
+//! Full entry details scraped from the agenda's detail popups.
+//!
+//! The Excel export truncates long notes, so `fetch --details` also opens each
+//! agenda item in the range and writes what the popup shows to a JSON sidecar
+//! next to the export:
+//!
+//! ```text
+//! data/export_20250115_073005.xls
+//! data/export_20250115_073005.details.json
+//! ```
+//!
+//! Photo attachments (whiteboard pictures) are downloaded to
+//! `data/attachments/` so compitutto can OCR them.
+//!
+//! compitutto merges the sidecar into the matching entries' tasks when it
+//! parses the export.
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::HashSet;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+use crate::scraper::DateRange;
+
+/// Contents of a `.details.json` sidecar.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct DetailsFile {
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub entries: Vec<EntryDetail>,
+}
+
+/// One agenda item as shown in its detail popup.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct EntryDetail {
+    pub date: NaiveDate,
+    pub subject: String,
+    /// Full note text
+    pub text: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub attachments: Vec<Attachment>,
+}
+
+/// A file linked from the detail popup. Only photos are downloaded.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Attachment {
+    pub name: String,
+    pub url: String,
+    /// Local copy of a photo, relative to the sidecar's directory
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+}
+
+/// Extensions of attachments worth downloading for OCR
+const PHOTO_EXTENSIONS: &[&str] = &["jpg", "jpeg", "png", "webp", "gif", "bmp", "tif", "tiff"];
+
+/// Directory for downloaded photos, next to the sidecars
+pub const ATTACHMENTS_DIR: &str = "attachments";
+
+impl Attachment {
+    /// The photo's extension, judging by its name or else its URL
+    fn photo_extension(&self) -> Option<String> {
+        [
+            self.name.as_str(),
+            self.url.split(['?', '#']).next().unwrap_or(""),
+        ]
+        .into_iter()
+        .filter_map(|s| s.rsplit_once('.').map(|(_, ext)| ext.to_lowercase()))
+        .find(|ext| PHOTO_EXTENSIONS.contains(&ext.as_str()))
+    }
+
+    /// Where a photo is saved, relative to the sidecar's directory. Named
+    /// after the URL, so fetching the same range again reuses the file.
+    pub fn photo_path(&self) -> Option<String> {
+        let ext = self.photo_extension()?;
+        let mut hasher = DefaultHasher::new();
+        self.url.hash(&mut hasher);
+        Some(format!(
+            "{}/{:016x}.{}",
+            ATTACHMENTS_DIR,
+            hasher.finish(),
+            ext
+        ))
+    }
+}
+
+/// Raw popup contents as read by the page script.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawDetail {
+    pub subject: String,
+    pub text: String,
+    #[serde(default)]
+    pub attachments: Vec<Attachment>,
+}
+
+impl EntryDetail {
+    /// Build a detail from popup contents, tidying whitespace. Returns `None`
+    /// when the popup had no text.
+    pub fn from_raw(date: NaiveDate, raw: RawDetail) -> Option<Self> {
+        let text = tidy_text(&raw.text);
+        if text.is_empty() {
+            return None;
+        }
+        Some(Self {
+            date,
+            subject: raw.subject.split_whitespace().collect::<Vec<_>>().join(" "),
+            text,
+            attachments: raw
+                .attachments
+                .into_iter()
+                .filter(|a| !a.url.is_empty())
+                .collect(),
+        })
+    }
+}
+
+impl DetailsFile {
+    /// Collect details for `range`, dropping items outside it and duplicates
+    /// seen on more than one agenda page.
+    pub fn new(range: &DateRange, details: Vec<EntryDetail>) -> Self {
+        let mut seen = HashSet::new();
+        let entries = details
+            .into_iter()
+            .filter(|d| d.date >= range.from && d.date <= range.to)
+            .filter(|d| seen.insert((d.date, d.subject.clone(), d.text.clone())))
+            .collect();
+        Self {
+            from: range.from,
+            to: range.to,
+            entries,
+        }
+    }
+
+    /// Write the sidecar as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize details")?;
+        crate::download::write_atomic(path, json.as_bytes())
+            .with_context(|| format!("Failed to write details file: {}", path.display()))
+    }
+}
+
+/// Sidecar path for an export: `export_x.xls` -> `export_x.details.json`.
+pub fn sidecar_path(export_path: &Path) -> PathBuf {
+    export_path.with_extension("details.json")
+}
+
+/// Trim each line and drop blank runs, keeping single line breaks.
+fn tidy_text(text: &str) -> String {
+    text.lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>().join(" "))
+        .filter(|line| !line.is_empty())
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(day: u32) -> NaiveDate {
+        NaiveDate::from_ymd_opt(2025, 1, day).unwrap()
+    }
+
+    fn detail(day: u32, text: &str) -> EntryDetail {
+        EntryDetail {
+            date: date(day),
+            subject: "STORIA".to_string(),
+            text: text.to_string(),
+            attachments: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_sidecar_path() {
+        assert_eq!(
+            sidecar_path(Path::new("data/export_20250115_073005.xls")),
+            PathBuf::from("data/export_20250115_073005.details.json")
+        );
+        assert_eq!(
+            sidecar_path(Path::new("data/export_a.xlsx")),
+            PathBuf::from("data/export_a.details.json")
+        );
+    }
+
+    #[test]
+    fn test_from_raw_tidies_text() {
+        let raw = RawDetail {
+            subject: "  STORIA \n".to_string(),
+            text: "  Studiare   cap. 5\n\n\n  e fare la mappa  ".to_string(),
+            attachments: vec![
+                Attachment {
+                    name: "mappa.pdf".to_string(),
+                    url: "https://example.it/mappa.pdf".to_string(),
+                    file: None,
+                },
+                Attachment {
+                    name: "empty".to_string(),
+                    url: String::new(),
+                    file: None,
+                },
+            ],
+        };
+        let detail = EntryDetail::from_raw(date(15), raw).unwrap();
+        assert_eq!(detail.subject, "STORIA");
+        assert_eq!(detail.text, "Studiare cap. 5\ne fare la mappa");
+        assert_eq!(detail.attachments.len(), 1);
+
+        let empty = RawDetail {
+            subject: "STORIA".to_string(),
+            text: " \n ".to_string(),
+            attachments: Vec::new(),
+        };
+        assert!(EntryDetail::from_raw(date(15), empty).is_none());
+    }
+
+    #[test]
+    fn test_details_file_filters_range_and_duplicates() {
+        let range = DateRange::new(date(10), date(20));
+        let file = DetailsFile::new(
+            &range,
+            vec![
+                detail(9, "before"),
+                detail(10, "first day"),
+                detail(15, "middle"),
+                detail(15, "middle"),
+                detail(20, "last day"),
+                detail(21, "after"),
+            ],
+        );
+        let texts: Vec<_> = file.entries.iter().map(|d| d.text.as_str()).collect();
+        assert_eq!(texts, vec!["first day", "middle", "last day"]);
+
+        let json = serde_json::to_string(&file).unwrap();
+        assert!(json.contains(r#""date":"2025-01-15""#));
+        assert!(!json.contains("attachments"));
+    }
+
+    #[test]
+    fn test_photo_attachments() {
+        let attachment = |name: &str, url: &str| Attachment {
+            name: name.to_string(),
+            url: url.to_string(),
+            file: None,
+        };
+        let photo = attachment("lavagna.JPG", "https://example.it/dl?id=1");
+        let path = photo.photo_path().unwrap();
+        assert!(path.starts_with("attachments/") && path.ends_with(".jpg"));
+        assert_eq!(photo.photo_path(), Some(path));
+
+        // The name has no extension, the URL does
+        assert!(attachment("Foto", "https://example.it/foto.png?x=1")
+            .photo_path()
+            .is_some());
+        assert_eq!(
+            attachment("mappa.pdf", "https://example.it/mappa.pdf").photo_path(),
+            None
+        );
+        assert_eq!(
+            attachment("Compiti", "https://example.it/dl").photo_path(),
+            None
+        );
+    }
+}
@@ -0,0 +1,407 @@
+//! `raschietto doctor`: check the browser setup and say how to fix it.
+//!
+//! Fetching needs three things that live outside this binary: the Playwright
+//! driver (unpacked from the binary into the cache dir on first use, with its
+//! own Node.js), a Chromium build for this OS and CPU, and the system
+//! libraries Chromium links against. Each check reports what it found and,
+//! when something is missing, the command that fixes it. `--fix` runs those
+//! commands where it can.
+//!
+//! The driver bundled by playwright-rust ships an x86_64 Node.js, so on ARM
+//! Linux (Raspberry Pi) the driver only runs once it's pointed at the
+//! system's `node`.
+
+use anyhow::{bail, Context, Result};
+use playwright::Driver;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use crate::browser::{self, BrowserOptions, BrowserSession};
+
+/// Outcome of one check.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Status {
+    Ok,
+    Warn,
+    Fail,
+}
+
+/// One line of the report.
+#[derive(Debug, Clone)]
+pub struct Check {
+    pub name: &'static str,
+    pub status: Status,
+    pub detail: String,
+    /// What to run to fix it
+    pub fix: Option<String>,
+}
+
+impl Check {
+    pub fn ok(name: &'static str, detail: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    pub fn fail(name: &'static str, detail: impl Into<String>, fix: impl Into<String>) -> Self {
+        Self {
+            name,
+            status: Status::Fail,
+            detail: detail.into(),
+            fix: Some(fix.into()),
+        }
+    }
+}
+
+/// The OS and CPU this binary runs on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct HostPlatform {
+    pub os: &'static str,
+    pub arch: &'static str,
+}
+
+impl HostPlatform {
+    pub fn current() -> Self {
+        Self {
+            os: std::env::consts::OS,
+            arch: std::env::consts::ARCH,
+        }
+    }
+
+    /// Playwright's name for this platform's browser builds, or `None` when
+    /// it publishes none (32-bit ARM, Windows on ARM).
+    pub fn playwright_name(&self) -> Option<&'static str> {
+        match (self.os, self.arch) {
+            ("linux", "x86_64") => Some("linux"),
+            ("linux", "aarch64") => Some("linux-arm64"),
+            ("macos", "x86_64") => Some("mac"),
+            ("macos", "aarch64") => Some("mac-arm64"),
+            ("windows", "x86_64") => Some("win64"),
+            _ => None,
+        }
+    }
+
+    /// Whether the driver's bundled Node.js can run here. macOS runs the
+    /// x86_64 build through Rosetta; Linux has no such fallback.
+    pub fn runs_bundled_node(&self) -> bool {
+        self.arch == "x86_64" || self.os == "macos"
+    }
+
+    fn node_binary(&self) -> &'static str {
+        if self.os == "windows" {
+            "node.exe"
+        } else {
+            "node"
+        }
+    }
+
+    fn npx(&self) -> &'static str {
+        if self.os == "windows" {
+            "npx.cmd"
+        } else {
+            "npx"
+        }
+    }
+}
+
+/// Run every check, fixing what it can when `fix` is set.
+pub async fn run(fix: bool) -> Vec<Check> {
+    let platform = HostPlatform::current();
+    let mut checks = vec![check_platform(&platform)];
+
+    let driver = check_driver(&platform, fix);
+    let driver_ok = driver.status == Status::Ok;
+    checks.push(driver);
+
+    let browser = check_browser(&platform, fix);
+    let browser_ok = browser.status == Status::Ok;
+    checks.push(browser);
+
+    if driver_ok && browser_ok {
+        checks.push(check_launch(&platform).await);
+    }
+    checks
+}
+
+fn check_platform(platform: &HostPlatform) -> Check {
+    let detail = format!("{}/{}", platform.os, platform.arch);
+    match platform.playwright_name() {
+        Some(name) => Check::ok("platform", format!("{} ({})", detail, name)),
+        None => Check {
+            name: "platform",
+            status: Status::Warn,
+            detail: format!("{} has no Playwright browser builds", detail),
+            fix: Some(format!(
+                "Install Chromium from your OS packages and set {}=/path/to/chromium",
+                browser::CHROMIUM_ENV
+            )),
+        },
+    }
+}
+
+/// The driver unpacks itself on first use; what can go wrong is its Node.js.
+fn check_driver(platform: &HostPlatform, fix: bool) -> Check {
+    let dir = Driver::default_dest();
+    if let Err(e) = Driver::install() {
+        return Check::fail(
+            "driver",
+            format!(
+                "Can't unpack the Playwright driver into {}: {}",
+                dir.display(),
+                e
+            ),
+            "Check that the cache directory is writable",
+        );
+    }
+
+    let node = dir.join(platform.node_binary());
+    if runs(&node) {
+        return Check::ok("driver", dir.display().to_string());
+    }
+
+    let detail = if platform.runs_bundled_node() {
+        format!("The driver's Node.js ({}) doesn't run", node.display())
+    } else {
+        format!(
+            "The driver's Node.js is built for x86_64 and can't run on {}",
+            platform.arch
+        )
+    };
+    let system_node = find_on_path(platform.node_binary());
+    if fix {
+        if let Some(system_node) = &system_node {
+            return match link_node(system_node, &node) {
+                Ok(()) => Check::ok(
+                    "driver",
+                    format!("{} (now using {})", dir.display(), system_node.display()),
+                ),
+                Err(e) => Check::fail(
+                    "driver",
+                    format!("{}: {:#}", detail, e),
+                    manual_node_fix(&node),
+                ),
+            };
+        }
+    }
+    let fix_hint = match system_node {
+        Some(_) => "Run 'raschietto doctor --fix' to use the system Node.js".to_string(),
+        None => format!(
+            "Install Node.js 18 or later (e.g. 'sudo apt install nodejs'), then run \
+             'raschietto doctor --fix' (or: {})",
+            manual_node_fix(&node)
+        ),
+    };
+    Check::fail("driver", detail, fix_hint)
+}
+
+fn manual_node_fix(node: &Path) -> String {
+    format!("ln -sf \"$(command -v node)\" \"{}\"", node.display())
+}
+
+fn check_browser(platform: &HostPlatform, fix: bool) -> Check {
+    if let Some(path) = std::env::var_os(browser::CHROMIUM_ENV) {
+        let path = PathBuf::from(path);
+        return if path.exists() {
+            Check::ok(
+                "browser",
+                format!("{} (from {})", path.display(), browser::CHROMIUM_ENV),
+            )
+        } else {
+            Check::fail(
+                "browser",
+                format!(
+                    "{} points at {}, which doesn't exist",
+                    browser::CHROMIUM_ENV,
+                    path.display()
+                ),
+                format!("Fix or unset {}", browser::CHROMIUM_ENV),
+            )
+        };
+    }
+    if let Some(path) = browser::find_chromium_executable() {
+        return Check::ok("browser", path.display().to_string());
+    }
+
+    let searched: Vec<String> = browser::playwright_cache_dirs()
+        .iter()
+        .map(|d| d.display().to_string())
+        .collect();
+    let detail = format!("No Chromium found in {}", searched.join(", "));
+    if fix {
+        return match install_chromium(platform) {
+            Ok(()) => match browser::find_chromium_executable() {
+                Some(path) => Check::ok("browser", format!("{} (installed)", path.display())),
+                None => Check::fail(
+                    "browser",
+                    "Chromium was installed but isn't where raschietto looks",
+                    format!("Set {}=/path/to/chrome", browser::CHROMIUM_ENV),
+                ),
+            },
+            Err(e) => Check::fail(
+                "browser",
+                format!("{}: {:#}", detail, e),
+                browser_fix_hint(platform),
+            ),
+        };
+    }
+    Check::fail("browser", detail, browser_fix_hint(platform))
+}
+
+fn browser_fix_hint(platform: &HostPlatform) -> String {
+    match platform.playwright_name() {
+        Some("linux-arm64") => format!(
+            "Run 'raschietto doctor --fix' (needs npx), or 'sudo apt install chromium' \
+             and set {}=/usr/bin/chromium",
+            browser::CHROMIUM_ENV
+        ),
+        Some(_) => {
+            "Run 'raschietto doctor --fix' (or 'npx playwright install chromium')".to_string()
+        }
+        None => format!(
+            "Install Chromium from your OS packages and set {}=/path/to/chromium",
+            browser::CHROMIUM_ENV
+        ),
+    }
+}
+
+/// Download Chromium for this platform. The npm Playwright (see
+/// package.json) has builds for every platform including linux-arm64; the
+/// bundled driver is older and is only the fallback when npx is missing.
+fn install_chromium(platform: &HostPlatform) -> Result<()> {
+    if platform.playwright_name().is_none() {
+        bail!(
+            "Playwright has no Chromium build for {}/{}",
+            platform.os,
+            platform.arch
+        );
+    }
+    let (program, args): (PathBuf, &[&str]) = match find_on_path(platform.npx()) {
+        Some(npx) => (npx, &["--yes", "playwright", "install", "chromium"]),
+        None if platform.os == "linux" && platform.arch != "x86_64" => {
+            bail!("npx not found, and the bundled driver can't download ARM builds")
+        }
+        None => (Driver::install()?.executable(), &["install", "chromium"]),
+    };
+    println!("Running {} {}", program.display(), args.join(" "));
+    let status = Command::new(&program)
+        .args(args)
+        .status()
+        .with_context(|| format!("Failed to run {}", program.display()))?;
+    if !status.success() {
+        bail!("{} exited with {}", program.display(), status);
+    }
+    Ok(())
+}
+
+async fn check_launch(platform: &HostPlatform) -> Check {
+    let launched = async {
+        let session = BrowserSession::launch(BrowserOptions::default()).await?;
+        session.close().await
+    };
+    match launched.await {
+        Ok(()) => Check::ok("launch", "Headless Chromium starts"),
+        Err(e) => {
+            let fix = if platform.os == "linux" {
+                "Install Chromium's system libraries: 'sudo npx playwright install-deps chromium'"
+            } else {
+                "Reinstall the browser with 'raschietto doctor --fix'"
+            };
+            Check::fail("launch", format!("{:#}", e), fix)
+        }
+    }
+}
+
+/// Whether `program --version` runs.
+fn runs(program: &Path) -> bool {
+    Command::new(program)
+        .arg("--version")
+        .output()
+        .is_ok_and(|out| out.status.success())
+}
+
+fn find_on_path(name: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    std::env::split_paths(&path)
+        .map(|dir| dir.join(name))
+        .find(|candidate| candidate.is_file())
+}
+
+#[cfg(unix)]
+fn link_node(system_node: &Path, driver_node: &Path) -> Result<()> {
+    if driver_node.exists() {
+        std::fs::rename(driver_node, driver_node.with_extension("bundled"))
+            .context("Failed to move the bundled Node.js aside")?;
+    }
+    std::os::unix::fs::symlink(system_node, driver_node)
+        .context("Failed to link the system Node.js")
+}
+
+#[cfg(not(unix))]
+fn link_node(system_node: &Path, driver_node: &Path) -> Result<()> {
+    std::fs::copy(system_node, driver_node).context("Failed to copy the system Node.js")?;
+    Ok(())
+}
+
+/// Render the checks, one per line with the fix indented below.
+pub fn render(checks: &[Check]) -> String {
+    let mut out = String::new();
+    for check in checks {
+        let mark = match check.status {
+            Status::Ok => "✓",
+            Status::Warn => "!",
+            Status::Fail => "✗",
+        };
+        let _ = writeln!(out, "{} {:<9} {}", mark, check.name, check.detail);
+        if let Some(fix) = &check.fix {
+            let _ = writeln!(out, "  → {}", fix);
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_playwright_platform_names() {
+        let name = |os, arch| HostPlatform { os, arch }.playwright_name();
+        assert_eq!(name("linux", "aarch64"), Some("linux-arm64"));
+        assert_eq!(name("macos", "aarch64"), Some("mac-arm64"));
+        assert_eq!(name("windows", "x86_64"), Some("win64"));
+        assert_eq!(name("linux", "arm"), None);
+
+        let pi = HostPlatform {
+            os: "linux",
+            arch: "aarch64",
+        };
+        assert!(!pi.runs_bundled_node());
+        assert!(browser_fix_hint(&pi).contains("apt install chromium"));
+        assert!(HostPlatform {
+            os: "macos",
+            arch: "aarch64"
+        }
+        .runs_bundled_node());
+    }
+
+    #[test]
+    fn test_render() {
+        let checks = vec![
+            Check::ok("platform", "linux/aarch64 (linux-arm64)"),
+            Check::fail(
+                "browser",
+                "No Chromium found",
+                "Run 'raschietto doctor --fix'",
+            ),
+        ];
+        assert_eq!(
+            render(&checks),
+            "✓ platform  linux/aarch64 (linux-arm64)\n\
+             ✗ browser   No Chromium found\n  → Run 'raschietto doctor --fix'\n"
+        );
+    }
+}
@@ -0,0 +1,243 @@
+//! Checking and saving downloaded exports.
+//!
+//! compitutto's watcher imports any `export_*.xls` the moment it appears, so
+//! a half-written or truncated file must never get that name. Downloads are
+//! checked first (non-empty, a spreadsheet and not an HTML error page, and
+//! complete for its format), written to a hidden `.export_….part` file in the
+//! same directory, and only then renamed into place. Sidecars and photos are
+//! saved the same way with [`write_atomic`]. Temp names carry the process id
+//! and a counter, so two runs can never write the same `.part` file.
+//!
+//! | Format       | Magic bytes           | Complete when                       |
+//! |--------------|-----------------------|-------------------------------------|
+//! | SpreadsheetML| `<?xml`               | ends with `</Workbook>`             |
+//! | `.xlsx`      | `PK\x03\x04`          | has the zip end-of-directory record |
+//! | legacy `.xls`| `D0 CF 11 E0 …`       | whole 512-byte sectors              |
+
+use anyhow::{bail, Context, Result};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, Ordering};
+use tracing::{info, warn};
+
+const OLE: &[u8] = &[0xD0, 0xCF, 0x11, 0xE0, 0xA1, 0xB1, 0x1A, 0xE1];
+const ZIP: &[u8] = b"PK\x03\x04";
+const ZIP_END: &[u8] = b"PK\x05\x06";
+/// End-of-directory record plus the longest zip comment
+const ZIP_END_SEARCH: usize = 22 + u16::MAX as usize;
+const OLE_SECTOR: usize = 512;
+
+/// Suffix of in-progress files; the leading `.` keeps them out of the watcher.
+const PARTIAL_SUFFIX: &str = ".part";
+
+/// Temp files written by this process so far, for unique names
+static PARTIAL_COUNT: AtomicU64 = AtomicU64::new(0);
+
+/// Which kind of spreadsheet a download is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    SpreadsheetMl,
+    Xlsx,
+    Xls,
+}
+
+/// Recognise an export from its first bytes; `None` for anything else (such
+/// as an HTML login page).
+pub fn sniff(bytes: &[u8]) -> Option<ExportFormat> {
+    if bytes.starts_with(OLE) {
+        return Some(ExportFormat::Xls);
+    }
+    if bytes.starts_with(ZIP) {
+        return Some(ExportFormat::Xlsx);
+    }
+    let head = String::from_utf8_lossy(&bytes[..bytes.len().min(512)]);
+    let head = head.trim_start_matches('\u{feff}').trim_start();
+    (head.starts_with("<?xml") && !head.to_ascii_lowercase().contains("<html"))
+        .then_some(ExportFormat::SpreadsheetMl)
+}
+
+/// Check that `bytes` is a whole export. `expected_len` is the response's
+/// Content-Length, when it sent one.
+pub fn verify(bytes: &[u8], expected_len: Option<u64>) -> Result<ExportFormat> {
+    if bytes.is_empty() {
+        bail!("Download is empty");
+    }
+    if let Some(expected) = expected_len {
+        if bytes.len() as u64 != expected {
+            bail!(
+                "Download truncated: got {} of {} bytes",
+                bytes.len(),
+                expected
+            );
+        }
+    }
+    let format = sniff(bytes).context(
+        "Download is not a spreadsheet (session expired or the portal returned an error page?)",
+    )?;
+    let complete = match format {
+        ExportFormat::SpreadsheetMl => {
+            let tail = String::from_utf8_lossy(&bytes[bytes.len().saturating_sub(256)..]);
+            let tail = tail.trim_end();
+            tail.ends_with("</Workbook>") || tail.ends_with(":Workbook>")
+        }
+        ExportFormat::Xlsx => bytes[bytes.len().saturating_sub(ZIP_END_SEARCH)..]
+            .windows(ZIP_END.len())
+            .any(|w| w == ZIP_END),
+        ExportFormat::Xls => bytes.len().is_multiple_of(OLE_SECTOR),
+    };
+    if !complete {
+        bail!("Download truncated: {:?} file is incomplete", format);
+    }
+    Ok(format)
+}
+
+/// Verify `bytes` and save them as `output_dir/filename` atomically.
+///
+/// Nothing is written if verification fails, and a failed write leaves no
+/// partial file behind.
+pub fn save_export(
+    output_dir: &Path,
+    filename: &str,
+    bytes: &[u8],
+    expected_len: Option<u64>,
+) -> Result<PathBuf> {
+    let format = verify(bytes, expected_len)?;
+
+    let output_path = output_dir.join(filename);
+    if output_path.exists() {
+        warn!("Overwriting existing file: {:?}", output_path);
+    }
+    write_atomic(&output_path, bytes).context("Failed to write downloaded file")?;
+
+    info!(
+        "Download saved to: {:?} ({} bytes, {:?})",
+        output_path,
+        bytes.len(),
+        format
+    );
+    Ok(output_path)
+}
+
+/// Write `bytes` to `path` through a uniquely named `.part` file next to it,
+/// so readers only ever see the old or the new contents. A failed write
+/// leaves no temp file behind.
+pub fn write_atomic(path: &Path, bytes: &[u8]) -> Result<()> {
+    let partial_path = partial_path(path);
+    let written = write_synced(&partial_path, bytes)
+        .and_then(|()| fs::rename(&partial_path, path).map_err(Into::into));
+    if let Err(e) = written {
+        let _ = fs::remove_file(&partial_path);
+        return Err(e).with_context(|| format!("Failed to write {:?}", path));
+    }
+    Ok(())
+}
+
+/// `dir/.name.<pid>-<n>.part` for `dir/name`
+fn partial_path(path: &Path) -> PathBuf {
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let n = PARTIAL_COUNT.fetch_add(1, Ordering::Relaxed);
+    path.with_file_name(format!(
+        ".{}.{}-{}{}",
+        name,
+        std::process::id(),
+        n,
+        PARTIAL_SUFFIX
+    ))
+}
+
+fn write_synced(path: &Path, bytes: &[u8]) -> Result<()> {
+    let mut file = fs::File::create(path)?;
+    file.write_all(bytes)?;
+    file.sync_all()?;
+    Ok(())
+}
+
+/// Delete `.part` files left in `dir` by an interrupted run.
+pub fn remove_partial_files(dir: &Path) -> Result<usize> {
+    let mut removed = 0;
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let path = entry?.path();
+        let is_partial = path
+            .file_name()
+            .and_then(|n| n.to_str())
+            .is_some_and(|n| n.starts_with('.') && n.ends_with(PARTIAL_SUFFIX));
+        if is_partial {
+            warn!("Removing partial download: {:?}", path);
+            fs::remove_file(&path)?;
+            removed += 1;
+        }
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const WORKBOOK: &[u8] = b"\xEF\xBB\xBF<?xml version=\"1.0\"?>\n<Workbook>\n</Workbook>\n";
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raschietto-{}-{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn test_verify_formats() {
+        assert_eq!(verify(WORKBOOK, None).unwrap(), ExportFormat::SpreadsheetMl);
+        let mut xlsx = b"PK\x03\x04data".to_vec();
+        xlsx.extend_from_slice(b"PK\x05\x06");
+        xlsx.extend_from_slice(&[0; 18]);
+        assert_eq!(verify(&xlsx, None).unwrap(), ExportFormat::Xlsx);
+        let mut xls = OLE.to_vec();
+        xls.resize(OLE_SECTOR * 3, 0);
+        assert_eq!(verify(&xls, None).unwrap(), ExportFormat::Xls);
+
+        assert!(sniff(b"<!DOCTYPE html><html><body>Login</body>").is_none());
+        assert!(verify(b"", None).is_err());
+        assert!(verify(
+            b"<?xml version=\"1.0\"?><html><body>Errore</body></html>",
+            None
+        )
+        .is_err());
+    }
+
+    #[test]
+    fn test_verify_rejects_truncated() {
+        let cut = &WORKBOOK[..WORKBOOK.len() - 8];
+        assert!(verify(cut, None)
+            .unwrap_err()
+            .to_string()
+            .contains("truncated"));
+        assert!(verify(b"PK\x03\x04only the start", None).is_err());
+        assert!(verify(OLE, None).is_err());
+
+        let err = verify(WORKBOOK, Some(WORKBOOK.len() as u64 + 100)).unwrap_err();
+        assert!(err.to_string().contains("got"));
+    }
+
+    #[test]
+    fn test_save_export_is_atomic() {
+        let dir = scratch_dir("save");
+        let path = save_export(&dir, "export_a.xls", WORKBOOK, None).unwrap();
+        assert_eq!(fs::read(&path).unwrap(), WORKBOOK);
+
+        // A truncated download leaves the directory as it was
+        assert!(save_export(&dir, "export_b.xls", &WORKBOOK[..20], None).is_err());
+        let mut names: Vec<String> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|e| e.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, vec!["export_a.xls"]);
+
+        let first = partial_path(&dir.join("export_c.xls"));
+        assert_ne!(first, partial_path(&dir.join("export_c.xls")));
+        fs::write(&first, b"<?xml").unwrap();
+        assert_eq!(remove_partial_files(&dir).unwrap(), 1);
+        assert!(dir.join("export_a.xls").exists());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
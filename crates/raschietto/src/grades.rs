@@ -0,0 +1,137 @@
+//! Grades read from the portal's grades page.
+//!
+//! The portal has no spreadsheet export for grades, so `fetch --export grades`
+//! reads the grades table and writes it as JSON next to the agenda export:
+//!
+//! ```text
+//! data/export_20250115_073005.xls
+//! data/grades_20250115_073005.json
+//! ```
+//!
+//! The name deliberately doesn't start with `export_`, so compitutto's
+//! watcher leaves it alone.
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Contents of a `grades_*.json` file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct GradesFile {
+    /// Day the page was read
+    pub fetched: NaiveDate,
+    pub grades: Vec<Grade>,
+}
+
+/// One grade as shown on the grades page.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Grade {
+    pub subject: String,
+    /// As displayed: "7", "6+", "8½", "g"
+    pub value: String,
+    /// As displayed, usually `DD/MM`
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub date: String,
+    /// The cell's tooltip (test type or teacher's comment)
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+/// Raw cell contents as read by the page script.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawGrade {
+    pub subject: String,
+    pub value: String,
+    #[serde(default)]
+    pub date: String,
+    #[serde(default)]
+    pub note: String,
+}
+
+impl Grade {
+    /// Build a grade from a table cell, collapsing whitespace. Returns `None`
+    /// for empty cells.
+    pub fn from_raw(raw: RawGrade) -> Option<Self> {
+        let value = squash(&raw.value);
+        if value.is_empty() {
+            return None;
+        }
+        Some(Self {
+            subject: squash(&raw.subject),
+            value,
+            date: squash(&raw.date),
+            note: squash(&raw.note),
+        })
+    }
+}
+
+impl GradesFile {
+    /// Write the file as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize grades")?;
+        crate::download::write_atomic(path, json.as_bytes())
+            .with_context(|| format!("Failed to write grades file: {}", path.display()))
+    }
+}
+
+/// Grades path for an export: `export_x.xls` -> `grades_x.json`.
+pub fn grades_path(export_path: &Path) -> PathBuf {
+    let stem = export_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let rest = stem.strip_prefix("export_").unwrap_or(&stem);
+    export_path.with_file_name(format!("grades_{}.json", rest))
+}
+
+fn squash(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_grades_path() {
+        assert_eq!(
+            grades_path(Path::new("data/export_20250115_073005.xls")),
+            PathBuf::from("data/grades_20250115_073005.json")
+        );
+        assert_eq!(
+            grades_path(Path::new("data/export_mario_2025-01-08.xlsx")),
+            PathBuf::from("data/grades_mario_2025-01-08.json")
+        );
+    }
+
+    #[test]
+    fn test_from_raw_tidies_and_skips_empty_cells() {
+        let grade = Grade::from_raw(RawGrade {
+            subject: " MATEMATICA \n".to_string(),
+            value: " 7+ ".to_string(),
+            date: "15/01".to_string(),
+            note: "Scritto:\n  equazioni".to_string(),
+        })
+        .unwrap();
+        assert_eq!(grade.subject, "MATEMATICA");
+        assert_eq!(grade.value, "7+");
+        assert_eq!(grade.note, "Scritto: equazioni");
+
+        let empty = RawGrade {
+            subject: "STORIA".to_string(),
+            value: " ".to_string(),
+            date: String::new(),
+            note: String::new(),
+        };
+        assert!(Grade::from_raw(empty).is_none());
+
+        let json = serde_json::to_string(&Grade {
+            date: String::new(),
+            note: String::new(),
+            ..grade
+        })
+        .unwrap();
+        assert_eq!(json, r#"{"subject":"MATEMATICA","value":"7+"}"#);
+    }
+}
@@ -0,0 +1,288 @@
+//! A log of past fetches (`raschietto history`).
+//!
+//! Every fetch that gets as far as locking its output directory appends one
+//! line to `<output>/.raschietto-history.jsonl` when it's done: when it
+//! started, how long it took and in which steps (see `metrics`), the profile
+//! and range, how it ended, the agenda export it saved and about how many
+//! entries that export has. So
+//! "did it actually fetch yesterday?" is `raschietto history` away, without
+//! digging through cron mail. The file starts with a `.` so compitutto's
+//! watcher and the retention pass leave it alone, and only the newest
+//! [`MAX_RUNS`] lines are kept.
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::path::{Path, PathBuf};
+use tracing::warn;
+
+use crate::changes;
+use crate::metrics::{StepTiming, Timings};
+use crate::scraper::DateRange;
+
+/// History file in the output directory
+pub const HISTORY_FILE: &str = ".raschietto-history.jsonl";
+
+/// Runs kept; older lines go when a new run is added
+pub const MAX_RUNS: usize = 500;
+
+/// Runs shown by `raschietto history` without `--last`
+pub const DEFAULT_SHOWN: usize = 10;
+
+/// How a run ended
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Outcome {
+    Ok,
+    /// `--dry-run`: logged in, nothing downloaded
+    DryRun,
+    /// Stopped by a CAPTCHA or browser check (see `challenge`)
+    Challenge,
+    Failed,
+}
+
+impl Outcome {
+    fn label(self) -> &'static str {
+        match self {
+            Outcome::Ok => "ok",
+            Outcome::DryRun => "dry run",
+            Outcome::Challenge => "challenge",
+            Outcome::Failed => "failed",
+        }
+    }
+}
+
+/// One fetch, as logged
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Run {
+    /// Local time
+    pub started: NaiveDateTime,
+    pub seconds: u64,
+    pub profile: String,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub outcome: Outcome,
+    /// Agenda export saved, file name only
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub file: Option<String>,
+    /// Rows in that export, when it could be read
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entries: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Time per step; empty for runs logged before steps were timed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub timings: Vec<StepTiming>,
+}
+
+impl Run {
+    /// The run that started at `started` and ended with `result`: the agenda
+    /// export it saved, if any, or why it failed, and where the time went
+    pub fn finished(
+        started: DateTime<Local>,
+        profile: &str,
+        range: &DateRange,
+        dry_run: bool,
+        result: &Result<Option<PathBuf>>,
+        timings: Timings,
+    ) -> Self {
+        let (outcome, export, error) = match result {
+            Ok(_) if dry_run => (Outcome::DryRun, None, None),
+            Ok(export) => (Outcome::Ok, export.as_deref(), None),
+            Err(e) if crate::challenge::find(e).is_some() => {
+                (Outcome::Challenge, None, Some(format!("{:#}", e)))
+            }
+            Err(e) => (Outcome::Failed, None, Some(format!("{:#}", e))),
+        };
+        Run {
+            started: started.naive_local(),
+            seconds: (Local::now() - started).num_seconds().max(0) as u64,
+            profile: profile.to_string(),
+            from: range.from,
+            to: range.to,
+            outcome,
+            file: export
+                .and_then(|p| p.file_name())
+                .map(|n| n.to_string_lossy().into_owned()),
+            entries: export.and_then(count_entries),
+            error,
+            timings: timings.into_steps(),
+        }
+    }
+}
+
+/// Agenda rows in the export at `path`; `None` for formats `changes` can't
+/// read
+fn count_entries(path: &Path) -> Option<usize> {
+    let bytes = std::fs::read(path).ok()?;
+    changes::read_rows(&bytes).ok().map(|rows| rows.len())
+}
+
+pub fn history_path(output_dir: &Path) -> PathBuf {
+    output_dir.join(HISTORY_FILE)
+}
+
+/// Every logged run in `output_dir`, oldest first. Lines that don't parse
+/// are skipped with a warning.
+pub fn load(output_dir: &Path) -> Result<Vec<Run>> {
+    let path = history_path(output_dir);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let text =
+        std::fs::read_to_string(&path).with_context(|| format!("Failed to read {:?}", path))?;
+    Ok(text
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(i, line)| match serde_json::from_str(line) {
+            Ok(run) => Some(run),
+            Err(e) => {
+                warn!("Skipping line {} of {:?}: {}", i + 1, path, e);
+                None
+            }
+        })
+        .collect())
+}
+
+/// Add `run` to the log in `output_dir`, dropping the oldest beyond
+/// [`MAX_RUNS`]. Called with the output lock held.
+pub fn record(output_dir: &Path, run: &Run) -> Result<()> {
+    let mut runs = load(output_dir)?;
+    runs.push(run.clone());
+    let skip = runs.len().saturating_sub(MAX_RUNS);
+    let mut text = String::new();
+    for run in &runs[skip..] {
+        text.push_str(&serde_json::to_string(run)?);
+        text.push('\n');
+    }
+    crate::download::write_atomic(&history_path(output_dir), text.as_bytes())
+}
+
+/// The last `count` runs, newest first, one line each plus an indented
+/// line with the step times when they were recorded
+pub fn render(runs: &[Run], count: usize) -> String {
+    if runs.is_empty() {
+        return "No fetches logged yet\n".to_string();
+    }
+    let mut out = String::new();
+    for run in runs.iter().rev().take(count) {
+        let _ = write!(
+            out,
+            "{}  {:<9}  {}  {} → {}  {}s",
+            run.started.format("%Y-%m-%d %H:%M"),
+            run.outcome.label(),
+            run.profile,
+            run.from,
+            run.to,
+            run.seconds
+        );
+        if let Some(file) = &run.file {
+            let _ = write!(out, "  {}", file);
+        }
+        if let Some(entries) = run.entries {
+            let _ = write!(out, " ({} entries)", entries);
+        }
+        if let Some(error) = &run.error {
+            let _ = write!(out, "  {}", error);
+        }
+        out.push('\n');
+        if !run.timings.is_empty() {
+            let steps: Vec<String> = run
+                .timings
+                .iter()
+                .map(|t| format!("{} {:.1}s", t.step.label(), t.seconds))
+                .collect();
+            let _ = writeln!(out, "    {}", steps.join(", "));
+        }
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::metrics::Phase;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raschietto-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    fn run(day: u32, outcome: Outcome) -> Run {
+        Run {
+            started: NaiveDate::from_ymd_opt(2025, 1, day)
+                .unwrap()
+                .and_hms_opt(7, 30, 0)
+                .unwrap(),
+            seconds: 12,
+            profile: "classeviva".to_string(),
+            from: NaiveDate::from_ymd_opt(2025, 1, 8).unwrap(),
+            to: NaiveDate::from_ymd_opt(2025, 1, 30).unwrap(),
+            outcome,
+            file: (outcome == Outcome::Ok).then(|| "export_20250115_073005.xls".to_string()),
+            entries: (outcome == Outcome::Ok).then_some(42),
+            error: (outcome == Outcome::Failed).then(|| "Login form did not appear".to_string()),
+            timings: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_record_and_render() {
+        let dir = scratch_dir("history");
+        assert!(load(&dir).unwrap().is_empty());
+        record(&dir, &run(14, Outcome::Failed)).unwrap();
+        record(&dir, &run(15, Outcome::Ok)).unwrap();
+        // A torn line doesn't lose the rest
+        let mut text = std::fs::read_to_string(history_path(&dir)).unwrap();
+        text.push_str("{\"started\":\n");
+        std::fs::write(history_path(&dir), text).unwrap();
+
+        let runs = load(&dir).unwrap();
+        assert_eq!(runs, vec![run(14, Outcome::Failed), run(15, Outcome::Ok)]);
+        assert_eq!(
+            render(&runs, 1),
+            "2025-01-15 07:30  ok         classeviva  2025-01-08 → 2025-01-30  12s  \
+             export_20250115_073005.xls (42 entries)\n"
+        );
+        assert!(render(&runs, 10).lines().nth(1).unwrap().ends_with(
+            "failed     classeviva  2025-01-08 → 2025-01-30  12s  Login form did not appear"
+        ));
+
+        let timed = Run {
+            timings: vec![
+                StepTiming {
+                    step: Phase::Login,
+                    seconds: 4.26,
+                },
+                StepTiming {
+                    step: Phase::Download,
+                    seconds: 1.0,
+                },
+            ],
+            ..run(16, Outcome::Ok)
+        };
+        assert!(render(&[timed], 1).ends_with("\n    login 4.3s, download 1.0s\n"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_record_keeps_the_newest() {
+        let dir = scratch_dir("history-trim");
+        let mut text = String::new();
+        for _ in 0..MAX_RUNS {
+            text.push_str(&serde_json::to_string(&run(14, Outcome::Failed)).unwrap());
+            text.push('\n');
+        }
+        std::fs::write(history_path(&dir), text).unwrap();
+
+        record(&dir, &run(15, Outcome::Ok)).unwrap();
+        let runs = load(&dir).unwrap();
+        assert_eq!(runs.len(), MAX_RUNS);
+        assert_eq!(runs.last(), Some(&run(15, Outcome::Ok)));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
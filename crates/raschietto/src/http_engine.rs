@@ -0,0 +1,246 @@
+//! Experimental browserless fetch (`--engine http`).
+//!
+//! Logs in by posting the login form with reqwest, keeps the session in a
+//! cookie jar and requests the export URL directly. Much lighter than
+//! Playwright (no Chromium on a Raspberry Pi), but it depends on endpoints
+//! the portal doesn't document, so `fetch` falls back to the browser when any
+//! step here fails.
+//!
+//! The endpoints come from the profile's `[http]` table. The export URL is
+//! not known up front: a browser fetch logs the URL it downloaded from with
+//! the dates already replaced by `{from}`/`{to}`, ready to paste into
+//! `raschietto.toml`.
+
+use anyhow::{anyhow, bail, Context, Result};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use tracing::{debug, info};
+
+use crate::config::Credentials;
+use crate::download;
+use crate::metrics::{self, Phase, Timings};
+use crate::network::NetworkOptions;
+use crate::profile::PortalProfile;
+use crate::scraper::DateRange;
+
+/// Some portals reject requests without a browser-like user agent.
+const USER_AGENT: &str = "Mozilla/5.0 (X11; Linux aarch64) raschietto";
+
+/// Fetches exports with plain HTTP requests.
+pub struct HttpFetcher {
+    client: reqwest::Client,
+    profile: PortalProfile,
+    credentials: Credentials,
+    /// Time spent logging in and downloading
+    timings: Mutex<Timings>,
+}
+
+impl HttpFetcher {
+    pub fn new(
+        profile: PortalProfile,
+        credentials: Credentials,
+        network: &NetworkOptions,
+    ) -> Result<Self> {
+        let client = network
+            .client_builder()
+            .cookie_store(true)
+            .user_agent(USER_AGENT)
+            .build()
+            .context("Failed to build HTTP client")?;
+        Ok(Self {
+            client,
+            profile,
+            credentials,
+            timings: Mutex::new(Timings::default()),
+        })
+    }
+
+    /// Where the time went so far, also after a failed fetch
+    pub fn timings(&self) -> Timings {
+        self.timings.lock().unwrap().clone()
+    }
+
+    /// Post the login form, then check the agenda no longer redirects to it.
+    pub async fn login(&self) -> Result<()> {
+        let http = &self.profile.http;
+
+        // Pick up any pre-login session cookie the login form expects
+        self.client
+            .get(&self.profile.agenda_url)
+            .send()
+            .await
+            .context("Failed to reach the agenda page")?;
+
+        let mut form = http.login_extra.clone();
+        form.insert(
+            http.username_field.clone(),
+            self.credentials.username.clone(),
+        );
+        form.insert(
+            http.password_field.clone(),
+            self.credentials.password.clone(),
+        );
+
+        info!("Logging in over HTTP");
+        let response = self
+            .client
+            .post(&http.login_url)
+            .header("Referer", &self.profile.agenda_url)
+            .form(&form)
+            .send()
+            .await
+            .context("Login request failed")?;
+        if !response.status().is_success() {
+            bail!("Login request failed with status: {}", response.status());
+        }
+        let body = response.text().await.unwrap_or_default();
+        let errors = login_errors(&body);
+        if !errors.is_empty() {
+            bail!("Login rejected: {}", errors.join("; "));
+        }
+
+        let agenda = self
+            .client
+            .get(&self.profile.agenda_url)
+            .send()
+            .await
+            .context("Failed to load the agenda page after login")?;
+        let landed = agenda.url().to_string();
+        if !landed.starts_with(&self.profile.agenda_url) {
+            bail!("Login was not accepted (agenda redirected to {})", landed);
+        }
+        debug!("Session accepted by the agenda page");
+        Ok(())
+    }
+
+    /// Request the export for `range` and save it to `output_dir/filename`.
+    pub async fn download(
+        &self,
+        range: &DateRange,
+        output_dir: &Path,
+        filename: &str,
+    ) -> Result<PathBuf> {
+        let template = self.profile.http.export_url.as_deref().ok_or_else(|| {
+            anyhow!(
+                "No export_url in the profile's [http] settings; run a browser fetch once \
+                 and copy the export_url it logs into raschietto.toml"
+            )
+        })?;
+        let url = render_export_url(template, range);
+        debug!("Requesting export: {}", url);
+
+        let response = self
+            .client
+            .get(&url)
+            .header("Referer", &self.profile.agenda_url)
+            .send()
+            .await
+            .context("Export request failed")?;
+        if !response.status().is_success() {
+            bail!("Export request failed with status: {}", response.status());
+        }
+        let expected_len = response.content_length();
+        let bytes = response
+            .bytes()
+            .await
+            .context("Failed to read export response body")?;
+        download::save_export(output_dir, filename, &bytes, expected_len)
+            .context("Export response rejected (session expired or URL changed?)")
+    }
+
+    /// Log in and download, mirroring `ClasseVivaScraper::fetch`.
+    pub async fn fetch(
+        &self,
+        range: &DateRange,
+        output_dir: &Path,
+        filename: &str,
+        dry_run: bool,
+    ) -> Result<Option<PathBuf>> {
+        metrics::timed(&self.timings, Phase::HttpLogin, self.login()).await?;
+        if dry_run {
+            info!("Dry run mode - stopping after login");
+            return Ok(None);
+        }
+        metrics::timed(
+            &self.timings,
+            Phase::Download,
+            self.download(range, output_dir, filename),
+        )
+        .await
+        .map(Some)
+    }
+}
+
+/// Fill `{from}` / `{to}` in an export URL template.
+pub fn render_export_url(template: &str, range: &DateRange) -> String {
+    template
+        .replace("{from}", &DateRange::format_date(range.from))
+        .replace("{to}", &DateRange::format_date(range.to))
+}
+
+/// Turn a URL a browser fetch downloaded from back into a template, if both
+/// dates appear in it.
+pub fn export_url_template(download_url: &str, range: &DateRange) -> Option<String> {
+    let from = DateRange::format_date(range.from);
+    let to = DateRange::format_date(range.to);
+    if !(download_url.contains(&from) && download_url.contains(&to)) {
+        return None;
+    }
+    Some(download_url.replace(&from, "{from}").replace(&to, "{to}"))
+}
+
+/// Error messages from a JSON login response (`{"error": ["..."]}`).
+/// Anything that isn't JSON is left to the agenda redirect check.
+fn login_errors(body: &str) -> Vec<String> {
+    let Ok(json) = serde_json::from_str::<serde_json::Value>(body) else {
+        return Vec::new();
+    };
+    match json.get("error") {
+        Some(serde_json::Value::Array(errors)) => errors
+            .iter()
+            .map(|e| {
+                e.as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| e.to_string())
+            })
+            .collect(),
+        Some(serde_json::Value::String(error)) if !error.is_empty() => vec![error.clone()],
+        _ => Vec::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    fn range() -> DateRange {
+        DateRange::new(
+            NaiveDate::from_ymd_opt(2025, 1, 8).unwrap(),
+            NaiveDate::from_ymd_opt(2025, 1, 30).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_export_url_template_round_trip() {
+        let url = "https://example.it/export.php?dal=08-01-2025&al=30-01-2025&formato=xls";
+        let template = export_url_template(url, &range()).unwrap();
+        assert_eq!(
+            template,
+            "https://example.it/export.php?dal={from}&al={to}&formato=xls"
+        );
+        assert_eq!(render_export_url(&template, &range()), url);
+
+        assert!(export_url_template("https://example.it/export.php?id=3", &range()).is_none());
+    }
+
+    #[test]
+    fn test_login_errors() {
+        assert!(login_errors(r#"{"data":{"auth":{"loggedIn":true}},"error":[]}"#).is_empty());
+        assert_eq!(
+            login_errors(r#"{"error":["auth:password errata"]}"#),
+            vec!["auth:password errata"]
+        );
+        assert!(login_errors("<html>ok</html>").is_empty());
+    }
+}
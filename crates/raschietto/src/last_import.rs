@@ -0,0 +1,159 @@
+//! Where the last import left off (`fetch --since-last-import`).
+//!
+//! Fetching the same months on every run only re-downloads what compitutto
+//! already has. With `--since-last-import`, the fetch asks compitutto for
+//! the newest agenda date it has imported from an export (`source_date`,
+//! which stays put when an entry is moved by hand) and starts the range
+//! there. compitutto is reached either through its database file or
+//! through a running server's `/api/entries`.
+//!
+//! The newest date is usually in the coming weeks, and teachers still edit
+//! those days, so the range never starts after today.
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use rusqlite::{Connection, OpenFlags};
+use serde::Deserialize;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+/// How long to wait for a compitutto server
+const API_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Where to find compitutto's entries
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Source {
+    /// `data/homework.db`
+    Db(PathBuf),
+    /// A compitutto server, e.g. `http://localhost:8080`
+    Api(String),
+}
+
+impl Source {
+    /// An `http(s)://` address is a server, anything else a database file
+    pub fn parse(arg: &str) -> Self {
+        if arg.starts_with("http://") || arg.starts_with("https://") {
+            Source::Api(arg.to_string())
+        } else {
+            Source::Db(PathBuf::from(arg))
+        }
+    }
+}
+
+/// The newest agenda date compitutto has imported, `None` when it has
+/// nothing from an export yet.
+pub async fn newest_date(source: &Source) -> Result<Option<NaiveDate>> {
+    match source {
+        Source::Db(path) => newest_in_db(path),
+        Source::Api(url) => newest_from_api(url).await,
+    }
+}
+
+fn newest_in_db(path: &Path) -> Result<Option<NaiveDate>> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let newest: Option<String> = conn
+        .query_row("SELECT MAX(source_date) FROM entries", [], |row| row.get(0))
+        .with_context(|| format!("{} is not a compitutto database", path.display()))?;
+    Ok(newest.as_deref().and_then(parse_date))
+}
+
+/// The part of `/api/entries` this needs
+#[derive(Deserialize)]
+struct Entry {
+    #[serde(default)]
+    source_date: Option<String>,
+}
+
+/// `/api/entries` on the server at `url`, unless `url` already points there
+fn entries_url(url: &str) -> String {
+    let url = url.trim_end_matches('/');
+    if url.ends_with("/api/entries") {
+        url.to_string()
+    } else {
+        format!("{}/api/entries", url)
+    }
+}
+
+async fn newest_from_api(url: &str) -> Result<Option<NaiveDate>> {
+    let url = entries_url(url);
+    let client = reqwest::Client::builder().timeout(API_TIMEOUT).build()?;
+    let entries: Vec<Entry> = client
+        .get(&url)
+        .send()
+        .await
+        .and_then(|r| r.error_for_status())
+        .with_context(|| format!("Failed to reach {}", url))?
+        .json()
+        .await
+        .with_context(|| format!("{} didn't return compitutto entries", url))?;
+    Ok(entries
+        .iter()
+        .filter_map(|e| e.source_date.as_deref().and_then(parse_date))
+        .max())
+}
+
+fn parse_date(s: &str) -> Option<NaiveDate> {
+    NaiveDate::parse_from_str(s, "%Y-%m-%d").ok()
+}
+
+/// First day to fetch when the newest import is `newest`
+pub fn start_date(newest: NaiveDate, today: NaiveDate) -> NaiveDate {
+    newest.min(today)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn day(s: &str) -> NaiveDate {
+        parse_date(s).unwrap()
+    }
+
+    #[test]
+    fn test_parse_source() {
+        assert_eq!(
+            Source::parse("data/homework.db"),
+            Source::Db(PathBuf::from("data/homework.db"))
+        );
+        assert_eq!(
+            Source::parse("http://localhost:8080"),
+            Source::Api("http://localhost:8080".to_string())
+        );
+        assert_eq!(
+            entries_url("http://localhost:8080/"),
+            "http://localhost:8080/api/entries"
+        );
+        assert_eq!(
+            entries_url("https://casa.example/api/entries"),
+            "https://casa.example/api/entries"
+        );
+    }
+
+    #[test]
+    fn test_start_date() {
+        let today = day("2025-03-10");
+        assert_eq!(start_date(day("2025-01-20"), today), day("2025-01-20"));
+        assert_eq!(start_date(day("2025-03-24"), today), today);
+    }
+
+    #[test]
+    fn test_newest_in_db() {
+        let dir = std::env::temp_dir().join(format!("raschietto-import-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("homework.db");
+        let conn = Connection::open(&path).unwrap();
+        conn.execute_batch(
+            "CREATE TABLE entries (id TEXT PRIMARY KEY, date TEXT, source_date TEXT);
+             INSERT INTO entries VALUES ('a', '2025-01-20', '2025-01-15');
+             INSERT INTO entries VALUES ('b', '2025-02-01', '2025-01-28');
+             INSERT INTO entries VALUES ('by-hand', '2025-04-01', NULL);",
+        )
+        .unwrap();
+        drop(conn);
+
+        assert_eq!(newest_in_db(&path).unwrap(), Some(day("2025-01-28")));
+        assert!(newest_in_db(&dir.join("missing.db")).is_err());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
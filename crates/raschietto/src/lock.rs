@@ -0,0 +1,133 @@
+//! One fetch at a time per output directory.
+//!
+//! A cron or daemon run and a manual `raschietto fetch` can overlap, and
+//! with a name template without `{timestamp}` both would write the same
+//! export, sidecars and change manifest, and each would clear the other's
+//! `.part` files on start. Every fetch therefore takes an advisory lock on
+//! `<output>/.raschietto.lock` before touching the directory and holds it
+//! until it has finished (pruning included). A second run waits for it, up
+//! to `--lock-timeout`, then gives up. The lock belongs to the open file,
+//! so a run that crashes or is killed never leaves it behind; the file
+//! itself stays, holding the pid of the last run that took it.
+
+use anyhow::{bail, Context, Result};
+use std::fs::{File, OpenOptions, TryLockError};
+use std::io::Write;
+use std::path::Path;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Lock file in the output directory; hidden, so compitutto's watcher and
+/// the retention pass leave it alone
+pub const LOCK_FILE: &str = ".raschietto.lock";
+
+/// How often a waiting run tries again
+const POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Held for as long as the run writes to its output directory; dropping it
+/// releases the lock.
+#[derive(Debug)]
+pub struct OutputLock {
+    _file: File,
+}
+
+impl OutputLock {
+    /// Lock `output_dir` (created if needed), waiting up to `timeout` for
+    /// another run to release it. A zero timeout fails right away.
+    pub async fn acquire(output_dir: &Path, timeout: Duration) -> Result<Self> {
+        std::fs::create_dir_all(output_dir)
+            .with_context(|| format!("Failed to create {:?}", output_dir))?;
+        let path = output_dir.join(LOCK_FILE);
+        let started = Instant::now();
+        let mut announced = false;
+        loop {
+            if let Some(lock) = Self::try_acquire(&path)? {
+                return Ok(lock);
+            }
+            let holder = holder(&path);
+            if started.elapsed() >= timeout {
+                bail!(
+                    "Another raschietto run{} is still writing to {:?} after {}s; try again later or raise --lock-timeout",
+                    holder,
+                    output_dir,
+                    timeout.as_secs()
+                );
+            }
+            if !announced {
+                info!(
+                    "Another raschietto run{} is writing to {:?}; waiting for it",
+                    holder, output_dir
+                );
+                announced = true;
+            }
+            tokio::time::sleep(POLL_INTERVAL.min(timeout.saturating_sub(started.elapsed()))).await;
+        }
+    }
+
+    /// Take the lock at `path` if it's free; `None` if another run has it.
+    fn try_acquire(path: &Path) -> Result<Option<Self>> {
+        let mut file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .with_context(|| format!("Failed to open {:?}", path))?;
+        match file.try_lock() {
+            Ok(()) => {}
+            Err(TryLockError::WouldBlock) => return Ok(None),
+            Err(TryLockError::Error(e)) => {
+                return Err(e).with_context(|| format!("Failed to lock {:?}", path))
+            }
+        }
+        // For the message a waiting run prints; not needed for the lock
+        let _ = file
+            .set_len(0)
+            .and_then(|()| writeln!(file, "{}", std::process::id()));
+        Ok(Some(Self { _file: file }))
+    }
+}
+
+/// " (pid N)" for the run holding the lock at `path`, when it says
+fn holder(path: &Path) -> String {
+    std::fs::read_to_string(path)
+        .ok()
+        .and_then(|s| s.trim().parse::<u32>().ok())
+        .map(|pid| format!(" (pid {})", pid))
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    fn scratch_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("raschietto-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        dir
+    }
+
+    #[tokio::test]
+    async fn test_second_run_waits_for_the_first() {
+        let dir = scratch_dir("lock");
+        let first = OutputLock::acquire(&dir, Duration::ZERO).await.unwrap();
+        assert_eq!(
+            holder(&dir.join(LOCK_FILE)),
+            format!(" (pid {})", std::process::id())
+        );
+
+        let err = OutputLock::acquire(&dir, Duration::ZERO).await.unwrap_err();
+        assert!(err.to_string().contains("--lock-timeout"));
+
+        // Released while the second run waits
+        let waiting = tokio::spawn({
+            let dir = dir.clone();
+            async move { OutputLock::acquire(&dir, Duration::from_secs(10)).await }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+        drop(first);
+        assert!(waiting.await.unwrap().is_ok());
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -1,22 +1,57 @@
 //! Raschietto - Automated fetcher for Classe Viva homework exports.
 //!
 //! Uses Playwright to automate logging into Classe Viva, navigating to the
-//! agenda page, and downloading homework exports as Excel files.
+//! agenda page, and downloading homework exports as Excel files. An
+//! experimental `--engine http` mode does the same with plain requests.
 
 mod browser;
+mod challenge;
+mod changes;
 mod config;
+mod credentials;
+mod details;
+mod doctor;
+mod download;
+mod grades;
+mod history;
+mod http_engine;
+mod last_import;
+mod lock;
+mod metrics;
+mod naming;
+mod network;
+mod notify;
+mod preview;
+mod profile;
+mod register;
+mod retention;
+mod schedule;
 mod scraper;
+mod session;
+mod status;
+mod wait;
 
 use anyhow::{Context, Result};
 use chrono::NaiveDate;
-use clap::{Parser, Subcommand};
-use std::path::PathBuf;
-use tracing::{error, info};
+use clap::{Parser, Subcommand, ValueEnum};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{error, info, warn};
 use tracing_subscriber::EnvFilter;
 
 use browser::{BrowserOptions, BrowserSession};
 use config::Credentials;
-use scraper::{ClasseVivaScraper, DateRange};
+use credentials::{Store, Vault};
+use http_engine::HttpFetcher;
+use metrics::{Phase, Timings};
+use naming::{NameContext, NameTemplate};
+use network::NetworkOptions;
+use notify::{FetchFailure, Notifier};
+use profile::PortalProfile;
+use retention::Retention;
+use schedule::QuietHours;
+use scraper::{AgendaFilter, ClasseVivaScraper, DateRange, ExportKind, FetchPlan, LoginMode};
+use session::CookieFormat;
 
 #[derive(Parser)]
 #[command(name = "raschietto")]
@@ -25,6 +60,36 @@ use scraper::{ClasseVivaScraper, DateRange};
 struct Cli {
     #[command(subcommand)]
     command: Commands,
+
+    /// Portal profile to use (built-in: classeviva, or one defined in the config file)
+    #[arg(long, global = true, env = "RASCHIETTO_PROFILE", default_value = profile::DEFAULT_PROFILE)]
+    profile: String,
+
+    /// Config file with custom portal profiles
+    #[arg(long, global = true, default_value = profile::DEFAULT_CONFIG_FILE)]
+    config: PathBuf,
+
+    /// Where the logged-in browser session is saved (default: .raschietto/<profile>.json)
+    #[arg(long, global = true, env = "RASCHIETTO_SESSION_FILE")]
+    session_file: Option<PathBuf>,
+
+    /// Encrypted credentials file (default: .raschietto/<profile>.credentials.age)
+    #[arg(long, global = true, env = "RASCHIETTO_CREDENTIALS_FILE")]
+    credentials_file: Option<PathBuf>,
+
+    /// age key file that unlocks the credentials file instead of a
+    /// passphrase; `credentials set --store file` creates it if missing
+    #[arg(long, global = true, env = "RASCHIETTO_KEY_FILE")]
+    key_file: Option<PathBuf>,
+
+    /// PEM bundle of extra CA certificates to trust for the portal (a
+    /// school network that intercepts TLS)
+    #[arg(long, global = true, env = "RASCHIETTO_CA_CERT")]
+    ca_cert: Option<PathBuf>,
+
+    /// Don't check the portal's certificates at all. Unsafe: prefer --ca-cert
+    #[arg(long, global = true, env = "RASCHIETTO_IGNORE_HTTPS_ERRORS")]
+    ignore_https_errors: bool,
 }
 
 #[derive(Subcommand)]
@@ -41,6 +106,18 @@ enum Commands {
         #[arg(long)]
         to: Option<NaiveDate>,
 
+        /// Start the range at the newest agenda date compitutto has
+        /// imported (never later than today), read from its database file
+        /// or a server URL such as http://localhost:8080. Falls back to the
+        /// default range if compitutto can't be read.
+        #[arg(
+            long,
+            value_name = "DB|URL",
+            env = "RASCHIETTO_SINCE_LAST_IMPORT",
+            conflicts_with = "from"
+        )]
+        since_last_import: Option<String>,
+
         /// Show browser window instead of running headless
         #[arg(long)]
         headed: bool,
@@ -49,13 +126,214 @@ enum Commands {
         #[arg(long)]
         dry_run: bool,
 
+        /// Log in by hand in a visible browser window, then continue the
+        /// export automatically. Useful when the login form changes or a
+        /// CAPTCHA appears. Implies --headed.
+        #[arg(long)]
+        manual_login: bool,
+
+        /// Seconds to wait for a manual login to complete
+        #[arg(long, default_value_t = 300)]
+        login_timeout: u64,
+
         /// Output directory for downloaded files
         /// Default: ./data
         #[arg(long, short)]
         output: Option<PathBuf>,
+
+        /// Filename template for the download. Placeholders: {student},
+        /// {profile}, {from}, {to}, {date}, {timestamp}. Must start with
+        /// "export_" and end in .xls/.xlsx.
+        #[arg(long, env = "RASCHIETTO_NAME_TEMPLATE", default_value = naming::DEFAULT_NAME_TEMPLATE)]
+        name_template: String,
+
+        /// Also open each agenda item's detail popup and save the full note
+        /// text to a .details.json sidecar next to the export
+        #[arg(long)]
+        details: bool,
+
+        /// Choose this class in the agenda's class dropdown before reading
+        /// or exporting it (an option's label or value, or a unique part of
+        /// the label)
+        #[arg(long, value_name = "NAME", env = "RASCHIETTO_CLASS")]
+        class: Option<String>,
+
+        /// Choose this group in the agenda's group (gruppo) dropdown, for
+        /// teachers who give each group its own homework
+        #[arg(long, value_name = "NAME", env = "RASCHIETTO_GROUP")]
+        group: Option<String>,
+
+        /// What to export, in one logged-in session (comma-separated)
+        #[arg(
+            long = "export",
+            value_enum,
+            value_delimiter = ',',
+            default_value = "agenda"
+        )]
+        exports: Vec<ExportKind>,
+
+        /// How to talk to the portal. "http" (experimental) skips the browser
+        /// and falls back to it if the HTTP flow fails.
+        #[arg(long, value_enum, env = "RASCHIETTO_ENGINE", default_value_t = Engine::Browser)]
+        engine: Engine,
+
+        /// Wait a random 0 to 2×N minutes before starting, so a cron job
+        /// scheduled N minutes early runs within ±N minutes of its time (at
+        /// most 720)
+        #[arg(
+            long,
+            value_name = "MINUTES",
+            env = "RASCHIETTO_JITTER",
+            default_value_t = 0,
+            value_parser = clap::value_parser!(u64).range(0..=720)
+        )]
+        jitter: u64,
+
+        /// Skip the fetch if it would start in this window, e.g. 22:00-07:00
+        #[arg(long, value_name = "HH:MM-HH:MM", env = "RASCHIETTO_QUIET_HOURS")]
+        quiet_hours: Option<QuietHours>,
+
+        /// After a successful fetch, keep only the N newest exports in the
+        /// output directory; older ones go with their sidecars
+        #[arg(
+            long,
+            value_name = "N",
+            env = "RASCHIETTO_KEEP_LAST",
+            value_parser = clap::value_parser!(u64).range(1..),
+            conflicts_with = "keep_days"
+        )]
+        keep_last: Option<u64>,
+
+        /// After a successful fetch, delete exports (and their sidecars and
+        /// debug artifacts) older than DAYS, always keeping the newest
+        #[arg(
+            long,
+            value_name = "DAYS",
+            env = "RASCHIETTO_KEEP_DAYS",
+            value_parser = clap::value_parser!(u64).range(1..)
+        )]
+        keep_days: Option<u64>,
+
+        /// Seconds to wait for another run writing to the same output
+        /// directory to finish before giving up (0: don't wait)
+        #[arg(
+            long,
+            value_name = "SECONDS",
+            env = "RASCHIETTO_LOCK_TIMEOUT",
+            default_value_t = 600
+        )]
+        lock_timeout: u64,
+
+        /// After each run, write its outcome and step timings to this file
+        /// in Prometheus' text format (e.g. for node_exporter's textfile
+        /// collector; one file per profile)
+        #[arg(long, value_name = "FILE", env = "RASCHIETTO_METRICS_FILE")]
+        metrics_file: Option<PathBuf>,
+
+        /// After downloading, print the export's first N entries (date,
+        /// subject, task) to check the range and student at a glance
+        #[arg(
+            long,
+            value_name = "N",
+            num_args = 0..=1,
+            default_missing_value = "10"
+        )]
+        preview: Option<usize>,
+    },
+
+    /// Check whether the saved session still works, without downloading
+    /// anything. Exits 1 when a fetch would need to log in again.
+    Status,
+
+    /// Show the last fetches logged in the output directory: when, the
+    /// range, how they ended, the export and its entry count
+    History {
+        /// Output directory the fetches wrote to
+        /// Default: ./data
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+
+        /// How many runs to show, newest first
+        #[arg(long, short = 'n', value_name = "N", default_value_t = history::DEFAULT_SHOWN)]
+        last: usize,
+
+        /// Print the runs as JSON lines, oldest first
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Work with the saved browser session
+    Session {
+        #[command(subcommand)]
+        command: SessionCommand,
+    },
+
+    /// Keep the portal login in the OS keychain or an encrypted file,
+    /// instead of CLASSEVIVA_PASSWORD
+    Credentials {
+        #[command(subcommand)]
+        command: CredentialsCommand,
+    },
+
+    /// Check the Playwright driver and browser install, and say how to fix it
+    Doctor {
+        /// Download Chromium for this OS/CPU and repair the driver where possible
+        #[arg(long)]
+        fix: bool,
     },
 }
 
+#[derive(Subcommand)]
+enum SessionCommand {
+    /// Print the portal's cookies from the saved session, so scripts (curl,
+    /// wget, other scrapers) can reuse the login
+    Export {
+        /// Cookie file format
+        #[arg(long, value_enum, default_value_t = CookieFormat::Netscape)]
+        format: CookieFormat,
+
+        /// Write to this file (owner-only on Unix) instead of stdout
+        #[arg(long, short)]
+        output: Option<PathBuf>,
+    },
+}
+
+#[derive(Subcommand)]
+enum CredentialsCommand {
+    /// Store a username and password (asked for, or read from stdin when
+    /// it's piped: username, then password, one per line)
+    Set {
+        #[arg(long, value_enum, default_value_t = Store::Keychain)]
+        store: Store,
+
+        /// Don't ask for the username
+        #[arg(long)]
+        username: Option<String>,
+    },
+
+    /// Show the stored username and where it's kept
+    Get {
+        /// Print the password too
+        #[arg(long)]
+        show_password: bool,
+    },
+
+    /// Remove the stored login (from both stores without --store)
+    Delete {
+        #[arg(long, value_enum)]
+        store: Option<Store>,
+    },
+}
+
+/// Fetch engine.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Engine {
+    /// Drive Chromium through Playwright
+    Browser,
+    /// Plain HTTP requests with a cookie jar; no browser needed
+    Http,
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     // Initialize tracing
@@ -66,81 +344,447 @@ async fn main() -> Result<()> {
         .init();
 
     let cli = Cli::parse();
+    let portal = PortalProfile::load(&cli.profile, &cli.config)?;
+    let profile_name = cli.profile;
+    let session_file = cli
+        .session_file
+        .unwrap_or_else(|| session::default_path(&profile_name));
+    let vault = Vault::new(
+        &profile_name,
+        cli.credentials_file
+            .unwrap_or_else(|| credentials::default_file(&profile_name)),
+        cli.key_file,
+    );
+    let (ca_cert, ignore_https_errors) = (cli.ca_cert, cli.ignore_https_errors);
+    let network = || NetworkOptions::load(ca_cert.as_deref(), ignore_https_errors);
 
     match cli.command {
         Commands::Fetch {
             from,
             to,
+            since_last_import,
             headed,
             dry_run,
+            manual_login,
+            login_timeout,
             output,
+            name_template,
+            details,
+            class,
+            group,
+            exports,
+            engine,
+            jitter,
+            quiet_hours,
+            keep_last,
+            keep_days,
+            lock_timeout,
+            metrics_file,
+            preview,
         } => {
-            fetch_command(from, to, headed, dry_run, output).await?;
+            let name_template = NameTemplate::parse(&name_template)?;
+            if !wait_for_start(jitter, quiet_hours).await {
+                return Ok(());
+            }
+            let from = match &since_last_import {
+                Some(source) => last_import_start(source, to).await,
+                None => from,
+            };
+            let range = date_range(from, to);
+            let login_mode = if manual_login {
+                LoginMode::Manual {
+                    timeout: Duration::from_secs(login_timeout),
+                }
+            } else {
+                LoginMode::Automatic
+            };
+            let output_dir = output.unwrap_or_else(|| PathBuf::from("data"));
+            // Held until the end of the fetch, pruning included
+            let _lock =
+                lock::OutputLock::acquire(&output_dir, Duration::from_secs(lock_timeout)).await?;
+            let options = FetchOptions {
+                headed,
+                dry_run,
+                login_mode,
+                output_dir: output_dir.clone(),
+                name_template,
+                plan: FetchPlan::new(&exports, details).with_filter(AgendaFilter { class, group }),
+                engine,
+                session_file,
+                preview,
+                vault,
+                network: network()?,
+            };
+            let notifier = Notifier::from_env();
+            let mut artifacts = Vec::new();
+            let mut timings = Timings::default();
+            let started = chrono::Local::now();
+            let result = fetch_command(
+                portal,
+                &profile_name,
+                &range,
+                options,
+                &mut artifacts,
+                &mut timings,
+            )
+            .await;
+            let run =
+                history::Run::finished(started, &profile_name, &range, dry_run, &result, timings);
+            if let Err(e) = history::record(&output_dir, &run) {
+                warn!("Couldn't add the run to the fetch history: {:#}", e);
+            }
+            if let Some(path) = &metrics_file {
+                if let Err(e) = metrics::write_textfile(path, &run) {
+                    warn!("Couldn't write the metrics file: {:#}", e);
+                }
+            }
+            if let Err(e) = result {
+                notifier
+                    .notify(&FetchFailure::new(&profile_name, &e, artifacts))
+                    .await;
+                if challenge::find(&e).is_some() {
+                    error!("{:#}", e);
+                    std::process::exit(challenge::EXIT_CODE);
+                }
+                return Err(e);
+            }
+            if let Some(retention) = Retention::from_args(keep_last, keep_days) {
+                if !dry_run {
+                    prune_output(&output_dir, retention);
+                }
+            }
+        }
+        Commands::Status => {
+            let checks = status::run(portal, &session_file, network()?).await;
+            print!("{}", doctor::render(&checks));
+            if checks.iter().any(|c| c.status == doctor::Status::Fail) {
+                std::process::exit(1);
+            }
+        }
+        Commands::History { output, last, json } => {
+            let output_dir = output.unwrap_or_else(|| PathBuf::from("data"));
+            let runs = history::load(&output_dir)?;
+            if json {
+                let skip = runs.len().saturating_sub(last);
+                for run in &runs[skip..] {
+                    println!("{}", serde_json::to_string(run)?);
+                }
+            } else {
+                print!("{}", history::render(&runs, last));
+            }
+        }
+        Commands::Session {
+            command: SessionCommand::Export { format, output },
+        } => {
+            let state = session::load(&session_file)?;
+            let host = session::host_of(&portal.agenda_url);
+            let cookies = session::export_cookies(&state, &host, format)?;
+            match output {
+                Some(path) => {
+                    session::write_private(&path, cookies.as_bytes())?;
+                    info!("Cookies written to {:?}", path);
+                }
+                None => print!("{}", cookies),
+            }
+        }
+        Commands::Credentials { command } => credentials_command(command, &vault)?,
+        Commands::Doctor { fix } => {
+            let checks = doctor::run(fix).await;
+            print!("{}", doctor::render(&checks));
+            if checks.iter().any(|c| c.status == doctor::Status::Fail) {
+                std::process::exit(1);
+            }
         }
     }
 
     Ok(())
 }
 
-async fn fetch_command(
-    from: Option<NaiveDate>,
-    to: Option<NaiveDate>,
+/// `raschietto credentials set/get/delete`
+fn credentials_command(command: CredentialsCommand, vault: &Vault) -> Result<()> {
+    match command {
+        CredentialsCommand::Set { store, username } => {
+            let username = match username {
+                Some(username) => username,
+                None => credentials::read_line("Username: ")?,
+            };
+            let password = credentials::read_password("Password: ")?;
+            if username.is_empty() || password.is_empty() {
+                anyhow::bail!("Both a username and a password are needed");
+            }
+            vault.save(store, &Credentials { username, password })?;
+            match store {
+                Store::Keychain => println!("Saved in the OS keychain"),
+                Store::File => println!("Saved in {:?}", vault.file()),
+            }
+            if Credentials::from_env().is_ok() {
+                warn!("CLASSEVIVA_USER and CLASSEVIVA_PASSWORD are set too, and are used first");
+            }
+        }
+        CredentialsCommand::Get { show_password } => match vault.load()? {
+            Some((credentials, store)) => {
+                println!("{} (in {})", credentials.username, store.describe());
+                if show_password {
+                    println!("{}", credentials.password);
+                }
+            }
+            None => anyhow::bail!("No login stored; run `raschietto credentials set`"),
+        },
+        CredentialsCommand::Delete { store } => {
+            let stores = store.map_or(vec![Store::Keychain, Store::File], |s| vec![s]);
+            let mut deleted = false;
+            for each in stores {
+                match vault.delete(each) {
+                    Ok(true) => {
+                        println!("Deleted the login from {}", each.describe());
+                        deleted = true;
+                    }
+                    Ok(false) => {}
+                    // Without --store, an unreachable keychain has nothing to delete
+                    Err(e) if store.is_none() => warn!("{:#}", e),
+                    Err(e) => return Err(e),
+                }
+            }
+            if !deleted {
+                println!("No login stored");
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Sleep off the jitter. Returns false, without waiting, when the fetch
+/// would start in quiet hours.
+async fn wait_for_start(jitter: u64, quiet_hours: Option<QuietHours>) -> bool {
+    let delay = schedule::jitter_delay(jitter);
+    let now = chrono::Local::now().naive_local();
+    let Some(start) = schedule::start_time(now, delay, quiet_hours) else {
+        info!("Quiet hours; skipping this fetch");
+        return false;
+    };
+    if !delay.is_zero() {
+        info!(
+            "Waiting {}s (jitter); starting at {}",
+            delay.as_secs(),
+            start.format("%H:%M:%S")
+        );
+        tokio::time::sleep(delay).await;
+    }
+    true
+}
+
+/// `--since-last-import`: where compitutto's imports end, or `None` (the
+/// default range) when it has none or can't be read
+async fn last_import_start(source: &str, to: Option<NaiveDate>) -> Option<NaiveDate> {
+    let source = last_import::Source::parse(source);
+    match last_import::newest_date(&source).await {
+        Ok(Some(newest)) => {
+            let today = chrono::Local::now().date_naive();
+            let from = last_import::start_date(newest, today);
+            info!(
+                "Newest imported agenda date is {}; fetching from {}",
+                newest, from
+            );
+            Some(to.map_or(from, |to| from.min(to)))
+        }
+        Ok(None) => {
+            info!("compitutto has no imported entries yet; using the default range");
+            None
+        }
+        Err(e) => {
+            warn!(
+                "Couldn't read the last import ({:#}); using the default range",
+                e
+            );
+            None
+        }
+    }
+}
+
+/// Delete old exports after a fetch. Best effort: a fetch that worked doesn't
+/// fail over this.
+fn prune_output(output_dir: &Path, retention: Retention) {
+    match retention::prune(output_dir, retention, std::time::SystemTime::now()) {
+        Ok(removed) if removed.is_empty() => {}
+        Ok(removed) => info!("Pruned {} old file(s) from {:?}", removed.len(), output_dir),
+        Err(e) => warn!("Couldn't prune old exports: {:#}", e),
+    }
+}
+
+/// How a fetch should run and where its download goes.
+struct FetchOptions {
     headed: bool,
     dry_run: bool,
-    output: Option<PathBuf>,
-) -> Result<()> {
-    // Load credentials
-    let credentials = Credentials::from_env().context("Failed to load credentials")?;
-    info!("Loaded credentials for user: {}", credentials.username);
-
-    // Determine date range
-    let range = match (from, to) {
-        (Some(f), Some(t)) => DateRange::new(f, t),
-        (Some(f), None) => {
-            let default = DateRange::default_range();
-            DateRange::new(f, default.to)
-        }
-        (None, Some(t)) => {
-            let default = DateRange::default_range();
-            DateRange::new(default.from, t)
-        }
-        (None, None) => DateRange::default_range(),
+    login_mode: LoginMode,
+    output_dir: PathBuf,
+    name_template: NameTemplate,
+    plan: FetchPlan,
+    engine: Engine,
+    /// Where to save the browser session after logging in
+    session_file: PathBuf,
+    /// `--preview`: how many entries of the export to print
+    preview: Option<usize>,
+    /// Stored login, when the environment has none
+    vault: Vault,
+    /// `--ca-cert`, `--ignore-https-errors` and the proxy
+    network: NetworkOptions,
+}
+
+/// `--from`/`--to`, each defaulting to its end of the default range
+fn date_range(from: Option<NaiveDate>, to: Option<NaiveDate>) -> DateRange {
+    let default = DateRange::default_range();
+    DateRange::new(from.unwrap_or(default.from), to.unwrap_or(default.to))
+}
+
+/// Run a fetch. Returns the agenda export it saved, if any. Debug artifacts
+/// saved on failure are added to `artifacts`, and the time each step took
+/// to `timings`, whether it worked or not.
+async fn fetch_command(
+    portal: PortalProfile,
+    profile_name: &str,
+    range: &DateRange,
+    options: FetchOptions,
+    artifacts: &mut Vec<PathBuf>,
+    timings: &mut Timings,
+) -> Result<Option<PathBuf>> {
+    let FetchOptions {
+        headed,
+        dry_run,
+        login_mode,
+        output_dir,
+        name_template,
+        plan,
+        engine,
+        session_file,
+        preview,
+        vault,
+        network,
+    } = options;
+
+    // Load credentials (optional when logging in by hand)
+    let credentials = match login_mode {
+        LoginMode::Automatic => {
+            Some(Credentials::load(&vault).context("Failed to load credentials")?)
+        }
+        LoginMode::Manual { .. } => Credentials::load(&vault).ok(),
     };
+    if let Some(credentials) = &credentials {
+        info!("Loaded credentials for user: {}", credentials.username);
+    }
+
     info!("Date range: {} to {}", range.from, range.to);
+    info!("Portal: {}", portal.agenda_url);
+    let filter = plan.filter();
+    if let Some(class) = &filter.class {
+        info!("Class: {}", class);
+    }
+    if let Some(group) = &filter.group {
+        info!("Group: {}", group);
+    }
 
-    // Determine output directory
-    let output_dir = output.unwrap_or_else(|| PathBuf::from("data"));
     if !output_dir.exists() {
         std::fs::create_dir_all(&output_dir).context("Failed to create output directory")?;
     }
     info!("Output directory: {:?}", output_dir);
+    download::remove_partial_files(&output_dir)?;
+
+    let filename = name_template.render(&NameContext {
+        student: credentials.as_ref().map(|c| c.username.as_str()),
+        profile: profile_name,
+        from: range.from,
+        to: range.to,
+        now: chrono::Local::now(),
+    });
+    info!("Output filename: {}", filename);
 
-    // Launch browser
-    let options = BrowserOptions { headed };
+    if engine == Engine::Http {
+        if plan.needs_browser() || matches!(login_mode, LoginMode::Manual { .. }) {
+            info!("--details, --class, --group, --export grades/absences/notes and --manual-login need the browser; not using the HTTP engine");
+        } else if let Some(credentials) = &credentials {
+            let fetcher = HttpFetcher::new(portal.clone(), credentials.clone(), &network)?;
+            let fetched = fetcher.fetch(range, &output_dir, &filename, dry_run).await;
+            timings.merge(fetcher.timings());
+            match fetched {
+                Ok(Some(path)) => {
+                    info!("Successfully downloaded to: {:?}", path);
+                    report_changes(&output_dir, profile_name, range, &path);
+                    if let Some(limit) = preview {
+                        print_preview(&path, limit, Some(&credentials.username), range);
+                    }
+                    return Ok(Some(path));
+                }
+                Ok(None) => {
+                    info!("Dry run completed successfully");
+                    return Ok(None);
+                }
+                Err(e) => warn!("HTTP engine failed ({:#}); falling back to the browser", e),
+            }
+        }
+    }
+
+    // Launch browser (manual login needs a window to type into)
+    let headed = headed || matches!(login_mode, LoginMode::Manual { .. });
+    let options = BrowserOptions {
+        headed,
+        network: network.clone(),
+    };
     info!(
         "Launching browser ({})",
         if headed { "headed" } else { "headless" }
     );
 
+    let launch_started = std::time::Instant::now();
     let session = BrowserSession::launch(options)
         .await
-        .context("Failed to launch browser")?;
-
-    // Create browser context
-    let context = session.new_context().await?;
+        .context("Failed to launch browser");
+    let context = match session {
+        Ok(session) => session
+            .new_context()
+            .await
+            .map(|context| (session, context)),
+        Err(e) => Err(e),
+    };
+    timings.add(Phase::Launch, launch_started.elapsed());
+    let (session, context) = context?;
 
     // Create scraper and run
-    let scraper = ClasseVivaScraper::new(context, credentials);
+    let student = credentials.as_ref().map(|c| c.username.clone());
+    let scraper = ClasseVivaScraper::new(context, portal, credentials, login_mode)
+        .with_debug_dir(output_dir.join(scraper::DEBUG_DIR))
+        .with_network(network);
 
-    match scraper.fetch(range, &output_dir, dry_run).await {
-        Ok(Some(path)) => {
-            info!("Successfully downloaded to: {:?}", path);
-        }
-        Ok(None) => {
+    let result = scraper
+        .fetch(&plan, range.clone(), &output_dir, &filename, dry_run)
+        .await;
+    timings.merge(scraper.timings());
+    if result.is_ok() {
+        save_session(&scraper, &session_file).await;
+    }
+    let mut saved = None;
+    match result {
+        Ok(_) if dry_run => {
             info!("Dry run completed successfully");
         }
+        Ok(paths) => {
+            for path in &paths {
+                info!("Successfully saved: {:?}", path);
+            }
+            let export = output_dir.join(&filename);
+            if paths.contains(&export) {
+                report_changes(&output_dir, profile_name, range, &export);
+                if let Some(limit) = preview {
+                    print_preview(&export, limit, student.as_deref(), range);
+                }
+                saved = Some(export);
+            }
+        }
         Err(e) => {
             error!("Fetch failed: {}", e);
+            if let Some(screenshot) = challenge::find(&e).and_then(|c| c.screenshot.clone()) {
+                artifacts.push(screenshot);
+            }
+            artifacts.extend(scraper.save_debug_artifacts(&output_dir).await);
+            let _ = session.close().await;
             return Err(e);
         }
     }
@@ -148,5 +792,50 @@ async fn fetch_command(
     // Close browser
     session.close().await?;
 
-    Ok(())
+    Ok(saved)
+}
+
+/// Print what changed in the agenda since the previous fetch. Best effort:
+/// a fetch that worked doesn't fail over this.
+fn report_changes(output_dir: &Path, profile: &str, range: &DateRange, export: &Path) {
+    match changes::compare_with_previous(output_dir, profile, range, export) {
+        Ok(None) => info!("First fetch for this profile; nothing to compare with yet"),
+        Ok(Some(diff)) if diff.is_empty() => info!("No agenda changes since the last fetch"),
+        Ok(Some(diff)) => {
+            info!(
+                added = diff.added.len(),
+                removed = diff.removed.len(),
+                changed = diff.changed.len(),
+                "Agenda changed since the last fetch"
+            );
+            println!("Agenda changes since the last fetch:");
+            print!("{}", diff.render());
+        }
+        Err(e) => warn!("Couldn't compare with the last fetch: {:#}", e),
+    }
+}
+
+/// `--preview`: print the first `limit` entries of the export. Best effort,
+/// like the change report.
+fn print_preview(export: &Path, limit: usize, student: Option<&str>, range: &DateRange) {
+    let rows = std::fs::read(export)
+        .with_context(|| format!("Failed to read {:?}", export))
+        .and_then(|bytes| changes::read_rows(&bytes));
+    match rows {
+        Ok(rows) => print!("{}", preview::render(&rows, limit, student, range)),
+        Err(e) => warn!("Couldn't preview the export: {:#}", e),
+    }
+}
+
+/// Save the logged-in session for `raschietto status`. Best effort: a fetch
+/// that worked doesn't fail over this.
+async fn save_session(scraper: &ClasseVivaScraper, path: &std::path::Path) {
+    let saved = match scraper.storage_state().await {
+        Ok(state) => session::save(path, &state),
+        Err(e) => Err(e),
+    };
+    match saved {
+        Ok(()) => info!("Session saved to {:?}", path),
+        Err(e) => warn!("Couldn't save the session: {:#}", e),
+    }
 }
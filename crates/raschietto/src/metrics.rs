@@ -0,0 +1,285 @@
+//! How long each part of a fetch takes, and a Prometheus textfile for
+//! keeping an eye on it.
+//!
+//! Both engines time their steps (launching the browser, logging in,
+//! reaching the agenda page, the export dialog, the download, and the
+//! details/grades/register scraping) into a [`Timings`], which ends up in
+//! the run's history line (`raschietto history` shows it under the run).
+//! A step that fails still counts the time it took, so a timeout shows up as
+//! the step that hit it. With `--metrics-file` every run also rewrites a
+//! file in Prometheus' text format for node_exporter's textfile collector
+//! (or anything else that reads it), so a dashboard can show which step is
+//! creeping up over the school year before it needs a longer wait.
+
+use serde::{Deserialize, Serialize};
+use std::fmt::Write as _;
+use std::future::Future;
+use std::path::Path;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::history::{Outcome, Run};
+
+/// A timed part of a fetch
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum Phase {
+    /// Starting Chromium and a browser context
+    Launch,
+    /// From opening the agenda to being logged in on it
+    Login,
+    /// Logging in with `--engine http`, kept apart from `Login` so a
+    /// fallback to the browser doesn't add both up as one login
+    HttpLogin,
+    /// Going to each step's page and choosing the class and group
+    Navigate,
+    /// Opening the export dialog and filling in the dates
+    Dialog,
+    /// Confirming the export until the file is saved
+    Download,
+    /// Reading the agenda's detail popups and saving photos
+    Details,
+    /// Reading grades, absences and notes
+    Register,
+}
+
+impl Phase {
+    pub fn label(self) -> &'static str {
+        match self {
+            Phase::Launch => "launch",
+            Phase::Login => "login",
+            Phase::HttpLogin => "http_login",
+            Phase::Navigate => "navigate",
+            Phase::Dialog => "dialog",
+            Phase::Download => "download",
+            Phase::Details => "details",
+            Phase::Register => "register",
+        }
+    }
+}
+
+/// Time spent in one phase, over the whole run
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct StepTiming {
+    pub step: Phase,
+    pub seconds: f64,
+}
+
+/// Time per phase, in the order they first ran
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Timings {
+    steps: Vec<StepTiming>,
+}
+
+impl Timings {
+    /// Add `elapsed` to `phase` (a phase can run more than once, e.g.
+    /// navigating to each export's page)
+    pub fn add(&mut self, phase: Phase, elapsed: Duration) {
+        let seconds = elapsed.as_secs_f64();
+        match self.steps.iter_mut().find(|s| s.step == phase) {
+            Some(step) => step.seconds += seconds,
+            None => self.steps.push(StepTiming {
+                step: phase,
+                seconds,
+            }),
+        }
+    }
+
+    /// Add every phase of `other`
+    pub fn merge(&mut self, other: Timings) {
+        for step in other.steps {
+            self.add(step.step, Duration::from_secs_f64(step.seconds));
+        }
+    }
+
+    /// The phases, rounded to milliseconds
+    pub fn into_steps(self) -> Vec<StepTiming> {
+        self.steps
+            .into_iter()
+            .map(|s| StepTiming {
+                seconds: (s.seconds * 1000.0).round() / 1000.0,
+                ..s
+            })
+            .collect()
+    }
+}
+
+/// Await `work`, adding the time it took to `phase` whether it worked or not
+pub async fn timed<T>(timings: &Mutex<Timings>, phase: Phase, work: impl Future<Output = T>) -> T {
+    let started = Instant::now();
+    let result = work.await;
+    timings.lock().unwrap().add(phase, started.elapsed());
+    result
+}
+
+/// `run` in Prometheus' text exposition format. `finished` is the Unix time
+/// the run ended.
+pub fn render_textfile(run: &Run, finished: i64) -> String {
+    let profile = escape_label(&run.profile);
+    let mut out = String::new();
+    let mut gauge = |name: &str, help: &str, samples: &[(String, String)]| {
+        let _ = writeln!(out, "# HELP raschietto_{} {}", name, help);
+        let _ = writeln!(out, "# TYPE raschietto_{} gauge", name);
+        for (labels, value) in samples {
+            let _ = writeln!(out, "raschietto_{}{{{}}} {}", name, labels, value);
+        }
+    };
+    let labels = format!("profile=\"{}\"", profile);
+
+    gauge(
+        "last_run_timestamp_seconds",
+        "When the last fetch ended, as a Unix time.",
+        &[(labels.clone(), finished.to_string())],
+    );
+    gauge(
+        "last_run_success",
+        "Whether the last fetch worked (1) or not (0).",
+        &[(
+            labels.clone(),
+            u8::from(matches!(run.outcome, Outcome::Ok | Outcome::DryRun)).to_string(),
+        )],
+    );
+    gauge(
+        "last_run_challenge",
+        "Whether the last fetch was stopped by a CAPTCHA or browser check.",
+        &[(
+            labels.clone(),
+            u8::from(run.outcome == Outcome::Challenge).to_string(),
+        )],
+    );
+    gauge(
+        "last_run_duration_seconds",
+        "How long the last fetch took.",
+        &[(labels.clone(), run.seconds.to_string())],
+    );
+    if let Some(entries) = run.entries {
+        gauge(
+            "last_run_entries",
+            "Agenda rows in the last export.",
+            &[(labels.clone(), entries.to_string())],
+        );
+    }
+    if !run.timings.is_empty() {
+        let samples: Vec<(String, String)> = run
+            .timings
+            .iter()
+            .map(|t| {
+                (
+                    format!("{},step=\"{}\"", labels, t.step.label()),
+                    t.seconds.to_string(),
+                )
+            })
+            .collect();
+        gauge(
+            "last_run_step_seconds",
+            "Time the last fetch spent in each step.",
+            &samples,
+        );
+    }
+    out
+}
+
+/// Backslash, quote and newline escaped for a label value
+fn escape_label(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
+
+/// Replace the metrics file at `path` with `run`'s
+pub fn write_textfile(path: &Path, run: &Run) -> anyhow::Result<()> {
+    let text = render_textfile(run, chrono::Local::now().timestamp());
+    crate::download::write_atomic(path, text.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::NaiveDate;
+
+    #[test]
+    fn test_timings_add_up_per_phase() {
+        let mut timings = Timings::default();
+        timings.add(Phase::Login, Duration::from_millis(4200));
+        timings.add(Phase::Navigate, Duration::from_millis(300));
+        let mut scraper = Timings::default();
+        // The HTTP engine failed after logging in and the browser took over
+        scraper.add(Phase::HttpLogin, Duration::from_millis(800));
+        scraper.add(Phase::Navigate, Duration::from_millis(250));
+        scraper.add(Phase::Download, Duration::from_micros(1_234_567));
+        timings.merge(scraper);
+        assert_eq!(
+            timings.into_steps(),
+            vec![
+                StepTiming {
+                    step: Phase::Login,
+                    seconds: 4.2
+                },
+                StepTiming {
+                    step: Phase::Navigate,
+                    seconds: 0.55
+                },
+                StepTiming {
+                    step: Phase::HttpLogin,
+                    seconds: 0.8
+                },
+                StepTiming {
+                    step: Phase::Download,
+                    seconds: 1.235
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_textfile() {
+        let run = Run {
+            started: NaiveDate::from_ymd_opt(2025, 1, 15)
+                .unwrap()
+                .and_hms_opt(7, 30, 0)
+                .unwrap(),
+            seconds: 12,
+            profile: "scuola \"media\"".to_string(),
+            from: NaiveDate::from_ymd_opt(2025, 1, 8).unwrap(),
+            to: NaiveDate::from_ymd_opt(2025, 1, 30).unwrap(),
+            outcome: Outcome::Ok,
+            file: Some("export_20250115_073005.xls".to_string()),
+            entries: Some(42),
+            error: None,
+            timings: vec![
+                StepTiming {
+                    step: Phase::Launch,
+                    seconds: 1.5,
+                },
+                StepTiming {
+                    step: Phase::Login,
+                    seconds: 6.25,
+                },
+            ],
+        };
+        let text = render_textfile(&run, 1736922612);
+        assert!(text.contains(
+            "# TYPE raschietto_last_run_success gauge\n\
+             raschietto_last_run_success{profile=\"scuola \\\"media\\\"\"} 1\n"
+        ));
+        assert!(text.contains(
+            "raschietto_last_run_timestamp_seconds{profile=\"scuola \\\"media\\\"\"} 1736922612\n"
+        ));
+        assert!(text.contains("raschietto_last_run_entries{profile=\"scuola \\\"media\\\"\"} 42\n"));
+        assert!(text.contains(
+            "raschietto_last_run_step_seconds{profile=\"scuola \\\"media\\\"\",step=\"login\"} 6.25\n"
+        ));
+
+        let failed = Run {
+            outcome: Outcome::Failed,
+            entries: None,
+            timings: Vec::new(),
+            ..run
+        };
+        let text = render_textfile(&failed, 0);
+        assert!(text.contains("raschietto_last_run_success{profile=\"scuola \\\"media\\\"\"} 0\n"));
+        assert!(!text.contains("entries"));
+        assert!(!text.contains("step_seconds"));
+    }
+}
@@ -0,0 +1,168 @@
+//! Output filename templates for downloaded exports.
+//!
+//! A template such as `export_{student}_{from}_{to}_{timestamp}.xls` is
+//! expanded with:
+//!
+//! | Placeholder   | Value                                    |
+//! |---------------|------------------------------------------|
+//! | `{student}`   | Login username, sanitised for filenames  |
+//! | `{profile}`   | Portal profile name                      |
+//! | `{from}`      | Start of the export range (`YYYY-MM-DD`) |
+//! | `{to}`        | End of the export range (`YYYY-MM-DD`)   |
+//! | `{date}`      | Today (`YYYY-MM-DD`)                     |
+//! | `{timestamp}` | Local time of the fetch (`YYYYMMDD_HHMMSS`) |
+//!
+//! Names must start with `export_` and end in `.xls`/`.xlsx` so compitutto's
+//! file watcher picks them up.
+
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Local, NaiveDate};
+
+/// Template used when `--name-template` isn't given.
+pub const DEFAULT_NAME_TEMPLATE: &str = "export_{timestamp}.xls";
+
+const PLACEHOLDERS: &[&str] = &["student", "profile", "from", "to", "date", "timestamp"];
+
+/// Values substituted into a template.
+#[derive(Debug, Clone)]
+pub struct NameContext<'a> {
+    pub student: Option<&'a str>,
+    pub profile: &'a str,
+    pub from: NaiveDate,
+    pub to: NaiveDate,
+    pub now: DateTime<Local>,
+}
+
+/// A validated filename template.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NameTemplate(String);
+
+impl NameTemplate {
+    /// Parse and validate a template.
+    pub fn parse(template: &str) -> Result<Self> {
+        if template.contains(['/', '\\']) {
+            bail!(
+                "Name template must be a file name, not a path: {}",
+                template
+            );
+        }
+        if !template.starts_with("export_") {
+            bail!(
+                "Name template must start with 'export_' so compitutto picks the file up: {}",
+                template
+            );
+        }
+        if !(template.ends_with(".xls") || template.ends_with(".xlsx")) {
+            bail!("Name template must end in .xls or .xlsx: {}", template);
+        }
+
+        let mut rest = template;
+        while let Some(start) = rest.find('{') {
+            let end = rest[start..]
+                .find('}')
+                .ok_or_else(|| anyhow!("Unclosed '{{' in name template: {}", template))?;
+            let name = &rest[start + 1..start + end];
+            if !PLACEHOLDERS.contains(&name) {
+                bail!(
+                    "Unknown placeholder {{{}}} in name template (available: {})",
+                    name,
+                    PLACEHOLDERS
+                        .iter()
+                        .map(|p| format!("{{{}}}", p))
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            rest = &rest[start + end + 1..];
+        }
+
+        Ok(Self(template.to_string()))
+    }
+
+    /// Expand the template into a file name.
+    pub fn render(&self, ctx: &NameContext) -> String {
+        self.0
+            .replace("{student}", &sanitize(ctx.student.unwrap_or("unknown")))
+            .replace("{profile}", &sanitize(ctx.profile))
+            .replace("{from}", &ctx.from.format("%Y-%m-%d").to_string())
+            .replace("{to}", &ctx.to.format("%Y-%m-%d").to_string())
+            .replace("{date}", &ctx.now.format("%Y-%m-%d").to_string())
+            .replace("{timestamp}", &ctx.now.format("%Y%m%d_%H%M%S").to_string())
+    }
+}
+
+impl Default for NameTemplate {
+    fn default() -> Self {
+        Self(DEFAULT_NAME_TEMPLATE.to_string())
+    }
+}
+
+/// Keep letters, digits, `-` and `.`; everything else becomes `_`.
+fn sanitize(value: &str) -> String {
+    value
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn ctx(student: Option<&str>) -> NameContext<'_> {
+        NameContext {
+            student,
+            profile: "classeviva",
+            from: NaiveDate::from_ymd_opt(2025, 1, 8).unwrap(),
+            to: NaiveDate::from_ymd_opt(2025, 1, 30).unwrap(),
+            now: Local.with_ymd_and_hms(2025, 1, 15, 7, 30, 5).unwrap(),
+        }
+    }
+
+    #[test]
+    fn test_default_template_matches_previous_names() {
+        let name = NameTemplate::default().render(&ctx(None));
+        assert_eq!(name, "export_20250115_073005.xls");
+    }
+
+    #[test]
+    fn test_render_all_placeholders() {
+        let template =
+            NameTemplate::parse("export_{student}_{profile}_{from}_{to}_{date}_{timestamp}.xls")
+                .unwrap();
+        assert_eq!(
+            template.render(&ctx(Some("mario.rossi@example.it"))),
+            "export_mario.rossi_example.it_classeviva_2025-01-08_2025-01-30_2025-01-15_20250115_073005.xls"
+        );
+        assert_eq!(
+            NameTemplate::parse("export_{student}.xlsx")
+                .unwrap()
+                .render(&ctx(None)),
+            "export_unknown.xlsx"
+        );
+    }
+
+    #[test]
+    fn test_parse_rejects_invalid_templates() {
+        for template in [
+            "homework_{timestamp}.xls",
+            "export_{timestamp}.csv",
+            "../export_{timestamp}.xls",
+            "export_{nope}.xls",
+            "export_{timestamp.xls",
+        ] {
+            assert!(
+                NameTemplate::parse(template).is_err(),
+                "{} should be rejected",
+                template
+            );
+        }
+    }
+}
@@ -0,0 +1,364 @@
+//! Notifications when a fetch run fails.
+//!
+//! raschietto usually runs from cron, where a failure only shows up as a
+//! log line nobody reads. When a run ends in an error (after the HTTP
+//! engine's fallback to the browser), every channel configured here is told
+//! what went wrong and where the debug artifacts are.
+//!
+//! | Variable                          | Channel                                  |
+//! |-----------------------------------|------------------------------------------|
+//! | `RASCHIETTO_NOTIFY_WEBHOOK`       | POST a JSON body to this URL             |
+//! | `RASCHIETTO_NOTIFY_TELEGRAM_TOKEN`| Telegram bot token (with `…_CHAT_ID`)    |
+//! | `RASCHIETTO_NOTIFY_TELEGRAM_CHAT_ID` | Chat the bot writes to                |
+//! | `RASCHIETTO_NOTIFY_EMAIL`         | Recipient, sent through `sendmail -t`    |
+//! | `RASCHIETTO_NOTIFY_SENDMAIL`      | Sendmail-compatible command (optional)   |
+//!
+//! A channel that fails to deliver is logged and skipped; it never replaces
+//! the fetch error as the run's result.
+
+use anyhow::{bail, Context, Result};
+use serde::Serialize;
+use std::fmt;
+use std::path::PathBuf;
+use std::process::Stdio;
+use std::time::Duration;
+use tokio::io::AsyncWriteExt;
+use tracing::{info, warn};
+
+const TELEGRAM_API: &str = "https://api.telegram.org";
+const DEFAULT_SENDMAIL: &str = "sendmail";
+const SEND_TIMEOUT: Duration = Duration::from_secs(15);
+
+/// Rough cause of a failed run, so the message says where to start looking.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ErrorClass {
+    /// `CLASSEVIVA_USER` / `CLASSEVIVA_PASSWORD` missing
+    Credentials,
+    /// Playwright driver or Chromium didn't start
+    Browser,
+    /// The portal rejected the login or the page never got past it
+    Login,
+    /// A CAPTCHA or browser check stood in front of the login
+    Challenge,
+    /// Logged in, but the export itself failed or was unusable
+    Download,
+    /// The portal couldn't be reached
+    Network,
+    Other,
+}
+
+impl ErrorClass {
+    /// Classify `error` from the messages in its context chain.
+    pub fn of(error: &anyhow::Error) -> Self {
+        if crate::challenge::find(error).is_some() {
+            return Self::Challenge;
+        }
+        if error.chain().any(|cause| {
+            cause
+                .downcast_ref::<reqwest::Error>()
+                .is_some_and(|e| e.is_connect() || e.is_timeout())
+        }) {
+            return Self::Network;
+        }
+        let text = format!("{:#}", error).to_lowercase();
+        let has = |needles: &[&str]| needles.iter().any(|n| text.contains(n));
+        if has(&["credentials", "environment variable not set"]) {
+            Self::Credentials
+        } else if has(&["launch browser", "playwright", "driver", "chromium"]) {
+            Self::Browser
+        } else if has(&["login", "username", "password"]) {
+            Self::Login
+        } else if has(&["download", "export", "spreadsheet", "truncated"]) {
+            Self::Download
+        } else if has(&["dns", "connection", "timed out", "navigate"]) {
+            Self::Network
+        } else {
+            Self::Other
+        }
+    }
+}
+
+impl fmt::Display for ErrorClass {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            Self::Credentials => "credentials",
+            Self::Browser => "browser",
+            Self::Login => "login",
+            Self::Challenge => "challenge",
+            Self::Download => "download",
+            Self::Network => "network",
+            Self::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+/// What went wrong in a fetch run.
+#[derive(Debug, Serialize)]
+pub struct FetchFailure {
+    pub class: ErrorClass,
+    pub profile: String,
+    pub error: String,
+    /// Screenshots and page dumps saved when the run failed
+    pub artifacts: Vec<PathBuf>,
+}
+
+impl FetchFailure {
+    pub fn new(profile: &str, error: &anyhow::Error, artifacts: Vec<PathBuf>) -> Self {
+        Self {
+            class: ErrorClass::of(error),
+            profile: profile.to_string(),
+            error: format!("{:#}", error),
+            artifacts,
+        }
+    }
+
+    pub fn subject(&self) -> String {
+        format!(
+            "raschietto: fetch failed ({}) for profile {}",
+            self.class, self.profile
+        )
+    }
+
+    /// Plain-text message shared by every channel.
+    pub fn message(&self) -> String {
+        let mut text = format!("{}\n\nError: {}\n", self.subject(), self.error);
+        if self.artifacts.is_empty() {
+            text.push_str("\nNo debug artifacts were saved.\n");
+        } else {
+            text.push_str("\nDebug artifacts:\n");
+            for path in &self.artifacts {
+                text.push_str(&format!("  {}\n", path.display()));
+            }
+        }
+        text
+    }
+}
+
+/// Telegram bot and the chat it posts to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Telegram {
+    pub token: String,
+    pub chat_id: String,
+}
+
+/// Configured notification channels; all optional.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Notifier {
+    pub webhook: Option<String>,
+    pub telegram: Option<Telegram>,
+    pub email: Option<String>,
+    pub sendmail: String,
+}
+
+impl Notifier {
+    /// Load channels from the `RASCHIETTO_NOTIFY_*` variables (and `.env`).
+    pub fn from_env() -> Self {
+        let _ = dotenvy::dotenv();
+        Self::from_vars(|name| std::env::var(name).ok())
+    }
+
+    fn from_vars(var: impl Fn(&str) -> Option<String>) -> Self {
+        let var = |name: &str| var(name).filter(|v| !v.trim().is_empty());
+        let telegram = match (
+            var("RASCHIETTO_NOTIFY_TELEGRAM_TOKEN"),
+            var("RASCHIETTO_NOTIFY_TELEGRAM_CHAT_ID"),
+        ) {
+            (Some(token), Some(chat_id)) => Some(Telegram { token, chat_id }),
+            (None, None) => None,
+            _ => {
+                warn!("Telegram notifications need both RASCHIETTO_NOTIFY_TELEGRAM_TOKEN and RASCHIETTO_NOTIFY_TELEGRAM_CHAT_ID");
+                None
+            }
+        };
+        Self {
+            webhook: var("RASCHIETTO_NOTIFY_WEBHOOK"),
+            telegram,
+            email: var("RASCHIETTO_NOTIFY_EMAIL"),
+            sendmail: var("RASCHIETTO_NOTIFY_SENDMAIL")
+                .unwrap_or_else(|| DEFAULT_SENDMAIL.to_string()),
+        }
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        self.webhook.is_some() || self.telegram.is_some() || self.email.is_some()
+    }
+
+    /// Send `failure` to every configured channel.
+    pub async fn notify(&self, failure: &FetchFailure) {
+        if !self.is_enabled() {
+            return;
+        }
+        let client = match reqwest::Client::builder().timeout(SEND_TIMEOUT).build() {
+            Ok(client) => client,
+            Err(e) => {
+                warn!("Can't send failure notifications: {}", e);
+                return;
+            }
+        };
+
+        if let Some(url) = &self.webhook {
+            report("webhook", send_webhook(&client, url, failure).await);
+        }
+        if let Some(telegram) = &self.telegram {
+            report("Telegram", send_telegram(&client, telegram, failure).await);
+        }
+        if let Some(to) = &self.email {
+            report("email", send_email(&self.sendmail, to, failure).await);
+        }
+    }
+}
+
+fn report(channel: &str, result: Result<()>) {
+    match result {
+        Ok(()) => info!("Failure notification sent via {}", channel),
+        Err(e) => warn!("Failure notification via {} failed: {:#}", channel, e),
+    }
+}
+
+/// Webhook body: the structured failure plus a `text` field, which chat
+/// webhooks (Slack, Mattermost, …) display as the message.
+#[derive(Serialize)]
+struct WebhookBody<'a> {
+    event: &'static str,
+    text: String,
+    #[serde(flatten)]
+    failure: &'a FetchFailure,
+}
+
+async fn send_webhook(client: &reqwest::Client, url: &str, failure: &FetchFailure) -> Result<()> {
+    let body = WebhookBody {
+        event: "fetch_failed",
+        text: failure.message(),
+        failure,
+    };
+    client
+        .post(url)
+        .json(&body)
+        .send()
+        .await?
+        .error_for_status()?;
+    Ok(())
+}
+
+async fn send_telegram(
+    client: &reqwest::Client,
+    telegram: &Telegram,
+    failure: &FetchFailure,
+) -> Result<()> {
+    let url = format!("{}/bot{}/sendMessage", TELEGRAM_API, telegram.token);
+    client
+        .post(url)
+        .json(&serde_json::json!({
+            "chat_id": telegram.chat_id,
+            "text": failure.message(),
+            "disable_web_page_preview": true,
+        }))
+        .send()
+        .await?
+        .error_for_status()
+        // The URL holds the bot token; keep it out of the logs
+        .map_err(|e| e.without_url())?;
+    Ok(())
+}
+
+/// Hand the message to a sendmail-compatible command, which reads the
+/// recipients from the headers with `-t`.
+async fn send_email(sendmail: &str, to: &str, failure: &FetchFailure) -> Result<()> {
+    let mail = format!(
+        "To: {}\nSubject: {}\nContent-Type: text/plain; charset=utf-8\n\n{}",
+        to,
+        failure.subject(),
+        failure.message()
+    );
+    let mut child = tokio::process::Command::new(sendmail)
+        .arg("-t")
+        .stdin(Stdio::piped())
+        .spawn()
+        .with_context(|| format!("Failed to run {}", sendmail))?;
+    let mut stdin = child.stdin.take().context("sendmail has no stdin")?;
+    stdin.write_all(mail.as_bytes()).await?;
+    drop(stdin);
+    let status = tokio::time::timeout(SEND_TIMEOUT, child.wait())
+        .await
+        .context("sendmail timed out")??;
+    if !status.success() {
+        bail!("{} exited with {}", sendmail, status);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::anyhow;
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_error_class() {
+        let missing = anyhow!("CLASSEVIVA_USER environment variable not set")
+            .context("Failed to load credentials");
+        assert_eq!(ErrorClass::of(&missing), ErrorClass::Credentials);
+
+        let launch = anyhow!("driver not found").context("Failed to launch browser");
+        assert_eq!(ErrorClass::of(&launch), ErrorClass::Browser);
+
+        let login = anyhow!("Timeout 30000ms exceeded").context("Login form did not appear");
+        assert_eq!(ErrorClass::of(&login), ErrorClass::Login);
+
+        let export = anyhow!("agenda: Download truncated: got 10 of 20 bytes");
+        assert_eq!(ErrorClass::of(&export), ErrorClass::Download);
+
+        assert_eq!(ErrorClass::of(&anyhow!("boom")), ErrorClass::Other);
+
+        let challenge = anyhow::Error::new(crate::challenge::ChallengeDetected {
+            kind: "reCAPTCHA".to_string(),
+            url: "https://web.spaggiari.eu/".to_string(),
+            screenshot: None,
+        })
+        .context("Login failed");
+        assert_eq!(ErrorClass::of(&challenge), ErrorClass::Challenge);
+    }
+
+    #[test]
+    fn test_failure_message() {
+        let error = anyhow!("Timeout").context("Export button not found - login may have failed");
+        let failure = FetchFailure::new(
+            "classeviva",
+            &error,
+            vec![PathBuf::from("data/debug/fetch_20250115_070000_0.png")],
+        );
+        // "login may have failed" wins over "export": the button is only
+        // missing when the page never got past the login
+        assert_eq!(failure.class, ErrorClass::Login);
+        let message = failure.message();
+        assert!(message.starts_with("raschietto: fetch failed (login) for profile classeviva"));
+        assert!(message.contains("Error: Export button not found - login may have failed: Timeout"));
+        assert!(message.contains("  data/debug/fetch_20250115_070000_0.png\n"));
+
+        let json = serde_json::to_value(&failure).unwrap();
+        assert_eq!(json["class"], "login");
+    }
+
+    #[test]
+    fn test_notifier_from_vars() {
+        let vars = HashMap::from([
+            ("RASCHIETTO_NOTIFY_WEBHOOK", "https://hooks.example.com/x"),
+            ("RASCHIETTO_NOTIFY_TELEGRAM_TOKEN", "123:abc"),
+            ("RASCHIETTO_NOTIFY_EMAIL", " "),
+        ]);
+        let notifier = Notifier::from_vars(|name| vars.get(name).map(|v| v.to_string()));
+        assert_eq!(
+            notifier.webhook.as_deref(),
+            Some("https://hooks.example.com/x")
+        );
+        // Token without a chat id is ignored, blank values count as unset
+        assert_eq!(notifier.telegram, None);
+        assert_eq!(notifier.email, None);
+        assert_eq!(notifier.sendmail, DEFAULT_SENDMAIL);
+        assert!(notifier.is_enabled());
+
+        assert!(!Notifier::from_vars(|_| None).is_enabled());
+    }
+}
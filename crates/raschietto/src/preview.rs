@@ -0,0 +1,133 @@
+//! `fetch --preview`: the first rows of the export just downloaded, printed
+//! as a table on stdout.
+//!
+//! Meant for someone running a fetch by hand: one look says whether the
+//! dates and the student are the ones they wanted, without opening the
+//! file or waiting for compitutto to import it. The header names the
+//! account and the range asked for, and the dates the export actually
+//! covers. Like the change report, only SpreadsheetML exports can be read.
+
+use std::fmt::Write as _;
+
+use crate::changes::ExportRow;
+use crate::scraper::DateRange;
+
+/// Widest a subject gets before it's cut
+const SUBJECT_WIDTH: usize = 20;
+
+/// Widest a task gets before it's cut
+const TASK_WIDTH: usize = 60;
+
+/// `text` cut to `width` characters, with `…` when something was cut
+fn truncate(text: &str, width: usize) -> String {
+    let text = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text.chars().count() <= width {
+        return text;
+    }
+    let mut cut: String = text.chars().take(width.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// The first `limit` of `rows`, by date, under a header with `student` and
+/// the `range` fetched
+pub fn render(
+    rows: &[ExportRow],
+    limit: usize,
+    student: Option<&str>,
+    range: &DateRange,
+) -> String {
+    let mut out = String::new();
+    let _ = write!(out, "Fetched {} → {}", range.from, range.to);
+    if let Some(student) = student {
+        let _ = write!(out, " for {}", student);
+    }
+    let mut rows: Vec<&ExportRow> = rows.iter().collect();
+    rows.sort_by_key(|r| r.date);
+    match (rows.first(), rows.last()) {
+        (Some(first), Some(last)) => {
+            let _ = writeln!(
+                out,
+                ": {} entries, dated {} → {}",
+                rows.len(),
+                first.date,
+                last.date
+            );
+        }
+        _ => {
+            let _ = writeln!(out, ": no entries");
+            return out;
+        }
+    }
+
+    let shown = &rows[..rows.len().min(limit)];
+    let subjects: Vec<String> = shown
+        .iter()
+        .map(|r| truncate(&r.subject, SUBJECT_WIDTH))
+        .collect();
+    let width = subjects
+        .iter()
+        .map(|s| s.chars().count())
+        .chain(["Subject".len()])
+        .max()
+        .unwrap_or_default();
+    let _ = writeln!(out, "  {:<10}  {:<width$}  Task", "Date", "Subject");
+    for (row, subject) in shown.iter().zip(&subjects) {
+        let _ = writeln!(
+            out,
+            "  {}  {:<width$}  {}",
+            row.date,
+            subject,
+            truncate(&row.task, TASK_WIDTH)
+        );
+    }
+    if rows.len() > shown.len() {
+        let _ = writeln!(out, "  … and {} more", rows.len() - shown.len());
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn row(date: &str, subject: &str, task: &str) -> ExportRow {
+        ExportRow {
+            date: date.parse().unwrap(),
+            subject: subject.to_string(),
+            kind: "compiti".to_string(),
+            task: task.to_string(),
+        }
+    }
+
+    #[test]
+    fn test_truncate() {
+        assert_eq!(truncate("Es. 4 pag. 10", 20), "Es. 4 pag. 10");
+        assert_eq!(truncate("Es. 4\n  pag. 10", 20), "Es. 4 pag. 10");
+        assert_eq!(truncate("Ricerca sul Rinascimento", 10), "Ricerca s…");
+        assert_eq!(truncate("è già così", 5), "è gi…");
+    }
+
+    #[test]
+    fn test_render() {
+        let range = DateRange::new("2025-01-10".parse().unwrap(), "2025-01-25".parse().unwrap());
+        let rows = vec![
+            row("2025-01-14", "ITALIANO", "Tema"),
+            row("2025-01-13", "MATEMATICA", "Es. 4 & 5"),
+            row("2025-01-20", "STORIA", "Cap. 5"),
+        ];
+
+        assert_eq!(
+            render(&rows, 2, Some("S1234567"), &range),
+            "Fetched 2025-01-10 → 2025-01-25 for S1234567: 3 entries, dated 2025-01-13 → 2025-01-20\n\
+             \x20 Date        Subject     Task\n\
+             \x20 2025-01-13  MATEMATICA  Es. 4 & 5\n\
+             \x20 2025-01-14  ITALIANO    Tema\n\
+             \x20 … and 1 more\n"
+        );
+        assert_eq!(
+            render(&[], 10, None, &range),
+            "Fetched 2025-01-10 → 2025-01-25: no entries\n"
+        );
+    }
+}
@@ -0,0 +1,366 @@
+//! Portal profiles: base URL and page selectors for a Spaggiari portal.
+//!
+//! The built-in `classeviva` preset targets `web.spaggiari.eu`. Regional
+//! variants or test instances can be described in `raschietto.toml`:
+//!
+//! ```toml
+//! [profiles.staging]
+//! agenda_url = "https://staging.example.it/fml/app/default/agenda_studenti.php"
+//!
+//! [profiles.staging.selectors]
+//! login_username = "#user"
+//! ```
+//!
+//! Any field left out falls back to the `classeviva` preset. Slow portals
+//! can get longer timeouts under `[profiles.X.waits]` (see `wait`).
+
+use anyhow::{anyhow, Context, Result};
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::wait::WaitPolicy;
+
+/// Name of the built-in profile used when none is given.
+pub const DEFAULT_PROFILE: &str = "classeviva";
+
+/// Default location of the config file, relative to the working directory.
+pub const DEFAULT_CONFIG_FILE: &str = "raschietto.toml";
+
+/// CSS selectors for the page elements the scraper interacts with.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct Selectors {
+    pub login_username: String,
+    pub login_password: String,
+    pub login_submit: String,
+    /// "Continua senza associare l'email" skip link on the post-login nag screen.
+    pub skip_email_link: String,
+    /// A CAPTCHA or browser check in place of the login (see `challenge`)
+    pub challenge: String,
+    /// Export button - an <a> tag with class "export" and alt="scarica"
+    pub export_button: String,
+    pub export_dialog: String,
+    pub date_from: String,
+    pub date_to: String,
+    pub confirm_button: String,
+    /// An item on the agenda calendar (opens its detail popup when clicked)
+    pub agenda_event: String,
+    pub agenda_prev: String,
+    pub agenda_next: String,
+    /// Class dropdown on the agenda page (`--class`)
+    pub agenda_class: String,
+    /// Group dropdown on the agenda page (`--group`)
+    pub agenda_group: String,
+    pub detail_dialog: String,
+    pub detail_subject: String,
+    pub detail_text: String,
+    /// Links inside the detail popup, recorded as attachments
+    pub detail_attachment: String,
+    pub detail_close: String,
+    /// One subject's row on the grades page
+    pub grade_row: String,
+    /// Subject name inside a grade row
+    pub grade_subject: String,
+    /// One grade inside a grade row
+    pub grade_cell: String,
+    /// Displayed value inside a grade cell (falls back to the cell's text)
+    pub grade_value: String,
+    pub grade_date: String,
+    /// One row of the absences table (rows without a date are skipped)
+    pub absence_row: String,
+    pub absence_date: String,
+    /// "Assenza", "Ritardo", "Uscita anticipata"
+    pub absence_kind: String,
+    /// Justification status ("Giustificata", "Da giustificare")
+    pub absence_status: String,
+    pub absence_note: String,
+    /// One row of the disciplinary notes table
+    pub note_row: String,
+    pub note_date: String,
+    pub note_teacher: String,
+    pub note_kind: String,
+    pub note_text: String,
+}
+
+impl Default for Selectors {
+    fn default() -> Self {
+        Self {
+            login_username: "#login".to_string(),
+            login_password: "#password".to_string(),
+            login_submit: "button[type='submit']".to_string(),
+            skip_email_link: "a:has-text('Continua senza associare')".to_string(),
+            challenge:
+                ".g-recaptcha, .h-captcha, .cf-turnstile, #challenge-form, iframe[src*='captcha']"
+                    .to_string(),
+            export_button: "a.export[alt='scarica']".to_string(),
+            export_dialog: "div.ui-dialog[role='dialog']".to_string(),
+            date_from: "#dal".to_string(),
+            date_to: "#al".to_string(),
+            confirm_button: "div.ui-dialog button:has-text('Conferma')".to_string(),
+            agenda_event: ".fc-event".to_string(),
+            agenda_prev: ".fc-button-prev, .fc-prev-button".to_string(),
+            agenda_next: ".fc-button-next, .fc-next-button".to_string(),
+            agenda_class: "select#classe, select[name='classe']".to_string(),
+            agenda_group: "select#gruppo, select[name='gruppo']".to_string(),
+            detail_dialog: "div.ui-dialog[role='dialog']".to_string(),
+            detail_subject: "div.ui-dialog .ui-dialog-title".to_string(),
+            detail_text: "div.ui-dialog .ui-dialog-content".to_string(),
+            detail_attachment: "div.ui-dialog .ui-dialog-content a[href]".to_string(),
+            detail_close: "div.ui-dialog .ui-dialog-titlebar-close".to_string(),
+            grade_row: "tr.riga_materia_componente".to_string(),
+            grade_subject: "td:first-child".to_string(),
+            grade_cell: "td.cella_voto".to_string(),
+            grade_value: ".s_reg_testo".to_string(),
+            grade_date: ".voto_data".to_string(),
+            absence_row: "table#sort_table tr.rigtab".to_string(),
+            absence_date: "td:nth-child(1)".to_string(),
+            absence_kind: "td:nth-child(2)".to_string(),
+            absence_status: "td:nth-child(3)".to_string(),
+            absence_note: "td:nth-child(4)".to_string(),
+            note_row: "table#sort_table tr.rigtab".to_string(),
+            note_date: "td:nth-child(2)".to_string(),
+            note_teacher: "td:nth-child(1)".to_string(),
+            note_kind: "td:nth-child(3)".to_string(),
+            note_text: "td:nth-child(4)".to_string(),
+        }
+    }
+}
+
+/// Endpoints for `--engine http`, which talks to the portal with plain
+/// requests instead of a browser.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct HttpEndpoints {
+    /// Where the login form posts to
+    pub login_url: String,
+    pub username_field: String,
+    pub password_field: String,
+    /// Extra form fields sent with the login
+    pub login_extra: BTreeMap<String, String>,
+    /// Export download URL with `{from}` / `{to}` (`DD-MM-YYYY`) placeholders.
+    /// Not known in advance for every portal: a browser fetch logs the URL it
+    /// downloaded from, which can be pasted here with the dates replaced.
+    pub export_url: Option<String>,
+}
+
+impl Default for HttpEndpoints {
+    fn default() -> Self {
+        Self {
+            login_url: "https://web.spaggiari.eu/auth-p7/app/default/AuthApi4.php?a=aLoginPwd"
+                .to_string(),
+            username_field: "uid".to_string(),
+            password_field: "pwd".to_string(),
+            login_extra: BTreeMap::from([
+                ("cid".to_string(), String::new()),
+                ("pin".to_string(), String::new()),
+                ("target".to_string(), String::new()),
+            ]),
+            export_url: None,
+        }
+    }
+}
+
+/// A portal the scraper can talk to.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct PortalProfile {
+    /// Agenda page URL; unauthenticated visits redirect to the login form.
+    pub agenda_url: String,
+    /// Grades page, read by `fetch --export grades`
+    pub grades_url: String,
+    /// Absences page, read by `fetch --export absences`
+    pub absences_url: String,
+    /// Disciplinary notes page, read by `fetch --export notes`
+    pub notes_url: String,
+    pub selectors: Selectors,
+    pub http: HttpEndpoints,
+    /// Timeouts for the browser steps (`[profiles.X.waits]`)
+    pub waits: WaitPolicy,
+}
+
+impl Default for PortalProfile {
+    fn default() -> Self {
+        Self::classeviva()
+    }
+}
+
+impl PortalProfile {
+    /// Built-in preset for the main Classe Viva portal.
+    pub fn classeviva() -> Self {
+        Self {
+            agenda_url: "https://web.spaggiari.eu/fml/app/default/agenda_studenti.php".to_string(),
+            grades_url: "https://web.spaggiari.eu/cvv/app/default/genitori_voti.php".to_string(),
+            absences_url: "https://web.spaggiari.eu/tic/app/default/consultasingolo.php"
+                .to_string(),
+            notes_url: "https://web.spaggiari.eu/fml/app/default/gioprof_note_studente.php"
+                .to_string(),
+            selectors: Selectors::default(),
+            http: HttpEndpoints::default(),
+            waits: WaitPolicy::default(),
+        }
+    }
+
+    /// Resolve a profile by name from the config file, falling back to the
+    /// built-in presets.
+    ///
+    /// A missing config file is fine as long as the name is a built-in preset.
+    pub fn load(name: &str, config_path: &Path) -> Result<Self> {
+        let config = if config_path.exists() {
+            ProfilesFile::read(config_path)?
+        } else {
+            ProfilesFile::default()
+        };
+        config.profile(name)
+    }
+}
+
+/// The `[profiles.*]` tables of the config file.
+#[derive(Debug, Default, Deserialize)]
+struct ProfilesFile {
+    #[serde(default)]
+    profiles: BTreeMap<String, PortalProfile>,
+}
+
+impl ProfilesFile {
+    fn read(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid config file: {}", path.display()))
+    }
+
+    fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    fn profile(&self, name: &str) -> Result<PortalProfile> {
+        if let Some(profile) = self.profiles.get(name) {
+            return Ok(profile.clone());
+        }
+        match name {
+            DEFAULT_PROFILE => Ok(PortalProfile::classeviva()),
+            _ => Err(anyhow!(
+                "Unknown profile '{}' (built-in: {}; configured: {})",
+                name,
+                DEFAULT_PROFILE,
+                if self.profiles.is_empty() {
+                    "none".to_string()
+                } else {
+                    self.profiles.keys().cloned().collect::<Vec<_>>().join(", ")
+                }
+            )),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::wait::WaitStep;
+
+    #[test]
+    fn test_builtin_classeviva_profile() {
+        let profile = ProfilesFile::default().profile("classeviva").unwrap();
+        assert_eq!(profile, PortalProfile::classeviva());
+        assert!(profile.agenda_url.starts_with("https://web.spaggiari.eu/"));
+        assert_eq!(profile.selectors.date_from, "#dal");
+    }
+
+    #[test]
+    fn test_unknown_profile_is_an_error() {
+        let err = ProfilesFile::default().profile("nope").unwrap_err();
+        assert!(err.to_string().contains("nope"));
+    }
+
+    #[test]
+    fn test_partial_profile_falls_back_to_preset() {
+        let config = ProfilesFile::parse(
+            r##"
+            [profiles.staging]
+            agenda_url = "https://staging.example.it/agenda.php"
+
+            [profiles.staging.selectors]
+            login_username = "#user"
+            "##,
+        )
+        .unwrap();
+
+        let profile = config.profile("staging").unwrap();
+        assert_eq!(profile.agenda_url, "https://staging.example.it/agenda.php");
+        assert_eq!(profile.selectors.login_username, "#user");
+        assert_eq!(profile.selectors.login_password, "#password");
+        assert_eq!(
+            profile.selectors.confirm_button,
+            Selectors::default().confirm_button
+        );
+    }
+
+    #[test]
+    fn test_config_can_override_builtin_preset() {
+        let config = ProfilesFile::parse(
+            r#"
+            [profiles.classeviva.selectors]
+            export_button = "a.export"
+            "#,
+        )
+        .unwrap();
+
+        let profile = config.profile("classeviva").unwrap();
+        assert_eq!(profile.selectors.export_button, "a.export");
+        assert_eq!(profile.agenda_url, PortalProfile::classeviva().agenda_url);
+    }
+
+    #[test]
+    fn test_http_endpoints_from_config() {
+        let config = ProfilesFile::parse(
+            r#"
+            [profiles.classeviva.http]
+            export_url = "https://web.spaggiari.eu/export.php?dal={from}&al={to}"
+            "#,
+        )
+        .unwrap();
+
+        let http = config.profile("classeviva").unwrap().http;
+        assert_eq!(
+            http.export_url.as_deref(),
+            Some("https://web.spaggiari.eu/export.php?dal={from}&al={to}")
+        );
+        assert_eq!(http.login_url, HttpEndpoints::default().login_url);
+        assert_eq!(http.username_field, "uid");
+    }
+
+    #[test]
+    fn test_waits_from_config() {
+        let config = ProfilesFile::parse(
+            r#"
+            [profiles.classeviva.waits]
+            timeout_ms = 60000
+
+            [profiles.classeviva.waits.steps]
+            post_login = 90000
+            "#,
+        )
+        .unwrap();
+
+        let waits = config.profile("classeviva").unwrap().waits;
+        assert_eq!(waits.timeout_ms(WaitStep::PostLogin), 90_000);
+        assert_eq!(waits.timeout_ms(WaitStep::ExportButton), 60_000);
+        assert_eq!(waits.idle_ms, WaitPolicy::default().idle_ms);
+
+        let err = ProfilesFile::parse(
+            r#"
+            [profiles.classeviva.waits.steps]
+            lunch = 1000
+            "#,
+        )
+        .unwrap_err();
+        assert!(format!("{:#}", err).contains("lunch"));
+    }
+
+    #[test]
+    fn test_load_without_config_file() {
+        let profile =
+            PortalProfile::load("classeviva", Path::new("/nonexistent/raschietto.toml")).unwrap();
+        assert_eq!(profile, PortalProfile::classeviva());
+    }
+}
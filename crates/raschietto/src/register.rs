@@ -0,0 +1,242 @@
+//! Absences and disciplinary notes read from the portal's register pages.
+//!
+//! Like grades, neither page has a spreadsheet export, so `fetch --export
+//! absences,notes` reads their tables and writes them as JSON next to the
+//! agenda export, where compitutto picks them up:
+//!
+//! ```text
+//! data/export_20250115_073005.xls
+//! data/absences_20250115_073005.json
+//! data/notes_20250115_073005.json
+//! ```
+//!
+//! Each file is a snapshot of the whole page (the school year so far).
+
+use anyhow::{Context, Result};
+use chrono::NaiveDate;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+
+/// Contents of an `absences_*.json` file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AbsencesFile {
+    /// Day the page was read
+    pub fetched: NaiveDate,
+    pub absences: Vec<Absence>,
+}
+
+/// An absence, late entrance or early exit.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Absence {
+    /// As displayed, usually `DD/MM/YYYY`
+    pub date: String,
+    /// As displayed: "Assenza", "Ritardo", "Uscita anticipata"
+    pub kind: String,
+    pub justified: bool,
+    /// Reason or time, when the page shows one
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub note: String,
+}
+
+/// Contents of a `notes_*.json` file.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct NotesFile {
+    /// Day the page was read
+    pub fetched: NaiveDate,
+    pub notes: Vec<Note>,
+}
+
+/// A disciplinary note (or any other note the teachers wrote).
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Note {
+    /// As displayed, usually `DD/MM/YYYY`
+    pub date: String,
+    pub teacher: String,
+    /// As displayed: "Nota disciplinare", "Annotazione", "Richiamo"
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub kind: String,
+    pub text: String,
+}
+
+/// Raw row of the absences table as read by the page script.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawAbsence {
+    #[serde(default)]
+    pub date: String,
+    #[serde(default)]
+    pub kind: String,
+    /// The justification column: "Giustificata", "Da giustificare", …
+    #[serde(default)]
+    pub status: String,
+    #[serde(default)]
+    pub note: String,
+}
+
+/// Raw row of the notes table as read by the page script.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RawNote {
+    #[serde(default)]
+    pub date: String,
+    #[serde(default)]
+    pub teacher: String,
+    #[serde(default)]
+    pub kind: String,
+    #[serde(default)]
+    pub text: String,
+}
+
+impl Absence {
+    /// Build an absence from a table row, collapsing whitespace. Returns
+    /// `None` for rows without a date (headers, month separators).
+    pub fn from_raw(raw: RawAbsence) -> Option<Self> {
+        let date = squash(&raw.date);
+        if date.is_empty() {
+            return None;
+        }
+        Some(Self {
+            date,
+            kind: squash(&raw.kind),
+            justified: is_justified(&raw.status),
+            note: squash(&raw.note),
+        })
+    }
+}
+
+impl Note {
+    /// Build a note from a table row, collapsing whitespace. Returns `None`
+    /// for rows without any text.
+    pub fn from_raw(raw: RawNote) -> Option<Self> {
+        let text = squash(&raw.text);
+        if text.is_empty() {
+            return None;
+        }
+        Some(Self {
+            date: squash(&raw.date),
+            teacher: squash(&raw.teacher),
+            kind: squash(&raw.kind),
+            text,
+        })
+    }
+}
+
+/// "Giustificata" / "Giustificato il 16/01" count; "Da giustificare" and
+/// "Non giustificata" don't.
+fn is_justified(status: &str) -> bool {
+    let status = status.to_lowercase();
+    status.contains("giustificat") && !status.contains("non ")
+}
+
+impl AbsencesFile {
+    /// Write the file as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        write_json(self, path, "absences")
+    }
+}
+
+impl NotesFile {
+    /// Write the file as pretty-printed JSON.
+    pub fn write(&self, path: &Path) -> Result<()> {
+        write_json(self, path, "notes")
+    }
+}
+
+fn write_json<T: Serialize>(value: &T, path: &Path, what: &str) -> Result<()> {
+    let json = serde_json::to_string_pretty(value)
+        .with_context(|| format!("Failed to serialize {}", what))?;
+    crate::download::write_atomic(path, json.as_bytes())
+        .with_context(|| format!("Failed to write {} file: {}", what, path.display()))
+}
+
+/// Absences path for an export: `export_x.xls` -> `absences_x.json`.
+pub fn absences_path(export_path: &Path) -> PathBuf {
+    named_like(export_path, "absences")
+}
+
+/// Notes path for an export: `export_x.xls` -> `notes_x.json`.
+pub fn notes_path(export_path: &Path) -> PathBuf {
+    named_like(export_path, "notes")
+}
+
+fn named_like(export_path: &Path, prefix: &str) -> PathBuf {
+    let stem = export_path
+        .file_stem()
+        .map(|s| s.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let rest = stem.strip_prefix("export_").unwrap_or(&stem);
+    export_path.with_file_name(format!("{}_{}.json", prefix, rest))
+}
+
+fn squash(text: &str) -> String {
+    text.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_register_paths() {
+        let export = Path::new("data/export_20250115_073005.xls");
+        assert_eq!(
+            absences_path(export),
+            PathBuf::from("data/absences_20250115_073005.json")
+        );
+        assert_eq!(
+            notes_path(Path::new("data/export_mario_2025-01-08.xlsx")),
+            PathBuf::from("data/notes_mario_2025-01-08.json")
+        );
+    }
+
+    #[test]
+    fn test_absence_from_raw() {
+        let raw = |status: &str| RawAbsence {
+            date: " 15/01/2025\n".to_string(),
+            kind: "Ritardo".to_string(),
+            status: status.to_string(),
+            note: "Entrata  alle 09:05".to_string(),
+        };
+        let absence = Absence::from_raw(raw("Giustificato il 16/01")).unwrap();
+        assert_eq!(absence.date, "15/01/2025");
+        assert_eq!(absence.note, "Entrata alle 09:05");
+        assert!(absence.justified);
+        assert!(!Absence::from_raw(raw("Da giustificare")).unwrap().justified);
+        assert!(
+            !Absence::from_raw(raw("Non giustificata"))
+                .unwrap()
+                .justified
+        );
+
+        let separator = RawAbsence {
+            date: " ".to_string(),
+            kind: "Gennaio".to_string(),
+            status: String::new(),
+            note: String::new(),
+        };
+        assert!(Absence::from_raw(separator).is_none());
+    }
+
+    #[test]
+    fn test_note_from_raw() {
+        let note = Note::from_raw(RawNote {
+            date: "20/01/2025".to_string(),
+            teacher: "ROSSI  MARIA".to_string(),
+            kind: String::new(),
+            text: "Disturba\nla lezione".to_string(),
+        })
+        .unwrap();
+        assert_eq!(note.teacher, "ROSSI MARIA");
+        assert_eq!(note.text, "Disturba la lezione");
+        assert_eq!(
+            serde_json::to_string(&note).unwrap(),
+            r#"{"date":"20/01/2025","teacher":"ROSSI MARIA","text":"Disturba la lezione"}"#
+        );
+
+        let empty = RawNote {
+            date: "20/01/2025".to_string(),
+            teacher: String::new(),
+            kind: String::new(),
+            text: " ".to_string(),
+        };
+        assert!(Note::from_raw(empty).is_none());
+    }
+}
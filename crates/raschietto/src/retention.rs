@@ -0,0 +1,258 @@
+//! Pruning old exports from the output directory.
+//!
+//! Every fetch adds an export, and with it a `.details.json` sidecar and
+//! `grades_*`, `absences_*` and `notes_*` files as asked for, so over a school
+//! year the data directory only grows. After a successful fetch
+//! `--keep-last N` keeps the N newest exports and `--keep-days D` those
+//! modified in the last D days; older ones are deleted with their companion
+//! files. Failure and challenge screenshots in `debug/` are pruned the same
+//! way, one failed run at a time.
+//!
+//! The newest export is always kept. The per-profile change manifest is a
+//! single file and stays, and so do the photos in `attachments/`: compitutto
+//! may still show one after the sidecar that listed it is gone.
+
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tracing::info;
+
+use crate::{details, grades, register};
+
+/// How many old exports to keep.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Retention {
+    /// The N newest
+    KeepLast(u64),
+    /// Those modified in the last N days
+    KeepDays(u64),
+}
+
+impl Retention {
+    /// From `--keep-last` and `--keep-days` (clap makes them exclusive);
+    /// `None` keeps everything.
+    pub fn from_args(keep_last: Option<u64>, keep_days: Option<u64>) -> Option<Self> {
+        keep_last
+            .map(Retention::KeepLast)
+            .or(keep_days.map(Retention::KeepDays))
+    }
+}
+
+/// A group of files that are kept or deleted together.
+#[derive(Debug)]
+struct Run {
+    modified: SystemTime,
+    files: Vec<PathBuf>,
+}
+
+/// Delete what `retention` doesn't keep from `output_dir` and its `debug/`
+/// subdirectory, as of `now`. Returns the deleted files.
+pub fn prune(output_dir: &Path, retention: Retention, now: SystemTime) -> Result<Vec<PathBuf>> {
+    let mut removed = Vec::new();
+    // Keep at least one export: the one this fetch just saved
+    for run in stale(export_runs(output_dir)?, retention, now, 1) {
+        removed.extend(remove_run(run)?);
+    }
+    let debug_dir = output_dir.join(crate::scraper::DEBUG_DIR);
+    if debug_dir.is_dir() {
+        for run in stale(debug_runs(&debug_dir)?, retention, now, 0) {
+            removed.extend(remove_run(run)?);
+        }
+    }
+    Ok(removed)
+}
+
+/// The runs `retention` doesn't keep, always sparing the `min_keep` newest.
+fn stale(mut runs: Vec<Run>, retention: Retention, now: SystemTime, min_keep: usize) -> Vec<Run> {
+    runs.sort_by_key(|run| std::cmp::Reverse(run.modified));
+    let kept = match retention {
+        Retention::KeepLast(n) => usize::try_from(n).unwrap_or(usize::MAX),
+        Retention::KeepDays(days) => {
+            let cutoff = now
+                .checked_sub(Duration::from_secs(days.saturating_mul(86_400)))
+                .unwrap_or(SystemTime::UNIX_EPOCH);
+            runs.iter().take_while(|run| run.modified >= cutoff).count()
+        }
+    };
+    runs.into_iter().skip(kept.max(min_keep)).collect()
+}
+
+/// Each `export_*.xls`/`.xlsx` with the companion files that exist.
+fn export_runs(dir: &Path) -> Result<Vec<Run>> {
+    let mut runs = Vec::new();
+    for (path, name) in files_in(dir)? {
+        let is_export =
+            name.starts_with("export_") && (name.ends_with(".xls") || name.ends_with(".xlsx"));
+        if !is_export {
+            continue;
+        }
+        let companions = [
+            details::sidecar_path(&path),
+            grades::grades_path(&path),
+            register::absences_path(&path),
+            register::notes_path(&path),
+        ];
+        let mut files = vec![path.clone()];
+        files.extend(companions.into_iter().filter(|p| p.is_file()));
+        runs.push(Run {
+            modified: modified(&path)?,
+            files,
+        });
+    }
+    Ok(runs)
+}
+
+/// `debug/fetch_<timestamp>_<n>.{png,html}` and
+/// `debug/challenge_<timestamp>.png`, grouped by timestamp.
+fn debug_runs(dir: &Path) -> Result<Vec<Run>> {
+    let mut by_stamp: BTreeMap<String, Run> = BTreeMap::new();
+    for (path, name) in files_in(dir)? {
+        let stem = name.split('.').next().unwrap_or_default();
+        let stamp = match stem.strip_prefix("fetch_") {
+            Some(rest) => rest.rsplit_once('_').map(|(stamp, _)| stamp),
+            None => stem.strip_prefix("challenge_"),
+        };
+        let Some(stamp) = stamp else {
+            continue;
+        };
+        let modified = modified(&path)?;
+        let run = by_stamp.entry(stamp.to_string()).or_insert(Run {
+            modified,
+            files: Vec::new(),
+        });
+        run.modified = run.modified.max(modified);
+        run.files.push(path);
+    }
+    Ok(by_stamp.into_values().collect())
+}
+
+/// Regular files in `dir` with their names, skipping hidden ones.
+fn files_in(dir: &Path) -> Result<Vec<(PathBuf, String)>> {
+    let mut files = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {:?}", dir))? {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|n| n.to_str()) else {
+            continue;
+        };
+        if name.starts_with('.') || !path.is_file() {
+            continue;
+        }
+        let name = name.to_string();
+        files.push((path, name));
+    }
+    Ok(files)
+}
+
+fn modified(path: &Path) -> Result<SystemTime> {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .with_context(|| format!("Failed to read the modification time of {:?}", path))
+}
+
+fn remove_run(run: Run) -> Result<Vec<PathBuf>> {
+    for path in &run.files {
+        info!("Removing old file: {:?}", path);
+        fs::remove_file(path).with_context(|| format!("Failed to remove {:?}", path))?;
+    }
+    Ok(run.files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DAY: Duration = Duration::from_secs(24 * 60 * 60);
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!(
+            "raschietto-retention-{}-{}",
+            name,
+            std::process::id()
+        ));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    /// Write `path`, last modified `age` before `now`.
+    fn touch(path: &Path, now: SystemTime, age: Duration) {
+        fs::write(path, b"x").unwrap();
+        let file = fs::File::options().write(true).open(path).unwrap();
+        file.set_modified(now - age).unwrap();
+    }
+
+    #[test]
+    fn test_keep_last_removes_old_exports_with_companions() {
+        let dir = temp_dir("last");
+        let now = SystemTime::now();
+        for (i, name) in ["export_a", "export_b", "export_c"].iter().enumerate() {
+            let age = DAY * (3 - i as u32);
+            touch(&dir.join(format!("{}.xls", name)), now, age);
+            touch(&dir.join(format!("{}.details.json", name)), now, age);
+        }
+        touch(&dir.join("grades_a.json"), now, DAY * 3);
+        touch(
+            &dir.join(".raschietto-classeviva.manifest.json"),
+            now,
+            DAY * 9,
+        );
+        touch(&dir.join("homework.db"), now, DAY * 9);
+        fs::create_dir(dir.join("attachments")).unwrap();
+        touch(&dir.join("attachments/photo.jpg"), now, DAY * 9);
+
+        let removed = prune(&dir, Retention::KeepLast(2), now).unwrap();
+
+        assert_eq!(removed.len(), 3);
+        assert!(!dir.join("export_a.xls").exists());
+        assert!(!dir.join("export_a.details.json").exists());
+        assert!(!dir.join("grades_a.json").exists());
+        assert!(dir.join("export_b.xls").exists());
+        assert!(dir.join("export_c.details.json").exists());
+        assert!(dir.join(".raschietto-classeviva.manifest.json").exists());
+        assert!(dir.join("homework.db").exists());
+        assert!(dir.join("attachments/photo.jpg").exists());
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn test_keep_days_keeps_newest_export_and_prunes_debug_runs() {
+        let dir = temp_dir("days");
+        let now = SystemTime::now();
+        touch(&dir.join("export_old.xls"), now, DAY * 40);
+        touch(&dir.join("export_older.xlsx"), now, DAY * 50);
+        let debug = dir.join("debug");
+        fs::create_dir(&debug).unwrap();
+        touch(&debug.join("fetch_20250101_070000_0.png"), now, DAY * 40);
+        touch(&debug.join("fetch_20250101_070000_0.html"), now, DAY * 40);
+        touch(&debug.join("fetch_20250301_070000_0.png"), now, DAY);
+        touch(&debug.join("challenge_20241201_070000.png"), now, DAY * 60);
+        touch(&debug.join("challenge_20250302_070000.png"), now, DAY);
+
+        let removed = prune(&dir, Retention::KeepDays(30), now).unwrap();
+
+        // Both exports are old, but the newest one stays
+        assert!(dir.join("export_old.xls").exists());
+        assert!(!dir.join("export_older.xlsx").exists());
+        assert!(!debug.join("fetch_20250101_070000_0.png").exists());
+        assert!(!debug.join("fetch_20250101_070000_0.html").exists());
+        assert!(debug.join("fetch_20250301_070000_0.png").exists());
+        assert!(!debug.join("challenge_20241201_070000.png").exists());
+        assert!(debug.join("challenge_20250302_070000.png").exists());
+        assert_eq!(removed.len(), 4);
+        // A huge --keep-days keeps everything rather than overflowing
+        assert!(prune(&dir, Retention::KeepDays(u64::MAX), now)
+            .unwrap()
+            .is_empty());
+
+        assert_eq!(
+            Retention::from_args(None, Some(30)),
+            Some(Retention::KeepDays(30))
+        );
+        assert_eq!(Retention::from_args(None, None), None);
+
+        let _ = fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,123 @@
+//! Jitter and quiet hours for scheduled (cron) fetches.
+//!
+//! A cron job fires at the same second every day. With `--jitter N` the
+//! fetch waits a random 0..=2N minutes first, so scheduling the job N minutes
+//! early spreads it ±N minutes around the intended time. With
+//! `--quiet-hours 22:00-07:00` a fetch that would start inside the window is
+//! skipped instead; the window may wrap past midnight.
+
+use anyhow::{bail, Context, Result};
+use chrono::{NaiveDateTime, NaiveTime};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// A daily window in which no fetch starts. `start` is inclusive, `end`
+/// exclusive.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QuietHours {
+    pub start: NaiveTime,
+    pub end: NaiveTime,
+}
+
+impl QuietHours {
+    pub fn contains(&self, time: NaiveTime) -> bool {
+        if self.start <= self.end {
+            time >= self.start && time < self.end
+        } else {
+            time >= self.start || time < self.end
+        }
+    }
+}
+
+impl FromStr for QuietHours {
+    type Err = anyhow::Error;
+
+    /// `HH:MM-HH:MM`, e.g. `22:00-07:00`
+    fn from_str(s: &str) -> Result<Self> {
+        let (start, end) = s
+            .split_once('-')
+            .with_context(|| format!("Quiet hours must look like 22:00-07:00, got {:?}", s))?;
+        let parse = |t: &str| {
+            NaiveTime::parse_from_str(t.trim(), "%H:%M")
+                .with_context(|| format!("Invalid time {:?} in quiet hours (use HH:MM)", t.trim()))
+        };
+        let hours = QuietHours {
+            start: parse(start)?,
+            end: parse(end)?,
+        };
+        if hours.start == hours.end {
+            bail!("Quiet hours {:?} are empty; start and end must differ", s);
+        }
+        Ok(hours)
+    }
+}
+
+/// A random wait of 0..=2 × `jitter_minutes`, to the second.
+pub fn jitter_delay(jitter_minutes: u64) -> Duration {
+    Duration::from_secs(fastrand::u64(0..=jitter_minutes.saturating_mul(2 * 60)))
+}
+
+/// When a fetch starting now, after `delay`, would begin, or `None` if that
+/// falls in `quiet`.
+pub fn start_time(
+    now: NaiveDateTime,
+    delay: Duration,
+    quiet: Option<QuietHours>,
+) -> Option<NaiveDateTime> {
+    let start = now + chrono::Duration::from_std(delay).unwrap_or_default();
+    match quiet {
+        Some(quiet) if quiet.contains(start.time()) => None,
+        _ => Some(start),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn time(s: &str) -> NaiveTime {
+        NaiveTime::parse_from_str(s, "%H:%M").unwrap()
+    }
+
+    #[test]
+    fn test_quiet_hours_parse_and_wrap() {
+        let night: QuietHours = "22:00-07:00".parse().unwrap();
+        assert!(night.contains(time("23:30")));
+        assert!(night.contains(time("00:00")));
+        assert!(night.contains(time("06:59")));
+        assert!(!night.contains(time("07:00")));
+        assert!(!night.contains(time("12:00")));
+
+        let lunch: QuietHours = " 13:00 - 14:30 ".parse().unwrap();
+        assert!(lunch.contains(time("13:00")));
+        assert!(!lunch.contains(time("14:30")));
+        assert!(!lunch.contains(time("09:00")));
+
+        assert!("22:00".parse::<QuietHours>().is_err());
+        assert!("25:00-07:00".parse::<QuietHours>().is_err());
+        assert!("07:00-07:00".parse::<QuietHours>().is_err());
+    }
+
+    #[test]
+    fn test_jitter_and_start_time() {
+        assert_eq!(jitter_delay(0), Duration::ZERO);
+        for _ in 0..100 {
+            assert!(jitter_delay(10) <= Duration::from_secs(20 * 60));
+        }
+        // Saturates instead of overflowing
+        let _ = jitter_delay(u64::MAX);
+
+        let now = chrono::NaiveDate::from_ymd_opt(2025, 1, 15)
+            .unwrap()
+            .and_time(time("21:50"));
+        let quiet = "22:00-07:00".parse().ok();
+        let ten_minutes = Duration::from_secs(600);
+        // Jitter pushes the start into quiet hours
+        assert_eq!(start_time(now, ten_minutes, quiet), None);
+        assert_eq!(
+            start_time(now, Duration::from_secs(60), quiet),
+            Some(now + chrono::Duration::minutes(1))
+        );
+        assert!(start_time(now, ten_minutes, None).is_some());
+    }
+}
@@ -1,32 +1,230 @@
 //! Classe Viva page interactions: login, navigation, modal handling, download.
+//!
+//! A fetch logs in once and then runs a [`FetchPlan`]: the steps for every
+//! requested export kind, in an order that keeps navigation to a minimum,
+//! all on the same logged-in page.
 
-use anyhow::{anyhow, Context, Result};
+use anyhow::{anyhow, bail, Context, Result};
 use chrono::NaiveDate;
+use clap::ValueEnum;
 use playwright::api::frame::FrameState;
 use playwright::api::page::{Event, EventType};
-use playwright::api::{BrowserContext, Page};
+use playwright::api::{BrowserContext, Download, Page, StorageState};
+use serde::Deserialize;
 use std::path::{Path, PathBuf};
+use std::sync::Mutex;
 use std::time::Duration;
-use tracing::{debug, info};
+use tracing::{debug, info, warn};
 
+use crate::challenge::{self, ChallengeDetected};
 use crate::config::Credentials;
+use crate::details::{self, DetailsFile, EntryDetail, RawDetail};
+use crate::download;
+use crate::grades::{self, Grade, GradesFile, RawGrade};
+use crate::http_engine;
+use crate::metrics::{self, Phase, Timings};
+use crate::network::NetworkOptions;
+use crate::profile::PortalProfile;
+use crate::register::{self, Absence, AbsencesFile, Note, NotesFile, RawAbsence, RawNote};
+use crate::wait::{self, WaitStep};
 
-/// URLs for Classe Viva.
-const AGENDA_URL: &str = "https://web.spaggiari.eu/fml/app/default/agenda_studenti.php";
-
-/// CSS selectors for page elements.
-mod selectors {
-    pub const LOGIN_USERNAME: &str = "#login";
-    pub const LOGIN_PASSWORD: &str = "#password";
-    pub const LOGIN_SUBMIT: &str = "button[type='submit']";
-    /// "Continua senza associare l'email" skip link on the post-login nag screen.
-    pub const SKIP_EMAIL_LINK: &str = "a:has-text('Continua senza associare')";
-    /// Export button - an <a> tag with class "export" and alt="scarica"
-    pub const EXPORT_BUTTON: &str = "a.export[alt='scarica']";
-    pub const EXPORT_DIALOG: &str = "div.ui-dialog[role='dialog']";
-    pub const DATE_FROM: &str = "#dal";
-    pub const DATE_TO: &str = "#al";
-    pub const CONFIRM_BUTTON: &str = "div.ui-dialog button:has-text('Conferma')";
+/// How often to check whether a manual login has reached the agenda page.
+const MANUAL_LOGIN_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Upper bound on agenda pages walked in either direction while collecting details.
+const MAX_AGENDA_PAGES: usize = 12;
+
+/// First and last `YYYY-MM-DD` day shown on the agenda calendar.
+const VISIBLE_RANGE_JS: &str = r#"
+    () => {
+        const dates = [...document.querySelectorAll('[data-date]')]
+            .map(el => el.dataset.date)
+            .filter(d => /^\d{4}-\d{2}-\d{2}$/.test(d))
+            .sort();
+        return dates.length ? [dates[0], dates[dates.length - 1]] : null;
+    }
+"#;
+
+/// Every agenda item with its position and, when it can be worked out, its day.
+/// Items inside a day cell carry the cell's `data-date`; otherwise fall back
+/// to the event FullCalendar attached to the element.
+const LIST_EVENTS_JS: &str = r#"
+    (selector) => [...document.querySelectorAll(selector)].map((el, index) => {
+        let date = null;
+        const cell = el.closest('[data-date]');
+        if (cell) {
+            date = cell.dataset.date;
+        } else if (typeof jQuery !== 'undefined') {
+            const seg = jQuery(el).data('fcSeg') || jQuery(el).data('fc-seg');
+            const start = seg && seg.event && seg.event.start;
+            if (start) {
+                date = typeof start.format === 'function'
+                    ? start.format('YYYY-MM-DD')
+                    : String(start).slice(0, 10);
+            }
+        }
+        return { index, date };
+    })
+"#;
+
+const CLICK_EVENT_JS: &str = r#"
+    ([selector, index]) => {
+        const el = document.querySelectorAll(selector)[index];
+        if (el) el.click();
+        return !!el;
+    }
+"#;
+
+/// Read the visible detail popup. jQuery UI keeps closed dialogs in the DOM,
+/// so only visible matches count.
+const READ_DETAIL_JS: &str = r#"
+    ([subjectSelector, textSelector, linkSelector]) => {
+        const visible = s => [...document.querySelectorAll(s)].filter(el => el.offsetParent !== null);
+        const text = s => { const el = visible(s)[0]; return el ? el.innerText : ''; };
+        return {
+            subject: text(subjectSelector),
+            text: text(textSelector),
+            attachments: visible(linkSelector).map(a => ({
+                name: (a.innerText || a.getAttribute('download') || '').trim(),
+                url: a.href,
+            })),
+        };
+    }
+"#;
+
+const CLOSE_DETAIL_JS: &str = r#"
+    (selector) => {
+        const el = [...document.querySelectorAll(selector)].find(el => el.offsetParent !== null);
+        if (el) el.click();
+    }
+"#;
+
+/// Every grade on the grades page, with the subject of its row.
+const READ_GRADES_JS: &str = r#"
+    ([rowSelector, subjectSelector, cellSelector, valueSelector, dateSelector]) => {
+        const text = el => el ? el.innerText : '';
+        const grades = [];
+        for (const row of document.querySelectorAll(rowSelector)) {
+            const subject = text(row.querySelector(subjectSelector));
+            for (const cell of row.querySelectorAll(cellSelector)) {
+                grades.push({
+                    subject,
+                    value: text(cell.querySelector(valueSelector) || cell),
+                    date: text(cell.querySelector(dateSelector)),
+                    note: cell.getAttribute('title') || '',
+                });
+            }
+        }
+        return grades;
+    }
+"#;
+
+/// One object per row of a table: `fields` maps each key to a selector
+/// inside the row, whose text becomes the value.
+const READ_ROWS_JS: &str = r#"
+    ([rowSelector, fields]) => {
+        const text = el => el ? el.innerText : '';
+        return [...document.querySelectorAll(rowSelector)].map(row =>
+            Object.fromEntries(Object.entries(fields).map(
+                ([key, selector]) => [key, text(row.querySelector(selector))]
+            ))
+        );
+    }
+"#;
+
+/// The options of the first `<select>` matching the selector.
+const LIST_OPTIONS_JS: &str = r#"
+    (selector) => {
+        const el = document.querySelector(selector);
+        if (!el || !el.options) return [];
+        return [...el.options].map(o => ({
+            value: o.value,
+            label: o.text.trim(),
+            selected: o.selected,
+        }));
+    }
+"#;
+
+/// Choose an option and fire the events a person choosing it would, so the
+/// agenda reloads for it.
+const SELECT_OPTION_JS: &str = r#"
+    ([selector, value]) => {
+        const el = document.querySelector(selector);
+        el.value = value;
+        el.dispatchEvent(new Event('input', { bubbles: true }));
+        el.dispatchEvent(new Event('change', { bubbles: true }));
+    }
+"#;
+
+/// An item on the agenda page, as listed by `LIST_EVENTS_JS`.
+#[derive(Debug, Deserialize)]
+struct AgendaEvent {
+    index: usize,
+    date: Option<String>,
+}
+
+/// An option of a class/group dropdown, as listed by `LIST_OPTIONS_JS`.
+#[derive(Debug, Clone, Deserialize)]
+struct SelectOption {
+    value: String,
+    label: String,
+    selected: bool,
+}
+
+/// `--class` / `--group`: what the agenda's class and group dropdowns are
+/// set to before its items are read or exported. Each names an option by
+/// its label or value.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AgendaFilter {
+    pub class: Option<String>,
+    pub group: Option<String>,
+}
+
+impl AgendaFilter {
+    pub fn is_empty(&self) -> bool {
+        self.class.is_none() && self.group.is_none()
+    }
+}
+
+/// The option of the `what` dropdown that `wanted` names: the one whose
+/// label or value it is, ignoring case, or else the only label containing
+/// it. The error lists the choices.
+fn pick_option<'a>(
+    options: &'a [SelectOption],
+    what: &str,
+    wanted: &str,
+) -> Result<&'a SelectOption> {
+    let wanted = wanted.trim();
+    let needle = wanted.to_lowercase();
+    if let Some(option) = options
+        .iter()
+        .find(|o| o.label.to_lowercase() == needle || o.value.to_lowercase() == needle)
+    {
+        return Ok(option);
+    }
+    let partial: Vec<&SelectOption> = options
+        .iter()
+        .filter(|o| o.label.to_lowercase().contains(&needle))
+        .collect();
+    if let [option] = partial[..] {
+        return Ok(option);
+    }
+    let choices = if partial.is_empty() {
+        options.iter().collect()
+    } else {
+        partial
+    };
+    let labels: Vec<&str> = choices.iter().map(|o| o.label.as_str()).collect();
+    bail!(
+        "No single {} matches '{}' (choices: {})",
+        what,
+        wanted,
+        if labels.is_empty() {
+            "none".to_string()
+        } else {
+            labels.join(", ")
+        }
+    )
 }
 
 /// Date range for export.
@@ -51,28 +249,212 @@ impl DateRange {
     }
 
     /// Format date for Classe Viva input fields (DD-MM-YYYY).
-    fn format_date(date: NaiveDate) -> String {
+    pub(crate) fn format_date(date: NaiveDate) -> String {
         date.format("%d-%m-%Y").to_string()
     }
 }
 
+/// Something `fetch --export` can produce.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum ExportKind {
+    /// The agenda spreadsheet compitutto imports
+    Agenda,
+    /// The grades table, saved as `grades_*.json`
+    Grades,
+    /// Absences, late entrances and early exits, saved as `absences_*.json`
+    Absences,
+    /// Disciplinary notes, saved as `notes_*.json`
+    Notes,
+}
+
+/// One navigation + download step of a fetch.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FetchStep {
+    /// Read the agenda detail popups into the `.details.json` sidecar
+    AgendaDetails,
+    /// Download the agenda export
+    AgendaExport,
+    /// Read the grades page into `grades_*.json`
+    Grades,
+    /// Read the absences page into `absences_*.json`
+    Absences,
+    /// Read the disciplinary notes page into `notes_*.json`
+    Notes,
+}
+
+impl FetchStep {
+    /// Page the step runs on.
+    fn url(self, profile: &PortalProfile) -> &str {
+        match self {
+            FetchStep::AgendaDetails | FetchStep::AgendaExport => &profile.agenda_url,
+            FetchStep::Grades => &profile.grades_url,
+            FetchStep::Absences => &profile.absences_url,
+            FetchStep::Notes => &profile.notes_url,
+        }
+    }
+
+    /// Best-effort steps only log a warning when they fail.
+    fn required(self) -> bool {
+        !matches!(self, FetchStep::AgendaDetails)
+    }
+
+    fn label(self) -> &'static str {
+        match self {
+            FetchStep::AgendaDetails => "agenda details",
+            FetchStep::AgendaExport => "agenda export",
+            FetchStep::Grades => "grades",
+            FetchStep::Absences => "absences",
+            FetchStep::Notes => "notes",
+        }
+    }
+}
+
+/// The steps of a fetch, run in order on one logged-in page.
+///
+/// Agenda steps come first since login lands on the agenda; the details
+/// sidecar is written before the export so compitutto's watcher finds it
+/// when the export appears.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FetchPlan {
+    steps: Vec<FetchStep>,
+    filter: AgendaFilter,
+}
+
+impl FetchPlan {
+    /// Plan the steps for `kinds` (the agenda when empty).
+    pub fn new(kinds: &[ExportKind], with_details: bool) -> Self {
+        let agenda = kinds.is_empty() || kinds.contains(&ExportKind::Agenda);
+        let mut steps = Vec::new();
+        if agenda && with_details {
+            steps.push(FetchStep::AgendaDetails);
+        }
+        if agenda {
+            steps.push(FetchStep::AgendaExport);
+        }
+        if kinds.contains(&ExportKind::Grades) {
+            steps.push(FetchStep::Grades);
+        }
+        if kinds.contains(&ExportKind::Absences) {
+            steps.push(FetchStep::Absences);
+        }
+        if kinds.contains(&ExportKind::Notes) {
+            steps.push(FetchStep::Notes);
+        }
+        Self {
+            steps,
+            filter: AgendaFilter::default(),
+        }
+    }
+
+    /// Set the agenda's class and group dropdowns before the agenda steps.
+    pub fn with_filter(mut self, filter: AgendaFilter) -> Self {
+        self.filter = filter;
+        self
+    }
+
+    pub fn steps(&self) -> &[FetchStep] {
+        &self.steps
+    }
+
+    pub fn filter(&self) -> &AgendaFilter {
+        &self.filter
+    }
+
+    /// Whether any step needs a browser (the HTTP engine only downloads the
+    /// agenda export, and can't choose a class or group).
+    pub fn needs_browser(&self) -> bool {
+        !self.filter.is_empty() || self.steps.iter().any(|s| *s != FetchStep::AgendaExport)
+    }
+}
+
+/// How the scraper gets past the login screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoginMode {
+    /// Fill and submit the login form with the configured credentials.
+    Automatic,
+    /// Let the user log in by hand in a headed browser, polling until the
+    /// agenda page is reached or `timeout` elapses.
+    Manual { timeout: Duration },
+}
+
+/// What the agenda URL showed to a saved session.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionProbe {
+    /// The portal redirected to its login form
+    LoginRequired,
+    /// Still logged in; `export_button` is whether the agenda's export
+    /// button was on the page
+    LoggedIn { export_button: bool },
+}
+
 /// Scraper for Classe Viva homework export.
 pub struct ClasseVivaScraper {
     context: BrowserContext,
-    credentials: Credentials,
+    profile: PortalProfile,
+    credentials: Option<Credentials>,
+    login_mode: LoginMode,
+    /// Where a challenge page's screenshot goes (none without)
+    debug_dir: Option<PathBuf>,
+    /// CAs and certificate checking for the download client
+    network: NetworkOptions,
+    /// Time spent in each phase of the fetch so far
+    timings: Mutex<Timings>,
 }
 
 impl ClasseVivaScraper {
-    /// Create a new scraper with the given browser context and credentials.
-    pub fn new(context: BrowserContext, credentials: Credentials) -> Self {
+    /// Create a new scraper for the given portal profile and browser context.
+    ///
+    /// Credentials are required for [`LoginMode::Automatic`]; in manual mode
+    /// they are only used to pre-fill the login form when available.
+    pub fn new(
+        context: BrowserContext,
+        profile: PortalProfile,
+        credentials: Option<Credentials>,
+        login_mode: LoginMode,
+    ) -> Self {
         Self {
             context,
+            profile,
             credentials,
+            login_mode,
+            debug_dir: None,
+            network: NetworkOptions::default(),
+            timings: Mutex::new(Timings::default()),
         }
     }
 
-    /// Perform login and return the page.
+    /// Where the time went so far, also after a failed fetch
+    pub fn timings(&self) -> Timings {
+        self.timings.lock().unwrap().clone()
+    }
+
+    /// Save the screenshot of a challenge page in `dir`.
+    pub fn with_debug_dir(mut self, dir: PathBuf) -> Self {
+        self.debug_dir = Some(dir);
+        self
+    }
+
+    /// Download with the same CAs and certificate checking as the browser.
+    pub fn with_network(mut self, network: NetworkOptions) -> Self {
+        self.network = network;
+        self
+    }
+
+    /// Perform login according to the configured [`LoginMode`] and return the page.
     pub async fn login(&self) -> Result<Page> {
+        match self.login_mode {
+            LoginMode::Automatic => self.login_automatic().await,
+            LoginMode::Manual { timeout } => self.login_manual(timeout).await,
+        }
+    }
+
+    /// Fill and submit the login form with the configured credentials.
+    async fn login_automatic(&self) -> Result<Page> {
+        let credentials = self
+            .credentials
+            .as_ref()
+            .ok_or_else(|| anyhow!("Credentials are required for automatic login"))?;
+
         info!("Navigating to Classe Viva agenda page");
         let page = self
             .context
@@ -81,33 +463,53 @@ impl ClasseVivaScraper {
             .context("Failed to create new page")?;
 
         // Navigate to agenda - will redirect to login if not authenticated
-        page.goto_builder(AGENDA_URL)
+        page.goto_builder(&self.profile.agenda_url)
             .goto()
             .await
             .context("Failed to navigate to agenda page")?;
 
-        // Wait for login form to appear
+        // Wait for login form to appear, or a CAPTCHA in its place
         debug!("Waiting for login form");
-        page.wait_for_selector_builder(selectors::LOGIN_USERNAME)
+        let selectors = &self.profile.selectors;
+        let form = page
+            .wait_for_selector_builder(&format!(
+                "{}, {}",
+                selectors.login_username, selectors.challenge
+            ))
+            .timeout(self.profile.waits.timeout(WaitStep::LoginForm))
             .wait_for_selector()
-            .await
-            .context("Login form did not appear")?;
+            .await;
+        let has_form = matches!(
+            page.query_selector(&selectors.login_username).await,
+            Ok(Some(_))
+        );
+        if !has_form {
+            self.check_challenge(&page).await?;
+        }
+        form.context("Login form did not appear")?;
 
         // Fill credentials
         info!("Filling login credentials");
-        page.fill_builder(selectors::LOGIN_USERNAME, &self.credentials.username)
-            .fill()
-            .await
-            .context("Failed to fill username")?;
+        page.fill_builder(
+            &self.profile.selectors.login_username,
+            &credentials.username,
+        )
+        .fill()
+        .await
+        .context("Failed to fill username")?;
 
-        page.fill_builder(selectors::LOGIN_PASSWORD, &self.credentials.password)
-            .fill()
-            .await
-            .context("Failed to fill password")?;
+        page.fill_builder(
+            &self.profile.selectors.login_password,
+            &credentials.password,
+        )
+        .fill()
+        .await
+        .context("Failed to fill password")?;
 
         // Submit form
         debug!("Submitting login form");
-        page.click_builder(selectors::LOGIN_SUBMIT)
+        let login_url = page.url().context("Failed to read the login page URL")?;
+        page.click_builder(&self.profile.selectors.login_submit)
             .click()
             .await
             .context("Failed to click login button")?;
@@ -115,34 +517,210 @@ impl ClasseVivaScraper {
         // After submitting, the page navigates. Wait for that navigation to
         // settle, then check whether the email nag screen appeared.
         info!("Login submitted, waiting for post-login page");
-
-        // Wait for the page to finish loading after the redirect.
-        tokio::time::sleep(Duration::from_secs(2)).await;
+        let waits = &self.profile.waits;
+        if let Err(e) = wait::url_change(&page, &login_url, waits, WaitStep::PostLogin).await {
+            self.check_challenge(&page).await?;
+            return Err(e).context("The login page did not go away - wrong credentials?");
+        }
+        wait::network_idle(&page, waits, WaitStep::PostLogin).await?;
+        self.check_challenge(&page).await?;
 
         self.dismiss_email_nag(&page).await?;
 
         Ok(page)
     }
 
+    /// Fail with [`ChallengeDetected`] if `page` is a CAPTCHA or browser
+    /// check, after saving a screenshot of it.
+    async fn check_challenge(&self, page: &Page) -> Result<()> {
+        let by_selector = page
+            .evaluate::<_, bool>(
+                &challenge::shown_js(),
+                self.profile.selectors.challenge.clone(),
+            )
+            .await
+            .unwrap_or(false);
+        let kind = if by_selector {
+            Some("CAPTCHA")
+        } else {
+            page.evaluate::<(), String>(&challenge::page_text_js(), ())
+                .await
+                .ok()
+                .and_then(|text| challenge::sniff(&text))
+        };
+        let Some(kind) = kind else {
+            return Ok(());
+        };
+
+        let url = page.url().unwrap_or_default();
+        warn!("Challenge page detected ({}) at {}", kind, url);
+        let screenshot = match &self.debug_dir {
+            Some(dir) => self.save_challenge_screenshot(page, dir).await,
+            None => None,
+        };
+        Err(ChallengeDetected {
+            kind: kind.to_string(),
+            url,
+            screenshot,
+        }
+        .into())
+    }
+
+    async fn save_challenge_screenshot(&self, page: &Page, dir: &Path) -> Option<PathBuf> {
+        if let Err(e) = std::fs::create_dir_all(dir) {
+            warn!("Can't create {:?}: {}", dir, e);
+            return None;
+        }
+        let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        let path = dir.join(format!("challenge_{}.png", stamp));
+        match page.screenshot_builder().full_page(true).screenshot().await {
+            Ok(png) => write_artifact(path, &png),
+            Err(e) => {
+                warn!("Screenshot failed: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Wait for the user to complete login by hand in the headed browser.
+    ///
+    /// Used when the automated flow breaks (site redesign, CAPTCHA). The
+    /// login form is pre-filled on a best-effort basis if credentials are
+    /// available; after that we poll until the agenda page with its export
+    /// button is showing, then hand the page back to the automated flow.
+    async fn login_manual(&self, timeout: Duration) -> Result<Page> {
+        info!("Navigating to Classe Viva agenda page");
+        let page = self
+            .context
+            .new_page()
+            .await
+            .context("Failed to create new page")?;
+
+        page.goto_builder(&self.profile.agenda_url)
+            .goto()
+            .await
+            .context("Failed to navigate to agenda page")?;
+
+        if let Some(credentials) = &self.credentials {
+            // Ignore failures: if the form changed, the user types instead.
+            let _ = page
+                .fill_builder(
+                    &self.profile.selectors.login_username,
+                    &credentials.username,
+                )
+                .timeout(2_000f64)
+                .fill()
+                .await;
+            let _ = page
+                .fill_builder(
+                    &self.profile.selectors.login_password,
+                    &credentials.password,
+                )
+                .timeout(2_000f64)
+                .fill()
+                .await;
+        }
+
+        info!(
+            "Complete the login in the browser window (waiting up to {}s)",
+            timeout.as_secs()
+        );
+
+        let deadline = tokio::time::Instant::now() + timeout;
+        loop {
+            if self.is_on_agenda(&page).await {
+                info!("Agenda page reached — continuing automated flow");
+                return Ok(page);
+            }
+            if tokio::time::Instant::now() >= deadline {
+                return Err(anyhow!(
+                    "Timed out after {}s waiting for manual login",
+                    timeout.as_secs()
+                ));
+            }
+            tokio::time::sleep(MANUAL_LOGIN_POLL_INTERVAL).await;
+        }
+    }
+
+    /// Cookies and local storage of the logged-in context, for
+    /// [`crate::session::save`].
+    pub async fn storage_state(&self) -> Result<StorageState> {
+        self.context
+            .storage_state()
+            .await
+            .context("Failed to read the browser session")
+    }
+
+    /// Open the agenda with whatever session the context already has and
+    /// report what the portal shows, without logging in or downloading.
+    pub async fn probe_session(&self) -> Result<SessionProbe> {
+        let page = self
+            .context
+            .new_page()
+            .await
+            .context("Failed to create new page")?;
+        page.goto_builder(&self.profile.agenda_url)
+            .goto()
+            .await
+            .context("Failed to navigate to agenda page")?;
+
+        let selectors = &self.profile.selectors;
+        let any = format!(
+            "{}, {}, {}",
+            selectors.export_button, selectors.login_username, selectors.skip_email_link
+        );
+        page.wait_for_selector_builder(&any)
+            .timeout(self.profile.waits.timeout(WaitStep::Probe))
+            .wait_for_selector()
+            .await
+            .context("Neither the agenda nor the login form appeared")?;
+
+        if matches!(
+            page.query_selector(&selectors.login_username).await,
+            Ok(Some(_))
+        ) {
+            return Ok(SessionProbe::LoginRequired);
+        }
+        Ok(SessionProbe::LoggedIn {
+            export_button: self.is_on_agenda(&page).await,
+        })
+    }
+
+    /// Whether the page is showing the agenda with its export button.
+    async fn is_on_agenda(&self, page: &Page) -> bool {
+        let on_agenda_url = page
+            .url()
+            .map(|url| url.starts_with(&self.profile.agenda_url))
+            .unwrap_or(false);
+        if !on_agenda_url {
+            return false;
+        }
+        matches!(
+            page.query_selector(&self.profile.selectors.export_button)
+                .await,
+            Ok(Some(_))
+        )
+    }
+
     /// After login, Classe Viva sometimes shows an "associate your email" nag
     /// screen before the agenda. Detect it by looking for the skip link with a
-    /// short timeout — if the selector doesn't appear within 5 s we're already
-    /// on the agenda and can move on.
+    /// short timeout (`email_nag`, 5 s by default) — if the selector doesn't
+    /// appear by then we're already on the agenda and can move on.
     async fn dismiss_email_nag(&self, page: &Page) -> Result<()> {
         debug!("Checking for email nag screen");
 
-        // 5 000 ms timeout: enough time for the nag to render if it's coming,
-        // short enough not to add painful delay when it isn't there.
+        // Enough time for the nag to render if it's coming, short enough not
+        // to add painful delay when it isn't there.
         let found = page
-            .wait_for_selector_builder(selectors::SKIP_EMAIL_LINK)
-            .timeout(5_000f64)
+            .wait_for_selector_builder(&self.profile.selectors.skip_email_link)
+            .timeout(self.profile.waits.timeout(WaitStep::EmailNag))
             .wait_for_selector()
             .await;
 
         match found {
             Ok(Some(_)) => {
                 info!("Email nag screen detected — clicking skip link");
-                page.click_builder(selectors::SKIP_EMAIL_LINK)
+                page.click_builder(&self.profile.selectors.skip_email_link)
                     .click()
                     .await
                     .context("Failed to click 'Continua senza associare l'email'")?;
@@ -161,21 +739,24 @@ impl ClasseVivaScraper {
     pub async fn open_export_dialog(&self, page: &Page) -> Result<()> {
         info!("Opening export dialog");
 
-        // Wait for the export button to be visible and stable
+        // Wait for the export button to be visible and usable: the agenda
+        // loads its items after the button shows up, and a click before that
+        // is sometimes lost
         debug!("Waiting for export button to appear");
-        page.wait_for_selector_builder(selectors::EXPORT_BUTTON)
-            .state(FrameState::Visible)
-            .wait_for_selector()
-            .await
-            .context("Export button not found - login may have failed")?;
-
-        // Small delay to ensure the page is fully interactive
-        // This helps with race conditions where the element exists but isn't clickable
-        tokio::time::sleep(Duration::from_millis(500)).await;
+        let waits = &self.profile.waits;
+        wait::enabled(
+            page,
+            &self.profile.selectors.export_button,
+            waits,
+            WaitStep::ExportButton,
+        )
+        .await
+        .context("Export button not found - login may have failed")?;
+        wait::network_idle(page, waits, WaitStep::ExportButton).await?;
 
         // Click with force option to bypass actionability checks if needed
         debug!("Clicking export button");
-        page.click_builder(selectors::EXPORT_BUTTON)
+        page.click_builder(&self.profile.selectors.export_button)
             .force(true)
             .click()
             .await
@@ -183,8 +764,9 @@ impl ClasseVivaScraper {
 
         // Wait for dialog to appear
         debug!("Waiting for export dialog");
-        page.wait_for_selector_builder(selectors::EXPORT_DIALOG)
+        page.wait_for_selector_builder(&self.profile.selectors.export_dialog)
             .state(FrameState::Visible)
+            .timeout(waits.timeout(WaitStep::ExportDialog))
             .wait_for_selector()
             .await
             .context("Export dialog did not appear")?;
@@ -193,6 +775,45 @@ impl ClasseVivaScraper {
         Ok(())
     }
 
+    /// Choose `filter`'s class and group in the agenda's dropdowns, waiting
+    /// for the agenda to reload after each. A dropdown already showing the
+    /// choice is left alone.
+    pub async fn select_agenda_filter(&self, page: &Page, filter: &AgendaFilter) -> Result<()> {
+        let selectors = &self.profile.selectors;
+        let waits = &self.profile.waits;
+        let dropdowns = [
+            ("class", &selectors.agenda_class, &filter.class),
+            ("group", &selectors.agenda_group, &filter.group),
+        ];
+        for (what, selector, wanted) in dropdowns {
+            let Some(wanted) = wanted else { continue };
+            page.wait_for_selector_builder(selector)
+                .state(FrameState::Attached)
+                .timeout(waits.timeout(WaitStep::AgendaFilter))
+                .wait_for_selector()
+                .await
+                .with_context(|| format!("The agenda has no {} dropdown ({})", what, selector))?;
+            let options: Vec<SelectOption> = page
+                .evaluate(LIST_OPTIONS_JS, selector.clone())
+                .await
+                .with_context(|| format!("Failed to read the {} dropdown", what))?;
+            let option = pick_option(&options, what, wanted)?;
+            if option.selected {
+                debug!("{} '{}' is already selected", what, option.label);
+                continue;
+            }
+            info!("Selecting {} '{}'", what, option.label);
+            page.evaluate::<_, ()>(
+                SELECT_OPTION_JS,
+                serde_json::json!([selector, option.value]),
+            )
+            .await
+            .with_context(|| format!("Failed to select {} '{}'", what, option.label))?;
+            wait::network_idle(page, waits, WaitStep::AgendaFilter).await?;
+        }
+        Ok(())
+    }
+
     /// Fill the date range in the export dialog.
     ///
     /// Uses Playwright's fill method and JavaScript to handle jQuery datepicker fields.
@@ -233,18 +854,17 @@ impl ClasseVivaScraper {
             Ok(())
         }
 
-        // Set the "from" date
+        // Set each date and let its datepicker settle before moving on, so
+        // the UI is up to date before clicking confirm
+        let waits = &self.profile.waits;
+        let selectors = &self.profile.selectors;
         debug!("Setting from date: {}", from_str);
-        fill_date_field(page, selectors::DATE_FROM, &from_str).await?;
+        fill_date_field(page, &selectors.date_from, &from_str).await?;
+        wait::field_settled(page, &selectors.date_from, waits, WaitStep::DateFields).await?;
 
-        tokio::time::sleep(Duration::from_millis(200)).await;
-
-        // Set the "to" date
         debug!("Setting to date: {}", to_str);
-        fill_date_field(page, selectors::DATE_TO, &to_str).await?;
-
-        // Pause after setting dates to let UI fully update before clicking confirm
-        tokio::time::sleep(Duration::from_millis(500)).await;
+        fill_date_field(page, &selectors.date_to, &to_str).await?;
+        wait::field_settled(page, &selectors.date_to, waits, WaitStep::DateFields).await?;
 
         Ok(())
     }
@@ -257,19 +877,25 @@ impl ClasseVivaScraper {
     ///
     /// We arm both listeners before clicking Confirm, then race them with
     /// `tokio::select!`. Whichever fires first wins; we extract the Download
-    /// from it and save via Playwright's built-in handling.
+    /// from it and use the browser's finished file, or fetch its URL again
+    /// with reqwest when that isn't available.
     ///
-    /// Returns the path to the downloaded file.
-    pub async fn trigger_download(&self, page: &Page, output_dir: &Path) -> Result<PathBuf> {
+    /// Saves to `output_dir/filename` (checked and atomic, see `download`)
+    /// and returns the path.
+    pub async fn trigger_download(
+        &self,
+        page: &Page,
+        range: &DateRange,
+        output_dir: &Path,
+        filename: &str,
+    ) -> Result<PathBuf> {
         info!("Triggering download");
 
-        // Generate output filename with timestamp
-        let timestamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
-        let filename = format!("export_{}.xls", timestamp);
         let output_path = output_dir
             .canonicalize()
             .context("Failed to resolve output directory path")?
-            .join(&filename);
+            .join(filename);
+        let output_dir = output_path.parent().unwrap_or(output_dir);
 
         // Arm both listeners BEFORE clicking so we don't miss the event.
         let direct_download_future = page.expect_event(EventType::Download);
@@ -277,7 +903,7 @@ impl ClasseVivaScraper {
 
         // Click confirm to trigger whichever download mechanism the site uses.
         debug!("Clicking confirm button");
-        page.click_builder(selectors::CONFIRM_BUTTON)
+        page.click_builder(&self.profile.selectors.confirm_button)
             .click()
             .await
             .context("Failed to click confirm button")?;
@@ -306,7 +932,7 @@ impl ClasseVivaScraper {
                     _ => return Err(anyhow!("Unexpected event, expected Popup")),
                 };
                 let dl_event = tokio::time::timeout(
-                    Duration::from_secs(30),
+                    self.profile.waits.duration(WaitStep::Download),
                     popup.expect_event(EventType::Download),
                 )
                 .await
@@ -322,16 +948,34 @@ impl ClasseVivaScraper {
             }
         };
 
+        // Use the browser's own copy when it finishes and checks out.
+        if let Some(bytes) = finished_download(&download).await {
+            match download::save_export(output_dir, filename, &bytes, None) {
+                Ok(path) => return Ok(path),
+                Err(e) => warn!("Browser download rejected, fetching again: {:#}", e),
+            }
+        }
+
         // Playwright's download interception is unreliable in headed mode —
         // the browser's native download manager takes over and saves to
         // ~/Downloads instead. Instead we grab the URL and cookies from the
         // Download event and fetch the file ourselves with reqwest.
         let download_url = download.url().to_string();
+        if let Some(template) = http_engine::export_url_template(&download_url, range) {
+            info!("For --engine http, set export_url = \"{}\"", template);
+        }
+
+        let (bytes, expected_len) = self.fetch_with_session(&download_url).await?;
+        download::save_export(output_dir, filename, &bytes, expected_len)
+    }
 
+    /// GET `url` with reqwest, authenticated with the browser's cookies.
+    /// Returns the body and the length the server announced.
+    async fn fetch_with_session(&self, url: &str) -> Result<(Vec<u8>, Option<u64>)> {
         // Extract cookies from the browser context for authentication.
         let cookies = self
             .context
-            .cookies(&[AGENDA_URL.to_string()])
+            .cookies(std::slice::from_ref(&self.profile.agenda_url))
             .await
             .context("Failed to get cookies from browser")?;
 
@@ -343,15 +987,16 @@ impl ClasseVivaScraper {
 
         debug!("Fetching file via reqwest ({} cookies)", cookies.len());
 
-        let client = reqwest::Client::builder()
-            .use_rustls_tls()
+        let client = self
+            .network
+            .client_builder()
             .build()
             .context("Failed to build HTTP client")?;
 
         let response = client
-            .get(&download_url)
+            .get(url)
             .header("Cookie", &cookie_header)
-            .header("Referer", AGENDA_URL)
+            .header("Referer", &self.profile.agenda_url)
             .send()
             .await
             .context("Failed to fetch download URL")?;
@@ -363,47 +1008,582 @@ impl ClasseVivaScraper {
             ));
         }
 
+        let expected_len = response.content_length();
         let bytes = response
             .bytes()
             .await
             .context("Failed to read download response body")?;
+        Ok((bytes.to_vec(), expected_len))
+    }
 
-        std::fs::write(&output_path, &bytes).context("Failed to write downloaded file")?;
+    /// Download the photo attachments in `details` into `output_dir` (see
+    /// `Attachment::photo_path`) and record where each one went. Photos
+    /// already on disk are reused; failures are logged and skipped.
+    async fn save_photos(&self, details: &mut [EntryDetail], output_dir: &Path) {
+        let mut saved = 0;
+        for attachment in details.iter_mut().flat_map(|d| d.attachments.iter_mut()) {
+            let Some(relative) = attachment.photo_path() else {
+                continue;
+            };
+            let path = output_dir.join(&relative);
+            if !path.exists() {
+                let result = match self.fetch_with_session(&attachment.url).await {
+                    Ok((bytes, _)) => path
+                        .parent()
+                        .map_or(Ok(()), std::fs::create_dir_all)
+                        .map_err(Into::into)
+                        .and_then(|_| download::write_atomic(&path, &bytes))
+                        .context("Failed to save photo"),
+                    Err(e) => Err(e),
+                };
+                if let Err(e) = result {
+                    warn!("Could not download {}: {:#}", attachment.name, e);
+                    continue;
+                }
+                saved += 1;
+            }
+            attachment.file = Some(relative);
+        }
+        if saved > 0 {
+            info!("Saved {} photo attachments", saved);
+        }
+    }
 
-        info!(
-            "Download saved to: {:?} ({} bytes)",
-            output_path,
-            bytes.len()
-        );
-        Ok(output_path)
+    /// Open every agenda item in `range` and read its detail popup.
+    ///
+    /// Pages the agenda back until `range.from` is showing, then forward until
+    /// `range.to` is. Items whose day can't be worked out are skipped; an item
+    /// whose popup fails to open is logged and skipped.
+    pub async fn scrape_details(&self, page: &Page, range: &DateRange) -> Result<Vec<EntryDetail>> {
+        let selectors = &self.profile.selectors;
+        info!("Collecting entry details from the agenda");
+
+        page.wait_for_selector_builder(&selectors.export_button)
+            .state(FrameState::Visible)
+            .timeout(self.profile.waits.timeout(WaitStep::AgendaPage))
+            .wait_for_selector()
+            .await
+            .context("Agenda page did not load")?;
+        wait::network_idle(page, &self.profile.waits, WaitStep::AgendaPage).await?;
+
+        // Rewind to the page showing the start of the range
+        for _ in 0..MAX_AGENDA_PAGES {
+            match self.visible_range(page).await? {
+                Some((first, _)) if first > range.from => {
+                    self.turn_agenda_page(page, &selectors.agenda_prev).await?
+                }
+                _ => break,
+            }
+        }
+
+        let mut details = Vec::new();
+        let mut undated = 0;
+        for _ in 0..MAX_AGENDA_PAGES {
+            let events: Vec<AgendaEvent> = page
+                .evaluate(LIST_EVENTS_JS, selectors.agenda_event.clone())
+                .await
+                .context("Failed to list agenda items")?;
+            debug!("{} agenda items on this page", events.len());
+
+            for event in events {
+                let Some(date) = event
+                    .date
+                    .as_deref()
+                    .and_then(|d| NaiveDate::parse_from_str(d, "%Y-%m-%d").ok())
+                else {
+                    undated += 1;
+                    continue;
+                };
+                if date < range.from || date > range.to {
+                    continue;
+                }
+                match self.read_detail(page, event.index).await {
+                    Ok(Some(raw)) => details.extend(EntryDetail::from_raw(date, raw)),
+                    Ok(None) => {}
+                    Err(e) => warn!("Could not read details for item on {}: {}", date, e),
+                }
+            }
+
+            match self.visible_range(page).await? {
+                Some((_, last)) if last < range.to => {
+                    self.turn_agenda_page(page, &selectors.agenda_next).await?
+                }
+                _ => break,
+            }
+        }
+
+        if undated > 0 {
+            warn!("Skipped {} agenda items with no recognisable date", undated);
+        }
+        info!("Collected details for {} items", details.len());
+        Ok(details)
+    }
+
+    /// First and last day shown on the agenda, if the calendar exposes them.
+    async fn visible_range(&self, page: &Page) -> Result<Option<(NaiveDate, NaiveDate)>> {
+        let range: Option<(String, String)> = page
+            .evaluate(VISIBLE_RANGE_JS, ())
+            .await
+            .context("Failed to read the visible agenda range")?;
+        Ok(range.and_then(|(first, last)| {
+            Some((
+                NaiveDate::parse_from_str(&first, "%Y-%m-%d").ok()?,
+                NaiveDate::parse_from_str(&last, "%Y-%m-%d").ok()?,
+            ))
+        }))
+    }
+
+    /// Click the agenda's previous/next button and wait for the calendar to
+    /// load the new page's items.
+    async fn turn_agenda_page(&self, page: &Page, selector: &str) -> Result<()> {
+        page.click_builder(selector)
+            .click()
+            .await
+            .context("Failed to change agenda page")?;
+        wait::network_idle(page, &self.profile.waits, WaitStep::AgendaPage).await?;
+        Ok(())
+    }
+
+    /// Open the detail popup for the `index`-th agenda item, read it and close it.
+    async fn read_detail(&self, page: &Page, index: usize) -> Result<Option<RawDetail>> {
+        let selectors = &self.profile.selectors;
+        let waits = &self.profile.waits;
+
+        let clicked: bool = page
+            .evaluate(
+                CLICK_EVENT_JS,
+                serde_json::json!([selectors.agenda_event, index]),
+            )
+            .await
+            .context("Failed to click agenda item")?;
+        if !clicked {
+            return Ok(None);
+        }
+
+        page.wait_for_selector_builder(&selectors.detail_dialog)
+            .state(FrameState::Visible)
+            .timeout(waits.timeout(WaitStep::DetailPopup))
+            .wait_for_selector()
+            .await
+            .context("Detail popup did not appear")?;
+        // The popup body is sometimes filled in after the dialog opens
+        wait::network_idle(page, waits, WaitStep::DetailPopup).await?;
+
+        let raw: RawDetail = page
+            .evaluate(
+                READ_DETAIL_JS,
+                serde_json::json!([
+                    selectors.detail_subject,
+                    selectors.detail_text,
+                    selectors.detail_attachment
+                ]),
+            )
+            .await
+            .context("Failed to read detail popup")?;
+
+        // Ignore failures: a popup left open is replaced by the next one.
+        let _ = page
+            .evaluate::<_, ()>(CLOSE_DETAIL_JS, selectors.detail_close.clone())
+            .await;
+        let _ = page
+            .wait_for_selector_builder(&selectors.detail_dialog)
+            .state(FrameState::Hidden)
+            .timeout(waits.timeout(WaitStep::DetailPopup))
+            .wait_for_selector()
+            .await;
+
+        Ok(Some(raw))
+    }
+
+    /// Read the grades table. The page must already be showing.
+    pub async fn scrape_grades(&self, page: &Page) -> Result<Vec<Grade>> {
+        let selectors = &self.profile.selectors;
+        info!("Reading grades");
+        page.wait_for_selector_builder(&selectors.grade_row)
+            .timeout(self.profile.waits.timeout(WaitStep::Grades))
+            .wait_for_selector()
+            .await
+            .context("Grades table not found")?;
+
+        let raw: Vec<RawGrade> = page
+            .evaluate(
+                READ_GRADES_JS,
+                serde_json::json!([
+                    selectors.grade_row,
+                    selectors.grade_subject,
+                    selectors.grade_cell,
+                    selectors.grade_value,
+                    selectors.grade_date,
+                ]),
+            )
+            .await
+            .context("Failed to read grades table")?;
+        Ok(raw.into_iter().filter_map(Grade::from_raw).collect())
+    }
+
+    /// Read the absences table. The page must already be showing.
+    pub async fn scrape_absences(&self, page: &Page) -> Result<Vec<Absence>> {
+        let s = &self.profile.selectors;
+        info!("Reading absences");
+        let raw: Vec<RawAbsence> = self
+            .read_rows(
+                page,
+                &s.absence_row,
+                serde_json::json!({
+                    "date": s.absence_date,
+                    "kind": s.absence_kind,
+                    "status": s.absence_status,
+                    "note": s.absence_note,
+                }),
+                WaitStep::Absences,
+            )
+            .await
+            .context("Failed to read absences table")?;
+        Ok(raw.into_iter().filter_map(Absence::from_raw).collect())
+    }
+
+    /// Read the disciplinary notes table. The page must already be showing.
+    pub async fn scrape_notes(&self, page: &Page) -> Result<Vec<Note>> {
+        let s = &self.profile.selectors;
+        info!("Reading disciplinary notes");
+        let raw: Vec<RawNote> = self
+            .read_rows(
+                page,
+                &s.note_row,
+                serde_json::json!({
+                    "date": s.note_date,
+                    "teacher": s.note_teacher,
+                    "kind": s.note_kind,
+                    "text": s.note_text,
+                }),
+                WaitStep::Notes,
+            )
+            .await
+            .context("Failed to read notes table")?;
+        Ok(raw.into_iter().filter_map(Note::from_raw).collect())
     }
 
-    /// Perform the complete fetch operation.
+    /// Read the rows matching `row_selector` once the page has settled. No
+    /// rows is a valid answer (a student without absences), so the table
+    /// isn't waited for.
+    async fn read_rows<T: serde::de::DeserializeOwned>(
+        &self,
+        page: &Page,
+        row_selector: &str,
+        fields: serde_json::Value,
+        step: WaitStep,
+    ) -> Result<Vec<T>> {
+        wait::network_idle(page, &self.profile.waits, step).await?;
+        let rows = page
+            .evaluate(READ_ROWS_JS, serde_json::json!([row_selector, fields]))
+            .await?;
+        Ok(rows)
+    }
+
+    /// Navigate to `url` unless the page is already there.
+    async fn ensure_on(&self, page: &Page, url: &str) -> Result<()> {
+        if page.url().is_ok_and(|current| current.starts_with(url)) {
+            return Ok(());
+        }
+        debug!("Navigating to {}", url);
+        page.goto_builder(url)
+            .goto()
+            .await
+            .with_context(|| format!("Failed to navigate to {}", url))?;
+        Ok(())
+    }
+
+    /// Run one step on the logged-in page. Returns the file it wrote.
+    async fn run_step(
+        &self,
+        step: FetchStep,
+        page: &Page,
+        range: &DateRange,
+        filter: &AgendaFilter,
+        output_dir: &Path,
+        filename: &str,
+    ) -> Result<PathBuf> {
+        metrics::timed(&self.timings, Phase::Navigate, async {
+            self.ensure_on(page, step.url(&self.profile)).await?;
+            if matches!(step, FetchStep::AgendaDetails | FetchStep::AgendaExport) {
+                self.select_agenda_filter(page, filter).await?;
+            }
+            Ok::<_, anyhow::Error>(())
+        })
+        .await?;
+        let timings = &self.timings;
+        match step {
+            FetchStep::AgendaDetails => {
+                let mut collected = metrics::timed(timings, Phase::Details, async {
+                    let mut collected = self.scrape_details(page, range).await?;
+                    self.save_photos(&mut collected, output_dir).await;
+                    Ok::<_, anyhow::Error>(collected)
+                })
+                .await?;
+                let path = details::sidecar_path(&output_dir.join(filename));
+                let file = DetailsFile::new(range, collected);
+                file.write(&path)?;
+                info!(
+                    "Details saved to: {:?} ({} items)",
+                    path,
+                    file.entries.len()
+                );
+                Ok(path)
+            }
+            FetchStep::AgendaExport => {
+                metrics::timed(timings, Phase::Dialog, async {
+                    self.open_export_dialog(page).await?;
+                    self.fill_date_range(page, range).await
+                })
+                .await?;
+                metrics::timed(
+                    timings,
+                    Phase::Download,
+                    self.trigger_download(page, range, output_dir, filename),
+                )
+                .await
+            }
+            FetchStep::Grades => {
+                let file = GradesFile {
+                    fetched: chrono::Local::now().date_naive(),
+                    grades: metrics::timed(timings, Phase::Register, self.scrape_grades(page))
+                        .await?,
+                };
+                let path = grades::grades_path(&output_dir.join(filename));
+                file.write(&path)?;
+                info!("Grades saved to: {:?} ({} grades)", path, file.grades.len());
+                Ok(path)
+            }
+            FetchStep::Absences => {
+                let file = AbsencesFile {
+                    fetched: chrono::Local::now().date_naive(),
+                    absences: metrics::timed(timings, Phase::Register, self.scrape_absences(page))
+                        .await?,
+                };
+                let path = register::absences_path(&output_dir.join(filename));
+                file.write(&path)?;
+                info!(
+                    "Absences saved to: {:?} ({} absences)",
+                    path,
+                    file.absences.len()
+                );
+                Ok(path)
+            }
+            FetchStep::Notes => {
+                let file = NotesFile {
+                    fetched: chrono::Local::now().date_naive(),
+                    notes: metrics::timed(timings, Phase::Register, self.scrape_notes(page))
+                        .await?,
+                };
+                let path = register::notes_path(&output_dir.join(filename));
+                file.write(&path)?;
+                info!("Notes saved to: {:?} ({} notes)", path, file.notes.len());
+                Ok(path)
+            }
+        }
+    }
+
+    /// Log in once, then run every step of `plan` on the same page.
     ///
-    /// If `dry_run` is true, stops after login without downloading.
+    /// If `dry_run` is true, stops after login. A failed step doesn't stop
+    /// the ones after it; best-effort steps (details) only warn, and any
+    /// other failure makes the whole fetch an error once every step has run.
+    /// Returns the files written.
     pub async fn fetch(
         &self,
+        plan: &FetchPlan,
         range: DateRange,
         output_dir: &Path,
+        filename: &str,
         dry_run: bool,
-    ) -> Result<Option<PathBuf>> {
-        // Step 1: Login
-        let page = self.login().await?;
+    ) -> Result<Vec<PathBuf>> {
+        let page = metrics::timed(&self.timings, Phase::Login, self.login()).await?;
 
         if dry_run {
             info!("Dry run mode - stopping after login");
-            return Ok(None);
+            return Ok(Vec::new());
+        }
+
+        let mut saved = Vec::new();
+        let mut failed = Vec::new();
+        for &step in plan.steps() {
+            match self
+                .run_step(step, &page, &range, plan.filter(), output_dir, filename)
+                .await
+            {
+                Ok(path) => saved.push(path),
+                Err(e) if step.required() => {
+                    warn!("Step '{}' failed: {:#}", step.label(), e);
+                    failed.push(format!("{}: {:#}", step.label(), e));
+                }
+                Err(e) => warn!("Skipping {}: {:#}", step.label(), e),
+            }
+        }
+
+        if !failed.is_empty() {
+            for path in &saved {
+                info!("Saved before the failure: {:?}", path);
+            }
+            bail!("{}", failed.join("; "));
         }
+        Ok(saved)
+    }
 
-        // Step 2: Open export dialog
-        self.open_export_dialog(&page).await?;
+    /// Save a full-page screenshot and the HTML of every open page to
+    /// `output_dir/debug/`, for looking into a failed run afterwards.
+    ///
+    /// Best effort: returns whatever could be written.
+    pub async fn save_debug_artifacts(&self, output_dir: &Path) -> Vec<PathBuf> {
+        let dir = output_dir.join(DEBUG_DIR);
+        let pages = match self.context.pages() {
+            Ok(pages) => pages,
+            Err(e) => {
+                warn!("Can't list pages for debug artifacts: {}", e);
+                return Vec::new();
+            }
+        };
+        if pages.is_empty() {
+            return Vec::new();
+        }
+        if let Err(e) = std::fs::create_dir_all(&dir) {
+            warn!("Can't create {:?}: {}", dir, e);
+            return Vec::new();
+        }
 
-        // Step 3: Fill date range
-        self.fill_date_range(&page, &range).await?;
+        let stamp = chrono::Local::now().format("%Y%m%d_%H%M%S");
+        let mut saved = Vec::new();
+        for (i, page) in pages.iter().enumerate() {
+            let base = dir.join(format!("fetch_{}_{}", stamp, i));
+            match page.screenshot_builder().full_page(true).screenshot().await {
+                Ok(png) => saved.extend(write_artifact(base.with_extension("png"), &png)),
+                Err(e) => warn!("Screenshot failed: {}", e),
+            }
+            match page.content().await {
+                Ok(html) => {
+                    saved.extend(write_artifact(base.with_extension("html"), html.as_bytes()))
+                }
+                Err(e) => warn!("Saving page HTML failed: {}", e),
+            }
+        }
+        for path in &saved {
+            info!("Debug artifact saved: {:?}", path);
+        }
+        saved
+    }
+}
 
-        // Step 4: Trigger download
-        let output_path = self.trigger_download(&page, output_dir).await?;
+/// Subdirectory of the output directory for failure screenshots and pages.
+pub const DEBUG_DIR: &str = "debug";
 
-        Ok(Some(output_path))
+fn write_artifact(path: PathBuf, bytes: &[u8]) -> Option<PathBuf> {
+    match std::fs::write(&path, bytes) {
+        Ok(()) => Some(path),
+        Err(e) => {
+            warn!("Can't write {:?}: {}", path, e);
+            None
+        }
+    }
+}
+
+/// How long to wait for the browser to finish writing a download.
+const DOWNLOAD_FINISH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// The browser's finished copy of `download`, or `None` if it failed, didn't
+/// finish in time or went to the native download manager instead.
+///
+/// `path()` only returns once the download is complete, so a file it names
+/// is never partial. Playwright's copy is deleted after reading.
+async fn finished_download(download: &Download) -> Option<Vec<u8>> {
+    let path = match tokio::time::timeout(DOWNLOAD_FINISH_TIMEOUT, download.path()).await {
+        Ok(Ok(Some(path))) => path,
+        Ok(Ok(None)) => return None,
+        Ok(Err(e)) => {
+            debug!("Browser download unavailable: {}", e);
+            return None;
+        }
+        Err(_) => {
+            debug!(
+                "Browser download didn't finish in {:?}",
+                DOWNLOAD_FINISH_TIMEOUT
+            );
+            return None;
+        }
+    };
+    if let Ok(Some(failure)) = download.failure().await {
+        warn!("Browser download failed: {}", failure);
+        return None;
+    }
+    let bytes = std::fs::read(&path).ok();
+    if let Err(e) = download.delete().await {
+        debug!("Failed to delete browser download copy: {}", e);
+    }
+    bytes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fetch_plan_orders_steps() {
+        use FetchStep::*;
+        assert_eq!(FetchPlan::new(&[], false).steps(), &[AgendaExport]);
+        assert_eq!(
+            FetchPlan::new(&[ExportKind::Grades, ExportKind::Agenda], true).steps(),
+            &[AgendaDetails, AgendaExport, Grades]
+        );
+        // Details belong to the agenda; no agenda, no details
+        assert_eq!(
+            FetchPlan::new(&[ExportKind::Grades], true).steps(),
+            &[Grades]
+        );
+        assert_eq!(
+            FetchPlan::new(
+                &[ExportKind::Notes, ExportKind::Agenda, ExportKind::Absences],
+                false
+            )
+            .steps(),
+            &[AgendaExport, Absences, Notes]
+        );
+    }
+
+    #[test]
+    fn test_fetch_plan_needs_browser() {
+        assert!(!FetchPlan::new(&[ExportKind::Agenda], false).needs_browser());
+        assert!(FetchPlan::new(&[ExportKind::Agenda], true).needs_browser());
+        assert!(FetchPlan::new(&[ExportKind::Agenda, ExportKind::Grades], false).needs_browser());
+        let filter = AgendaFilter {
+            class: None,
+            group: Some("Gruppo A".to_string()),
+        };
+        assert!(FetchPlan::new(&[ExportKind::Agenda], false)
+            .with_filter(filter)
+            .needs_browser());
+    }
+
+    #[test]
+    fn test_pick_option() {
+        let option = |value: &str, label: &str| SelectOption {
+            value: value.to_string(),
+            label: label.to_string(),
+            selected: false,
+        };
+        let options = [
+            option("0", "Tutti"),
+            option("12", "Gruppo A - Inglese"),
+            option("13", "Gruppo B - Inglese"),
+            option("14", "Religione"),
+        ];
+        let pick = |wanted: &str| pick_option(&options, "group", wanted).map(|o| o.value.as_str());
+        assert_eq!(pick("tutti").unwrap(), "0");
+        assert_eq!(pick("13").unwrap(), "13");
+        assert_eq!(pick("gruppo a").unwrap(), "12");
+        // More than one label contains it: only those are offered
+        let err = pick("inglese").unwrap_err().to_string();
+        assert!(err.ends_with("(choices: Gruppo A - Inglese, Gruppo B - Inglese)"));
+        assert!(pick("Gruppo C")
+            .unwrap_err()
+            .to_string()
+            .contains("Tutti, Gruppo A - Inglese"));
     }
 }
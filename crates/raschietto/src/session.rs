@@ -0,0 +1,283 @@
+//! The logged-in browser session, saved between runs.
+//!
+//! After a browser fetch logs in, the context's storage state (cookies and
+//! local storage) is written to `.raschietto/<profile>.json`, or the path in
+//! `--session-file`. `raschietto status` loads it to check whether the
+//! portal still accepts it, and `raschietto session export` hands its
+//! cookies to other tools. The file holds live session cookies, so on Unix
+//! it is only readable by its owner.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use clap::ValueEnum;
+use playwright::api::{Cookie, StorageState};
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+
+/// Where sessions are saved when `--session-file` isn't given.
+pub const SESSION_DIR: &str = ".raschietto";
+
+/// Default session file for `profile`.
+pub fn default_path(profile: &str) -> PathBuf {
+    Path::new(SESSION_DIR).join(format!("{}.json", profile))
+}
+
+/// Write `state` to `path`, creating its directory.
+pub fn save(path: &Path, state: &StorageState) -> Result<()> {
+    let json = serde_json::to_vec_pretty(state)?;
+    write_private(path, &json).context("Failed to write session")
+}
+
+/// Write `contents` to `path`, creating its directory, readable only by the
+/// owner on Unix. A new file is created with that mode, so the contents are
+/// never readable by others, not even between the write and a chmod; an
+/// existing one is narrowed before it's written.
+pub fn write_private(path: &Path, contents: &[u8]) -> Result<()> {
+    if let Some(dir) = path.parent().filter(|d| !d.as_os_str().is_empty()) {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {:?}", dir))?;
+    }
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::OpenOptionsExt;
+        options.mode(0o600);
+    }
+    let mut file = options
+        .open(path)
+        .with_context(|| format!("Failed to write {:?}", path))?;
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        file.set_permissions(fs::Permissions::from_mode(0o600))?;
+    }
+    file.write_all(contents)
+        .with_context(|| format!("Failed to write {:?}", path))
+}
+
+/// Read a session saved by [`save`].
+pub fn load(path: &Path) -> Result<StorageState> {
+    let json = fs::read(path).with_context(|| format!("No saved session at {:?}", path))?;
+    serde_json::from_slice(&json).with_context(|| format!("Invalid session file {:?}", path))
+}
+
+/// The host cookies are matched against: the one in `url`, or "" when it
+/// doesn't parse.
+pub fn host_of(url: &str) -> String {
+    reqwest::Url::parse(url)
+        .ok()
+        .and_then(|url| url.host_str().map(str::to_string))
+        .unwrap_or_default()
+}
+
+/// The cookies `state` holds for `host`.
+fn portal_cookies<'a>(state: &'a StorageState, host: &str) -> Vec<&'a Cookie> {
+    state
+        .cookies
+        .iter()
+        .flatten()
+        .filter(|c| c.domain.as_deref().is_some_and(|d| domain_matches(d, host)))
+        .collect()
+}
+
+/// When the portal's cookies in a saved session run out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Expiry {
+    /// The earliest persistent cookie expires then
+    At(DateTime<Utc>),
+    /// Only session cookies: they last until the portal's idle timeout
+    ServerTimeout,
+    /// Every persistent cookie has already expired
+    Expired,
+    /// No cookies for the portal at all
+    NoCookies,
+}
+
+/// Work out [`Expiry`] from the cookies `state` holds for `host`.
+pub fn expiry(state: &StorageState, host: &str, now: DateTime<Utc>) -> Expiry {
+    let cookies = portal_cookies(state, host);
+    if cookies.is_empty() {
+        return Expiry::NoCookies;
+    }
+
+    // Playwright stores session cookies with `expires: -1`
+    let persistent: Vec<DateTime<Utc>> = cookies
+        .iter()
+        .filter_map(|c| c.expires.filter(|&e| e > 0.0))
+        .filter_map(|e| DateTime::from_timestamp(e as i64, 0))
+        .collect();
+    if persistent.is_empty() {
+        return Expiry::ServerTimeout;
+    }
+    match persistent.iter().filter(|&&e| e > now).min() {
+        Some(&earliest) => Expiry::At(earliest),
+        None => Expiry::Expired,
+    }
+}
+
+/// Cookie file formats for `raschietto session export`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum CookieFormat {
+    /// cookies.txt, as read by `curl -b`, `wget --load-cookies` and yt-dlp
+    Netscape,
+    /// Playwright's cookie objects, as a JSON array
+    Json,
+}
+
+/// The cookies `state` holds for `host`, as a file in `format`. Fails when
+/// there are none, since the export would be useless.
+pub fn export_cookies(state: &StorageState, host: &str, format: CookieFormat) -> Result<String> {
+    let cookies = portal_cookies(state, host);
+    if cookies.is_empty() {
+        bail!("The saved session has no cookies for {}", host);
+    }
+    Ok(match format {
+        CookieFormat::Json => serde_json::to_string_pretty(&cookies)? + "\n",
+        CookieFormat::Netscape => {
+            let mut out = String::from("# Netscape HTTP Cookie File\n");
+            for cookie in cookies {
+                out.push_str(&netscape_line(cookie));
+                out.push('\n');
+            }
+            out
+        }
+    })
+}
+
+/// One tab-separated cookies.txt line. HttpOnly cookies get curl's
+/// `#HttpOnly_` prefix; session cookies expire at 0.
+fn netscape_line(cookie: &Cookie) -> String {
+    let domain = cookie.domain.as_deref().unwrap_or_default();
+    let flag = |b: bool| if b { "TRUE" } else { "FALSE" };
+    let expires = cookie.expires.filter(|&e| e > 0.0).unwrap_or(0.0) as i64;
+    format!(
+        "{}{}\t{}\t{}\t{}\t{}\t{}\t{}",
+        if cookie.http_only == Some(true) {
+            "#HttpOnly_"
+        } else {
+            ""
+        },
+        domain,
+        flag(domain.starts_with('.')),
+        cookie.path.as_deref().unwrap_or("/"),
+        flag(cookie.secure == Some(true)),
+        expires,
+        cookie.name,
+        cookie.value
+    )
+}
+
+/// Whether a cookie for `domain` (maybe with a leading `.`) is sent to `host`.
+fn domain_matches(domain: &str, host: &str) -> bool {
+    let domain = domain.trim_start_matches('.');
+    host == domain || host.ends_with(&format!(".{}", domain))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cookie(name: &str, domain: &str, expires: f64) -> Cookie {
+        let mut cookie = Cookie::with_url(name, "v", "https://web.spaggiari.eu/");
+        cookie.url = None;
+        cookie.domain = Some(domain.to_string());
+        cookie.expires = Some(expires);
+        cookie
+    }
+
+    fn state(cookies: Vec<Cookie>) -> StorageState {
+        StorageState {
+            cookies: Some(cookies),
+            origins: None,
+        }
+    }
+
+    #[test]
+    fn test_expiry() {
+        let host = "web.spaggiari.eu";
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let later = DateTime::from_timestamp(1_700_003_600, 0).unwrap();
+
+        let mixed = state(vec![
+            cookie("PHPSESSID", "web.spaggiari.eu", -1.0),
+            cookie("webrole", ".spaggiari.eu", 1_700_003_600.0),
+            cookie("other", ".spaggiari.eu", 1_700_090_000.0),
+            cookie("tracker", ".example.com", 1_700_000_100.0),
+        ]);
+        assert_eq!(expiry(&mixed, host, now), Expiry::At(later));
+
+        let session_only = state(vec![cookie("PHPSESSID", "web.spaggiari.eu", -1.0)]);
+        assert_eq!(expiry(&session_only, host, now), Expiry::ServerTimeout);
+
+        let old = state(vec![cookie("webrole", ".spaggiari.eu", 1_600_000_000.0)]);
+        assert_eq!(expiry(&old, host, now), Expiry::Expired);
+
+        assert_eq!(expiry(&state(vec![]), host, now), Expiry::NoCookies);
+        assert!(!domain_matches("paggiari.eu", host));
+    }
+
+    #[test]
+    fn test_export_cookies() {
+        let mut sid = cookie("PHPSESSID", "web.spaggiari.eu", -1.0);
+        sid.http_only = Some(true);
+        sid.secure = Some(true);
+        sid.path = Some("/".to_string());
+        let saved = state(vec![
+            sid,
+            cookie("webrole", ".spaggiari.eu", 1_700_003_600.0),
+            cookie("tracker", ".example.com", 1_700_000_100.0),
+        ]);
+        let host = "web.spaggiari.eu";
+
+        let txt = export_cookies(&saved, host, CookieFormat::Netscape).unwrap();
+        let lines: Vec<&str> = txt.lines().collect();
+        assert_eq!(lines[0], "# Netscape HTTP Cookie File");
+        assert_eq!(
+            lines[1],
+            "#HttpOnly_web.spaggiari.eu\tFALSE\t/\tTRUE\t0\tPHPSESSID\tv"
+        );
+        assert_eq!(
+            lines[2],
+            ".spaggiari.eu\tTRUE\t/\tFALSE\t1700003600\twebrole\tv"
+        );
+        assert_eq!(lines.len(), 3);
+
+        let json = export_cookies(&saved, host, CookieFormat::Json).unwrap();
+        let parsed: Vec<Cookie> = serde_json::from_str(&json).unwrap();
+        let names: Vec<&str> = parsed.iter().map(|c| c.name.as_str()).collect();
+        assert_eq!(names, vec!["PHPSESSID", "webrole"]);
+
+        assert!(export_cookies(&saved, "example.org", CookieFormat::Json).is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = std::env::temp_dir().join(format!("raschietto-session-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        let path = dir.join("nested").join("classeviva.json");
+
+        save(
+            &path,
+            &state(vec![cookie("PHPSESSID", "web.spaggiari.eu", -1.0)]),
+        )
+        .unwrap();
+        let loaded = load(&path).unwrap();
+        assert_eq!(loaded.cookies.unwrap()[0].name, "PHPSESSID");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+
+            // A file left readable by others is narrowed when rewritten
+            fs::set_permissions(&path, fs::Permissions::from_mode(0o644)).unwrap();
+            write_private(&path, b"{}").unwrap();
+            let mode = fs::metadata(&path).unwrap().permissions().mode();
+            assert_eq!(mode & 0o777, 0o600);
+            assert_eq!(fs::read(&path).unwrap(), b"{}");
+        }
+        assert!(load(&dir.join("missing.json")).is_err());
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,165 @@
+//! `raschietto status`: is the saved session still good?
+//!
+//! Loads the session the last browser fetch saved, opens the agenda
+//! headlessly with it and reports whether the portal still accepts it, when
+//! its cookies run out and whether the export button is there. Nothing is
+//! downloaded and no login is attempted, so it's cheap to run from cron
+//! before a fetch.
+
+use chrono::{DateTime, Local, Utc};
+use std::path::Path;
+use std::time::SystemTime;
+
+use crate::browser::{BrowserOptions, BrowserSession};
+use crate::doctor::{Check, Status};
+use crate::network::NetworkOptions;
+use crate::profile::PortalProfile;
+use crate::scraper::{ClasseVivaScraper, LoginMode, SessionProbe};
+use crate::session::{self, Expiry};
+
+/// Cookies running out sooner than this get a warning.
+const EXPIRY_WARNING_HOURS: i64 = 24;
+
+const FETCH_FIX: &str = "Run 'raschietto fetch' (or 'fetch --manual-login') to log in again";
+
+/// Run the checks for `portal` with the session saved at `session_path`.
+pub async fn run(
+    portal: PortalProfile,
+    session_path: &Path,
+    network: NetworkOptions,
+) -> Vec<Check> {
+    let mut checks = Vec::new();
+
+    let state = match session::load(session_path) {
+        Ok(state) => state,
+        Err(e) => {
+            checks.push(Check::fail(
+                "session",
+                format!("{:#}", e),
+                "Run 'raschietto fetch' once to log in and save a session",
+            ));
+            return checks;
+        }
+    };
+    let saved = std::fs::metadata(session_path)
+        .and_then(|m| m.modified())
+        .map(|modified| format!("saved {} ago", format_age(modified)))
+        .unwrap_or_else(|_| "saved".to_string());
+    checks.push(Check::ok(
+        "session",
+        format!("{} ({})", session_path.display(), saved),
+    ));
+
+    let host = session::host_of(&portal.agenda_url);
+    checks.push(check_expiry(
+        session::expiry(&state, &host, Utc::now()),
+        Utc::now(),
+    ));
+
+    let probed = async {
+        let browser = BrowserSession::launch(BrowserOptions {
+            headed: false,
+            network,
+        })
+        .await?;
+        let context = browser.restore_context(state).await?;
+        let scraper = ClasseVivaScraper::new(context, portal, None, LoginMode::Automatic);
+        let probe = scraper.probe_session().await;
+        let _ = browser.close().await;
+        probe
+    };
+    match probed.await {
+        Ok(SessionProbe::LoginRequired) => {
+            checks.push(Check::fail(
+                "login",
+                "The portal asked to log in again",
+                FETCH_FIX,
+            ));
+        }
+        Ok(SessionProbe::LoggedIn { export_button }) => {
+            checks.push(Check::ok("login", "Agenda opened without logging in"));
+            checks.push(if export_button {
+                Check::ok("export", "Export button reachable")
+            } else {
+                Check::fail(
+                    "export",
+                    "Export button not on the agenda page",
+                    "Check the export_button selector for this profile in raschietto.toml",
+                )
+            });
+        }
+        Err(e) => {
+            checks.push(Check::fail(
+                "agenda",
+                format!("{:#}", e),
+                "Run 'raschietto doctor' if the browser didn't start",
+            ));
+        }
+    }
+    checks
+}
+
+fn check_expiry(expiry: Expiry, now: DateTime<Utc>) -> Check {
+    match expiry {
+        Expiry::At(at) => {
+            let detail = format!(
+                "Cookies expire {} (in {})",
+                at.with_timezone(&Local).format("%Y-%m-%d %H:%M"),
+                format_duration(at - now)
+            );
+            if at - now < chrono::Duration::hours(EXPIRY_WARNING_HOURS) {
+                Check {
+                    name: "expiry",
+                    status: Status::Warn,
+                    detail,
+                    fix: Some(FETCH_FIX.to_string()),
+                }
+            } else {
+                Check::ok("expiry", detail)
+            }
+        }
+        Expiry::ServerTimeout => Check::ok(
+            "expiry",
+            "Session cookies only: valid until the portal's idle timeout",
+        ),
+        Expiry::Expired => Check::fail("expiry", "Every saved cookie has expired", FETCH_FIX),
+        Expiry::NoCookies => Check::fail("expiry", "No cookies saved for the portal", FETCH_FIX),
+    }
+}
+
+fn format_age(since: SystemTime) -> String {
+    let elapsed = since.elapsed().unwrap_or_default();
+    format_duration(chrono::Duration::from_std(elapsed).unwrap_or_default())
+}
+
+/// `"45 min"`, `"5 h"` or `"3 d"`.
+fn format_duration(duration: chrono::Duration) -> String {
+    if duration.num_hours() < 1 {
+        format!("{} min", duration.num_minutes().max(0))
+    } else if duration.num_days() < 2 {
+        format!("{} h", duration.num_hours())
+    } else {
+        format!("{} d", duration.num_days())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_check_expiry() {
+        let now = DateTime::from_timestamp(1_700_000_000, 0).unwrap();
+        let soon = check_expiry(Expiry::At(now + chrono::Duration::hours(5)), now);
+        assert_eq!(soon.status, Status::Warn);
+        assert!(soon.detail.ends_with("(in 5 h)"));
+
+        let later = check_expiry(Expiry::At(now + chrono::Duration::days(10)), now);
+        assert_eq!(later.status, Status::Ok);
+        assert!(later.detail.ends_with("(in 10 d)"));
+
+        assert_eq!(check_expiry(Expiry::ServerTimeout, now).status, Status::Ok);
+        assert_eq!(check_expiry(Expiry::Expired, now).status, Status::Fail);
+        assert_eq!(format_duration(chrono::Duration::minutes(45)), "45 min");
+    }
+}
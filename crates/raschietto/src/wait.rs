@@ -0,0 +1,295 @@
+//! Condition-based waits for the browser steps, instead of fixed sleeps.
+//!
+//! Each step waits for something it can observe (the URL changing after
+//! login, the page's requests settling, a button becoming enabled) for at
+//! most the step's timeout. Timeouts come from the profile's [`WaitPolicy`]
+//! and can be raised per step for slow school servers:
+//!
+//! ```toml
+//! [profiles.classeviva.waits]
+//! timeout_ms = 60000
+//! idle_ms = 750
+//!
+//! [profiles.classeviva.waits.steps]
+//! post_login = 90000
+//! ```
+
+use anyhow::{anyhow, bail, Context, Result};
+use playwright::api::frame::{FrameState, Polling};
+use playwright::api::Page;
+use serde::Deserialize;
+use std::collections::BTreeMap;
+use std::time::Duration;
+use tokio::time::Instant;
+use tracing::debug;
+
+/// A browser step with its own timeout.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum WaitStep {
+    /// The login form showing up
+    LoginForm,
+    /// Leaving the login page after submitting, and the next page settling
+    PostLogin,
+    /// The "associate your email" nag, which usually isn't there
+    EmailNag,
+    /// The agenda's export button becoming usable
+    ExportButton,
+    ExportDialog,
+    /// A date field taking its new value (and its datepicker closing)
+    DateFields,
+    /// The download starting inside a popup window
+    Download,
+    /// The agenda calendar (re)loading its items
+    AgendaPage,
+    /// A class/group dropdown showing up, and the agenda reloading after a
+    /// choice (`--class`, `--group`)
+    AgendaFilter,
+    DetailPopup,
+    Grades,
+    Absences,
+    Notes,
+    /// `raschietto status` waiting for the agenda or the login form
+    Probe,
+}
+
+impl WaitStep {
+    /// Built-in timeout for steps that differ from `timeout_ms`
+    fn default_ms(self) -> Option<u64> {
+        match self {
+            // Short: not waiting for it is the common case
+            WaitStep::EmailNag => Some(5_000),
+            WaitStep::DetailPopup => Some(5_000),
+            WaitStep::Grades | WaitStep::Absences | WaitStep::Notes => Some(15_000),
+            WaitStep::Probe => Some(20_000),
+            _ => None,
+        }
+    }
+}
+
+/// How long the browser steps may wait, per portal profile.
+#[derive(Debug, Clone, Deserialize, PartialEq, Eq)]
+#[serde(default)]
+pub struct WaitPolicy {
+    /// Timeout for any step without its own (Playwright's default)
+    pub timeout_ms: u64,
+    /// How long the page must be without requests to count as idle
+    pub idle_ms: u64,
+    /// How often conditions are re-checked
+    pub poll_ms: u64,
+    /// Per-step timeouts, overriding `timeout_ms` and the built-in ones
+    pub steps: BTreeMap<WaitStep, u64>,
+}
+
+impl Default for WaitPolicy {
+    fn default() -> Self {
+        Self {
+            timeout_ms: 30_000,
+            idle_ms: 500,
+            poll_ms: 100,
+            steps: BTreeMap::new(),
+        }
+    }
+}
+
+impl WaitPolicy {
+    /// Timeout for `step`, in milliseconds
+    pub fn timeout_ms(&self, step: WaitStep) -> u64 {
+        self.steps
+            .get(&step)
+            .copied()
+            .or_else(|| step.default_ms())
+            .unwrap_or(self.timeout_ms)
+    }
+
+    /// Timeout for `step` as Playwright takes it
+    pub fn timeout(&self, step: WaitStep) -> f64 {
+        self.timeout_ms(step) as f64
+    }
+
+    pub fn duration(&self, step: WaitStep) -> Duration {
+        Duration::from_millis(self.timeout_ms(step))
+    }
+
+    fn poll(&self) -> Duration {
+        Duration::from_millis(self.poll_ms.max(10))
+    }
+}
+
+/// Quiet once the document has loaded, jQuery has no requests in flight
+/// and no new resource has been fetched for `idleMs`.
+const NETWORK_IDLE_JS: &str = r#"
+    (idleMs) => {
+        const w = window;
+        if (w.__raschiettoQuietSince === undefined) {
+            performance.setResourceTimingBufferSize(10000);
+        }
+        const count = performance.getEntriesByType('resource').length;
+        const busy = document.readyState !== 'complete'
+            || (typeof jQuery !== 'undefined' && jQuery.active > 0);
+        const now = Date.now();
+        if (busy || w.__raschiettoResources !== count) {
+            w.__raschiettoResources = count;
+            w.__raschiettoQuietSince = now;
+            return false;
+        }
+        return now - w.__raschiettoQuietSince >= idleMs;
+    }
+"#;
+
+/// The (CSS-selected) field has a value and no jQuery animation (such as a
+/// datepicker closing) is still running. The datepicker may reformat the
+/// value, so it isn't compared with what was typed.
+const FIELD_SETTLED_JS: &str = r#"
+    (selector) => {
+        const el = document.querySelector(selector);
+        if (!el || el.value === '') return false;
+        return typeof jQuery === 'undefined' || jQuery(':animated').length === 0;
+    }
+"#;
+
+/// Wait until the page's URL is no longer `from` (e.g. after submitting a
+/// form) and return the new one.
+pub async fn url_change(
+    page: &Page,
+    from: &str,
+    policy: &WaitPolicy,
+    step: WaitStep,
+) -> Result<String> {
+    let deadline = Instant::now() + policy.duration(step);
+    loop {
+        let url = page.url().context("Failed to read the page URL")?;
+        if url != from {
+            debug!("Navigated to {}", url);
+            return Ok(url);
+        }
+        if Instant::now() >= deadline {
+            bail!(
+                "Still on {} after {} ms ({:?})",
+                from,
+                policy.timeout_ms(step),
+                step
+            );
+        }
+        tokio::time::sleep(policy.poll()).await;
+    }
+}
+
+/// Wait until the page has finished loading and its requests have settled.
+/// Survives a navigation happening meanwhile.
+pub async fn network_idle(page: &Page, policy: &WaitPolicy, step: WaitStep) -> Result<()> {
+    let deadline = Instant::now() + policy.duration(step);
+    loop {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let result = page
+            .wait_for_function_builder(NETWORK_IDLE_JS)
+            .arg(&policy.idle_ms)
+            .polling(Polling::Millis(policy.poll_ms.max(10) as u32))
+            .timeout(remaining.as_millis() as f64)
+            .wait_for_function()
+            .await;
+        match result {
+            Ok(_) => return Ok(()),
+            // The page navigated away mid-check: check the new one
+            Err(e) if Instant::now() < deadline && is_navigation_error(&e.to_string()) => {
+                debug!("Page navigated while waiting for it to settle");
+                tokio::time::sleep(policy.poll()).await;
+            }
+            Err(e) => {
+                return Err(anyhow!("{}", e)).with_context(|| {
+                    format!(
+                        "Page didn't settle within {} ms ({:?})",
+                        policy.timeout_ms(step),
+                        step
+                    )
+                })
+            }
+        }
+    }
+}
+
+fn is_navigation_error(message: &str) -> bool {
+    let message = message.to_lowercase();
+    message.contains("context was destroyed") || message.contains("navigat")
+}
+
+/// Wait until `selector` is visible and enabled.
+pub async fn enabled(
+    page: &Page,
+    selector: &str,
+    policy: &WaitPolicy,
+    step: WaitStep,
+) -> Result<()> {
+    let deadline = Instant::now() + policy.duration(step);
+    page.wait_for_selector_builder(selector)
+        .state(FrameState::Visible)
+        .timeout(policy.timeout(step))
+        .wait_for_selector()
+        .await
+        .with_context(|| format!("{} did not appear ({:?})", selector, step))?;
+    loop {
+        if page
+            .is_enabled(selector, Some(policy.poll_ms as f64))
+            .await
+            .unwrap_or(false)
+        {
+            return Ok(());
+        }
+        if Instant::now() >= deadline {
+            bail!(
+                "{} still disabled after {} ms ({:?})",
+                selector,
+                policy.timeout_ms(step),
+                step
+            );
+        }
+        tokio::time::sleep(policy.poll()).await;
+    }
+}
+
+/// Wait until the field at (CSS) `selector` has been filled and settled.
+pub async fn field_settled(
+    page: &Page,
+    selector: &str,
+    policy: &WaitPolicy,
+    step: WaitStep,
+) -> Result<()> {
+    page.wait_for_function_builder(FIELD_SETTLED_JS)
+        .arg(&selector)
+        .polling(Polling::Millis(policy.poll_ms.max(10) as u32))
+        .timeout(policy.timeout(step))
+        .wait_for_function()
+        .await
+        .with_context(|| format!("{} was not filled in ({:?})", selector, step))?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_step_timeouts() {
+        let policy = WaitPolicy::default();
+        assert_eq!(policy.timeout_ms(WaitStep::PostLogin), 30_000);
+        assert_eq!(policy.timeout_ms(WaitStep::EmailNag), 5_000);
+
+        let policy = WaitPolicy {
+            timeout_ms: 60_000,
+            steps: BTreeMap::from([(WaitStep::EmailNag, 8_000)]),
+            ..WaitPolicy::default()
+        };
+        assert_eq!(policy.timeout_ms(WaitStep::PostLogin), 60_000);
+        assert_eq!(policy.timeout_ms(WaitStep::EmailNag), 8_000);
+        // Built-in step timeouts aren't scaled by timeout_ms
+        assert_eq!(policy.timeout_ms(WaitStep::Grades), 15_000);
+    }
+
+    #[test]
+    fn test_navigation_errors() {
+        assert!(is_navigation_error(
+            "Execution context was destroyed, most likely because of a navigation"
+        ));
+        assert!(!is_navigation_error("Timeout 30000ms exceeded"));
+    }
+}